@@ -0,0 +1,48 @@
+use bitflags::bitflags;
+
+use crate::sysno::*;
+
+bitflags! {
+    /// Capabilities held by a process, gating the privileged system calls.
+    ///
+    /// A process inherits the capabilities of its creator and may only drop them afterwards;
+    /// the initial process of a partition receives whatever the partition is configured with.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Capabilities: usize {
+        const LOG = 1 << 0;
+        const LOG_RING = 1 << 1;
+        const HALT = 1 << 2;
+        const PARTITION_MODE = 1 << 3;
+        const PROCESS_MANAGE = 1 << 4;
+        const TUNE = 1 << 5;
+    }
+}
+
+macro_rules! def_syscap {
+    ($($($sysno:ident)|+ => $cap:ident,)*) => {
+        /// Returns the capabilities required to issue `sysno`.
+        pub const fn required(sysno: usize) -> Capabilities {
+            match sysno {
+                $($($sysno)|+ => Capabilities::$cap,)*
+                _ => Capabilities::empty(),
+            }
+        }
+    };
+}
+
+def_syscap! {
+    SYS_SET_PARTITION_MODE => PARTITION_MODE,
+    SYS_CREATE_PROCESS
+        | SYS_SET_PRIORITY
+        | SYS_SUSPEND
+        | SYS_RESUME
+        | SYS_STOP
+        | SYS_START
+        | SYS_DELAYED_START
+        | SYS_INITIALIZE_PROCESS_CORE_AFFINITY
+        | SYS_TRAP_SIGNAL => PROCESS_MANAGE,
+    SYS_SET_TUNABLE => TUNE,
+    SYS_DEBUG_LOG => LOG,
+    SYS_DEBUG_HALT => HALT,
+    SYS_DEBUG_LOG_RING_SETUP | SYS_DEBUG_LOG_RING_DOORBELL => LOG_RING,
+}
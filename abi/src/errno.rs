@@ -0,0 +1,33 @@
+/// Error of the system calls following POSIX conventions, returned negated in place of their
+/// result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Errno(pub isize);
+
+pub const EPERM: Errno = Errno(1);
+pub const ENOENT: Errno = Errno(2);
+pub const ESRCH: Errno = Errno(3);
+pub const EINTR: Errno = Errno(4);
+pub const EAGAIN: Errno = Errno(11);
+pub const ENOMEM: Errno = Errno(12);
+pub const EFAULT: Errno = Errno(14);
+pub const EBUSY: Errno = Errno(16);
+pub const EEXIST: Errno = Errno(17);
+pub const EINVAL: Errno = Errno(22);
+pub const ENOSYS: Errno = Errno(38);
+pub const ETIMEDOUT: Errno = Errno(110);
+
+impl Errno {
+    /// Returns the value a system call returns for the error.
+    pub const fn as_ret(self) -> usize {
+        self.0.wrapping_neg() as usize
+    }
+
+    /// Splits the value returned by a system call into its result or error.
+    pub const fn from_ret(ret: usize) -> Result<usize, Self> {
+        match ret as isize {
+            ret @ -4095..=-1 => Err(Self(-ret)),
+            _ => Ok(ret),
+        }
+    }
+}
@@ -1,5 +1,11 @@
 #![no_std]
 
+pub mod cap;
+pub mod errno;
+pub mod logring;
+pub mod rseq;
 #[cfg(feature = "sysfn")]
 pub mod sysfn;
 pub mod sysno;
+pub mod time;
+pub mod trap;
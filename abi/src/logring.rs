@@ -0,0 +1,185 @@
+use core::sync::atomic::{fence, AtomicU32, Ordering};
+
+pub const LOG_RING_SLOT_SIZE: usize = 128;
+pub const LOG_RING_PAYLOAD_MAX: usize = LOG_RING_SLOT_SIZE - 2 * core::mem::size_of::<u32>();
+
+const SLOT_WRITING: u32 = u32::MAX;
+
+#[repr(C)]
+struct LogRingHeader {
+    capacity: u32,
+    head: AtomicU32,
+    tail: AtomicU32,
+    dropped: AtomicU32,
+}
+
+#[repr(C)]
+struct LogRingSlot {
+    seq: AtomicU32,
+    len: AtomicU32,
+    payload: [u8; LOG_RING_PAYLOAD_MAX],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LogRing {
+    base: *mut u8,
+}
+
+unsafe impl Send for LogRing {}
+unsafe impl Sync for LogRing {}
+
+impl LogRing {
+    pub const fn capacity_of(len: usize) -> usize {
+        match (len / LOG_RING_SLOT_SIZE).saturating_sub(1) {
+            0 => 0,
+            slots => 1 << slots.ilog2(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `base` must point to `len` writable bytes aligned to `LOG_RING_SLOT_SIZE`,
+    /// which stay valid for the lifetime of the returned ring and all its copies.
+    pub unsafe fn init(base: *mut u8, len: usize) -> Self {
+        let capacity = Self::capacity_of(len);
+        assert!(capacity > 0 && capacity < u32::MAX as usize);
+
+        let ring = Self { base };
+        core::ptr::write(
+            ring.header_ptr(),
+            LogRingHeader {
+                capacity: capacity as u32,
+                head: AtomicU32::new(0),
+                tail: AtomicU32::new(0),
+                dropped: AtomicU32::new(0),
+            },
+        );
+        for index in 0..capacity {
+            (*ring.slot_ptr(index as u32))
+                .seq
+                .store(SLOT_WRITING, Ordering::Relaxed);
+        }
+        fence(Ordering::Release);
+        ring
+    }
+
+    /// # Safety
+    ///
+    /// `base` must point to a ring previously set up by [`LogRing::init`].
+    pub unsafe fn from_raw(base: *mut u8) -> Self {
+        Self { base }
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.base
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.header().capacity
+    }
+
+    pub fn pushed(&self) -> u32 {
+        self.header().head.load(Ordering::Acquire)
+    }
+
+    pub fn drained(&self) -> u32 {
+        self.header().tail.load(Ordering::Acquire)
+    }
+
+    pub fn pending(&self) -> u32 {
+        self.pushed().wrapping_sub(self.drained())
+    }
+
+    pub fn dropped(&self) -> u32 {
+        self.header().dropped.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let header = self.header();
+        header.head.load(Ordering::Acquire) == header.tail.load(Ordering::Acquire)
+    }
+
+    pub fn push(&self, record: &[u8]) {
+        let header = self.header();
+        let seq = header.head.load(Ordering::Relaxed);
+        let slot = self.slot(seq, self.capacity());
+        let len = record.len().min(LOG_RING_PAYLOAD_MAX);
+
+        slot.seq.store(SLOT_WRITING, Ordering::Relaxed);
+        fence(Ordering::Release);
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                record.as_ptr(),
+                core::ptr::addr_of!(slot.payload) as *mut u8,
+                len,
+            );
+        }
+        slot.len.store(len as u32, Ordering::Relaxed);
+        slot.seq.store(seq, Ordering::Release);
+        header.head.store(seq.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Pops the oldest record into `buf`, returning its sequence number and length.
+    ///
+    /// `capacity` must be [`LogRing::capacity_of`] the mapped length: the header lives in
+    /// memory shared with the producer, so its copy of the capacity is not trusted.
+    pub fn pop(&self, capacity: u32, buf: &mut [u8; LOG_RING_PAYLOAD_MAX]) -> Option<(u32, usize)> {
+        assert!(capacity > 0);
+        let header = self.header();
+
+        loop {
+            let tail = header.tail.load(Ordering::Relaxed);
+            let head = header.head.load(Ordering::Acquire);
+            if head == tail {
+                return None;
+            }
+
+            let pending = head.wrapping_sub(tail);
+            if pending > capacity {
+                header
+                    .dropped
+                    .fetch_add(pending - capacity, Ordering::Release);
+                header
+                    .tail
+                    .store(head.wrapping_sub(capacity), Ordering::Release);
+                continue;
+            }
+
+            let slot = self.slot(tail, capacity);
+            let len = slot.len.load(Ordering::Relaxed) as usize;
+            let valid = slot.seq.load(Ordering::Acquire) == tail && {
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        core::ptr::addr_of!(slot.payload) as *const u8,
+                        buf.as_mut_ptr(),
+                        len.min(LOG_RING_PAYLOAD_MAX),
+                    );
+                }
+                fence(Ordering::Acquire);
+                slot.seq.load(Ordering::Relaxed) == tail
+            };
+
+            header.tail.store(tail.wrapping_add(1), Ordering::Release);
+            if valid {
+                return Some((tail, len.min(LOG_RING_PAYLOAD_MAX)));
+            }
+            header.dropped.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    fn header_ptr(&self) -> *mut LogRingHeader {
+        self.base as *mut LogRingHeader
+    }
+
+    fn header(&self) -> &LogRingHeader {
+        unsafe { &*self.header_ptr() }
+    }
+
+    fn slot_ptr(&self, index: u32) -> *mut LogRingSlot {
+        unsafe { self.base.add((index as usize + 1) * LOG_RING_SLOT_SIZE) as *mut LogRingSlot }
+    }
+
+    fn slot(&self, seq: u32, capacity: u32) -> &LogRingSlot {
+        unsafe { &*self.slot_ptr(seq % capacity) }
+    }
+}
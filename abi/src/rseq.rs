@@ -0,0 +1,75 @@
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+pub const RSEQ_CPU_ID_UNINITIALIZED: u32 = u32::MAX;
+
+/// Per-process area registered through `SYS_RSEQ_REGISTER`.
+///
+/// Whenever the process returns to user mode after being preempted or migrated, the kernel
+/// publishes the current CPU id in `cpu_id`. If the user pc then lies within the critical
+/// section described by `rseq_cs`, the kernel redirects it to the abort ip of that section,
+/// and clears `rseq_cs` in either case.
+#[repr(C, align(32))]
+pub struct Rseq {
+    cpu_id: AtomicU32,
+    rseq_cs: AtomicUsize,
+}
+
+/// Descriptor of a restartable critical section, `[start_ip, start_ip + post_commit_offset)`.
+///
+/// The section must end with its single committing store, and `abort_ip` must lie outside of
+/// it, since execution continues there whenever the section is interrupted.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct RseqCs {
+    pub start_ip: usize,
+    pub post_commit_offset: usize,
+    pub abort_ip: usize,
+}
+
+impl Rseq {
+    pub const fn new() -> Self {
+        Self {
+            cpu_id: AtomicU32::new(RSEQ_CPU_ID_UNINITIALIZED),
+            rseq_cs: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn cpu_id(&self) -> Option<usize> {
+        match self.cpu_id.load(Ordering::Relaxed) {
+            RSEQ_CPU_ID_UNINITIALIZED => None,
+            cpu_id => Some(cpu_id as usize),
+        }
+    }
+
+    pub fn set_cpu_id(&self, cpu_id: usize) {
+        self.cpu_id.store(cpu_id as u32, Ordering::Relaxed);
+    }
+
+    pub fn cpu_id_ptr(&self) -> *const u32 {
+        self.cpu_id.as_ptr()
+    }
+
+    pub fn rseq_cs(&self) -> usize {
+        self.rseq_cs.load(Ordering::Relaxed)
+    }
+
+    pub fn clear_rseq_cs(&self) {
+        self.rseq_cs.store(0, Ordering::Relaxed);
+    }
+
+    pub fn rseq_cs_ptr(&self) -> *mut usize {
+        self.rseq_cs.as_ptr()
+    }
+}
+
+impl Default for Rseq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RseqCs {
+    pub fn contains(&self, ip: usize) -> bool {
+        ip.wrapping_sub(self.start_ip) < self.post_commit_offset
+    }
+}
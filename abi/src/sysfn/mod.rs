@@ -2,7 +2,7 @@ mod macros;
 
 use jrinx_apex::*;
 
-use crate::sysno::*;
+use crate::{rseq::Rseq, sysno::*, time::Timespec};
 use macros::*;
 
 def_sysfn! {
@@ -108,6 +108,124 @@ def_sysfn! {
     ) -> ApexReturnCode
 }
 
+def_sysfn! {
+    @SYS_CREATE_SEMAPHORE
+    sys_create_semaphore(
+        name: *const ApexSemaphoreName,
+        current_value: ApexSemaphoreValue,
+        maximum_value: ApexSemaphoreValue,
+        queuing_discipline: ApexQueueDiscipline,
+        id: *mut ApexSemaphoreId,
+    ) -> ApexReturnCode
+
+    @SYS_WAIT_SEMAPHORE
+    sys_wait_semaphore(
+        id: ApexSemaphoreId,
+        timeout: ApexSystemTime,
+    ) -> ApexReturnCode
+
+    @SYS_SIGNAL_SEMAPHORE
+    sys_signal_semaphore(
+        id: ApexSemaphoreId,
+    ) -> ApexReturnCode
+
+    @SYS_GET_SEMAPHORE_ID
+    sys_get_semaphore_id(
+        name: *const ApexSemaphoreName,
+        id: *mut ApexSemaphoreId,
+    ) -> ApexReturnCode
+
+    @SYS_GET_SEMAPHORE_STATUS
+    sys_get_semaphore_status(
+        id: ApexSemaphoreId,
+        status: *mut ApexSemaphoreStatus,
+    ) -> ApexReturnCode
+}
+
+def_sysfn! {
+    @SYS_GET_CAPABILITIES
+    sys_get_capabilities(
+        caps: *mut usize,
+    ) -> ApexReturnCode
+
+    @SYS_DROP_CAPABILITIES
+    sys_drop_capabilities(
+        caps: usize,
+    ) -> ApexReturnCode
+}
+
+def_sysfn! {
+    @SYS_RSEQ_REGISTER
+    sys_rseq_register(
+        area: *const Rseq,
+    ) -> ApexReturnCode
+}
+
+def_sysfn! {
+    @SYS_GET_TUNABLE
+    sys_get_tunable(
+        key: *const u8,
+        key_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+        len: *mut usize,
+    ) -> ApexReturnCode
+
+    @SYS_SET_TUNABLE
+    sys_set_tunable(
+        key: *const u8,
+        key_len: usize,
+        value: *const u8,
+        value_len: usize,
+    ) -> ApexReturnCode
+}
+
+def_sysfn! {
+    @SYS_TRAP_HANDLER_SET
+    sys_trap_handler_set(
+        mask: usize,
+        entry: usize,
+        stack_top: usize,
+    ) -> ApexReturnCode
+
+    @SYS_TRAP_RETURN
+    sys_trap_return() -> ApexReturnCode
+
+    @SYS_TRAP_SIGNAL
+    sys_trap_signal(
+        id: ApexProcessId,
+        signal: usize,
+    ) -> ApexReturnCode
+}
+
+def_sysfn! {
+    @SYS_MAP_PAGE
+    sys_map_page(
+        addr: usize,
+    ) -> ApexReturnCode
+}
+
+def_sysfn! {
+    @SYS_CLOCK_GETTIME
+    sys_clock_gettime(
+        clock_id: usize,
+        tp: *mut Timespec,
+    ) -> usize
+
+    @SYS_NANOSLEEP
+    sys_nanosleep(
+        req: *const Timespec,
+        rem: *mut Timespec,
+    ) -> usize
+}
+
+def_sysfn! {
+    @SYS_STRACE
+    sys_strace(
+        enabled: usize,
+    ) -> ApexReturnCode
+}
+
 def_sysfn! {
     @SYS_DEBUG_LOG
     sys_debug_log(
@@ -117,4 +235,49 @@ def_sysfn! {
 
     @SYS_DEBUG_HALT
     sys_debug_halt() -> !
+
+    @SYS_DEBUG_LOG_RING_SETUP
+    sys_debug_log_ring_setup(
+        len: usize,
+        ring: *mut usize,
+    ) -> ApexReturnCode
+
+    @SYS_DEBUG_LOG_RING_DOORBELL
+    sys_debug_log_ring_doorbell() -> ApexReturnCode
+
+    @SYS_DEBUG_EXIT
+    sys_debug_exit(
+        code: usize,
+    ) -> !
+}
+
+/// Exit code of a panic, as passed to [`sys_debug_exit`].
+pub const EXIT_PANICKED: usize = 101;
+
+/// Makes the system call `sysno` with raw `args`, returning what the kernel returns as is.
+#[inline(always)]
+pub fn sys_raw(sysno: usize, args: [usize; 7]) -> usize {
+    let ret: usize;
+
+    cfg_if::cfg_if! {
+        if #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))] {
+            unsafe {
+                core::arch::asm!(
+                    "ecall",
+                    inlateout("a0") args[0] => ret,
+                    in("a1") args[1],
+                    in("a2") args[2],
+                    in("a3") args[3],
+                    in("a4") args[4],
+                    in("a5") args[5],
+                    in("a6") args[6],
+                    in("a7") sysno,
+                );
+            }
+        } else {
+            compile_error!("unsupported architecture");
+        }
+    }
+
+    ret
 }
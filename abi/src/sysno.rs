@@ -114,7 +114,138 @@ def_sysno! {
     SYS_GET_PROCESS_MUTEX_STATE,
 }
 
+def_sysno! {
+    SYS_GET_CAPABILITIES = 0x6000,
+    SYS_DROP_CAPABILITIES,
+}
+
+def_sysno! {
+    SYS_RSEQ_REGISTER = 0x6100,
+}
+
+def_sysno! {
+    SYS_GET_TUNABLE = 0x6200,
+    SYS_SET_TUNABLE,
+}
+
+def_sysno! {
+    SYS_TRAP_HANDLER_SET = 0x6300,
+    SYS_TRAP_RETURN,
+    SYS_TRAP_SIGNAL,
+}
+
+def_sysno! {
+    SYS_MAP_PAGE = 0x6400,
+}
+
+def_sysno! {
+    SYS_CLOCK_GETTIME = 0x6500,
+    SYS_NANOSLEEP,
+}
+
+def_sysno! {
+    SYS_STRACE = 0x6600,
+}
+
 def_sysno! {
     SYS_DEBUG_LOG = 0xdbdbdbdb,
     SYS_DEBUG_HALT,
+    SYS_DEBUG_LOG_RING_SETUP,
+    SYS_DEBUG_LOG_RING_DOORBELL,
+    SYS_DEBUG_EXIT,
+}
+
+macro_rules! def_sysname {
+    ($($sysno:ident,)*) => {
+        /// Returns the name of `sysno`, as it is defined here.
+        pub const fn name(sysno: usize) -> Option<&'static str> {
+            match sysno {
+                $($sysno => Some(stringify!($sysno)),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+def_sysname! {
+    SYS_GET_PARTITION_STATUS,
+    SYS_SET_PARTITION_MODE,
+    SYS_GET_PROCESS_ID,
+    SYS_GET_PROCESS_STATUS,
+    SYS_CREATE_PROCESS,
+    SYS_SET_PRIORITY,
+    SYS_SUSPEND_SELF,
+    SYS_SUSPEND,
+    SYS_RESUME,
+    SYS_STOP_SELF,
+    SYS_STOP,
+    SYS_START,
+    SYS_DELAYED_START,
+    SYS_LOCK_PREEMPTION,
+    SYS_UNLOCK_PREEMPTION,
+    SYS_GET_MY_ID,
+    SYS_INITIALIZE_PROCESS_CORE_AFFINITY,
+    SYS_GET_MY_PROCESSOR_CORE_ID,
+    SYS_GET_MY_INDEX,
+    SYS_TIMED_WAIT,
+    SYS_PERIODIC_WAIT,
+    SYS_GET_TIME,
+    SYS_REPLENISH,
+    SYS_CREATE_SAMPLING_PORT,
+    SYS_WRITE_SAMPLING_MESSAGE,
+    SYS_READ_SAMPLING_MESSAGE,
+    SYS_GET_SAMPLING_PORT_ID,
+    SYS_GET_SAMPLING_PORT_STATUS,
+    SYS_CREATE_QUEUING_PORT,
+    SYS_SEND_QUEUING_MESSAGE,
+    SYS_RECEIVE_QUEUING_MESSAGE,
+    SYS_GET_QUEUING_PORT_ID,
+    SYS_GET_QUEUING_PORT_STATUS,
+    SYS_CLEAR_QUEUING_PORT,
+    SYS_CREATE_BUFFER,
+    SYS_SEND_BUFFER,
+    SYS_RECEIVE_BUFFER,
+    SYS_GET_BUFFER_ID,
+    SYS_GET_BUFFER_STATUS,
+    SYS_CREATE_BLACKBOARD,
+    SYS_DISPLAY_BLACKBOARD,
+    SYS_READ_BLACKBOARD,
+    SYS_CLEAR_BLACKBOARD,
+    SYS_GET_BLACKBOARD_ID,
+    SYS_GET_BLACKBOARD_STATUS,
+    SYS_CREATE_SEMAPHORE,
+    SYS_WAIT_SEMAPHORE,
+    SYS_SIGNAL_SEMAPHORE,
+    SYS_GET_SEMAPHORE_ID,
+    SYS_GET_SEMAPHORE_STATUS,
+    SYS_CREATE_EVENT,
+    SYS_SET_EVENT,
+    SYS_RESET_EVENT,
+    SYS_WAIT_EVENT,
+    SYS_GET_EVENT_ID,
+    SYS_GET_EVENT_STATUS,
+    SYS_CREATE_MUTEX,
+    SYS_ACQUIRE_MUTEX,
+    SYS_RELEASE_MUTEX,
+    SYS_RESET_MUTEX,
+    SYS_GET_MUTEX_ID,
+    SYS_GET_MUTEX_STATUS,
+    SYS_GET_PROCESS_MUTEX_STATE,
+    SYS_GET_CAPABILITIES,
+    SYS_DROP_CAPABILITIES,
+    SYS_RSEQ_REGISTER,
+    SYS_GET_TUNABLE,
+    SYS_SET_TUNABLE,
+    SYS_TRAP_HANDLER_SET,
+    SYS_TRAP_RETURN,
+    SYS_TRAP_SIGNAL,
+    SYS_MAP_PAGE,
+    SYS_CLOCK_GETTIME,
+    SYS_NANOSLEEP,
+    SYS_STRACE,
+    SYS_DEBUG_LOG,
+    SYS_DEBUG_HALT,
+    SYS_DEBUG_LOG_RING_SETUP,
+    SYS_DEBUG_LOG_RING_DOORBELL,
+    SYS_DEBUG_EXIT,
 }
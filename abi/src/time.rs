@@ -0,0 +1,35 @@
+use core::time::Duration;
+
+/// Clock of the wall time, available once the kernel has anchored it.
+pub const CLOCK_REALTIME: usize = 0;
+/// Clock of the time since boot, which never goes backwards.
+pub const CLOCK_MONOTONIC: usize = 1;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// A point or span of time, as passed to and from the kernel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(C)]
+pub struct Timespec {
+    pub sec: u64,
+    pub nsec: u64,
+}
+
+impl Timespec {
+    /// Returns the span of time, unless `nsec` is a second or more.
+    pub const fn to_duration(self) -> Option<Duration> {
+        if self.nsec >= NANOS_PER_SEC {
+            return None;
+        }
+        Some(Duration::new(self.sec, self.nsec as u32))
+    }
+}
+
+impl From<Duration> for Timespec {
+    fn from(duration: Duration) -> Self {
+        Self {
+            sec: duration.as_secs(),
+            nsec: duration.subsec_nanos() as u64,
+        }
+    }
+}
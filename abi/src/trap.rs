@@ -0,0 +1,23 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Traps a process takes in its own handler, set through `SYS_TRAP_HANDLER_SET`.
+    ///
+    /// The handler is entered on its own stack with the trap in `a0`, as one of these bits, then
+    /// an address in `a1` and the interrupted pc in `a2`. The address is the faulting one for
+    /// page faults, that of the breakpoint for breakpoints, and the signal number for signals.
+    /// Returning through `SYS_TRAP_RETURN` resumes the interrupted context, which retries a
+    /// faulting access but goes on past a breakpoint.
+    ///
+    /// A trap taken while the handler runs terminates the process, and signals wait until the
+    /// handler returns.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct TrapMask: usize {
+        const BREAKPOINT = 1 << 0;
+        const PAGE_FAULT = 1 << 1;
+        const SIGNAL = 1 << 2;
+    }
+}
+
+/// Signals a process may be sent through `SYS_TRAP_SIGNAL`, numbered from zero.
+pub const TRAP_SIGNAL_MAX: usize = usize::BITS as usize;
@@ -5,6 +5,7 @@ pub(crate) mod bindings;
 pub(crate) mod basic;
 pub(crate) mod partition;
 pub(crate) mod process;
+pub(crate) mod semaphore;
 pub(crate) mod time;
 
 pub use bindings::*;
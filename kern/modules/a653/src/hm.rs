@@ -0,0 +1,19 @@
+//! Health-monitor errors the kernel raises against partitions.
+
+use core::time::Duration;
+
+/// Errors kept per partition until taken, beyond which the oldest are dropped.
+pub(crate) const HM_ERROR_MAX: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HmErrorKind {
+    /// The partition was held responsible for the kernel heap running out.
+    HeapExhausted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HmError {
+    pub kind: HmErrorKind,
+    /// Time the error was raised at, on the clock of [`jrinx_hal::Cpu::get_time`].
+    pub time: Duration,
+}
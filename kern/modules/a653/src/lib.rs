@@ -1,5 +1,6 @@
 #![no_std]
 #![feature(allocator_api)]
+#![feature(used_with_arg)]
 
 extern crate alloc;
 
@@ -9,8 +10,12 @@ extern crate log;
 #[macro_use]
 extern crate jrinx_hal;
 
+pub mod hm;
+pub mod lowmem;
 pub mod partition;
 pub mod process;
+pub mod semaphore;
+pub mod uptr;
 
 #[derive(Debug, Clone, Copy)]
 pub enum A653Entry {
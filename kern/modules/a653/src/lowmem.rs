@@ -0,0 +1,162 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use jrinx_error::{InternalError, Result};
+use jrinx_init::kernel_init;
+use jrinx_tunable::TunableValue;
+use spin::RwLock;
+
+use crate::{hm::HmErrorKind, partition::Partition};
+
+/// Action applied to the partition held responsible for the kernel heap running out, along
+/// with a [`HmErrorKind::HeapExhausted`] health-monitor error raised against it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LowMemAction {
+    /// Only raises the health-monitor error, leaving the partition as it is.
+    Report,
+    /// Fences the partition off the heap, so that its allocations fail without reaching the
+    /// kernel heap until it is [`unfenced`](Partition::unfence_heap).
+    Fence,
+    /// Pauses the partition until its inspector is resumed, see [`Partition::pause`].
+    Pause,
+    /// Restarts the partition in warm-start mode, see [`Partition::warm_restart`].
+    WarmRestart,
+}
+
+impl LowMemAction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "report" => Some(Self::Report),
+            "fence" => Some(Self::Fence),
+            "pause" => Some(Self::Pause),
+            "warm-restart" => Some(Self::WarmRestart),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Report => "report",
+            Self::Fence => "fence",
+            Self::Pause => "pause",
+            Self::WarmRestart => "warm-restart",
+        }
+    }
+}
+
+impl TunableValue for LowMemAction {
+    const KIND: &'static str = "lowmem-action";
+
+    fn parse(text: &str) -> Option<Self> {
+        Self::from_name(text)
+    }
+
+    fn show(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+const DEFAULT_POLICY: Option<LowMemAction> = Some(LowMemAction::Report);
+
+static POLICY: RwLock<Option<LowMemAction>> = RwLock::new(DEFAULT_POLICY);
+
+/// Whether the kernel heap is out of memory since the last failing allocation.
+static PRESSURE: AtomicBool = AtomicBool::new(false);
+
+/// Registers the tunable `lowmem.policy`, the action applied to partitions not configured with
+/// their own, where `none` leaves them alone.
+#[kernel_init(name = "lowmem-tunables", depends = ["heap"])]
+fn register_tunables() -> Result<()> {
+    jrinx_tunable::register(
+        "lowmem.policy",
+        DEFAULT_POLICY,
+        |_| Ok(()),
+        |&action| *POLICY.write() = action,
+    )
+    .map_err(|_| InternalError::RepeatInitialization)
+}
+
+pub fn policy() -> Option<LowMemAction> {
+    *POLICY.read()
+}
+
+/// Ends the pressure episode, once an allocation from the kernel heap succeeds again.
+pub(crate) fn relieve() {
+    PRESSURE.store(false, Ordering::Relaxed);
+}
+
+/// Handles an allocation of `size` bytes, made on behalf of `requester`, that the kernel heap
+/// failed although the partition was within its own memory budget.
+///
+/// On the first failure of a pressure episode, the user partition using the most memory is
+/// held responsible, whichever partition happened to allocate next. Kernel partitions and
+/// the allocations made outside of partitions are exempt.
+pub(crate) fn on_alloc_failure(requester: &Partition, size: usize) {
+    if PRESSURE.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    let (allocated, total) = jrinx_heap::usage();
+    warn!(
+        "kernel heap exhausted: {:#x} bytes requested by partition {:?}, {:#x} of {:#x} bytes allocated",
+        size,
+        requester.name(),
+        allocated,
+        total
+    );
+
+    let Some(offender) = Partition::all()
+        .into_iter()
+        .filter(|partition| !partition.kernel() && !partition.heap_fenced())
+        .max_by_key(|partition| partition.memory_used())
+    else {
+        warn!("low-memory policy: no partition to hold responsible");
+        return;
+    };
+
+    let used = offender.memory_used();
+    let Some(action) = offender.lowmem_action().or_else(policy) else {
+        warn!(
+            "low-memory policy: selected partition {:?} using {:#x} bytes, no action configured",
+            offender.name(),
+            used
+        );
+        return;
+    };
+
+    warn!(
+        "low-memory policy: selected partition {:?} using {:#x} bytes, applying {}",
+        offender.name(),
+        used,
+        action.name()
+    );
+    offender.raise_hm_error(HmErrorKind::HeapExhausted);
+    let applied = match action {
+        LowMemAction::Report => Ok(()),
+        LowMemAction::Fence => {
+            offender.fence_heap();
+            Ok(())
+        }
+        LowMemAction::Pause => offender.pause(),
+        LowMemAction::WarmRestart => offender.warm_restart(),
+    };
+    if let Err(err) = applied {
+        warn!(
+            "low-memory policy: failed to apply {} to partition {:?}: {}",
+            action.name(),
+            offender.name(),
+            err
+        );
+    }
+
+    let (allocated, total) = jrinx_heap::usage();
+    warn!(
+        "low-memory policy: partition {:?} using {:#x} bytes, {:#x} of {:#x} heap bytes allocated",
+        offender.name(),
+        offender.memory_used(),
+        allocated,
+        total
+    );
+}
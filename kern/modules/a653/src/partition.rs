@@ -5,28 +5,46 @@ use alloc::{
     sync::{Arc, Weak},
     vec::Vec,
 };
-use core::{alloc::Allocator, ops::Deref, sync::atomic::AtomicUsize};
+use core::{
+    alloc::Allocator,
+    ops::{Deref, Range},
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use elf::{
-    abi::{PF_R, PF_W, PF_X},
+    abi::{PF_R, PF_W, PF_X, PT_LOAD},
     endian::AnyEndian,
     ElfBytes,
 };
-use jrinx_addr::VirtAddr;
+use jrinx_abi::cap::Capabilities;
+use jrinx_addr::{VirtAddr, VirtAddrRange};
 use jrinx_apex::*;
-use jrinx_error::{InternalError, Result};
-use jrinx_hal::{hal, Cache, Hal, Vm};
-use jrinx_loader::ElfLoader;
-use jrinx_multitask::inspector::Inspector;
-use jrinx_paging::{common::PageTable, GenericPagePerm, GenericPageTable, PagePerm};
+use jrinx_config::PAGE_SIZE;
+use jrinx_error::{ContextError, InternalError, Result, ResultExt};
+use jrinx_hal::{hal, Cache, Cpu, Hal, Vm};
+use jrinx_multitask::{
+    executor::Executor,
+    inspector::{Inspector, InspectorId, ResourceLimits},
+    runtime::Runtime,
+};
+use jrinx_paging::{
+    common::{DirtyIter, PageTable},
+    GenericPagePerm, GenericPageTable, PagePerm,
+};
 use jrinx_phys_frame::PhysFrame;
 use jrinx_serial_id_macro::SerialId;
 use jrinx_stack_alloc::StackAllocator;
+use jrinx_trap::smp;
 use jrinx_vmm::KERN_PAGE_TABLE;
-use spin::{Mutex, RwLock, RwLockReadGuard};
+use spin::{Mutex, Once, RwLock, RwLockReadGuard};
 
 use crate::{
+    hm::{HmError, HmErrorKind, HM_ERROR_MAX},
+    lowmem::{self, LowMemAction},
     process::{Process, ProcessId},
+    semaphore::{Semaphore, SemaphoreConfig, SemaphoreId},
     A653Entry,
 };
 
@@ -39,8 +57,11 @@ pub struct Partition {
     name: ApexName,
     memory: PartitionMemory,
     page_table: RwLock<PageTable>,
+    lazy_regions: RwLock<Vec<LazyRegion>>,
     pre_start_hooks: RwLock<VecDeque<Box<dyn FnOnce() + Send + Sync>>>,
     process_registry: RwLock<PartitionProcessRegistry>,
+    semaphore_registry: RwLock<PartitionSemaphoreRegistry>,
+    semaphore_configs: Vec<SemaphoreConfig>,
     stack_allocator: StackAllocator,
     next_index: AtomicUsize,
     entry: A653Entry,
@@ -51,11 +72,59 @@ pub struct Partition {
     start_condition: ApexStartCondition,
     num_assigned_cores: ApexNumCores,
     assigned_cores: RwLock<Vec<ApexProcessorCoreId>>,
+    limits: ResourceLimits,
+    capabilities: Capabilities,
+    hm_errors: Mutex<VecDeque<HmError>>,
 }
 
+/// Builds the executor running the init process of a restarted partition, registered by the
+/// kernel, which knows how the system calls of its processes are handled.
+pub type PartitionRestart = fn(partition: &Arc<Partition>) -> Result<Pin<Box<Executor>>>;
+
+static RESTART: Once<PartitionRestart> = Once::new();
+
 struct PartitionMemory {
     size: usize,
     free: Mutex<usize>,
+    fenced: AtomicBool,
+    lowmem_action: Option<LowMemAction>,
+}
+
+/// Part of an address space populated page by page as it is first touched, such as a
+/// segment of the program of the partition.
+struct LazyRegion {
+    range: VirtAddrRange,
+    perm: PagePerm,
+    /// Bytes backing the start of the region, the rest of which is zero-filled.
+    data: &'static [u8],
+}
+
+impl LazyRegion {
+    /// Copies the bytes backing the page at `page` into `frame`, which is zero-filled.
+    fn fill(&self, page: VirtAddr, frame: &PhysFrame) {
+        let backed = VirtAddrRange::new(self.range.start(), self.range.start() + self.data.len());
+        let Some(copied) = page_range(page).intersection(backed) else {
+            return;
+        };
+
+        let src =
+            &self.data[copied.start() - self.range.start()..copied.end() - self.range.start()];
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                src.as_ptr(),
+                (frame.addr().to_virt().as_usize() + (copied.start() - page)) as *mut u8,
+                src.len(),
+            );
+        }
+    }
+
+    fn overlaps(&self, page: VirtAddr) -> bool {
+        self.range.overlaps(page_range(page))
+    }
+}
+
+fn page_range(page: VirtAddr) -> VirtAddrRange {
+    VirtAddrRange::new(page, page + PAGE_SIZE)
 }
 
 struct PartitionProcessRegistry {
@@ -63,6 +132,11 @@ struct PartitionProcessRegistry {
     names: BTreeMap<ApexName, ProcessId>,
 }
 
+struct PartitionSemaphoreRegistry {
+    registry: BTreeMap<SemaphoreId, Arc<Semaphore>>,
+    names: BTreeMap<ApexName, SemaphoreId>,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct PartitionMemoryAllocator {
     partition_id: PartitionId,
@@ -74,6 +148,10 @@ pub struct PartitionConfig<'a> {
     pub period: ApexSystemTime,
     pub duration: ApexSystemTime,
     pub num_cores: ApexNumCores,
+    pub limits: ResourceLimits,
+    pub capabilities: Capabilities,
+    pub lowmem_action: Option<LowMemAction>,
+    pub semaphores: Vec<SemaphoreConfig>,
     pub partition_type: PartitionTypeConfig<'a>,
 }
 
@@ -85,7 +163,9 @@ pub enum PartitionTypeConfig<'a> {
 static PARTITIONS: RwLock<BTreeMap<PartitionId, Weak<Partition>>> = RwLock::new(BTreeMap::new());
 
 impl Partition {
-    pub fn new(config: &PartitionConfig) -> Result<Arc<Self>> {
+    /// Creates a partition, the segments of whose program are populated as they are first
+    /// touched, see [`Partition::resolve_page_fault`].
+    pub fn new(config: &PartitionConfig<'static>) -> Result<Arc<Self>> {
         let page_table = PageTable::new_from(&KERN_PAGE_TABLE.read())?;
         let partition_id = PartitionId::new();
 
@@ -94,7 +174,7 @@ impl Partition {
                 VirtAddr::new(jrinx_config::UPROG_STACK_REGION.addr),
                 jrinx_config::UPROG_STACK_REGION.len,
             ),
-            jrinx_config::PAGE_SIZE,
+            PAGE_SIZE,
             move |addr| {
                 let partition = Partition::find_by_id(partition_id).unwrap();
                 partition.page_table.write().map(
@@ -116,10 +196,13 @@ impl Partition {
             kernel: matches!(config.partition_type, PartitionTypeConfig::Kern),
             identifier: partition_id,
             name: config.name,
-            memory: PartitionMemory::new(config.memory),
+            memory: PartitionMemory::new(config.memory, config.lowmem_action),
             page_table: RwLock::new(page_table),
+            lazy_regions: RwLock::new(Vec::new()),
             pre_start_hooks: RwLock::new(VecDeque::new()),
             process_registry: RwLock::new(PartitionProcessRegistry::new()),
+            semaphore_registry: RwLock::new(PartitionSemaphoreRegistry::new()),
+            semaphore_configs: config.semaphores.clone(),
             stack_allocator,
             next_index: AtomicUsize::new(0),
             period: config.period,
@@ -129,6 +212,9 @@ impl Partition {
             start_condition: ApexStartCondition::NormalStart,
             num_assigned_cores: config.num_cores,
             assigned_cores: RwLock::new(Vec::new()),
+            limits: config.limits,
+            capabilities: config.capabilities,
+            hm_errors: Mutex::new(VecDeque::with_capacity(HM_ERROR_MAX)),
             entry: match &config.partition_type {
                 PartitionTypeConfig::Kern => todo!(),
                 PartitionTypeConfig::User(program) => A653Entry::User(program.ehdr.e_entry as _),
@@ -140,7 +226,10 @@ impl Partition {
             .insert(partition.identifier, Arc::downgrade(&partition));
 
         if let PartitionTypeConfig::User(program) = &config.partition_type {
-            partition.load_program(program)?;
+            partition.load_program(program).map_err(|err| {
+                warn!("partition {:?}: {}", partition.name(), err);
+                err.into_error()
+            })?;
         }
 
         Ok(partition)
@@ -154,6 +243,14 @@ impl Partition {
         PARTITIONS.read().get(&id)?.upgrade()
     }
 
+    pub fn all() -> Vec<Arc<Self>> {
+        PARTITIONS
+            .read()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
     pub fn find_by_name(name: &ApexName) -> Option<Arc<Self>> {
         PARTITIONS
             .read()
@@ -181,6 +278,29 @@ impl Partition {
         *self.memory.free.lock()
     }
 
+    pub fn memory_used(&self) -> usize {
+        self.memory.size - self.memory_free()
+    }
+
+    /// Returns the action of the low-memory policy configured for the partition, if any
+    /// overrides the global one.
+    pub fn lowmem_action(&self) -> Option<LowMemAction> {
+        self.memory.lowmem_action
+    }
+
+    pub fn heap_fenced(&self) -> bool {
+        self.memory.fenced.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn fence_heap(&self) {
+        self.memory.fenced.store(true, Ordering::SeqCst);
+    }
+
+    /// Lets the partition allocate again after the low-memory policy fenced it off the heap.
+    pub fn unfence_heap(&self) {
+        self.memory.fenced.store(false, Ordering::SeqCst);
+    }
+
     pub fn entry(&self) -> A653Entry {
         self.entry
     }
@@ -209,6 +329,15 @@ impl Partition {
         *self.lock_level.write() = level;
     }
 
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// Returns the capabilities granted to the initial process of the partition.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
     pub fn assigned_cores(&self) -> Vec<ApexProcessorCoreId> {
         self.assigned_cores.read().clone()
     }
@@ -259,6 +388,205 @@ impl Partition {
         }
     }
 
+    /// Clears the soft-dirty bits of the pages in `range`, so that [`collect_dirty`] enumerates
+    /// only the pages written from now on, as an incremental checkpoint needs.
+    ///
+    /// [`collect_dirty`]: Self::collect_dirty
+    pub fn clear_soft_dirty(&self, range: Range<VirtAddr>) -> Result<usize> {
+        let cleared = self.page_table.write().clear_soft_dirty(range)?;
+
+        // The lock is released first, as the assigned cores may be spinning on it to resolve
+        // write faults with interrupts off.
+        let cores = self
+            .assigned_cores
+            .read()
+            .iter()
+            .map(|&core_id| core_id as usize)
+            .collect::<Vec<_>>();
+        smp::call(&cores, Duration::from_secs(1), || {
+            hal!().vm().sync_all();
+            0
+        })?
+        .into_result()?;
+
+        Ok(cleared)
+    }
+
+    /// Passes the pages in `range` written since the soft-dirty bits were last cleared to `f`.
+    pub fn collect_dirty<F, R>(&self, range: Range<VirtAddr>, f: F) -> Result<R>
+    where
+        F: FnOnce(DirtyIter<'_>) -> R,
+    {
+        Ok(f(self.page_table.read().collect_dirty(range)?))
+    }
+
+    /// Resolves faults in the address space of the current process, or in the kernel page table
+    /// outside of any process: write faults on pages write-protected for soft-dirty tracking or
+    /// copy-on-write, and faults on pages of lazily-populated regions not touched yet.
+    pub fn resolve_page_fault(addr: VirtAddr, perm: PagePerm) -> bool {
+        let partition =
+            Process::current().and_then(|process| Partition::find_by_id(process.partition_id()));
+
+        if perm.contains(PagePerm::W) {
+            let resolved = match &partition {
+                Some(partition) => {
+                    let soft = partition.page_table.read().resolve_soft_write(addr);
+                    soft || partition
+                        .page_table
+                        .write()
+                        .resolve_cow_write(addr, partition.allocator())
+                        .unwrap_or(false)
+                }
+                None => KERN_PAGE_TABLE.read().resolve_soft_write(addr),
+            };
+            if resolved {
+                hal!().vm().sync_all();
+                return true;
+            }
+        }
+
+        partition.is_some_and(|partition| partition.populate(addr, perm))
+    }
+
+    /// Tells whether the processes of the partition can access the `len` bytes at `addr` with
+    /// `perm`, resolving the faults such an access from user mode would, so that the kernel
+    /// accesses them on behalf of a process without faulting.
+    pub fn check_user_access(&self, addr: VirtAddr, len: usize, perm: PagePerm) -> bool {
+        let perm = perm | PagePerm::V | PagePerm::U;
+        let Some(end) = addr.as_usize().checked_add(len) else {
+            return false;
+        };
+        if end > usize::MAX / 2 {
+            return false;
+        }
+
+        let mut page = addr.align_page_down();
+        while page.as_usize() < end {
+            let mapped = self
+                .page_table
+                .read()
+                .lookup(page)
+                .map(|(_, mapped)| mapped);
+            let accessible = match mapped {
+                Ok(mapped) if mapped.contains(perm) => true,
+                // Pages write-protected for soft-dirty tracking or copy-on-write.
+                Ok(mapped) if perm.contains(PagePerm::W) && mapped.contains(perm - PagePerm::W) => {
+                    let resolved = self.page_table.read().resolve_soft_write(page)
+                        || self
+                            .page_table
+                            .write()
+                            .resolve_cow_write(page, self.allocator())
+                            .unwrap_or(false);
+                    if resolved {
+                        hal!().vm().sync_all();
+                    }
+                    resolved
+                }
+                Ok(_) => false,
+                Err(_) => self.populate(page, perm),
+            };
+            if !accessible {
+                return false;
+            }
+            page = page + PAGE_SIZE;
+        }
+        true
+    }
+
+    /// Maps the page at `addr` with the bytes of the lazily-populated regions it lies in,
+    /// returning whether an access requiring `perm` can be retried.
+    fn populate(&self, addr: VirtAddr, perm: PagePerm) -> bool {
+        let page = addr.align_page_down();
+        let regions = self.lazy_regions.read();
+        let mut overlapping = regions
+            .iter()
+            .filter(|region| region.overlaps(page))
+            .peekable();
+        if overlapping.peek().is_none() {
+            warn!(
+                "partition {:?} faults on {} outside of any region",
+                self.identifier, addr
+            );
+            return false;
+        }
+        let allowed = overlapping
+            .clone()
+            .fold(PagePerm::V | PagePerm::U, |allowed, region| {
+                allowed | region.perm
+            });
+        if !allowed.contains(perm) {
+            warn!(
+                "partition {:?} faults on {} for {}, beyond {} of its region",
+                self.identifier, addr, perm, allowed
+            );
+            return false;
+        }
+
+        // Tasks of the partition faulting on the same page are serialized here, and the later
+        // ones find it populated.
+        let mut page_table = self.page_table.write();
+        if let Ok((_, mapped)) = page_table.lookup(page) {
+            hal!().vm().sync_all();
+            return mapped.contains(perm);
+        }
+
+        let Ok(frame) = PhysFrame::alloc_in(self.allocator()) else {
+            warn!(
+                "partition {:?} has no memory left to populate {}",
+                self.identifier, page
+            );
+            return false;
+        };
+        overlapping.for_each(|region| region.fill(page, &frame));
+        if page_table.map(page, frame, allowed).is_err() {
+            return false;
+        }
+        drop(page_table);
+
+        if allowed.contains(PagePerm::X) {
+            hal!().cache().sync_all();
+        }
+        hal!().vm().sync_all();
+        true
+    }
+
+    /// Maps a zero-filled page at `addr` for the processes of the partition to read and write,
+    /// out of the regions of its program and of its stacks.
+    pub fn map_page(&self, addr: VirtAddr) -> Result<()> {
+        let page = addr.align_page_down();
+        let stacks = VirtAddrRange::from_len(
+            VirtAddr::new(jrinx_config::UPROG_STACK_REGION.addr),
+            jrinx_config::UPROG_STACK_REGION.len,
+        )
+        .unwrap();
+        if page.as_usize() == 0
+            || page.as_usize() >= usize::MAX / 2
+            || stacks.contains(page)
+            || self
+                .lazy_regions
+                .read()
+                .iter()
+                .any(|region| region.overlaps(page))
+        {
+            return Err(InternalError::InvalidVirtAddr(addr));
+        }
+
+        let mut page_table = self.page_table.write();
+        if page_table.lookup(page).is_ok() {
+            return Err(InternalError::RepeatInitialization);
+        }
+        let frame = PhysFrame::alloc_in(self.allocator())?;
+        page_table.map(
+            page,
+            frame,
+            PagePerm::V | PagePerm::U | PagePerm::R | PagePerm::W,
+        )?;
+        drop(page_table);
+
+        hal!().vm().sync_all();
+        Ok(())
+    }
+
     pub fn status(&self) -> ApexPartitionStatus {
         ApexPartitionStatus {
             period: self.period,
@@ -272,7 +600,89 @@ impl Partition {
     }
 
     pub fn gen_inspector(self: &Arc<Self>) -> Result<Inspector> {
-        Ok(Inspector::new_with_ext(self.clone()))
+        Ok(Inspector::new_with_ext(self.clone()).with_limits(self.limits))
+    }
+
+    pub fn register_restart(restart: PartitionRestart) {
+        RESTART.call_once(|| restart);
+    }
+
+    /// Raises a health-monitor error against the partition, kept until
+    /// [`taken`](Partition::take_hm_error).
+    ///
+    /// The queue of errors never grows, so that errors can be raised with the heap exhausted.
+    pub fn raise_hm_error(&self, kind: HmErrorKind) {
+        warn!(
+            "health monitor: partition {:?} raised {:?}",
+            self.name(),
+            kind
+        );
+        let mut errors = self.hm_errors.lock();
+        if errors.len() == HM_ERROR_MAX {
+            errors.pop_front();
+        }
+        errors.push_back(HmError {
+            kind,
+            time: hal!().cpu().get_time(),
+        });
+    }
+
+    pub fn take_hm_error(&self) -> Option<HmError> {
+        self.hm_errors.lock().pop_front()
+    }
+
+    /// Pauses the inspector running the partition, see [`Runtime::pause_inspector`].
+    pub fn pause(&self) -> Result<()> {
+        let (cpu_id, id) = self.locate_inspector()?;
+        Runtime::with_spec_cpu(cpu_id, |rt| rt.pause_inspector(id))?
+    }
+
+    /// Restarts the partition in warm-start mode, with a new inspector running a new init
+    /// process, in place of the inspector running it, which is paused and retired along with
+    /// its processes.
+    ///
+    /// This fails unless the kernel [registered](Partition::register_restart) how to restart
+    /// partitions, and for partitions scheduled by a schedule table, whose inspectors cannot
+    /// be retired.
+    pub fn warm_restart(self: &Arc<Self>) -> Result<()> {
+        let restart = RESTART.get().ok_or(InternalError::InvalidInspectorStatus)?;
+        let (cpu_id, id) = self.locate_inspector()?;
+        Runtime::with_spec_cpu(cpu_id, |rt| {
+            // The inspector may be paused already, and is not scheduled again either way.
+            let _ = rt.pause_inspector(id);
+            rt.retire_inspector(id)
+        })??;
+
+        self.set_operating_mode(ApexOperatingMode::WarmStart);
+        self.process_registry.write().clear();
+        let inspector = self.gen_inspector()?;
+        inspector.register(restart(self)?)?;
+        Runtime::with_spec_cpu(cpu_id, |rt| rt.register(inspector))?
+    }
+
+    /// Returns the CPU and the id of the inspector running the partition.
+    fn locate_inspector(&self) -> Result<(usize, InspectorId)> {
+        (0..hal!().cpu().nproc())
+            .find_map(|cpu_id| {
+                Runtime::with_spec_cpu(cpu_id, |rt| {
+                    rt.with_registry(|registry| {
+                        registry
+                            .values()
+                            .find(|is| {
+                                is.ext()
+                                    .deref()
+                                    .downcast_ref::<Arc<Partition>>()
+                                    .is_some_and(|partition| {
+                                        partition.identifier == self.identifier
+                                    })
+                            })
+                            .map(|is| (cpu_id, is.id()))
+                    })
+                })
+                .ok()
+                .flatten()
+            })
+            .ok_or(InternalError::InvalidInspectorId)
     }
 
     pub(crate) fn allocate_stack(&self, stack_size: usize) -> Result<VirtAddr> {
@@ -308,10 +718,58 @@ impl Partition {
             .and_then(|id| self.find_process_by_id(*id))
     }
 
-    fn load_program(&self, program: &ElfBytes<'_, AnyEndian>) -> Result<()> {
-        let mut page_table = self.page_table.write();
+    pub(crate) fn semaphore_config(&self, name: &ApexSemaphoreName) -> Option<SemaphoreConfig> {
+        self.semaphore_configs
+            .iter()
+            .find(|config| config.name == *name)
+            .copied()
+    }
+
+    pub(crate) fn register_semaphore(&self, semaphore: Arc<Semaphore>) {
+        self.semaphore_registry.write().insert(semaphore);
+    }
+
+    pub(crate) fn find_semaphore_by_id(&self, identifier: SemaphoreId) -> Option<Arc<Semaphore>> {
+        self.semaphore_registry
+            .read()
+            .registry
+            .get(&identifier)
+            .cloned()
+    }
+
+    pub(crate) fn find_semaphore_by_name(
+        &self,
+        name: &ApexSemaphoreName,
+    ) -> Option<Arc<Semaphore>> {
+        self.semaphore_registry
+            .read()
+            .names
+            .get(name)
+            .and_then(|id| self.find_semaphore_by_id(*id))
+    }
+
+    pub(crate) fn semaphores(&self) -> Vec<Arc<Semaphore>> {
+        self.semaphore_registry
+            .read()
+            .registry
+            .values()
+            .cloned()
+            .collect()
+    }
 
-        ElfLoader::new(program).load(|elf, phdr, vaddr, offst, len| {
+    fn load_program(
+        &self,
+        program: &ElfBytes<'static, AnyEndian>,
+    ) -> core::result::Result<(), ContextError> {
+        let mut regions = self.lazy_regions.write();
+
+        for phdr in program
+            .segments()
+            .ok_or(InternalError::ElfParseError)
+            .context("read program headers")?
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_LOAD)
+        {
             let mut perm = PagePerm::V | PagePerm::U;
             if phdr.p_flags & PF_R != 0 {
                 perm |= PagePerm::R;
@@ -323,36 +781,20 @@ impl Partition {
                 perm |= PagePerm::X;
             }
 
-            let paddr = if let Ok((phys_frame, old_perm)) = page_table.lookup(vaddr) {
-                let paddr = phys_frame.addr();
-                if !old_perm.contains(perm) {
-                    page_table.map(vaddr, phys_frame, perm | old_perm)?;
-                }
-                paddr
-            } else {
-                let phys_frame = PhysFrame::alloc_in(self.allocator())?;
-                let paddr = phys_frame.addr();
-                page_table.map(vaddr, phys_frame, perm)?;
-                paddr
-            };
-
-            if len != 0 {
-                unsafe {
-                    core::ptr::copy_nonoverlapping(
-                        elf.segment_data(phdr)
-                            .map_err(|_| InternalError::ElfParseError)?
-                            .as_ptr()
-                            .add(offst),
-                        (paddr.to_virt().as_usize() + (vaddr - vaddr.align_page_down())) as *mut u8,
-                        len,
-                    );
-                }
-            }
-
-            Ok(())
-        })?;
-
-        hal!().cache().sync_all();
+            let start = VirtAddr::new(phdr.p_vaddr as usize);
+            regions.push(LazyRegion {
+                range: VirtAddrRange::from_len(start, phdr.p_memsz as usize)
+                    .ok_or(InternalError::ElfParseError)
+                    .with_context(|| start)
+                    .context("place segment")?,
+                perm,
+                data: program
+                    .segment_data(&phdr)
+                    .map_err(|_| InternalError::ElfParseError)
+                    .with_context(|| start)
+                    .context("read segment data")?,
+            });
+        }
 
         Ok(())
     }
@@ -378,13 +820,35 @@ impl PartitionProcessRegistry {
         self.registry.insert(identifier, process.clone());
         self.names.insert(name, identifier);
     }
+
+    fn clear(&mut self) {
+        self.registry.clear();
+        self.names.clear();
+    }
+}
+
+impl PartitionSemaphoreRegistry {
+    const fn new() -> Self {
+        Self {
+            registry: BTreeMap::new(),
+            names: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, semaphore: Arc<Semaphore>) {
+        let identifier = semaphore.identifier();
+        self.registry.insert(identifier, semaphore.clone());
+        self.names.insert(semaphore.name(), identifier);
+    }
 }
 
 impl PartitionMemory {
-    fn new(size: usize) -> Self {
+    fn new(size: usize, lowmem_action: Option<LowMemAction>) -> Self {
         Self {
             size,
             free: Mutex::new(size),
+            fenced: AtomicBool::new(false),
+            lowmem_action,
         }
     }
 }
@@ -395,13 +859,25 @@ unsafe impl Allocator for PartitionMemoryAllocator {
         layout: core::alloc::Layout,
     ) -> core::prelude::v1::Result<core::ptr::NonNull<[u8]>, core::alloc::AllocError> {
         let partition = Partition::find_by_id(self.partition_id).ok_or(core::alloc::AllocError)?;
-        let mut free = partition.memory.free.lock();
-        if *free > layout.size() {
+        if partition.heap_fenced() {
+            return Err(core::alloc::AllocError);
+        }
+
+        {
+            let mut free = partition.memory.free.lock();
+            if *free <= layout.size() {
+                return Err(core::alloc::AllocError);
+            }
             *free -= layout.size();
-            Global.allocate(layout)
-        } else {
-            Err(core::alloc::AllocError)
         }
+
+        Global
+            .allocate(layout)
+            .inspect(|_| lowmem::relieve())
+            .inspect_err(|_| {
+                *partition.memory.free.lock() += layout.size();
+                lowmem::on_alloc_failure(&partition, layout.size());
+            })
     }
 
     unsafe fn deallocate(&self, ptr: core::ptr::NonNull<u8>, layout: core::alloc::Layout) {
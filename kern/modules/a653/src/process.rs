@@ -1,22 +1,39 @@
 use alloc::{boxed::Box, format, sync::Arc};
-use core::{future::Future, ops::Deref, pin::Pin};
+use core::{
+    fmt::Display,
+    future::{poll_fn, Future},
+    ops::Deref,
+    pin::{pin, Pin},
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Poll, Waker},
+    time::Duration,
+};
+use jrinx_abi::{
+    cap::Capabilities,
+    logring::{LogRing, LOG_RING_PAYLOAD_MAX},
+    rseq::{Rseq, RseqCs},
+    trap::{TrapMask, TRAP_SIGNAL_MAX},
+};
 use jrinx_apex::*;
-use jrinx_paging::GenericPageTable;
-use jrinx_trap::{arch::Context, GenericContext};
+use jrinx_paging::{GenericPageTable, PagePerm};
+use jrinx_trap::{arch::Context, nest, GenericContext, TrapKind, TrapReason};
 
 use jrinx_addr::VirtAddr;
 use jrinx_config::PAGE_SIZE;
-use jrinx_error::{InternalError, Result};
-use jrinx_hal::{Hal, Vm};
+use jrinx_error::{InternalError, ResourceKind, Result};
+use jrinx_hal::{Cpu, Hal, Vm};
 use jrinx_multitask::{
     executor::{Executor, ExecutorPriority},
+    inspector::{Inspector, ResourceCharge},
     Task, TaskPriority,
 };
 use jrinx_serial_id_macro::SerialId;
-use spin::RwLock;
+use jrinx_vmm::KERN_PAGE_TABLE;
+use spin::{Mutex, Once, RwLock};
 
 use crate::{
     partition::{Partition, PartitionId},
+    uptr::{uptr_try_cast, UserPtr},
     A653Entry,
 };
 
@@ -39,6 +56,68 @@ pub struct Process {
     deadline_time: RwLock<ApexSystemTime>,
     process_state: RwLock<ApexProcessState>,
     core_affinity: RwLock<Option<usize>>,
+    capabilities: RwLock<Capabilities>,
+    log_ring: Mutex<Option<ProcessLogRing>>,
+    rseq: Mutex<Option<ProcessRseq>>,
+    upcall: Mutex<ProcessUpcall>,
+    strace: AtomicBool,
+    exit: Once<ProcessExit>,
+}
+
+/// How a process stopped running for good.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessExit {
+    /// The process asked to exit with a code, zero meaning success.
+    Exited(usize),
+    /// The process took a trap the kernel cannot handle on its behalf.
+    Faulted { reason: TrapReason, pc: usize },
+}
+
+impl Display for ProcessExit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Exited(code) => write!(f, "exited with code {}", code),
+            Self::Faulted { reason, pc } => write!(f, "faulted at {:#x}: {:x?}", pc, reason),
+        }
+    }
+}
+
+struct ProcessLogRing {
+    top: VirtAddr,
+    ring: LogRing,
+    capacity: u32,
+    next_seq: u32,
+    /// Bytes of the ring charged to the inspector of the process.
+    _shared: Option<ResourceCharge>,
+}
+
+struct ProcessRseq {
+    area: VirtAddr,
+    cpu_id: Option<usize>,
+    restarts: usize,
+}
+
+/// Handler taking the traps of a process in user mode, see [`TrapMask`].
+#[derive(Default)]
+struct ProcessUpcall {
+    mask: TrapMask,
+    entry: usize,
+    stack_top: usize,
+    /// Context interrupted by the running handler, resumed once it returns.
+    saved: Option<Context>,
+    returning: bool,
+    signals: usize,
+    /// Waker of the process sleeping, woken as it is sent a signal.
+    sleeper: Option<Waker>,
+}
+
+impl ProcessUpcall {
+    fn enter(&mut self, ctx: &mut Context, trap: TrapMask, addr: usize) {
+        let pc = ctx.pc();
+        self.saved = Some(*ctx);
+        ctx.user_setup(self.entry, self.stack_top);
+        ctx.set_args(&[trap.bits(), addr, pc]);
+    }
 }
 
 pub struct ProcessConfig {
@@ -49,6 +128,9 @@ pub struct ProcessConfig {
     pub period: ApexSystemTime,
     pub stack_size: ApexStackSize,
     pub time_capacity: ApexSystemTime,
+    pub capabilities: Capabilities,
+    /// Whether the system calls of the process are traced, see [`Process::strace`].
+    pub strace: bool,
 }
 
 impl From<ProcessId> for ApexProcessId {
@@ -87,6 +169,12 @@ impl Process {
             deadline_time: RwLock::new(APEX_TIME_INFINITY),
             process_state: RwLock::new(ApexProcessState::Dormant),
             core_affinity: RwLock::new(None),
+            capabilities: RwLock::new(config.capabilities),
+            log_ring: Mutex::new(None),
+            rseq: Mutex::new(None),
+            upcall: Mutex::new(ProcessUpcall::default()),
+            strace: AtomicBool::new(config.strace),
+            exit: Once::new(),
         });
 
         partition.register_process(process.clone());
@@ -110,6 +198,8 @@ impl Process {
                 period: APEX_TIME_INFINITY,
                 stack_size: PAGE_SIZE as _,
                 time_capacity: APEX_TIME_INFINITY,
+                capabilities: partition.capabilities(),
+                strace: false,
             },
         )
     }
@@ -192,6 +282,17 @@ impl Process {
         *self.process_state.write() = state;
     }
 
+    /// Stops the process for good, once its runner is back from user mode.
+    ///
+    /// Only the first call counts, later ones are ignored.
+    pub fn exit(&self, exit: ProcessExit) {
+        self.exit.call_once(|| exit);
+    }
+
+    pub fn exit_status(&self) -> Option<ProcessExit> {
+        self.exit.get().copied()
+    }
+
     pub fn core_affinity(&self) -> Option<usize> {
         *self.core_affinity.read()
     }
@@ -200,6 +301,295 @@ impl Process {
         *self.core_affinity.write() = cpu_id;
     }
 
+    pub fn capabilities(&self) -> Capabilities {
+        *self.capabilities.read()
+    }
+
+    /// Drops `caps` from the process, returning whether it held any of them.
+    ///
+    /// There is deliberately no way back: once dropped, a capability can only be held again by
+    /// a process created from a configuration granting it.
+    pub fn drop_capabilities(&self, caps: Capabilities) -> bool {
+        let mut capabilities = self.capabilities.write();
+        let held = capabilities.intersects(caps);
+        capabilities.remove(caps);
+        held
+    }
+
+    /// Returns whether the system calls of the process are logged on entry and exit.
+    pub fn strace(&self) -> bool {
+        self.strace.load(Ordering::Relaxed)
+    }
+
+    pub fn set_strace(&self, enabled: bool) {
+        self.strace.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn setup_log_ring(&self, len: usize) -> Result<VirtAddr> {
+        let mut log_ring = self.log_ring.lock();
+        if log_ring.is_some() {
+            return Err(InternalError::RepeatInitialization);
+        }
+
+        let len = len.next_multiple_of(PAGE_SIZE);
+        if LogRing::capacity_of(len) == 0 {
+            return Err(InternalError::InvalidLength(len));
+        }
+
+        let shared = Inspector::with_current(|is| is.charge(ResourceKind::SharedBytes, len))
+            .ok()
+            .transpose()?;
+        let partition = Partition::find_by_id(self.partition_id).unwrap();
+        let top = partition.allocate_stack(len)?;
+        hal!().vm().sync_all();
+
+        let base = top - len;
+        // Safety:
+        //   The region is freshly mapped into the current address space and is only
+        //   released by `Drop for Process`, after which the ring is never touched again.
+        let ring = unsafe { LogRing::init(base.as_usize() as *mut u8, len) };
+        *log_ring = Some(ProcessLogRing {
+            top,
+            ring,
+            capacity: LogRing::capacity_of(len) as u32,
+            next_seq: 0,
+            _shared: shared,
+        });
+
+        Ok(base)
+    }
+
+    /// Drains the log ring of this process, which must be called within its address space.
+    ///
+    /// `on_dropped` is called with the number of records overwritten since the last drain
+    /// before the records following them are passed to `on_record`.
+    pub fn drain_log_ring(
+        &self,
+        mut on_record: impl FnMut(&[u8]),
+        mut on_dropped: impl FnMut(u32),
+    ) {
+        let mut log_ring = self.log_ring.lock();
+        let Some(log_ring) = log_ring.as_mut() else {
+            return;
+        };
+
+        let mut buf = [0u8; LOG_RING_PAYLOAD_MAX];
+        while let Some((seq, len)) = log_ring.ring.pop(log_ring.capacity, &mut buf) {
+            let dropped = seq.wrapping_sub(log_ring.next_seq);
+            if dropped != 0 {
+                on_dropped(dropped);
+            }
+            log_ring.next_seq = seq.wrapping_add(1);
+            on_record(&buf[..len]);
+        }
+
+        let dropped = log_ring.ring.drained().wrapping_sub(log_ring.next_seq);
+        if dropped != 0 {
+            on_dropped(dropped);
+            log_ring.next_seq = log_ring.ring.drained();
+        }
+    }
+
+    pub fn rseq_register(&self, area: VirtAddr) -> Result<()> {
+        let mut rseq = self.rseq.lock();
+        if rseq.is_some() {
+            return Err(InternalError::RepeatInitialization);
+        }
+        if area.as_usize() % core::mem::align_of::<Rseq>() != 0 {
+            return Err(InternalError::InvalidVirtAddr(area));
+        }
+        uptr_try_cast::<Rseq>(area.as_usize())?;
+
+        *rseq = Some(ProcessRseq {
+            area,
+            cpu_id: None,
+            restarts: 0,
+        });
+        Ok(())
+    }
+
+    /// Returns how many critical sections were restarted, if an rseq area is registered.
+    pub fn rseq_restarts(&self) -> Option<usize> {
+        self.rseq.lock().as_ref().map(|rseq| rseq.restarts)
+    }
+
+    /// Brings the rseq area up to date before returning to user mode at `ctx`, which must be
+    /// called within the address space of this process.
+    ///
+    /// Nothing is done unless the process was preempted or runs on another CPU since its last
+    /// return to user mode, so system calls issued outside critical sections stay cheap.
+    fn rseq_resume(&self, ctx: &mut Context, preempted: bool) {
+        let mut rseq = self.rseq.lock();
+        let Some(rseq) = rseq.as_mut() else {
+            return;
+        };
+
+        let cpu_id = hal!().cpu().id();
+        if !preempted && rseq.cpu_id == Some(cpu_id) {
+            return;
+        }
+
+        // Both the area and the descriptor are only in user memory, which may have been unmapped
+        // since, so they are checked against the page table before every access.
+        let Some(partition) = Partition::find_by_id(self.partition_id) else {
+            return;
+        };
+        let Some(area) = UserPtr::<Rseq>::new(rseq.area.as_usize()).ok().filter(|_| {
+            partition.check_user_access(
+                rseq.area,
+                core::mem::size_of::<Rseq>(),
+                PagePerm::R | PagePerm::W,
+            )
+        }) else {
+            warn!("rseq area at {} is no longer accessible", rseq.area);
+            return;
+        };
+        let area = area.as_ref();
+        let cs = area.rseq_cs();
+        if cs != 0 {
+            match UserPtr::<RseqCs>::new(cs).ok().filter(|_| {
+                partition.check_user_access(
+                    VirtAddr::new(cs),
+                    core::mem::size_of::<RseqCs>(),
+                    PagePerm::R,
+                )
+            }) {
+                Some(cs) if cs.as_ref().contains(ctx.pc()) => {
+                    ctx.set_pc(cs.as_ref().abort_ip);
+                    rseq.restarts += 1;
+                }
+                Some(_) => {}
+                None => warn!("invalid rseq critical section descriptor at {:#x}", cs),
+            }
+            area.clear_rseq_cs();
+        }
+        area.set_cpu_id(cpu_id);
+        rseq.cpu_id = Some(cpu_id);
+    }
+
+    /// Sets the handler of the process to run at `entry` on `stack_top` upon the traps in
+    /// `mask`, or unsets it if `mask` is empty.
+    pub fn trap_handler_set(
+        &self,
+        mask: TrapMask,
+        entry: VirtAddr,
+        stack_top: VirtAddr,
+    ) -> Result<()> {
+        if !mask.is_empty() {
+            uptr_try_cast::<u8>(entry.as_usize())?;
+            uptr_try_cast::<usize>(
+                stack_top
+                    .as_usize()
+                    .saturating_sub(core::mem::size_of::<usize>()),
+            )?;
+        }
+
+        let mut upcall = self.upcall.lock();
+        upcall.mask = mask;
+        upcall.entry = entry.as_usize();
+        upcall.stack_top = stack_top.as_usize();
+        if !mask.contains(TrapMask::SIGNAL) {
+            upcall.signals = 0;
+        }
+        Ok(())
+    }
+
+    /// Makes the process resume the context its handler interrupted, once back from the
+    /// current system call, returning whether a handler is running at all.
+    pub fn trap_return(&self) -> bool {
+        let mut upcall = self.upcall.lock();
+        upcall.returning = upcall.saved.is_some();
+        upcall.returning
+    }
+
+    /// Sends `signal` to the process, returning whether its handler takes signals.
+    ///
+    /// The signal is taken the next time the process returns to user mode outside of its
+    /// handler, and sending it again before that has no further effect.
+    pub fn trap_signal(&self, signal: usize) -> bool {
+        let mut upcall = self.upcall.lock();
+        if signal >= TRAP_SIGNAL_MAX || !upcall.mask.contains(TrapMask::SIGNAL) {
+            return false;
+        }
+        upcall.signals |= 1 << signal;
+        if let Some(sleeper) = upcall.sleeper.take() {
+            sleeper.wake();
+        }
+        true
+    }
+
+    /// Sleeps for `duration` without holding the CPU, returning the time left if a signal sent
+    /// to the process cut the sleep short, or zero.
+    ///
+    /// A signal cuts the sleep short only if the handler of the process is not running, as
+    /// it is otherwise not taken until the handler returns.
+    pub async fn sleep(&self, duration: Duration) -> Duration {
+        let mut sleep = pin!(jrinx_multitask::time::sleep(duration));
+        let deadline = sleep.deadline();
+
+        self.set_process_state(ApexProcessState::Waiting);
+        let remaining = poll_fn(|cx| {
+            if sleep.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Duration::ZERO);
+            }
+            let mut upcall = self.upcall.lock();
+            if upcall.saved.is_none() && upcall.signals != 0 {
+                upcall.sleeper = None;
+                return Poll::Ready(deadline.saturating_sub(hal!().cpu().get_time()));
+            }
+            upcall.sleeper = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await;
+        self.upcall.lock().sleeper = None;
+        self.set_process_state(ApexProcessState::Ready);
+
+        remaining
+    }
+
+    /// Enters the handler of the process upon a trap from user mode at `ctx`, returning whether
+    /// it takes the trap.
+    ///
+    /// A trap while the handler runs is not taken, so that the process is terminated.
+    fn upcall(&self, ctx: &mut Context, reason: TrapReason) -> bool {
+        let (trap, addr) = match reason {
+            TrapReason::Breakpoint { addr } => (TrapMask::BREAKPOINT, addr),
+            TrapReason::PageFault { addr, .. } => (TrapMask::PAGE_FAULT, addr),
+            _ => return false,
+        };
+
+        let mut upcall = self.upcall.lock();
+        if !upcall.mask.contains(trap) {
+            return false;
+        }
+        if upcall.saved.is_some() {
+            warn!("process {:?} traps in its trap handler", self.name);
+            return false;
+        }
+
+        upcall.enter(ctx, trap, addr.as_usize());
+        if trap == TrapMask::BREAKPOINT {
+            upcall.saved.as_mut().unwrap().pc_advance();
+        }
+        true
+    }
+
+    /// Resumes the context interrupted by the handler if it returned, then enters the handler
+    /// upon the lowest pending signal if none runs, before returning to user mode at `ctx`.
+    fn upcall_resume(&self, ctx: &mut Context) {
+        let mut upcall = self.upcall.lock();
+        if upcall.returning {
+            upcall.returning = false;
+            *ctx = upcall.saved.take().unwrap();
+        }
+
+        if upcall.saved.is_none() && upcall.signals != 0 {
+            let signal = upcall.signals.trailing_zeros() as usize;
+            upcall.signals &= !(1 << signal);
+            upcall.enter(ctx, TrapMask::SIGNAL, signal);
+        }
+    }
+
     pub fn status(&self) -> ApexProcessStatus {
         ApexProcessStatus {
             attributes: ApexProcessAttribute {
@@ -230,7 +620,7 @@ impl Process {
     ) -> Result<Pin<Box<Executor>>>
     where
         H: Fn(usize, [usize; 7]) -> F + Send + Sync + 'static,
-        F: Future<Output = Result<usize>> + Send + 'static,
+        F: Future<Output = usize> + Send + 'static,
     {
         Ok(Executor::new_with_ext(
             ExecutorPriority::new(
@@ -247,6 +637,9 @@ impl Process {
 impl Drop for Process {
     fn drop(&mut self) {
         if let Some(partition) = Partition::find_by_id(self.partition_id) {
+            if let Some(log_ring) = self.log_ring.get_mut().take() {
+                partition.deallocate_stack(log_ring.top).unwrap();
+            }
             partition.deallocate_stack(self.stack_top).unwrap();
             hal!().vm().sync_all();
         }
@@ -256,7 +649,7 @@ impl Drop for Process {
 pub struct ProcessRunner<H, F>
 where
     H: Fn(usize, [usize; 7]) -> F,
-    F: Future<Output = Result<usize>>,
+    F: Future<Output = usize>,
 {
     pub syscall: H,
 }
@@ -264,7 +657,7 @@ where
 impl<H, F> ProcessRunner<H, F>
 where
     H: Fn(usize, [usize; 7]) -> F,
-    F: Future<Output = Result<usize>>,
+    F: Future<Output = usize>,
 {
     pub async fn run(self, process: Arc<Process>) {
         debug!("run process: {:?}", process.name());
@@ -291,11 +684,14 @@ where
         let mut ctx = Context::default();
         ctx.user_setup(entry, process.stack_top().as_usize());
 
-        loop {
+        let mut preempted = false;
+        let exit = loop {
             Partition::find_by_id(process.partition_id())
                 .unwrap()
                 .pt_sync();
 
+            process.upcall_resume(&mut ctx);
+            process.rseq_resume(&mut ctx, preempted);
             ctx.run();
             trace!("process trap: {:?}", ctx.trap_reason());
 
@@ -303,21 +699,64 @@ where
                 .unwrap()
                 .pt_sync();
 
-            self.user_handle_trap(&mut ctx).await;
+            preempted = self.user_handle_trap(&process, &mut ctx).await;
+            if let Some(exit) = process.exit_status() {
+                break exit;
+            }
+        };
+
+        hal!().vm().enable(KERN_PAGE_TABLE.read().addr());
+        hal!().vm().sync_all();
+
+        process.set_process_state(ApexProcessState::Dormant);
+        match exit {
+            ProcessExit::Exited(0) => debug!("process {:?} {}", process.name(), exit),
+            _ => warn!("process {:?} {}", process.name(), exit),
         }
     }
-    async fn user_handle_trap(&self, ctx: &mut Context) {
+
+    /// Handles a trap from user mode, returning whether it preempted the process.
+    ///
+    /// A trap that cannot be handled makes the process exit as faulted. Handlers which run
+    /// before the process resumes count in the trap depth, unlike system calls, which may switch
+    /// tasks while awaited.
+    async fn user_handle_trap(&self, process: &Process, ctx: &mut Context) -> bool {
         let reason = ctx.trap_reason();
         match reason {
+            reason if nest::trap(|| jrinx_trap::handler::dispatch(ctx, reason)) => {
+                TrapKind::from(reason).is_interrupt()
+            }
             jrinx_trap::TrapReason::SystemCall => {
-                ctx.syscall_ret(
-                    (self.syscall)(ctx.syscall_num(), ctx.syscall_args())
-                        .await
-                        .unwrap(), // TODO: handle error
-                );
+                ctx.syscall_ret((self.syscall)(ctx.syscall_num(), ctx.syscall_args()).await);
                 ctx.pc_advance();
+                false
+            }
+            jrinx_trap::TrapReason::TimerInterrupt
+            | jrinx_trap::TrapReason::SoftwareInterrupt
+            | jrinx_trap::TrapReason::ExternalInterrupt => {
+                jrinx_trap::handle_user_int(ctx);
+                true
+            }
+            jrinx_trap::TrapReason::PageFault { .. }
+                if nest::trap(|| jrinx_trap::page_fault::handle(ctx)) =>
+            {
+                false
+            }
+            jrinx_trap::TrapReason::IllegalInstruction { .. }
+                if nest::trap(|| jrinx_trap::fp::handle_first_use(ctx)) =>
+            {
+                false
+            }
+            reason if process.upcall(ctx, reason) => false,
+            reason => {
+                jrinx_trap::fault::report(ctx);
+                debug!("process {:?} faulted: {:#x?}", process.name(), ctx);
+                process.exit(ProcessExit::Faulted {
+                    reason,
+                    pc: ctx.pc(),
+                });
+                false
             }
-            _ => unimplemented!("{:#x?}", ctx),
         }
     }
 }
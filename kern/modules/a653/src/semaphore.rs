@@ -0,0 +1,381 @@
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use core::{
+    future::poll_fn,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Poll, Waker},
+    time::Duration,
+};
+
+use jrinx_apex::*;
+use jrinx_error::{InternalError, ResourceKind, Result};
+use jrinx_hal::{Cpu, Hal};
+use jrinx_multitask::inspector::{Inspector, ResourceCharge};
+use jrinx_serial_id_macro::SerialId;
+use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+use spin::Mutex;
+
+use crate::{
+    partition::{Partition, PartitionId},
+    process::Process,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SerialId)]
+pub struct SemaphoreId(ApexSemaphoreId);
+
+/// Protocol bounding the priority inversion on a semaphore.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SemaphoreProtocol {
+    /// The holders run at the highest priority among the waiting processes.
+    #[default]
+    Inheritance,
+    /// The holders run at the priority ceiling of the semaphore as soon as they acquire it.
+    Ceiling,
+}
+
+impl SemaphoreProtocol {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "inherit" => Some(Self::Inheritance),
+            "ceiling" => Some(Self::Ceiling),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Inheritance => "inherit",
+            Self::Ceiling => "ceiling",
+        }
+    }
+}
+
+/// Configuration of a semaphore, applied when a process of the partition creates it by name.
+#[derive(Debug, Clone, Copy)]
+pub struct SemaphoreConfig {
+    pub name: ApexSemaphoreName,
+    pub ceiling: ApexPriority,
+    pub protocol: SemaphoreProtocol,
+}
+
+pub struct Semaphore {
+    identifier: SemaphoreId,
+    name: ApexSemaphoreName,
+    partition_id: PartitionId,
+    maximum: ApexSemaphoreValue,
+    discipline: ApexQueueDiscipline,
+    ceiling: Option<ApexPriority>,
+    protocol: SemaphoreProtocol,
+    inner: Mutex<SemaphoreInner>,
+    /// Descriptor charged to the inspector of the creating process.
+    _descriptor: Option<ResourceCharge>,
+}
+
+struct SemaphoreInner {
+    value: ApexSemaphoreValue,
+    holders: Vec<Arc<Process>>,
+    waiters: VecDeque<SemaphoreWaiter>,
+}
+
+struct SemaphoreWaiter {
+    process: Arc<Process>,
+    ticket: Arc<WaitTicket>,
+}
+
+#[derive(Default)]
+struct WaitTicket {
+    granted: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl From<SemaphoreId> for ApexSemaphoreId {
+    fn from(id: SemaphoreId) -> Self {
+        id.0
+    }
+}
+
+impl From<ApexSemaphoreId> for SemaphoreId {
+    fn from(value: ApexSemaphoreId) -> Self {
+        Self(value)
+    }
+}
+
+impl Semaphore {
+    /// Creates a semaphore in the partition, with the ceiling and protocol configured for
+    /// `name` if any. Semaphores left out of the configuration have no ceiling.
+    ///
+    /// Fails with [`InternalError::ResourceLimitExceeded`] if the inspector of the creating
+    /// process holds too many descriptors.
+    pub fn new(
+        partition_id: PartitionId,
+        name: ApexSemaphoreName,
+        value: ApexSemaphoreValue,
+        maximum: ApexSemaphoreValue,
+        discipline: ApexQueueDiscipline,
+    ) -> Result<Arc<Self>> {
+        let partition = Partition::find_by_id(partition_id).unwrap();
+        let config = partition.semaphore_config(&name);
+        let descriptor = Inspector::with_current(|is| is.charge(ResourceKind::Descriptors, 1))
+            .ok()
+            .transpose()?;
+
+        let semaphore = Arc::new(Self {
+            identifier: SemaphoreId::new(),
+            name,
+            partition_id,
+            maximum,
+            discipline,
+            ceiling: config.map(|config| config.ceiling),
+            protocol: config.map(|config| config.protocol).unwrap_or_default(),
+            inner: Mutex::new(SemaphoreInner {
+                value,
+                holders: Vec::new(),
+                waiters: VecDeque::new(),
+            }),
+            _descriptor: descriptor,
+        });
+
+        partition.register_semaphore(semaphore.clone());
+
+        Ok(semaphore)
+    }
+
+    pub fn find_by_id(partition_id: PartitionId, identifier: SemaphoreId) -> Option<Arc<Self>> {
+        Partition::find_by_id(partition_id)
+            .and_then(|partition| partition.find_semaphore_by_id(identifier))
+    }
+
+    pub fn find_by_name(partition_id: PartitionId, name: &ApexSemaphoreName) -> Option<Arc<Self>> {
+        Partition::find_by_id(partition_id)
+            .and_then(|partition| partition.find_semaphore_by_name(name))
+    }
+
+    pub fn identifier(&self) -> SemaphoreId {
+        self.identifier
+    }
+
+    pub fn name(&self) -> ApexSemaphoreName {
+        self.name
+    }
+
+    pub fn partition_id(&self) -> PartitionId {
+        self.partition_id
+    }
+
+    pub fn ceiling(&self) -> Option<ApexPriority> {
+        self.ceiling
+    }
+
+    pub fn protocol(&self) -> SemaphoreProtocol {
+        self.protocol
+    }
+
+    pub fn status(&self) -> ApexSemaphoreStatus {
+        let inner = self.inner.lock();
+        ApexSemaphoreStatus {
+            current_value: inner.value,
+            maximum_value: self.maximum,
+            waiting_processes: inner.waiters.len() as _,
+        }
+    }
+
+    /// Acquires the semaphore for `process`, waiting up to `timeout` for it to be signaled,
+    /// where [`Duration::MAX`] waits forever. Returns whether it was acquired.
+    ///
+    /// A process whose current priority is above the ceiling of the semaphore is refused with
+    /// [`InternalError::InvalidApexPriority`], since the ceiling would no longer bound the
+    /// priority of the holders.
+    pub async fn wait(&self, process: &Arc<Process>, timeout: Duration) -> Result<bool> {
+        if self
+            .ceiling
+            .is_some_and(|ceiling| process.curr_priority() > ceiling)
+        {
+            return Err(InternalError::InvalidApexPriority);
+        }
+
+        let ticket = {
+            let mut inner = self.inner.lock();
+            if inner.value > 0 {
+                inner.value -= 1;
+                inner.holders.push(process.clone());
+                None
+            } else if timeout.is_zero() {
+                return Ok(false);
+            } else {
+                let ticket = Arc::new(WaitTicket::default());
+                let position = match self.discipline {
+                    ApexQueueDiscipline::Fifo => inner.waiters.len(),
+                    ApexQueueDiscipline::Priority => {
+                        let priority = process.curr_priority();
+                        inner
+                            .waiters
+                            .partition_point(|waiter| waiter.process.curr_priority() >= priority)
+                    }
+                };
+                inner.waiters.insert(
+                    position,
+                    SemaphoreWaiter {
+                        process: process.clone(),
+                        ticket: ticket.clone(),
+                    },
+                );
+                Some(ticket)
+            }
+        };
+
+        let Some(ticket) = ticket else {
+            reprioritize(process);
+            return Ok(true);
+        };
+        self.reprioritize_holders();
+        let pending = PendingWait {
+            semaphore: self,
+            ticket,
+        };
+
+        process.set_process_state(ApexProcessState::Waiting);
+        let deadline = hal!().cpu().get_time().saturating_add(timeout);
+        let timer = (timeout != Duration::MAX).then(|| {
+            let ticket = pending.ticket.clone();
+            TimedEvent::create(
+                deadline,
+                TimedEventHandler::new(move || ticket.wake(), || {}),
+            )
+        });
+
+        let granted = poll_fn(|cx| {
+            *pending.ticket.waker.lock() = Some(cx.waker().clone());
+            if pending.ticket.granted.load(Ordering::SeqCst) {
+                Poll::Ready(true)
+            } else if hal!().cpu().get_time() >= deadline {
+                Poll::Ready(false)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        process.set_process_state(ApexProcessState::Ready);
+
+        if let Some(timer) = timer.filter(|timer| !timer.retired()) {
+            let _ = timer.cancel();
+        }
+
+        // Signaled between the timeout and the withdrawal if there is nothing to withdraw.
+        Ok(granted || !pending.withdraw())
+    }
+
+    /// Signals the semaphore from `process`, handing it over to the first waiting process if
+    /// any. Returns `false` if the semaphore is already at its maximum value.
+    pub fn signal(&self, process: &Arc<Process>) -> bool {
+        let (released, granted) = {
+            let mut inner = self.inner.lock();
+            if inner.waiters.is_empty() && inner.value >= self.maximum {
+                return false;
+            }
+
+            let released = inner
+                .holders
+                .iter()
+                .position(|holder| Arc::ptr_eq(holder, process))
+                .map(|position| inner.holders.swap_remove(position));
+            let granted = inner.waiters.pop_front();
+            match &granted {
+                Some(waiter) => inner.holders.push(waiter.process.clone()),
+                None => inner.value += 1,
+            }
+            (released, granted)
+        };
+
+        if let Some(process) = released {
+            reprioritize(&process);
+        }
+        if let Some(waiter) = granted {
+            reprioritize(&waiter.process);
+            waiter.ticket.granted.store(true, Ordering::SeqCst);
+            waiter.ticket.wake();
+        }
+        true
+    }
+
+    /// Returns the priority `process` is raised to by holding this semaphore, if it does.
+    fn boost(&self, process: &Arc<Process>) -> Option<ApexPriority> {
+        let inner = self.inner.lock();
+        if !inner
+            .holders
+            .iter()
+            .any(|holder| Arc::ptr_eq(holder, process))
+        {
+            return None;
+        }
+        match self.protocol {
+            SemaphoreProtocol::Ceiling => self.ceiling,
+            SemaphoreProtocol::Inheritance => inner
+                .waiters
+                .iter()
+                .map(|waiter| waiter.process.curr_priority())
+                .max(),
+        }
+    }
+
+    fn reprioritize_holders(&self) {
+        let holders = self.inner.lock().holders.clone();
+        for holder in holders {
+            reprioritize(&holder);
+        }
+    }
+}
+
+/// Recomputes the current priority of `process` from its base priority and the boosts of
+/// the semaphores it holds, taking the lock of one semaphore at a time.
+fn reprioritize(process: &Arc<Process>) {
+    let Some(partition) = Partition::find_by_id(process.partition_id()) else {
+        return;
+    };
+    let priority = partition
+        .semaphores()
+        .iter()
+        .filter_map(|semaphore| semaphore.boost(process))
+        .fold(process.base_priority(), ApexPriority::max);
+    process.set_curr_priority(priority);
+}
+
+/// A waiter queued on a semaphore, withdrawn if the wait is abandoned before being granted.
+struct PendingWait<'a> {
+    semaphore: &'a Semaphore,
+    ticket: Arc<WaitTicket>,
+}
+
+impl PendingWait<'_> {
+    /// Removes the waiter from the queue, returning whether it was still queued.
+    fn withdraw(&self) -> bool {
+        let withdrawn = {
+            let mut inner = self.semaphore.inner.lock();
+            let position = inner
+                .waiters
+                .iter()
+                .position(|waiter| Arc::ptr_eq(&waiter.ticket, &self.ticket));
+            position
+                .map(|position| inner.waiters.remove(position))
+                .is_some()
+        };
+        if withdrawn {
+            self.semaphore.reprioritize_holders();
+        }
+        withdrawn
+    }
+}
+
+impl Drop for PendingWait<'_> {
+    fn drop(&mut self) {
+        if !self.ticket.granted.load(Ordering::SeqCst) {
+            self.withdraw();
+        }
+    }
+}
+
+impl WaitTicket {
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use core::{
+    marker::PhantomData,
+    mem::{align_of, size_of},
+};
+
+use jrinx_addr::VirtAddr;
+use jrinx_error::{InternalError, Result};
+
+pub fn uptr_try_cast<'a, T>(ptr: usize) -> Result<&'a mut T> {
+    if ptr >= usize::MAX / 2 || ptr + core::mem::size_of::<T>() > usize::MAX / 2 {
+        return Err(InternalError::InvalidVirtAddr(VirtAddr::new(ptr)));
+    }
+    if ptr == 0 {
+        return Err(InternalError::InvalidVirtAddr(VirtAddr::new(ptr)));
+    }
+    Ok(unsafe { &mut *(ptr as *mut T) })
+}
+
+pub fn uptr_try_cast_array<'a, T>(ptr: usize, len: usize) -> Result<&'a mut [T]> {
+    if ptr >= usize::MAX / 2 || ptr + len * core::mem::size_of::<T>() > usize::MAX / 2 {
+        return Err(InternalError::InvalidVirtAddr(VirtAddr::new(ptr)));
+    }
+    if ptr == 0 {
+        return Err(InternalError::InvalidVirtAddr(VirtAddr::new(ptr)));
+    }
+    Ok(unsafe { core::slice::from_raw_parts_mut(ptr as *mut T, len) })
+}
+
+/// Returns whether `addr .. addr + len` lies in the address space of user programs, off the
+/// regions the kernel maps into every address space.
+fn is_user_range(addr: usize, len: usize) -> bool {
+    let Some(end) = addr.checked_add(len) else {
+        return false;
+    };
+    addr != 0
+        && end <= usize::MAX / 2
+        && jrinx_config::REMAP_MEM_REGIONS
+            .iter()
+            .all(|region| end <= region.virt_addr || addr >= region.virt_addr + region.len)
+}
+
+/// A pointer passed in by a user program, to a `T` lying in its address space.
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> UserPtr<T> {
+    pub fn new(addr: usize) -> Result<Self> {
+        if addr % align_of::<T>() != 0 || !is_user_range(addr, size_of::<T>()) {
+            return Err(InternalError::InvalidVirtAddr(VirtAddr::new(addr)));
+        }
+        Ok(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    pub fn as_ref(&self) -> &T {
+        unsafe { &*(self.addr as *const T) }
+    }
+
+    pub fn as_mut(&mut self) -> &mut T {
+        unsafe { &mut *(self.addr as *mut T) }
+    }
+
+    pub fn write(&mut self, value: T) {
+        unsafe { (self.addr as *mut T).write(value) }
+    }
+}
+
+/// A pointer and a length passed in by a user program, to `len` of `T` lying in its address
+/// space.
+pub struct UserSlice<T> {
+    addr: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> UserSlice<T> {
+    pub fn new(addr: usize, len: usize) -> Result<Self> {
+        let size = len
+            .checked_mul(size_of::<T>())
+            .ok_or(InternalError::InvalidVirtAddr(VirtAddr::new(addr)))?;
+        if addr % align_of::<T>() != 0 || !is_user_range(addr, size) {
+            return Err(InternalError::InvalidVirtAddr(VirtAddr::new(addr)));
+        }
+        Ok(Self {
+            addr,
+            len,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.addr as *const T, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { core::slice::from_raw_parts_mut(self.addr as *mut T, self.len) }
+    }
+}
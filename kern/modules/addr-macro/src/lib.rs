@@ -1,5 +1,5 @@
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::{parse_macro_input, Data, DataStruct, Fields};
 
 const NOTE: &str = "Address can only be derived for tuple structs with one field";
@@ -15,6 +15,7 @@ pub fn address(item: TokenStream) -> TokenStream {
             ..
         }) if f.unnamed.len() == 1 => {
             let ty = &f.unnamed.first().unwrap().ty;
+            let range = format_ident!("{}Range", name);
             Ok(quote! {
                 impl core::ops::Add<#ty> for #name {
                     type Output = Self;
@@ -62,6 +63,145 @@ pub fn address(item: TokenStream) -> TokenStream {
                     pub fn align_page_up(self) -> Self {
                         Self((self.0 + jrinx_config::PAGE_SIZE - 1) & !(jrinx_config::PAGE_SIZE - 1))
                     }
+
+                    /// Returns `self + rhs`, or `None` past the top of the address space.
+                    pub const fn checked_add(self, rhs: #ty) -> Option<Self> {
+                        match self.0.checked_add(rhs) {
+                            Some(addr) => Some(Self(addr)),
+                            None => None,
+                        }
+                    }
+
+                    /// Returns `self - rhs`, or `None` below zero.
+                    pub const fn checked_sub(self, rhs: #ty) -> Option<Self> {
+                        match self.0.checked_sub(rhs) {
+                            Some(addr) => Some(Self(addr)),
+                            None => None,
+                        }
+                    }
+
+                    /// Rounds the address down to a multiple of `to`, a power of two.
+                    pub const fn align_down(self, to: #ty) -> Self {
+                        assert!(to.is_power_of_two(), "alignment is not a power of two");
+                        Self(self.0 & !(to - 1))
+                    }
+
+                    /// Rounds the address up to a multiple of `to`, a power of two, or returns
+                    /// `None` if there is none below the top of the address space.
+                    pub const fn align_up(self, to: #ty) -> Option<Self> {
+                        assert!(to.is_power_of_two(), "alignment is not a power of two");
+                        match self.0.checked_add(to - 1) {
+                            Some(addr) => Some(Self(addr & !(to - 1))),
+                            None => None,
+                        }
+                    }
+
+                    /// Returns whether the address is a multiple of `to`, a power of two.
+                    pub const fn is_aligned(self, to: #ty) -> bool {
+                        assert!(to.is_power_of_two(), "alignment is not a power of two");
+                        self.0 & (to - 1) == 0
+                    }
+
+                    pub const fn page_number(self) -> #ty {
+                        self.0 / jrinx_config::PAGE_SIZE
+                    }
+
+                    pub const fn page_offset(self) -> #ty {
+                        self.0 & (jrinx_config::PAGE_SIZE - 1)
+                    }
+                }
+
+                /// Half-open range of addresses, empty unless its start is below its end, as
+                /// for [`Range`](core::ops::Range).
+                #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                pub struct #range {
+                    start: #name,
+                    end: #name,
+                }
+
+                impl #range {
+                    pub const fn new(start: #name, end: #name) -> Self {
+                        Self { start, end }
+                    }
+
+                    /// Returns the range of the `len` addresses from `start`, or `None` if it
+                    /// passes the top of the address space.
+                    pub const fn from_len(start: #name, len: #ty) -> Option<Self> {
+                        match start.checked_add(len) {
+                            Some(end) => Some(Self { start, end }),
+                            None => None,
+                        }
+                    }
+
+                    pub const fn start(self) -> #name {
+                        self.start
+                    }
+
+                    pub const fn end(self) -> #name {
+                        self.end
+                    }
+
+                    pub const fn len(self) -> #ty {
+                        if self.is_empty() {
+                            0
+                        } else {
+                            self.end.0 - self.start.0
+                        }
+                    }
+
+                    pub const fn is_empty(self) -> bool {
+                        self.start.0 >= self.end.0
+                    }
+
+                    pub const fn contains(self, addr: #name) -> bool {
+                        self.start.0 <= addr.0 && addr.0 < self.end.0
+                    }
+
+                    /// Returns whether the ranges share any address.
+                    pub const fn overlaps(self, other: Self) -> bool {
+                        self.intersection(other).is_some()
+                    }
+
+                    /// Returns the addresses the ranges share, or `None` if they share none.
+                    pub const fn intersection(self, other: Self) -> Option<Self> {
+                        let start = if self.start.0 > other.start.0 { self.start } else { other.start };
+                        let end = if self.end.0 < other.end.0 { self.end } else { other.end };
+                        if start.0 < end.0 {
+                            Some(Self { start, end })
+                        } else {
+                            None
+                        }
+                    }
+
+                    /// Iterates over the pages of `page_size`, a power of two, that the range
+                    /// lies in, by the address of each.
+                    pub fn iter_pages(self, page_size: #ty) -> impl Iterator<Item = #name> {
+                        assert!(page_size.is_power_of_two(), "page size is not a power of two");
+                        let first = if self.is_empty() {
+                            self.end.0
+                        } else {
+                            self.start.0 & !(page_size - 1)
+                        };
+                        (first..self.end.0).step_by(page_size).map(#name)
+                    }
+                }
+
+                impl core::fmt::Display for #range {
+                    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                        write!(f, "[{}, {})", self.start, self.end)
+                    }
+                }
+
+                impl From<core::ops::Range<#name>> for #range {
+                    fn from(range: core::ops::Range<#name>) -> Self {
+                        Self::new(range.start, range.end)
+                    }
+                }
+
+                impl From<#range> for core::ops::Range<#name> {
+                    fn from(range: #range) -> Self {
+                        range.start..range.end
+                    }
                 }
             })
         }
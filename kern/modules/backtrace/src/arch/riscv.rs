@@ -0,0 +1,22 @@
+use core::mem::size_of;
+
+/// Bytes below a frame pointer holding the frame record.
+pub const FRAME_RECORD_SIZE: usize = 2 * size_of::<usize>();
+
+/// Returns the frame pointer of the calling function.
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp;
+    unsafe { core::arch::asm!("mv {}, s0", out(reg) fp) };
+    fp
+}
+
+/// Reads the return address and caller frame pointer below `fp`.
+///
+/// # Safety
+///
+/// The [`FRAME_RECORD_SIZE`] bytes below `fp` must be readable.
+pub unsafe fn frame_record(fp: usize) -> (usize, usize) {
+    let record = (fp - FRAME_RECORD_SIZE) as *const usize;
+    (record.add(1).read(), record.read())
+}
@@ -0,0 +1,69 @@
+//! Symbol table of the kernel, written into the `.ksyms` section by the build after linking.
+//!
+//! Layout: `KSYM`, the count as `u32`, entries sorted by address as `u64` address, `u32` name
+//! offset and `u32` name length, then the names. All little-endian.
+
+/// Bytes reserved for the symbol table.
+pub const KSYMS_SIZE: usize = 1024 * 1024;
+
+const MAGIC: &[u8; 4] = b"KSYM";
+
+const HEADER_SIZE: usize = 8;
+
+const ENTRY_SIZE: usize = 16;
+
+#[used(linker)]
+#[link_section = ".ksyms"]
+static KSYMS: [u8; KSYMS_SIZE] = [0; KSYMS_SIZE];
+
+/// Function of the kernel, starting at `addr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: &'static str,
+    pub addr: usize,
+}
+
+fn table() -> Option<&'static [u8]> {
+    let start = jrinx_layout::_sksyms();
+    let table =
+        unsafe { core::slice::from_raw_parts(start as *const u8, jrinx_layout::_eksyms() - start) };
+    (table.get(..MAGIC.len())? == MAGIC).then_some(table)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Returns the function holding `addr`, if any.
+pub fn resolve(addr: usize) -> Option<Symbol> {
+    if !(jrinx_layout::_stext()..jrinx_layout::_etext()).contains(&addr) {
+        return None;
+    }
+    let table = table()?;
+    let count = read_u32(table, MAGIC.len())? as usize;
+    let entries = table.get(HEADER_SIZE..HEADER_SIZE + count * ENTRY_SIZE)?;
+    let names = &table[HEADER_SIZE + count * ENTRY_SIZE..];
+    let entry_addr = |index: usize| read_u64(entries, index * ENTRY_SIZE).map(|addr| addr as usize);
+
+    // Find the last entry at or below `addr`.
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if entry_addr(mid)? <= addr {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    let index = low.checked_sub(1)?;
+    let offset = read_u32(entries, index * ENTRY_SIZE + 8)? as usize;
+    let len = read_u32(entries, index * ENTRY_SIZE + 12)? as usize;
+    Some(Symbol {
+        name: core::str::from_utf8(names.get(offset..offset + len)?).ok()?,
+        addr: entry_addr(index)?,
+    })
+}
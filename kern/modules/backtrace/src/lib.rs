@@ -0,0 +1,150 @@
+//! Backtraces of kernel code, walking frame records checked against their stack bounds.
+
+#![no_std]
+#![feature(used_with_arg)]
+
+#[macro_use]
+extern crate log;
+
+mod arch;
+pub mod ksyms;
+
+use core::{
+    fmt::{self, Display},
+    ops::Range,
+};
+
+use jrinx_addr::VirtAddr;
+use spin::Once;
+
+pub use arch::frame_pointer;
+
+/// Frames walked at most.
+pub const MAX_FRAMES: usize = 32;
+
+static STACK_OF: Once<fn(addr: VirtAddr) -> Option<Range<VirtAddr>>> = Once::new();
+
+/// Registers `stack_of` to tell the bounds of kernel stacks besides the boot stack.
+pub fn register(stack_of: fn(addr: VirtAddr) -> Option<Range<VirtAddr>>) {
+    STACK_OF.call_once(|| stack_of);
+}
+
+fn stack_of(addr: usize) -> Option<Range<usize>> {
+    let boot_stack = jrinx_layout::_sstack()..jrinx_layout::_estack();
+    if boot_stack.contains(&addr) {
+        return Some(boot_stack);
+    }
+    STACK_OF
+        .get()
+        .and_then(|stack_of| stack_of(VirtAddr::new(addr)))
+        .map(|stack| stack.start.as_usize()..stack.end.as_usize())
+}
+
+/// How a walk of frames ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkEnd {
+    /// The outermost frame was reached.
+    Outermost,
+    /// [`MAX_FRAMES`] frames were walked.
+    Truncated,
+    /// The frame pointer lies on no known stack.
+    UnknownStack(usize),
+    /// The frame pointer is misaligned or does not move outwards.
+    BadFramePointer(usize),
+}
+
+/// Return addresses of the frames walked from a frame pointer, innermost first.
+#[derive(Debug, Clone)]
+pub struct Backtrace {
+    addrs: [usize; MAX_FRAMES],
+    len: usize,
+    end: WalkEnd,
+}
+
+impl Backtrace {
+    /// Walks the frames of the caller.
+    #[inline(always)]
+    pub fn capture() -> Self {
+        Self::walk(frame_pointer())
+    }
+
+    /// Walks the frames from the one whose frame pointer is `fp`.
+    pub fn walk(mut fp: usize) -> Self {
+        let mut addrs = [0; MAX_FRAMES];
+        let mut len = 0;
+        let end = match stack_of(fp.wrapping_sub(1)) {
+            None if fp == 0 => WalkEnd::Outermost,
+            None => WalkEnd::UnknownStack(fp),
+            Some(stack) => loop {
+                if fp == 0 {
+                    break WalkEnd::Outermost;
+                }
+                if fp % core::mem::align_of::<usize>() != 0
+                    || fp < stack.start + arch::FRAME_RECORD_SIZE
+                    || fp > stack.end
+                {
+                    break WalkEnd::BadFramePointer(fp);
+                }
+                if len == MAX_FRAMES {
+                    break WalkEnd::Truncated;
+                }
+                // The record lies within the stack, as checked above.
+                let (ra, caller_fp) = unsafe { arch::frame_record(fp) };
+                if ra == 0 {
+                    break WalkEnd::Outermost;
+                }
+                addrs[len] = ra;
+                len += 1;
+                if caller_fp != 0 && caller_fp <= fp {
+                    break WalkEnd::BadFramePointer(caller_fp);
+                }
+                fp = caller_fp;
+            },
+        };
+        Self { addrs, len, end }
+    }
+
+    /// Returns the return addresses of the frames walked, innermost first.
+    pub fn addrs(&self) -> &[usize] {
+        &self.addrs[..self.len]
+    }
+
+    pub fn end(&self) -> WalkEnd {
+        self.end
+    }
+
+    /// Logs the frames walked, each with the function it returns into.
+    pub fn log(&self) {
+        error!("backtrace:");
+        for (index, &addr) in self.addrs().iter().enumerate() {
+            // A return address may be just past a call ending its function.
+            error!("  #{:<2} {:#x} {}", index, addr, Resolved(addr - 1, addr));
+        }
+        match self.end {
+            WalkEnd::Outermost => {}
+            WalkEnd::Truncated => error!("  ... (truncated at {} frames)", MAX_FRAMES),
+            WalkEnd::UnknownStack(fp) => {
+                error!("  ... (frame pointer {:#x} is on no known stack)", fp)
+            }
+            WalkEnd::BadFramePointer(fp) => error!("  ... (bad frame pointer {:#x})", fp),
+        }
+    }
+}
+
+/// Logs the backtrace of code interrupted by a trap at `pc`, with `fp` as its frame pointer.
+pub fn log_trapped(pc: usize, fp: usize) {
+    error!("trapped at {:#x} {}", pc, Resolved(pc, pc));
+    Backtrace::walk(fp).log();
+}
+
+/// Function holding an address, shown as `function+offset` of another address.
+struct Resolved(usize, usize);
+
+impl Display for Resolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match ksyms::resolve(self.0) {
+            Some(symbol) => write!(f, "{}+{:#x}", symbol.name, self.1 - symbol.addr),
+            None => f.write_str("<unknown>"),
+        }
+    }
+}
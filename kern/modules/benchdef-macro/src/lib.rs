@@ -0,0 +1,45 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, ItemFn, ReturnType};
+
+#[proc_macro_attribute]
+pub fn benchdef(_: TokenStream, func: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(func as ItemFn);
+    let func_attrs = &func.attrs;
+    let func_vis = &func.vis;
+    let func_name = &func.sig.ident;
+    let func_block = &func.block;
+    let func_inputs = &func.sig.inputs;
+
+    if func.sig.asyncness.is_none()
+        || func.sig.inputs.len() != 1
+        || !matches!(func.sig.output, ReturnType::Default)
+    {
+        return syn::Error::new(
+            func.sig.span(),
+            "bench function must be `async fn(&mut Bencher)`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let caller = quote! {
+        #(#func_attrs)*
+        #func_vis fn #func_name(#func_inputs) -> jrinx_benchdef::BenchFuture<'_> {
+            #[cfg_attr(feature = "no_test", used)]
+            #[cfg_attr(
+                not(feature = "no_test"),
+                used(linker),
+                link_section = concat!(".bench.", module_path!()),
+            )]
+            static __BENCH_DEF: &jrinx_benchdef::BenchDef = &jrinx_benchdef::BenchDef::new(
+                module_path!(),
+                #func_name,
+            );
+
+            ::alloc::boxed::Box::pin(async move #func_block)
+        }
+    };
+
+    caller.into()
+}
@@ -0,0 +1,200 @@
+#![no_std]
+
+extern crate alloc;
+
+use core::{fmt::Display, future::Future, hint::black_box, pin::Pin, time::Duration};
+
+use alloc::{boxed::Box, vec::Vec};
+use jrinx_hal::{hal, Cpu, Hal};
+
+pub use jrinx_benchdef_macro::*;
+
+/// Samples a bench takes, each of a batch of iterations.
+const SAMPLES: usize = 50;
+
+/// Time a batch of iterations is calibrated to take, so that a bench runs for about 100ms.
+const SAMPLE_TIME: Duration = Duration::from_millis(2);
+
+/// Most iterations in a batch, for routines too quick for the clock to tell.
+const MAX_BATCH: u64 = 1 << 24;
+
+pub type BenchFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+#[repr(C)]
+pub struct BenchDef {
+    name: &'static str,
+    bench: fn(&mut Bencher) -> BenchFuture<'_>,
+}
+
+impl BenchDef {
+    pub const fn new(name: &'static str, bench: fn(&mut Bencher) -> BenchFuture<'_>) -> Self {
+        Self { name, bench }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Runs the bench, returning its summary unless it never measured anything.
+    pub async fn run(&self) -> Option<Summary> {
+        let mut bencher = Bencher {
+            samples: Vec::with_capacity(SAMPLES),
+            iters: 0,
+        };
+        (self.bench)(&mut bencher).await;
+        bencher.summary()
+    }
+}
+
+pub fn all() -> impl Iterator<Item = &'static BenchDef> {
+    benchdef_iter()
+}
+
+/// Returns the benches whose name matches the glob `pattern` as for tests, see
+/// [`jrinx_testdef::find_all`].
+pub fn find_all(pattern: &str) -> impl Iterator<Item = &'static BenchDef> + '_ {
+    benchdef_iter().filter(move |bench_def| {
+        let name = bench_def.name;
+        core::iter::once(name)
+            .chain(name.match_indices("::").map(|(i, _)| &name[i + 2..]))
+            .any(|name| jrinx_testdef::glob_match(pattern, name))
+    })
+}
+
+fn benchdef_iter() -> impl Iterator<Item = &'static BenchDef> {
+    (jrinx_layout::_sbench()..jrinx_layout::_ebench())
+        .step_by(core::mem::size_of::<&BenchDef>())
+        .map(|a| unsafe { *(a as *const &BenchDef) })
+}
+
+/// Times the routine of a bench, in cycles of the CPU it runs on.
+///
+/// The routine runs in batches, first doubled in size until one takes [`SAMPLE_TIME`], then
+/// repeated for [`SAMPLES`] samples of the cycles per iteration. The results of the routine
+/// are passed through [`black_box`], so that the compiler cannot optimize it away.
+pub struct Bencher {
+    samples: Vec<u64>,
+    iters: u64,
+}
+
+impl Bencher {
+    pub fn iter<T>(&mut self, mut routine: impl FnMut() -> T) {
+        let mut batch = 1;
+        loop {
+            let stopwatch = Stopwatch::start();
+            for _ in 0..batch {
+                black_box(routine());
+            }
+            if self.calibrate(&mut batch, stopwatch) {
+                break;
+            }
+        }
+        while self.samples.len() < SAMPLES {
+            let stopwatch = Stopwatch::start();
+            for _ in 0..batch {
+                black_box(routine());
+            }
+            self.record(batch, stopwatch);
+        }
+    }
+
+    /// Times the future returned by `routine` as [`Bencher::iter`] times a routine.
+    pub async fn iter_async<T, F>(&mut self, mut routine: impl FnMut() -> F)
+    where
+        F: Future<Output = T>,
+    {
+        let mut batch = 1;
+        loop {
+            let stopwatch = Stopwatch::start();
+            for _ in 0..batch {
+                black_box(routine().await);
+            }
+            if self.calibrate(&mut batch, stopwatch) {
+                break;
+            }
+        }
+        while self.samples.len() < SAMPLES {
+            let stopwatch = Stopwatch::start();
+            for _ in 0..batch {
+                black_box(routine().await);
+            }
+            self.record(batch, stopwatch);
+        }
+    }
+
+    /// Returns whether a batch of `batch` iterations, as timed by `stopwatch`, is long enough
+    /// to sample, doubling it otherwise.
+    fn calibrate(&mut self, batch: &mut u64, stopwatch: Stopwatch) -> bool {
+        self.samples.clear();
+        self.iters = 0;
+        if stopwatch.elapsed().1 >= SAMPLE_TIME || *batch >= MAX_BATCH {
+            return true;
+        }
+        *batch *= 2;
+        false
+    }
+
+    fn record(&mut self, batch: u64, stopwatch: Stopwatch) {
+        self.samples.push(stopwatch.elapsed().0 / batch);
+        self.iters += batch;
+    }
+
+    fn summary(mut self) -> Option<Summary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        self.samples.sort_unstable();
+        let (min, max) = (self.samples[0], self.samples[self.samples.len() - 1]);
+        Some(Summary {
+            min,
+            median: self.samples[self.samples.len() / 2],
+            mean: self.samples.iter().sum::<u64>() / self.samples.len() as u64,
+            spread: max - min,
+            iters: self.iters,
+        })
+    }
+}
+
+/// Cycles per iteration of a bench, over its samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Summary {
+    pub min: u64,
+    pub median: u64,
+    pub mean: u64,
+    /// Difference between the slowest and the quickest sample.
+    pub spread: u64,
+    pub iters: u64,
+}
+
+/// Shows the median first, as `1234 cycles/iter (+/- 56)`, for scripts to parse.
+impl Display for Summary {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} cycles/iter (+/- {}), min {}, mean {}, {} iters",
+            self.median, self.spread, self.min, self.mean, self.iters
+        )
+    }
+}
+
+struct Stopwatch {
+    cycles: u64,
+    time: Duration,
+}
+
+impl Stopwatch {
+    fn start() -> Self {
+        Self {
+            cycles: hal!().cpu().get_cycles(),
+            time: hal!().cpu().get_time(),
+        }
+    }
+
+    /// Returns the cycles and the time elapsed since the start.
+    fn elapsed(&self) -> (u64, Duration) {
+        (
+            hal!().cpu().get_cycles().wrapping_sub(self.cycles),
+            hal!().cpu().get_time().saturating_sub(self.time),
+        )
+    }
+}
@@ -16,6 +16,10 @@ cfg_if! {
                 len: PHYS_MEM_LIMIT - PHYS_MEM_BASE,
             },
         ];
+        pub const MMIO_REGION: VirtMemRegion = VirtMemRegion {
+            addr: 0xC000_0000,
+            len: 0xE000_0000 - 0xC000_0000,
+        };
         pub const EXECUTOR_STACK_REGION: VirtMemRegion = VirtMemRegion {
             addr: 0xE000_0000,
             len: 0xF000_0000 - 0xE000_0000,
@@ -40,6 +44,10 @@ cfg_if! {
                 len: PHYS_MEM_LIMIT - PHYS_MEM_BASE,
             },
         ];
+        pub const MMIO_REGION: VirtMemRegion = VirtMemRegion {
+            addr: REMAP_MEM_OFFSET,
+            len: PHYS_MEM_BASE,
+        };
         pub const EXECUTOR_STACK_REGION: VirtMemRegion = VirtMemRegion {
             addr: 0xFFFF_FFE0_0000_0000,
             len: 0xFFFF_FFFF_0000_0000 - 0xFFFF_FFE0_0000_0000,
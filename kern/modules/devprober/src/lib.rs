@@ -1,7 +1,7 @@
 #![no_std]
 
 use fdt::{node::FdtNode, Fdt};
-use jrinx_error::Result;
+use jrinx_error::{ContextError, Result, ResultExt};
 
 pub use jrinx_devprober_macro::*;
 
@@ -24,15 +24,20 @@ impl DevProber {
     }
 }
 
-pub fn probe_all_device(fdt: &Fdt) -> Result<()> {
-    for devprober in devprober_iter() {
+/// Probes every device in `fdt` with the probers matching it whose identifier passes `filter`,
+/// failing with the name of the node whose probe failed.
+pub fn probe_all_device(
+    fdt: &Fdt,
+    filter: impl Fn(&DevIdent) -> bool,
+) -> core::result::Result<(), ContextError> {
+    for devprober in devprober_iter().filter(|devprober| filter(&devprober.ident)) {
         match devprober.ident {
             DevIdent::DeviceType(device_type) => {
                 for node in fdt.all_nodes().filter(|node| {
                     node.property("device_type")
                         .is_some_and(|prop| prop.as_str().is_some_and(|ty| ty == device_type))
                 }) {
-                    (devprober.probe)(&node)?;
+                    (devprober.probe)(&node).with_context(|| node.name)?;
                 }
             }
             DevIdent::Compatible(compatible) => {
@@ -40,7 +45,7 @@ pub fn probe_all_device(fdt: &Fdt) -> Result<()> {
                     node.compatible()
                         .is_some_and(|cp| cp.all().any(|c| c == compatible))
                 }) {
-                    (devprober.probe)(&node)?;
+                    (devprober.probe)(&node).with_context(|| node.name)?;
                 }
             }
         }
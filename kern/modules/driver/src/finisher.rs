@@ -0,0 +1,20 @@
+use fdt::node::FdtNode;
+use jrinx_addr::PhysAddr;
+use jrinx_devprober::devprober;
+use jrinx_error::{InternalError, Result};
+
+#[devprober(compatible = "sifive,test0")]
+fn probe(node: &FdtNode) -> Result<()> {
+    let region =
+        node.reg()
+            .and_then(|mut regions| regions.next())
+            .ok_or(InternalError::DevProbeError {
+                compatible: "sifive,test0",
+            })?;
+    jrinx_hal::finisher::set(PhysAddr::new(region.starting_address as usize));
+    info!(
+        "halts exit through the finisher at {:#x}",
+        region.starting_address as usize
+    );
+    Ok(())
+}
@@ -6,12 +6,61 @@ extern crate alloc;
 #[macro_use]
 extern crate log;
 
+mod finisher;
 mod mem;
+mod plic;
 mod serial;
 
-use fdt::Fdt;
+use fdt::{node::FdtNode, Fdt};
+use jrinx_devprober::DevIdent;
+use jrinx_error::ResultExt;
+use spin::Once;
 
-pub fn probe_all(fdt: &Fdt<'_>) {
+static FDT: Once<Fdt<'static>> = Once::new();
+
+/// Probes the memory, into which the heap grows, before the kernel page table is built.
+pub fn probe_memory(fdt: &Fdt<'static>) {
+    FDT.call_once(|| *fdt);
+    info!("probing memory");
+    if let Err(err) = jrinx_devprober::probe_all_device(fdt, is_memory).context("probe memory") {
+        panic!("{}", err);
+    }
+}
+
+/// Probes all devices but the memory, whose registers are mapped into the kernel page table.
+pub fn probe_all(fdt: &Fdt<'static>) {
+    FDT.call_once(|| *fdt);
     info!("probing all devices");
-    jrinx_devprober::probe_all_device(fdt).unwrap();
+    if let Err(err) =
+        jrinx_devprober::probe_all_device(fdt, |ident| !is_memory(ident)).context("probe devices")
+    {
+        panic!("{}", err);
+    }
+}
+
+fn is_memory(ident: &DevIdent) -> bool {
+    *ident == DevIdent::DeviceType("memory")
+}
+
+/// Returns the id of the hart whose local interrupt controller has `phandle`.
+fn hart_of_intc(phandle: u32) -> Option<usize> {
+    FDT.get()?
+        .find_node("/cpus")?
+        .children()
+        .find(|cpu| {
+            cpu.children().any(|intc| {
+                intc.name == "interrupt-controller"
+                    && intc.property("phandle").and_then(|prop| prop.as_usize())
+                        == Some(phandle as usize)
+            })
+        })
+        .and_then(|cpu| cpu.reg()?.next())
+        .map(|reg| reg.starting_address as usize)
+}
+
+/// Tells whether `node` is the standard output chosen by the firmware.
+fn is_stdout(node: &FdtNode) -> bool {
+    FDT.get()
+        .and_then(|fdt| fdt.chosen().stdout())
+        .is_some_and(|stdout| stdout.name == node.name)
 }
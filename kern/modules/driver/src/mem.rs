@@ -9,7 +9,9 @@ use jrinx_util::interval::{Bound, ExclusiveIntervals};
 #[devprober(device_type = "memory")]
 fn probe(node: &FdtNode) -> Result<()> {
     node.reg()
-        .ok_or(InternalError::DevProbeError)?
+        .ok_or(InternalError::DevProbeError {
+            compatible: "memory",
+        })?
         .filter_map(|mem_region| {
             mem_region.size.map(|size| {
                 let addr = PhysAddr::new(mem_region.starting_address as usize).to_virt();
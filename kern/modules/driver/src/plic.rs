@@ -0,0 +1,115 @@
+//! Platform-level interrupt controller, routing the interrupts of devices to the supervisor
+//! mode of each hart.
+
+use alloc::collections::BTreeMap;
+use fdt::node::FdtNode;
+use jrinx_addr::PhysAddr;
+use jrinx_devprober::devprober;
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::{hal, Cpu, Hal};
+use jrinx_mmio::MmioRegion;
+use jrinx_trap::external_int::{self, IrqChip};
+use spin::Once;
+
+const PRIORITY_BASE: usize = 0x0000;
+const ENABLE_BASE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_BASE: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const CONTEXT_THRESHOLD: usize = 0x0;
+const CONTEXT_CLAIM: usize = 0x4;
+
+/// Interrupt raised on the local controller of a hart by its supervisor context.
+const SUPERVISOR_EXTERNAL_INTERRUPT: u32 = 9;
+
+static PLIC: Once<Plic> = Once::new();
+
+struct Plic {
+    region: MmioRegion,
+    /// Supervisor context of each hart, by hart id.
+    contexts: BTreeMap<usize, usize>,
+}
+
+impl Plic {
+    fn context(&self) -> Option<usize> {
+        self.contexts.get(&hal!().cpu().id()).copied()
+    }
+}
+
+impl IrqChip for Plic {
+    fn claim(&self) -> Option<u32> {
+        let context = self.context()?;
+        let irq = self
+            .region
+            .read::<u32>(CONTEXT_BASE + context * CONTEXT_STRIDE + CONTEXT_CLAIM);
+        (irq != 0).then_some(irq)
+    }
+
+    fn complete(&self, irq: u32) {
+        if let Some(context) = self.context() {
+            self.region
+                .write(CONTEXT_BASE + context * CONTEXT_STRIDE + CONTEXT_CLAIM, irq);
+        }
+    }
+
+    fn enable(&self, irq: u32) {
+        let Some(context) = self.context() else {
+            warn!("no plic context for cpu#{}", hal!().cpu().id());
+            return;
+        };
+        let irq = irq as usize;
+        self.region.write(PRIORITY_BASE + irq * 4, 1u32);
+        let enable = ENABLE_BASE + context * ENABLE_STRIDE + irq / 32 * 4;
+        let bits = self.region.read::<u32>(enable);
+        self.region.write(enable, bits | 1 << (irq % 32));
+    }
+}
+
+#[devprober(compatible = "sifive,plic-1.0.0")]
+fn probe(node: &FdtNode) -> Result<()> {
+    let err = || InternalError::DevProbeError {
+        compatible: "sifive,plic-1.0.0",
+    };
+    let region = node
+        .reg()
+        .and_then(|mut regions| regions.next())
+        .ok_or_else(err)?;
+    let addr = region.starting_address as usize;
+
+    // Contexts are numbered by their position in `interrupts-extended`, each a pair of the
+    // phandle of a hart's local controller and the interrupt it raises there.
+    let contexts = node
+        .property("interrupts-extended")
+        .ok_or_else(err)?
+        .value
+        .chunks_exact(8)
+        .enumerate()
+        .filter(|(_, cells)| be32(&cells[4..]) == SUPERVISOR_EXTERNAL_INTERRUPT)
+        .filter_map(|(context, cells)| Some((crate::hart_of_intc(be32(&cells[..4]))?, context)))
+        .collect::<BTreeMap<_, _>>();
+
+    let plic = PLIC.try_call_once(|| {
+        Ok::<_, InternalError>(Plic {
+            region: MmioRegion::map(PhysAddr::new(addr), region.size.ok_or_else(err)?)?,
+            contexts,
+        })
+    })?;
+    for &context in plic.contexts.values() {
+        plic.region.write(
+            CONTEXT_BASE + context * CONTEXT_STRIDE + CONTEXT_THRESHOLD,
+            0u32,
+        );
+    }
+    external_int::set_chip(plic);
+
+    info!(
+        "plic at {:#x} routes to {} supervisor contexts",
+        addr,
+        plic.contexts.len()
+    );
+    Ok(())
+}
+
+fn be32(cells: &[u8]) -> u32 {
+    u32::from_be_bytes(cells.try_into().unwrap())
+}
@@ -1,9 +1,163 @@
+//! UART of the 16550A family, which takes the console over from the firmware once probed if it
+//! is the chosen standard output, with input received on its interrupt.
+
 use fdt::node::FdtNode;
+use jrinx_addr::PhysAddr;
 use jrinx_devprober::devprober;
-use jrinx_error::Result;
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::ConsoleUart;
+use jrinx_mmio::{register_block, MmioRegion};
+use jrinx_sync::IrqSafeMutex;
+use jrinx_trap::external_int;
+use spin::Once;
+
+register_block! {
+    struct Regs {
+        @0x00 rbr: u8 [ro],
+        @0x00 thr: u8 [wo],
+        @0x01 ier: u8 [rw],
+        @0x05 lsr: u8 [ro],
+    }
+}
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+const LSR_TX_EMPTY: u8 = 1 << 6;
+
+const RX_QUEUE_SIZE: usize = 256;
+
+static CONSOLE: Once<Ns16550a> = Once::new();
+
+struct Ns16550a {
+    regs: Regs,
+    rx: IrqSafeMutex<RxQueue>,
+}
+
+/// Bytes received on interrupt and not read yet, the newest dropped once it is full.
+struct RxQueue {
+    buf: [u8; RX_QUEUE_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl RxQueue {
+    fn push(&mut self, c: u8) -> bool {
+        if self.len == RX_QUEUE_SIZE {
+            return false;
+        }
+        self.buf[(self.head + self.len) % RX_QUEUE_SIZE] = c;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let c = self.buf[self.head];
+        self.head = (self.head + 1) % RX_QUEUE_SIZE;
+        self.len -= 1;
+        Some(c)
+    }
+}
+
+impl Ns16550a {
+    fn recv(&self) -> Option<u8> {
+        (self.regs.lsr().read() & LSR_DATA_READY != 0).then(|| self.regs.rbr().read())
+    }
+}
+
+impl ConsoleUart for Ns16550a {
+    fn putc(&self, c: u8) {
+        if c == b'\n' {
+            self.putc(b'\r');
+        }
+        while self.regs.lsr().read() & LSR_THR_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        self.regs.thr().write(c);
+    }
+
+    /// Reads a byte received on interrupt, polling the receiver otherwise, as the debugger
+    /// reads with interrupts off.
+    fn getc(&self) -> Option<u8> {
+        let mut rx = self.rx.lock();
+        rx.pop().or_else(|| self.recv())
+    }
+
+    /// Tells whether both the holding and the shift register are empty.
+    fn is_drained(&self) -> bool {
+        self.regs.lsr().read() & LSR_TX_EMPTY != 0
+    }
+}
+
+fn handle_rx(_irq: u32) {
+    let Some(uart) = CONSOLE.get() else {
+        return;
+    };
+    let mut rx = uart.rx.lock();
+    let mut dropped = 0;
+    while let Some(c) = uart.recv() {
+        if !rx.push(c) {
+            dropped += 1;
+        }
+    }
+    drop(rx);
+    if dropped != 0 {
+        warn!("ns16550a dropped {} received bytes", dropped);
+    }
+}
 
 #[devprober(compatible = "ns16550a")]
-fn probe(_node: &FdtNode) -> Result<()> {
-    // TODO
+fn probe(node: &FdtNode) -> Result<()> {
+    let err = || InternalError::DevProbeError {
+        compatible: "ns16550a",
+    };
+    if !crate::is_stdout(node) {
+        return Ok(());
+    }
+    if node
+        .property("reg-shift")
+        .and_then(|prop| prop.as_usize())
+        .is_some_and(|shift| shift != 0)
+    {
+        warn!(
+            "ns16550a {} has spread registers, left to the firmware",
+            node.name
+        );
+        return Ok(());
+    }
+    let region = node
+        .reg()
+        .and_then(|mut regions| regions.next())
+        .ok_or_else(err)?;
+    let irq = node
+        .interrupts()
+        .and_then(|mut irqs| irqs.next())
+        .ok_or_else(err)?;
+    let addr = region.starting_address as usize;
+
+    let uart = CONSOLE.try_call_once(|| {
+        Ok::<_, InternalError>(Ns16550a {
+            regs: Regs::new(MmioRegion::map(
+                PhysAddr::new(addr),
+                region.size.ok_or_else(err)?,
+            )?),
+            rx: IrqSafeMutex::new(
+                "ns16550a-rx",
+                RxQueue {
+                    buf: [0; RX_QUEUE_SIZE],
+                    head: 0,
+                    len: 0,
+                },
+            ),
+        })
+    })?;
+    external_int::register(irq as u32, handle_rx);
+    uart.regs.ier().modify(|ier| ier | IER_RX_AVAILABLE);
+    jrinx_hal::earlycon::set_uart(uart);
+
+    info!("console on ns16550a at {:#x}, irq {}", addr, irq);
     Ok(())
 }
@@ -0,0 +1,152 @@
+use core::fmt::{self, Display, Write};
+
+use crate::InternalError;
+
+/// Most context frames a [`ContextError`] holds, past which the outermost ones are dropped.
+pub const CONTEXT_FRAMES: usize = 4;
+
+/// Longest context rendered by [`ResultExt::with_context`], past which it is truncated.
+const FRAME_LEN: usize = 48;
+
+#[derive(Clone, Copy)]
+enum Frame {
+    Static(&'static str),
+    Inline { buf: [u8; FRAME_LEN], len: usize },
+}
+
+impl Frame {
+    fn render(context: impl Display) -> Self {
+        let mut frame = Self::Inline {
+            buf: [0; FRAME_LEN],
+            len: 0,
+        };
+        let _ = write!(frame, "{}", context);
+        frame
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Static(context) => context,
+            Self::Inline { buf, len } => core::str::from_utf8(&buf[..*len]).unwrap_or_default(),
+        }
+    }
+}
+
+/// Truncates what does not fit at a character boundary instead of failing.
+impl Write for Frame {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let Self::Inline { buf, len } = self else {
+            return Err(fmt::Error);
+        };
+        let mut end = s.len().min(FRAME_LEN - *len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        buf[*len..*len + end].copy_from_slice(&s.as_bytes()[..end]);
+        *len += end;
+        Ok(())
+    }
+}
+
+/// An [`InternalError`] with the context it passed through on its way up, rendered by
+/// `Display` as `outer: inner: error`.
+///
+/// The frames are held inline, so that adding context never allocates.
+pub struct ContextError {
+    error: InternalError,
+    frames: [Frame; CONTEXT_FRAMES],
+    len: usize,
+    dropped: usize,
+}
+
+impl ContextError {
+    pub fn error(&self) -> &InternalError {
+        &self.error
+    }
+
+    pub fn into_error(self) -> InternalError {
+        self.error
+    }
+
+    /// Returns the context frames, outermost first.
+    pub fn frames(&self) -> impl Iterator<Item = &str> {
+        self.frames[..self.len].iter().rev().map(Frame::as_str)
+    }
+
+    fn fmt_frames(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.dropped != 0 {
+            write!(f, "...: ")?;
+        }
+        for frame in self.frames() {
+            write!(f, "{}: ", frame)?;
+        }
+        Ok(())
+    }
+
+    fn push(mut self, frame: Frame) -> Self {
+        if self.len < CONTEXT_FRAMES {
+            self.frames[self.len] = frame;
+            self.len += 1;
+        } else {
+            self.dropped += 1;
+        }
+        self
+    }
+}
+
+impl From<InternalError> for ContextError {
+    fn from(error: InternalError) -> Self {
+        Self {
+            error,
+            frames: [Frame::Static(""); CONTEXT_FRAMES],
+            len: 0,
+            dropped: 0,
+        }
+    }
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_frames(f)?;
+        write!(f, "{}", self.error)
+    }
+}
+
+impl fmt::Debug for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_frames(f)?;
+        write!(f, "{:?}", self.error)
+    }
+}
+
+impl core::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Adds context to the error of a [`Result`](core::result::Result) as it is passed up.
+pub trait ResultExt<T> {
+    fn context(self, context: &'static str) -> core::result::Result<T, ContextError>;
+
+    /// Adds the context returned by `context`, which is only called on error and rendered
+    /// into the error itself, truncated to a few dozen bytes.
+    fn with_context<C, F>(self, context: F) -> core::result::Result<T, ContextError>
+    where
+        C: Display,
+        F: FnOnce() -> C;
+}
+
+impl<T, E: Into<ContextError>> ResultExt<T> for core::result::Result<T, E> {
+    fn context(self, context: &'static str) -> core::result::Result<T, ContextError> {
+        self.map_err(|err| err.into().push(Frame::Static(context)))
+    }
+
+    fn with_context<C, F>(self, context: F) -> core::result::Result<T, ContextError>
+    where
+        C: Display,
+        F: FnOnce() -> C,
+    {
+        self.map_err(|err| err.into().push(Frame::render(context())))
+    }
+}
@@ -0,0 +1,85 @@
+use crate::InternalError;
+
+/// Error of a system call as seen by user mode, returned negated in place of its result.
+///
+/// The values follow POSIX and are part of the system call interface: they must match
+/// `jrinx_abi::errno` and never change, unlike the variants of [`InternalError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(isize)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Errno {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EFAULT = 14,
+    EBUSY = 16,
+    EEXIST = 17,
+    EINVAL = 22,
+    ENOSYS = 38,
+    ETIMEDOUT = 110,
+}
+
+impl Errno {
+    pub const ALL: [Self; 12] = [
+        Self::EPERM,
+        Self::ENOENT,
+        Self::ESRCH,
+        Self::EINTR,
+        Self::EAGAIN,
+        Self::ENOMEM,
+        Self::EFAULT,
+        Self::EBUSY,
+        Self::EEXIST,
+        Self::EINVAL,
+        Self::ENOSYS,
+        Self::ETIMEDOUT,
+    ];
+
+    pub const fn value(self) -> isize {
+        self as isize
+    }
+
+    /// Returns the value a system call returns for the error.
+    pub const fn into_ret(self) -> usize {
+        self.value().wrapping_neg() as usize
+    }
+
+    /// Returns the error a system call returned `ret` for, if it is one.
+    pub fn from_ret(ret: usize) -> Option<Self> {
+        let value = (ret as isize).checked_neg()?;
+        Self::ALL.into_iter().find(|errno| errno.value() == value)
+    }
+}
+
+impl From<InternalError> for Errno {
+    fn from(err: InternalError) -> Self {
+        Self::from(&err)
+    }
+}
+
+/// The mapping is part of the system call interface, and must not change for a given error.
+impl From<&InternalError> for Errno {
+    fn from(err: &InternalError) -> Self {
+        match err {
+            InternalError::InvalidVirtAddr(_) => Self::EFAULT,
+            InternalError::NotEnoughMem => Self::ENOMEM,
+            InternalError::InvalidSyscallNumber => Self::ENOSYS,
+            InternalError::TaskCancelled => Self::EINTR,
+            InternalError::BusyLock => Self::EBUSY,
+            InternalError::SmpCallTimeout => Self::ETIMEDOUT,
+            InternalError::ChannelFull
+            | InternalError::SmpCallQueueFull
+            | InternalError::ResourceLimitExceeded(_) => Self::EAGAIN,
+            InternalError::RepeatInitialization
+            | InternalError::DuplicateTaskId(_)
+            | InternalError::DuplicateExecutorId
+            | InternalError::DuplicateInspectorId
+            | InternalError::DuplicateRuntimeSchedTable
+            | InternalError::DuplicateBreakpoint => Self::EEXIST,
+            _ => Self::EINVAL,
+        }
+    }
+}
@@ -1,19 +1,40 @@
 #![no_std]
+#![feature(error_in_core)]
+
+mod context;
+mod errno;
+
+use core::fmt;
+
+use jrinx_addr::VirtAddr;
+
+pub use context::{ContextError, ResultExt, CONTEXT_FRAMES};
+pub use errno::Errno;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum InternalError {
     RepeatInitialization,
-    DevProbeError,
+    DevProbeError { compatible: &'static str },
     ElfParseError,
     NotEnoughMem,
-    InvalidCpuId,
-    InvalidVirtAddr,
-    DuplicateTaskId,
+    InvalidCpuId(usize),
+    InvalidCpuAffinity,
+    InvalidVirtAddr(VirtAddr),
+    InvalidLength(usize),
+    DuplicateTaskId(u64),
+    TaskCancelled,
+    NoCurrentTask,
+    TaskLocalInUse,
+    ChannelFull,
+    ChannelEmpty,
+    ChannelClosed,
     InvalidExecutorId,
     DuplicateExecutorId,
     InvalidInspectorId,
     DuplicateInspectorId,
     InvalidInspectorStatus,
+    UnscheduledInspector,
     InvalidRuntimeStatus,
     InvalidRuntimeSchedTable,
     DuplicateRuntimeSchedTable,
@@ -22,6 +43,85 @@ pub enum InternalError {
     InvalidApexPriority,
     InvalidApexNumCores,
     InvalidSyscallNumber,
+    BusyLock,
+    SmpCallNested,
+    SmpCallTimeout,
+    SmpCallQueueFull,
+    InvalidBreakpointId,
+    DuplicateBreakpoint,
+    InvalidWatchpointId,
+    NotEnoughTriggers,
+    InvalidTrapHandlerId,
+    ResourceLimitExceeded(ResourceKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Executors,
+    Tasks,
+    TimedEvents,
+    Descriptors,
+    SharedBytes,
+}
+
+impl fmt::Display for InternalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RepeatInitialization => write!(f, "already initialized"),
+            Self::DevProbeError { compatible } => write!(f, "failed to probe device {compatible}"),
+            Self::ElfParseError => write!(f, "malformed elf"),
+            Self::NotEnoughMem => write!(f, "out of memory"),
+            Self::InvalidCpuId(cpu_id) => write!(f, "invalid cpu#{cpu_id}"),
+            Self::InvalidCpuAffinity => write!(f, "invalid cpu affinity"),
+            Self::InvalidVirtAddr(addr) => write!(f, "invalid virtual address {addr}"),
+            Self::InvalidLength(len) => write!(f, "invalid length {len}"),
+            Self::DuplicateTaskId(id) => write!(f, "duplicate task#{id}"),
+            Self::TaskCancelled => write!(f, "task cancelled"),
+            Self::NoCurrentTask => write!(f, "no current task"),
+            Self::TaskLocalInUse => write!(f, "task local in use"),
+            Self::ChannelFull => write!(f, "channel full"),
+            Self::ChannelEmpty => write!(f, "channel empty"),
+            Self::ChannelClosed => write!(f, "channel closed"),
+            Self::InvalidExecutorId => write!(f, "invalid executor id"),
+            Self::DuplicateExecutorId => write!(f, "duplicate executor id"),
+            Self::InvalidInspectorId => write!(f, "invalid inspector id"),
+            Self::DuplicateInspectorId => write!(f, "duplicate inspector id"),
+            Self::InvalidInspectorStatus => write!(f, "invalid inspector status"),
+            Self::UnscheduledInspector => write!(f, "inspector not in the schedule table"),
+            Self::InvalidRuntimeStatus => write!(f, "invalid runtime status"),
+            Self::InvalidRuntimeSchedTable => write!(f, "invalid runtime schedule table"),
+            Self::DuplicateRuntimeSchedTable => write!(f, "duplicate runtime schedule table"),
+            Self::InvalidTimedEventStatus => write!(f, "invalid timed event status"),
+            Self::InvalidApexName => write!(f, "invalid apex name"),
+            Self::InvalidApexPriority => write!(f, "invalid apex priority"),
+            Self::InvalidApexNumCores => write!(f, "invalid apex number of cores"),
+            Self::InvalidSyscallNumber => write!(f, "invalid system call number"),
+            Self::BusyLock => write!(f, "lock busy"),
+            Self::SmpCallNested => write!(f, "nested smp call"),
+            Self::SmpCallTimeout => write!(f, "smp call timed out"),
+            Self::SmpCallQueueFull => write!(f, "smp call queue full"),
+            Self::InvalidBreakpointId => write!(f, "invalid breakpoint id"),
+            Self::DuplicateBreakpoint => write!(f, "duplicate breakpoint"),
+            Self::InvalidWatchpointId => write!(f, "invalid watchpoint id"),
+            Self::NotEnoughTriggers => write!(f, "out of hardware triggers"),
+            Self::InvalidTrapHandlerId => write!(f, "invalid trap handler id"),
+            Self::ResourceLimitExceeded(kind) => write!(f, "too many {kind}"),
+        }
+    }
+}
+
+impl core::error::Error for InternalError {}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Executors => write!(f, "executors"),
+            Self::Tasks => write!(f, "tasks"),
+            Self::TimedEvents => write!(f, "timed events"),
+            Self::Descriptors => write!(f, "descriptors"),
+            Self::SharedBytes => write!(f, "shared bytes"),
+        }
+    }
 }
 
 pub type Result<T> = core::result::Result<T, InternalError>;
@@ -1,5 +1,3 @@
-use core::time::Duration;
-
 use riscv::register;
 
 use crate::Cpu;
@@ -19,13 +17,21 @@ impl Cpu for CpuImpl {
         id
     }
 
+    fn get_ticks(&self) -> u64 {
+        register::time::read64()
+    }
+
+    fn get_cycles(&self) -> u64 {
+        register::cycle::read64()
+    }
+
     fn get_time(&self) -> core::time::Duration {
-        match self.timebase_freq() {
-            freq if freq != 0 => {
-                Duration::from_nanos(register::time::read64() * 1_000_000_000u64 / freq)
-            }
-            _ => Duration::ZERO,
-        }
+        self.ticks_to_time(self.get_ticks())
+    }
+
+    /// The `time` CSR reflects the platform-wide `mtime` on every hart.
+    fn has_shared_clock(&self) -> bool {
+        true
     }
 
     fn set_timer(&self, next: core::time::Duration) {
@@ -1,14 +1,201 @@
-use crate::Earlycon;
+//! Early console on the SBI, through the debug console extension (DBCN) if the firmware has
+//! it, and the legacy console calls otherwise, until a UART driver takes over.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use jrinx_addr::VirtAddr;
+use sbi::base::{probe_extension, ExtensionAvailability};
+use spin::{Mutex, Once};
+
+use crate::{ConsoleUart, Earlycon, Hal, HalImpl, Interrupt};
+
+/// Extension ID of the SBI debug console, "DBCN" in ASCII.
+const DBCN_EXTENSION_ID: usize = 0x4442_434e;
+const DBCN_CONSOLE_WRITE: usize = 0;
+const DBCN_CONSOLE_READ: usize = 1;
+const DBCN_CONSOLE_WRITE_BYTE: usize = 2;
+
+/// Bytes handed to the SBI by one write or read of the debug console.
+pub const DBCN_CHUNK_SIZE: usize = 256;
+
+const BACKEND_UNPROBED: u8 = 0;
+
+static BACKEND: AtomicU8 = AtomicU8::new(BACKEND_UNPROBED);
+
+static UART: Once<&'static dyn ConsoleUart> = Once::new();
+
+/// Buffer shared with the SBI, which takes physical addresses, so it lives in the kernel
+/// image rather than on a stack.
+static STAGING: Mutex<[u8; DBCN_CHUNK_SIZE]> = Mutex::new([0; DBCN_CHUNK_SIZE]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EarlyconBackend {
+    Legacy = 1,
+    DebugConsole = 2,
+}
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct EarlyconImpl;
 
 impl Earlycon for EarlyconImpl {
     fn getc(&self) -> Option<u8> {
+        if let Some(uart) = UART.get() {
+            return uart.getc();
+        }
+        if backend() == EarlyconBackend::DebugConsole {
+            let read = HalImpl.interrupt().with_saved_off(|| {
+                let mut staging = STAGING.lock();
+                dbcn_call(DBCN_CONSOLE_READ, &mut staging[..1])
+                    .map(|len| (len == 1).then_some(staging[0]))
+            });
+            match read {
+                Ok(c) => return c,
+                Err(_) => fall_back(),
+            }
+        }
         sbi::legacy::console_getchar()
     }
 
+    /// Writes `c` synchronously, which suits the panic path.
     fn putc(&self, c: u8) {
+        if let Some(uart) = UART.get() {
+            uart.putc(c);
+            return;
+        }
+        if backend() == EarlyconBackend::DebugConsole {
+            let (error, _) = sbi_call(DBCN_EXTENSION_ID, DBCN_CONSOLE_WRITE_BYTE, c as usize, 0, 0);
+            if error == 0 {
+                return;
+            }
+            fall_back();
+        }
         sbi::legacy::console_putchar(c);
     }
+
+    /// Writes `bytes` in chunks of [`DBCN_CHUNK_SIZE`], resuming after partial writes.
+    fn write(&self, bytes: &[u8]) {
+        if let Some(uart) = UART.get() {
+            for &c in bytes {
+                uart.putc(c);
+            }
+            return;
+        }
+        let mut rest = bytes;
+        if backend() == EarlyconBackend::DebugConsole {
+            match HalImpl.interrupt().with_saved_off(|| dbcn_write(bytes)) {
+                Ok(()) => return,
+                Err(written) => {
+                    fall_back();
+                    rest = &bytes[written..];
+                }
+            }
+        }
+        for &c in rest {
+            sbi::legacy::console_putchar(c);
+        }
+    }
+
+    /// Tells whether the UART has shifted out all bytes, the SBI calls returning once done.
+    fn is_drained(&self) -> bool {
+        UART.get().map_or(true, |uart| uart.is_drained())
+    }
+}
+
+/// Hands the console over to `uart`, once its driver has set it up.
+pub fn set_uart(uart: &'static dyn ConsoleUart) {
+    UART.call_once(|| uart);
+}
+
+/// Returns the backend of the early console, probing the firmware on first use.
+pub fn backend() -> EarlyconBackend {
+    if BACKEND.load(Ordering::Relaxed) == BACKEND_UNPROBED {
+        let probed = if dbcn_available() {
+            EarlyconBackend::DebugConsole
+        } else {
+            EarlyconBackend::Legacy
+        };
+        let _ = BACKEND.compare_exchange(
+            BACKEND_UNPROBED,
+            probed as u8,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+    if BACKEND.load(Ordering::Relaxed) == EarlyconBackend::DebugConsole as u8 {
+        EarlyconBackend::DebugConsole
+    } else {
+        EarlyconBackend::Legacy
+    }
+}
+
+/// Selects the backend of the early console, which fails if the firmware lacks it.
+pub fn select(backend: EarlyconBackend) -> bool {
+    if backend == EarlyconBackend::DebugConsole && !dbcn_available() {
+        return false;
+    }
+    BACKEND.store(backend as u8, Ordering::Relaxed);
+    true
+}
+
+pub fn dbcn_available() -> bool {
+    matches!(
+        probe_extension(DBCN_EXTENSION_ID),
+        ExtensionAvailability::Available(_)
+    )
+}
+
+/// Writes `bytes` through the debug console, returning how many were written if it fails.
+fn dbcn_write(bytes: &[u8]) -> Result<(), usize> {
+    let mut staging = STAGING.lock();
+    let mut written = 0;
+    for chunk in bytes.chunks(DBCN_CHUNK_SIZE) {
+        staging[..chunk.len()].copy_from_slice(chunk);
+        let mut done = 0;
+        while done < chunk.len() {
+            done += dbcn_call(DBCN_CONSOLE_WRITE, &mut staging[done..chunk.len()])
+                .map_err(|_| written + done)?;
+        }
+        written += chunk.len();
+    }
+    Ok(())
+}
+
+fn fall_back() {
+    BACKEND.store(EarlyconBackend::Legacy as u8, Ordering::Relaxed);
+}
+
+/// Issues a debug console call on `buf`, which must lie in the kernel image, returning the
+/// number of bytes transferred.
+fn dbcn_call(function: usize, buf: &mut [u8]) -> Result<usize, isize> {
+    let addr = VirtAddr::new(buf.as_mut_ptr() as usize)
+        .to_phys()
+        .as_usize() as u64;
+    // The upper half of the address only goes in its own register on 32-bit harts.
+    let (addr_lo, addr_hi) = if usize::BITS == 32 {
+        (addr as u32 as usize, (addr >> 32) as usize)
+    } else {
+        (addr as usize, 0)
+    };
+    let (error, value) = sbi_call(DBCN_EXTENSION_ID, function, buf.len(), addr_lo, addr_hi);
+    match error {
+        0 => Ok(value),
+        _ => Err(error),
+    }
+}
+
+fn sbi_call(extension: usize, function: usize, a0: usize, a1: usize, a2: usize) -> (isize, usize) {
+    let (error, value);
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") a0 => error,
+            inlateout("a1") a1 => value,
+            in("a2") a2,
+            in("a6") function,
+            in("a7") extension,
+            options(nostack),
+        );
+    }
+    (error, value)
 }
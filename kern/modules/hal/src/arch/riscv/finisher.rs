@@ -0,0 +1,69 @@
+//! The test finisher of QEMU (`sifive,test0`), by which a halt sets the exit status of QEMU.
+//!
+//! The SBI system reset has no say in the exit status, so halting through it always exits
+//! with zero.
+
+use jrinx_addr::PhysAddr;
+use riscv::register::satp;
+use spin::Once;
+
+static FINISHER: Once<PhysAddr> = Once::new();
+
+const FINISHER_FAIL: u32 = 0x3333;
+const FINISHER_PASS: u32 = 0x5555;
+
+const PAGE_SHIFT: usize = 12;
+
+/// Entries of a page table, each the size of a word.
+const PTES: usize = (1 << PAGE_SHIFT) / core::mem::size_of::<usize>();
+
+/// Size of a page mapped by the root page table, in bits.
+const SUPERPAGE_SHIFT: usize = if cfg!(target_arch = "riscv32") {
+    22
+} else {
+    30
+};
+
+/// Sets the address of the finisher, through which halts go from then on.
+pub fn set(addr: PhysAddr) {
+    FINISHER.call_once(|| addr);
+}
+
+/// Exits QEMU with `code` through the finisher, returning only if there is none.
+pub(super) fn exit(code: u8) {
+    let Some(&addr) = FINISHER.get() else {
+        return;
+    };
+    let value = match code {
+        0 => FINISHER_PASS,
+        code => (code as u32) << 16 | FINISHER_FAIL,
+    };
+    unsafe {
+        map_superpage(addr);
+        (addr.to_virt().as_usize() as *mut u32).write_volatile(value);
+    }
+}
+
+/// Maps the superpage holding `addr` at its remapped address, as devices are left out of the
+/// memory remapped at boot.
+///
+/// # Safety
+///
+/// This overwrites an entry of the root page table in use, which is only fine once the system
+/// is going down.
+unsafe fn map_superpage(addr: PhysAddr) {
+    const V: usize = 1 << 0;
+    const R: usize = 1 << 1;
+    const W: usize = 1 << 2;
+    const A: usize = 1 << 6;
+    const D: usize = 1 << 7;
+
+    let root = PhysAddr::new(satp::read().ppn() << PAGE_SHIFT)
+        .to_virt()
+        .as_usize() as *mut usize;
+    let index = (addr.to_virt().as_usize() >> SUPERPAGE_SHIFT) % PTES;
+    let ppn = addr.as_usize() >> SUPERPAGE_SHIFT << SUPERPAGE_SHIFT >> PAGE_SHIFT;
+    root.add(index)
+        .write_volatile(ppn << 10 | V | R | W | A | D);
+    riscv::asm::sfence_vma_all();
+}
@@ -1,8 +1,11 @@
 use riscv::register::{sip, sstatus};
 use sbi::HartMask;
+use spin::Once;
 
 use crate::Interrupt;
 
+static IPI_HOOK: Once<fn(&[usize])> = Once::new();
+
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct InterruptImpl;
 
@@ -40,6 +43,9 @@ impl Interrupt for InterruptImpl {
     }
 
     fn send_ipi(&self, cpu_ids: &[usize]) {
+        if let Some(hook) = IPI_HOOK.get() {
+            hook(cpu_ids);
+        }
         let mut mask = HartMask::new(0);
         for &cpu_id in cpu_ids {
             mask = mask.with(cpu_id);
@@ -47,3 +53,8 @@ impl Interrupt for InterruptImpl {
         sbi::ipi::send_ipi(mask).unwrap();
     }
 }
+
+/// Registers `hook` to run with the targets of every IPI right before it is sent.
+pub fn set_ipi_hook(hook: fn(cpu_ids: &[usize])) {
+    IPI_HOOK.call_once(|| hook);
+}
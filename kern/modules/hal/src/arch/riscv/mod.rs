@@ -1,6 +1,7 @@
 pub mod cache;
 pub mod cpu;
 pub mod earlycon;
+pub mod finisher;
 pub mod interrupt;
 pub mod vm;
 
@@ -25,6 +26,7 @@ impl Hal for HalImpl {
     }
 
     fn halt(&self, reason: crate::HaltReason) -> ! {
+        finisher::exit(reason.exit_code());
         let _ = sbi::system_reset::system_reset(
             sbi::system_reset::ResetType::WarmReboot,
             match reason {
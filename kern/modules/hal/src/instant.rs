@@ -0,0 +1,131 @@
+use core::{
+    ops::{Add, AddAssign, Sub, SubAssign},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{Cpu, Hal};
+
+/// Latest reading handed out on a platform whose CPUs have clocks of their own.
+static LATEST_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// A reading of the monotonic clock, measured from the start of the platform counter.
+///
+/// Readings never go backwards, whether across the trap path or across CPUs. Arithmetic
+/// saturates rather than overflowing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(Duration);
+
+impl Instant {
+    pub const ZERO: Self = Self(Duration::ZERO);
+    pub const MAX: Self = Self(Duration::MAX);
+
+    pub fn now() -> Self {
+        crate::HalImpl.cpu().now()
+    }
+
+    pub const fn from_duration(since_start: Duration) -> Self {
+        Self(since_start)
+    }
+
+    pub const fn as_duration(self) -> Duration {
+        self.0
+    }
+
+    pub fn from_nanos(nanos: u64) -> Self {
+        Self(Duration::from_nanos(nanos))
+    }
+
+    pub fn from_micros(micros: u64) -> Self {
+        Self(Duration::from_micros(micros))
+    }
+
+    pub fn from_millis(millis: u64) -> Self {
+        Self(Duration::from_millis(millis))
+    }
+
+    pub fn as_nanos(self) -> u128 {
+        self.0.as_nanos()
+    }
+
+    pub fn as_micros(self) -> u128 {
+        self.0.as_micros()
+    }
+
+    pub fn as_millis(self) -> u128 {
+        self.0.as_millis()
+    }
+
+    /// Returns the time from `earlier` to `self`, or zero if `earlier` is later.
+    pub fn duration_since(self, earlier: Self) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    pub fn checked_duration_since(self, earlier: Self) -> Option<Duration> {
+        self.0.checked_sub(earlier.0)
+    }
+
+    pub fn elapsed(self) -> Duration {
+        Self::now().duration_since(self)
+    }
+
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        self.0.checked_add(duration).map(Self)
+    }
+
+    pub fn checked_sub(self, duration: Duration) -> Option<Self> {
+        self.0.checked_sub(duration).map(Self)
+    }
+
+    pub fn saturating_add(self, duration: Duration) -> Self {
+        Self(self.0.saturating_add(duration))
+    }
+
+    pub fn saturating_sub(self, duration: Duration) -> Self {
+        Self(self.0.saturating_sub(duration))
+    }
+
+    /// Makes `reading` monotonic across CPUs whose clocks are not shared, by never handing out
+    /// a reading older than the latest one.
+    pub(crate) fn clamp_unshared(reading: Duration) -> Self {
+        let nanos = u64::try_from(reading.as_nanos()).unwrap_or(u64::MAX);
+        let latest = LATEST_NANOS.fetch_max(nanos, Ordering::SeqCst);
+        Self::from_nanos(latest.max(nanos))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Self;
+
+    fn add(self, duration: Duration) -> Self {
+        self.saturating_add(duration)
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Self;
+
+    fn sub(self, duration: Duration) -> Self {
+        self.saturating_sub(duration)
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Self) -> Duration {
+        self.duration_since(earlier)
+    }
+}
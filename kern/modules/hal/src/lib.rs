@@ -3,10 +3,12 @@
 extern crate alloc;
 
 mod arch;
+mod instant;
 use core::time::Duration;
 
 use alloc::vec::Vec;
 pub use arch::*;
+pub use instant::Instant;
 
 use jrinx_addr::PhysAddr;
 use spin::Once;
@@ -65,8 +67,41 @@ pub trait Cpu: Send + Sync {
         *CPU_TIMEBASE_FREQ.get().unwrap_or(&0)
     }
 
+    /// Reads the free-running counter behind [`Cpu::get_time`], in timebase ticks.
+    fn get_ticks(&self) -> u64;
+
+    /// Reads the cycle counter of the current CPU, which is only comparable on the same CPU.
+    fn get_cycles(&self) -> u64;
+
+    fn ticks_to_time(&self, ticks: u64) -> Duration {
+        match self.timebase_freq() {
+            freq if freq != 0 => {
+                Duration::from_nanos((ticks as u128 * 1_000_000_000u128 / freq as u128) as u64)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
     fn get_time(&self) -> Duration;
 
+    /// Whether all CPUs read one clock, so that their [`Cpu::get_time`] agree.
+    ///
+    /// Ports whose CPUs count on their own must return `false`, and [`Cpu::now`] then keeps
+    /// readings monotonic across CPUs at the cost of a shared atomic.
+    fn has_shared_clock(&self) -> bool {
+        false
+    }
+
+    /// Reads the monotonic clock, comparable across CPUs.
+    fn now(&self) -> Instant {
+        let time = self.get_time();
+        if self.has_shared_clock() {
+            Instant::from_duration(time)
+        } else {
+            Instant::clamp_unshared(time)
+        }
+    }
+
     fn set_timer(&self, next: Duration);
 }
 
@@ -74,6 +109,31 @@ pub trait Earlycon: Send + Sync {
     fn putc(&self, c: u8);
 
     fn getc(&self) -> Option<u8>;
+
+    fn write(&self, bytes: &[u8]) {
+        for &c in bytes {
+            self.putc(c);
+        }
+    }
+
+    /// Whether all bytes written so far have left the console.
+    fn is_drained(&self) -> bool {
+        true
+    }
+
+    /// Whether the console renders ANSI escape sequences, rather than printing them.
+    fn is_color_capable(&self) -> bool {
+        true
+    }
+}
+
+/// UART driven by the kernel, to which the early console writes directly once it is set.
+pub trait ConsoleUart: Send + Sync {
+    fn putc(&self, c: u8);
+
+    fn getc(&self) -> Option<u8>;
+
+    fn is_drained(&self) -> bool;
 }
 
 pub trait Cache: Send + Sync {
@@ -140,7 +200,25 @@ pub trait Vm: Send + Sync {
     fn sync_all(&self);
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HaltReason {
     NormalExit,
     SysFailure,
+    Failure(u32),
+    StackOverflow,
+    WatchdogTimeout,
+    /// A test run over, with the number of tests which failed.
+    TestFailure(u32),
+}
+
+impl HaltReason {
+    /// Returns the exit status the machine reports, where it can: zero for a normal exit, the
+    /// number of failed tests, up to 255, for a test failure, and one otherwise.
+    pub fn exit_code(&self) -> u8 {
+        match *self {
+            Self::NormalExit => 0,
+            Self::TestFailure(failed) => failed.clamp(1, u8::MAX as u32) as u8,
+            _ => 1,
+        }
+    }
 }
@@ -1,28 +1,95 @@
 #![no_std]
+#![feature(used_with_arg)]
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use buddy_system_allocator::LockedHeap;
 use jrinx_addr::VirtAddr;
+use jrinx_error::Result;
+use jrinx_hal::{hal, Cpu, Hal};
+use jrinx_init::kernel_init;
 
 use jrinx_config::{HEAP_ORDER, KHEAP_SIZE};
 
 #[global_allocator]
-static mut HEAP_ALLOCATOR: LockedHeap<HEAP_ORDER> = LockedHeap::new();
+static HEAP_ALLOCATOR: Heap = Heap(LockedHeap::new());
+
+/// CPUs inside a [`forbid_alloc`] section, one bit per CPU.
+static ALLOC_FORBIDDEN: AtomicUsize = AtomicUsize::new(0);
+
+struct Heap(LockedHeap<HEAP_ORDER>);
 
-pub fn init() {
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        check_alloc_allowed();
+        self.0.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+}
+
+#[kernel_init]
+pub fn init() -> Result<()> {
     #[repr(C, align(4096))]
     struct HeapSpace([u8; KHEAP_SIZE]);
     static mut HEAP_SPACE: HeapSpace = HeapSpace([0; KHEAP_SIZE]);
     unsafe {
         HEAP_ALLOCATOR
+            .0
             .lock()
             .init(HEAP_SPACE.0.as_ptr() as usize, KHEAP_SIZE);
     };
+    Ok(())
+}
+
+/// Returns the number of bytes allocated from the heap, and its total size.
+pub fn usage() -> (usize, usize) {
+    let heap = HEAP_ALLOCATOR.0.lock();
+    (heap.stats_alloc_actual(), heap.stats_total_bytes())
 }
 
 pub fn enlarge(region: (VirtAddr, usize)) {
     unsafe {
         HEAP_ALLOCATOR
+            .0
             .lock()
             .add_to_heap(region.0.as_usize(), region.0.as_usize() + region.1);
     }
 }
+
+/// Runs `f`, which must not allocate on the current CPU, as checked in debug builds.
+///
+/// Interrupts must be disabled, since their handlers may allocate.
+pub fn forbid_alloc<R>(f: impl FnOnce() -> R) -> R {
+    let Some(bit) = cpu_bit() else {
+        return f();
+    };
+    let outer = ALLOC_FORBIDDEN.fetch_or(bit, Ordering::SeqCst) & bit != 0;
+    let result = f();
+    if !outer {
+        ALLOC_FORBIDDEN.fetch_and(!bit, Ordering::SeqCst);
+    }
+    result
+}
+
+fn check_alloc_allowed() {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let Some(bit) = cpu_bit() else {
+        return;
+    };
+    // Lift the section first, as reporting the violation may allocate.
+    if ALLOC_FORBIDDEN.fetch_and(!bit, Ordering::SeqCst) & bit != 0 {
+        panic!("heap allocation inside a no-alloc section");
+    }
+}
+
+fn cpu_bit() -> Option<usize> {
+    1usize.checked_shl(hal!().cpu().id() as u32)
+}
@@ -0,0 +1,101 @@
+use proc_macro::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    parse::Parse, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Expr, ItemFn, Lit,
+    LitStr, MetaNameValue, Token,
+};
+
+#[proc_macro_attribute]
+pub fn kernel_init(attr: TokenStream, func: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as KernelInitAttr);
+    let name = attr.name.unwrap_or_else(|| {
+        let pkg_name = std::env::var("CARGO_PKG_NAME").unwrap();
+        LitStr::new(
+            pkg_name.strip_prefix("jrinx-").unwrap_or(&pkg_name),
+            Span::call_site().into(),
+        )
+    });
+    let depends = attr.depends;
+
+    let func = parse_macro_input!(func as ItemFn);
+    let func_attrs = &func.attrs;
+    let func_vis = &func.vis;
+    let func_name = &func.sig.ident;
+    let func_block = &func.block;
+    let func_output = &func.sig.output;
+
+    if !func.sig.inputs.is_empty() || !func.sig.generics.params.is_empty() {
+        return syn::Error::new(func.sig.span(), "kernel init function takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let caller = quote! {
+        #(#func_attrs)*
+        #func_vis fn #func_name() #func_output {
+            static __INIT_STATE: jrinx_init::InitState = jrinx_init::InitState::new();
+
+            #[used(linker)]
+            #[link_section = concat!(".kinit.", #name)]
+            static __INIT_DEF: &jrinx_init::InitDef = &jrinx_init::InitDef::new(
+                #name,
+                &[#(#depends),*],
+                #func_name,
+                &__INIT_STATE,
+            );
+
+            fn __init() #func_output #func_block
+
+            __INIT_STATE.enter()?;
+            __INIT_STATE.leave(__init())
+        }
+    };
+
+    caller.into()
+}
+
+struct KernelInitAttr {
+    name: Option<LitStr>,
+    depends: Vec<LitStr>,
+}
+
+impl Parse for KernelInitAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let pairs: Punctuated<MetaNameValue, Token![,]> = Punctuated::parse_terminated(input)?;
+        let mut attr = KernelInitAttr {
+            name: None,
+            depends: Vec::new(),
+        };
+
+        for pair in pairs.iter() {
+            let ident = pair
+                .path
+                .get_ident()
+                .ok_or(syn::Error::new(pair.path.span(), "ident expected"))?;
+            match ident.to_string().as_str() {
+                "name" => attr.name = Some(lit_str(&pair.value)?),
+                "depends" => {
+                    let Expr::Array(array) = &pair.value else {
+                        return Err(syn::Error::new(pair.value.span(), "array expected"));
+                    };
+                    for elem in array.elems.iter() {
+                        attr.depends.push(lit_str(elem)?);
+                    }
+                }
+                _ => return Err(syn::Error::new(ident.span(), "invalid attribute")),
+            }
+        }
+
+        Ok(attr)
+    }
+}
+
+fn lit_str(expr: &Expr) -> syn::Result<LitStr> {
+    let Expr::Lit(expr_lit) = expr else {
+        return Err(syn::Error::new(expr.span(), "lit expected"));
+    };
+    let Lit::Str(lit_str) = &expr_lit.lit else {
+        return Err(syn::Error::new(expr_lit.lit.span(), "str expected"));
+    };
+    Ok(lit_str.clone())
+}
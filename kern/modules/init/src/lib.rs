@@ -0,0 +1,200 @@
+#![no_std]
+
+use core::{
+    fmt::Display,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+use jrinx_error::{InternalError, Result};
+
+pub use jrinx_init_macro::*;
+
+#[macro_use]
+extern crate log;
+
+const STATE_IDLE: u8 = 0;
+const STATE_RUNNING: u8 = 1;
+const STATE_DONE: u8 = 2;
+const STATE_FAILED: u8 = 3;
+
+static CURRENT_PHASE: AtomicUsize = AtomicUsize::new(0);
+
+#[repr(C)]
+pub struct InitDef {
+    name: &'static str,
+    depends: &'static [&'static str],
+    init: fn() -> Result<()>,
+    state: &'static InitState,
+}
+
+impl InitDef {
+    pub const fn new(
+        name: &'static str,
+        depends: &'static [&'static str],
+        init: fn() -> Result<()>,
+        state: &'static InitState,
+    ) -> Self {
+        Self {
+            name,
+            depends,
+            init,
+            state,
+        }
+    }
+
+    fn ready(&self, phase: usize) -> bool {
+        self.depends
+            .iter()
+            .all(|&dep| find(dep).is_some_and(|dep| dep.state.done_before(phase)))
+    }
+}
+
+/// Completion record of a kernel init function, making it run at most once.
+pub struct InitState {
+    state: AtomicU8,
+    phase: AtomicUsize,
+}
+
+impl Default for InitState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InitState {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(STATE_IDLE),
+            phase: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    pub fn enter(&self) -> Result<()> {
+        self.state
+            .compare_exchange(
+                STATE_IDLE,
+                STATE_RUNNING,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .map(|_| ())
+            .map_err(|_| InternalError::RepeatInitialization)
+    }
+
+    pub fn leave(&self, result: Result<()>) -> Result<()> {
+        self.phase
+            .store(CURRENT_PHASE.load(Ordering::SeqCst), Ordering::SeqCst);
+        self.state.store(
+            if result.is_ok() {
+                STATE_DONE
+            } else {
+                STATE_FAILED
+            },
+            Ordering::SeqCst,
+        );
+        result
+    }
+
+    fn done(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == STATE_DONE
+    }
+
+    fn done_before(&self, phase: usize) -> bool {
+        self.done() && self.phase.load(Ordering::SeqCst) < phase
+    }
+
+    fn settled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) != STATE_IDLE
+    }
+}
+
+/// Runs every registered init function in dependency order.
+///
+/// Init functions run in phases: phase `n` holds those whose dependencies all completed in
+/// earlier phases, so the phases double as the boot milestones. A dependency on an unknown
+/// init function, a name registered twice, or a dependency cycle, halts the boot with a
+/// description of the problem.
+pub fn run_all() {
+    for phase in 0.. {
+        CURRENT_PHASE.store(phase, Ordering::SeqCst);
+
+        let mut progress = false;
+        for def in initdef_iter() {
+            if def.state.settled() || !def.ready(phase) {
+                continue;
+            }
+            if let Err(err) = (def.init)() {
+                panic!("kernel init '{}' failed: {:?}", def.name, err);
+            }
+            progress = true;
+        }
+
+        if !progress {
+            break;
+        }
+        debug!("boot phase {} done: {}", phase, PhaseNames(phase));
+    }
+
+    for (i, def) in initdef_iter().enumerate() {
+        if initdef_iter().take(i).any(|prev| prev.name == def.name) {
+            panic!("kernel init '{}' is registered more than once", def.name);
+        }
+    }
+
+    for def in initdef_iter().filter(|def| !def.state.settled()) {
+        if let Some(dep) = def.depends.iter().find(|&&dep| find(dep).is_none()) {
+            panic!(
+                "kernel init '{}' depends on unknown kernel init '{}'",
+                def.name, dep
+            );
+        }
+    }
+    if initdef_iter().any(|def| !def.state.settled()) {
+        panic!("kernel init dependency cycle among: {}", Unsettled);
+    }
+}
+
+/// Returns the boot phase the init function `name` completed in, if it did.
+pub fn phase_of(name: &str) -> Option<usize> {
+    find(name)
+        .filter(|def| def.state.done())
+        .map(|def| def.state.phase.load(Ordering::SeqCst))
+}
+
+pub fn all() -> impl Iterator<Item = &'static str> {
+    initdef_iter().map(|def| def.name)
+}
+
+fn find(name: &str) -> Option<&'static InitDef> {
+    initdef_iter().find(|def| def.name == name)
+}
+
+fn initdef_iter() -> impl Iterator<Item = &'static InitDef> {
+    (jrinx_layout::_skinit()..jrinx_layout::_ekinit())
+        .step_by(core::mem::size_of::<&InitDef>())
+        .map(|a| unsafe { *(a as *const &InitDef) })
+}
+
+struct PhaseNames(usize);
+
+impl Display for PhaseNames {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let names = initdef_iter().filter(|def| phase_of(def.name) == Some(self.0));
+        for (i, def) in names.enumerate() {
+            write!(f, "{}{}", if i == 0 { "" } else { ", " }, def.name)?;
+        }
+        Ok(())
+    }
+}
+
+struct Unsettled;
+
+impl Display for Unsettled {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let names = initdef_iter().filter(|def| !def.state.settled());
+        for (i, def) in names.enumerate() {
+            write!(f, "{}{}", if i == 0 { "" } else { ", " }, def.name)?;
+        }
+        Ok(())
+    }
+}
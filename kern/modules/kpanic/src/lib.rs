@@ -0,0 +1,131 @@
+#![no_std]
+
+use core::fmt::Display;
+
+use spin::Mutex;
+
+/// Maximum number of key-value words a [`PanicPayload`] carries.
+pub const PANIC_WORDS: usize = 4;
+
+static PAYLOAD: Mutex<Option<PanicPayload>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum PanicCode {
+    SchedInvariant = 1,
+    InspectorInvariant,
+    ExecutorInvariant,
+    RuntimeInvariant,
+}
+
+impl PanicCode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SchedInvariant => "sched-invariant",
+            Self::InspectorInvariant => "inspector-invariant",
+            Self::ExecutorInvariant => "executor-invariant",
+            Self::RuntimeInvariant => "runtime-invariant",
+        }
+    }
+}
+
+/// Converts a value into a word of a [`PanicPayload`].
+pub trait PanicWord {
+    fn panic_word(&self) -> u64;
+}
+
+macro_rules! impl_panic_word {
+    ($($ty:ty),*) => {
+        $(
+            impl PanicWord for $ty {
+                fn panic_word(&self) -> u64 {
+                    *self as u64
+                }
+            }
+        )*
+    };
+}
+
+impl_panic_word!(u8, u16, u32, u64, usize, bool);
+
+/// Machine-readable context of a panic raised through [`kpanic!`].
+#[derive(Debug, Clone, Copy)]
+pub struct PanicPayload {
+    code: PanicCode,
+    words: [(&'static str, u64); PANIC_WORDS],
+    len: usize,
+}
+
+impl PanicPayload {
+    pub fn new<const N: usize>(code: PanicCode, words: [(&'static str, u64); N]) -> Self {
+        const { assert!(N <= PANIC_WORDS, "too many panic payload words") };
+
+        let mut payload = Self {
+            code,
+            words: [("", 0); PANIC_WORDS],
+            len: N,
+        };
+        payload.words[..N].copy_from_slice(&words);
+        payload
+    }
+
+    pub fn code(&self) -> PanicCode {
+        self.code
+    }
+
+    pub fn words(&self) -> &[(&'static str, u64)] {
+        &self.words[..self.len]
+    }
+}
+
+impl Display for PanicPayload {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "code={}({})", self.code.name(), self.code as u32)?;
+        for (key, value) in self.words() {
+            write!(f, " {}={:#x}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records `payload` for the panic handler, unless an earlier panic already recorded one.
+pub fn record(payload: PanicPayload) {
+    if let Some(mut slot) = PAYLOAD.try_lock() {
+        slot.get_or_insert(payload);
+    }
+}
+
+/// Takes the payload recorded by the first [`kpanic!`], if any.
+pub fn take() -> Option<PanicPayload> {
+    PAYLOAD.try_lock().and_then(|mut slot| slot.take())
+}
+
+/// Panics with a [`PanicPayload`] made of `code` and up to [`PANIC_WORDS`] key-value words,
+/// followed by the usual panic message.
+///
+/// ```ignore
+/// kpanic!(
+///     code = PanicCode::SchedInvariant,
+///     inspector = id,
+///     "queue contains unregistered inspector {id:?}"
+/// );
+/// ```
+#[macro_export]
+macro_rules! kpanic {
+    (code = $code:expr, $($rest:tt)+) => {
+        $crate::kpanic!(@words $code, [], $($rest)+)
+    };
+
+    (@words $code:expr, [$($words:tt)*], $key:ident = $value:expr, $($rest:tt)+) => {
+        $crate::kpanic!(
+            @words $code,
+            [$($words)* (stringify!($key), $crate::PanicWord::panic_word(&$value)),],
+            $($rest)+
+        )
+    };
+
+    (@words $code:expr, [$($words:tt)*], $($arg:tt)+) => {{
+        $crate::record($crate::PanicPayload::new($code, [$($words)*]));
+        panic!($($arg)+)
+    }};
+}
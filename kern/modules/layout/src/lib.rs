@@ -17,6 +17,9 @@ def_ld_sym!(_etext);
 def_ld_sym!(_srodata);
 def_ld_sym!(_erodata);
 
+def_ld_sym!(_sksyms);
+def_ld_sym!(_eksyms);
+
 def_ld_sym!(_spercpu);
 def_ld_sym!(_epercpu);
 
@@ -26,6 +29,9 @@ def_ld_sym!(_edata);
 def_ld_sym!(_sbss);
 def_ld_sym!(_ebss);
 
+def_ld_sym!(_sstack);
+def_ld_sym!(_estack);
+
 def_ld_sym!(_end);
 
 def_ld_sym!(_sdev);
@@ -33,3 +39,15 @@ def_ld_sym!(_edev);
 
 def_ld_sym!(_stest);
 def_ld_sym!(_etest);
+
+def_ld_sym!(_sbench);
+def_ld_sym!(_ebench);
+
+def_ld_sym!(_sshell);
+def_ld_sym!(_eshell);
+
+def_ld_sym!(_stgroup);
+def_ld_sym!(_etgroup);
+
+def_ld_sym!(_skinit);
+def_ld_sym!(_ekinit);
@@ -5,7 +5,7 @@ use core::cmp;
 use elf::{abi::PT_LOAD, endian::AnyEndian, segment::ProgramHeader, ElfBytes};
 use jrinx_addr::VirtAddr;
 use jrinx_config::PAGE_SIZE;
-use jrinx_error::{InternalError, Result};
+use jrinx_error::{ContextError, InternalError, Result, ResultExt};
 
 pub struct ElfLoader<'elf, 'a> {
     elf: &'elf ElfBytes<'a, AnyEndian>,
@@ -16,18 +16,20 @@ impl<'elf, 'a> ElfLoader<'elf, 'a> {
         Self { elf }
     }
 
-    pub fn load<F>(&self, mut loader: F) -> Result<()>
+    pub fn load<F>(&self, mut loader: F) -> core::result::Result<(), ContextError>
     where
         F: FnMut(&ElfBytes<'_, AnyEndian>, &ProgramHeader, VirtAddr, usize, usize) -> Result<()>,
     {
         for seg_header in self
             .elf
             .segments()
-            .ok_or(InternalError::ElfParseError)?
+            .ok_or(InternalError::ElfParseError)
+            .context("read program headers")?
             .iter()
             .filter(|seg_header| seg_header.p_type == PT_LOAD)
         {
-            self.load_segment(&mut loader, &seg_header)?;
+            self.load_segment(&mut loader, &seg_header)
+                .context("load segment")?;
         }
 
         Ok(())
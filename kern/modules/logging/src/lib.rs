@@ -1,58 +1,189 @@
 #![no_std]
+#![feature(used_with_arg)]
 
 extern crate alloc;
 
-use core::fmt::Write;
+pub mod ring;
 
-use alloc::{
-    fmt, format,
-    string::{String, ToString},
+use core::{
+    fmt::{self, Display, Write},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
 };
-use jrinx_error::InternalError;
+
+use alloc::{borrow::ToOwned, format, string::String, vec::Vec};
+use jrinx_error::{InternalError, Result};
 use jrinx_hal::{hal, Cpu, Earlycon, Hal, Interrupt};
+use jrinx_init::kernel_init;
 use jrinx_multitask::{
-    executor::Executor,
-    inspector::Inspector,
+    executor::{Executor, ExecutorId},
+    inspector::{Inspector, InspectorId},
     runtime::{Runtime, RuntimeStatus},
 };
-use jrinx_util::color;
-use spin::Mutex;
-
-#[cfg(feature = "colorful")]
-macro_rules! with_color {
-    ($color:expr, $restore_color:expr, $($arg:tt)*) => {
-        format_args!(
-            "\u{1B}[{color}m{arg}\u{1B}[{restore}m",
-            color = $color as u8,
-            arg = format_args!($($arg)*),
-            restore = $restore_color as u8,
-        )
-    };
-}
-
-#[cfg(not(feature = "colorful"))]
-macro_rules! with_color {
-    ($color:expr, $restore_color:expr, $($arg:tt)*) => {{
-        $color as u8;
-        $restore_color as u8;
-        format_args!($($arg)*)
-    }};
+use jrinx_util::color::{ColorCode, ColorSpec};
+use log::{Level, LevelFilter};
+use spin::{Mutex, RwLock};
+
+/// Size of the buffer a log record is formatted into, longer records are truncated.
+pub const LOG_BUFFER_SIZE: usize = 1024;
+
+/// Ends a truncated log record.
+pub const TRUNCATION_MARK: &str = "…";
+
+/// Whether records are written byte by byte, waiting for each, as the panic path requires.
+static SYNCHRONOUS: AtomicBool = AtomicBool::new(false);
+
+/// Most verbose level logged by targets without a filter of their own.
+static LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+
+/// Most verbose levels logged by the targets under the modules paired with them.
+static FILTERS: RwLock<Vec<(String, LevelFilter)>> = RwLock::new(Vec::new());
+
+/// Whether records are colored, as far as the console allows.
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Colors of the levels of records, from [`Level::Error`] to [`Level::Trace`].
+static COLORS: RwLock<[ColorSpec; 5]> = RwLock::new([
+    ColorSpec::fg(ColorCode::Red),
+    ColorSpec::fg(ColorCode::Yellow),
+    ColorSpec::fg(ColorCode::Green),
+    ColorSpec::fg(ColorCode::Cyan),
+    ColorSpec::fg(ColorCode::Magenta),
+]);
+
+/// Receives everything written to the console along with it, if installed.
+static SINK: RwLock<Option<fn(&str)>> = RwLock::new(None);
+
+/// Time of a record since boot, as `seconds.microseconds`, or `?.??????` if the clock is not
+/// calibrated yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp(pub Option<Duration>);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        if hal!().cpu().timebase_freq() == 0 {
+            Self(None)
+        } else {
+            Self(Some(hal!().cpu().now().as_duration()))
+        }
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(time) => {
+                let micros = time.as_micros();
+                write!(f, "{:>6}.{:06}", micros / 1000000, micros % 1000000)
+            }
+            None => write!(f, "{:>6}.??????", '?'),
+        }
+    }
+}
+
+/// Text written in `color`, then in `restore`, or plainly if `enabled` does not hold.
+struct Colored<'a> {
+    enabled: bool,
+    color: ColorSpec,
+    restore: ColorSpec,
+    args: fmt::Arguments<'a>,
+}
+
+impl Display for Colored<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.enabled {
+            write!(f, "{}{}{}", self.color, self.args, self.restore)
+        } else {
+            f.write_fmt(self.args)
+        }
+    }
 }
 
 struct Logger;
 
+/// Writes to the console and to the sink, if one is installed.
+struct Tee(Option<fn(&str)>);
+
+impl Write for Tee {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        Logger.write_str(s)?;
+        if let Some(sink) = self.0 {
+            sink(s);
+        }
+        Ok(())
+    }
+}
+
+/// Buffer on the stack of the logging CPU, so that logging works before the heap exists, and
+/// never allocates after.
+struct Staging<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> Staging<N> {
+    fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only whole characters are staged.
+        unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+}
+
+impl<const N: usize> Write for Staging<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Ok(());
+        }
+        let room = N - self.len;
+        if s.len() <= room.saturating_sub(TRUNCATION_MARK.len()) {
+            self.buf[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+            self.len += s.len();
+            return Ok(());
+        }
+
+        let mut cut = room.saturating_sub(TRUNCATION_MARK.len()).min(s.len());
+        while !s.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        for part in [&s[..cut], TRUNCATION_MARK] {
+            self.buf[self.len..self.len + part.len()].copy_from_slice(part.as_bytes());
+            self.len += part.len();
+        }
+        self.truncated = true;
+        Ok(())
+    }
+}
+
+impl Logger {
+    fn write_bytes(&self, bytes: &[u8]) {
+        if SYNCHRONOUS.load(Ordering::Relaxed) {
+            for &b in bytes {
+                hal!().earlycon().putc(b);
+            }
+        } else {
+            hal!().earlycon().write(bytes);
+        }
+    }
+}
+
 impl Write for Logger {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for b in s.bytes() {
-            hal!().earlycon().putc(b);
-        }
+        self.write_bytes(s.as_bytes());
         Ok(())
     }
 }
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= log::max_level()
+        metadata.level() <= level_of(metadata.target())
     }
 
     fn log(&self, record: &log::Record) {
@@ -62,72 +193,283 @@ impl log::Log for Logger {
         }
 
         let cpu_id = hal!().cpu().id();
-        let cpu_time = hal!().cpu().get_time();
+        let time = Timestamp::now();
         let level = record.level();
-        let color = match level {
-            log::Level::Error => color::ColorCode::Red,
-            log::Level::Warn => color::ColorCode::Yellow,
-            log::Level::Info => color::ColorCode::Green,
-            log::Level::Debug => color::ColorCode::Cyan,
-            log::Level::Trace => color::ColorCode::Magenta,
-        };
+        let color = color_of(level);
+        let enabled = is_color_enabled();
+        let plain = ColorSpec::fg(ColorCode::White);
+
+        hal!().interrupt().with_saved_off(|| {
+            jrinx_heap::forbid_alloc(|| {
+                let mut message = Staging::<LOG_BUFFER_SIZE>::new();
+                let _ = message.write_fmt(*record.args());
+                let kernel_state = KernelState::analyse(cpu_id);
 
-        let kernel_state = analyse_kernel_state();
-        fmt::format(*record.args()).split('\n').for_each(|args| {
-            hal!().interrupt().with_saved_off(|| {
                 let mutex = MUTEX.lock();
-                Logger.write_fmt(with_color! {
-                    color::ColorCode::White,
-                    color::ColorCode::White,
-                    "[ {time} cpu#{id} {level} ] ( {kernel_state} ) {args}\n",
-                    time = {
-                        let micros = cpu_time.as_micros();
-                        format_args!("{s:>6}.{us:06}", s = micros / 1000000, us = micros % 1000000)
-                    },
-                    id = cpu_id,
-                    level = with_color!(color, color::ColorCode::White, "{:>5}", level),
-                    kernel_state = with_color!(color::ColorCode::Blue, color::ColorCode::White, "{:^14}", kernel_state),
-                    args = with_color!(color::ColorCode::White, color::ColorCode::White, "{}", args),
-                }).unwrap();
+                let mut tee = Tee(*SINK.read());
+                message.as_str().split('\n').for_each(|args| {
+                    ring::push(time, cpu_id, level, args);
+                    tee.write_fmt(format_args!(
+                        "{}",
+                        Colored {
+                            enabled,
+                            color: plain,
+                            restore: ColorSpec::default(),
+                            args: format_args!(
+                                "[ {time} cpu#{id} {level} ] ( {kernel_state} ) {args}\n",
+                                id = cpu_id,
+                                level = Colored {
+                                    enabled,
+                                    color,
+                                    restore: plain,
+                                    args: format_args!("{:>5}", level),
+                                },
+                                kernel_state = Colored {
+                                    enabled,
+                                    color: ColorSpec::fg(ColorCode::Blue),
+                                    restore: plain,
+                                    args: format_args!("{:^14}", kernel_state),
+                                },
+                            ),
+                        }
+                    ))
+                    .unwrap();
+                });
                 core::hint::black_box(mutex);
             });
         });
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        flush_all();
+    }
+}
+
+pub fn flush_all() {
+    const FLUSH_TIMEOUT: Duration = Duration::from_millis(100);
+
+    let deadline = hal!().cpu().get_time() + FLUSH_TIMEOUT;
+    while !hal!().earlycon().is_drained() {
+        if hal!().cpu().get_time() >= deadline {
+            // Straight to the device, bypassing the logger.
+            hal!()
+                .earlycon()
+                .write(b"\n[ console flush timed out, output may be truncated ]\n");
+            return;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// Returns the most verbose level logged by targets without a filter of their own.
+pub fn level() -> LevelFilter {
+    LEVEL_FILTERS[LEVEL.load(Ordering::Relaxed)]
+}
+
+/// Sets the most verbose level logged by targets without a filter of their own.
+pub fn set_level(level: LevelFilter) {
+    LEVEL.store(level as usize, Ordering::Relaxed);
+    update_max_level(&FILTERS.read());
+}
+
+/// Replaces the filters, each setting the most verbose level logged by the targets under a
+/// module, such as `jrinx_multitask` for `jrinx_multitask::runtime`. The filter of the
+/// innermost module applies.
+pub fn set_filters(filters: impl IntoIterator<Item = (String, LevelFilter)>) {
+    let filters = filters.into_iter().collect();
+    hal!().interrupt().with_saved_off(|| {
+        let mut guard = FILTERS.write();
+        *guard = filters;
+        update_max_level(&guard);
+    });
+}
+
+/// Returns the filter of the targets under `module` itself, if it has one.
+pub fn target_filter(module: &str) -> Option<LevelFilter> {
+    FILTERS
+        .read()
+        .iter()
+        .find(|(filtered, _)| filtered == module)
+        .map(|&(_, filter)| filter)
+}
+
+/// Sets the most verbose level logged by the targets under `module`, replacing its filter if it
+/// has one.
+pub fn set_target_filter(module: &str, filter: LevelFilter) {
+    let mut entry = Some((module.to_owned(), filter));
+    hal!().interrupt().with_saved_off(|| {
+        let mut guard = FILTERS.write();
+        match guard.iter_mut().find(|(filtered, _)| filtered == module) {
+            Some((_, old)) => *old = filter,
+            None => guard.push(entry.take().unwrap()),
+        }
+        update_max_level(&guard);
+    });
+}
+
+/// Removes the filter of the targets under `module`, which then log as their parent module does.
+pub fn clear_target_filter(module: &str) {
+    hal!().interrupt().with_saved_off(|| {
+        let mut guard = FILTERS.write();
+        if let Some(index) = guard.iter().position(|(filtered, _)| filtered == module) {
+            guard.swap_remove(index);
+        }
+        update_max_level(&guard);
+    });
+}
+
+/// Runs `f` with the targets under `module` logging up to `filter`, then gives `module` back its
+/// former filter.
+pub fn with_log_level<R>(module: &str, filter: LevelFilter, f: impl FnOnce() -> R) -> R {
+    let former = target_filter(module);
+    set_target_filter(module, filter);
+    let result = f();
+    match former {
+        Some(former) => set_target_filter(module, former),
+        None => clear_target_filter(module),
+    }
+    result
+}
+
+/// Returns the most verbose level logged by `target`.
+fn level_of(target: &str) -> LevelFilter {
+    FILTERS
+        .read()
+        .iter()
+        .filter(|(module, _)| {
+            target
+                .strip_prefix(module.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+        .max_by_key(|(module, _)| module.len())
+        .map_or_else(level, |&(_, filter)| filter)
+}
+
+/// Lets records through the macros of `log` up to the most verbose level any target logs.
+fn update_max_level(filters: &[(String, LevelFilter)]) {
+    let max = filters
+        .iter()
+        .fold(level(), |max, &(_, filter)| max.max(filter));
+    log::set_max_level(max);
 }
 
-pub fn init() {
+const LEVEL_FILTERS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+/// Returns whether records are colored, which takes the `colorful` feature, a console
+/// rendering colors, and colors not having been turned off.
+pub fn is_color_enabled() -> bool {
+    cfg!(feature = "colorful")
+        && COLOR.load(Ordering::Relaxed)
+        && hal!().earlycon().is_color_capable()
+}
+
+/// Turns the coloring of records on or off.
+pub fn set_color_enabled(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns the color of records of `level`.
+pub fn color_of(level: Level) -> ColorSpec {
+    COLORS.read()[level as usize - 1]
+}
+
+/// Sets the colors of records of the levels paired with them, the other levels keeping theirs.
+pub fn set_colors(colors: impl IntoIterator<Item = (Level, ColorSpec)>) {
+    let colors = colors.into_iter().collect::<Vec<_>>();
+    hal!().interrupt().with_saved_off(|| {
+        let mut guard = COLORS.write();
+        for (level, color) in colors {
+            guard[level as usize - 1] = color;
+        }
+    });
+}
+
+/// Installs `sink` to receive everything the logger writes along with the console, or
+/// uninstalls it.
+///
+/// The sink is called with records serialized and allocation forbidden, so that it must write
+/// into storage of its own, such as to capture records in tests.
+pub fn set_sink(sink: Option<fn(&str)>) {
+    hal!().interrupt().with_saved_off(|| *SINK.write() = sink);
+}
+
+/// Makes all further records written synchronously, for the panic path.
+pub fn set_synchronous() {
+    SYNCHRONOUS.store(true, Ordering::Relaxed);
+}
+
+#[kernel_init]
+pub fn init() -> Result<()> {
     static LOGGER: Logger = Logger;
-    log::set_logger(&LOGGER).unwrap();
+    log::set_logger(&LOGGER).map_err(|_| InternalError::RepeatInitialization)?;
     if let Some(level) = option_env!("LOGLEVEL") {
-        log::set_max_level(level.parse().unwrap());
+        set_level(level.parse().unwrap());
     } else {
-        log::set_max_level(log::LevelFilter::Info);
-    }
-}
-
-pub fn set_max_level(level: log::LevelFilter) {
-    log::set_max_level(level);
-}
-
-fn analyse_kernel_state() -> String {
-    if let Ok(state) = match Executor::with_current(|ex| ex.id()) {
-        Ok(id) => Ok(format!("executor#{}", id)),
-        Err(err) => Err(err),
-    } {
-        state
-    } else if let Ok(state) = match Inspector::with_current(|is| is.id()) {
-        Ok(id) => Ok(format!("inspector#{}", id)),
-        Err(err) => Err(err),
-    } {
-        state
-    } else if let Ok(state) = match Runtime::with_current(|rt| rt.status()) {
-        RuntimeStatus::Unused => Err(InternalError::InvalidRuntimeStatus),
-        _ => Ok("runtime".to_string()),
-    } {
-        state
-    } else {
-        "bootstrap".to_string()
+        set_level(LevelFilter::Info);
+    }
+    Ok(())
+}
+
+/// Registers the tunable `log.level`, the most verbose level logged.
+#[kernel_init(name = "log-tunables", depends = ["heap"])]
+fn register_tunables() -> Result<()> {
+    jrinx_tunable::register(
+        "log.level",
+        level(),
+        |&level| {
+            if level <= log::STATIC_MAX_LEVEL {
+                Ok(())
+            } else {
+                Err(format!(
+                    "levels above {} are compiled out",
+                    log::STATIC_MAX_LEVEL
+                ))
+            }
+        },
+        |&level| set_level(level),
+    )
+    .map_err(|_| InternalError::RepeatInitialization)
+}
+
+enum KernelState {
+    Executor(ExecutorId),
+    Inspector(InspectorId),
+    Runtime,
+    Bootstrap,
+}
+
+impl KernelState {
+    fn analyse(cpu_id: usize) -> Self {
+        if !jrinx_percpu::local_pointer_ready(cpu_id) {
+            Self::Bootstrap
+        } else if let Ok(id) = Executor::with_current(|ex| ex.id()) {
+            Self::Executor(id)
+        } else if let Ok(id) = Inspector::with_current(|is| is.id()) {
+            Self::Inspector(id)
+        } else if Runtime::with_current(|rt| rt.status()) != RuntimeStatus::Unused {
+            Self::Runtime
+        } else {
+            Self::Bootstrap
+        }
+    }
+}
+
+impl Display for KernelState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut state = Staging::<32>::new();
+        let _ = match self {
+            Self::Executor(id) => write!(state, "executor#{}", id),
+            Self::Inspector(id) => write!(state, "inspector#{}", id),
+            Self::Runtime => write!(state, "runtime"),
+            Self::Bootstrap => write!(state, "bootstrap"),
+        };
+        f.pad(state.as_str())
     }
 }
@@ -0,0 +1,295 @@
+//! Ring of the latest records, kept in memory for when the console is slow or the lines of
+//! interest scrolled away.
+//!
+//! Records are stored without colors, each line with a sequence number of its own, so that
+//! readers tell overwritten records by the gap in sequence numbers.
+
+use core::{
+    fmt::{self, Display, Write},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use alloc::{string::String, vec::Vec};
+use jrinx_hal::{hal, Hal, Interrupt};
+use log::Level;
+use spin::{Mutex, MutexGuard};
+
+use crate::{Logger, Timestamp, LOG_BUFFER_SIZE, SYNCHRONOUS};
+
+/// Bytes of the ring of the kernel.
+pub const LOG_RING_SIZE: usize = 64 * 1024;
+
+/// Times the panic path tries the lock of the ring, which a CPU stopped while writing holds.
+const PANIC_LOCK_SPINS: usize = 1 << 16;
+
+const HEADER_SIZE: usize = 8 + 8 + 4 + 1 + 2;
+
+const NO_TIME: u64 = u64::MAX;
+
+const LEVELS: [Level; 5] = [
+    Level::Error,
+    Level::Warn,
+    Level::Info,
+    Level::Debug,
+    Level::Trace,
+];
+
+static RING: Mutex<Ring<LOG_RING_SIZE>> = Mutex::new(Ring::new());
+
+/// Line of a record retained by a ring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub seq: u64,
+    pub time: Timestamp,
+    pub cpu_id: usize,
+    pub level: Level,
+    pub text: String,
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} [ {} cpu#{} {:>5} ] {}",
+            self.seq, self.time, self.cpu_id, self.level, self.text
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    seq: u64,
+    micros: u64,
+    cpu_id: u32,
+    level: u8,
+    len: u16,
+}
+
+impl Header {
+    fn to_bytes(self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..8].copy_from_slice(&self.seq.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.micros.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.cpu_id.to_le_bytes());
+        bytes[20] = self.level;
+        bytes[21..23].copy_from_slice(&self.len.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Self {
+        Self {
+            seq: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            micros: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            cpu_id: u32::from_le_bytes(bytes[16..20].try_into().unwrap()),
+            level: bytes[20],
+            len: u16::from_le_bytes(bytes[21..23].try_into().unwrap()),
+        }
+    }
+
+    fn size(&self) -> usize {
+        HEADER_SIZE + self.len as usize
+    }
+
+    fn time(&self) -> Timestamp {
+        Timestamp((self.micros != NO_TIME).then(|| Duration::from_micros(self.micros)))
+    }
+
+    fn level(&self) -> Level {
+        LEVELS[self.level as usize - 1]
+    }
+}
+
+/// Ring of `N` bytes, where each record is written whole into room reserved for it by
+/// overwriting the oldest records.
+pub struct Ring<const N: usize> {
+    buf: [u8; N],
+    start: usize,
+    len: usize,
+    first_seq: u64,
+    next_seq: u64,
+}
+
+impl<const N: usize> Ring<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            start: 0,
+            len: 0,
+            first_seq: 0,
+            next_seq: 0,
+        }
+    }
+
+    /// Returns the sequence number of the oldest record retained.
+    pub fn first_seq(&self) -> u64 {
+        self.first_seq
+    }
+
+    /// Returns the sequence number the next record pushed gets.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Pushes a record, truncated to fit the ring, and returns its sequence number.
+    pub fn push(&mut self, time: Timestamp, cpu_id: usize, level: Level, text: &str) -> u64 {
+        let mut cut = text.len().min(N - HEADER_SIZE).min(u16::MAX as usize);
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let text = &text[..cut];
+        let header = Header {
+            seq: self.next_seq,
+            micros: time.0.map_or(NO_TIME, |time| time.as_micros() as u64),
+            cpu_id: cpu_id as u32,
+            level: level as u8,
+            len: text.len() as u16,
+        };
+
+        while N - self.len < header.size() {
+            let oldest = self.header_at(self.start);
+            self.start = (self.start + oldest.size()) % N;
+            self.len -= oldest.size();
+            self.first_seq += 1;
+        }
+        let end = (self.start + self.len) % N;
+        self.copy_in(end, &header.to_bytes());
+        self.copy_in((end + HEADER_SIZE) % N, text.as_bytes());
+        self.len += header.size();
+        self.next_seq += 1;
+        header.seq
+    }
+
+    /// Returns the records retained from sequence number `seq` on.
+    pub fn read_since(&self, seq: u64) -> Vec<Record> {
+        let mut records = Vec::new();
+        self.visit(seq, |header, [head, tail]| {
+            let mut text = Vec::with_capacity(head.len() + tail.len());
+            text.extend_from_slice(head);
+            text.extend_from_slice(tail);
+            records.push(Record {
+                seq: header.seq,
+                time: header.time(),
+                cpu_id: header.cpu_id as usize,
+                level: header.level(),
+                text: String::from_utf8(text).unwrap(),
+            });
+        });
+        records
+    }
+
+    /// Calls `f` on the records retained from sequence number `seq` on, with their text in
+    /// two parts, split where the ring wraps.
+    fn visit(&self, seq: u64, mut f: impl FnMut(Header, [&[u8]; 2])) {
+        let mut pos = self.start;
+        let mut remaining = self.len;
+        while remaining > 0 {
+            let header = self.header_at(pos);
+            if header.seq >= seq {
+                let text = (pos + HEADER_SIZE) % N;
+                let len = header.len as usize;
+                let head = &self.buf[text..(text + len).min(N)];
+                let tail = &self.buf[..(text + len).saturating_sub(N)];
+                f(header, [head, tail]);
+            }
+            pos = (pos + header.size()) % N;
+            remaining -= header.size();
+        }
+    }
+
+    fn header_at(&self, pos: usize) -> Header {
+        let mut bytes = [0; HEADER_SIZE];
+        self.copy_out(pos, &mut bytes);
+        Header::from_bytes(&bytes)
+    }
+
+    fn copy_in(&mut self, pos: usize, bytes: &[u8]) {
+        let head = bytes.len().min(N - pos);
+        self.buf[pos..pos + head].copy_from_slice(&bytes[..head]);
+        self.buf[..bytes.len() - head].copy_from_slice(&bytes[head..]);
+    }
+
+    fn copy_out(&self, pos: usize, bytes: &mut [u8]) {
+        let head = bytes.len().min(N - pos);
+        bytes[..head].copy_from_slice(&self.buf[pos..pos + head]);
+        let len = bytes.len();
+        bytes[head..].copy_from_slice(&self.buf[..len - head]);
+    }
+}
+
+impl<const N: usize> Default for Ring<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pushes a line of a record into the ring of the kernel.
+///
+/// Once records are written synchronously, the ring is given up on rather than waited for
+/// long, since the panic path may have stopped its holder.
+pub(crate) fn push(time: Timestamp, cpu_id: usize, level: Level, text: &str) {
+    hal!().interrupt().with_saved_off(|| {
+        let ring = if SYNCHRONOUS.load(Ordering::Relaxed) {
+            lock_for_panic()
+        } else {
+            Some(RING.lock())
+        };
+        if let Some(mut ring) = ring {
+            ring.push(time, cpu_id, level, text);
+        }
+    });
+}
+
+/// Returns the sequence number the next record of the kernel gets.
+pub fn next_seq() -> u64 {
+    hal!().interrupt().with_saved_off(|| RING.lock().next_seq())
+}
+
+/// Returns the records retained by the ring of the kernel from sequence number `seq` on, which
+/// start after `seq` if the records in between were overwritten.
+pub fn read_since(seq: u64) -> impl Iterator<Item = Record> {
+    hal!()
+        .interrupt()
+        .with_saved_off(|| RING.lock().read_since(seq))
+        .into_iter()
+}
+
+/// Writes the last `records` records of the ring of the kernel to the console, without
+/// allocating, for the panic path.
+pub fn dump_tail(records: usize) {
+    let Some(ring) = lock_for_panic() else {
+        let _ = Logger.write_str("[ log ring is locked, not dumped ]\n");
+        return;
+    };
+    let since = ring.next_seq().saturating_sub(records as u64);
+    let _ = writeln!(
+        Logger,
+        "[ last {} records of the log ring ]",
+        ring.next_seq() - since.max(ring.first_seq())
+    );
+    ring.visit(since, |header, [head, tail]| {
+        let _ = write!(
+            Logger,
+            "#{} [ {} cpu#{} {:>5} ] ",
+            header.seq,
+            header.time(),
+            header.cpu_id,
+            header.level()
+        );
+        Logger.write_bytes(head);
+        Logger.write_bytes(tail);
+        let _ = Logger.write_str("\n");
+    });
+}
+
+fn lock_for_panic() -> Option<MutexGuard<'static, Ring<LOG_RING_SIZE>>> {
+    for _ in 0..PANIC_LOCK_SPINS {
+        if let Some(ring) = RING.try_lock() {
+            return Some(ring);
+        }
+        core::hint::spin_loop();
+    }
+    None
+}
+
+const _: () = assert!(LOG_RING_SIZE > HEADER_SIZE + LOG_BUFFER_SIZE);
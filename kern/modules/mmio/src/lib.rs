@@ -0,0 +1,171 @@
+#![no_std]
+
+mod macros;
+
+use core::marker::PhantomData;
+
+use jrinx_addr::{PhysAddr, VirtAddr, VirtAddrRange};
+use jrinx_config::{MMIO_REGION, PAGE_SIZE};
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::{hal, Hal, Vm};
+use jrinx_paging::{GenericPagePerm, PagePerm};
+use jrinx_vmm::KERN_PAGE_TABLE;
+use spin::Mutex;
+
+static MMIO_NEXT: Mutex<usize> = Mutex::new(MMIO_REGION.addr);
+
+pub trait MmioValue: Copy + private::Sealed {
+    fn from_le(raw: Self) -> Self;
+    fn to_le(self) -> Self;
+}
+
+macro_rules! impl_mmio_value {
+    ($($ty:ty),+) => {
+        $(
+            impl private::Sealed for $ty {}
+
+            impl MmioValue for $ty {
+                fn from_le(raw: Self) -> Self {
+                    <$ty>::from_le(raw)
+                }
+
+                fn to_le(self) -> Self {
+                    <$ty>::to_le(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_mmio_value!(u8, u16, u32, u64);
+
+mod private {
+    pub trait Sealed {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmioRegion {
+    base: VirtAddr,
+    len: usize,
+}
+
+impl MmioRegion {
+    /// Maps the registers at `phys` into the kernel half of every address space, at the next
+    /// free address of [`MMIO_REGION`].
+    pub fn map(phys: PhysAddr, len: usize) -> Result<Self> {
+        let start = phys.align_page_down();
+        let end = phys
+            .checked_add(len)
+            .and_then(|end| end.align_up(PAGE_SIZE))
+            .ok_or(InternalError::InvalidLength(len))?;
+
+        let mut next = MMIO_NEXT.lock();
+        let range = VirtAddrRange::from_len(VirtAddr::new(*next), end - start)
+            .filter(|range| range.end().as_usize() <= MMIO_REGION.addr + MMIO_REGION.len)
+            .ok_or(InternalError::NotEnoughMem)?;
+        KERN_PAGE_TABLE.write().map_range_best_fit(
+            range,
+            start,
+            PagePerm::G | PagePerm::W | PagePerm::R,
+        )?;
+        *next = range.end().as_usize();
+        hal!().vm().sync_all();
+
+        Ok(Self {
+            base: range.start() + phys.page_offset(),
+            len,
+        })
+    }
+
+    /// # Safety
+    ///
+    /// `base` must point to `len` bytes which stay mapped for the lifetime of the region.
+    pub const unsafe fn from_raw(base: VirtAddr, len: usize) -> Self {
+        Self { base, len }
+    }
+
+    pub fn base(&self) -> VirtAddr {
+        self.base
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn read<T: MmioValue>(&self, offset: usize) -> T {
+        T::from_le(unsafe { core::ptr::read_volatile(self.ptr::<T>(offset)) })
+    }
+
+    pub fn write<T: MmioValue>(&self, offset: usize, val: T) {
+        unsafe { core::ptr::write_volatile(self.ptr::<T>(offset), val.to_le()) }
+    }
+
+    pub fn register<T: MmioValue, A>(&self, offset: usize) -> Register<'_, T, A> {
+        self.ptr::<T>(offset);
+        Register {
+            region: self,
+            offset,
+            _marker: PhantomData,
+        }
+    }
+
+    fn ptr<T>(&self, offset: usize) -> *mut T {
+        let size = core::mem::size_of::<T>();
+        assert!(
+            offset % size == 0,
+            "misaligned {size}-byte mmio access at offset {offset:#x}"
+        );
+        assert!(
+            offset + size <= self.len,
+            "mmio access at offset {offset:#x} out of region of {:#x} bytes",
+            self.len
+        );
+        (self.base + offset).as_usize() as *mut T
+    }
+}
+
+pub struct ReadOnly;
+pub struct WriteOnly;
+pub struct ReadWrite;
+
+pub trait Readable {}
+pub trait Writable {}
+
+impl Readable for ReadOnly {}
+impl Readable for ReadWrite {}
+impl Writable for WriteOnly {}
+impl Writable for ReadWrite {}
+
+pub struct Register<'a, T: MmioValue, A> {
+    region: &'a MmioRegion,
+    offset: usize,
+    _marker: PhantomData<(T, A)>,
+}
+
+impl<T: MmioValue, A> Register<'_, T, A> {
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<T: MmioValue, A: Readable> Register<'_, T, A> {
+    pub fn read(&self) -> T {
+        self.region.read(self.offset)
+    }
+}
+
+impl<T: MmioValue, A: Writable> Register<'_, T, A> {
+    pub fn write(&self, val: T) {
+        self.region.write(self.offset, val)
+    }
+}
+
+impl<T: MmioValue, A: Readable + Writable> Register<'_, T, A> {
+    pub fn modify(&self, f: impl FnOnce(T) -> T) {
+        self.write(f(self.read()))
+    }
+}
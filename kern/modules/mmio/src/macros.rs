@@ -0,0 +1,56 @@
+/// Declares a block of named registers over an [`MmioRegion`](crate::MmioRegion).
+///
+/// Each register is given as `@<offset> <name>: <type> [ro|wo|rw]`, e.g.
+///
+/// ```ignore
+/// register_block! {
+///     pub struct Ns16550a {
+///         @0x00 rbr: u8 [ro],
+///         @0x00 thr: u8 [wo],
+///         @0x05 lsr: u8 [ro],
+///     }
+/// }
+/// ```
+///
+/// Offsets are checked against the register width at compile time.
+#[macro_export]
+macro_rules! register_block {
+    (
+        $vis:vis struct $name:ident {
+            $(
+                @$offset:literal $reg:ident: $ty:ty [$access:ident]
+            ),* $(,)?
+        }
+    ) => {
+        $vis struct $name {
+            region: $crate::MmioRegion,
+        }
+
+        $(
+            const _: () = assert!(
+                $offset % core::mem::size_of::<$ty>() == 0,
+                "misaligned register offset"
+            );
+        )*
+
+        impl $name {
+            $vis fn new(region: $crate::MmioRegion) -> Self {
+                Self { region }
+            }
+
+            $vis fn region(&self) -> &$crate::MmioRegion {
+                &self.region
+            }
+
+            $(
+                $vis fn $reg(&self) -> $crate::Register<'_, $ty, $crate::register_block!(@access $access)> {
+                    self.region.register($offset)
+                }
+            )*
+        }
+    };
+
+    (@access ro) => { $crate::ReadOnly };
+    (@access wo) => { $crate::WriteOnly };
+    (@access rw) => { $crate::ReadWrite };
+}
@@ -191,6 +191,12 @@ core::arch::global_asm! {
     executor_launch:
         mv a0, s0
         call {EXECUTOR_START}
+
+    .global executor_relaunch
+    executor_relaunch:
+        mv sp, a1
+        mv s0, a0
+        j executor_launch
     ",
     EXECUTOR_START = sym crate::executor::Executor::start,
 }
@@ -198,6 +204,10 @@ core::arch::global_asm! {
 extern "C" {
     pub fn executor_launch();
 
+    /// Starts the executor at `executor` over on its stack ending at `stack_top`, dropping
+    /// whatever is on it.
+    pub fn executor_relaunch(executor: usize, stack_top: usize) -> !;
+
     #[link_name = "switch_context"]
     pub fn switch(old_ctx: usize, new_ctx: usize);
 
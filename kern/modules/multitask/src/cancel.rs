@@ -0,0 +1,119 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+};
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use jrinx_error::{InternalError, Result};
+use jrinx_sync::IrqSafeMutex;
+
+/// A flag requesting a task to stop, shared by the task and whoever may cancel it.
+///
+/// Cancelling wakes the task, and the executor drops it instead of polling it again. Dropping
+/// its future releases whatever it waits on, such as the timed event of a
+/// [`Sleep`](crate::time::Sleep), so a blocked task stops as promptly as a running one. A
+/// task may also await [`CancellationToken::cancelled`] to clean up by itself.
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<CancelState>,
+}
+
+struct CancelState {
+    cancelled: AtomicBool,
+    waiters: IrqSafeMutex<Waiters>,
+}
+
+#[derive(Default)]
+struct Waiters {
+    task: Option<Waker>,
+    next_key: usize,
+    futures: BTreeMap<usize, Waker>,
+}
+
+impl CancellationToken {
+    pub(crate) fn new() -> Self {
+        Self::try_new().expect("not enough memory for a cancellation token")
+    }
+
+    pub(crate) fn try_new() -> Result<Self> {
+        Ok(Self {
+            inner: Arc::try_new(CancelState {
+                cancelled: AtomicBool::new(false),
+                waiters: IrqSafeMutex::new("cancel-waiters", Waiters::default()),
+            })
+            .map_err(|_| InternalError::NotEnoughMem)?,
+        })
+    }
+
+    /// Marks the task cancelled and wakes it, doing nothing if it is cancelled already.
+    pub fn cancel(&self) {
+        if self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let waiters = core::mem::take(&mut *self.inner.waiters.lock());
+        waiters
+            .task
+            .into_iter()
+            .chain(waiters.futures.into_values())
+            .for_each(Waker::wake);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future completing once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled {
+            token: self,
+            key: None,
+        }
+    }
+
+    /// Sets the waker of the task owning the token, woken on cancellation.
+    pub(crate) fn bind(&self, waker: &Waker) {
+        let mut waiters = self.inner.waiters.lock();
+        if self.is_cancelled() {
+            waker.wake_by_ref();
+        } else {
+            waiters.task = Some(waker.clone());
+        }
+    }
+}
+
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+    key: Option<usize>,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let token = self.token;
+        let mut waiters = token.inner.waiters.lock();
+        // Checked under the lock, which `cancel` takes after setting the flag.
+        if token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let key = *self.key.get_or_insert_with(|| {
+            let key = waiters.next_key;
+            waiters.next_key += 1;
+            key
+        });
+        waiters.futures.insert(key, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.token.inner.waiters.lock().futures.remove(&key);
+        }
+    }
+}
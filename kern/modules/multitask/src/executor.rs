@@ -1,30 +1,52 @@
 use core::{
     any::Any,
-    fmt::Display,
+    cmp::Reverse,
+    fmt::{Display, Write},
+    future::Future,
+    ops::Range,
+    panic::Location,
     pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
     task::{Context, Poll, Waker},
+    time::Duration,
 };
 
-use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, task::Wake};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BinaryHeap},
+    sync::Arc,
+    task::Wake,
+    vec::Vec,
+};
 use jrinx_addr::VirtAddr;
-use jrinx_error::{InternalError, Result};
-use jrinx_hal::{Hal, Vm};
+use jrinx_error::{InternalError, ResourceKind, Result};
+use jrinx_hal::{Cpu, Hal, Interrupt, Vm};
+use jrinx_kpanic::{kpanic, PanicCode, PanicWord};
 use jrinx_paging::{GenericPagePerm, GenericPageTable, PagePerm};
+use jrinx_percpu::percpu;
 use jrinx_phys_frame::PhysFrame;
 use jrinx_serial_id_macro::SerialId;
 use jrinx_stack_alloc::StackAllocator;
-use jrinx_util::fastpq::{FastPriority, FastPriorityQueueWithLock};
+use jrinx_sync::IrqSafeMutex;
+use jrinx_util::fastpq::{FastPriority, FastPriorityQueue};
 use jrinx_vmm::KERN_PAGE_TABLE;
 use spin::Lazy;
 
 use crate::{
     arch::{self, SwitchContext},
-    inspector::{Inspector, InspectorStatus},
-    runtime::Runtime,
-    Task, TaskId, TaskPriority,
+    inspector::{Inspector, InspectorStatus, ResourceAccount},
+    preempt,
+    runtime::{Runtime, RuntimeStatus},
+    task_local::TaskLocals,
+    Task, TaskDeadline, TaskId, TaskName, TaskPriority, TaskStats,
 };
 
-type TaskQueue = FastPriorityQueueWithLock<TaskPriority, TaskId>;
+/// Task being polled on each CPU, for panic messages.
+#[percpu]
+static POLLING: spin::Mutex<Option<PollingTask>> = spin::Mutex::new(None);
+
+/// Time over which the recent CPU usage of a task decays to half.
+pub const TASK_USAGE_HALF_LIFE: Duration = Duration::from_secs(1);
 
 static EXECUTOR_STACK_ALLOCATOR: Lazy<StackAllocator> = Lazy::new(|| {
     StackAllocator::new(
@@ -56,6 +78,12 @@ impl Display for ExecutorId {
     }
 }
 
+impl PanicWord for ExecutorId {
+    fn panic_word(&self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ExecutorPriority(FastPriority);
 
@@ -74,6 +102,372 @@ impl From<ExecutorPriority> for FastPriority {
     }
 }
 
+/// Task being polled on a CPU, as told by [`polling_task`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollingTask {
+    pub executor: ExecutorId,
+    pub task: TaskId,
+    pub name: TaskName,
+}
+
+/// Returns the task being polled on the current CPU, if any.
+///
+/// This never blocks, so that the panic handler may call it, and gives `None` if the task is
+/// being updated.
+pub fn polling_task() -> Option<PollingTask> {
+    hal!()
+        .interrupt()
+        .with_saved_off(|| POLLING.as_ref().try_lock().and_then(|task| *task))
+}
+
+/// Returns the name of the task being polled on the current CPU, if any, as
+/// [`polling_task`] does.
+pub fn polling_task_name() -> Option<TaskName> {
+    polling_task().map(|task| task.name)
+}
+
+/// Writes the name of the task being polled on the current CPU, or `<none>`, for reports
+/// that cannot allocate.
+pub fn write_polling_task_name(w: &mut dyn Write) -> core::fmt::Result {
+    match polling_task_name() {
+        Some(name) => write!(w, "{}", name),
+        None => w.write_str("<none>"),
+    }
+}
+
+/// Writes what the current CPU runs, as its inspector and the task being polled, for dumps of
+/// fatal traps.
+///
+/// This never blocks nor allocates, and writes `?` for what is being updated.
+pub fn describe_current(w: &mut dyn Write) -> core::fmt::Result {
+    match Runtime::with_current(|rt| rt.try_status()) {
+        Some(RuntimeStatus::Running(inspector_id)) => write!(w, "inspector#{}", inspector_id)?,
+        Some(status) => write!(w, "runtime {:?}", status)?,
+        None => w.write_str("inspector#?")?,
+    }
+    match polling_task() {
+        Some(task) => write!(
+            w,
+            ", executor#{}, task#{} ({})",
+            task.executor,
+            task.task.value(),
+            task.name
+        ),
+        None => w.write_str(", no task"),
+    }
+}
+
+/// Returns the bounds of the executor stack whose guard holds `addr`, as a task running past
+/// that stack would fault on.
+pub fn overflowed_stack(addr: VirtAddr) -> Option<Range<VirtAddr>> {
+    EXECUTOR_STACK_ALLOCATOR.guarded_by(addr)
+}
+
+/// Returns the bounds of the executor stack holding `addr`, for walking the frames on it.
+pub fn stack_of(addr: VirtAddr) -> Option<Range<VirtAddr>> {
+    EXECUTOR_STACK_ALLOCATOR.containing(addr)
+}
+
+/// Returns whether a task is being polled on the current CPU, which [`abort_polling`] may then
+/// give up on.
+pub fn can_abort_polling() -> bool {
+    polling_task_name().is_some()
+}
+
+/// Gives up on the task being polled on the current CPU, after it faulted beyond repair, and
+/// goes on with the next task of its executor.
+///
+/// The stack of the executor is dropped by starting it over, so the task is leaked rather than
+/// dropped in the middle of its poll. Whatever it holds, such as locks and parks, stays held,
+/// and its join handle never resolves.
+pub fn abort_polling() -> ! {
+    let (executor, stack_top) = Executor::with_current(|ex| {
+        ex.abandon_polling();
+        (&**ex as *const Executor as usize, ex.stack_top)
+    })
+    .unwrap_or_else(|err| {
+        kpanic!(
+            code = PanicCode::ExecutorInvariant,
+            "task abort outside of any executor: {:?}",
+            err
+        )
+    });
+    unsafe { arch::executor_relaunch(executor, stack_top.as_usize()) }
+}
+
+pub(crate) fn set_polling(task: Option<PollingTask>) {
+    hal!()
+        .interrupt()
+        .with_saved_off(|| *POLLING.as_ref().lock() = task);
+}
+
+/// CPU time an executor may run for in each period, set by [`Executor::with_budget`].
+///
+/// Periods follow each other from the time the budget is set. An executor having used up its
+/// budget is throttled, not queued again by its inspector until the next period starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutorBudget {
+    pub runtime: Duration,
+    pub period: Duration,
+}
+
+impl ExecutorBudget {
+    pub const fn new(runtime: Duration, period: Duration) -> Self {
+        assert!(!period.is_zero(), "budget period must be positive");
+        Self { runtime, period }
+    }
+}
+
+struct BudgetAccount {
+    budget: ExecutorBudget,
+    period_start: Duration,
+    used: Duration,
+    throttles: u64,
+}
+
+impl BudgetAccount {
+    fn new(budget: ExecutorBudget) -> Self {
+        Self {
+            budget,
+            period_start: hal!().cpu().get_time(),
+            used: Duration::ZERO,
+            throttles: 0,
+        }
+    }
+
+    /// Charges a run of `ran` ending at `now`, returning the start of the next period if the
+    /// budget is used up.
+    ///
+    /// A run crossing into a new period is only charged with its part in that period.
+    fn charge(&mut self, ran: Duration, now: Duration) -> Option<Duration> {
+        let period = self.budget.period;
+        let elapsed = now.saturating_sub(self.period_start);
+        if elapsed >= period {
+            let into_period = (elapsed.as_nanos() % period.as_nanos()) as u64;
+            self.period_start = now - Duration::from_nanos(into_period);
+            self.used = ran.min(now - self.period_start);
+        } else {
+            self.used += ran;
+        }
+
+        if self.used < self.budget.runtime {
+            return None;
+        }
+        self.throttles += 1;
+        Some(self.period_start + period)
+    }
+}
+
+/// CPU time attributed to a task of an executor, as reported by [`Executor::top`].
+#[derive(Debug, Clone, Copy)]
+pub struct TaskUsage {
+    pub id: TaskId,
+    pub name: TaskName,
+    pub spawn_site: &'static Location<'static>,
+    pub polls: u64,
+    pub run_time: Duration,
+    /// Run time decayed by half every [`TASK_USAGE_HALF_LIFE`].
+    pub recent: Duration,
+}
+
+impl Display for TaskUsage {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "task {:?} {} (spawned at {}): polls={} run-time={:?} recent={:?}",
+            self.id, self.name, self.spawn_site, self.polls, self.run_time, self.recent
+        )
+    }
+}
+
+struct TaskAccount {
+    name: TaskName,
+    spawn_site: &'static Location<'static>,
+    polls: u64,
+    ticks: u64,
+    recent: u64,
+    epoch: u64,
+}
+
+impl TaskAccount {
+    fn new(task: &Task) -> Self {
+        Self {
+            name: task.name(),
+            spawn_site: task.spawn_site,
+            polls: 0,
+            ticks: 0,
+            recent: 0,
+            epoch: 0,
+        }
+    }
+
+    fn recent_at(&self, epoch: u64) -> u64 {
+        u32::try_from(epoch.saturating_sub(self.epoch))
+            .ok()
+            .and_then(|shift| self.recent.checked_shr(shift))
+            .unwrap_or(0)
+    }
+
+    fn charge(&mut self, ticks: u64, epoch: u64) {
+        self.recent = self.recent_at(epoch) + ticks;
+        self.epoch = epoch;
+        self.ticks += ticks;
+        self.polls += 1;
+    }
+
+    fn usage(&self, id: TaskId, epoch: u64) -> TaskUsage {
+        let cpu = hal!().cpu();
+        TaskUsage {
+            id,
+            name: self.name,
+            spawn_site: self.spawn_site,
+            polls: self.polls,
+            run_time: cpu.ticks_to_time(self.ticks),
+            recent: cpu.ticks_to_time(self.recent_at(epoch)),
+        }
+    }
+}
+
+/// Returns the number of [`TASK_USAGE_HALF_LIFE`] periods elapsed at `ticks`.
+fn usage_epoch(ticks: u64) -> u64 {
+    let period = hal!().cpu().timebase_freq() * TASK_USAGE_HALF_LIFE.as_secs();
+    ticks.checked_div(period).unwrap_or(0)
+}
+
+/// Work-stealing counters of an executor, as reported by [`Executor::steal_stats`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StealStats {
+    /// Tasks taken from siblings.
+    pub stolen: u64,
+    /// Tasks taken by siblings.
+    pub given: u64,
+    /// Steal attempts finding nothing to take.
+    pub misses: u64,
+}
+
+impl Display for StealStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "stolen = {}, given = {}, misses = {}",
+            self.stolen, self.given, self.misses
+        )
+    }
+}
+
+/// Ready tasks of an executor, pushed by wakers from any context, including interrupt
+/// handlers, and taken by the executor itself or by an idle sibling stealing from it.
+struct TaskQueue(IrqSafeMutex<ReadyTasks>);
+
+struct ReadyTasks {
+    fixed: FastPriorityQueue<TaskPriority, TaskId>,
+    /// Tasks with a deadline, earliest first, if the executor is in EDF mode.
+    edf: Option<BinaryHeap<Reverse<(TaskDeadline, u64, TaskPriority, TaskId)>>>,
+    /// Order of arrival, keeping tasks with the same deadline first in, first out.
+    seq: u64,
+}
+
+impl TaskQueue {
+    fn new() -> Self {
+        Self(IrqSafeMutex::new(
+            "executor-task-queue",
+            ReadyTasks {
+                fixed: FastPriorityQueue::new(),
+                edf: None,
+                seq: 0,
+            },
+        ))
+    }
+
+    fn enable_edf(&self) {
+        self.0.lock().edf.get_or_insert_with(BinaryHeap::new);
+    }
+
+    fn enqueue(&self, priority: TaskPriority, deadline: Option<TaskDeadline>, id: TaskId) {
+        let mut ready = self.0.lock();
+        let ReadyTasks { fixed, edf, seq } = &mut *ready;
+        match (edf, deadline) {
+            (Some(edf), Some(deadline)) => {
+                edf.push(Reverse((deadline, *seq, priority, id)));
+                *seq += 1;
+            }
+            // Tasks without a deadline take turns, whatever their priorities.
+            (Some(_), None) => fixed.enqueue(TaskPriority::default(), id),
+            (None, _) => fixed.enqueue(priority, id),
+        }
+    }
+
+    fn reserve(&self, priority: TaskPriority) -> Result<()> {
+        let mut ready = self.0.lock();
+        let priority = match ready.edf.as_mut() {
+            Some(edf) => {
+                edf.try_reserve(1)
+                    .map_err(|_| InternalError::NotEnoughMem)?;
+                TaskPriority::default()
+            }
+            None => priority,
+        };
+        ready
+            .fixed
+            .try_reserve(priority, 1)
+            .map_err(|_| InternalError::NotEnoughMem)
+    }
+
+    fn dequeue(&self) -> Option<(TaskPriority, TaskId)> {
+        let mut ready = self.0.lock();
+        if let Some(Reverse((_, _, priority, id))) = ready.edf.as_mut().and_then(BinaryHeap::pop) {
+            return Some((priority, id));
+        }
+        ready.fixed.dequeue()
+    }
+
+    fn len(&self) -> usize {
+        let ready = self.0.lock();
+        ready.fixed.len() + ready.edf.as_ref().map_or(0, BinaryHeap::len)
+    }
+
+    /// Takes up to `count` tasks, never ones waiting on their deadline in EDF mode.
+    fn steal(&self, count: usize, mut filter: impl FnMut(&TaskId) -> bool) -> Vec<TaskId> {
+        self.0
+            .lock()
+            .fixed
+            .steal(count, |_, id| filter(id))
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect()
+    }
+}
+
+/// A task of an executor, boxed to stay in place while siblings steal others.
+struct TaskSlot {
+    task: Task,
+    home: Arc<TaskWaker>,
+    waker: Waker,
+    /// Last deadline the task was found to miss, so that a miss is only counted once.
+    missed: Option<TaskDeadline>,
+}
+
+impl TaskSlot {
+    fn try_new(task: Task, task_queue: Arc<TaskQueue>) -> Result<Box<Self>> {
+        let home = Arc::try_new(TaskWaker {
+            task_id: task.id,
+            task_priority: task.priority,
+            task_deadline: IrqSafeMutex::new("task-deadline", task.deadline),
+            task_queue: IrqSafeMutex::new("task-waker", task_queue),
+        })
+        .map_err(|_| InternalError::NotEnoughMem)?;
+        let waker = Waker::from(home.clone());
+        task.cancel.bind(&waker);
+        Box::try_new(Self {
+            task,
+            home,
+            waker,
+            missed: None,
+        })
+        .map_err(|_| InternalError::NotEnoughMem)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExecutorStatus {
     Runnable,
@@ -87,9 +481,19 @@ pub struct Executor {
     status: ExecutorStatus,
     stack_top: VirtAddr,
     switch_context: SwitchContext,
-    task_registry: BTreeMap<TaskId, Task>,
+    task_registry: BTreeMap<TaskId, Box<TaskSlot>>,
     task_queue: Arc<TaskQueue>,
-    task_waker: BTreeMap<TaskId, Waker>,
+    task_usage: IrqSafeMutex<BTreeMap<TaskId, TaskAccount>>,
+    polling: Option<TaskId>,
+    slice_end: Option<Duration>,
+    preempt: bool,
+    parked: Arc<AtomicUsize>,
+    migratable: bool,
+    steal_stats: StealStats,
+    budget: Option<BudgetAccount>,
+    deadline_misses: u64,
+    /// Account of the inspector the executor is registered in, charged for its tasks.
+    account: Option<Arc<ResourceAccount>>,
     ext: Arc<dyn Any + Send + Sync>,
 }
 
@@ -98,6 +502,10 @@ impl Executor {
         Self::new_with_ext(priority, root_task, ())
     }
 
+    /// Creates an executor handing `ext` to its tasks through [`Executor::with_current`].
+    ///
+    /// Tasks of an executor with an extension never migrate to a sibling, nor does it take
+    /// tasks from them, since the extension would change under their feet.
     pub fn new_with_ext(
         priority: ExecutorPriority,
         root_task: Task,
@@ -118,7 +526,16 @@ impl Executor {
             switch_context: SwitchContext::new_executor(entry, stack_top),
             task_registry: BTreeMap::new(),
             task_queue: Arc::new(TaskQueue::new()),
-            task_waker: BTreeMap::new(),
+            task_usage: IrqSafeMutex::new("executor-task-usage", BTreeMap::new()),
+            polling: None,
+            slice_end: None,
+            preempt: false,
+            parked: Arc::new(AtomicUsize::new(0)),
+            migratable: (&ext as &dyn Any).is::<()>(),
+            steal_stats: StealStats::default(),
+            budget: None,
+            deadline_misses: 0,
+            account: None,
             ext: Arc::new(ext),
         });
 
@@ -133,6 +550,27 @@ impl Executor {
         executor
     }
 
+    /// Bounds the CPU time of the executor to `budget`, which executors without one are not
+    /// bounded in.
+    ///
+    /// The time is charged when the executor switches back to its inspector, so a run may
+    /// overdraw the budget by up to the time slice of its task.
+    pub fn with_budget(mut self: Pin<Box<Self>>, budget: ExecutorBudget) -> Pin<Box<Self>> {
+        self.budget = Some(BudgetAccount::new(budget));
+        self
+    }
+
+    /// Switches the executor to earliest-deadline-first scheduling.
+    ///
+    /// Ready tasks with a deadline are polled first, earliest deadline first, and the others
+    /// take turns after them regardless of their priorities. Tasks waiting on their deadline
+    /// are not stolen by siblings. Tasks are not preempted by ones with earlier deadlines, so
+    /// a task set is only feasible if it leaves room for the longest poll.
+    pub fn with_edf(self: Pin<Box<Self>>) -> Pin<Box<Self>> {
+        self.task_queue.enable_edf();
+        self
+    }
+
     pub fn id(&self) -> ExecutorId {
         self.id
     }
@@ -141,6 +579,13 @@ impl Executor {
         self.priority
     }
 
+    /// Changes the priority of the executor, which takes effect the next time it is queued.
+    ///
+    /// Use [`Inspector::set_priority`] for an executor waiting in the queue of an inspector.
+    pub fn set_priority(&mut self, priority: ExecutorPriority) {
+        self.priority = priority;
+    }
+
     pub fn status(&self) -> ExecutorStatus {
         self.status
     }
@@ -153,13 +598,153 @@ impl Executor {
         self.ext.clone()
     }
 
+    /// Spawns `task` in the executor, failing with [`InternalError::NotEnoughMem`] if the heap
+    /// cannot hold its waker or its place in the queue, and with
+    /// [`InternalError::ResourceLimitExceeded`] if its inspector holds too many tasks.
     pub fn spawn(&mut self, task: Task) -> Result<&mut Self> {
+        if let Some(account) = &self.account {
+            account.charge(ResourceKind::Tasks, 1)?;
+        }
+        if let Err(err) = self.insert_task(task) {
+            self.uncharge_task();
+            return Err(err);
+        }
+        Ok(self)
+    }
+
+    fn insert_task(&mut self, task: Task) -> Result<()> {
         let id = task.id;
-        self.task_queue.enqueue(task.priority, id);
+        let account = TaskAccount::new(&task);
+        let (priority, deadline) = (task.priority, task.deadline);
+        let slot = TaskSlot::try_new(task, self.task_queue.clone())?;
+        self.task_queue.reserve(priority)?;
         self.task_registry
-            .try_insert(id, task)
-            .map_err(|_| InternalError::DuplicateTaskId)?;
-        Ok(self)
+            .try_insert(id, slot)
+            .map_err(|_| InternalError::DuplicateTaskId(id.value()))?;
+        self.task_queue.enqueue(priority, deadline, id);
+        self.task_usage.lock().insert(id, account);
+        Ok(())
+    }
+
+    fn uncharge_task(&self) {
+        if let Some(account) = &self.account {
+            account.uncharge(ResourceKind::Tasks, 1);
+        }
+    }
+
+    pub fn task_count(&self) -> usize {
+        self.task_registry.len()
+    }
+
+    pub(crate) fn set_account(&mut self, account: Arc<ResourceAccount>) {
+        self.account = Some(account);
+    }
+
+    /// Spawns `future` as a task of the executor, failing with [`InternalError::NotEnoughMem`]
+    /// instead of aborting if the heap cannot hold it.
+    ///
+    /// The task and everything it owns are allocated fallibly. Only the nodes of the maps
+    /// tracking it are not, which are much smaller than most futures.
+    #[track_caller]
+    pub fn try_spawn<F>(&mut self, future: F, priority: TaskPriority) -> Result<TaskId>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let task = Task::try_new(future, priority)?;
+        let id = task.id;
+        self.spawn(task)?;
+        Ok(id)
+    }
+
+    /// Cancels the task `id` of the executor, returning whether it was found.
+    ///
+    /// The task is dropped instead of polled the next time it is dequeued. A finished task, or
+    /// one stolen by a sibling, is not found, which is not an error.
+    pub fn cancel_task(&self, id: TaskId) -> bool {
+        let Some(slot) = self.task_registry.get(&id) else {
+            return false;
+        };
+        slot.task.cancel.cancel();
+        true
+    }
+
+    /// Moves the deadline of the task being polled, failing with
+    /// [`InternalError::NoCurrentTask`] outside of a poll.
+    pub fn set_task_deadline(&self, deadline: Option<TaskDeadline>) -> Result<()> {
+        let slot = self
+            .polling
+            .and_then(|id| self.task_registry.get(&id))
+            .ok_or(InternalError::NoCurrentTask)?;
+        *slot.home.task_deadline.lock() = deadline;
+        Ok(())
+    }
+
+    /// Returns the number of times a task was dispatched after its deadline, counting each
+    /// deadline once.
+    pub fn deadline_misses(&self) -> u64 {
+        self.deadline_misses
+    }
+
+    pub fn steal_stats(&self) -> StealStats {
+        self.steal_stats
+    }
+
+    pub fn budget(&self) -> Option<ExecutorBudget> {
+        self.budget.as_ref().map(|account| account.budget)
+    }
+
+    /// Returns the number of times the executor used up its budget.
+    pub fn throttles(&self) -> u64 {
+        self.budget.as_ref().map_or(0, |account| account.throttles)
+    }
+
+    /// Charges the executor with a run of `ticks` ending now, returning when it may run again
+    /// if that uses up its budget.
+    pub(crate) fn charge_budget(&mut self, ticks: u64) -> Option<Duration> {
+        let account = self.budget.as_mut()?;
+        let cpu = hal!().cpu();
+        account.charge(cpu.ticks_to_time(ticks), cpu.get_time())
+    }
+
+    pub(crate) fn migratable(&self) -> bool {
+        self.migratable
+    }
+
+    /// Returns the number of tasks ready to be polled, counting a task woken more than once
+    /// as many times.
+    pub(crate) fn ready(&self) -> usize {
+        self.task_queue.len()
+    }
+
+    /// Returns the `n` tasks with the most recent CPU usage, heaviest first.
+    ///
+    /// Finished tasks are dropped from the accounting in the same step that charges their last
+    /// poll, so they never show up here.
+    pub fn top(&self, n: usize) -> Vec<TaskUsage> {
+        let epoch = usage_epoch(hal!().cpu().get_ticks());
+        let mut usage: Vec<_> = self
+            .task_usage
+            .lock()
+            .iter()
+            .map(|(&id, account)| account.usage(id, epoch))
+            .collect();
+        usage.sort_unstable_by_key(|usage| Reverse((usage.recent, usage.run_time)));
+        usage.truncate(n);
+        usage
+    }
+
+    /// Returns the poll counters of the unfinished tasks of the executor, see [`Task::stats`].
+    pub fn task_stats(&self) -> BTreeMap<TaskId, TaskStats> {
+        self.task_registry
+            .iter()
+            .map(|(&id, slot)| (id, slot.task.stats()))
+            .collect()
+    }
+
+    /// Returns the task-local values of the task being polled, if any.
+    pub(crate) fn task_locals(&self) -> Option<TaskLocals> {
+        let slot = self.task_registry.get(&self.polling?)?;
+        Some(slot.task.locals.clone())
     }
 
     pub fn with_current<F, R>(f: F) -> Result<R>
@@ -168,48 +753,246 @@ impl Executor {
     {
         Inspector::with_current(|is| {
             let executor_id = match is.status() {
-                InspectorStatus::Running(executor_id) | InspectorStatus::Pending(executor_id) => {
-                    executor_id
-                }
+                InspectorStatus::Running(executor_id)
+                | InspectorStatus::Pending(executor_id)
+                | InspectorStatus::Paused(Some(executor_id)) => executor_id,
                 _ => return Err(InternalError::InvalidInspectorStatus),
             };
             is.with_executor(executor_id, f)
         })?
     }
 
+    /// Keeps the executor from finishing while the guard lives, for a task waiting for a
+    /// wakeup which is sure to come, such as the completion of a joined task.
+    pub(crate) fn park(&self) -> ParkGuard {
+        self.parked.fetch_add(1, Ordering::SeqCst);
+        ParkGuard(self.parked.clone())
+    }
+
     pub(crate) fn switch_context(&self) -> VirtAddr {
         VirtAddr::new(&self.switch_context as *const _ as usize)
     }
 
     pub(crate) fn run(&mut self) {
-        let Self {
-            task_registry,
-            task_queue,
-            task_waker,
-            ..
-        } = self;
+        loop {
+            while let Some((_, task_id)) = self.task_queue.dequeue() {
+                self.poll_task(task_id);
+            }
 
-        while let Some((_, task_id)) = task_queue.dequeue() {
-            let task = match task_registry.get_mut(&task_id) {
-                Some(task) => task,
-                None => continue,
-            };
+            if self.steal() {
+                continue;
+            }
+
+            // Parked tasks are woken through the task queue, from other executors or interrupt
+            // handlers, which is checked again the next time the executor is scheduled.
+            if self.parked.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            Runtime::switch_yield();
+        }
+
+        self.status = ExecutorStatus::Finished;
+    }
+
+    fn poll_task(&mut self, task_id: TaskId) {
+        let Some(slot) = self.task_registry.get_mut(&task_id) else {
+            return;
+        };
+        let slot: &mut TaskSlot = slot;
+
+        let name = slot.task.name();
 
-            let waker = task_waker
-                .entry(task_id)
-                .or_insert_with(|| TaskWaker::create(task.id, task.priority, task_queue.clone()));
+        // Dropping the task releases whatever it waits on, such as timed events and parks.
+        if slot.task.is_cancelled() {
+            trace!(
+                "executor {} drops cancelled task {:?} ({})",
+                self.id,
+                task_id,
+                name
+            );
+            self.task_usage.lock().remove(&task_id);
+            self.task_registry.remove(&task_id);
+            self.uncharge_task();
+            return;
+        }
+
+        let deadline = *slot.home.task_deadline.lock();
+        if let Some(deadline) = deadline {
+            let now = hal!().cpu().get_time();
+            if now > deadline.at() && slot.missed != Some(deadline) {
+                slot.missed = Some(deadline);
+                self.deadline_misses += 1;
+                warn!(
+                    "executor {} dispatches task {:?} ({}) {:?} after its deadline",
+                    self.id,
+                    task_id,
+                    name,
+                    now - deadline.at()
+                );
+            }
+        }
 
-            let mut context = Context::from_waker(waker);
-            match task.poll(&mut context) {
-                Poll::Ready(()) => {
-                    task_registry.remove(&task_id);
-                    task_waker.remove(&task_id);
+        // The task may switch the executor out in the middle of the poll, letting siblings
+        // steal from the registry, but never the task itself.
+        trace!("executor {} polls task {:?} ({})", self.id, task_id, name);
+        self.polling = Some(task_id);
+        self.slice_end = Some(preempt::begin_slice(slot.task.priority));
+        set_polling(Some(PollingTask {
+            executor: self.id,
+            task: task_id,
+            name,
+        }));
+        let mut context = Context::from_waker(&slot.waker);
+        let begin = hal!().cpu().get_ticks();
+        #[cfg(feature = "task-stats")]
+        let begin_cycles = hal!().cpu().get_cycles();
+        let poll = slot.task.poll(&mut context);
+        #[cfg(feature = "task-stats")]
+        slot.task
+            .record_poll(hal!().cpu().get_cycles().saturating_sub(begin_cycles));
+        let end = hal!().cpu().get_ticks();
+        set_polling(None);
+        self.polling = None;
+        self.slice_end = None;
+
+        match poll {
+            Poll::Ready(()) => {
+                self.task_usage.lock().remove(&task_id);
+                self.task_registry.remove(&task_id);
+                self.uncharge_task();
+            }
+            Poll::Pending => {
+                if let Some(account) = self.task_usage.lock().get_mut(&task_id) {
+                    account.charge(end.saturating_sub(begin), usage_epoch(end));
                 }
-                Poll::Pending => {}
             }
         }
 
-        self.status = ExecutorStatus::Finished;
+        // The slice ran out, but the task returned before the executor could be preempted.
+        if core::mem::take(&mut self.preempt) {
+            preempt::preempt();
+        }
+    }
+
+    /// Leaks the task being polled, to be called before starting the executor over.
+    fn abandon_polling(&mut self) {
+        set_polling(None);
+        self.slice_end = None;
+        self.preempt = false;
+        let Some(task_id) = self.polling.take() else {
+            return;
+        };
+        self.task_usage.lock().remove(&task_id);
+        if let Some(slot) = self.task_registry.remove(&task_id) {
+            self.uncharge_task();
+            warn!(
+                "executor {} aborts task {:?} ({})",
+                self.id,
+                task_id,
+                slot.task.name()
+            );
+            core::mem::forget(slot);
+        }
+    }
+
+    /// Marks the executor for preemption if the slice of the task being polled is over at
+    /// `now`, returning the end of the slice otherwise.
+    pub(crate) fn expire_slice(&mut self, now: Duration) -> Option<Duration> {
+        let end = self.slice_end?;
+        if now < end {
+            return Some(end);
+        }
+        self.preempt = true;
+        None
+    }
+
+    /// Takes the preemption mark, if the executor is in the middle of a poll.
+    pub(crate) fn take_preemption(&mut self) -> bool {
+        self.slice_end.is_some() && core::mem::take(&mut self.preempt)
+    }
+
+    /// Starts a new slice for the task being polled, dropping a pending preemption.
+    pub(crate) fn renew_slice(&mut self) {
+        let Some(slot) = self.polling.and_then(|id| self.task_registry.get(&id)) else {
+            return;
+        };
+        self.slice_end = Some(preempt::begin_slice(slot.task.priority));
+        self.preempt = false;
+    }
+
+    /// Publishes the task being polled, once the executor is switched back in.
+    pub(crate) fn publish_polling(&self) {
+        set_polling(self.polling.and_then(|id| {
+            self.task_registry.get(&id).map(|slot| PollingTask {
+                executor: self.id,
+                task: id,
+                name: slot.task.name(),
+            })
+        }));
+    }
+
+    /// Takes half of the ready tasks of the most loaded sibling of the same priority, returning
+    /// whether any was taken.
+    fn steal(&mut self) -> bool {
+        if !self.migratable {
+            return false;
+        }
+
+        let (id, priority) = (self.id, self.priority);
+        let stolen = Inspector::with_current(|is| {
+            is.with_most_loaded(id, priority, |victim| victim.give_away())
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+
+        if stolen.is_empty() {
+            self.steal_stats.misses += 1;
+            return false;
+        }
+        trace!("executor {} stole {} tasks", self.id, stolen.len());
+
+        for (slot, account) in stolen {
+            let (task_id, task_priority) = (slot.task.id, slot.task.priority);
+            let task_deadline = *slot.home.task_deadline.lock();
+            *slot.home.task_queue.lock() = self.task_queue.clone();
+            self.task_registry.insert(task_id, slot);
+            if let Some(account) = account {
+                self.task_usage.lock().insert(task_id, account);
+            }
+            self.task_queue
+                .enqueue(task_priority, task_deadline, task_id);
+            self.steal_stats.stolen += 1;
+        }
+        true
+    }
+
+    /// Hands half of the ready tasks over to a stealing sibling, leaving out pinned tasks and
+    /// the one being polled.
+    fn give_away(&mut self) -> Vec<(Box<TaskSlot>, Option<TaskAccount>)> {
+        let Self {
+            task_registry,
+            task_queue,
+            task_usage,
+            polling,
+            steal_stats,
+            ..
+        } = self;
+
+        let ids = task_queue.steal(task_queue.len() / 2, |id| {
+            Some(*id) != *polling
+                && task_registry
+                    .get(id)
+                    .is_some_and(|slot| !slot.task.is_pinned())
+        });
+
+        let mut task_usage = task_usage.lock();
+        let given: Vec<_> = ids
+            .into_iter()
+            .filter_map(|id| Some((task_registry.remove(&id)?, task_usage.remove(&id))))
+            .collect();
+        steal_stats.given += given.len() as u64;
+        given
     }
 
     pub(crate) fn start(address: usize) -> ! {
@@ -234,10 +1017,20 @@ impl Drop for Executor {
     }
 }
 
+pub(crate) struct ParkGuard(Arc<AtomicUsize>);
+
+impl Drop for ParkGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 struct TaskWaker {
     task_id: TaskId,
     task_priority: TaskPriority,
-    task_queue: Arc<TaskQueue>,
+    task_deadline: IrqSafeMutex<Option<TaskDeadline>>,
+    /// Queue of the executor the task lives in, switched when the task is stolen.
+    task_queue: IrqSafeMutex<Arc<TaskQueue>>,
 }
 
 impl Wake for TaskWaker {
@@ -251,15 +1044,10 @@ impl Wake for TaskWaker {
 }
 
 impl TaskWaker {
-    fn create(task_id: TaskId, task_priority: TaskPriority, task_queue: Arc<TaskQueue>) -> Waker {
-        Waker::from(Arc::new(Self {
-            task_id,
-            task_priority,
-            task_queue,
-        }))
-    }
-
     fn wake_task(&self) {
-        self.task_queue.enqueue(self.task_priority, self.task_id);
+        let deadline = *self.task_deadline.lock();
+        self.task_queue
+            .lock()
+            .enqueue(self.task_priority, deadline, self.task_id);
     }
 }
@@ -0,0 +1,244 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    ptr,
+    task::{Context, Poll, Waker},
+};
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+use spin::Mutex;
+
+use crate::{cancel::CancellationToken, Task, TaskPriority};
+
+pub struct TaskGroup {
+    spawner: TaskGroupSpawner,
+    detached: bool,
+}
+
+#[derive(Clone)]
+pub struct TaskGroupSpawner {
+    inner: Arc<Mutex<GroupState>>,
+}
+
+/// Children of a group, linked through the [`ChildLink`] each of them owns.
+struct GroupState {
+    head: *mut ChildLink,
+    len: usize,
+    joiners: BTreeMap<usize, Waker>,
+    next_joiner: usize,
+}
+
+/// Place of a child in its group, unlinked when the child is dropped.
+struct ChildLink {
+    cancel: CancellationToken,
+    prev: *mut ChildLink,
+    next: *mut ChildLink,
+}
+
+// SAFETY: links are only read or written with the lock of their group held, and freed by
+// their child once unlinked.
+unsafe impl Send for GroupState {}
+unsafe impl Send for GroupedFuture {}
+
+impl GroupState {
+    fn link(&mut self, child: *mut ChildLink) {
+        unsafe { (*child).next = self.head };
+        if let Some(head) = unsafe { self.head.as_mut() } {
+            head.prev = child;
+        }
+        self.head = child;
+        self.len += 1;
+    }
+
+    fn unlink(&mut self, child: *mut ChildLink) {
+        let child = unsafe { &*child };
+        match unsafe { child.prev.as_mut() } {
+            Some(prev) => prev.next = child.next,
+            None => self.head = child.next,
+        }
+        if let Some(next) = unsafe { child.next.as_mut() } {
+            next.prev = child.prev;
+        }
+        self.len -= 1;
+        if self.len == 0 {
+            self.joiners.values().for_each(Waker::wake_by_ref);
+        }
+    }
+
+    fn children(&self) -> impl Iterator<Item = &ChildLink> {
+        let mut child = self.head;
+        core::iter::from_fn(move || {
+            let link = unsafe { child.as_ref() }?;
+            child = link.next;
+            Some(link)
+        })
+    }
+}
+
+impl Default for TaskGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TaskGroup {
+    pub fn new() -> Self {
+        Self {
+            spawner: TaskGroupSpawner {
+                inner: Arc::new(Mutex::new(GroupState {
+                    head: ptr::null_mut(),
+                    len: 0,
+                    joiners: BTreeMap::new(),
+                    next_joiner: 0,
+                })),
+            },
+            detached: false,
+        }
+    }
+
+    pub fn spawner(&self) -> TaskGroupSpawner {
+        self.spawner.clone()
+    }
+
+    #[track_caller]
+    pub fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        self.spawner.spawn(future);
+    }
+
+    #[track_caller]
+    pub fn spawn_with_priority(
+        &self,
+        future: impl Future<Output = ()> + Send + 'static,
+        priority: TaskPriority,
+    ) {
+        self.spawner.spawn_with_priority(future, priority);
+    }
+
+    pub fn len(&self) -> usize {
+        self.spawner.inner.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn join_all(&self) -> impl Future<Output = ()> + '_ {
+        JoinAll {
+            group: &self.spawner.inner,
+            key: None,
+        }
+    }
+
+    /// Cancels the children through their tokens, leaving the group once their executor drops
+    /// them.
+    pub fn cancel_all(&self) {
+        self.spawner
+            .inner
+            .lock()
+            .children()
+            .for_each(|child| child.cancel.cancel());
+    }
+
+    pub fn detach(mut self) {
+        self.detached = true;
+    }
+}
+
+impl Drop for TaskGroup {
+    fn drop(&mut self) {
+        if !self.detached {
+            self.cancel_all();
+        }
+    }
+}
+
+impl TaskGroupSpawner {
+    #[track_caller]
+    pub fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        self.spawn_with_priority(future, TaskPriority::default());
+    }
+
+    #[track_caller]
+    pub fn spawn_with_priority(
+        &self,
+        future: impl Future<Output = ()> + Send + 'static,
+        priority: TaskPriority,
+    ) {
+        let cancel = CancellationToken::new();
+        let link = Box::into_raw(Box::new(ChildLink {
+            cancel: cancel.clone(),
+            prev: ptr::null_mut(),
+            next: ptr::null_mut(),
+        }));
+        self.inner.lock().link(link);
+
+        let mut task = Task::new(
+            GroupedFuture {
+                group: self.inner.clone(),
+                link,
+                future: Box::pin(future),
+            },
+            priority,
+        );
+        task.cancel = cancel;
+        crate::spawn_task(task);
+    }
+}
+
+/// Future of a child, leaving the group when dropped, whether it completed or not.
+struct GroupedFuture {
+    group: Arc<Mutex<GroupState>>,
+    link: *mut ChildLink,
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+impl Future for GroupedFuture {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.future.as_mut().poll(cx)
+    }
+}
+
+impl Drop for GroupedFuture {
+    fn drop(&mut self) {
+        self.group.lock().unlink(self.link);
+        drop(unsafe { Box::from_raw(self.link) });
+    }
+}
+
+struct JoinAll<'a> {
+    group: &'a Mutex<GroupState>,
+    key: Option<usize>,
+}
+
+impl Future for JoinAll<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let group = self.group;
+        let mut state = group.lock();
+        if state.len == 0 {
+            if let Some(key) = self.key {
+                state.joiners.remove(&key);
+            }
+            return Poll::Ready(());
+        }
+
+        let key = *self.key.get_or_insert_with(|| {
+            let key = state.next_joiner;
+            state.next_joiner += 1;
+            key
+        });
+        state.joiners.insert(key, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for JoinAll<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.group.lock().joiners.remove(&key);
+        }
+    }
+}
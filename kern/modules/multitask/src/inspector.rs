@@ -1,20 +1,34 @@
 use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
-use core::{any::Any, fmt::Display, pin::Pin};
+use core::{
+    any::Any,
+    fmt::Display,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
 
 use jrinx_addr::VirtAddr;
-use jrinx_error::{InternalError, Result};
+use jrinx_error::{InternalError, ResourceKind, Result};
+use jrinx_hal::{Cpu, Hal};
+use jrinx_kpanic::PanicWord;
 use jrinx_serial_id_macro::SerialId;
+use jrinx_timed_event::{TimedEvent, TimedEventHandler, TimedEventTracker};
 use jrinx_util::fastpq::FastPriorityQueueWithLock;
 use spin::{Mutex, RwLock};
 
 use crate::{
     arch,
     executor::{Executor, ExecutorId, ExecutorPriority, ExecutorStatus},
-    runtime::{Runtime, RuntimeStatus},
+    runtime::{Locked, Runtime, RuntimeSchedTableEntry, RuntimeStatus},
 };
 
 type ExecutorQueue = FastPriorityQueueWithLock<ExecutorPriority, ExecutorId>;
 
+/// Set in the reference count of an inspector once it is retired.
+const INSPECTOR_DRAINING: usize = 1 << (usize::BITS - 1);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SerialId)]
 pub struct InspectorId(u64);
 
@@ -24,17 +38,261 @@ impl Display for InspectorId {
     }
 }
 
+impl PanicWord for InspectorId {
+    fn panic_word(&self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InspectorStatus {
     Idle,
     Running(ExecutorId),
     Pending(ExecutorId),
+    /// Left out of scheduling until resumed, while still running the executor if any.
+    Paused(Option<ExecutorId>),
+}
+
+/// Set of CPUs an inspector may be registered on, up to the 64th.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuAffinity(u64);
+
+impl CpuAffinity {
+    pub const ALL: Self = Self(u64::MAX);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn single(cpu_id: usize) -> Self {
+        Self::empty().with(cpu_id)
+    }
+
+    pub const fn with(self, cpu_id: usize) -> Self {
+        if cpu_id < u64::BITS as usize {
+            Self(self.0 | 1 << cpu_id)
+        } else {
+            self
+        }
+    }
+
+    pub const fn contains(&self, cpu_id: usize) -> bool {
+        cpu_id < u64::BITS as usize && self.0 & 1 << cpu_id != 0
+    }
+}
+
+impl Default for CpuAffinity {
+    fn default() -> Self {
+        Self::ALL
+    }
 }
 
+impl Display for CpuAffinity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if *self == Self::ALL {
+            return write!(f, "all");
+        }
+        let mut cpus = (0..u64::BITS as usize).filter(|&cpu_id| self.contains(cpu_id));
+        if let Some(first) = cpus.next() {
+            write!(f, "{}", first)?;
+        }
+        for cpu_id in cpus {
+            write!(f, ",{}", cpu_id)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    pub max_executors: Option<usize>,
+    pub max_tasks: Option<usize>,
+    pub max_timed_events: Option<usize>,
+    pub max_descriptors: Option<usize>,
+    pub max_shared_bytes: Option<usize>,
+}
+
+impl ResourceLimits {
+    pub const UNLIMITED: Self = Self {
+        max_executors: None,
+        max_tasks: None,
+        max_timed_events: None,
+        max_descriptors: None,
+        max_shared_bytes: None,
+    };
+
+    fn max(&self, kind: ResourceKind) -> usize {
+        match kind {
+            ResourceKind::Executors => self.max_executors,
+            ResourceKind::Tasks => self.max_tasks,
+            ResourceKind::TimedEvents => self.max_timed_events,
+            ResourceKind::Descriptors => self.max_descriptors,
+            ResourceKind::SharedBytes => self.max_shared_bytes,
+        }
+        .unwrap_or(usize::MAX)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub executors: usize,
+    pub tasks: usize,
+    pub timed_events: usize,
+    pub descriptors: usize,
+    pub shared_bytes: usize,
+    pub violations: usize,
+}
+
+impl Display for ResourceUsage {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "executors = {}, tasks = {}, timed-events = {}, descriptors = {}, shared-bytes = {:#x}, \
+             violations = {}",
+            self.executors,
+            self.tasks,
+            self.timed_events,
+            self.descriptors,
+            self.shared_bytes,
+            self.violations
+        )
+    }
+}
+
+pub(crate) struct ResourceAccount {
+    id: InspectorId,
+    limits: ResourceLimits,
+    executors: AtomicUsize,
+    tasks: AtomicUsize,
+    timed_events: AtomicUsize,
+    descriptors: AtomicUsize,
+    shared_bytes: AtomicUsize,
+    violations: AtomicUsize,
+}
+
+impl ResourceAccount {
+    fn new(id: InspectorId, limits: ResourceLimits) -> Self {
+        Self {
+            id,
+            limits,
+            executors: AtomicUsize::new(0),
+            tasks: AtomicUsize::new(0),
+            timed_events: AtomicUsize::new(0),
+            descriptors: AtomicUsize::new(0),
+            shared_bytes: AtomicUsize::new(0),
+            violations: AtomicUsize::new(0),
+        }
+    }
+
+    fn counter(&self, kind: ResourceKind) -> &AtomicUsize {
+        match kind {
+            ResourceKind::Executors => &self.executors,
+            ResourceKind::Tasks => &self.tasks,
+            ResourceKind::TimedEvents => &self.timed_events,
+            ResourceKind::Descriptors => &self.descriptors,
+            ResourceKind::SharedBytes => &self.shared_bytes,
+        }
+    }
+
+    /// Charges `amount` of `kind`, or raises a health-monitor event if it would exceed the
+    /// limit.
+    pub(crate) fn charge(&self, kind: ResourceKind, amount: usize) -> Result<()> {
+        let max = self.limits.max(kind);
+        self.counter(kind)
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(amount).filter(|&used| used <= max)
+            })
+            .map(|_| ())
+            .map_err(|_| {
+                self.violations.fetch_add(1, Ordering::SeqCst);
+                warn!(
+                    "health monitor: inspector {} exceeded its {} limit ({})",
+                    self.id,
+                    kind,
+                    self.usage()
+                );
+                InternalError::ResourceLimitExceeded(kind)
+            })
+    }
+
+    pub(crate) fn uncharge(&self, kind: ResourceKind, amount: usize) {
+        self.counter(kind).fetch_sub(amount, Ordering::SeqCst);
+    }
+
+    fn usage(&self) -> ResourceUsage {
+        ResourceUsage {
+            executors: self.executors.load(Ordering::SeqCst),
+            tasks: self.tasks.load(Ordering::SeqCst),
+            timed_events: self.timed_events.load(Ordering::SeqCst),
+            descriptors: self.descriptors.load(Ordering::SeqCst),
+            shared_bytes: self.shared_bytes.load(Ordering::SeqCst),
+            violations: self.violations.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Resources charged to an inspector by [`Inspector::charge`], given back once dropped.
+pub struct ResourceCharge {
+    account: Arc<ResourceAccount>,
+    kind: ResourceKind,
+    amount: usize,
+}
+
+impl Drop for ResourceCharge {
+    fn drop(&mut self) {
+        self.account.uncharge(self.kind, self.amount);
+    }
+}
+
+/// Time an inspector spent running, as reported by [`Inspector::stats`].
+///
+/// A round lasts from the switch into the inspector to the switch out of it, leaving out the
+/// time the CPU idles in between.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InspectorStats {
+    pub rounds: u64,
+    pub run_time: Duration,
+    pub last_round: Duration,
+    pub longest_round: Duration,
+    /// Rounds lasting longer than the overrun threshold of the inspector.
+    pub overruns: u64,
+}
+
+impl Display for InspectorStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "rounds = {}, run-time = {:?}, last-round = {:?}, longest-round = {:?}, overruns = {}",
+            self.rounds, self.run_time, self.last_round, self.longest_round, self.overruns
+        )
+    }
+}
+
+#[derive(Default)]
+struct RoundAccount {
+    rounds: AtomicU64,
+    ticks: AtomicU64,
+    last: AtomicU64,
+    longest: AtomicU64,
+    overruns: AtomicU64,
+}
+
+/// A group of executors sharing the windows of the inspector on one CPU.
+///
+/// The highest priority runnable executor is always run first, and executors of equal priority
+/// take turns. An executor switching out is queued behind its equals, so one that stays
+/// runnable starves those of lower priority until it finishes or blocks. That is the contract,
+/// not a bug: lower priority work resumes as soon as no higher priority executor is runnable.
 pub struct Inspector {
     id: InspectorId,
     status: Mutex<InspectorStatus>,
     scheduler: RwLock<Scheduler>,
+    account: Arc<ResourceAccount>,
+    affinity: CpuAffinity,
+    overrun_threshold: Option<Duration>,
+    rounds: RoundAccount,
+    refs: Arc<InspectorRefs>,
+    sched_windows: Mutex<Option<Vec<RuntimeSchedTableEntry>>>,
     ext: Arc<dyn Any + Send + Sync>,
 }
 
@@ -42,8 +300,27 @@ struct Scheduler {
     registry: BTreeMap<ExecutorId, Pin<Box<Executor>>>,
     queue: ExecutorQueue,
     wait_list: Vec<ExecutorId>,
+    /// Executors out of budget, queued again once their next period starts.
+    throttled: Vec<ExecutorId>,
+}
+
+struct InspectorRefs {
+    id: InspectorId,
+    count: AtomicUsize,
+    retired: AtomicBool,
+    waker: Mutex<Option<Waker>>,
 }
 
+/// A counted reference to an inspector, held by in-flight work targeting it, such as timed
+/// events and wake requests from other CPUs.
+///
+/// Once the inspector is retired, no new reference can be taken, but the existing ones keep
+/// resolving it, and it is only freed after the last of them is dropped.
+pub struct InspectorRef(Arc<InspectorRefs>);
+
+/// Completes once a retired inspector is drained of its references and freed.
+pub struct InspectorRetirement(Arc<InspectorRefs>);
+
 impl Default for Inspector {
     fn default() -> Self {
         Self::new()
@@ -56,18 +333,49 @@ impl Inspector {
     }
 
     pub fn new_with_ext(ext: impl Any + Send + Sync) -> Self {
+        let id = InspectorId::new();
         Self {
-            id: InspectorId::new(),
+            id,
             status: Mutex::new(InspectorStatus::Idle),
             scheduler: RwLock::new(Scheduler {
                 registry: BTreeMap::new(),
                 queue: ExecutorQueue::new(),
                 wait_list: Vec::new(),
+                throttled: Vec::new(),
             }),
+            account: Arc::new(ResourceAccount::new(id, ResourceLimits::UNLIMITED)),
+            affinity: CpuAffinity::ALL,
+            overrun_threshold: None,
+            rounds: RoundAccount::default(),
+            refs: Arc::new(InspectorRefs {
+                id,
+                count: AtomicUsize::new(0),
+                retired: AtomicBool::new(false),
+                waker: Mutex::new(None),
+            }),
+            sched_windows: Mutex::new(None),
             ext: Arc::new(ext),
         }
     }
 
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.account = Arc::new(ResourceAccount::new(self.id, limits));
+        self
+    }
+
+    /// Restricts the CPUs whose runtime accepts the inspector, on registration and migration.
+    pub fn with_affinity(mut self, affinity: CpuAffinity) -> Self {
+        self.affinity = affinity;
+        self
+    }
+
+    /// Counts the rounds of the inspector lasting longer than `threshold` as overruns, warning
+    /// about each of them.
+    pub fn with_overrun_threshold(mut self, threshold: Duration) -> Self {
+        self.overrun_threshold = Some(threshold);
+        self
+    }
+
     pub fn id(&self) -> InspectorId {
         self.id
     }
@@ -80,6 +388,100 @@ impl Inspector {
         self.ext.clone()
     }
 
+    pub fn affinity(&self) -> CpuAffinity {
+        self.affinity
+    }
+
+    pub fn limits(&self) -> ResourceLimits {
+        self.account.limits
+    }
+
+    pub fn resource_usage(&self) -> ResourceUsage {
+        self.account.usage()
+    }
+
+    pub fn overrun_threshold(&self) -> Option<Duration> {
+        self.overrun_threshold
+    }
+
+    pub fn stats(&self) -> InspectorStats {
+        let cpu = hal!().cpu();
+        let rounds = &self.rounds;
+        InspectorStats {
+            rounds: rounds.rounds.load(Ordering::SeqCst),
+            run_time: cpu.ticks_to_time(rounds.ticks.load(Ordering::SeqCst)),
+            last_round: cpu.ticks_to_time(rounds.last.load(Ordering::SeqCst)),
+            longest_round: cpu.ticks_to_time(rounds.longest.load(Ordering::SeqCst)),
+            overruns: rounds.overruns.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Takes the windows announced to the inspector by the last schedule-table handover
+    /// changing them, if it has not been taken yet.
+    ///
+    /// An empty list means the inspector no longer has any window.
+    pub fn take_sched_windows(&self) -> Option<Vec<RuntimeSchedTableEntry>> {
+        self.sched_windows.lock().take()
+    }
+
+    /// Takes a reference to the inspector, which fails once it is retired.
+    pub fn acquire_ref(&self) -> Result<InspectorRef> {
+        self.refs
+            .count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count & INSPECTOR_DRAINING == 0).then_some(count + 1)
+            })
+            .map_err(|_| InternalError::InvalidInspectorStatus)?;
+        Ok(InspectorRef(self.refs.clone()))
+    }
+
+    pub fn draining(&self) -> bool {
+        self.refs.count.load(Ordering::SeqCst) & INSPECTOR_DRAINING != 0
+    }
+
+    /// Returns the status and the number of queued executors, each unless locked, for reports
+    /// that must not wait on a lock.
+    pub fn try_report(&self) -> (Option<InspectorStatus>, Option<usize>) {
+        (
+            self.status.try_lock().map(|status| *status),
+            self.scheduler
+                .try_read()
+                .and_then(|scheduler| scheduler.queue.try_len()),
+        )
+    }
+
+    /// Logs the inspector and each of its executors with its tasks.
+    pub(crate) fn report_executors(&self) {
+        let status = self.status.try_lock().map(|status| *status);
+        let Some(scheduler) = self.scheduler.try_read() else {
+            info!(
+                "  inspector {}: {}, executors locked, {}",
+                self.id,
+                Locked(status),
+                self.account.usage()
+            );
+            return;
+        };
+        info!(
+            "  inspector {}: {}, {} executors, {}",
+            self.id,
+            Locked(status),
+            scheduler.registry.len(),
+            self.account.usage()
+        );
+        for (id, executor) in scheduler.registry.iter() {
+            info!(
+                "    executor {}: {:?}, {:?}",
+                id,
+                executor.status(),
+                executor.priority()
+            );
+            for task in executor.top(usize::MAX) {
+                info!("      {}", task);
+            }
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.scheduler.read().registry.is_empty()
     }
@@ -97,29 +499,126 @@ impl Inspector {
         Ok(())
     }
 
-    pub fn register(&self, executor: Pin<Box<Executor>>) -> Result<()> {
+    /// Pauses the inspector, which switches out at its next switch point if it is running.
+    pub(crate) fn pause(&self) -> Result<()> {
+        let mut status = self.status.lock();
+        *status = match *status {
+            InspectorStatus::Idle => InspectorStatus::Paused(None),
+            InspectorStatus::Running(executor_id) | InspectorStatus::Pending(executor_id) => {
+                InspectorStatus::Paused(Some(executor_id))
+            }
+            InspectorStatus::Paused(_) => return Err(InternalError::InvalidInspectorStatus),
+        };
+        Ok(())
+    }
+
+    /// Resumes the paused inspector, returning whether it is still to switch out, in which
+    /// case it is queued again once it does.
+    pub(crate) fn resume(&self) -> Result<bool> {
+        let mut status = self.status.lock();
+        let (resumed, running) = match *status {
+            InspectorStatus::Paused(None) => (InspectorStatus::Idle, false),
+            InspectorStatus::Paused(Some(executor_id)) => {
+                (InspectorStatus::Pending(executor_id), true)
+            }
+            _ => return Err(InternalError::InvalidInspectorStatus),
+        };
+        *status = resumed;
+        Ok(running)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self.status(), InspectorStatus::Paused(_))
+    }
+
+    /// Registers `executor`, charging it and the tasks it holds to the inspector, which go on
+    /// charging the tasks spawned in it.
+    pub fn register(&self, mut executor: Pin<Box<Executor>>) -> Result<()> {
         let mut scheduler = self.scheduler.write();
 
         let id = executor.id();
         let priority = executor.priority();
 
-        scheduler
-            .registry
-            .try_insert(id, executor)
-            .map_err(|_| InternalError::DuplicateExecutorId)?;
+        if scheduler.registry.contains_key(&id) {
+            return Err(InternalError::DuplicateExecutorId);
+        }
+        self.account.charge(ResourceKind::Executors, 1)?;
+        if let Err(err) = self
+            .account
+            .charge(ResourceKind::Tasks, executor.task_count())
+        {
+            self.account.uncharge(ResourceKind::Executors, 1);
+            return Err(err);
+        }
+        executor.set_account(self.account.clone());
+
+        scheduler.registry.insert(id, executor);
         scheduler.queue.enqueue(priority, id);
         Ok(())
     }
 
     pub fn unregister(&self, executor_id: ExecutorId) -> Result<()> {
-        self.scheduler
+        let executor = self
+            .scheduler
             .write()
             .registry
             .remove(&executor_id)
             .ok_or(InternalError::InvalidExecutorId)?;
+        self.account.uncharge(ResourceKind::Executors, 1);
+        self.account
+            .uncharge(ResourceKind::Tasks, executor.task_count());
+        Ok(())
+    }
+
+    /// Changes the priority of the executor `id`, moving it to its new place if it is queued.
+    pub fn set_priority(&self, id: ExecutorId, priority: ExecutorPriority) -> Result<()> {
+        let mut scheduler = self.scheduler.write();
+        scheduler
+            .registry
+            .get_mut(&id)
+            .ok_or(InternalError::InvalidExecutorId)?
+            .set_priority(priority);
+
+        let mut queued = false;
+        scheduler.queue.retain(|_, &queued_id| {
+            queued |= queued_id == id;
+            queued_id != id
+        });
+        if queued {
+            scheduler.queue.enqueue(priority, id);
+        }
         Ok(())
     }
 
+    pub fn create_timed_event(
+        &self,
+        time: Duration,
+        timeout: impl FnOnce() + Send + 'static,
+        cancel: impl FnOnce() + Send + 'static,
+    ) -> Result<TimedEventTracker> {
+        let timeout_ref = self.acquire_ref()?;
+        let cancel_ref = timeout_ref.clone();
+        self.account.charge(ResourceKind::TimedEvents, 1)?;
+
+        let timeout_account = self.account.clone();
+        let cancel_account = self.account.clone();
+        Ok(TimedEvent::create(
+            time,
+            TimedEventHandler::new(
+                move || {
+                    timeout_account.uncharge(ResourceKind::TimedEvents, 1);
+                    timeout();
+                    drop(timeout_ref);
+                },
+                move || {
+                    cancel_account.uncharge(ResourceKind::TimedEvents, 1);
+                    cancel();
+                    drop(cancel_ref);
+                },
+            ),
+        ))
+    }
+
     pub fn with_current<F, R>(f: F) -> Result<R>
     where
         F: FnOnce(&Inspector) -> R,
@@ -152,6 +651,63 @@ impl Inspector {
         }
     }
 
+    /// Charges `amount` of `kind` to the inspector, for resources held outside of it such as
+    /// descriptors and shared regions, until the returned charge is dropped.
+    pub fn charge(&self, kind: ResourceKind, amount: usize) -> Result<ResourceCharge> {
+        self.account.charge(kind, amount)?;
+        Ok(ResourceCharge {
+            account: self.account.clone(),
+            kind,
+            amount,
+        })
+    }
+
+    /// Forbids new references to the inspector, returning the future completing once it is
+    /// freed.
+    pub(crate) fn start_draining(&self) -> InspectorRetirement {
+        self.refs
+            .count
+            .fetch_or(INSPECTOR_DRAINING, Ordering::SeqCst);
+        InspectorRetirement(self.refs.clone())
+    }
+
+    pub(crate) fn drained(&self) -> bool {
+        self.refs.count.load(Ordering::SeqCst) == INSPECTOR_DRAINING
+    }
+
+    pub(crate) fn complete_retirement(&self) {
+        self.refs.retired.store(true, Ordering::SeqCst);
+        if let Some(waker) = self.refs.waker.lock().take() {
+            waker.wake();
+        }
+    }
+
+    /// Charges the inspector with a round of `ticks`, checking it against the overrun threshold.
+    pub(crate) fn end_round(&self, ticks: u64) {
+        let rounds = &self.rounds;
+        rounds.rounds.fetch_add(1, Ordering::SeqCst);
+        rounds.ticks.fetch_add(ticks, Ordering::SeqCst);
+        rounds.last.store(ticks, Ordering::SeqCst);
+        rounds.longest.fetch_max(ticks, Ordering::SeqCst);
+
+        let Some(threshold) = self.overrun_threshold else {
+            return;
+        };
+        let round = hal!().cpu().ticks_to_time(ticks);
+        if round > threshold {
+            rounds.overruns.fetch_add(1, Ordering::SeqCst);
+            warn!(
+                "inspector {} overran its round: {:?} > {:?}",
+                self.id, round, threshold
+            );
+        }
+    }
+
+    pub(crate) fn notify_sched_windows(&self, windows: Vec<RuntimeSchedTableEntry>) {
+        debug!("inspector {} has new sched windows: {:?}", self.id, windows);
+        *self.sched_windows.lock() = Some(windows);
+    }
+
     pub(crate) fn with_executor<F, R>(&self, id: ExecutorId, f: F) -> Result<R>
     where
         F: FnOnce(&mut Pin<Box<Executor>>) -> R,
@@ -164,6 +720,30 @@ impl Inspector {
             .ok_or(InternalError::InvalidExecutorId)?))
     }
 
+    /// Runs `f` on the executor with the most ready tasks among the migratable ones of
+    /// `priority` other than `thief`, if any has a ready task.
+    pub(crate) fn with_most_loaded<F, R>(
+        &self,
+        thief: ExecutorId,
+        priority: ExecutorPriority,
+        f: F,
+    ) -> Option<R>
+    where
+        F: FnOnce(&mut Pin<Box<Executor>>) -> R,
+    {
+        let mut scheduler = self.scheduler.write();
+        scheduler
+            .registry
+            .iter_mut()
+            .filter(|(&id, executor)| {
+                id != thief && executor.priority() == priority && executor.migratable()
+            })
+            .map(|(_, executor)| (executor.ready(), executor))
+            .filter(|&(ready, _)| ready > 0)
+            .max_by_key(|&(ready, _)| ready)
+            .map(|(_, executor)| f(executor))
+    }
+
     pub(crate) fn dequeue(&self) -> Option<ExecutorId> {
         let mut scheduler = self.scheduler.write();
         while let Some((_, id)) = scheduler.queue.dequeue() {
@@ -192,6 +772,41 @@ impl Inspector {
         Ok(())
     }
 
+    /// Keeps the executor `id` out of the queue until `until`, when a timed event queues it
+    /// again.
+    ///
+    /// The timed event is not charged to the limits of the inspector, which would otherwise
+    /// leave the executor throttled for good once they are reached.
+    fn throttle(&self, id: ExecutorId, until: Duration) -> Result<()> {
+        let Ok(inspector_ref) = self.acquire_ref() else {
+            return self.enqueue(id);
+        };
+        trace!("executor {} is throttled until {:?}", id, until);
+
+        self.scheduler.write().throttled.push(id);
+        TimedEvent::create(
+            until,
+            TimedEventHandler::new(
+                move || {
+                    let _ = inspector_ref.with(|is| is.replenish(id));
+                },
+                || {},
+            ),
+        );
+        Ok(())
+    }
+
+    fn replenish(&self, id: ExecutorId) {
+        let mut scheduler = self.scheduler.write();
+
+        if let Some(index) = scheduler.throttled.iter().position(|&x| x == id) {
+            scheduler.throttled.swap_remove(index);
+            if let Some(executor) = scheduler.registry.get(&id) {
+                scheduler.queue.enqueue(executor.priority(), id);
+            }
+        }
+    }
+
     pub(crate) fn set_current(&self, id: Option<ExecutorId>) {
         let mut status = self.status.lock();
 
@@ -200,12 +815,19 @@ impl Inspector {
                 InspectorStatus::Running(ref mut executor_id) => {
                     *executor_id = id;
                 }
+                InspectorStatus::Paused(ref mut executor_id) => {
+                    *executor_id = Some(id);
+                }
                 _ => {
                     *status = InspectorStatus::Running(id);
                 }
             }
-        } else if let InspectorStatus::Running(_) = *status {
-            *status = InspectorStatus::Idle;
+        } else {
+            match *status {
+                InspectorStatus::Running(_) => *status = InspectorStatus::Idle,
+                InspectorStatus::Paused(Some(_)) => *status = InspectorStatus::Paused(None),
+                _ => {}
+            }
         }
     }
 
@@ -223,27 +845,44 @@ impl Inspector {
 
             let executor_switch_ctx = Executor::with_current(|ex| ex.switch_context()).unwrap();
 
+            let begin = hal!().cpu().get_ticks();
             unsafe {
                 arch::switch_with_int_saved_on(
                     runtime_switch_ctx.as_usize(),
                     executor_switch_ctx.as_usize(),
                 );
             }
+            let ticks = hal!().cpu().get_ticks().saturating_sub(begin);
 
             Inspector::with_current(|is| is.set_current(None)).unwrap();
 
             trace!("switch from executor {:?}", executor_id);
 
+            // The executor is charged on its switch back rather than on timer interrupts, which
+            // may come in the middle of its run.
             let switch_out = Inspector::with_current(|is| {
-                if is
-                    .with_executor(executor_id, |ex| ex.status() == ExecutorStatus::Finished)
-                    .unwrap()
-                {
+                let (finished, throttled) = is
+                    .with_executor(executor_id, |ex| {
+                        let finished = ex.status() == ExecutorStatus::Finished;
+                        let throttled = if finished {
+                            None
+                        } else {
+                            ex.charge_budget(ticks)
+                        };
+                        (finished, throttled)
+                    })
+                    .unwrap();
+                if finished {
                     is.unregister(executor_id).unwrap();
+                } else if let Some(until) = throttled {
+                    is.throttle(executor_id, until).unwrap();
                 } else {
                     is.enqueue(executor_id).unwrap();
                 }
-                matches!(is.status(), InspectorStatus::Pending(_))
+                matches!(
+                    is.status(),
+                    InspectorStatus::Pending(_) | InspectorStatus::Paused(_)
+                ) || Runtime::is_shutting_down()
             })
             .unwrap();
 
@@ -253,3 +892,55 @@ impl Inspector {
         }
     }
 }
+
+impl InspectorRef {
+    pub fn id(&self) -> InspectorId {
+        self.0.id
+    }
+
+    /// Resolves the inspector on whichever CPU it is registered, even if it is retired.
+    pub fn with<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&Inspector) -> R,
+    {
+        Runtime::with_registered(self.0.id, f)
+    }
+}
+
+impl Clone for InspectorRef {
+    fn clone(&self) -> Self {
+        self.0.count.fetch_add(1, Ordering::SeqCst);
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for InspectorRef {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == INSPECTOR_DRAINING | 1 {
+            Runtime::reap(self.0.id);
+        }
+    }
+}
+
+impl InspectorRetirement {
+    pub fn id(&self) -> InspectorId {
+        self.0.id
+    }
+
+    pub fn retired(&self) -> bool {
+        self.0.retired.load(Ordering::SeqCst)
+    }
+}
+
+impl Future for InspectorRetirement {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        *self.0.waker.lock() = Some(cx.waker().clone());
+        if self.retired() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Running closures on other CPUs.
+//!
+//! Calls go through the bounded lock-free call queue of each target CPU, which its
+//! software-interrupt handler drains once an IPI reaches it. A CPU among the targets of its
+//! own call runs the closure in place, without an IPI.
+
+use core::time::Duration;
+
+use jrinx_error::Result;
+use jrinx_trap::smp;
+
+/// Runs `f` on each of `cpus` without waiting for the remote ones.
+///
+/// This fails with [`InternalError::SmpCallQueueFull`](jrinx_error::InternalError) if the
+/// call queue of a target is full, in which case `f` is withdrawn from the other targets.
+pub fn remote_call(cpus: &[usize], f: impl Fn() + Send + Sync + 'static) -> Result<()> {
+    smp::call_async(cpus, f)
+}
+
+/// Runs `f` on each of `cpus`, waiting up to `timeout` for all of them to acknowledge.
+///
+/// Besides the errors of [`remote_call`], this fails with
+/// [`InternalError::SmpCallTimeout`](jrinx_error::InternalError) if some target has not run
+/// `f` in time.
+pub fn remote_call_sync(
+    cpus: &[usize],
+    timeout: Duration,
+    f: impl Fn() + Send + Sync + 'static,
+) -> Result<()> {
+    smp::call(cpus, timeout, move || {
+        f();
+        0
+    })?
+    .into_result()
+    .map(|_| ())
+}
@@ -0,0 +1,128 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use alloc::sync::Arc;
+use jrinx_error::{InternalError, Result};
+use spin::Mutex;
+
+use crate::{
+    cancel::CancellationToken,
+    executor::{Executor, ParkGuard},
+};
+
+/// A future resolving with the output of a spawned task once it completes, or with
+/// [`InternalError::TaskCancelled`] if the task is cancelled before.
+///
+/// Dropping the handle detaches the task, which keeps running and drops its output. The task
+/// may run in any executor of the inspector awaiting the handle.
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+    cancel: CancellationToken,
+    parked: Option<ParkGuard>,
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    finished: bool,
+    cancelled: bool,
+    waker: Option<Waker>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns whether the task completed or was dropped after being cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().finished
+    }
+
+    /// Cancels the task, which is a no-op if it is finished already.
+    pub fn abort(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        {
+            let mut state = self.state.lock();
+            if state.finished {
+                let output = if state.cancelled {
+                    Err(InternalError::TaskCancelled)
+                } else {
+                    Ok(state
+                        .output
+                        .take()
+                        .expect("join handle polled after completion"))
+                };
+                drop(state);
+                self.parked = None;
+                return Poll::Ready(output);
+            }
+            state.waker = Some(cx.waker().clone());
+        }
+
+        // The executor would finish once its queue is empty, dropping the awaiting task, if
+        // the joined task runs in another executor. The awaiting task may have been stolen by
+        // a sibling since the last poll, so the current executor is parked again.
+        self.parked = Executor::with_current(|ex| ex.park()).ok();
+        Poll::Pending
+    }
+}
+
+/// Wraps `future` to hand its output over to the returned handle, cancelled by `cancel`.
+pub(crate) fn joinable<F>(
+    future: F,
+    cancel: CancellationToken,
+) -> (impl Future<Output = ()>, JoinHandle<F::Output>)
+where
+    F: Future,
+{
+    let state = Arc::new(Mutex::new(JoinState {
+        output: None,
+        finished: false,
+        cancelled: false,
+        waker: None,
+    }));
+
+    let handle = JoinHandle {
+        state: state.clone(),
+        cancel,
+        parked: None,
+    };
+
+    // The completion is dropped once the task completes, or along with the task if it is
+    // cancelled first.
+    let completion = Completion(state);
+    let future = async move {
+        let completion = completion;
+        let output = future.await;
+        completion.0.lock().output = Some(output);
+    };
+
+    (future, handle)
+}
+
+/// Wakes the handle once the task is done, marking it cancelled if no output was handed over.
+struct Completion<T>(Arc<Mutex<JoinState<T>>>);
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.0.lock();
+            state.finished = true;
+            state.cancelled = state.output.is_none();
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
@@ -1,13 +1,23 @@
 #![no_std]
+#![feature(allocator_api)]
 #![feature(asm_const)]
 #![feature(iter_map_windows)]
 #![feature(map_try_insert)]
 #![feature(sync_unsafe_cell)]
 
 mod arch;
+pub mod cancel;
 pub mod executor;
+pub mod group;
 pub mod inspector;
+pub mod ipi;
+pub mod join;
+pub mod preempt;
 pub mod runtime;
+pub mod sync;
+pub mod task_local;
+pub mod time;
+pub mod workqueue;
 
 extern crate alloc;
 #[macro_use]
@@ -16,19 +26,33 @@ extern crate log;
 extern crate jrinx_hal;
 
 use core::{
+    fmt::Display,
     future::Future,
+    panic::Location,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use alloc::boxed::Box;
+use cancel::CancellationToken;
 use executor::Executor;
+use join::JoinHandle;
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::{Cpu, Hal};
 use jrinx_serial_id_macro::SerialId;
 use jrinx_util::fastpq::FastPriority;
+use task_local::TaskLocals;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SerialId)]
 pub struct TaskId(u64);
 
+impl TaskId {
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TaskPriority(FastPriority);
 
@@ -39,6 +63,10 @@ impl TaskPriority {
     pub const fn new(priority: u8) -> Self {
         Self(FastPriority::new(priority))
     }
+
+    pub const fn value(self) -> u8 {
+        self.0.value()
+    }
 }
 
 impl From<TaskPriority> for FastPriority {
@@ -53,35 +81,254 @@ impl From<u8> for TaskPriority {
     }
 }
 
+/// Time by which a task should be dispatched, on the clock of [`jrinx_hal::Cpu::get_time`].
+///
+/// Executors in EDF mode poll the ready task with the earliest deadline first, see
+/// [`Executor::with_edf`]. Every executor counts the tasks it dispatches late.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskDeadline(Duration);
+
+impl TaskDeadline {
+    pub const fn new(at: Duration) -> Self {
+        Self(at)
+    }
+
+    /// Returns the deadline `relative` from now.
+    pub fn after(relative: Duration) -> Self {
+        Self(hal!().cpu().get_time().saturating_add(relative))
+    }
+
+    pub const fn at(self) -> Duration {
+        self.0
+    }
+}
+
+/// Poll counters of a task, all zero if the `task-stats` feature is off.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TaskStats {
+    pub polls: u64,
+    /// Cycles spent polling the task, counted on whichever CPU polled it.
+    pub busy_cycles: u64,
+    pub last_poll_cycles: u64,
+}
+
+impl Display for TaskStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "polls = {}, busy-cycles = {}, last-poll-cycles = {}",
+            self.polls, self.busy_cycles, self.last_poll_cycles
+        )
+    }
+}
+
+/// Name of a task, given at spawn or made up from its id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskName {
+    Named(&'static str),
+    Anonymous(TaskId),
+}
+
+impl Display for TaskName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Named(name) => f.write_str(name),
+            Self::Anonymous(id) => write!(f, "task-{}", id.value()),
+        }
+    }
+}
+
 pub struct Task {
     id: TaskId,
     priority: TaskPriority,
+    deadline: Option<TaskDeadline>,
+    name: Option<&'static str>,
+    spawn_site: &'static Location<'static>,
+    pinned: bool,
+    cancel: CancellationToken,
+    locals: TaskLocals,
+    #[cfg(feature = "task-stats")]
+    stats: TaskStats,
     future: Pin<Box<dyn Future<Output = ()> + Send>>,
 }
 
 impl Task {
-    pub fn new(future: impl Future<Output = ()> + Send + 'static, priority: TaskPriority) -> Self {
-        Self {
+    /// Creates an anonymous task, spawned at the caller.
+    #[track_caller]
+    pub fn new<F>(future: F, priority: TaskPriority) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self::try_new(future, priority).expect("not enough memory for a task")
+    }
+
+    /// Creates a task like [`Task::new`], failing with [`InternalError::NotEnoughMem`] instead
+    /// of aborting if the heap cannot hold it.
+    #[track_caller]
+    pub fn try_new<F>(future: F, priority: TaskPriority) -> Result<Self>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let future = Box::try_new(future).map_err(|_| InternalError::NotEnoughMem)?;
+        Ok(Self {
             id: TaskId::new(),
             priority,
-            future: Box::pin(future),
+            deadline: None,
+            name: None,
+            spawn_site: Location::caller(),
+            pinned: false,
+            cancel: CancellationToken::try_new()?,
+            locals: TaskLocals::try_new()?,
+            #[cfg(feature = "task-stats")]
+            stats: TaskStats::default(),
+            future: Box::into_pin(future),
+        })
+    }
+
+    /// Creates a task like [`Task::new`], along with a handle to await its output.
+    #[track_caller]
+    pub fn new_joinable<F>(future: F, priority: TaskPriority) -> (Self, JoinHandle<F::Output>)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let cancel = CancellationToken::new();
+        let (joined, handle) = join::joinable(future, cancel.clone());
+        let mut task = Self::new(joined, priority);
+        task.cancel = cancel;
+        (task, handle)
+    }
+
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Keeps the task in the executor it is spawned in, out of reach of stealing siblings.
+    pub fn pinned(mut self) -> Self {
+        self.pinned = true;
+        self
+    }
+
+    /// Gives the task a deadline, which it may move later on with [`set_deadline`].
+    pub fn with_deadline(mut self, deadline: TaskDeadline) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Returns the deadline the task is spawned with.
+    pub fn deadline(&self) -> Option<TaskDeadline> {
+        self.deadline
+    }
+
+    /// Returns the name of the task, which is `task-<id>` unless it is given one.
+    pub fn name(&self) -> TaskName {
+        match self.name {
+            Some(name) => TaskName::Named(name),
+            None => TaskName::Anonymous(self.id),
         }
     }
 
+    pub fn spawn_site(&self) -> &'static Location<'static> {
+        self.spawn_site
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// Returns the token cancelling the task, which drops it before its next poll.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Returns the poll counters of the task, as charged by the executor polling it.
+    pub fn stats(&self) -> TaskStats {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "task-stats")] {
+                self.stats
+            } else {
+                TaskStats::default()
+            }
+        }
+    }
+
+    #[cfg(feature = "task-stats")]
+    pub(crate) fn record_poll(&mut self, cycles: u64) {
+        self.stats.polls += 1;
+        self.stats.busy_cycles += cycles;
+        self.stats.last_poll_cycles = cycles;
+    }
+
     pub fn poll(&mut self, cx: &mut Context) -> Poll<()> {
         self.future.as_mut().poll(cx)
     }
 }
 
-pub fn do_spawn(future: impl Future<Output = ()> + Send + 'static, priority: TaskPriority) {
-    Executor::with_current(|ex| {
-        ex.spawn(Task::new(future, priority)).unwrap();
+#[track_caller]
+pub fn do_spawn<F>(future: F, priority: TaskPriority) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (task, handle) = Task::new_joinable(future, priority);
+    spawn_task(task);
+    handle
+}
+
+/// Spawns `future` like [`do_spawn`], naming the task `name`.
+#[track_caller]
+pub fn do_spawn_named<F>(
+    future: F,
+    name: &'static str,
+    priority: TaskPriority,
+) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (task, handle) = Task::new_joinable(future, priority);
+    spawn_task(task.with_name(name));
+    handle
+}
+
+pub(crate) fn spawn_task(task: Task) {
+    // A task over the limit of the inspector is dropped, which its join handle reports as a
+    // cancellation.
+    Executor::with_current(|ex| match ex.spawn(task) {
+        Ok(_) | Err(InternalError::ResourceLimitExceeded(_)) => {}
+        Err(err) => panic!("failed to spawn task: {}", err),
     })
     .unwrap();
 }
 
+/// Spawns a future as a task of the current executor, returning its [`JoinHandle`].
+///
+/// The task may be given a static name and a priority, in either order before the future:
+/// `spawn!(name = "flush", pri = TaskPriority::new(5), async { ... })`. An unnamed task is
+/// named `task-<id>`.
 #[macro_export]
 macro_rules! spawn {
+    (name = $name:expr, pri = $priority:expr, $future:expr $(,)?) => {
+        $crate::do_spawn_named($future, $name, $priority.into())
+    };
+    (pri = $priority:expr, name = $name:expr, $future:expr $(,)?) => {
+        $crate::do_spawn_named($future, $name, $priority.into())
+    };
+    (name = $name:expr, $future:expr $(,)?) => {
+        $crate::do_spawn_named($future, $name, $crate::TaskPriority::default())
+    };
+    (pri = $priority:expr, $future:expr $(,)?) => {
+        $crate::do_spawn($future, $priority.into())
+    };
     ($future: expr) => {
         $crate::do_spawn($future, $crate::TaskPriority::default())
     };
@@ -90,6 +337,13 @@ macro_rules! spawn {
     };
 }
 
+/// Moves the deadline of the current task, taking effect the next time it is woken.
+///
+/// A periodic task sets the deadline of its next job before waiting for its release.
+pub fn set_deadline(deadline: Option<TaskDeadline>) -> Result<()> {
+    Executor::with_current(|ex| ex.set_task_deadline(deadline))?
+}
+
 pub async fn do_yield() {
     struct YieldNow {
         done: bool,
@@ -0,0 +1,114 @@
+//! Time slicing of tasks, preempting executors whose task runs past the slice of its priority.
+//!
+//! Each poll starts a slice, tracked by at most one timed event per CPU. Once the slice is
+//! over, the executor is marked for preemption: it switches out as soon as the task returns,
+//! or on the return path of the next interrupt if the task keeps running, kernel or user code
+//! alike. Code running with interrupts disabled, such as everything under the runtime and
+//! inspector locks, is never preempted, but spinlocks taken with interrupts enabled are not
+//! released either, so the other executors of the inspector must not contend on them.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use jrinx_hal::{Cpu, Hal, Interrupt};
+use jrinx_percpu::percpu;
+use jrinx_timed_event::{TimedEvent, TimedEventHandler, TimedEventTracker};
+use spin::Mutex;
+
+use crate::{executor::Executor, runtime::Runtime, TaskPriority};
+
+pub const DEFAULT_TIME_SLICE: Duration = Duration::from_millis(10);
+
+static TIME_SLICES: [AtomicU64; TaskPriority::NUM] =
+    [const { AtomicU64::new(DEFAULT_TIME_SLICE.as_nanos() as u64) }; TaskPriority::NUM];
+
+static PREEMPTIONS: AtomicU64 = AtomicU64::new(0);
+
+#[percpu]
+static SLICE_TIMER: Mutex<Option<TimedEventTracker>> = Mutex::new(None);
+
+/// Returns the time a task of `priority` runs before its executor is preempted.
+pub fn time_slice(priority: TaskPriority) -> Duration {
+    match TIME_SLICES[priority.value() as usize].load(Ordering::SeqCst) {
+        u64::MAX => Duration::MAX,
+        nanos => Duration::from_nanos(nanos),
+    }
+}
+
+/// Sets the time slice of the tasks of `priority`, where [`Duration::MAX`] never preempts them.
+pub fn set_time_slice(priority: TaskPriority, slice: Duration) {
+    TIME_SLICES[priority.value() as usize].store(
+        u64::try_from(slice.as_nanos()).unwrap_or(u64::MAX),
+        Ordering::SeqCst,
+    );
+}
+
+/// Returns the number of times an executor was preempted since boot.
+pub fn count() -> u64 {
+    PREEMPTIONS.load(Ordering::SeqCst)
+}
+
+/// Switches the current executor out if it is marked for preemption in the middle of a poll.
+///
+/// This is meant to be called on the return path of interrupts.
+pub fn trap_return() {
+    if !Executor::with_current(|ex| ex.take_preemption()).unwrap_or(false) {
+        return;
+    }
+
+    preempt();
+}
+
+/// Returns the end of a slice for a task of `priority` starting now, and makes sure a timed
+/// event tracks it.
+pub(crate) fn begin_slice(priority: TaskPriority) -> Duration {
+    let end = hal!().cpu().get_time().saturating_add(time_slice(priority));
+    arm(end);
+    end
+}
+
+/// Switches the current executor out, queueing it behind the others of its priority.
+pub(crate) fn preempt() {
+    PREEMPTIONS.fetch_add(1, Ordering::SeqCst);
+    hal!().interrupt().with_saved_on(|| {
+        Runtime::switch_yield();
+    });
+}
+
+fn arm(end: Duration) {
+    if end == Duration::MAX {
+        return;
+    }
+    hal!().interrupt().with_saved_off(|| {
+        SLICE_TIMER.with_ref(|timer| {
+            timer.lock().get_or_insert_with(|| {
+                TimedEvent::create(end, TimedEventHandler::new(expire, || {}))
+            });
+        })
+    });
+}
+
+/// Cancels the slice timer of the current CPU, so that a runtime going idle is not woken up
+/// for a slice nobody runs.
+#[cfg(feature = "tickless")]
+pub(crate) fn disarm() {
+    let timer = hal!()
+        .interrupt()
+        .with_saved_off(|| SLICE_TIMER.with_ref(|timer| timer.lock().take()));
+    if let Some(timer) = timer {
+        let _ = timer.cancel();
+    }
+}
+
+/// Marks the current executor for preemption if its slice is over, or tracks the slice of the
+/// task polled since the timer was armed.
+fn expire() {
+    SLICE_TIMER.with_ref(|timer| timer.lock().take());
+
+    let now = hal!().cpu().get_time();
+    if let Ok(Some(end)) = Executor::with_current(|ex| ex.expire_slice(now)) {
+        arm(end);
+    }
+}
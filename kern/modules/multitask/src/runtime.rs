@@ -1,24 +1,39 @@
-use core::{cell::SyncUnsafeCell, future::Future, sync::atomic::AtomicUsize, time::Duration};
+use core::{
+    cell::SyncUnsafeCell,
+    future::Future,
+    panic::Location,
+    sync::atomic::{AtomicBool, AtomicUsize},
+    time::Duration,
+};
 
 use alloc::{
-    collections::{BTreeMap, VecDeque},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     vec::Vec,
 };
 use jrinx_addr::VirtAddr;
 use jrinx_error::{InternalError, Result};
 use jrinx_hal::{Cpu, Hal, HaltReason, Interrupt};
+use jrinx_kpanic::{kpanic, PanicCode};
 use jrinx_percpu::percpu;
-use jrinx_timed_event::{TimedEvent, TimedEventHandler, TimedEventTracker};
+#[cfg(not(feature = "tickless"))]
+use jrinx_timed_event::{TimedEvent, TimedEventHandler};
 use mtxgroup::MutexGroup;
-use spin::{Mutex, RwLock};
+use spin::{Mutex, Once, RwLock, RwLockWriteGuard};
 
 use crate::{
     arch::{self, SwitchContext},
-    executor::{Executor, ExecutorPriority},
-    inspector::{Inspector, InspectorId, InspectorStatus},
+    executor::{self, Executor, ExecutorPriority},
+    inspector::{Inspector, InspectorId, InspectorRef, InspectorRetirement, InspectorStatus},
     Task, TaskPriority,
 };
 
+/// Longest wait between two attempts of [`Runtime::with_current_spin_timeout`].
+const MAX_LOCK_BACKOFF_TICKS: u64 = 1 << 12;
+
+/// Period of the tick waking an idle runtime up, unless it is built `tickless`.
+#[cfg(not(feature = "tickless"))]
+pub const IDLE_TICK: Duration = Duration::from_millis(10);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RuntimeStatus {
     Unused,
@@ -31,13 +46,71 @@ pub enum RuntimeStatus {
 pub struct Runtime {
     scheduler: RwLock<RuntimeInspectorScheduler>,
     status: Mutex<RuntimeStatus>,
+    reaping: Mutex<Vec<InspectorId>>,
+    migrating: Mutex<BTreeMap<InspectorId, usize>>,
     switch_context: SyncUnsafeCell<SwitchContext>,
+    scheduler_owner: LockOwner,
+}
+
+/// The last owner of a lock taken for writing, recorded under the `lock-debug` feature to tell
+/// who holds a lock for too long.
+struct LockOwner {
+    #[cfg(feature = "lock-debug")]
+    cpu_id: AtomicUsize,
+    #[cfg(feature = "lock-debug")]
+    site: core::sync::atomic::AtomicPtr<Location<'static>>,
+}
+
+impl LockOwner {
+    const fn new() -> Self {
+        Self {
+            #[cfg(feature = "lock-debug")]
+            cpu_id: AtomicUsize::new(0),
+            #[cfg(feature = "lock-debug")]
+            site: core::sync::atomic::AtomicPtr::new(core::ptr::null_mut()),
+        }
+    }
+
+    #[track_caller]
+    fn record(&self) {
+        #[cfg(feature = "lock-debug")]
+        {
+            self.cpu_id
+                .store(hal!().cpu().id(), core::sync::atomic::Ordering::SeqCst);
+            self.site.store(
+                Location::caller() as *const _ as *mut _,
+                core::sync::atomic::Ordering::SeqCst,
+            );
+        }
+    }
+
+    /// Returns the CPU and the call site of the last owner, if recorded.
+    fn last(&self) -> Option<(usize, &'static Location<'static>)> {
+        #[cfg(feature = "lock-debug")]
+        if let Some(site) = unsafe {
+            self.site
+                .load(core::sync::atomic::Ordering::SeqCst)
+                .as_ref()
+        } {
+            return Some((self.cpu_id.load(core::sync::atomic::Ordering::SeqCst), site));
+        }
+        None
+    }
 }
 
 struct RuntimeInspectorScheduler {
     registry: BTreeMap<InspectorId, Inspector>,
     queue: VecDeque<InspectorId>,
     sched_table: Option<RuntimeSchedTable>,
+    pending_sched_table: Option<RuntimeSchedTable>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeSchedReplacePolicy {
+    /// Rejects a table that leaves a currently scheduled inspector without windows.
+    Strict,
+    /// Accepts such a table, pausing the inspectors left without windows until resumed.
+    PauseOmitted,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -53,7 +126,7 @@ pub struct RuntimeSchedTable {
     table: Vec<RuntimeSchedTableEntry>,
     next: AtomicUsize,
     datum: Mutex<Duration>,
-    events: Mutex<VecDeque<TimedEventTracker>>,
+    armed: AtomicBool,
 }
 
 impl Default for Runtime {
@@ -69,9 +142,13 @@ impl Runtime {
                 registry: BTreeMap::new(),
                 queue: VecDeque::new(),
                 sched_table: None,
+                pending_sched_table: None,
             }),
             status: Mutex::new(RuntimeStatus::Unused),
+            reaping: Mutex::new(Vec::new()),
+            migrating: Mutex::new(BTreeMap::new()),
             switch_context: SyncUnsafeCell::new(SwitchContext::new_runtime()),
+            scheduler_owner: LockOwner::new(),
         }
     }
 
@@ -79,11 +156,20 @@ impl Runtime {
         *self.status.lock() = RuntimeStatus::Init;
     }
 
+    /// Enacts `sched_table`, which takes over from round-robin scheduling once the runtime
+    /// switches back. Every inspector of the table must be registered.
     pub fn enact_sched_table(&self, sched_table: RuntimeSchedTable) -> Result<()> {
-        let mut scheduler = self.scheduler.write();
+        let mut scheduler = self.write_scheduler();
         if scheduler.sched_table.is_some() {
             return Err(InternalError::DuplicateRuntimeSchedTable);
         }
+        if !sched_table
+            .table
+            .iter()
+            .all(|entry| scheduler.registry.contains_key(&entry.inspector_id))
+        {
+            return Err(InternalError::InvalidInspectorId);
+        }
         scheduler.queue.retain(|&id| {
             sched_table
                 .table
@@ -94,17 +180,117 @@ impl Runtime {
         Ok(())
     }
 
+    /// Replaces the enacted schedule table with `sched_table` at the next major-frame boundary.
+    ///
+    /// Both tables are kept until the current frame is over, so no window is cut short. Every
+    /// inspector of the new table must be registered, and the inspectors whose windows change
+    /// are notified of their new timing at the handover.
+    pub fn replace_sched_table(
+        &self,
+        sched_table: RuntimeSchedTable,
+        policy: RuntimeSchedReplacePolicy,
+    ) -> Result<()> {
+        let mut scheduler = self.write_scheduler();
+        let Some(current) = scheduler.sched_table.as_ref() else {
+            return Err(InternalError::InvalidRuntimeSchedTable);
+        };
+        if scheduler.pending_sched_table.is_some() {
+            return Err(InternalError::DuplicateRuntimeSchedTable);
+        }
+        if !sched_table
+            .table
+            .iter()
+            .all(|entry| scheduler.registry.contains_key(&entry.inspector_id))
+        {
+            return Err(InternalError::InvalidInspectorId);
+        }
+
+        let omitted = current
+            .inspectors()
+            .into_iter()
+            .filter(|&id| scheduler.registry.contains_key(&id) && !sched_table.schedules(id))
+            .collect::<Vec<_>>();
+        if !omitted.is_empty() {
+            match policy {
+                RuntimeSchedReplacePolicy::Strict => {
+                    return Err(InternalError::UnscheduledInspector);
+                }
+                RuntimeSchedReplacePolicy::PauseOmitted => {
+                    for id in omitted {
+                        // An inspector paused already stays so.
+                        let _ = scheduler.registry[&id].pause();
+                    }
+                }
+            }
+        }
+
+        scheduler.queue.retain(|&id| !sched_table.schedules(id));
+        scheduler.pending_sched_table = Some(sched_table);
+        Ok(())
+    }
+
     pub fn revoke_sched_table(&self) -> Result<RuntimeSchedTable> {
-        self.scheduler
-            .write()
+        let mut scheduler = self.write_scheduler();
+        scheduler.pending_sched_table = None;
+        scheduler
             .sched_table
             .take()
             .ok_or(InternalError::InvalidRuntimeSchedTable)
     }
 
+    /// Pauses the inspector `id`, which keeps its executors and their state, but is not
+    /// scheduled until resumed. The running inspector is paused at its next switch point.
+    ///
+    /// Inspectors scheduled by a schedule table cannot be paused, since their windows are
+    /// fixed, and pausing a paused or finished inspector fails.
+    pub fn pause_inspector(&self, id: InspectorId) -> Result<()> {
+        let mut scheduler = self.write_scheduler();
+        let inspector = scheduler
+            .registry
+            .get(&id)
+            .ok_or(InternalError::InvalidInspectorId)?;
+        if [&scheduler.sched_table, &scheduler.pending_sched_table]
+            .into_iter()
+            .flatten()
+            .any(|table| table.schedules(id))
+        {
+            return Err(InternalError::InvalidRuntimeSchedTable);
+        }
+        let finished = inspector.draining()
+            || (inspector.is_empty() && inspector.status() == InspectorStatus::Idle);
+        if finished {
+            return Err(InternalError::InvalidInspectorStatus);
+        }
+        inspector.pause()?;
+
+        scheduler.queue.retain(|&queued| queued != id);
+        Ok(())
+    }
+
+    /// Resumes an inspector paused by [`Runtime::pause_inspector`] or by a schedule-table
+    /// replacement.
+    ///
+    /// It is scheduled in a round-robin manner again once no schedule table is enacted.
+    pub fn resume_inspector(&self, id: InspectorId) -> Result<()> {
+        let mut scheduler = self.write_scheduler();
+        let switching = scheduler
+            .registry
+            .get(&id)
+            .ok_or(InternalError::InvalidInspectorId)?
+            .resume()?;
+        // An inspector paused while running is still to be queued once it switches out.
+        if !switching && *self.status.lock() != RuntimeStatus::Running(id) {
+            scheduler.queue.push_back(id);
+        }
+        Ok(())
+    }
+
     pub fn register(&self, inspector: Inspector) -> Result<()> {
+        if !inspector.affinity().contains(self.cpu_id()) {
+            return Err(InternalError::InvalidCpuAffinity);
+        }
         let id = inspector.id();
-        let mut inspectors = self.scheduler.write();
+        let mut inspectors = self.write_scheduler();
         inspectors
             .registry
             .try_insert(id, inspector)
@@ -113,15 +299,142 @@ impl Runtime {
         Ok(())
     }
 
+    /// Registers `inspector` on the least loaded runtime it has affinity with, kicking that CPU
+    /// if it is not the current one, and returns the id of the CPU.
+    ///
+    /// The load of a runtime is the number of inspectors queued or running on it. Ties are
+    /// broken in favor of the current CPU, sparing an IPI, then of the lowest CPU id. Runtimes
+    /// driven by a schedule table take no balanced inspectors.
+    pub fn register_balanced(inspector: Inspector) -> Result<usize> {
+        let local = hal!().cpu().id();
+        let cpu_id = hal!().interrupt().with_saved_off(|| {
+            // Every scheduler is locked at once, in CPU order, so that concurrent balancing on
+            // other CPUs neither deadlocks nor picks from a stale view.
+            let mut schedulers: Vec<_> = RUNTIME.iter().map(|rt| rt.write_scheduler()).collect();
+
+            let (cpu_id, _) = RUNTIME
+                .iter()
+                .zip(&schedulers)
+                .enumerate()
+                .filter_map(|(cpu_id, (rt, scheduler))| {
+                    let status = *rt.status.lock();
+                    (inspector.affinity().contains(cpu_id)
+                        && status != RuntimeStatus::Unused
+                        && scheduler.sched_table.is_none())
+                    .then(|| {
+                        let running = matches!(status, RuntimeStatus::Running(_));
+                        (cpu_id, scheduler.queue.len() + running as usize)
+                    })
+                })
+                .min_by_key(|&(cpu_id, load)| (load, cpu_id != local, cpu_id))
+                .ok_or(InternalError::InvalidCpuAffinity)?;
+
+            let scheduler = &mut schedulers[cpu_id];
+            let id = inspector.id();
+            scheduler
+                .registry
+                .try_insert(id, inspector)
+                .map_err(|_| InternalError::DuplicateInspectorId)?;
+            scheduler.queue.push_back(id);
+            Ok(cpu_id)
+        })?;
+
+        trace!("inspector balanced to cpu#{}", cpu_id);
+        if cpu_id != local {
+            hal!().interrupt().send_ipi(&[cpu_id]);
+        }
+        Ok(cpu_id)
+    }
+
     pub fn unregister(&self, id: InspectorId) -> Result<()> {
-        self.scheduler
-            .write()
+        self.write_scheduler()
             .registry
             .remove(&id)
             .ok_or(InternalError::InvalidInspectorId)?;
         Ok(())
     }
 
+    /// Moves the inspector `id` to the runtime of `cpu_id`, kicking that CPU to run it.
+    ///
+    /// The running inspector is moved once it switches out, and moving an inspector to its
+    /// own CPU does nothing. Inspectors scheduled or paused by a schedule table cannot move.
+    pub fn migrate_inspector(&self, id: InspectorId, cpu_id: usize) -> Result<()> {
+        if cpu_id == self.cpu_id() {
+            return self.with_inspector(id, |_| ());
+        }
+        if cpu_id >= hal!().cpu().nproc() {
+            return Err(InternalError::InvalidCpuId(cpu_id));
+        }
+        Runtime::with_spec_cpu(cpu_id, |_| ())?;
+
+        let inspector = {
+            let mut scheduler = self.write_scheduler();
+            let affinity = scheduler
+                .registry
+                .get(&id)
+                .ok_or(InternalError::InvalidInspectorId)?
+                .affinity();
+            if !affinity.contains(cpu_id) {
+                return Err(InternalError::InvalidCpuAffinity);
+            }
+            if [&scheduler.sched_table, &scheduler.pending_sched_table]
+                .into_iter()
+                .flatten()
+                .any(|table| table.schedules(id))
+            {
+                return Err(InternalError::InvalidRuntimeSchedTable);
+            }
+            if scheduler.registry[&id].is_paused() {
+                return Err(InternalError::InvalidInspectorStatus);
+            }
+
+            if *self.status.lock() == RuntimeStatus::Running(id) {
+                self.migrating.lock().insert(id, cpu_id);
+                return Ok(());
+            }
+            scheduler.queue.retain(|&queued| queued != id);
+            scheduler.registry.remove(&id).unwrap()
+        };
+        Runtime::ship(inspector, cpu_id);
+        Ok(())
+    }
+
+    /// Returns the CPU the runtime belongs to.
+    pub fn cpu_id(&self) -> usize {
+        RUNTIME
+            .iter()
+            .position(|rt| core::ptr::eq(rt, self))
+            .unwrap_or_else(|| {
+                kpanic!(
+                    code = PanicCode::RuntimeInvariant,
+                    "runtime outside of the per-cpu area"
+                )
+            })
+    }
+
+    pub fn inspector_ref(&self, id: InspectorId) -> Result<InspectorRef> {
+        self.with_inspector(id, |is| is.acquire_ref())?
+    }
+
+    /// Retires an inspector in two phases: it takes no new references from now on, and is
+    /// freed once the in-flight ones are dropped and it is not running.
+    ///
+    /// It keeps being scheduled meanwhile, and lookups through existing [`InspectorRef`]s keep
+    /// resolving it. Inspectors scheduled by a schedule table cannot be retired.
+    pub fn retire_inspector(&self, id: InspectorId) -> Result<InspectorRetirement> {
+        {
+            let scheduler = self.scheduler.read();
+            if [&scheduler.sched_table, &scheduler.pending_sched_table]
+                .into_iter()
+                .flatten()
+                .any(|table| table.schedules(id))
+            {
+                return Err(InternalError::InvalidRuntimeSchedTable);
+            }
+        }
+        self.retire(id)
+    }
+
     pub fn with_current<F, R>(f: F) -> R
     where
         F: FnOnce(&Runtime) -> R,
@@ -162,27 +475,118 @@ impl Runtime {
                 }
             }
 
+            if Runtime::is_shutting_down() {
+                Runtime::finish_shutdown();
+            }
+
             debug!("runtime finished running all inspectors");
 
             Runtime::halt_if_all_finished_or_ipi();
 
             debug!("runtime send ipi and wait");
-            hal!().interrupt().with_saved_on(|| {
+            // A tickless runtime only wakes up for the timed events of its CPU, such as sleepers,
+            // sched-table windows and budget replenishments, or for an IPI.
+            #[cfg(feature = "tickless")]
+            crate::preempt::disarm();
+            #[cfg(not(feature = "tickless"))]
+            let tick = TimedEvent::create(
+                hal!().cpu().get_time() + IDLE_TICK,
+                TimedEventHandler::new(|| {}, || {}),
+            );
+            // Interrupts stay masked from the check through the wait, which returns on a pending
+            // interrupt all the same, so that a wakeup coming in between is not lost.
+            if !Runtime::with_current(|rt| rt.has_work()) {
                 hal!().interrupt().wait();
-            });
+            }
+            hal!().interrupt().with_saved_on(|| {});
+            #[cfg(not(feature = "tickless"))]
+            let _ = tick.cancel();
             debug!("runtime received ipi");
         }
     }
 
+    /// Brings up the runtime of a CPU onlined after boot, with no inspector of its own, and
+    /// starts it.
+    ///
+    /// The runtime takes part in balancing and migration from then on. It counts as finished
+    /// until it is given an inspector.
+    pub fn init_secondary() -> ! {
+        RUNTIME.as_ref().init();
+        debug!("runtime onlined late");
+
+        Runtime::start();
+    }
+
     pub fn switch_yield() {
         let runtime_switch_ctx = Runtime::with_current(|rt| rt.switch_context_addr());
-        let executor_switch_ctx = Executor::with_current(|ex| ex.switch_context()).unwrap();
+        let executor_switch_ctx =
+            Executor::with_current(|ex| ex.switch_context()).unwrap_or_else(|err| {
+                kpanic!(
+                    code = PanicCode::ExecutorInvariant,
+                    "runtime switch outside of any executor: {:?}",
+                    err
+                )
+            });
+        executor::set_polling(None);
         unsafe {
             arch::switch(
                 executor_switch_ctx.as_usize(),
                 runtime_switch_ctx.as_usize(),
             );
         }
+
+        // A task switching out in the middle of its poll resumes with a fresh time slice.
+        let _ = Executor::with_current(|ex| {
+            ex.renew_slice();
+            ex.publish_polling();
+        });
+    }
+
+    /// Runs `f` on the inspectors of the current runtime, failing with
+    /// [`InternalError::BusyLock`] rather than waiting if its scheduler is locked for writing.
+    ///
+    /// This never blocks, so it suits interrupt handlers.
+    pub fn with_current_try_lock<F, R>(f: F) -> Result<R>
+    where
+        F: FnOnce(&BTreeMap<InspectorId, Inspector>) -> R,
+    {
+        Runtime::with_current(|rt| {
+            let scheduler = rt.scheduler.try_read().ok_or(InternalError::BusyLock)?;
+            Ok(f(&scheduler.registry))
+        })
+    }
+
+    /// Runs `f` on the inspectors of the current runtime, waiting for its scheduler with an
+    /// exponential backoff for up to `timeout`.
+    ///
+    /// Once the time is out, the last owner of the scheduler is logged, which is only known
+    /// under the `lock-debug` feature, and this fails with [`InternalError::BusyLock`].
+    pub fn with_current_spin_timeout<F, R>(f: F, timeout: Duration) -> Result<R>
+    where
+        F: FnOnce(&BTreeMap<InspectorId, Inspector>) -> R,
+    {
+        let cpu = hal!().cpu();
+        let begin = cpu.get_time();
+        let mut f = Some(f);
+        let mut backoff = 1;
+        loop {
+            if let Some(output) = Runtime::with_current(|rt| {
+                let scheduler = rt.scheduler.try_read()?;
+                f.take().map(|f| f(&scheduler.registry))
+            }) {
+                return Ok(output);
+            }
+
+            if cpu.get_time() - begin >= timeout {
+                Runtime::with_current(|rt| rt.warn_busy(timeout));
+                return Err(InternalError::BusyLock);
+            }
+            let until = cpu.get_ticks().saturating_add(backoff);
+            while cpu.get_ticks() < until {
+                core::hint::spin_loop();
+            }
+            backoff = (backoff * 2).min(MAX_LOCK_BACKOFF_TICKS);
+        }
     }
 
     pub fn with_registry<F, R>(&self, f: F) -> R
@@ -196,6 +600,11 @@ impl Runtime {
         *self.status.lock()
     }
 
+    /// Returns the status of the runtime, or `None` rather than waiting if it is being updated.
+    pub fn try_status(&self) -> Option<RuntimeStatus> {
+        self.status.try_lock().map(|status| *status)
+    }
+
     pub(crate) fn with_inspector<F, R>(&self, id: InspectorId, f: F) -> Result<R>
     where
         F: FnOnce(&Inspector) -> R,
@@ -208,6 +617,110 @@ impl Runtime {
             .ok_or(InternalError::InvalidInspectorId)?))
     }
 
+    /// Looks up an inspector on whichever CPU it is registered.
+    pub(crate) fn with_registered<F, R>(id: InspectorId, f: F) -> Result<R>
+    where
+        F: FnOnce(&Inspector) -> R,
+    {
+        hal!().interrupt().with_saved_off(|| {
+            RUNTIME
+                .iter()
+                .find(|rt| rt.scheduler.read().registry.contains_key(&id))
+                .ok_or(InternalError::InvalidInspectorId)?
+                .with_inspector(id, f)
+        })
+    }
+
+    /// Frees the retired inspector `id` if it is drained, on whichever CPU it is registered.
+    ///
+    /// The last reference may be dropped under the scheduler lock, in which case freeing is
+    /// left to the runtime loop.
+    pub(crate) fn reap(id: InspectorId) {
+        hal!().interrupt().with_saved_off(|| {
+            for rt in RUNTIME.iter() {
+                match rt.try_write_scheduler() {
+                    Some(scheduler) => {
+                        if rt.free_locked(scheduler, id) {
+                            return;
+                        }
+                    }
+                    None => rt.reaping.lock().push(id),
+                }
+            }
+        });
+    }
+
+    fn reap_deferred(&self) {
+        let reaping = core::mem::take(&mut *self.reaping.lock());
+        for id in reaping {
+            self.try_free(id);
+        }
+    }
+
+    fn retire(&self, id: InspectorId) -> Result<InspectorRetirement> {
+        let retirement = self.with_inspector(id, |is| is.start_draining())?;
+        self.try_free(id);
+        Ok(retirement)
+    }
+
+    /// Frees the inspector `id` if it is retired, drained and not running, which is checked
+    /// under the scheduler lock, as the inspector to run next is picked.
+    fn try_free(&self, id: InspectorId) -> bool {
+        self.free_locked(self.write_scheduler(), id)
+    }
+
+    fn free_locked(
+        &self,
+        mut scheduler: RwLockWriteGuard<RuntimeInspectorScheduler>,
+        id: InspectorId,
+    ) -> bool {
+        if *self.status.lock() == RuntimeStatus::Running(id)
+            || !scheduler.registry.get(&id).is_some_and(|is| is.drained())
+        {
+            return false;
+        }
+        scheduler.queue.retain(|&queued| queued != id);
+        self.migrating.lock().remove(&id);
+        let inspector = scheduler.registry.remove(&id).unwrap();
+        drop(scheduler);
+
+        debug!("inspector {} retired", id);
+        inspector.complete_retirement();
+        true
+    }
+
+    /// Locks the scheduler for writing, recording the owner under the `lock-debug` feature.
+    #[track_caller]
+    fn write_scheduler(&self) -> RwLockWriteGuard<'_, RuntimeInspectorScheduler> {
+        let scheduler = self.scheduler.write();
+        self.scheduler_owner.record();
+        scheduler
+    }
+
+    #[track_caller]
+    fn try_write_scheduler(&self) -> Option<RwLockWriteGuard<'_, RuntimeInspectorScheduler>> {
+        let scheduler = self.scheduler.try_write()?;
+        self.scheduler_owner.record();
+        Some(scheduler)
+    }
+
+    fn warn_busy(&self, timeout: Duration) {
+        match self.scheduler_owner.last() {
+            Some((cpu_id, site)) => warn!(
+                "scheduler of runtime#{} stays locked for {:?}, last locked by cpu#{} at {}",
+                self.cpu_id(),
+                timeout,
+                cpu_id,
+                site
+            ),
+            None => warn!(
+                "scheduler of runtime#{} stays locked for {:?}",
+                self.cpu_id(),
+                timeout
+            ),
+        }
+    }
+
     fn set_current_inspector(&self, id: Option<InspectorId>) {
         let mut status = self.status.lock();
 
@@ -225,20 +738,79 @@ impl Runtime {
         }
     }
 
+    /// Picks the next inspector, marking it running before the scheduler lock is released so
+    /// that it cannot be freed in between.
     fn pop_front(&self) -> Option<InspectorId> {
-        self.scheduler.write().queue.pop_front()
+        let mut scheduler = self.write_scheduler();
+        let mut id = scheduler.queue.pop_front();
+        while let Some(paused) =
+            id.filter(|id| scheduler.registry.get(id).is_some_and(|is| is.is_paused()))
+        {
+            trace!("skip paused inspector {:?}", paused);
+            id = scheduler.queue.pop_front();
+        }
+        if id.is_some() {
+            self.set_current_inspector(id);
+        }
+        id
     }
 
+    /// Queues the inspector `id`, unless it is paused.
     fn push_back(&self, id: InspectorId) -> Result<()> {
-        let mut scheduler = self.scheduler.write();
+        let mut scheduler = self.write_scheduler();
 
         if !scheduler.registry.contains_key(&id) {
             return Err(InternalError::InvalidInspectorId);
         }
-        scheduler.queue.push_back(id);
+        if !scheduler.registry[&id].is_paused() {
+            scheduler.queue.push_back(id);
+        }
+        Ok(())
+    }
+
+    /// Queues the inspector `id` again once it switches out, unless it is migrating, in which
+    /// case it is moved to its new CPU instead.
+    fn requeue(&self, id: InspectorId) -> Result<()> {
+        let Some(cpu_id) = self.migrating.lock().remove(&id) else {
+            return self.push_back(id);
+        };
+        let inspector = self
+            .write_scheduler()
+            .registry
+            .remove(&id)
+            .ok_or(InternalError::InvalidInspectorId)?;
+        Runtime::ship(inspector, cpu_id);
         Ok(())
     }
 
+    /// Registers an inspector taken out of another runtime on `cpu_id`, whose affinity is
+    /// already checked.
+    fn ship(inspector: Inspector, cpu_id: usize) {
+        let id = inspector.id();
+        if let Err(err) =
+            Runtime::with_spec_cpu(cpu_id, |rt| rt.register(inspector)).and_then(|result| result)
+        {
+            kpanic!(
+                code = PanicCode::RuntimeInvariant,
+                inspector = id,
+                "inspector {id:?} lost migrating to cpu#{cpu_id}: {err:?}"
+            );
+        }
+        debug!("inspector {} migrated to cpu#{}", id, cpu_id);
+
+        if cpu_id != hal!().cpu().id() {
+            hal!().interrupt().send_ipi(&[cpu_id]);
+        }
+    }
+
+    fn has_work(&self) -> bool {
+        let scheduler = self.scheduler.read();
+        !scheduler.queue.is_empty()
+            || scheduler.sched_table.is_some()
+            || scheduler.pending_sched_table.is_some()
+            || Runtime::is_shutting_down()
+    }
+
     fn switch_context_addr(&self) -> VirtAddr {
         VirtAddr::new(self.switch_context.get() as *const _ as usize)
     }
@@ -252,7 +824,49 @@ impl Runtime {
             .ok_or(InternalError::InvalidRuntimeSchedTable)
     }
 
+    fn sched_table_handover(&self) {
+        let mut scheduler = self.write_scheduler();
+        if scheduler.pending_sched_table.is_none()
+            || !scheduler
+                .sched_table
+                .as_ref()
+                .is_some_and(|table| table.at_frame_boundary())
+        {
+            return;
+        }
+
+        let old = scheduler.sched_table.take().unwrap();
+        let new = scheduler.pending_sched_table.take().unwrap();
+        new.set_datum(old.get_datum());
+
+        let mut affected = old.inspectors();
+        affected.extend(new.inspectors());
+        for id in affected {
+            let windows = new.windows_of(id);
+            if windows != old.windows_of(id) {
+                if let Some(inspector) = scheduler.registry.get(&id) {
+                    inspector.notify_sched_windows(windows);
+                }
+            }
+        }
+        for id in new.inspectors() {
+            if let Some(inspector) = scheduler.registry.get(&id) {
+                // Only paused inspectors resume, which are queued by the table alone.
+                let _ = inspector.resume();
+            }
+        }
+
+        info!("sched table replaced at {:?}", new.get_datum());
+        scheduler.sched_table = Some(new);
+        drop(scheduler);
+        drop(old);
+    }
+
     fn sched_table_next(&self) -> Option<RuntimeSchedTableEntry> {
+        if Runtime::is_shutting_down() {
+            return None;
+        }
+        self.sched_table_handover();
         self.scheduler
             .read()
             .sched_table
@@ -265,7 +879,13 @@ impl Runtime {
 
         let runtime_switch_ctx = Runtime::with_current(|rt| rt.switch_context_addr());
 
-        Runtime::with_current(|rt| rt.sched_table_start().unwrap());
+        if let Err(err) = Runtime::with_current(|rt| rt.sched_table_start()) {
+            kpanic!(
+                code = PanicCode::SchedInvariant,
+                "sched table vanished before its start: {:?}",
+                err
+            );
+        }
 
         while let Some(entry) = Runtime::with_current(|rt| rt.sched_table_next()) {
             trace!("switch into inspector {:?}", entry.inspector_id);
@@ -274,7 +894,7 @@ impl Runtime {
                 rt.set_current_inspector(Some(entry.inspector_id));
             });
 
-            Inspector::run(runtime_switch_ctx);
+            Runtime::run_round(entry.inspector_id, runtime_switch_ctx);
 
             Runtime::with_current(|rt| {
                 rt.set_current_inspector(None);
@@ -290,7 +910,8 @@ impl Runtime {
         let runtime_switch_ctx = Runtime::with_current(|rt| rt.switch_context_addr());
 
         while let Some(inspector_id) = Runtime::with_current(|rt| {
-            if rt.scheduler.read().sched_table.is_none() {
+            rt.reap_deferred();
+            if rt.scheduler.read().sched_table.is_none() && !Runtime::is_shutting_down() {
                 rt.pop_front()
             } else {
                 None
@@ -298,9 +919,7 @@ impl Runtime {
         }) {
             trace!("switch into inspector {:?}", inspector_id);
 
-            Runtime::with_current(|rt| rt.set_current_inspector(Some(inspector_id)));
-
-            Inspector::run(runtime_switch_ctx);
+            Runtime::run_round(inspector_id, runtime_switch_ctx);
 
             Runtime::with_current(|rt| {
                 rt.set_current_inspector(None);
@@ -308,29 +927,204 @@ impl Runtime {
 
             trace!("switch from inspector {:?}", inspector_id);
 
-            if Runtime::with_current(|rt| {
-                rt.with_inspector(inspector_id, |is| {
+            // A finished inspector is retired rather than unregistered, so that it is parked
+            // until the in-flight references to it are dropped.
+            let requeued = Runtime::with_current(|rt| {
+                if rt.try_free(inspector_id) {
+                    return Ok(());
+                }
+                match rt.with_inspector(inspector_id, |is| {
                     is.is_empty() && is.status() == InspectorStatus::Idle
-                })
-                .unwrap()
-            }) {
-                Runtime::with_current(|rt| rt.unregister(inspector_id).unwrap());
-            } else {
-                Runtime::with_current(|rt| rt.push_back(inspector_id).unwrap());
+                }) {
+                    Ok(true) => rt.retire(inspector_id).map(|_| ()),
+                    Ok(false) => rt.requeue(inspector_id),
+                    Err(err) => Err(err),
+                }
+            });
+            if let Err(err) = requeued {
+                kpanic!(
+                    code = PanicCode::SchedInvariant,
+                    inspector = inspector_id,
+                    "queue contains unregistered inspector {inspector_id:?}: {err:?}"
+                );
             }
         }
     }
 
+    /// Runs the current inspector until it switches out, charging it with the ticks it ran for.
+    ///
+    /// The runtime only idles between rounds, so the idle time is never charged.
+    fn run_round(inspector_id: InspectorId, runtime_switch_ctx: VirtAddr) {
+        let begin = hal!().cpu().get_ticks();
+        Inspector::run(runtime_switch_ctx);
+        let ticks = hal!().cpu().get_ticks().saturating_sub(begin);
+
+        let _ =
+            Runtime::with_current(|rt| rt.with_inspector(inspector_id, |is| is.end_round(ticks)));
+    }
+
+    /// Registers `hook` to run on [`Runtime::shutdown`], before or after the runtime is set up.
+    ///
+    /// Hooks run in the reverse order of their registration, on the first CPU in use.
+    pub fn on_shutdown(hook: fn()) {
+        SHUTDOWN_HOOKS.lock().push(hook);
+    }
+
+    /// Whether [`Runtime::shutdown`] has been requested.
+    pub fn is_shutting_down() -> bool {
+        SHUTDOWN.is_completed()
+    }
+
+    /// Logs the status of every runtime and the queue depth of each of its inspectors.
+    ///
+    /// This is meant for a system that may be stuck, so it never waits on a lock, reporting
+    /// what it holds as locked instead, and never allocates.
+    pub fn report_all() {
+        for (cpu_id, rt) in RUNTIME.iter().enumerate() {
+            let status = rt.status.try_lock().map(|status| *status);
+            if status == Some(RuntimeStatus::Unused) {
+                continue;
+            }
+            let Some(scheduler) = rt.scheduler.try_read() else {
+                match rt.scheduler_owner.last() {
+                    Some((owner, site)) => error!(
+                        "runtime#{}: {}, scheduler locked, last by cpu#{} at {}",
+                        cpu_id,
+                        Locked(status),
+                        owner,
+                        site
+                    ),
+                    None => error!("runtime#{}: {}, scheduler locked", cpu_id, Locked(status)),
+                }
+                continue;
+            };
+            error!(
+                "runtime#{}: {}, {} inspectors, {} queued",
+                cpu_id,
+                Locked(status),
+                scheduler.registry.len(),
+                scheduler.queue.len()
+            );
+            for (id, inspector) in scheduler.registry.iter() {
+                let (status, depth) = inspector.try_report();
+                error!(
+                    "runtime#{}: inspector {}: {}, {} executors queued",
+                    cpu_id,
+                    id,
+                    Locked(status),
+                    Locked(depth)
+                );
+            }
+        }
+    }
+
+    /// Logs every runtime along with its inspectors, their executors and the tasks of each.
+    pub fn report_tasks() {
+        for (cpu_id, rt) in RUNTIME.iter().enumerate() {
+            let status = rt.status.try_lock().map(|status| *status);
+            if status == Some(RuntimeStatus::Unused) {
+                continue;
+            }
+            let Some(scheduler) = rt.scheduler.try_read() else {
+                info!("runtime#{}: {}, scheduler locked", cpu_id, Locked(status));
+                continue;
+            };
+            info!(
+                "runtime#{}: {}, {} inspectors",
+                cpu_id,
+                Locked(status),
+                scheduler.registry.len()
+            );
+            for inspector in scheduler.registry.values() {
+                inspector.report_executors();
+            }
+        }
+    }
+
+    /// Shuts the system down with `reason`, from any task.
+    ///
+    /// Runtimes stop picking inspectors, and the running ones switch out once their current
+    /// executor does, so that no task is torn down in the middle of a poll. The shutdown hooks
+    /// then run and the system halts. Only the first reason requested is kept.
+    pub fn shutdown(reason: HaltReason) -> ! {
+        SHUTDOWN.call_once(|| reason);
+        info!("runtime shutdown requested");
+        hal!().interrupt().broadcast_ipi();
+
+        if Executor::with_current(|_| ()).is_err() {
+            Runtime::finish_shutdown();
+        }
+        loop {
+            Runtime::switch_yield();
+        }
+    }
+
+    fn finish_shutdown() -> ! {
+        let cpu_id = hal!().cpu().id();
+        *RUNTIME.as_ref().status.lock() = RuntimeStatus::Endpoint;
+
+        let leader = RUNTIME
+            .iter()
+            .position(|rt| *rt.status.lock() != RuntimeStatus::Unused)
+            .unwrap_or(cpu_id);
+        if cpu_id != leader {
+            loop {
+                hal!().interrupt().with_saved_on(|| {
+                    hal!().interrupt().wait();
+                });
+            }
+        }
+
+        while RUNTIME.iter().any(|rt| {
+            !matches!(
+                *rt.status.lock(),
+                RuntimeStatus::Unused | RuntimeStatus::Endpoint
+            )
+        }) {
+            core::hint::spin_loop();
+        }
+
+        let hooks = core::mem::take(&mut *SHUTDOWN_HOOKS.lock());
+        for hook in hooks.into_iter().rev() {
+            hook();
+        }
+
+        let reason = SHUTDOWN.get().copied().unwrap_or(HaltReason::NormalExit);
+        info!("runtime shut down with {:?}", reason);
+        jrinx_wallclock::anchor();
+        log::logger().flush();
+        hal!().halt(reason);
+    }
+
     fn halt_if_all_finished_or_ipi() {
         let status = MutexGroup::new(RUNTIME.iter().map(|rt| &rt.status));
         let guards = status.lock();
 
+        // A shutdown requested after this runtime last checked must not be cut short here.
+        if Runtime::is_shutting_down() {
+            drop(guards);
+            Runtime::finish_shutdown();
+        }
+
+        // A runtime onlined late is idle until it is given an inspector. Its scheduler is only
+        // tried, as it is locked before the status elsewhere; a busy one may be taking work.
         if guards.iter().count() == 1
             || guards
                 .iter()
-                .filter(|&guard| **guard != RuntimeStatus::Unused)
-                .all(|guard| **guard == RuntimeStatus::Endpoint)
+                .zip(RUNTIME.iter())
+                .filter(|&(guard, _)| **guard != RuntimeStatus::Unused)
+                .all(|(guard, rt)| match **guard {
+                    RuntimeStatus::Endpoint => true,
+                    RuntimeStatus::Init => rt
+                        .scheduler
+                        .try_read()
+                        .is_some_and(|scheduler| scheduler.registry.is_empty()),
+                    _ => false,
+                })
         {
+            info!("all runtimes finished, halting");
+            jrinx_wallclock::anchor();
+            log::logger().flush();
             hal!().halt(HaltReason::NormalExit);
         } else {
             if let Some(cpu_id) = guards
@@ -346,11 +1140,18 @@ impl Runtime {
                 }
             }
 
+            let cpu_id = hal!().cpu().id();
             *guards
                 .into_iter()
                 .zip(0..)
-                .find_map(|(guard, cpu_id)| (cpu_id == hal!().cpu().id()).then_some(guard))
-                .unwrap() = RuntimeStatus::Endpoint;
+                .find_map(|(guard, id)| (id == cpu_id).then_some(guard))
+                .unwrap_or_else(|| {
+                    kpanic!(
+                        code = PanicCode::RuntimeInvariant,
+                        cpu = cpu_id,
+                        "no runtime for cpu#{cpu_id}"
+                    )
+                }) = RuntimeStatus::Endpoint;
         }
     }
 }
@@ -367,7 +1168,7 @@ impl RuntimeSchedTable {
             table,
             next: AtomicUsize::new(0),
             datum: Mutex::default(),
-            events: Mutex::default(),
+            armed: AtomicBool::new(false),
         };
         if !sched_table.valid() {
             return Err(InternalError::InvalidRuntimeSchedTable);
@@ -379,34 +1180,43 @@ impl RuntimeSchedTable {
         *self.datum.lock() = hal!().cpu().get_time();
     }
 
+    /// Picks the next window, waiting for its start and arming its end.
+    ///
+    /// Both window boundaries are armed as the primary deadline of the CPU, so they are enforced
+    /// ahead of any timed event due at the same time.
     pub(crate) fn sched_next(&self) -> RuntimeSchedTableEntry {
-        self.events.lock().retain(|event| !event.retired());
-
+        self.armed
+            .store(true, core::sync::atomic::Ordering::Relaxed);
         let next = self.table[self
             .next
             .fetch_add(1, core::sync::atomic::Ordering::Relaxed)];
 
         if hal!().cpu().get_time() < self.get_datum() + next.offset {
-            self.events.lock().push_back(TimedEvent::create(
-                self.get_datum() + next.offset,
-                TimedEventHandler::new(|| {}, || {}),
-            ));
+            jrinx_timed_event::with_current(|tq| {
+                tq.set_primary(self.get_datum() + next.offset, || {})
+            });
             hal!().interrupt().wait();
         }
 
         if next.duration != Duration::MAX {
-            self.events.lock().push_back(TimedEvent::create(
-                self.get_datum() + next.offset + next.duration,
-                TimedEventHandler::new(
-                    || {
-                        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
-                        hal!().interrupt().with_saved_on(|| {
-                            Runtime::switch_yield();
-                        });
-                    },
-                    || {},
-                ),
-            ));
+            jrinx_timed_event::with_current(|tq| {
+                tq.set_primary(self.get_datum() + next.offset + next.duration, || {
+                    if let Err(err) =
+                        Inspector::with_current(|is| is.mark_pending()).and_then(|result| result)
+                    {
+                        kpanic!(
+                            code = PanicCode::InspectorInvariant,
+                            "window ended without a running inspector: {:?}",
+                            err
+                        );
+                    }
+                    hal!().interrupt().with_saved_on(|| {
+                        Runtime::switch_yield();
+                    });
+                })
+            });
+        } else {
+            jrinx_timed_event::with_current(|tq| tq.clear_primary());
         }
 
         if self.next.load(core::sync::atomic::Ordering::Relaxed) >= self.table.len() {
@@ -419,6 +1229,26 @@ impl RuntimeSchedTable {
         next
     }
 
+    fn at_frame_boundary(&self) -> bool {
+        self.next.load(core::sync::atomic::Ordering::Relaxed) == 0
+    }
+
+    fn inspectors(&self) -> BTreeSet<InspectorId> {
+        self.table.iter().map(|entry| entry.inspector_id).collect()
+    }
+
+    fn schedules(&self, id: InspectorId) -> bool {
+        self.table.iter().any(|entry| entry.inspector_id == id)
+    }
+
+    fn windows_of(&self, id: InspectorId) -> Vec<RuntimeSchedTableEntry> {
+        self.table
+            .iter()
+            .filter(|entry| entry.inspector_id == id)
+            .copied()
+            .collect()
+    }
+
     fn get_datum(&self) -> Duration {
         *self.datum.lock()
     }
@@ -428,14 +1258,20 @@ impl RuntimeSchedTable {
     }
 
     fn valid(&self) -> bool {
+        if self.table.is_empty() {
+            return false;
+        }
+
+        // Windows lie inside the frame, without overlapping each other.
         for i in 0..self.table.len() {
             let entry = &self.table[i];
-            if entry.offset >= self.frame_size {
+            let end = entry.offset.saturating_add(entry.duration);
+            if entry.offset >= self.frame_size || end > self.frame_size {
                 return false;
             }
             if i + 1 < self.table.len() {
                 let next_entry = &self.table[i + 1];
-                if entry.offset + entry.duration > next_entry.offset {
+                if end > next_entry.offset {
                     return false;
                 }
             }
@@ -479,20 +1315,35 @@ impl RuntimeSchedTable {
 
 impl Drop for RuntimeSchedTable {
     fn drop(&mut self) {
+        if !self.armed.load(core::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
         hal!().interrupt().with_saved_off(|| {
             warn!("drop runtime sched table");
-            for event in self.events.lock().iter() {
-                if let Err(err) = event.cancel() {
-                    warn!("failed to cancel timed event: {:?}", err);
-                }
-            }
+            jrinx_timed_event::with_current(|tq| tq.clear_primary());
         });
     }
 }
 
+/// A value read with `try_lock`, shown as `<locked>` if the lock was held.
+pub(crate) struct Locked<T>(pub(crate) Option<T>);
+
+impl<T: core::fmt::Debug> core::fmt::Display for Locked<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.0 {
+            Some(value) => write!(f, "{:?}", value),
+            None => write!(f, "<locked>"),
+        }
+    }
+}
+
 #[percpu]
 static RUNTIME: Runtime = Runtime::new();
 
+static SHUTDOWN: Once<HaltReason> = Once::new();
+
+static SHUTDOWN_HOOKS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
 pub fn init(future: impl Future<Output = ()> + Send + Sync + 'static) {
     let inspector = Inspector::new();
     inspector
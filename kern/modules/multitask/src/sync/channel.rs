@@ -0,0 +1,339 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use alloc::{
+    collections::{BTreeSet, VecDeque},
+    sync::Arc,
+};
+use jrinx_error::{InternalError, Result};
+use jrinx_sync::IrqSafeMutex;
+
+use crate::executor::{Executor, ParkGuard};
+
+/// Creates a channel buffering up to `capacity` values, sent from any number of senders to a
+/// single receiver, possibly in other executors.
+///
+/// Senders finding the channel full wait in FIFO order. Each value received frees a slot for
+/// exactly one of them, which is reserved for it until it sends or gives up.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must be positive");
+
+    let channel = Arc::new(Channel {
+        capacity,
+        state: IrqSafeMutex::new(
+            "channel-state",
+            ChannelState {
+                buffer: VecDeque::with_capacity(capacity),
+                senders: 1,
+                receiver: true,
+                recv_waker: None,
+                next_key: 0,
+                send_waiters: VecDeque::new(),
+                granted: BTreeSet::new(),
+            },
+        ),
+    });
+
+    (
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    )
+}
+
+struct Channel<T> {
+    capacity: usize,
+    state: IrqSafeMutex<ChannelState<T>>,
+}
+
+struct ChannelState<T> {
+    buffer: VecDeque<T>,
+    senders: usize,
+    receiver: bool,
+    recv_waker: Option<Waker>,
+    next_key: u64,
+    send_waiters: VecDeque<(u64, Waker)>,
+    /// Waiting senders a slot is reserved for, which have not filled it yet.
+    granted: BTreeSet<u64>,
+}
+
+impl<T> ChannelState<T> {
+    fn vacant(&self, capacity: usize) -> bool {
+        self.buffer.len() + self.granted.len() < capacity
+    }
+
+    /// Reserves a freed slot for the first waiting sender, returning its waker.
+    fn grant(&mut self) -> Option<Waker> {
+        let (key, waker) = self.send_waiters.pop_front()?;
+        self.granted.insert(key);
+        Some(waker)
+    }
+
+    /// Buffers `value`, returning the waker of the receiver.
+    fn push(&mut self, value: T) -> Option<Waker> {
+        self.buffer.push_back(value);
+        self.recv_waker.take()
+    }
+
+    fn pop(&mut self) -> Option<(T, Option<Waker>)> {
+        let value = self.buffer.pop_front()?;
+        Some((value, self.grant()))
+    }
+}
+
+pub struct Sender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+pub struct Receiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Returns a future sending `value` once there is room for it, or failing with
+    /// [`InternalError::ChannelClosed`] if the receiver is dropped, in which case `value` is
+    /// dropped as well.
+    pub fn send(&self, value: T) -> SendFuture<'_, T> {
+        SendFuture {
+            sender: self,
+            value: Some(value),
+            key: None,
+            parked: None,
+        }
+    }
+
+    pub fn try_send(&self, value: T) -> Result<()> {
+        let mut state = self.channel.state.lock();
+        if !state.receiver {
+            return Err(InternalError::ChannelClosed);
+        }
+        if !state.vacant(self.channel.capacity) {
+            return Err(InternalError::ChannelFull);
+        }
+        let waker = state.push(value);
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.channel.state.lock().receiver
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.channel.capacity
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.channel.state.lock().senders += 1;
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.channel.state.lock();
+            state.senders -= 1;
+            if state.senders == 0 {
+                state.recv_waker.take()
+            } else {
+                None
+            }
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns a future receiving the next value, or `None` once all senders are dropped and
+    /// the buffer is drained.
+    pub fn recv(&mut self) -> RecvFuture<'_, T> {
+        RecvFuture {
+            receiver: self,
+            parked: None,
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Result<T> {
+        let mut state = self.channel.state.lock();
+        let Some((value, waker)) = state.pop() else {
+            return Err(if state.senders == 0 {
+                InternalError::ChannelClosed
+            } else {
+                InternalError::ChannelEmpty
+            });
+        };
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.channel.state.lock().buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let (buffer, waiters) = {
+            let mut state = self.channel.state.lock();
+            state.receiver = false;
+            state.granted.clear();
+            (
+                core::mem::take(&mut state.buffer),
+                core::mem::take(&mut state.send_waiters),
+            )
+        };
+        drop(buffer);
+        waiters.into_iter().for_each(|(_, waker)| waker.wake());
+    }
+}
+
+pub struct SendFuture<'a, T> {
+    sender: &'a Sender<T>,
+    value: Option<T>,
+    key: Option<u64>,
+    parked: Option<ParkGuard>,
+}
+
+/// The value is moved out as a whole, never pinned in place.
+impl<T> Unpin for SendFuture<'_, T> {}
+
+impl<T> Future for SendFuture<'_, T> {
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let sender = this.sender;
+        let channel = &sender.channel;
+        let mut state = channel.state.lock();
+
+        if !state.receiver {
+            drop(state);
+            this.key = None;
+            this.value = None;
+            this.parked = None;
+            return Poll::Ready(Err(InternalError::ChannelClosed));
+        }
+
+        match this.key {
+            Some(key) if state.granted.remove(&key) => {}
+            Some(key) => {
+                if let Some((_, waker)) = state.send_waiters.iter_mut().find(|(k, _)| *k == key) {
+                    waker.clone_from(cx.waker());
+                }
+                drop(state);
+                // The receiver may run in another executor, which would otherwise finish this
+                // one once its queue is empty, dropping the waiting task.
+                this.parked = Executor::with_current(|ex| ex.park()).ok();
+                return Poll::Pending;
+            }
+            None if state.vacant(channel.capacity) => {}
+            None => {
+                let key = state.next_key;
+                state.next_key += 1;
+                state.send_waiters.push_back((key, cx.waker().clone()));
+                drop(state);
+                this.key = Some(key);
+                this.parked = Executor::with_current(|ex| ex.park()).ok();
+                return Poll::Pending;
+            }
+        }
+
+        let value = this
+            .value
+            .take()
+            .expect("send future polled after completion");
+        let waker = state.push(value);
+        drop(state);
+
+        this.key = None;
+        this.parked = None;
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Drop for SendFuture<'_, T> {
+    fn drop(&mut self) {
+        let Some(key) = self.key else {
+            return;
+        };
+
+        // A sender giving up its reserved slot passes it on to the next one.
+        let waker = {
+            let mut state = self.sender.channel.state.lock();
+            if state.granted.remove(&key) {
+                state.grant()
+            } else {
+                state.send_waiters.retain(|(k, _)| *k != key);
+                None
+            }
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+pub struct RecvFuture<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    parked: Option<ParkGuard>,
+}
+
+impl<T> Future for RecvFuture<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.receiver.channel.state.lock();
+
+        let Some((value, waker)) = state.pop() else {
+            if state.senders == 0 {
+                drop(state);
+                self.parked = None;
+                return Poll::Ready(None);
+            }
+            state.recv_waker = Some(cx.waker().clone());
+            drop(state);
+            // The senders may run in other executors, like the holder of a mutex.
+            self.parked = Executor::with_current(|ex| ex.park()).ok();
+            return Poll::Pending;
+        };
+        drop(state);
+
+        self.parked = None;
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Some(value))
+    }
+}
+
+impl<T> Drop for RecvFuture<'_, T> {
+    fn drop(&mut self) {
+        self.receiver.channel.state.lock().recv_waker = None;
+    }
+}
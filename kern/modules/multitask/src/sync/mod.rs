@@ -0,0 +1,7 @@
+mod channel;
+mod mutex;
+mod wait_queue;
+
+pub use channel::{channel, Receiver, RecvFuture, SendFuture, Sender};
+pub use mutex::{Lock, Mutex, MutexGuard};
+pub use wait_queue::{WaitQueue, WaitUntil};
@@ -0,0 +1,215 @@
+use core::{
+    cell::UnsafeCell,
+    future::Future,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use alloc::collections::VecDeque;
+use jrinx_sync::IrqSafeMutex;
+
+use crate::executor::{Executor, ParkGuard};
+
+/// A mutex for tasks, which may be held across `.await`.
+///
+/// Contended tasks wait in FIFO order, and unlocking hands the mutex over to the first of them
+/// directly, so neither the holder nor the waiters spin or mask interrupts. Only the
+/// bookkeeping of waiters is guarded by an [`IrqSafeMutex`], for as long as it takes to
+/// update it, since a task may be preempted anywhere else.
+pub struct Mutex<T: ?Sized> {
+    state: IrqSafeMutex<MutexState>,
+    data: UnsafeCell<T>,
+}
+
+struct MutexState {
+    locked: bool,
+    /// Waiter the mutex is handed over to, which has not seen it yet.
+    granted: Option<u64>,
+    next_key: u64,
+    waiters: VecDeque<(u64, Waker)>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: IrqSafeMutex::new(
+                "task-mutex",
+                MutexState {
+                    locked: false,
+                    granted: None,
+                    next_key: 0,
+                    waiters: VecDeque::new(),
+                },
+            ),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Returns a future completing with the guard once the mutex is handed to the task.
+    ///
+    /// Dropping the future while it waits, as when its task is cancelled, withdraws it from
+    /// the waiters, or passes the mutex on if it was handed over already.
+    pub fn lock(&self) -> Lock<'_, T> {
+        Lock {
+            mutex: self,
+            key: None,
+            parked: None,
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let mut state = self.state.lock();
+        if state.locked {
+            return None;
+        }
+        state.locked = true;
+        Some(self.guard())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state.lock().locked
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn guard(&self) -> MutexGuard<'_, T> {
+        MutexGuard {
+            mutex: self,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Hands the mutex over to the first waiter, or unlocks it if there is none.
+    fn release(&self) {
+        let waker = {
+            let mut state = self.state.lock();
+            match state.waiters.pop_front() {
+                Some((key, waker)) => {
+                    state.granted = Some(key);
+                    Some(waker)
+                }
+                None => {
+                    state.locked = false;
+                    None
+                }
+            }
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+pub struct Lock<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    key: Option<u64>,
+    parked: Option<ParkGuard>,
+}
+
+impl<'a, T: ?Sized> Future for Lock<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mutex = self.mutex;
+        {
+            let mut state = mutex.state.lock();
+            match self.key {
+                Some(key) if state.granted == Some(key) => {
+                    state.granted = None;
+                }
+                Some(key) => {
+                    if let Some((_, waker)) = state.waiters.iter_mut().find(|(k, _)| *k == key) {
+                        waker.clone_from(cx.waker());
+                    }
+                    drop(state);
+                    // The holder may run in another executor, which would otherwise finish
+                    // this one once its queue is empty, dropping the waiting task.
+                    self.parked = Executor::with_current(|ex| ex.park()).ok();
+                    return Poll::Pending;
+                }
+                None if !state.locked => {
+                    state.locked = true;
+                }
+                None => {
+                    let key = state.next_key;
+                    state.next_key += 1;
+                    state.waiters.push_back((key, cx.waker().clone()));
+                    drop(state);
+                    self.key = Some(key);
+                    self.parked = Executor::with_current(|ex| ex.park()).ok();
+                    return Poll::Pending;
+                }
+            }
+        }
+
+        self.key = None;
+        self.parked = None;
+        Poll::Ready(mutex.guard())
+    }
+}
+
+impl<T: ?Sized> Drop for Lock<'_, T> {
+    fn drop(&mut self) {
+        let Some(key) = self.key else {
+            return;
+        };
+
+        let handed_over = {
+            let mut state = self.mutex.state.lock();
+            if state.granted == Some(key) {
+                state.granted = None;
+                true
+            } else {
+                state.waiters.retain(|(k, _)| *k != key);
+                false
+            }
+        };
+        if handed_over {
+            self.mutex.release();
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.release();
+    }
+}
@@ -0,0 +1,182 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use alloc::collections::{BTreeSet, VecDeque};
+use jrinx_sync::IrqSafeMutex;
+
+use crate::executor::{Executor, ParkGuard};
+
+/// Tasks waiting for a condition, woken by whoever makes it true, including interrupt
+/// handlers.
+///
+/// A waiter registers its waker before checking the condition, so a notification coming after
+/// the check is never lost. Wakeups may be spurious, and the condition is checked again before
+/// a waiter returns.
+pub struct WaitQueue {
+    waiters: IrqSafeMutex<Waiters>,
+}
+
+struct Waiters {
+    next_key: u64,
+    queue: VecDeque<(u64, Waker)>,
+    /// Waiters taken off the queue by [`WaitQueue::notify_one`], which have not been polled
+    /// since.
+    notified: BTreeSet<u64>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: IrqSafeMutex::new(
+                "wait-queue",
+                Waiters {
+                    next_key: 0,
+                    queue: VecDeque::new(),
+                    notified: BTreeSet::new(),
+                },
+            ),
+        }
+    }
+
+    /// Returns a future completing with the output of `condition` once it returns `Some`,
+    /// checking it again whenever the waiter is notified.
+    pub fn wait_until<T, F>(&self, condition: F) -> WaitUntil<'_, F>
+    where
+        F: FnMut() -> Option<T>,
+    {
+        WaitUntil {
+            wait_queue: self,
+            condition,
+            key: None,
+            parked: None,
+        }
+    }
+
+    /// Wakes the longest waiting task, returning whether there was one.
+    pub fn notify_one(&self) -> bool {
+        let Some(waker) = self.waiters.lock().notify_one() else {
+            return false;
+        };
+        waker.wake();
+        true
+    }
+
+    /// Wakes all waiting tasks, returning how many there were.
+    pub fn notify_all(&self) -> usize {
+        let queue = {
+            let mut waiters = self.waiters.lock();
+            let queue = core::mem::take(&mut waiters.queue);
+            waiters.notified.extend(queue.iter().map(|&(key, _)| key));
+            queue
+        };
+        let count = queue.len();
+        queue.into_iter().for_each(|(_, waker)| waker.wake());
+        count
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiters.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Waiters {
+    fn notify_one(&mut self) -> Option<Waker> {
+        let (key, waker) = self.queue.pop_front()?;
+        self.notified.insert(key);
+        Some(waker)
+    }
+}
+
+pub struct WaitUntil<'a, F> {
+    wait_queue: &'a WaitQueue,
+    condition: F,
+    key: Option<u64>,
+    parked: Option<ParkGuard>,
+}
+
+/// The condition is only ever called through a plain mutable reference.
+impl<F> Unpin for WaitUntil<'_, F> {}
+
+impl<T, F> Future for WaitUntil<'_, F>
+where
+    F: FnMut() -> Option<T>,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        {
+            let mut waiters = this.wait_queue.waiters.lock();
+            let key = match this.key {
+                Some(key) => {
+                    waiters.notified.remove(&key);
+                    key
+                }
+                None => {
+                    let key = waiters.next_key;
+                    waiters.next_key += 1;
+                    key
+                }
+            };
+            match waiters.queue.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, waker)) => waker.clone_from(cx.waker()),
+                None => waiters.queue.push_back((key, cx.waker().clone())),
+            }
+            this.key = Some(key);
+        }
+
+        // Checked once registered, so a notification racing with the check wakes the task.
+        if let Some(output) = (this.condition)() {
+            this.withdraw();
+            this.parked = None;
+            return Poll::Ready(output);
+        }
+
+        // The notifier may be another executor or an interrupt handler, the next one of which
+        // would otherwise find this executor finished.
+        this.parked = Executor::with_current(|ex| ex.park()).ok();
+        Poll::Pending
+    }
+}
+
+impl<F> WaitUntil<'_, F> {
+    /// Leaves the queue, passing on a notification not consumed by a poll.
+    fn withdraw(&mut self) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+
+        let waker = {
+            let mut waiters = self.wait_queue.waiters.lock();
+            waiters.queue.retain(|(k, _)| *k != key);
+            if waiters.notified.remove(&key) {
+                waiters.notify_one()
+            } else {
+                None
+            }
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<F> Drop for WaitUntil<'_, F> {
+    fn drop(&mut self) {
+        self.withdraw();
+    }
+}
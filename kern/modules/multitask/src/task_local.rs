@@ -0,0 +1,91 @@
+use core::any::Any;
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+use jrinx_error::{InternalError, Result};
+use jrinx_sync::IrqSafeMutex;
+
+use crate::executor::Executor;
+
+/// Declares task-local statics, of type [`LocalKey`], with the same syntax as `thread_local!`.
+///
+/// Each task gets its own value of such a static, initialized on its first access from the task
+/// and dropped along with the task.
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr; $($rest:tt)*) => {
+        $crate::task_local!($(#[$attr])* $vis static $name: $t = $init);
+        $crate::task_local!($($rest)*);
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task_local::LocalKey<$t> =
+            $crate::task_local::LocalKey::new(|| $init);
+    };
+}
+
+/// A key to the per-task values of a static declared by [`task_local!`].
+pub struct LocalKey<T: 'static> {
+    init: fn() -> T,
+}
+
+/// Task-local values of a task, by the address of their key.
+///
+/// A value is taken out while a closure borrows it, leaving `None` in its place.
+#[derive(Clone)]
+pub(crate) struct TaskLocals(Arc<IrqSafeMutex<BTreeMap<usize, Option<Box<dyn Any + Send>>>>>);
+
+impl TaskLocals {
+    pub(crate) fn try_new() -> Result<Self> {
+        Arc::try_new(IrqSafeMutex::new("task-locals", BTreeMap::new()))
+            .map(Self)
+            .map_err(|_| InternalError::NotEnoughMem)
+    }
+}
+
+impl<T: Send + 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new(init: fn() -> T) -> Self {
+        Self { init }
+    }
+
+    /// Runs `f` on the value of the current task, panicking outside of a task poll.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.try_with(f)
+            .unwrap_or_else(|err| panic!("failed to access a task-local: {:?}", err))
+    }
+
+    /// Runs `f` on the value of the current task.
+    ///
+    /// This fails with [`InternalError::NoCurrentTask`] outside of a task poll, such as in the
+    /// runtime, an inspector, or an executor dropping a finished task, and with
+    /// [`InternalError::TaskLocalInUse`] if `f` accesses the same key again. An interrupt
+    /// handler runs on top of the task it interrupts, and sees the values of that task.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let locals = Executor::with_current(|ex| ex.task_locals())
+            .ok()
+            .flatten()
+            .ok_or(InternalError::NoCurrentTask)?;
+        let key = self as *const _ as usize;
+
+        // The value is taken out, so that `f` may access other keys, and `None` is left in its
+        // place to catch accesses to this one.
+        let previous = locals.0.lock().insert(key, None);
+        let value: Box<dyn Any + Send> = match previous {
+            Some(Some(value)) => value,
+            Some(None) => return Err(InternalError::TaskLocalInUse),
+            None => Box::new((self.init)()),
+        };
+        let output = f(value
+            .downcast_ref()
+            .expect("task-local value is of the type of its key"));
+        locals.0.lock().insert(key, Some(value));
+        Ok(output)
+    }
+}
@@ -0,0 +1,279 @@
+use core::{
+    fmt::Display,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use alloc::sync::Arc;
+use jrinx_hal::{Cpu, Hal};
+use jrinx_sync::IrqSafeMutex;
+use jrinx_timed_event::TimedEventTracker;
+
+use crate::{
+    executor::{Executor, ParkGuard},
+    inspector::Inspector,
+};
+
+/// A future completing once the current time reaches its deadline.
+///
+/// The task is parked on a timed event of the inspector, which wakes it from the timer
+/// interrupt. If the inspector is out of timed events, the task keeps yielding until the
+/// deadline instead.
+pub struct Sleep {
+    deadline: Duration,
+    armed: Option<Armed>,
+}
+
+struct Armed {
+    tracker: TimedEventTracker,
+    waker: Arc<IrqSafeMutex<Waker>>,
+    parked: Option<ParkGuard>,
+}
+
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(hal!().cpu().get_time().saturating_add(duration))
+}
+
+pub fn sleep_until(deadline: Duration) -> Sleep {
+    Sleep {
+        deadline,
+        armed: None,
+    }
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+
+    fn arm(&self, cx: &Context<'_>) -> Option<Armed> {
+        let waker = Arc::new(IrqSafeMutex::new("sleep-waker", cx.waker().clone()));
+        let timeout_waker = waker.clone();
+        let tracker = Inspector::with_current(|is| {
+            is.create_timed_event(
+                self.deadline,
+                move || timeout_waker.lock().wake_by_ref(),
+                || {},
+            )
+        })
+        .and_then(|tracker| tracker)
+        .inspect_err(|err| debug!("sleep falls back to yielding: {:?}", err))
+        .ok()?;
+
+        Some(Armed {
+            tracker,
+            waker,
+            parked: Executor::with_current(|ex| ex.park()).ok(),
+        })
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if hal!().cpu().get_time() >= self.deadline {
+            self.armed = None;
+            return Poll::Ready(());
+        }
+
+        match &mut self.armed {
+            Some(armed) => {
+                // The task may have been stolen by a sibling executor since it was armed.
+                armed.waker.lock().clone_from(cx.waker());
+                armed.parked = Executor::with_current(|ex| ex.park()).ok();
+            }
+            None => {
+                self.armed = self.arm(cx);
+                if self.armed.is_none() {
+                    cx.waker().wake_by_ref();
+                }
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Armed {
+    fn drop(&mut self) {
+        if !self.tracker.retired() {
+            let _ = self.tracker.cancel();
+        }
+    }
+}
+
+/// What an [`Interval`] does with the ticks it missed while its consumer was late.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// Fires the missed ticks back to back, keeping the schedule of `start + n * period`.
+    #[default]
+    Burst,
+    /// Fires once now, and schedules the following ticks a period apart from now.
+    Delay,
+    /// Fires once now, and drops the missed ticks, keeping the schedule of `start + n * period`.
+    Skip,
+}
+
+/// A timer firing every period, anchored on its start rather than on the time of each tick.
+///
+/// Unlike [`sleep`] in a loop, late ticks do not push the following ones back, unless asked to
+/// by [`MissedTickBehavior::Delay`].
+pub struct Interval {
+    next: Duration,
+    period: Duration,
+    behavior: MissedTickBehavior,
+    clock: Option<fn() -> Duration>,
+    sleep: Option<Sleep>,
+}
+
+/// Creates an interval whose first tick fires right away.
+pub fn interval(period: Duration) -> Interval {
+    interval_at(hal!().cpu().get_time(), period)
+}
+
+/// Creates an interval whose first tick fires at `start`.
+pub fn interval_at(start: Duration, period: Duration) -> Interval {
+    assert!(!period.is_zero(), "interval period must be positive");
+    Interval {
+        next: start,
+        period,
+        behavior: MissedTickBehavior::default(),
+        clock: None,
+        sleep: None,
+    }
+}
+
+impl Interval {
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.behavior
+    }
+
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.behavior = behavior;
+    }
+
+    /// Reads the time from `clock` instead of the CPU, for tests to drive the interval.
+    ///
+    /// The interval then never parks its task, and keeps it woken until the clock is due.
+    pub fn with_clock(mut self, clock: fn() -> Duration) -> Self {
+        self.clock = Some(clock);
+        self.sleep = None;
+        self
+    }
+
+    /// Schedules the next tick a period from now.
+    pub fn reset(&mut self) {
+        self.next = self.now().saturating_add(self.period);
+        self.sleep = None;
+    }
+
+    /// Waits for the next tick, returning the time it was scheduled at.
+    pub async fn tick(&mut self) -> Duration {
+        core::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Duration> {
+        let now = self.now();
+        if now < self.next {
+            if self.clock.is_some() {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let next = self.next;
+            let sleep = self.sleep.get_or_insert_with(|| sleep_until(next));
+            if Pin::new(sleep).poll(cx).is_ready() {
+                self.sleep = None;
+                cx.waker().wake_by_ref();
+            }
+            return Poll::Pending;
+        }
+
+        let tick = self.next;
+        self.next = match self.behavior {
+            MissedTickBehavior::Burst => tick.saturating_add(self.period),
+            MissedTickBehavior::Delay => now.saturating_add(self.period),
+            MissedTickBehavior::Skip => {
+                let missed = (now - tick).as_nanos() / self.period.as_nanos();
+                let ahead = self.period.as_nanos() * (missed + 1);
+                tick.saturating_add(Duration::from_nanos(
+                    u64::try_from(ahead).unwrap_or(u64::MAX),
+                ))
+            }
+        };
+        self.sleep = None;
+        Poll::Ready(tick)
+    }
+
+    fn now(&self) -> Duration {
+        self.clock
+            .map_or_else(|| hal!().cpu().get_time(), |clock| clock())
+    }
+}
+
+/// Error of a [`Timeout`] whose deadline passed before its future completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+/// A future racing another future against a deadline.
+///
+/// The future is polled before the deadline is checked, so it wins a tie. The timed event of
+/// the deadline is cancelled as soon as the race is decided, or when the timeout is dropped.
+pub struct Timeout<F> {
+    future: F,
+    sleep: Option<Sleep>,
+}
+
+/// Runs `future`, giving up with [`Elapsed`] once `duration` has passed.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    timeout_at(hal!().cpu().get_time().saturating_add(duration), future)
+}
+
+/// Runs `future`, giving up with [`Elapsed`] once the current time reaches `deadline`.
+pub fn timeout_at<F: Future>(deadline: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: Some(sleep_until(deadline)),
+    }
+}
+
+impl<F> Timeout<F> {
+    pub fn deadline(&self) -> Option<Duration> {
+        self.sleep.as_ref().map(Sleep::deadline)
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of the pinned timeout, and `Sleep` is `Unpin`.
+        let this = unsafe { self.get_unchecked_mut() };
+        if this.sleep.is_none() {
+            panic!("timeout polled after completion");
+        }
+
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            this.sleep = None;
+            return Poll::Ready(Ok(output));
+        }
+
+        if Pin::new(this.sleep.as_mut().unwrap()).poll(cx).is_ready() {
+            this.sleep = None;
+            return Poll::Ready(Err(Elapsed));
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,252 @@
+use core::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc};
+use jrinx_hal::{Cpu, Hal};
+use jrinx_sync::IrqSafeMutex;
+use jrinx_timed_event::{TimedEvent, TimedEventHandler, TimedEventTracker};
+use spin::Mutex;
+
+use crate::{
+    executor::{Executor, ExecutorId, ExecutorPriority},
+    inspector::Inspector,
+    runtime::Runtime,
+    sync::WaitQueue,
+    Task, TaskPriority,
+};
+
+static SYSTEM_WORKQUEUE: IrqSafeMutex<VecDeque<DelayedWork>> =
+    IrqSafeMutex::new("system-workqueue", VecDeque::new());
+
+static WORKER: Mutex<Option<ExecutorId>> = Mutex::new(None);
+static WORKER_RUNNING: AtomicBool = AtomicBool::new(false);
+static OUTSTANDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Parks the worker until work is queued, or none is outstanding any more.
+static WORKER_WAIT: WaitQueue = WaitQueue::new();
+
+type WorkFn = dyn Fn(&DelayedWork) + Send + Sync;
+
+#[derive(Clone)]
+pub struct DelayedWork {
+    inner: Arc<DelayedWorkInner>,
+}
+
+struct DelayedWorkInner {
+    func: Box<WorkFn>,
+    state: IrqSafeMutex<DelayedWorkState>,
+}
+
+struct DelayedWorkState {
+    status: DelayedWorkStatus,
+    running: bool,
+}
+
+enum DelayedWorkStatus {
+    Idle,
+    Scheduled(TimedEventTracker),
+    Queued,
+}
+
+impl DelayedWork {
+    pub fn new(func: impl Fn(&DelayedWork) + Send + Sync + 'static) -> Self {
+        Self {
+            inner: Arc::new(DelayedWorkInner {
+                func: Box::new(func),
+                state: IrqSafeMutex::new(
+                    "delayed-work",
+                    DelayedWorkState {
+                        status: DelayedWorkStatus::Idle,
+                        running: false,
+                    },
+                ),
+            }),
+        }
+    }
+
+    /// Schedules the work to run on the system workqueue after `delay`.
+    ///
+    /// Returns `false` without touching the timer if the work is already pending.
+    pub fn schedule(&self, delay: Duration) -> bool {
+        {
+            let mut state = self.inner.state.lock();
+            if state.pending() {
+                return false;
+            }
+            OUTSTANDING.fetch_add(1, Ordering::SeqCst);
+            state.status = DelayedWorkStatus::Scheduled(self.arm(delay));
+        }
+        ensure_worker();
+        true
+    }
+
+    /// Moves the deadline of the work to `delay` from now, scheduling it if it is not pending.
+    ///
+    /// Returns whether the work was pending before.
+    pub fn reschedule(&self, delay: Duration) -> bool {
+        let pending = {
+            let mut state = self.inner.state.lock();
+            let pending = self.disarm(&mut state);
+            if !pending {
+                OUTSTANDING.fetch_add(1, Ordering::SeqCst);
+            }
+            state.status = DelayedWorkStatus::Scheduled(self.arm(delay));
+            pending
+        };
+        ensure_worker();
+        pending
+    }
+
+    /// Cancels the work and waits for a running instance of it to finish.
+    ///
+    /// Once this returns, the work is neither running nor going to run until it is scheduled
+    /// again. Called from within the work itself, it only prevents further runs. Called where
+    /// there is no task to yield, such as during boot or from a timed-event handler, it spins
+    /// until the running instance finishes on another CPU.
+    ///
+    /// Returns whether the work was pending.
+    pub fn cancel(&self) -> bool {
+        let pending = {
+            let mut state = self.inner.state.lock();
+            let pending = self.disarm(&mut state);
+            state.status = DelayedWorkStatus::Idle;
+            if pending && OUTSTANDING.fetch_sub(1, Ordering::SeqCst) == 1 {
+                WORKER_WAIT.notify_one();
+            }
+            pending
+        };
+
+        if !on_worker() {
+            while self.inner.state.lock().running {
+                yield_cpu();
+            }
+        }
+
+        pending
+    }
+
+    pub fn pending(&self) -> bool {
+        self.inner.state.lock().pending()
+    }
+
+    pub fn running(&self) -> bool {
+        self.inner.state.lock().running
+    }
+
+    fn arm(&self, delay: Duration) -> TimedEventTracker {
+        let work = self.clone();
+        TimedEvent::create(
+            hal!().cpu().get_time() + delay,
+            TimedEventHandler::new(move || work.fire(), || {}),
+        )
+    }
+
+    fn disarm(&self, state: &mut DelayedWorkState) -> bool {
+        match core::mem::replace(&mut state.status, DelayedWorkStatus::Idle) {
+            DelayedWorkStatus::Idle => false,
+            DelayedWorkStatus::Scheduled(tracker) => {
+                let _ = tracker.cancel();
+                true
+            }
+            DelayedWorkStatus::Queued => {
+                SYSTEM_WORKQUEUE
+                    .lock()
+                    .retain(|work| !Arc::ptr_eq(&work.inner, &self.inner));
+                true
+            }
+        }
+    }
+
+    fn fire(&self) {
+        let mut state = self.inner.state.lock();
+        if let DelayedWorkStatus::Scheduled(_) = state.status {
+            state.status = DelayedWorkStatus::Queued;
+            SYSTEM_WORKQUEUE.lock().push_back(self.clone());
+            WORKER_WAIT.notify_one();
+        }
+    }
+
+    fn run(&self) {
+        {
+            let mut state = self.inner.state.lock();
+            let DelayedWorkStatus::Queued = state.status else {
+                return;
+            };
+            state.status = DelayedWorkStatus::Idle;
+            state.running = true;
+            OUTSTANDING.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        (self.inner.func)(self);
+
+        self.inner.state.lock().running = false;
+    }
+}
+
+impl DelayedWorkState {
+    fn pending(&self) -> bool {
+        !matches!(self.status, DelayedWorkStatus::Idle)
+    }
+}
+
+fn ensure_worker() {
+    if WORKER_RUNNING
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return;
+    }
+
+    let inspector = Inspector::new();
+    let executor = Executor::new(
+        ExecutorPriority::default(),
+        Task::new(worker(), TaskPriority::default()).pinned(),
+    );
+    *WORKER.lock() = Some(executor.id());
+    inspector.register(executor).unwrap();
+    Runtime::with_current(|rt| rt.register(inspector).unwrap());
+}
+
+async fn worker() {
+    loop {
+        let work = WORKER_WAIT
+            .wait_until(|| match SYSTEM_WORKQUEUE.lock().pop_front() {
+                Some(work) => Some(Some(work)),
+                None => (OUTSTANDING.load(Ordering::SeqCst) == 0).then_some(None),
+            })
+            .await;
+        if let Some(work) = work {
+            work.run();
+            continue;
+        }
+
+        WORKER_RUNNING.store(false, Ordering::SeqCst);
+        if OUTSTANDING.load(Ordering::SeqCst) == 0
+            || WORKER_RUNNING
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+        {
+            break;
+        }
+    }
+}
+
+fn on_worker() -> bool {
+    let Some(worker) = *WORKER.lock() else {
+        return false;
+    };
+    Executor::with_current(|ex| ex.id() == worker).unwrap_or(false)
+}
+
+/// Yields the current task, or spins once if the caller is no task or a trap handler.
+fn yield_cpu() {
+    if jrinx_trap::nest::trap_depth() == 0
+        && Inspector::with_current(|is| is.mark_pending().unwrap()).is_ok()
+    {
+        Runtime::switch_yield();
+    } else {
+        core::hint::spin_loop();
+    }
+}
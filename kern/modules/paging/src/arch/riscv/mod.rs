@@ -1,7 +1,9 @@
 pub mod boot;
-use core::fmt::Display;
+use core::{
+    fmt::{Display, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use alloc::string::String;
 use bitflags::bitflags;
 use jrinx_addr::PhysAddr;
 
@@ -18,6 +20,8 @@ bitflags! {
         const __G = 1 << 5;
         const __A = 1 << 6;
         const __D = 1 << 7;
+        const __SW = 1 << 8; // software: writable, but write-protected for soft-dirty tracking
+        const __SD = 1 << 9; // software: soft-dirty
     }
 }
 
@@ -32,13 +36,56 @@ impl GenericPagePerm for PagePerm {
 
 impl Display for PagePerm {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let mut s = String::from("VRWXUG");
-        for i in 0..6 {
-            if self.bits() & (1 << i) == 0 {
-                s.replace_range(i..=i, "-");
-            }
+        for (i, c) in "VRWXUG".chars().enumerate() {
+            f.write_char(if self.bits() & (1 << i) == 0 { '-' } else { c })?;
+        }
+        Ok(())
+    }
+}
+
+/// Size of the memory a page table leaf maps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PageSize {
+    Size4K,
+    #[cfg(target_arch = "riscv32")]
+    Size4M,
+    #[cfg(target_arch = "riscv64")]
+    Size2M,
+    #[cfg(target_arch = "riscv64")]
+    Size1G,
+}
+
+impl PageSize {
+    /// Every page size, from the smallest.
+    pub const ALL: &'static [Self] = &[
+        Self::Size4K,
+        #[cfg(target_arch = "riscv32")]
+        Self::Size4M,
+        #[cfg(target_arch = "riscv64")]
+        Self::Size2M,
+        #[cfg(target_arch = "riscv64")]
+        Self::Size1G,
+    ];
+
+    pub const fn bytes(self) -> usize {
+        let index_bits = (jrinx_config::PAGE_SIZE / core::mem::size_of::<usize>()).ilog2();
+        jrinx_config::PAGE_SIZE << (index_bits as usize * self as usize)
+    }
+
+    /// Level of the leaves of this size, the root being level zero.
+    pub const fn level(self) -> usize {
+        Self::ALL.len() - 1 - self as usize
+    }
+
+    pub const fn at_level(level: usize) -> Self {
+        Self::ALL[Self::ALL.len() - 1 - level]
+    }
+
+    pub const fn smaller(self) -> Option<Self> {
+        match self as usize {
+            0 => None,
+            size => Some(Self::ALL[size - 1]),
         }
-        write!(f, "{}", s)
     }
 }
 
@@ -62,6 +109,62 @@ impl PageTableEntry {
     pub fn is_valid(&self) -> bool {
         self.bits & PagePerm::V.bits() != 0
     }
+
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid() && self.bits & (PagePerm::R | PagePerm::W | PagePerm::X).bits() != 0
+    }
+
+    /// Whether the page has been written since its soft-dirty bit was last cleared.
+    pub fn is_soft_dirty(&self) -> bool {
+        self.bits & PagePerm::__SD.bits() != 0
+    }
+
+    /// Clears the soft-dirty bit, write-protecting the page if it is writable, so that the
+    /// next write faults into [`resolve_soft_write`](Self::resolve_soft_write).
+    pub fn clear_soft_dirty(&mut self) {
+        self.update(|bits| {
+            if bits & PagePerm::__W.bits() != 0 {
+                bits & !(PagePerm::__W | PagePerm::__D | PagePerm::__SD).bits()
+                    | PagePerm::__SW.bits()
+            } else {
+                bits & !PagePerm::__SD.bits()
+            }
+        });
+    }
+
+    /// Upgrades a page write-protected by [`clear_soft_dirty`](Self::clear_soft_dirty) back
+    /// to writable and marks it soft-dirty, returning whether the faulting write can be retried.
+    pub fn resolve_soft_write(&mut self) -> bool {
+        let bits = self.update(|bits| {
+            if bits & PagePerm::__SW.bits() != 0 {
+                bits & !PagePerm::__SW.bits()
+                    | (PagePerm::__W | PagePerm::__D | PagePerm::__SD).bits()
+            } else {
+                bits
+            }
+        });
+        bits & PagePerm::__W.bits() != 0
+    }
+
+    /// Whether the page is writable, or only write-protected for soft-dirty tracking.
+    pub fn is_writable(&self) -> bool {
+        self.bits & (PagePerm::__W | PagePerm::__SW).bits() != 0
+    }
+
+    /// Write-protects the page for copy-on-write, dropping any soft-dirty write-protection
+    /// so that the next write faults past [`resolve_soft_write`](Self::resolve_soft_write).
+    pub fn write_protect(&mut self) {
+        self.update(|bits| bits & !(PagePerm::__W | PagePerm::__D | PagePerm::__SW).bits());
+    }
+
+    /// Updates the entry atomically, since other harts may resolve faults on it concurrently.
+    fn update(&mut self, f: impl Fn(usize) -> usize) -> usize {
+        let bits = unsafe { AtomicUsize::from_ptr(&mut self.bits) };
+        let old = bits
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |bits| Some(f(bits)))
+            .unwrap();
+        f(old)
+    }
 }
 
 impl GenericPageTableEntry<PagePerm> for PageTableEntry {
@@ -72,6 +175,8 @@ impl GenericPageTableEntry<PagePerm> for PageTableEntry {
         }
         if perm.contains(PagePerm::W) {
             perm.insert(PagePerm::__D); // dirty-bit
+            perm.insert(PagePerm::__SD); // soft-dirty-bit
+            perm.remove(PagePerm::__SW);
         }
         self.bits = (phys_addr.align_page_down().as_usize() >> 2) | perm.bits();
     }
@@ -1,16 +1,29 @@
-use alloc::{collections::BTreeMap, sync::Arc};
-use jrinx_addr::{PhysAddr, VirtAddr};
+use alloc::{
+    collections::{btree_map, BTreeMap, BTreeSet},
+    sync::Arc,
+    vec::Vec,
+};
+use core::ops::Range;
+use jrinx_addr::{PhysAddr, VirtAddr, VirtAddrRange};
+use jrinx_config::REMAP_MEM_REGIONS;
 use jrinx_error::{InternalError, Result};
-use jrinx_phys_frame::PhysFrame;
+use jrinx_phys_frame::{PhysFrame, PhysFrameAllocator};
 
 use crate::{
     boot::BootPageTable, CloneKernel, GenericPagePerm, GenericPageTable, GenericPageTableEntry,
-    PagePerm, PageTableEntry,
+    PagePerm, PageSize, PageTableEntry,
 };
 
 pub struct PageTable {
     root: PhysAddr,
     frames: BTreeMap<VirtAddr, Arc<PhysFrame>>,
+    /// Pages write-protected until their first write copies them, see
+    /// [`PageTable::duplicate_cow`].
+    cow: BTreeSet<VirtAddr>,
+    /// Pages mapped by [`PageTable::map_shared`].
+    shared: BTreeSet<VirtAddr>,
+    /// Leaves mapped by [`PageTable::map_huge`].
+    direct: BTreeMap<VirtAddr, PageSize>,
     generation: usize,
 }
 
@@ -26,18 +39,21 @@ impl GenericPageTable<PagePerm, PageTableEntry> for PageTable {
                 perm,
             ))
         } else {
-            Err(InternalError::InvalidVirtAddr)
+            self.direct_leaf(addr)
+                .and_then(|_| self.probe(addr))
+                .ok_or(InternalError::InvalidVirtAddr(addr))
         }
     }
 
     fn lookup(&self, addr: VirtAddr) -> jrinx_error::Result<(Arc<PhysFrame>, PagePerm)> {
         let addr = addr.align_page_down();
         let pte = self.find(addr)?;
-        if pte.valid() {
-            let (_, perm) = pte.clone().into();
-            Ok((self.frames[&addr].clone(), perm))
-        } else {
-            Err(InternalError::InvalidVirtAddr)
+        match self.frames.get(&addr) {
+            Some(frame) if pte.valid() => {
+                let (_, perm) = pte.clone().into();
+                Ok((frame.clone(), perm))
+            }
+            _ => Err(InternalError::InvalidVirtAddr(addr)),
         }
     }
 
@@ -50,6 +66,9 @@ impl GenericPageTable<PagePerm, PageTableEntry> for PageTable {
         let addr = addr.align_page_down();
         let phys_addr = phys_frame.addr();
         self.frames.insert(addr, phys_frame);
+        self.cow.remove(&addr);
+        self.shared.remove(&addr);
+        self.direct.remove(&addr);
 
         let pte = self.find_or_create(addr)?;
         pte.set(phys_addr, perm.union(PagePerm::V));
@@ -61,9 +80,15 @@ impl GenericPageTable<PagePerm, PageTableEntry> for PageTable {
 
     fn unmap(&mut self, addr: VirtAddr) -> jrinx_error::Result<()> {
         let addr = addr.align_page_down();
+        if !self.frames.contains_key(&addr) && self.direct_leaf(addr).is_some() {
+            let page = VirtAddrRange::new(addr, addr + jrinx_config::PAGE_SIZE);
+            return self.unmap_huge(page).map(|_| ());
+        }
         self.frames
             .remove(&addr)
-            .ok_or(InternalError::InvalidVirtAddr)?;
+            .ok_or(InternalError::InvalidVirtAddr(addr))?;
+        self.cow.remove(&addr);
+        self.shared.remove(&addr);
         let pte = self.find(addr)?;
         pte.clr();
 
@@ -83,32 +108,324 @@ impl<'a> From<&'a PageTable> for &'a [usize] {
 
 impl PageTable {
     pub fn new() -> Result<Self> {
+        let page_table = Self::empty()?;
+        BootPageTable.clone_kernel_into(page_table.root.to_virt().as_array_base());
+        Ok(page_table)
+    }
+
+    /// Creates the kernel page table, mapping the remapped regions with the largest pages.
+    pub fn new_kernel() -> Result<Self> {
+        let mut page_table = Self::empty()?;
+        for region in REMAP_MEM_REGIONS
+            .iter()
+            .filter(|region| region.virt_addr >= Self::user_end())
+        {
+            let range = VirtAddrRange::from_len(VirtAddr::new(region.virt_addr), region.len)
+                .ok_or(InternalError::InvalidVirtAddr(VirtAddr::new(
+                    region.virt_addr,
+                )))?;
+            page_table.map_range_best_fit(
+                range,
+                PhysAddr::new(region.phys_addr),
+                PagePerm::G | PagePerm::X | PagePerm::W | PagePerm::R,
+            )?;
+        }
+        Ok(page_table)
+    }
+
+    fn empty() -> Result<Self> {
         let frame = PhysFrame::alloc()?;
         let root = frame.addr();
         let mut frames = BTreeMap::new();
         frames.insert(root.to_virt(), frame);
-        BootPageTable.clone_kernel_into(root.to_virt().as_array_base());
         Ok(Self {
             root,
             frames,
+            cow: BTreeSet::new(),
+            shared: BTreeSet::new(),
+            direct: BTreeMap::new(),
             generation: 0,
         })
     }
 
     pub fn new_from(src: &Self) -> Result<Self> {
-        let frame = PhysFrame::alloc()?;
-        let root = frame.addr();
-        let mut frames = BTreeMap::new();
-        frames.insert(root.to_virt(), frame);
         let mut page_table = Self {
-            root,
-            frames,
             generation: src.generation,
+            ..Self::empty()?
         };
         page_table.sync_with(src);
         Ok(page_table)
     }
 
+    /// Unmaps every page in `range` within the user half in a single walk, returning how many
+    /// pages were mapped there.
+    ///
+    /// Page tables left without valid entries are freed along the way. Frames are released by
+    /// dropping their references, so a frame still mapped elsewhere outlives the unmap. TLB
+    /// maintenance is left to the caller, once for the whole range.
+    pub fn unmap_range_bulk(&mut self, range: Range<VirtAddr>) -> Result<usize> {
+        let range = VirtAddrRange::from(range);
+        let end = range
+            .end()
+            .align_up(jrinx_config::PAGE_SIZE)
+            .filter(|&end| end.as_usize() <= Self::user_end())
+            .ok_or(InternalError::InvalidVirtAddr(range.end()))?;
+        let pages = VirtAddrRange::new(range.start().align_down(jrinx_config::PAGE_SIZE), end);
+        if pages.is_empty() {
+            return Ok(0);
+        }
+
+        let unmapped = self.unmap_subtree(self.root, 0, 0, pages);
+        if unmapped != 0 {
+            self.generation += 1;
+        }
+
+        Ok(unmapped)
+    }
+
+    /// Unmaps the whole user half, leaving only the kernel half shared with other page tables.
+    pub fn clear(&mut self) -> Result<usize> {
+        self.unmap_range_bulk(VirtAddr::new(0)..VirtAddr::new(Self::user_end()))
+    }
+
+    /// Clears the soft-dirty bits of the pages in `range` within the user half, returning how
+    /// many pages there are.
+    ///
+    /// Writable pages get write-protected, so the caller must flush the TLBs of all harts the
+    /// page table may be active on before relying on [`collect_dirty`](Self::collect_dirty).
+    pub fn clear_soft_dirty(&mut self, range: Range<VirtAddr>) -> Result<usize> {
+        let mut cleared = 0;
+        for (&addr, _) in self.user_frames(range)? {
+            self.find(addr)?.clear_soft_dirty();
+            cleared += 1;
+        }
+        Ok(cleared)
+    }
+
+    /// Resolves a write fault on a page write-protected for soft-dirty tracking, in place and
+    /// without allocating, returning whether the faulting write can be retried.
+    pub fn resolve_soft_write(&self, addr: VirtAddr) -> bool {
+        addr.as_usize() < Self::user_end()
+            && self
+                .find(addr.align_page_down())
+                .is_ok_and(|pte| pte.valid() && pte.resolve_soft_write())
+    }
+
+    /// Enumerates the pages in `range` within the user half written since the soft-dirty bits
+    /// were last cleared, along with their frames.
+    pub fn collect_dirty(&self, range: Range<VirtAddr>) -> Result<DirtyIter<'_>> {
+        Ok(DirtyIter {
+            page_table: self,
+            frames: self.user_frames(range)?,
+        })
+    }
+
+    /// Maps `phys_frame` at `addr` like [`GenericPageTable::map`], to be shared rather than
+    /// copied on write by [`duplicate_cow`](Self::duplicate_cow).
+    pub fn map_shared(
+        &mut self,
+        addr: VirtAddr,
+        phys_frame: Arc<PhysFrame>,
+        perm: PagePerm,
+    ) -> Result<()> {
+        self.map(addr, phys_frame, perm)?;
+        self.shared.insert(addr.align_page_down());
+        Ok(())
+    }
+
+    /// Duplicates the user half into a new page table sharing the kernel half, whose writable
+    /// pages are write-protected in both tables until [`resolve_cow_write`] copies them.
+    ///
+    /// Read-only pages and pages mapped by [`map_shared`] are mapped to the same frames in both
+    /// tables as they are. The caller must flush the TLBs of all harts the page table may be
+    /// active on, as its writable pages get write-protected.
+    ///
+    /// [`resolve_cow_write`]: Self::resolve_cow_write
+    /// [`map_shared`]: Self::map_shared
+    pub fn duplicate_cow(&mut self) -> Result<Self> {
+        let mut dup = Self::new_from(self)?;
+        let pages: Vec<_> = self
+            .user_frames(VirtAddr::new(0)..VirtAddr::new(Self::user_end()))?
+            .map(|(&addr, frame)| (addr, frame.clone()))
+            .collect();
+
+        for (addr, frame) in pages {
+            let pte = self.find(addr)?.clone();
+            let (_, perm) = pte.clone().into();
+            if self.shared.contains(&addr) {
+                dup.map_shared(addr, frame, perm)?;
+            } else if pte.is_writable() || self.cow.contains(&addr) {
+                self.find(addr)?.write_protect();
+                self.cow.insert(addr);
+                dup.map(addr, frame, perm)?;
+                dup.find(addr)?.write_protect();
+                dup.cow.insert(addr);
+            } else {
+                dup.map(addr, frame, perm)?;
+            }
+        }
+        self.generation += 1;
+
+        Ok(dup)
+    }
+
+    /// Resolves a write fault on a page write-protected by [`duplicate_cow`], returning whether
+    /// the faulting write can be retried.
+    ///
+    /// The page is copied into a frame from `alloc`, unless no other page table holds its frame
+    /// any longer, in which case it is made writable in place. Frames are counted by their
+    /// references, which page tables on other harts may drop concurrently, at worst making a
+    /// copy that turns out unneeded. TLB maintenance is left to the caller.
+    ///
+    /// [`duplicate_cow`]: Self::duplicate_cow
+    pub fn resolve_cow_write(
+        &mut self,
+        addr: VirtAddr,
+        alloc: impl PhysFrameAllocator,
+    ) -> Result<bool> {
+        let addr = addr.align_page_down();
+        if !self.cow.contains(&addr) {
+            return Ok(false);
+        }
+
+        let (_, perm) = self.find(addr)?.clone().into();
+        let frame = &self.frames[&addr];
+        let frame = if Arc::strong_count(frame) == 1 {
+            frame.clone()
+        } else {
+            let copy = PhysFrame::alloc_in(alloc)?;
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    frame.addr().to_virt().as_usize() as *const u8,
+                    copy.addr().to_virt().as_usize() as *mut u8,
+                    jrinx_config::PAGE_SIZE,
+                );
+            }
+            copy
+        };
+        self.map(addr, frame, perm | PagePerm::W)?;
+
+        Ok(true)
+    }
+
+    /// Returns whether the page at `addr` is write-protected until its first write copies it.
+    pub fn is_cow(&self, addr: VirtAddr) -> bool {
+        self.cow.contains(&addr.align_page_down())
+    }
+
+    /// Maps the page of `size` at `addr` to memory the page table does not own.
+    pub fn map_huge(
+        &mut self,
+        addr: VirtAddr,
+        phys_addr: PhysAddr,
+        size: PageSize,
+        perm: PagePerm,
+    ) -> Result<()> {
+        if !addr.is_aligned(size.bytes()) {
+            return Err(InternalError::InvalidVirtAddr(addr));
+        }
+        if !phys_addr.is_aligned(size.bytes()) {
+            return Err(InternalError::InvalidVirtAddr(phys_addr.to_virt()));
+        }
+        let pte = self.find_or_create_at(addr, size.level())?;
+        if pte.valid() {
+            return Err(InternalError::RepeatInitialization);
+        }
+        pte.set(phys_addr, perm.union(PagePerm::V));
+        self.direct.insert(addr, size);
+        self.generation += 1;
+        Ok(())
+    }
+
+    /// Maps `range` from `phys_addr` with the largest pages that fit.
+    pub fn map_range_best_fit(
+        &mut self,
+        range: VirtAddrRange,
+        phys_addr: PhysAddr,
+        perm: PagePerm,
+    ) -> Result<()> {
+        let page_size = jrinx_config::PAGE_SIZE;
+        if !range.start().is_aligned(page_size) || !range.end().is_aligned(page_size) {
+            return Err(InternalError::InvalidVirtAddr(range.start()));
+        }
+        let mut mapped = 0;
+        while mapped < range.len() {
+            let (addr, phys_addr) = (range.start() + mapped, phys_addr + mapped);
+            let size = PageSize::ALL
+                .iter()
+                .rev()
+                .copied()
+                .find(|size| {
+                    addr.is_aligned(size.bytes())
+                        && phys_addr.is_aligned(size.bytes())
+                        && range.len() - mapped >= size.bytes()
+                })
+                .ok_or(InternalError::InvalidVirtAddr(phys_addr.to_virt()))?;
+            self.map_huge(addr, phys_addr, size, perm)?;
+            mapped += size.bytes();
+        }
+        Ok(())
+    }
+
+    /// Unmaps the huge pages in `range`, returning the bytes unmapped.
+    pub fn unmap_huge(&mut self, range: VirtAddrRange) -> Result<usize> {
+        let leaves = self.direct_leaves(range)?;
+        for &(addr, size) in &leaves {
+            self.find_at(addr, size.level())?.clr();
+            self.direct.remove(&addr);
+        }
+        if !leaves.is_empty() {
+            self.generation += 1;
+        }
+        Ok(leaves.iter().map(|(_, size)| size.bytes()).sum())
+    }
+
+    /// Sets the permissions of the huge pages in `range`, returning the bytes changed.
+    pub fn change_perm(&mut self, range: VirtAddrRange, perm: PagePerm) -> Result<usize> {
+        let leaves = self.direct_leaves(range)?;
+        for &(addr, size) in &leaves {
+            let pte = self.find_at(addr, size.level())?;
+            let (phys_addr, _) = pte.clone().into();
+            pte.set(phys_addr, perm.union(PagePerm::V));
+        }
+        if !leaves.is_empty() {
+            self.generation += 1;
+        }
+        Ok(leaves.iter().map(|(_, size)| size.bytes()).sum())
+    }
+
+    /// Returns the huge page `addr` lies in.
+    pub fn direct_leaf(&self, addr: VirtAddr) -> Option<(VirtAddr, PageSize)> {
+        self.direct
+            .range(..=addr)
+            .next_back()
+            .map(|(&leaf, &size)| (leaf, size))
+            .filter(|&(leaf, size)| addr - leaf < size.bytes())
+    }
+
+    /// Translates `addr` by walking the page table, including leaves it does not own.
+    pub fn probe(&self, addr: VirtAddr) -> Option<(PhysAddr, PagePerm)> {
+        let indexes = addr.indexes();
+        let mut pa = self.root;
+        for (level, &index) in indexes.iter().enumerate() {
+            let pte = &pa.to_virt().as_array_base::<PageTableEntry>()[index];
+            let (next, perm): (PhysAddr, PagePerm) = pte.clone().into();
+            if !perm.contains(PagePerm::V) {
+                return None;
+            }
+            if pte.is_leaf() {
+                return Some((next + (addr.as_usize() & (Self::span(level) - 1)), perm));
+            }
+            pa = next;
+        }
+        None
+    }
+
+    /// Returns the number of frames held, including the page tables themselves.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
     pub fn generation(&self) -> usize {
         self.generation
     }
@@ -121,28 +438,171 @@ impl PageTable {
         src.clone_kernel_into(self.root.to_virt().as_array_base());
     }
 
+    const ENTRIES: usize = jrinx_config::PAGE_SIZE / core::mem::size_of::<PageTableEntry>();
+
+    fn levels() -> usize {
+        VirtAddr::new(0).indexes().len()
+    }
+
+    fn user_frames(
+        &self,
+        range: Range<VirtAddr>,
+    ) -> Result<btree_map::Range<'_, VirtAddr, Arc<PhysFrame>>> {
+        if range.start > range.end || range.end.as_usize() > Self::user_end() {
+            return Err(InternalError::InvalidVirtAddr(range.end));
+        }
+        Ok(self.frames.range(range))
+    }
+
+    fn user_end() -> usize {
+        Self::ENTRIES / 2 * Self::span(0)
+    }
+
+    fn span(level: usize) -> usize {
+        jrinx_config::PAGE_SIZE << (Self::ENTRIES.ilog2() as usize * (Self::levels() - 1 - level))
+    }
+
+    fn unmap_subtree(
+        &mut self,
+        table: PhysAddr,
+        level: usize,
+        base: usize,
+        range: VirtAddrRange,
+    ) -> usize {
+        let span = Self::span(level);
+        let first = range.start().as_usize().saturating_sub(base) / span;
+        let last =
+            ((range.end() - 1).as_usize().saturating_sub(base) / span).min(Self::ENTRIES - 1);
+
+        let mut unmapped = 0;
+        for (index, pte) in table.to_virt().as_array_base::<PageTableEntry>()[first..=last]
+            .iter_mut()
+            .enumerate()
+        {
+            if !pte.valid() {
+                continue;
+            }
+            let addr = base.wrapping_add((first + index) * span);
+
+            if pte.is_leaf()
+                && addr >= range.start().as_usize()
+                && addr + span <= range.end().as_usize()
+            {
+                pte.clr();
+                self.frames.remove(&VirtAddr::new(addr));
+                self.cow.remove(&VirtAddr::new(addr));
+                self.shared.remove(&VirtAddr::new(addr));
+                self.direct.remove(&VirtAddr::new(addr));
+                unmapped += span / jrinx_config::PAGE_SIZE;
+                continue;
+            }
+            if pte.is_leaf() {
+                if self
+                    .shatter(VirtAddr::new(addr), PageSize::at_level(level))
+                    .is_err()
+                {
+                    continue;
+                }
+            }
+            let (pa, _) = pte.clone().into();
+
+            unmapped += self.unmap_subtree(pa, level + 1, addr, range);
+            if pa
+                .to_virt()
+                .as_array_base::<PageTableEntry>()
+                .iter()
+                .all(|pte| !pte.valid())
+            {
+                pte.clr();
+                self.frames.remove(&pa.to_virt());
+            }
+        }
+
+        unmapped
+    }
+
+    fn direct_leaves(&mut self, range: VirtAddrRange) -> Result<Vec<(VirtAddr, PageSize)>> {
+        let page_size = jrinx_config::PAGE_SIZE;
+        if !range.start().is_aligned(page_size) || !range.end().is_aligned(page_size) {
+            return Err(InternalError::InvalidVirtAddr(range.start()));
+        }
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+        for bound in [range.start(), range.end()] {
+            while let Some((leaf, size)) = self.direct_leaf(bound) {
+                if leaf == bound {
+                    break;
+                }
+                self.shatter(leaf, size)?;
+            }
+        }
+        Ok(self
+            .direct
+            .range(range.start()..range.end())
+            .map(|(&addr, &size)| (addr, size))
+            .collect())
+    }
+
+    /// Splits the huge page at `addr` into pages of the next size down.
+    fn shatter(&mut self, addr: VirtAddr, size: PageSize) -> Result<()> {
+        let smaller = size.smaller().ok_or(InternalError::InvalidVirtAddr(addr))?;
+        let (phys_addr, perm): (PhysAddr, PagePerm) =
+            self.find_at(addr, size.level())?.clone().into();
+
+        let frame = PhysFrame::alloc()?;
+        let table = frame.addr();
+        for (index, pte) in table
+            .to_virt()
+            .as_array_base::<PageTableEntry>()
+            .iter_mut()
+            .enumerate()
+        {
+            pte.set(phys_addr + index * smaller.bytes(), perm);
+        }
+        self.find_at(addr, size.level())?.set(table, PagePerm::V);
+        self.frames.insert(table.to_virt(), frame);
+
+        if self.direct.remove(&addr).is_some() {
+            for index in 0..Self::ENTRIES {
+                self.direct.insert(addr + index * smaller.bytes(), smaller);
+            }
+        }
+        Ok(())
+    }
+
     fn find(&self, addr: VirtAddr) -> Result<&mut PageTableEntry> {
+        self.find_at(addr, Self::levels() - 1)
+    }
+
+    fn find_at(&self, addr: VirtAddr, level: usize) -> Result<&mut PageTableEntry> {
         let indexes = addr.indexes();
         let mut pa = self.root;
-        for i in 0..indexes.len() {
+        for i in 0..=level {
             let pte = &mut pa.to_virt().as_array_base::<PageTableEntry>()[indexes[i]];
-            if i == indexes.len() - 1 {
+            if i == level {
                 return Ok(pte);
-            } else if !pte.valid() {
-                return Err(InternalError::InvalidVirtAddr);
+            } else if !pte.valid() || pte.is_leaf() {
+                return Err(InternalError::InvalidVirtAddr(addr));
             }
             (pa, _) = pte.clone().into();
         }
-        Err(InternalError::InvalidVirtAddr)
+        Err(InternalError::InvalidVirtAddr(addr))
     }
 
     fn find_or_create(&mut self, addr: VirtAddr) -> Result<&mut PageTableEntry> {
+        self.find_or_create_at(addr, Self::levels() - 1)
+    }
+
+    fn find_or_create_at(&mut self, addr: VirtAddr, level: usize) -> Result<&mut PageTableEntry> {
         let indexes = addr.indexes();
         let mut pa = self.root;
-        for i in 0..indexes.len() {
+        for i in 0..=level {
             let pte = &mut pa.to_virt().as_array_base::<PageTableEntry>()[indexes[i]];
-            if i == indexes.len() - 1 {
+            if i == level {
                 return Ok(pte);
+            } else if pte.is_leaf() {
+                return Err(InternalError::InvalidVirtAddr(addr));
             } else if !pte.valid() {
                 let frame = PhysFrame::alloc()?;
                 let addr = frame.addr();
@@ -151,6 +611,24 @@ impl PageTable {
             }
             (pa, _) = pte.clone().into();
         }
-        Err(InternalError::InvalidVirtAddr)
+        Err(InternalError::InvalidVirtAddr(addr))
+    }
+}
+
+pub struct DirtyIter<'a> {
+    page_table: &'a PageTable,
+    frames: btree_map::Range<'a, VirtAddr, Arc<PhysFrame>>,
+}
+
+impl<'a> Iterator for DirtyIter<'a> {
+    type Item = (VirtAddr, &'a Arc<PhysFrame>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.frames.find_map(|(&addr, frame)| {
+            self.page_table
+                .find(addr)
+                .is_ok_and(|pte| pte.is_soft_dirty())
+                .then_some((addr, frame))
+        })
     }
 }
@@ -30,6 +30,11 @@ pub fn local_area_base(cpu_id: usize) -> usize {
     global_area_base() + local_area_size() * cpu_id
 }
 
+/// Returns whether the local pointer of `cpu_id` is set, so per-CPU variables can be used.
+pub fn local_pointer_ready(cpu_id: usize) -> bool {
+    GLOBAL_AREA_BASE.is_completed() && get_local_pointer() == local_area_base(cpu_id)
+}
+
 pub fn init(nproc: usize) {
     let total_size = local_area_size() * nproc;
     let layout = Layout::from_size_align(total_size, PAGE_SIZE).unwrap();
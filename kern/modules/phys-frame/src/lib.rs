@@ -12,8 +12,12 @@ use core::{
     alloc::{Allocator, Layout},
     fmt::Debug,
     ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
+/// Frames allocated and not yet dropped.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
 pub trait PhysFrameAllocator = Allocator + Send + Sync + 'static;
 
 pub struct PhysFrame {
@@ -62,6 +66,7 @@ impl Drop for PhysFrame {
                 PHYS_FRAME_MEMORY_LAYOUT,
             );
         }
+        ALLOCATED.fetch_sub(1, Ordering::Relaxed);
     }
 }
 
@@ -80,6 +85,7 @@ impl PhysFrame {
             addr: VirtAddr::new(addr.as_ptr() as usize).to_phys(),
             alloc: Arc::new(alloc),
         };
+        ALLOCATED.fetch_add(1, Ordering::Relaxed);
 
         Ok(Arc::new(frame))
     }
@@ -88,3 +94,8 @@ impl PhysFrame {
         self.addr
     }
 }
+
+/// Returns the number of frames allocated and not yet dropped.
+pub fn allocated() -> usize {
+    ALLOCATED.load(Ordering::Relaxed)
+}
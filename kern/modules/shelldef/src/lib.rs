@@ -0,0 +1,89 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::{future::Future, pin::Pin};
+
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// A command of the kernel shell, registered through the linker by [`shelldef!`].
+#[repr(C)]
+pub struct ShellDef {
+    name: &'static str,
+    usage: &'static str,
+    help: &'static str,
+    run: fn(&str) -> CommandFuture<'_>,
+}
+
+impl ShellDef {
+    pub const fn new(
+        name: &'static str,
+        usage: &'static str,
+        help: &'static str,
+        run: fn(&str) -> CommandFuture<'_>,
+    ) -> Self {
+        Self {
+            name,
+            usage,
+            help,
+            run,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn usage(&self) -> &'static str {
+        self.usage
+    }
+
+    pub fn help(&self) -> &'static str {
+        self.help
+    }
+
+    pub fn run<'a>(&self, args: &'a str) -> CommandFuture<'a> {
+        (self.run)(args)
+    }
+}
+
+/// Registers a command of the kernel shell, run by `$run`, an `async fn(args: &str)`.
+///
+/// ```ignore
+/// shelldef!(name = "ps", usage = "", help = "Show the tasks of every runtime", run = ps);
+/// ```
+#[macro_export]
+macro_rules! shelldef {
+    (name = $name:literal, usage = $usage:literal, help = $help:literal, run = $run:path $(,)?) => {
+        const _: () = {
+            fn __shell_run(args: &str) -> $crate::CommandFuture<'_> {
+                $crate::__private::Box::pin($run(args))
+            }
+
+            #[used(linker)]
+            #[link_section = concat!(".shell.", $name)]
+            static __SHELL_DEF: &$crate::ShellDef =
+                &$crate::ShellDef::new($name, $usage, $help, __shell_run);
+        };
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::boxed::Box;
+}
+
+pub fn all() -> impl Iterator<Item = &'static ShellDef> {
+    shelldef_iter()
+}
+
+pub fn find(name: &str) -> Option<&'static ShellDef> {
+    shelldef_iter().find(|shell_def| shell_def.name == name)
+}
+
+fn shelldef_iter() -> impl Iterator<Item = &'static ShellDef> {
+    (jrinx_layout::_sshell()..jrinx_layout::_eshell())
+        .step_by(core::mem::size_of::<&ShellDef>())
+        .map(|a| unsafe { *(a as *const &ShellDef) })
+}
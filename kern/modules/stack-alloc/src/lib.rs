@@ -5,7 +5,7 @@ use alloc::{
     boxed::Box,
     collections::{BTreeMap, VecDeque},
 };
-use core::sync::atomic::AtomicUsize;
+use core::{ops::Range, sync::atomic::AtomicUsize};
 use spin::Mutex;
 
 use jrinx_addr::VirtAddr;
@@ -76,7 +76,7 @@ impl StackAllocator {
             .allocated
             .lock()
             .remove(&stack_top)
-            .ok_or(InternalError::InvalidVirtAddr)?;
+            .ok_or(InternalError::InvalidVirtAddr(stack_top))?;
 
         let va = stack_top - size - self.guard_size;
 
@@ -87,6 +87,26 @@ impl StackAllocator {
         self.cached.lock().entry(size).or_default().push_front(va);
         Ok(())
     }
+
+    /// Returns the bounds of the allocated stack holding `addr`, without waiting on the lock.
+    pub fn containing(&self, addr: VirtAddr) -> Option<Range<VirtAddr>> {
+        let allocated = self.allocated.try_lock()?;
+        let (&stack_top, &size) = allocated.range(addr..).next()?;
+        let stack_bottom = stack_top - size;
+        (stack_bottom <= addr).then_some(stack_bottom..stack_top)
+    }
+
+    /// Returns the bounds of the allocated stack whose guard holds `addr`, if any.
+    ///
+    /// The lock on allocated stacks is only tried, since the code running past its stack may
+    /// hold it.
+    pub fn guarded_by(&self, addr: VirtAddr) -> Option<Range<VirtAddr>> {
+        let allocated = self.allocated.try_lock()?;
+        let (&stack_top, &size) = allocated.range(addr..).next()?;
+        let stack_bottom = stack_top - size;
+        (stack_bottom - self.guard_size <= addr && addr < stack_bottom)
+            .then_some(stack_bottom..stack_top)
+    }
 }
 
 impl Drop for StackAllocator {
@@ -0,0 +1,180 @@
+#![no_std]
+
+extern crate alloc;
+
+use core::{
+    fmt::Display,
+    sync::atomic::{fence, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use alloc::vec::Vec;
+use jrinx_hal::{hal, Cpu, Hal};
+use jrinx_percpu::percpu;
+
+const SNAPSHOT_RETRIES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StatKind {
+    TimerInterrupt,
+    SoftwareInterrupt,
+    Breakpoint,
+    PageFault,
+    Fault,
+}
+
+impl StatKind {
+    pub const ALL: [Self; 5] = [
+        Self::TimerInterrupt,
+        Self::SoftwareInterrupt,
+        Self::Breakpoint,
+        Self::PageFault,
+        Self::Fault,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TimerInterrupt => "timer-interrupt",
+            Self::SoftwareInterrupt => "software-interrupt",
+            Self::Breakpoint => "breakpoint",
+            Self::PageFault => "page-fault",
+            Self::Fault => "fault",
+        }
+    }
+}
+
+struct CpuStats {
+    seq: AtomicUsize,
+    counters: [AtomicU64; StatKind::ALL.len()],
+}
+
+#[percpu]
+static CPU_STATS: CpuStats = CpuStats::new();
+
+#[derive(Debug, Clone, Copy)]
+pub struct CpuSnapshot {
+    cpu_id: usize,
+    counters: [u64; StatKind::ALL.len()],
+    torn: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatsSnapshot {
+    time: Duration,
+    cpus: Vec<CpuSnapshot>,
+}
+
+/// Bumps the counter of `kind` on the current CPU.
+///
+/// Each CPU is the only writer of its own counters, so this must not be interrupted by
+/// another [`record`] on the same CPU, i.e. it is meant to be called from trap handlers.
+pub fn record(kind: StatKind) {
+    CPU_STATS.with_ref(|stats| {
+        let seq = stats.seq.load(Ordering::Relaxed);
+        stats.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let counter = &stats.counters[kind as usize];
+        counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+
+        stats.seq.store(seq.wrapping_add(2), Ordering::Release);
+    });
+}
+
+pub fn snapshot() -> StatsSnapshot {
+    let time = hal!().cpu().get_time();
+    let cpus = CPU_STATS
+        .iter()
+        .enumerate()
+        .map(|(cpu_id, stats)| stats.read(cpu_id))
+        .collect();
+
+    StatsSnapshot { time, cpus }
+}
+
+impl CpuStats {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            counters: [const { AtomicU64::new(0) }; StatKind::ALL.len()],
+        }
+    }
+
+    fn read(&self, cpu_id: usize) -> CpuSnapshot {
+        let mut snapshot = CpuSnapshot {
+            cpu_id,
+            counters: [0; StatKind::ALL.len()],
+            torn: true,
+        };
+
+        for _ in 0..SNAPSHOT_RETRIES {
+            let begin = self.seq.load(Ordering::Acquire);
+            self.read_counters(&mut snapshot.counters);
+            fence(Ordering::Acquire);
+
+            if begin % 2 == 0 && self.seq.load(Ordering::Relaxed) == begin {
+                snapshot.torn = false;
+                break;
+            }
+            core::hint::spin_loop();
+        }
+
+        snapshot
+    }
+
+    fn read_counters(&self, counters: &mut [u64; StatKind::ALL.len()]) {
+        for (value, counter) in counters.iter_mut().zip(self.counters.iter()) {
+            *value = counter.load(Ordering::Relaxed);
+        }
+    }
+}
+
+impl CpuSnapshot {
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id
+    }
+
+    pub fn get(&self, kind: StatKind) -> u64 {
+        self.counters[kind as usize]
+    }
+
+    pub fn torn(&self) -> bool {
+        self.torn
+    }
+}
+
+impl StatsSnapshot {
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn cpus(&self) -> &[CpuSnapshot] {
+        &self.cpus
+    }
+
+    pub fn torn(&self) -> bool {
+        self.cpus.iter().any(|cpu| cpu.torn)
+    }
+
+    pub fn total(&self, kind: StatKind) -> u64 {
+        self.cpus.iter().map(|cpu| cpu.get(kind)).sum()
+    }
+}
+
+impl Display for StatsSnapshot {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "stats at {:?}{}:",
+            self.time,
+            if self.torn() { " (torn)" } else { "" }
+        )?;
+        for kind in StatKind::ALL {
+            write!(f, "\n\t{}: {}", kind.name(), self.total(kind))?;
+            for cpu in self.cpus.iter() {
+                write!(f, " cpu#{}={}", cpu.cpu_id, cpu.get(kind))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+#![no_std]
+
+pub mod lockdep;
+mod mutex;
+
+pub use mutex::{IrqSafeMutex, IrqSafeMutexGuard};
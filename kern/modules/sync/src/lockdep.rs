@@ -0,0 +1,256 @@
+use core::{
+    fmt::Display,
+    panic::Location,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering},
+};
+
+use jrinx_percpu::percpu;
+use spin::Mutex;
+
+pub const MAX_LOCK_CLASSES: usize = 64;
+const MAX_HELD_LOCKS: usize = 16;
+const UNASSIGNED: usize = usize::MAX;
+
+static CLASS_NAMES: Mutex<ClassNames> = Mutex::new(ClassNames::new());
+static ACQUIRED_BEFORE: [AtomicU64; MAX_LOCK_CLASSES] =
+    [const { AtomicU64::new(0) }; MAX_LOCK_CLASSES];
+/// Where each edge of [`ACQUIRED_BEFORE`] was first recorded, i.e. where its later lock was
+/// acquired.
+static EDGE_SITES: [[AtomicPtr<Location<'static>>; MAX_LOCK_CLASSES]; MAX_LOCK_CLASSES] =
+    [const { [const { AtomicPtr::new(ptr::null_mut()) }; MAX_LOCK_CLASSES] }; MAX_LOCK_CLASSES];
+
+#[percpu]
+static HELD_LOCKS: Mutex<HeldLocks> = Mutex::new(HeldLocks::new());
+
+/// Class of a lock, shared by all locks of the same name so that they are ordered as one.
+pub struct LockClass {
+    name: &'static str,
+    id: AtomicUsize,
+}
+
+impl LockClass {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            id: AtomicUsize::new(UNASSIGNED),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn id(&self) -> Option<usize> {
+        let id = self.id.load(Ordering::Acquire);
+        if id != UNASSIGNED {
+            return Some(id);
+        }
+
+        let id = CLASS_NAMES.lock().id_of(self.name)?;
+        self.id.store(id, Ordering::Release);
+        Some(id)
+    }
+}
+
+struct ClassNames {
+    names: [&'static str; MAX_LOCK_CLASSES],
+    len: usize,
+}
+
+impl ClassNames {
+    const fn new() -> Self {
+        Self {
+            names: [""; MAX_LOCK_CLASSES],
+            len: 0,
+        }
+    }
+
+    fn id_of(&mut self, name: &'static str) -> Option<usize> {
+        if let Some(id) = self.names[..self.len].iter().position(|&n| n == name) {
+            return Some(id);
+        }
+        if self.len == MAX_LOCK_CLASSES {
+            return None;
+        }
+
+        self.names[self.len] = name;
+        self.len += 1;
+        Some(self.len - 1)
+    }
+
+    fn name(&self, id: usize) -> &'static str {
+        self.names[id]
+    }
+}
+
+/// How a lock is being acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcquireKind {
+    /// Waiting for the lock, which must not complete a cycle of lock classes.
+    Lock,
+    /// Waiting for the lock in an order the caller vouches for, recording nothing.
+    Nested,
+    /// Taking the lock without waiting, which cannot deadlock by itself but orders it all the
+    /// same.
+    Try,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LockOrderViolation {
+    pub held: &'static str,
+    pub held_site: &'static Location<'static>,
+    pub acquiring: &'static str,
+    pub acquiring_site: &'static Location<'static>,
+    /// Lock held when `held` was acquired, in the recorded order leading to the violation.
+    pub recorded: &'static str,
+    pub recorded_site: &'static Location<'static>,
+}
+
+impl Display for LockOrderViolation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "lock order violation: acquiring '{}' at {} while holding '{}' (acquired at {}), \
+            but '{}' has been acquired before '{}' elsewhere ('{}' acquired at {} while holding \
+            '{}')",
+            self.acquiring,
+            self.acquiring_site,
+            self.held,
+            self.held_site,
+            self.acquiring,
+            self.held,
+            self.held,
+            self.recorded_site,
+            self.recorded,
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+struct HeldLock {
+    id: usize,
+    name: &'static str,
+    site: &'static Location<'static>,
+}
+
+struct HeldLocks {
+    locks: [Option<HeldLock>; MAX_HELD_LOCKS],
+    len: usize,
+}
+
+impl HeldLocks {
+    const fn new() -> Self {
+        Self {
+            locks: [None; MAX_HELD_LOCKS],
+            len: 0,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &HeldLock> {
+        self.locks[..self.len].iter().flatten()
+    }
+
+    fn push(&mut self, lock: HeldLock) {
+        if self.len < MAX_HELD_LOCKS {
+            self.locks[self.len] = Some(lock);
+            self.len += 1;
+        }
+    }
+
+    fn remove(&mut self, id: usize) {
+        let Some(pos) = self.locks[..self.len]
+            .iter()
+            .rposition(|lock| lock.is_some_and(|lock| lock.id == id))
+        else {
+            return;
+        };
+        self.locks.copy_within(pos + 1..self.len, pos);
+        self.len -= 1;
+        self.locks[self.len] = None;
+    }
+}
+
+pub fn acquire(
+    class: &LockClass,
+    site: &'static Location<'static>,
+    kind: AcquireKind,
+) -> Result<(), LockOrderViolation> {
+    let Some(id) = class.id() else {
+        return Ok(());
+    };
+
+    HELD_LOCKS.with_ref(|held| {
+        let mut held = held.lock();
+
+        if kind == AcquireKind::Lock {
+            if let Some((lock, recorded)) = held
+                .iter()
+                .filter(|lock| lock.id != id)
+                .find_map(|lock| Some((lock, last_edge(id, lock.id)?)))
+            {
+                let recorded_site = EDGE_SITES[recorded][lock.id].load(Ordering::Relaxed);
+                return Err(LockOrderViolation {
+                    held: lock.name,
+                    held_site: lock.site,
+                    acquiring: class.name,
+                    acquiring_site: site,
+                    recorded: CLASS_NAMES.lock().name(recorded),
+                    recorded_site: unsafe { &*recorded_site },
+                });
+            }
+        }
+
+        if kind != AcquireKind::Nested {
+            for lock in held.iter().filter(|lock| lock.id != id) {
+                let _ = EDGE_SITES[lock.id][id].compare_exchange(
+                    ptr::null_mut(),
+                    site as *const _ as *mut _,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+                ACQUIRED_BEFORE[lock.id].fetch_or(1 << id, Ordering::Release);
+            }
+        }
+
+        held.push(HeldLock {
+            id,
+            name: class.name,
+            site,
+        });
+        Ok(())
+    })
+}
+
+pub fn release(class: &LockClass) {
+    let Some(id) = class.id() else {
+        return;
+    };
+
+    HELD_LOCKS.with_ref(|held| held.lock().remove(id));
+}
+
+/// Looks for a recorded order from `from` to `to`, returning the class it reaches `to` from.
+fn last_edge(from: usize, to: usize) -> Option<usize> {
+    let mut visited = 0u64;
+    let mut frontier = 1u64 << from;
+
+    while frontier != 0 {
+        visited |= frontier;
+
+        let mut next = 0;
+        let mut bits = frontier;
+        while bits != 0 {
+            let class = bits.trailing_zeros() as usize;
+            let after = ACQUIRED_BEFORE[class].load(Ordering::Acquire);
+            if after & (1 << to) != 0 {
+                return Some(class);
+            }
+            next |= after;
+            bits &= bits - 1;
+        }
+        frontier = next & !visited;
+    }
+
+    None
+}
@@ -0,0 +1,130 @@
+use core::{
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
+
+use jrinx_hal::{hal, Hal, Interrupt};
+use spin::{Mutex, MutexGuard};
+
+use crate::lockdep::{AcquireKind, LockClass};
+
+pub struct IrqSafeMutex<T: ?Sized> {
+    class: LockClass,
+    inner: Mutex<T>,
+}
+
+pub struct IrqSafeMutexGuard<'a, T: ?Sized + 'a> {
+    #[cfg_attr(not(feature = "lockdep"), allow(dead_code))]
+    class: &'a LockClass,
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+    irq_enabled: bool,
+}
+
+impl<T> IrqSafeMutex<T> {
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Self {
+            class: LockClass::new(name),
+            inner: Mutex::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> IrqSafeMutex<T> {
+    pub fn class(&self) -> &LockClass {
+        &self.class
+    }
+
+    #[track_caller]
+    pub fn lock(&self) -> IrqSafeMutexGuard<'_, T> {
+        self.do_lock(AcquireKind::Lock)
+    }
+
+    #[track_caller]
+    pub fn lock_nested(&self) -> IrqSafeMutexGuard<'_, T> {
+        self.do_lock(AcquireKind::Nested)
+    }
+
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<IrqSafeMutexGuard<'_, T>> {
+        let irq_enabled = hal!().interrupt().is_enabled();
+        hal!().interrupt().disable();
+
+        let Some(guard) = self.inner.try_lock() else {
+            if irq_enabled {
+                hal!().interrupt().enable();
+            }
+            return None;
+        };
+
+        // Not waiting for the lock cannot deadlock, but its order still counts for the others.
+        #[cfg(feature = "lockdep")]
+        let _ = crate::lockdep::acquire(
+            &self.class,
+            core::panic::Location::caller(),
+            AcquireKind::Try,
+        );
+
+        Some(IrqSafeMutexGuard {
+            class: &self.class,
+            guard: ManuallyDrop::new(guard),
+            irq_enabled,
+        })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+
+    #[track_caller]
+    fn do_lock(&self, kind: AcquireKind) -> IrqSafeMutexGuard<'_, T> {
+        let irq_enabled = hal!().interrupt().is_enabled();
+        hal!().interrupt().disable();
+
+        #[cfg(feature = "lockdep")]
+        if let Err(violation) =
+            crate::lockdep::acquire(&self.class, core::panic::Location::caller(), kind)
+        {
+            if irq_enabled {
+                hal!().interrupt().enable();
+            }
+            panic!("{}", violation);
+        }
+        #[cfg(not(feature = "lockdep"))]
+        let _ = kind;
+
+        IrqSafeMutexGuard {
+            class: &self.class,
+            guard: ManuallyDrop::new(self.inner.lock()),
+            irq_enabled,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for IrqSafeMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for IrqSafeMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized> Drop for IrqSafeMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.guard);
+        }
+
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::release(self.class);
+
+        if self.irq_enabled {
+            hal!().interrupt().enable();
+        }
+    }
+}
@@ -1,16 +1,59 @@
 use alloc::borrow::ToOwned;
 use alloc::{format, string::String};
 
-use jrinx_a653::{partition::Partition, process::Process};
-use jrinx_abi::sysno::*;
+use jrinx_a653::{
+    partition::Partition,
+    process::{Process, ProcessExit},
+    uptr::{uptr_try_cast, uptr_try_cast_array, UserPtr, UserSlice},
+};
+use jrinx_abi::{cap::Capabilities, sysno::*, time::Timespec, trap::TrapMask};
+use jrinx_addr::VirtAddr;
 use jrinx_apex::*;
-use jrinx_error::{InternalError, Result};
+use jrinx_error::{Errno, InternalError, Result};
 use jrinx_hal::{Hal, HaltReason};
 
 use crate::partition::PartitionSyscallHandler;
 use crate::process::ProcessSyscallHandler;
+use crate::semaphore::SemaphoreSyscallHandler;
+use crate::table::{syscall_table, UserStr};
+use crate::time::TimeSyscallHandler;
+use crate::tunable::TunableSyscallHandler;
+
+syscall_table! {
+    SYS_GET_MY_ID => sys_get_my_id(result: UserPtr<ApexProcessId>),
+    SYS_GET_TUNABLE => sys_get_tunable(
+        key: UserStr,
+        buf: UserSlice<u8>,
+        len: UserPtr<usize>,
+    ),
+    SYS_CLOCK_GETTIME => sys_clock_gettime(clock_id: usize, tp: UserPtr<Timespec>),
+    SYS_NANOSLEEP => sys_nanosleep(req: UserPtr<Timespec>, rem: Option<UserPtr<Timespec>>),
+    SYS_STRACE => sys_strace(enabled: usize),
+}
+
+/// Handles the system call `sysno`, returning what it returns to user mode.
+///
+/// A system call failing inside the kernel returns the negated [`Errno`] its error maps to.
+pub async fn handle(sysno: usize, args: [usize; 7]) -> usize {
+    let traced = Process::current().is_some_and(|process| process.strace());
+    if traced {
+        crate::trace::enter(sysno, &args);
+    }
+    let ret = crate::budget::metered(sysno, args, dispatch(sysno, args)).await;
+    if traced {
+        crate::trace::exit(sysno, &ret);
+    }
+    ret.unwrap_or_else(|err| {
+        log::debug!("*{}>> syscall {:#x} failed: {:?}", log_prefix(), sysno, err);
+        Errno::from(err).into_ret()
+    })
+}
+
+async fn dispatch(sysno: usize, args: [usize; 7]) -> Result<usize> {
+    if let Err(code) = crate::cap::check(sysno) {
+        return Ok(code as usize);
+    }
 
-pub async fn handle(sysno: usize, args: [usize; 7]) -> Result<usize> {
     let ret: core::result::Result<(), ApexReturnCode> = match sysno {
         SYS_GET_PARTITION_STATUS => {
             let result: &mut ApexPartitionStatus = uptr_try_cast(args[0])?;
@@ -40,23 +83,133 @@ pub async fn handle(sysno: usize, args: [usize; 7]) -> Result<usize> {
         SYS_INITIALIZE_PROCESS_CORE_AFFINITY => {
             ProcessSyscallHandler.initialize_process_core_affinity(args[0] as _, args[1] as _)
         }
+        SYS_CREATE_SEMAPHORE => {
+            let name: &ApexSemaphoreName = uptr_try_cast(args[0])?;
+            let result: &mut ApexSemaphoreId = uptr_try_cast(args[4])?;
+            SemaphoreSyscallHandler
+                .create(name, args[1] as _, args[2] as _, args[3])
+                .map(|id| *result = id)
+        }
+        SYS_WAIT_SEMAPHORE => {
+            SemaphoreSyscallHandler
+                .wait(args[0] as _, args[1] as _)
+                .await
+        }
+        SYS_SIGNAL_SEMAPHORE => SemaphoreSyscallHandler.signal(args[0] as _),
+        SYS_GET_SEMAPHORE_ID => {
+            let name: &ApexSemaphoreName = uptr_try_cast(args[0])?;
+            let result: &mut ApexSemaphoreId = uptr_try_cast(args[1])?;
+            SemaphoreSyscallHandler.get_id(name).map(|id| *result = id)
+        }
+        SYS_GET_SEMAPHORE_STATUS => {
+            let id: ApexSemaphoreId = args[0] as _;
+            let result: &mut ApexSemaphoreStatus = uptr_try_cast(args[1])?;
+            SemaphoreSyscallHandler
+                .get_status(id)
+                .map(|status| *result = status)
+        }
+        SYS_GET_CAPABILITIES => {
+            let result: &mut usize = uptr_try_cast(args[0])?;
+            *result = Process::current().unwrap().capabilities().bits();
+            Ok(())
+        }
+        SYS_DROP_CAPABILITIES => match Capabilities::from_bits(args[0]) {
+            Some(caps) if Process::current().unwrap().drop_capabilities(caps) => Ok(()),
+            Some(_) => Err(ApexReturnCode::NoAction),
+            None => Err(ApexReturnCode::InvalidParam),
+        },
+        SYS_RSEQ_REGISTER => match Process::current()
+            .unwrap()
+            .rseq_register(VirtAddr::new(args[0]))
+        {
+            Ok(()) => Ok(()),
+            Err(InternalError::RepeatInitialization) => Err(ApexReturnCode::NoAction),
+            Err(_) => Err(ApexReturnCode::InvalidParam),
+        },
+        SYS_SET_TUNABLE => {
+            let key: &[u8] = uptr_try_cast_array(args[0], args[1])?;
+            let value: &[u8] = uptr_try_cast_array(args[2], args[3])?;
+            TunableSyscallHandler.set(key, value)
+        }
+        SYS_TRAP_HANDLER_SET => match TrapMask::from_bits(args[0]) {
+            Some(mask) => Process::current()
+                .unwrap()
+                .trap_handler_set(mask, VirtAddr::new(args[1]), VirtAddr::new(args[2]))
+                .map_err(|_| ApexReturnCode::InvalidParam),
+            None => Err(ApexReturnCode::InvalidParam),
+        },
+        SYS_TRAP_RETURN => match Process::current().unwrap().trap_return() {
+            true => Ok(()),
+            false => Err(ApexReturnCode::NoAction),
+        },
+        SYS_TRAP_SIGNAL => {
+            let id: ApexProcessId = args[0] as _;
+            let partition = Partition::current().unwrap();
+            match Process::find_by_id(partition.identifier(), id.into()) {
+                Some(process) if process.trap_signal(args[1]) => Ok(()),
+                Some(_) => Err(ApexReturnCode::NoAction),
+                None => Err(ApexReturnCode::InvalidParam),
+            }
+        }
+        SYS_MAP_PAGE => match Partition::current()
+            .unwrap()
+            .map_page(VirtAddr::new(args[0]))
+        {
+            Ok(()) => Ok(()),
+            Err(InternalError::RepeatInitialization) => Err(ApexReturnCode::NoAction),
+            Err(InternalError::NotEnoughMem) => Err(ApexReturnCode::InvalidConfig),
+            Err(_) => Err(ApexReturnCode::InvalidParam),
+        },
         SYS_DEBUG_LOG => {
             let len: usize = args[1];
             let msg: &[u8] = uptr_try_cast_array(args[0], len)?;
-            let partition_name = Partition::current().map(|p| format!("{:?}", p.name()));
-            let process_name = Process::current().map(|p| format!("{:?}", p.name()));
-            let prefix = format!(
-                "{}//{}",
-                partition_name.unwrap_or("<unknown>".to_owned()),
-                process_name.unwrap_or("<unknown>".to_owned())
-            );
+            drain_log_ring();
+            let prefix = log_prefix();
             for line in String::from_utf8_lossy(msg).split('\n') {
                 log::debug!("*{}>> {}", prefix, line);
             }
             Ok(())
         }
-        SYS_DEBUG_HALT => hal!().halt(HaltReason::NormalExit),
-        _ => return Err(InternalError::InvalidSyscallNumber),
+        SYS_DEBUG_HALT => {
+            drain_log_ring();
+            if let Some(restarts) = Process::current().and_then(|p| p.rseq_restarts()) {
+                log::info!("*{}>> rseq: {} restarts", log_prefix(), restarts);
+            }
+            jrinx_wallclock::anchor();
+            log::logger().flush();
+            hal!().halt(HaltReason::NormalExit)
+        }
+        SYS_DEBUG_LOG_RING_SETUP => {
+            let len: usize = args[0];
+            let result: &mut usize = uptr_try_cast(args[1])?;
+            match Process::current().unwrap().setup_log_ring(len) {
+                Ok(base) => {
+                    *result = base.as_usize();
+                    Ok(())
+                }
+                Err(InternalError::RepeatInitialization) => Err(ApexReturnCode::NoAction),
+                Err(InternalError::NotEnoughMem | InternalError::ResourceLimitExceeded(_)) => {
+                    Err(ApexReturnCode::InvalidConfig)
+                }
+                Err(_) => Err(ApexReturnCode::InvalidParam),
+            }
+        }
+        SYS_DEBUG_LOG_RING_DOORBELL => {
+            drain_log_ring();
+            Ok(())
+        }
+        SYS_DEBUG_EXIT => {
+            drain_log_ring();
+            Process::current()
+                .unwrap()
+                .exit(ProcessExit::Exited(args[0]));
+            Ok(())
+        }
+        _ => {
+            return Ok(table_dispatch(sysno, args)
+                .await
+                .unwrap_or(Errno::ENOSYS.into_ret()))
+        }
     };
 
     Ok(match ret {
@@ -65,22 +218,62 @@ pub async fn handle(sysno: usize, args: [usize; 7]) -> Result<usize> {
     })
 }
 
-fn uptr_try_cast<'a, T>(ptr: usize) -> Result<&'a mut T> {
-    if ptr >= usize::MAX / 2 || ptr + core::mem::size_of::<T>() > usize::MAX / 2 {
-        return Err(InternalError::InvalidVirtAddr);
-    }
-    if ptr == 0 {
-        return Err(InternalError::InvalidVirtAddr);
-    }
-    Ok(unsafe { &mut *(ptr as *mut T) })
+async fn sys_get_my_id(
+    mut result: UserPtr<ApexProcessId>,
+) -> core::result::Result<(), ApexReturnCode> {
+    result.write(Process::current().unwrap().identifier().into());
+    Ok(())
 }
 
-fn uptr_try_cast_array<'a, T>(ptr: usize, len: usize) -> Result<&'a mut [T]> {
-    if ptr >= usize::MAX / 2 || ptr + len * core::mem::size_of::<T>() > usize::MAX / 2 {
-        return Err(InternalError::InvalidVirtAddr);
-    }
-    if ptr == 0 {
-        return Err(InternalError::InvalidVirtAddr);
+async fn sys_get_tunable(
+    key: UserStr,
+    mut buf: UserSlice<u8>,
+    mut len: UserPtr<usize>,
+) -> core::result::Result<(), ApexReturnCode> {
+    TunableSyscallHandler.get(&key, buf.as_mut_slice(), len.as_mut())
+}
+
+async fn sys_clock_gettime(
+    clock_id: usize,
+    mut tp: UserPtr<Timespec>,
+) -> core::result::Result<usize, Errno> {
+    tp.write(TimeSyscallHandler.clock_gettime(clock_id)?);
+    Ok(0)
+}
+
+async fn sys_nanosleep(
+    req: UserPtr<Timespec>,
+    rem: Option<UserPtr<Timespec>>,
+) -> core::result::Result<usize, Errno> {
+    TimeSyscallHandler.nanosleep(*req.as_ref(), rem).await?;
+    Ok(0)
+}
+
+async fn sys_strace(enabled: usize) -> core::result::Result<(), ApexReturnCode> {
+    match enabled {
+        0 | 1 => Process::current().unwrap().set_strace(enabled == 1),
+        _ => return Err(ApexReturnCode::InvalidParam),
     }
-    Ok(unsafe { core::slice::from_raw_parts_mut(ptr as *mut T, len) })
+    Ok(())
+}
+
+pub(crate) fn log_prefix() -> String {
+    let partition_name = Partition::current().map(|p| format!("{:?}", p.name()));
+    let process_name = Process::current().map(|p| format!("{:?}", p.name()));
+    format!(
+        "{}//{}",
+        partition_name.unwrap_or("<unknown>".to_owned()),
+        process_name.unwrap_or("<unknown>".to_owned())
+    )
+}
+
+fn drain_log_ring() {
+    let Some(process) = Process::current() else {
+        return;
+    };
+    let prefix = log_prefix();
+    process.drain_log_ring(
+        |record| log::debug!("*{}>> {}", prefix, String::from_utf8_lossy(record)),
+        |dropped| log::warn!("*{}>> {} log records dropped", prefix, dropped),
+    );
 }
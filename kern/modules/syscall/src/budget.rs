@@ -0,0 +1,125 @@
+use alloc::collections::BTreeMap;
+use core::{
+    future::{poll_fn, Future},
+    pin::pin,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use jrinx_abi::sysno::*;
+use jrinx_apex::ApexReturnCode;
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::{Cpu, Hal};
+use jrinx_init::kernel_init;
+use spin::Mutex;
+
+use crate::all::log_prefix;
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+static BUDGETS: Mutex<BTreeMap<usize, Option<Duration>>> = Mutex::new(BTreeMap::new());
+static OVERRUNS: Mutex<BTreeMap<usize, u64>> = Mutex::new(BTreeMap::new());
+
+macro_rules! def_sysbudget {
+    ($($($sysno:ident)|+ => $budget:expr,)*) => {
+        /// Returns the default execution time budget of `sysno`, where `None` is unlimited.
+        const fn default_budget(sysno: usize) -> Option<Duration> {
+            match sysno {
+                $($($sysno)|+ => Some($budget),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+def_sysbudget! {
+    SYS_GET_PARTITION_STATUS
+        | SYS_GET_PROCESS_ID
+        | SYS_GET_PROCESS_STATUS
+        | SYS_GET_SEMAPHORE_ID
+        | SYS_GET_SEMAPHORE_STATUS
+        | SYS_GET_CAPABILITIES
+        | SYS_DROP_CAPABILITIES
+        | SYS_RSEQ_REGISTER
+        | SYS_GET_TUNABLE => Duration::from_millis(1),
+}
+
+/// Returns the execution time budget of `sysno`, where `None` is unlimited.
+pub fn budget(sysno: usize) -> Option<Duration> {
+    BUDGETS
+        .lock()
+        .get(&sysno)
+        .copied()
+        .unwrap_or(default_budget(sysno))
+}
+
+/// Overrides the execution time budget of `sysno`, where `None` is unlimited.
+pub fn set_budget(sysno: usize, budget: Option<Duration>) {
+    BUDGETS.lock().insert(sysno, budget);
+}
+
+/// Registers the tunable `syscall.strict_budgets`. In strict mode, a system call overrunning
+/// its budget returns [`ApexReturnCode::TimedOut`] in place of its result.
+#[kernel_init(name = "syscall-tunables", depends = ["heap"])]
+fn register_tunables() -> Result<()> {
+    jrinx_tunable::register(
+        "syscall.strict_budgets",
+        false,
+        |_| Ok(()),
+        |&strict| STRICT.store(strict, Ordering::SeqCst),
+    )
+    .map_err(|_| InternalError::RepeatInitialization)
+}
+
+/// Returns the number of times `sysno` overran its budget since boot.
+pub fn overruns(sysno: usize) -> u64 {
+    OVERRUNS.lock().get(&sysno).copied().unwrap_or(0)
+}
+
+/// Runs the `handler` of `sysno`, checking the time it spends running against its budget.
+///
+/// Only the time spent in polls of the handler counts, so that it may stay parked for as long
+/// as it waits for an event.
+pub async fn metered<F>(sysno: usize, args: [usize; 7], handler: F) -> Result<usize>
+where
+    F: Future<Output = Result<usize>>,
+{
+    let Some(budget) = budget(sysno) else {
+        return handler.await;
+    };
+
+    let mut handler = pin!(handler);
+    let mut running = Duration::ZERO;
+    let ret = poll_fn(|cx| {
+        let begin = hal!().cpu().get_time();
+        let poll = handler.as_mut().poll(cx);
+        running += hal!().cpu().get_time() - begin;
+        poll
+    })
+    .await;
+
+    if running <= budget {
+        return ret;
+    }
+
+    let overruns = {
+        let mut overruns = OVERRUNS.lock();
+        let overruns = overruns.entry(sysno).or_default();
+        *overruns += 1;
+        *overruns
+    };
+    log::warn!(
+        "*{}>> syscall {} ({:#x}) ran for {:?} over its {:?} budget, args {:x?} ({} overruns)",
+        log_prefix(),
+        jrinx_abi::sysno::name(sysno).unwrap_or("<unknown>"),
+        sysno,
+        running,
+        budget,
+        args,
+        overruns
+    );
+
+    match ret {
+        Ok(_) if STRICT.load(Ordering::SeqCst) => Ok(ApexReturnCode::TimedOut as usize),
+        ret => ret,
+    }
+}
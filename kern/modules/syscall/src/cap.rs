@@ -0,0 +1,78 @@
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use jrinx_a653::process::Process;
+use jrinx_abi::cap::{self, Capabilities};
+use jrinx_apex::ApexReturnCode;
+use jrinx_hal::{Cpu, Hal};
+use spin::Mutex;
+
+use crate::all::log_prefix;
+
+const AUDIT_LOG_INTERVAL: Duration = Duration::from_secs(1);
+const AUDIT_LOG_BURST: usize = 4;
+
+static DENIALS: AtomicU64 = AtomicU64::new(0);
+static AUDIT_LOG: Mutex<AuditLog> = Mutex::new(AuditLog {
+    window: Duration::ZERO,
+    logged: 0,
+});
+
+struct AuditLog {
+    window: Duration,
+    logged: usize,
+}
+
+/// Checks that the current process holds the capabilities required by `sysno`.
+///
+/// A denied system call is answered with [`ApexReturnCode::InvalidConfig`], as it is the
+/// configuration of the partition that does not grant it.
+pub(crate) fn check(sysno: usize) -> Result<(), ApexReturnCode> {
+    let required = cap::required(sysno);
+    if required.is_empty() {
+        return Ok(());
+    }
+    let Some(process) = Process::current() else {
+        return Ok(());
+    };
+
+    let missing = required.difference(process.capabilities());
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let denials = DENIALS.fetch_add(1, Ordering::SeqCst) + 1;
+    audit(sysno, missing, denials);
+    Err(ApexReturnCode::InvalidConfig)
+}
+
+/// Returns the number of system calls denied for lack of capabilities since boot.
+pub fn denials() -> u64 {
+    DENIALS.load(Ordering::SeqCst)
+}
+
+/// Logs a denial, at most [`AUDIT_LOG_BURST`] times per [`AUDIT_LOG_INTERVAL`].
+fn audit(sysno: usize, missing: Capabilities, denials: u64) {
+    let now = hal!().cpu().get_time();
+    {
+        let mut audit_log = AUDIT_LOG.lock();
+        if now >= audit_log.window + AUDIT_LOG_INTERVAL {
+            audit_log.window = now;
+            audit_log.logged = 0;
+        }
+        if audit_log.logged >= AUDIT_LOG_BURST {
+            return;
+        }
+        audit_log.logged += 1;
+    }
+
+    log::warn!(
+        "*{}>> syscall {:#x} denied, lacking {:?} ({} denials in total)",
+        log_prefix(),
+        sysno,
+        missing,
+        denials
+    );
+}
@@ -1,8 +1,16 @@
 #![no_std]
+#![feature(used_with_arg)]
 
 mod all;
+pub mod budget;
+mod cap;
 mod partition;
 mod process;
+mod semaphore;
+mod table;
+mod time;
+mod trace;
+mod tunable;
 
 extern crate alloc;
 
@@ -10,3 +18,4 @@ extern crate alloc;
 extern crate jrinx_hal;
 
 pub use all::handle;
+pub use cap::denials;
@@ -7,6 +7,7 @@ use jrinx_a653::{
     A653Entry,
 };
 use jrinx_apex::*;
+use jrinx_error::InternalError;
 use jrinx_hal::{Cpu, Hal, Interrupt};
 use jrinx_multitask::{
     executor::ExecutorStatus,
@@ -79,6 +80,8 @@ impl ProcessSyscallHandler {
                 period: attr.period,
                 stack_size: attr.stack_size,
                 time_capacity: attr.time_capacity,
+                capabilities: Process::current().unwrap().capabilities(),
+                strace: Process::current().unwrap().strace(),
             },
         )
         .map_err(|_| ApexReturnCode::InvalidConfig)?;
@@ -87,35 +90,36 @@ impl ProcessSyscallHandler {
     }
 
     pub(crate) fn start(&self, id: ApexProcessId) -> Result<(), ApexReturnCode> {
-        fn start_executor(process: Arc<Process>, status: ExecutorStatus) {
-            let mut executor = process
-                .gen_executor(ProcessRunner {
-                    syscall: crate::handle,
-                })
-                .unwrap();
+        fn start_executor(
+            process: Arc<Process>,
+            status: ExecutorStatus,
+        ) -> jrinx_error::Result<()> {
+            let mut executor = process.gen_executor(ProcessRunner {
+                syscall: crate::handle,
+            })?;
 
             executor.set_status(status);
 
             let cpu_id = process.core_affinity().unwrap_or(hal!().cpu().id());
             Runtime::with_spec_cpu(cpu_id, move |rt| {
                 rt.with_registry(|reg| {
-                    for (_, inspector) in reg.iter() {
-                        let that_partition: Option<Arc<Partition>> =
-                            inspector.ext().deref().downcast_ref().cloned();
-                        if let Some(that_partition) = that_partition {
-                            if that_partition.identifier() == process.partition_id() {
-                                inspector.register(executor).unwrap();
-                                break;
-                            }
-                        }
-                    }
+                    reg.values()
+                        .find(|inspector| {
+                            let that_partition: Option<Arc<Partition>> =
+                                inspector.ext().deref().downcast_ref().cloned();
+                            that_partition.is_some_and(|that_partition| {
+                                that_partition.identifier() == process.partition_id()
+                            })
+                        })
+                        .map_or(Err(InternalError::InvalidInspectorId), |inspector| {
+                            inspector.register(executor)
+                        })
                 })
-            })
-            .unwrap();
-            if Runtime::with_spec_cpu(cpu_id, |rt| rt.status() == RuntimeStatus::Endpoint).unwrap()
-            {
+            })??;
+            if Runtime::with_spec_cpu(cpu_id, |rt| rt.status() == RuntimeStatus::Endpoint)? {
                 hal!().interrupt().send_ipi(&[cpu_id]);
             }
+            Ok(())
         }
 
         let partition = Partition::current().unwrap();
@@ -139,17 +143,26 @@ impl ProcessSyscallHandler {
             process.set_curr_priority(process.base_priority());
             process.set_process_state(ApexProcessState::Waiting);
 
+            // A process that cannot be started, such as for lack of executors left to the
+            // partition, stays dormant.
             let start = move || {
                 process.clone().set_process_state(ApexProcessState::Ready);
                 process.clone().set_deadline_time(deadline_time); // TODO: set timed-event for deadline
-                start_executor(process.clone(), ExecutorStatus::Runnable);
+                let started = start_executor(process.clone(), ExecutorStatus::Runnable);
+                if let Err(err) = &started {
+                    log::warn!("process {:?} not started: {:?}", process.name(), err);
+                    process.set_process_state(ApexProcessState::Dormant);
+                }
+                started
             };
 
             if partition.operating_mode() == ApexOperatingMode::Normal {
-                start();
+                start().map_err(|_| ApexReturnCode::InvalidConfig)?;
                 Runtime::switch_yield();
             } else {
-                partition.add_pre_start_hook(start);
+                partition.add_pre_start_hook(move || {
+                    let _ = start();
+                });
             }
         } else {
             todo!("start periodic process");
@@ -0,0 +1,111 @@
+use jrinx_a653::{partition::Partition, process::Process, semaphore::Semaphore};
+use jrinx_apex::*;
+use jrinx_error::InternalError;
+
+pub(crate) struct SemaphoreSyscallHandler;
+
+impl SemaphoreSyscallHandler {
+    pub(crate) fn create(
+        &self,
+        name: &ApexSemaphoreName,
+        current_value: ApexSemaphoreValue,
+        maximum_value: ApexSemaphoreValue,
+        discipline: usize,
+    ) -> Result<ApexSemaphoreId, ApexReturnCode> {
+        let partition = Partition::current().unwrap();
+        if Semaphore::find_by_name(partition.identifier(), name).is_some() {
+            return Err(ApexReturnCode::NoAction);
+        }
+        if !(0..=APEX_MAX_SEMAPHORE_VALUE).contains(&current_value) {
+            return Err(ApexReturnCode::InvalidParam);
+        }
+        if !(1..=APEX_MAX_SEMAPHORE_VALUE).contains(&maximum_value) {
+            return Err(ApexReturnCode::InvalidParam);
+        }
+        if current_value > maximum_value {
+            return Err(ApexReturnCode::InvalidParam);
+        }
+        let discipline: ApexQueueDiscipline = (discipline as u32)
+            .try_into()
+            .map_err(|_| ApexReturnCode::InvalidParam)?;
+        if partition.operating_mode() == ApexOperatingMode::Normal {
+            return Err(ApexReturnCode::InvalidMode);
+        }
+
+        let semaphore = Semaphore::new(
+            partition.identifier(),
+            *name,
+            current_value,
+            maximum_value,
+            discipline,
+        )
+        .map_err(|_| ApexReturnCode::InvalidConfig)?;
+
+        Ok(semaphore.identifier().into())
+    }
+
+    pub(crate) async fn wait(
+        &self,
+        id: ApexSemaphoreId,
+        timeout: ApexSystemTime,
+    ) -> Result<(), ApexReturnCode> {
+        let partition = Partition::current().unwrap();
+        let semaphore = Semaphore::find_by_id(partition.identifier(), id.into())
+            .ok_or(ApexReturnCode::InvalidParam)?;
+        if timeout != APEX_TIME_INFINITY && timeout < 0 {
+            return Err(ApexReturnCode::InvalidParam);
+        }
+
+        let process = Process::current().unwrap();
+        match semaphore.wait(&process, time_as_duration(timeout)).await {
+            Ok(true) => Ok(()),
+            Ok(false) if timeout == 0 => Err(ApexReturnCode::NotAvailable),
+            Ok(false) => Err(ApexReturnCode::TimedOut),
+            Err(InternalError::InvalidApexPriority) => {
+                log::warn!(
+                    "{:?} (priority {}) refused on semaphore {:?} with ceiling {:?}",
+                    process.name(),
+                    process.curr_priority(),
+                    semaphore.name(),
+                    semaphore.ceiling(),
+                );
+                Err(ApexReturnCode::InvalidConfig)
+            }
+            Err(_) => Err(ApexReturnCode::InvalidParam),
+        }
+    }
+
+    pub(crate) fn signal(&self, id: ApexSemaphoreId) -> Result<(), ApexReturnCode> {
+        let partition = Partition::current().unwrap();
+        let semaphore = Semaphore::find_by_id(partition.identifier(), id.into())
+            .ok_or(ApexReturnCode::InvalidParam)?;
+
+        if semaphore.signal(&Process::current().unwrap()) {
+            Ok(())
+        } else {
+            Err(ApexReturnCode::NoAction)
+        }
+    }
+
+    pub(crate) fn get_id(
+        &self,
+        name: &ApexSemaphoreName,
+    ) -> Result<ApexSemaphoreId, ApexReturnCode> {
+        let partition = Partition::current().unwrap();
+        let semaphore = Semaphore::find_by_name(partition.identifier(), name)
+            .ok_or(ApexReturnCode::InvalidConfig)?;
+
+        Ok(semaphore.identifier().into())
+    }
+
+    pub(crate) fn get_status(
+        &self,
+        id: ApexSemaphoreId,
+    ) -> Result<ApexSemaphoreStatus, ApexReturnCode> {
+        let partition = Partition::current().unwrap();
+        let semaphore = Semaphore::find_by_id(partition.identifier(), id.into())
+            .ok_or(ApexReturnCode::InvalidParam)?;
+
+        Ok(semaphore.status())
+    }
+}
@@ -0,0 +1,247 @@
+use alloc::string::String;
+use core::{
+    fmt::{self, Write},
+    ops::Deref,
+};
+
+use jrinx_a653::uptr::{UserPtr, UserSlice};
+use jrinx_addr::VirtAddr;
+use jrinx_apex::ApexReturnCode;
+use jrinx_error::Errno;
+
+/// Most raw arguments a system call is passed in registers.
+pub(crate) const SYSCALL_ARGS: usize = 7;
+
+/// Longest prefix of a string argument copied from user memory into a trace.
+const TRACE_STR_MAX: usize = 64;
+
+/// A typed argument of a system call, decoded from as many raw arguments as it takes, or
+/// `None` if they are invalid.
+pub(crate) trait SyscallArg: Sized {
+    const SLOTS: usize = 1;
+
+    fn decode(args: &mut core::slice::Iter<'_, usize>) -> Option<Self>;
+
+    /// Renders the raw arguments taken into a trace, in hex unless told otherwise.
+    fn trace(args: &mut core::slice::Iter<'_, usize>, f: &mut dyn Write) -> fmt::Result {
+        for slot in 0..Self::SLOTS {
+            if slot != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{:#x}", next(args))?;
+        }
+        Ok(())
+    }
+}
+
+/// A string passed in by a user program as a pointer followed by a length, rendered as text
+/// in traces.
+pub(crate) struct UserStr(UserSlice<u8>);
+
+/// The result of a system call handler, as returned to user mode.
+pub(crate) trait SyscallRet {
+    /// Returns the result of a system call whose arguments do not decode.
+    fn invalid_arg() -> Self;
+
+    fn into_raw(self) -> usize;
+}
+
+fn next(args: &mut core::slice::Iter<'_, usize>) -> usize {
+    args.next().copied().unwrap_or_default()
+}
+
+macro_rules! impl_syscall_arg_int {
+    ($($ty:ty),*) => {
+        $(
+            impl SyscallArg for $ty {
+                fn decode(args: &mut core::slice::Iter<'_, usize>) -> Option<Self> {
+                    Some(next(args) as _)
+                }
+
+                fn trace(
+                    args: &mut core::slice::Iter<'_, usize>,
+                    f: &mut dyn Write,
+                ) -> fmt::Result {
+                    write!(f, "{}", next(args) as $ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_syscall_arg_int!(usize, isize, u32, i32, u64, i64);
+
+impl SyscallArg for VirtAddr {
+    fn decode(args: &mut core::slice::Iter<'_, usize>) -> Option<Self> {
+        Some(VirtAddr::new(next(args)))
+    }
+}
+
+impl<T> SyscallArg for UserPtr<T> {
+    fn decode(args: &mut core::slice::Iter<'_, usize>) -> Option<Self> {
+        UserPtr::new(next(args)).ok()
+    }
+}
+
+/// Decoded from a pointer that may be null.
+impl<T> SyscallArg for Option<UserPtr<T>> {
+    fn decode(args: &mut core::slice::Iter<'_, usize>) -> Option<Self> {
+        match next(args) {
+            0 => Some(None),
+            addr => UserPtr::new(addr).ok().map(Some),
+        }
+    }
+}
+
+/// Decoded from a pointer followed by a length.
+impl<T> SyscallArg for UserSlice<T> {
+    const SLOTS: usize = 2;
+
+    fn decode(args: &mut core::slice::Iter<'_, usize>) -> Option<Self> {
+        let addr = next(args);
+        let len = next(args);
+        UserSlice::new(addr, len).ok()
+    }
+
+    fn trace(args: &mut core::slice::Iter<'_, usize>, f: &mut dyn Write) -> fmt::Result {
+        write!(f, "{:#x}, {}", next(args), next(args))
+    }
+}
+
+impl SyscallArg for UserStr {
+    const SLOTS: usize = 2;
+
+    fn decode(args: &mut core::slice::Iter<'_, usize>) -> Option<Self> {
+        UserSlice::decode(args).map(Self)
+    }
+
+    /// Renders the string truncated to [`TRACE_STR_MAX`] bytes, or its raw pointer and length
+    /// if it does not lie in user memory.
+    fn trace(args: &mut core::slice::Iter<'_, usize>, f: &mut dyn Write) -> fmt::Result {
+        let (addr, len) = (next(args), next(args));
+        let Ok(slice) = UserSlice::<u8>::new(addr, len.min(TRACE_STR_MAX)) else {
+            return write!(f, "{:#x}, {}", addr, len);
+        };
+        let prefix = slice.as_slice().to_vec();
+        write!(f, "{:?}", String::from_utf8_lossy(&prefix))?;
+        if len > TRACE_STR_MAX {
+            write!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+impl Deref for UserStr {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_slice()
+    }
+}
+
+impl SyscallRet for Result<(), ApexReturnCode> {
+    fn invalid_arg() -> Self {
+        Err(ApexReturnCode::InvalidParam)
+    }
+
+    fn into_raw(self) -> usize {
+        match self {
+            Ok(()) => 0,
+            Err(e) => e as usize,
+        }
+    }
+}
+
+impl SyscallRet for Result<usize, Errno> {
+    fn invalid_arg() -> Self {
+        Err(Errno::EFAULT)
+    }
+
+    fn into_raw(self) -> usize {
+        match self {
+            Ok(ret) => ret,
+            Err(errno) => errno.into_ret(),
+        }
+    }
+}
+
+/// Defines `table_dispatch`, which decodes the raw arguments of the system calls listed into
+/// the typed arguments of their handlers, in order, and calls them.
+///
+/// Handlers are async functions returning a [`SyscallRet`]. The raw arguments a handler takes
+/// in total are checked against [`SYSCALL_ARGS`] at compile time.
+macro_rules! syscall_table {
+    ($($sysno:ident => $handler:ident($($arg:ident: $ty:ty),* $(,)?),)*) => {
+        /// Calls the handler of `sysno` in the table with `args` decoded, returning its raw
+        /// result if it has one.
+        async fn table_dispatch(
+            sysno: usize,
+            args: [usize; $crate::table::SYSCALL_ARGS],
+        ) -> Option<usize> {
+            use $crate::table::{SyscallArg, SyscallRet};
+
+            match sysno {
+                $(
+                    $sysno => {
+                        const _: () = assert!(
+                            0 $(+ <$ty as SyscallArg>::SLOTS)* <= $crate::table::SYSCALL_ARGS
+                        );
+                        #[allow(unused_variables)]
+                        let args = &mut args.iter();
+                        #[allow(unused_labels)]
+                        let ret = 'call: {
+                            $(
+                                let Some($arg) = <$ty as SyscallArg>::decode(args) else {
+                                    break 'call SyscallRet::invalid_arg();
+                                };
+                            )*
+                            $handler($($arg),*).await
+                        };
+                        Some(SyscallRet::into_raw(ret))
+                    }
+                )*
+                _ => None,
+            }
+        }
+
+        /// Renders `args` as the typed arguments of the handler of `sysno` in the table, returning
+        /// whether it has one.
+        pub(crate) fn table_trace(
+            sysno: usize,
+            args: &[usize; $crate::table::SYSCALL_ARGS],
+            f: &mut dyn core::fmt::Write,
+        ) -> Option<core::fmt::Result> {
+            $crate::table::trace_args!(sysno, args, f, $($sysno => ($($ty),*),)*)
+        }
+    };
+}
+
+/// Renders `args` as the arguments of types listed for `sysno`, if it is listed at all.
+macro_rules! trace_args {
+    ($sysno:expr, $args:expr, $f:expr, $($name:ident => ($($ty:ty),*),)*) => {
+        match $sysno {
+            $(
+                $name => {
+                    #[allow(unused_variables)]
+                    let args = &mut $args.iter();
+                    Some(Ok(()) $(.and_then(|()| $crate::table::trace_arg::<$ty>(args, $f)))*)
+                }
+            )*
+            _ => None,
+        }
+    };
+}
+
+/// Renders the raw arguments taken by an argument of type `T`, after a separator unless it
+/// comes first.
+pub(crate) fn trace_arg<T: SyscallArg>(
+    args: &mut core::slice::Iter<'_, usize>,
+    f: &mut dyn Write,
+) -> fmt::Result {
+    if args.len() < SYSCALL_ARGS {
+        f.write_str(", ")?;
+    }
+    T::trace(args, f)
+}
+
+pub(crate) use {syscall_table, trace_args};
@@ -0,0 +1,41 @@
+use core::time::Duration;
+
+use jrinx_a653::{process::Process, uptr::UserPtr};
+use jrinx_abi::time::{Timespec, CLOCK_MONOTONIC, CLOCK_REALTIME};
+use jrinx_error::Errno;
+use jrinx_hal::{Cpu, Hal};
+
+pub(crate) struct TimeSyscallHandler;
+
+impl TimeSyscallHandler {
+    /// Returns the current time of the clock `clock_id`, where the wall clock is unavailable
+    /// until the kernel has anchored it.
+    pub(crate) fn clock_gettime(&self, clock_id: usize) -> Result<Timespec, Errno> {
+        let now = hal!().cpu().get_time();
+        match clock_id {
+            CLOCK_MONOTONIC => Ok(now.into()),
+            CLOCK_REALTIME => jrinx_wallclock::to_wallclock(now)
+                .map(Timespec::from)
+                .ok_or(Errno::EINVAL),
+            _ => Err(Errno::EINVAL),
+        }
+    }
+
+    /// Sleeps for `req`, writing the time left into `rem` if a signal sent to the process cut
+    /// the sleep short.
+    pub(crate) async fn nanosleep(
+        &self,
+        req: Timespec,
+        rem: Option<UserPtr<Timespec>>,
+    ) -> Result<(), Errno> {
+        let duration = req.to_duration().ok_or(Errno::EINVAL)?;
+        let remaining = Process::current().unwrap().sleep(duration).await;
+        if remaining == Duration::ZERO {
+            return Ok(());
+        }
+        if let Some(mut rem) = rem {
+            rem.write(remaining.into());
+        }
+        Err(Errno::EINTR)
+    }
+}
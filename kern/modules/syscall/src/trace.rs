@@ -0,0 +1,80 @@
+//! Tracing of the system calls of processes, logged on entry as `name(args)` and on exit as
+//! `name = ret`.
+//!
+//! Arguments are rendered after the types of the handlers in the system call table, or after
+//! the types listed here for the system calls dispatched otherwise.
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+use jrinx_abi::sysno::*;
+use jrinx_error::Result;
+
+use crate::{
+    all::{log_prefix, table_trace},
+    table::{trace_args, SyscallArg, UserStr, SYSCALL_ARGS},
+};
+
+/// A raw argument rendered in hex, such as a pointer or a set of flags.
+struct Hex;
+
+impl SyscallArg for Hex {
+    fn decode(args: &mut core::slice::Iter<'_, usize>) -> Option<Self> {
+        args.next().map(|_| Self)
+    }
+}
+
+fn trace(sysno: usize, args: &[usize; SYSCALL_ARGS], f: &mut dyn Write) -> Option<fmt::Result> {
+    trace_args!(sysno, args, f,
+        SYS_GET_PARTITION_STATUS => (Hex),
+        SYS_SET_PARTITION_MODE => (usize),
+        SYS_GET_PROCESS_ID => (Hex, Hex),
+        SYS_GET_PROCESS_STATUS => (i64, Hex),
+        SYS_CREATE_PROCESS => (Hex, Hex),
+        SYS_START => (i64),
+        SYS_INITIALIZE_PROCESS_CORE_AFFINITY => (i64, usize),
+        SYS_CREATE_SEMAPHORE => (Hex, i64, i64, usize, Hex),
+        SYS_WAIT_SEMAPHORE => (i64, i64),
+        SYS_SIGNAL_SEMAPHORE => (i64),
+        SYS_GET_SEMAPHORE_ID => (Hex, Hex),
+        SYS_GET_SEMAPHORE_STATUS => (i64, Hex),
+        SYS_GET_CAPABILITIES => (Hex),
+        SYS_DROP_CAPABILITIES => (Hex),
+        SYS_RSEQ_REGISTER => (Hex),
+        SYS_SET_TUNABLE => (UserStr, UserStr),
+        SYS_TRAP_HANDLER_SET => (Hex, Hex, Hex),
+        SYS_TRAP_RETURN => (),
+        SYS_TRAP_SIGNAL => (i64, usize),
+        SYS_MAP_PAGE => (Hex),
+        SYS_DEBUG_LOG => (UserStr),
+        SYS_DEBUG_HALT => (),
+        SYS_DEBUG_LOG_RING_SETUP => (usize, Hex),
+        SYS_DEBUG_LOG_RING_DOORBELL => (),
+        SYS_DEBUG_EXIT => (usize),
+    )
+    .or_else(|| table_trace(sysno, args, f))
+}
+
+fn name(sysno: usize) -> String {
+    match jrinx_abi::sysno::name(sysno) {
+        Some(name) => name.trim_start_matches("SYS_").to_lowercase(),
+        None => alloc::format!("syscall_{:#x}", sysno),
+    }
+}
+
+pub(crate) fn enter(sysno: usize, args: &[usize; SYSCALL_ARGS]) {
+    let mut line = name(sysno);
+    line.push('(');
+    if trace(sysno, args, &mut line).is_none() {
+        let _ = write!(line, "{:#x?}", args);
+    }
+    line.push(')');
+    log::info!("*{}>> {}", log_prefix(), line);
+}
+
+pub(crate) fn exit(sysno: usize, ret: &Result<usize>) {
+    match ret {
+        Ok(ret) => log::info!("*{}>> {} = {}", log_prefix(), name(sysno), *ret as isize),
+        Err(err) => log::info!("*{}>> {} = Err({:?})", log_prefix(), name(sysno), err),
+    }
+}
@@ -0,0 +1,49 @@
+use jrinx_apex::*;
+use jrinx_tunable::TunableError;
+
+use crate::all::log_prefix;
+
+pub(crate) struct TunableSyscallHandler;
+
+impl TunableSyscallHandler {
+    /// Writes the value of the tunable `key` into `buf`, and its length into `len`, which is
+    /// written even if `buf` is too short to hold the value.
+    pub(crate) fn get(
+        &self,
+        key: &[u8],
+        buf: &mut [u8],
+        len: &mut usize,
+    ) -> Result<(), ApexReturnCode> {
+        let key = core::str::from_utf8(key).map_err(|_| ApexReturnCode::InvalidParam)?;
+        let value = jrinx_tunable::get(key).map_err(|_| ApexReturnCode::InvalidParam)?;
+
+        *len = value.len();
+        buf.get_mut(..value.len())
+            .ok_or(ApexReturnCode::InvalidConfig)?
+            .copy_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    pub(crate) fn set(&self, key: &[u8], value: &[u8]) -> Result<(), ApexReturnCode> {
+        let key = core::str::from_utf8(key).map_err(|_| ApexReturnCode::InvalidParam)?;
+        let value = core::str::from_utf8(value).map_err(|_| ApexReturnCode::InvalidParam)?;
+
+        match jrinx_tunable::set(key, value) {
+            Ok(()) => {
+                log::info!("*{}>> tunable {} set to {}", log_prefix(), key, value);
+                Ok(())
+            }
+            Err(TunableError::UnknownKey) => Err(ApexReturnCode::InvalidParam),
+            Err(err) => {
+                log::warn!(
+                    "*{}>> tunable {} not set to {}: {}",
+                    log_prefix(),
+                    key,
+                    value,
+                    err
+                );
+                Err(ApexReturnCode::InvalidConfig)
+            }
+        }
+    }
+}
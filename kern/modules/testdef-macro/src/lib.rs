@@ -1,9 +1,20 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use syn::{
+    parse::Parse, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Expr, ItemFn, Lit,
+    LitInt, Meta, Path, ReturnType, Token,
+};
 
 #[proc_macro_attribute]
-pub fn testdef(_: TokenStream, func: TokenStream) -> TokenStream {
+pub fn testdef(attr: TokenStream, func: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as TestDefAttr);
+    let should_panic = attr.should_panic;
+    let serial = attr.serial;
+    let timeout = match attr.timeout_ms {
+        Some(timeout_ms) => quote! { Some(core::time::Duration::from_millis(#timeout_ms)) },
+        None => quote! { None },
+    };
+
     let func = parse_macro_input!(func as ItemFn);
     let func_attrs = &func.attrs;
     let func_vis = &func.vis;
@@ -13,6 +24,22 @@ pub fn testdef(_: TokenStream, func: TokenStream) -> TokenStream {
     let func_inputs = &func.sig.inputs;
     let func_output = &func.sig.output;
 
+    // A test taking an argument belongs to a group, and is given its fixture.
+    let test_fn = match func.sig.inputs.len() {
+        0 => quote! { jrinx_testdef::TestFn::Plain(#func_name) },
+        1 => quote! {
+            jrinx_testdef::TestFn::Grouped(|fixture| #func_name(fixture.downcast_ref().unwrap()))
+        },
+        _ => {
+            return syn::Error::new(
+                func.sig.inputs.span(),
+                "test function must take no argument, or the fixture of its group",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
     let caller = quote! {
         #(#func_attrs)*
         #func_vis fn #func_name #func_generics(#func_inputs) #func_output {
@@ -24,7 +51,10 @@ pub fn testdef(_: TokenStream, func: TokenStream) -> TokenStream {
             )]
             static __TEST_DEF: &jrinx_testdef::TestDef = &jrinx_testdef::TestDef::new(
                 module_path!(),
-                #func_name,
+                #test_fn,
+                #should_panic,
+                #serial,
+                #timeout,
             );
 
             #func_block
@@ -33,3 +63,91 @@ pub fn testdef(_: TokenStream, func: TokenStream) -> TokenStream {
 
     caller.into()
 }
+
+/// Declares the tests in the submodules of the current module a group, whose fixture is set up
+/// by the function this is on, as `async fn() -> T`, and torn down by `async fn(T)` given as
+/// argument.
+///
+/// A test of the group takes the fixture by reference, as in `fn test(fixture: &T)`.
+#[proc_macro_attribute]
+pub fn testgroup(attr: TokenStream, func: TokenStream) -> TokenStream {
+    let teardown = parse_macro_input!(attr as Path);
+    let func = parse_macro_input!(func as ItemFn);
+    let func_name = &func.sig.ident;
+
+    if func.sig.asyncness.is_none()
+        || !func.sig.inputs.is_empty()
+        || matches!(func.sig.output, ReturnType::Default)
+    {
+        return syn::Error::new(
+            func.sig.span(),
+            "setup function must be `async fn() -> Fixture`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let group = quote! {
+        #func
+
+        #[cfg_attr(feature = "no_test", used)]
+        #[cfg_attr(
+            not(feature = "no_test"),
+            used(linker),
+            link_section = concat!(".tgroup.", module_path!()),
+        )]
+        static __TEST_GROUP: &jrinx_testdef::GroupDef = &jrinx_testdef::GroupDef::new(
+            module_path!(),
+            || {
+                ::alloc::boxed::Box::pin(async {
+                    ::alloc::boxed::Box::new(#func_name().await)
+                        as ::alloc::boxed::Box<jrinx_testdef::Fixture>
+                })
+            },
+            |fixture| ::alloc::boxed::Box::pin(#teardown(*fixture.downcast().unwrap())),
+        );
+    };
+
+    group.into()
+}
+
+struct TestDefAttr {
+    should_panic: bool,
+    serial: bool,
+    timeout_ms: Option<LitInt>,
+}
+
+impl Parse for TestDefAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let metas: Punctuated<Meta, Token![,]> = Punctuated::parse_terminated(input)?;
+        let mut attr = TestDefAttr {
+            should_panic: false,
+            serial: false,
+            timeout_ms: None,
+        };
+
+        for meta in metas.iter() {
+            match meta {
+                Meta::Path(path) if path.is_ident("should_panic") => attr.should_panic = true,
+                Meta::Path(path) if path.is_ident("serial") => attr.serial = true,
+                Meta::NameValue(pair) if pair.path.is_ident("timeout_ms") => {
+                    let Expr::Lit(lit) = &pair.value else {
+                        return Err(syn::Error::new(pair.value.span(), "integer expected"));
+                    };
+                    let Lit::Int(timeout_ms) = &lit.lit else {
+                        return Err(syn::Error::new(lit.span(), "integer expected"));
+                    };
+                    attr.timeout_ms = Some(timeout_ms.clone());
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        meta.span(),
+                        "expected `should_panic`, `serial` or `timeout_ms = <integer>`",
+                    ))
+                }
+            }
+        }
+
+        Ok(attr)
+    }
+}
@@ -1,32 +1,195 @@
 #![no_std]
 
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::{any::Any, future::Future, pin::Pin, time::Duration};
+
 pub use jrinx_testdef_macro::*;
 
+/// State set up for the tests of a group, type-erased as groups register through the linker.
+pub type Fixture = dyn Any + Send + Sync;
+
+pub type FixtureFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+#[derive(Clone, Copy)]
+pub enum TestFn {
+    Plain(fn()),
+    /// A test of a group, given the fixture of the group, see [`testgroup`].
+    Grouped(fn(&Fixture)),
+}
+
 #[repr(C)]
 pub struct TestDef {
     name: &'static str,
-    test: fn(),
+    test: TestFn,
+    should_panic: bool,
+    serial: bool,
+    timeout: Option<Duration>,
 }
 
 impl TestDef {
-    pub const fn new(name: &'static str, test: fn()) -> Self {
-        Self { name, test }
+    pub const fn new(
+        name: &'static str,
+        test: TestFn,
+        should_panic: bool,
+        serial: bool,
+        timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            name,
+            test,
+            should_panic,
+            serial,
+            timeout,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn test(&self) -> TestFn {
+        self.test
+    }
+
+    /// Whether the test passes by panicking, as set by `#[testdef(should_panic)]`.
+    pub fn should_panic(&self) -> bool {
+        self.should_panic
+    }
+
+    /// Whether the test must run alone, as set by `#[testdef(serial)]`, for it relies on the
+    /// state of the whole system.
+    pub fn serial(&self) -> bool {
+        self.serial
+    }
+
+    /// Returns the time the test must finish within, as set by `#[testdef(timeout_ms = ..)]`.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Returns the group a test taking a fixture belongs to, being the innermost one declared
+    /// in a module enclosing it.
+    pub fn group(&self) -> Option<&'static GroupDef> {
+        if !matches!(self.test, TestFn::Grouped(_)) {
+            return None;
+        }
+        groupdef_iter()
+            .filter(|group_def| {
+                self.name
+                    .strip_prefix(group_def.name)
+                    .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|group_def| group_def.name.len())
+    }
+
+    /// Whether the test can only run isolated in a task of its own, by the test runner.
+    pub fn needs_isolation(&self) -> bool {
+        self.should_panic || self.timeout.is_some() || matches!(self.test, TestFn::Grouped(_))
+    }
+}
+
+/// A group of tests sharing a fixture, set up before the first of them runs and torn down
+/// after the last, see [`testgroup`].
+#[repr(C)]
+pub struct GroupDef {
+    name: &'static str,
+    setup: fn() -> FixtureFuture<Box<Fixture>>,
+    teardown: fn(Box<Fixture>) -> FixtureFuture<()>,
+}
+
+impl GroupDef {
+    pub const fn new(
+        name: &'static str,
+        setup: fn() -> FixtureFuture<Box<Fixture>>,
+        teardown: fn(Box<Fixture>) -> FixtureFuture<()>,
+    ) -> Self {
+        Self {
+            name,
+            setup,
+            teardown,
+        }
     }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn setup(&self) -> FixtureFuture<Box<Fixture>> {
+        (self.setup)()
+    }
+
+    pub fn teardown(&self, fixture: Box<Fixture>) -> FixtureFuture<()> {
+        (self.teardown)(fixture)
+    }
+}
+
+pub fn all() -> impl Iterator<Item = &'static TestDef> {
+    testdef_iter()
 }
 
-pub fn all() -> impl Iterator<Item = &'static str> {
-    testdef_iter().map(|test_def| test_def.name)
+pub fn find(name: &str) -> Option<&'static TestDef> {
+    testdef_iter().find(|test_def| test_def.name.contains(name))
 }
 
-pub fn find(name: &str) -> Option<(&'static str, fn())> {
-    testdef_iter().find_map(|test_def| {
-        test_def
-            .name
-            .contains(name)
-            .then_some((test_def.name, test_def.test))
+/// Returns the tests whose name matches the glob `pattern` in full or from after any `::`, so
+/// that `task::*` matches `jrinx::test::task::runtime`.
+pub fn find_all(pattern: &str) -> impl Iterator<Item = &'static TestDef> + '_ {
+    testdef_iter().filter(move |test_def| {
+        let name = test_def.name;
+        core::iter::once(name)
+            .chain(name.match_indices("::").map(|(i, _)| &name[i + 2..]))
+            .any(|name| glob_match(pattern, name))
     })
 }
 
+/// Returns whether `s` is a glob rather than (part of) a test name.
+pub fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Returns whether `name` matches `pattern`, in which `*` stands for any characters and `?` for
+/// any one character, while all others stand for themselves.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (mut pattern, mut name) = (pattern, name);
+    // Where to resume after the last `*`, should it have to match one more character.
+    let mut backtrack = None;
+    loop {
+        let mut pattern_chars = pattern.chars();
+        let mut name_chars = name.chars();
+        match (pattern_chars.next(), name_chars.next()) {
+            (Some('*'), _) => {
+                pattern = pattern_chars.as_str();
+                backtrack = Some((pattern, name));
+            }
+            (Some(p), Some(n)) if p == '?' || p == n => {
+                pattern = pattern_chars.as_str();
+                name = name_chars.as_str();
+            }
+            (None, None) => return true,
+            _ => {
+                let Some((star_pattern, star_name)) = backtrack else {
+                    return false;
+                };
+                let mut star_chars = star_name.chars();
+                if star_chars.next().is_none() {
+                    return false;
+                }
+                pattern = star_pattern;
+                name = star_chars.as_str();
+                backtrack = Some((pattern, name));
+            }
+        }
+    }
+}
+
+fn groupdef_iter() -> impl Iterator<Item = &'static GroupDef> {
+    (jrinx_layout::_stgroup()..jrinx_layout::_etgroup())
+        .step_by(core::mem::size_of::<&GroupDef>())
+        .map(|a| unsafe { *(a as *const &GroupDef) })
+}
+
 fn testdef_iter() -> impl Iterator<Item = &'static TestDef> {
     (jrinx_layout::_stest()..jrinx_layout::_etest())
         .step_by(core::mem::size_of::<&TestDef>())
@@ -0,0 +1,112 @@
+use core::{fmt::Display, time::Duration};
+
+use jrinx_percpu::percpu;
+use jrinx_sync::IrqSafeMutex;
+
+/// Number of buckets in a [`JitterHistogram`].
+///
+/// Bucket `0` counts jitter below 1us, bucket `i` counts jitter in `[2^(i-1)us, 2^i us)`, and
+/// the last bucket additionally absorbs everything above its lower bound.
+pub const JITTER_BUCKETS: usize = 16;
+
+#[percpu]
+static PRIMARY_JITTER: IrqSafeMutex<JitterHistogram> =
+    IrqSafeMutex::new("primary-jitter", JitterHistogram::new());
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JitterHistogram {
+    buckets: [u64; JITTER_BUCKETS],
+    count: u64,
+    max: Duration,
+}
+
+impl JitterHistogram {
+    pub const fn new() -> Self {
+        Self {
+            buckets: [0; JITTER_BUCKETS],
+            count: 0,
+            max: Duration::ZERO,
+        }
+    }
+
+    pub fn record(&mut self, jitter: Duration) {
+        self.buckets[Self::bucket_of(jitter)] += 1;
+        self.count += 1;
+        self.max = self.max.max(jitter);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (bucket, other) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *bucket += other;
+        }
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn buckets(&self) -> &[u64; JITTER_BUCKETS] {
+        &self.buckets
+    }
+
+    /// Returns the exclusive upper bound of `bucket`, or `None` for the last, unbounded one.
+    pub fn bucket_bound(bucket: usize) -> Option<Duration> {
+        (bucket + 1 < JITTER_BUCKETS).then(|| Duration::from_micros(1 << bucket))
+    }
+
+    fn bucket_of(jitter: Duration) -> usize {
+        let micros = jitter.as_micros();
+        if micros == 0 {
+            0
+        } else {
+            ((u128::BITS - micros.leading_zeros()) as usize).min(JITTER_BUCKETS - 1)
+        }
+    }
+}
+
+impl Display for JitterHistogram {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "count={} max={:?}", self.count, self.max)?;
+        for (bucket, &hits) in self.buckets.iter().enumerate() {
+            if hits == 0 {
+                continue;
+            }
+            match Self::bucket_bound(bucket) {
+                Some(bound) => write!(f, " <{:?}:{}", bound, hits)?,
+                None => write!(
+                    f,
+                    " >={:?}:{}",
+                    Duration::from_micros(1 << (bucket - 1)),
+                    hits
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn record_primary(jitter: Duration) {
+    PRIMARY_JITTER.as_ref().lock().record(jitter);
+}
+
+/// Returns the jitter observed by primary deadlines, merged over all CPUs.
+pub fn primary() -> JitterHistogram {
+    PRIMARY_JITTER
+        .iter()
+        .fold(JitterHistogram::new(), |mut histogram, cpu| {
+            histogram.merge(&cpu.lock());
+            histogram
+        })
+}
+
+pub fn reset_primary() {
+    for cpu in PRIMARY_JITTER.iter() {
+        *cpu.lock() = JitterHistogram::new();
+    }
+}
@@ -1,20 +1,20 @@
 #![no_std]
-#![feature(const_binary_heap_constructor)]
 
 extern crate alloc;
 
-use core::{cmp::Reverse, fmt::Debug, time::Duration};
+pub mod jitter;
+pub mod wheel;
 
-use alloc::{
-    boxed::Box,
-    collections::{BTreeMap, BinaryHeap},
-    sync::Arc,
-};
+use core::{fmt::Debug, time::Duration};
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
 use jrinx_error::{InternalError, Result};
-use jrinx_hal::{hal, Cpu, Hal, Interrupt};
+use jrinx_hal::{hal, Cpu, Hal};
 use jrinx_percpu::percpu;
 use jrinx_serial_id_macro::SerialId;
+use jrinx_sync::IrqSafeMutex;
 use spin::Mutex;
+use wheel::{TimerHandle, TimingWheel};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, SerialId)]
 struct TimedEventId(u64);
@@ -46,25 +46,24 @@ impl TimedEventHandler {
 pub struct TimedEvent {
     id: TimedEventId,
     cpu_id: usize,
-    time: Duration,
     inner: Mutex<TimedEventInner>,
 }
 
 struct TimedEventInner {
+    time: Duration,
     status: TimedEventStatus,
     handler: Option<TimedEventHandler>,
 }
 
 #[percpu]
-static TIMED_EVENT_QUEUE: Mutex<TimedEventQueue> = Mutex::new(TimedEventQueue::new());
+static TIMED_EVENT_QUEUE: IrqSafeMutex<TimedEventQueue> =
+    IrqSafeMutex::new("timed-event-queue", TimedEventQueue::new());
 
 pub fn with_current<F, R>(f: F) -> R
 where
     F: FnOnce(&mut TimedEventQueue) -> R,
 {
-    hal!()
-        .interrupt()
-        .with_saved_off(|| f(&mut TIMED_EVENT_QUEUE.as_ref().lock()))
+    f(&mut TIMED_EVENT_QUEUE.as_ref().lock())
 }
 
 impl TimedEvent {
@@ -72,8 +71,8 @@ impl TimedEvent {
         let tracker = TimedEventTracker(Arc::new(Self {
             id: TimedEventId::new(),
             cpu_id: hal!().cpu().id(),
-            time,
             inner: Mutex::new(TimedEventInner {
+                time,
                 status: TimedEventStatus::Pending,
                 handler: Some(handler),
             }),
@@ -118,6 +117,23 @@ impl TimedEventTracker {
         self.0.invoke(TimedEventStatus::Cancelled)
     }
 
+    /// Moves the pending event to `time`, keeping its handler.
+    ///
+    /// This fails with [`InternalError::InvalidTimedEventStatus`] once the event has timed out
+    /// or been cancelled, including while its handler is about to run.
+    pub fn rearm(&self, time: Duration) -> Result<()> {
+        TIMED_EVENT_QUEUE.with_spec_ref(self.cpu_id(), |queue| {
+            let mut queue = queue.lock();
+            let mut inner = self.0.inner.lock();
+            if inner.status != TimedEventStatus::Pending {
+                return Err(InternalError::InvalidTimedEventStatus);
+            }
+            queue.move_to(self.id(), time)?;
+            inner.time = time;
+            Ok(())
+        })
+    }
+
     pub fn retired(&self) -> bool {
         self.0.inner.lock().status != TimedEventStatus::Pending
     }
@@ -131,13 +147,35 @@ impl TimedEventTracker {
     }
 
     fn time(&self) -> Duration {
-        self.0.time
+        self.0.inner.lock().time
+    }
+}
+
+/// A deadline taking precedence over all timed events of its CPU.
+///
+/// There is at most one primary deadline per CPU. It is reserved for schedule-table window
+/// switches, whose jitter must not depend on how many timed events are due at the same time.
+pub struct PrimaryDeadline {
+    time: Duration,
+    handler: Box<dyn FnOnce() + Send + 'static>,
+}
+
+impl PrimaryDeadline {
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Records the jitter of this deadline and runs its handler.
+    pub fn fire(self) {
+        jitter::record_primary(hal!().cpu().get_time().saturating_sub(self.time));
+        (self.handler)();
     }
 }
 
 pub struct TimedEventQueue {
-    registry: BTreeMap<TimedEventId, TimedEventTracker>,
-    queue: BinaryHeap<Reverse<(Duration, TimedEventId)>>,
+    registry: BTreeMap<TimedEventId, (TimedEventTracker, TimerHandle)>,
+    wheel: TimingWheel<TimedEventId>,
+    primary: Option<PrimaryDeadline>,
 }
 
 impl Default for TimedEventQueue {
@@ -150,44 +188,92 @@ impl TimedEventQueue {
     pub const fn new() -> Self {
         Self {
             registry: BTreeMap::new(),
-            queue: BinaryHeap::new(),
+            wheel: TimingWheel::new(),
+            primary: None,
         }
     }
 
-    pub fn peek_outdated(&self) -> Option<TimedEventTracker> {
-        self.peek()
-            .filter(|tracker| tracker.time() <= hal!().cpu().get_time())
+    /// Arms the primary deadline of this CPU, replacing the previous one without running it.
+    pub fn set_primary(&mut self, time: Duration, handler: impl FnOnce() + Send + 'static) {
+        self.primary = Some(PrimaryDeadline {
+            time,
+            handler: Box::new(handler),
+        });
+        self.update_timer();
+    }
+
+    /// Disarms the primary deadline of this CPU, returning whether it was armed.
+    pub fn clear_primary(&mut self) -> bool {
+        let armed = self.primary.take().is_some();
+        self.update_timer();
+        armed
+    }
+
+    /// Takes the primary deadline out if it is due, leaving the timer programmed for the rest.
+    ///
+    /// The caller is expected to [`fire`](PrimaryDeadline::fire) it after releasing the queue.
+    pub fn take_outdated_primary(&mut self) -> Option<PrimaryDeadline> {
+        let now = hal!().cpu().get_time();
+        if self.primary.as_ref()?.time > now {
+            return None;
+        }
+        let primary = self.primary.take();
+        self.update_timer();
+        primary
+    }
+
+    pub fn peek_outdated(&mut self) -> Option<TimedEventTracker> {
+        let now = hal!().cpu().get_time();
+        self.wheel.advance(now);
+        self.peek().filter(|tracker| tracker.time() <= now)
     }
 
     fn add(&mut self, tracker: TimedEventTracker) {
         let id = tracker.id();
         let time = tracker.time();
-        self.registry.insert(id, tracker);
-        self.queue.push(Reverse((time, id)));
+        let handle = self.wheel.insert(time, id);
+        self.registry.insert(id, (tracker, handle));
         self.update_timer();
     }
 
+    fn move_to(&mut self, id: TimedEventId, time: Duration) -> Result<()> {
+        let (_, handle) = self
+            .registry
+            .get_mut(&id)
+            .ok_or(InternalError::InvalidTimedEventStatus)?;
+        self.wheel.remove(*handle);
+        *handle = self.wheel.insert(time, id);
+        self.update_timer();
+        Ok(())
+    }
+
     fn peek(&self) -> Option<TimedEventTracker> {
-        self.queue
-            .peek()
-            .map(|Reverse((_, id))| self.registry.get(id).unwrap().clone())
+        self.wheel
+            .next_deadline()
+            .map(|(_, id)| self.registry.get(&id).unwrap().0.clone())
     }
 
     fn remove(&mut self, tracker: TimedEventTracker) -> Result<()> {
         let id = tracker.id();
-        self.registry
+        let (_, handle) = self
+            .registry
             .remove(&id)
             .ok_or(InternalError::InvalidTimedEventStatus)?;
-        self.queue.retain(|&Reverse((_, that_id))| that_id != id);
+        self.wheel.remove(handle);
         self.update_timer();
         Ok(())
     }
 
+    /// Returns the deadline the timer of this CPU is programmed for, if any.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        let wheel = self.wheel.next_deadline().map(|(time, _)| time);
+        let primary = self.primary.as_ref().map(|primary| primary.time);
+        wheel.into_iter().chain(primary).min()
+    }
+
     fn update_timer(&self) {
-        if let Some(Reverse((time, _))) = self.queue.peek() {
-            hal!().cpu().set_timer(*time);
-        } else {
-            hal!().cpu().set_timer(Duration::MAX);
-        }
+        hal!()
+            .cpu()
+            .set_timer(self.next_deadline().unwrap_or(Duration::MAX));
     }
 }
@@ -0,0 +1,274 @@
+//! Hierarchical timing wheel of [`LEVELS`] levels of [`SLOTS`] slots each, ticking every
+//! millisecond, with deadlines beyond the last level kept in an overflow min-heap.
+//!
+//! Timers live in a slab addressed by their [`TimerHandle`], so inserting and cancelling one
+//! are O(1). The earliest deadline is cached and only looked up again once its timer is
+//! removed.
+
+use core::{cmp::Reverse, time::Duration};
+
+use alloc::{collections::BinaryHeap, vec::Vec};
+
+const LEVELS: usize = 4;
+const SLOT_BITS: usize = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+const TICK_NANOS: u128 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Slot {
+        level: usize,
+        slot: usize,
+        index: usize,
+    },
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerHandle {
+    index: usize,
+    generation: u64,
+}
+
+struct Timer<K> {
+    time: Duration,
+    key: K,
+    generation: u64,
+    /// Where the timer is, unless its entry is free.
+    pos: Option<Position>,
+}
+
+pub struct TimingWheel<K> {
+    now_tick: u64,
+    timers: Vec<Timer<K>>,
+    free: Vec<usize>,
+    len: usize,
+    slots: [[Vec<usize>; SLOTS]; LEVELS],
+    occupied: [u64; LEVELS],
+    /// Timers past the last level, left in the heap once cancelled until they surface.
+    overflow: BinaryHeap<Reverse<(Duration, K, usize, u64)>>,
+    earliest: Option<usize>,
+}
+
+impl<K: Copy + Ord> Default for TimingWheel<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Copy + Ord> TimingWheel<K> {
+    pub const fn new() -> Self {
+        Self {
+            now_tick: 0,
+            timers: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            slots: [const { [const { Vec::new() }; SLOTS] }; LEVELS],
+            occupied: [0; LEVELS],
+            overflow: BinaryHeap::new(),
+            earliest: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, time: Duration, key: K) -> TimerHandle {
+        let index = match self.free.pop() {
+            Some(index) => {
+                let timer = &mut self.timers[index];
+                timer.time = time;
+                timer.key = key;
+                index
+            }
+            None => {
+                self.timers.push(Timer {
+                    time,
+                    key,
+                    generation: 0,
+                    pos: None,
+                });
+                self.timers.len() - 1
+            }
+        };
+        self.len += 1;
+        self.place(index);
+        if self.earliest.map_or(true, |earliest| {
+            self.deadline(index) < self.deadline(earliest)
+        }) {
+            self.earliest = Some(index);
+        }
+        TimerHandle {
+            index,
+            generation: self.timers[index].generation,
+        }
+    }
+
+    pub fn remove(&mut self, handle: TimerHandle) -> bool {
+        let Some(timer) = self
+            .timers
+            .get_mut(handle.index)
+            .filter(|timer| timer.generation == handle.generation)
+        else {
+            return false;
+        };
+        let Some(pos) = timer.pos.take() else {
+            return false;
+        };
+        timer.generation += 1;
+        self.free.push(handle.index);
+        self.len -= 1;
+
+        if let Position::Slot { level, slot, index } = pos {
+            let bucket = &mut self.slots[level][slot];
+            bucket.swap_remove(index);
+            if let Some(&moved) = bucket.get(index) {
+                self.timers[moved].pos = Some(Position::Slot { level, slot, index });
+            }
+            if bucket.is_empty() {
+                self.occupied[level] &= !(1 << slot);
+            }
+        }
+
+        if self.earliest == Some(handle.index) {
+            self.earliest = self.find_earliest();
+        }
+        true
+    }
+
+    pub fn next_deadline(&self) -> Option<(Duration, K)> {
+        self.earliest.map(|index| self.deadline(index))
+    }
+
+    pub fn advance(&mut self, now: Duration) {
+        let Some((deadline, _)) = self.next_deadline() else {
+            self.now_tick = self.now_tick.max(Self::tick_of(now));
+            return;
+        };
+
+        let target = Self::tick_of(now).min(Self::tick_of(deadline));
+        while self.now_tick < target {
+            let level = (0..LEVELS)
+                .find(|&level| self.occupied[level] != 0)
+                .unwrap_or(LEVELS);
+            let shift = SLOT_BITS * level;
+            let boundary = ((self.now_tick >> shift) + 1) << shift;
+
+            if boundary > target {
+                self.now_tick = target;
+                break;
+            }
+            self.now_tick = boundary;
+            self.cascade();
+        }
+    }
+
+    fn cascade(&mut self) {
+        if self.now_tick & ((1 << (SLOT_BITS * LEVELS)) - 1) == 0 {
+            while let Some(&Reverse((time, _, index, generation))) = self.overflow.peek() {
+                if self.is_live(index, generation)
+                    && Self::tick_of(time).saturating_sub(self.now_tick)
+                        >= 1 << (SLOT_BITS * LEVELS)
+                {
+                    break;
+                }
+                self.overflow.pop();
+                if self.is_live(index, generation) {
+                    self.place(index);
+                }
+            }
+        }
+
+        for level in (1..LEVELS).rev() {
+            let shift = SLOT_BITS * level;
+            if self.now_tick & ((1 << shift) - 1) != 0 {
+                continue;
+            }
+            let slot = ((self.now_tick >> shift) & SLOT_MASK) as usize;
+            self.occupied[level] &= !(1 << slot);
+            for index in core::mem::take(&mut self.slots[level][slot]) {
+                self.place(index);
+            }
+        }
+    }
+
+    fn place(&mut self, index: usize) {
+        let timer = &self.timers[index];
+        let tick = Self::tick_of(timer.time).max(self.now_tick);
+        let delta = tick - self.now_tick;
+
+        let pos = match (0..LEVELS).find(|&level| delta < 1 << (SLOT_BITS * (level + 1))) {
+            Some(level) => {
+                let slot = ((tick >> (SLOT_BITS * level)) & SLOT_MASK) as usize;
+                let bucket = &mut self.slots[level][slot];
+                bucket.push(index);
+                self.occupied[level] |= 1 << slot;
+                Position::Slot {
+                    level,
+                    slot,
+                    index: bucket.len() - 1,
+                }
+            }
+            None => {
+                self.overflow
+                    .push(Reverse((timer.time, timer.key, index, timer.generation)));
+                Position::Overflow
+            }
+        };
+        self.timers[index].pos = Some(pos);
+    }
+
+    /// Looks the earliest timer up among the first occupied slot of each level and the top of
+    /// the overflow.
+    fn find_earliest(&mut self) -> Option<usize> {
+        while let Some(&Reverse((_, _, index, generation))) = self.overflow.peek() {
+            if self.is_live(index, generation) {
+                break;
+            }
+            self.overflow.pop();
+        }
+
+        let overflow = self.overflow.peek().map(|&Reverse((_, _, index, _))| index);
+        (0..LEVELS)
+            .filter_map(|level| {
+                self.first_occupied(level)
+                    .map(|slot| &self.slots[level][slot])
+            })
+            .flatten()
+            .copied()
+            .chain(overflow)
+            .min_by_key(|&index| self.deadline(index))
+    }
+
+    fn is_live(&self, index: usize, generation: u64) -> bool {
+        let timer = &self.timers[index];
+        timer.generation == generation && timer.pos == Some(Position::Overflow)
+    }
+
+    fn deadline(&self, index: usize) -> (Duration, K) {
+        let timer = &self.timers[index];
+        (timer.time, timer.key)
+    }
+
+    fn first_occupied(&self, level: usize) -> Option<usize> {
+        let occupied = self.occupied[level];
+        if occupied == 0 {
+            return None;
+        }
+
+        let current = ((self.now_tick >> (SLOT_BITS * level)) & SLOT_MASK) as u32;
+        let start = (if level == 0 { current } else { current + 1 }) % SLOTS as u32;
+        let rotated = occupied.rotate_right(start);
+        Some(((rotated.trailing_zeros() + start) % SLOTS as u32) as usize)
+    }
+
+    fn tick_of(time: Duration) -> u64 {
+        (time.as_nanos() / TICK_NANOS).min(u64::MAX as u128) as u64
+    }
+}
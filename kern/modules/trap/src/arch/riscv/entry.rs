@@ -1,12 +1,27 @@
 use super::{handle_kern_trap, Context};
+use crate::stack_guard::EMERGENCY_STACK_TOP;
 
 use core::mem::{offset_of, size_of};
 
+use jrinx_config::{EXECUTOR_STACK_REGION, EXECUTOR_STACK_SIZE};
+
+// Executor stacks are all allocated with guards of their own size, so an address of their
+// region lies on a guard if and only if the bit of that size is clear. The region extends to
+// the top of the address space, which is then told by the sign of an arithmetic shift.
+const _: () = assert!(EXECUTOR_STACK_REGION.addr.wrapping_neg().is_power_of_two());
+const _: () = assert!(EXECUTOR_STACK_SIZE.is_power_of_two());
+const _: () = assert!(EXECUTOR_STACK_REGION.addr % (EXECUTOR_STACK_SIZE * 2) == 0);
+
+const GUARD_REGION_SHIFT: u32 = EXECUTOR_STACK_REGION.addr.wrapping_neg().trailing_zeros();
+const GUARD_STACK_SHIFT: u32 = usize::BITS - 1 - EXECUTOR_STACK_SIZE.trailing_zeros();
+
 core::arch::global_asm! {
     r#".attribute arch, "rv64imafd""#,
     r"
     .equ XLENB, {XLENB}
     .equ CTX_SIZE, {CTX_SIZE}
+    .equ GUARD_REGION_SHIFT, {GUARD_REGION_SHIFT}
+    .equ GUARD_STACK_SHIFT, {GUARD_STACK_SHIFT}
     .equ CTX_OFFS_REG_ZERO, {CTX_OFFS_REG_ZERO}
     .equ CTX_OFFS_REG_RA, {CTX_OFFS_REG_RA}
     .equ CTX_OFFS_REG_SP, {CTX_OFFS_REG_SP}
@@ -72,6 +87,7 @@ core::arch::global_asm! {
     .equ CTX_OFFS_FREG_FT9, {CTX_OFFS_FREG_FT9}
     .equ CTX_OFFS_FREG_FT10, {CTX_OFFS_FREG_FT10}
     .equ CTX_OFFS_FREG_FT11, {CTX_OFFS_FREG_FT11}
+    .equ CTX_OFFS_FREG_FCSR, {CTX_OFFS_FREG_FCSR}
 
     .equ CTX_OFFS_SSTATUS, {CTX_OFFS_SSTATUS}
     .equ CTX_OFFS_SCAUSE, {CTX_OFFS_SCAUSE}
@@ -81,6 +97,8 @@ core::arch::global_asm! {
     ",
     XLENB = const size_of::<usize>(),
     CTX_SIZE = const size_of::<Context>(),
+    GUARD_REGION_SHIFT = const GUARD_REGION_SHIFT,
+    GUARD_STACK_SHIFT = const GUARD_STACK_SHIFT,
     CTX_OFFS_REG_ZERO = const offset_of!(Context, regs.zero),
     CTX_OFFS_REG_RA = const offset_of!(Context, regs.ra),
     CTX_OFFS_REG_SP = const offset_of!(Context, regs.sp),
@@ -146,6 +164,7 @@ core::arch::global_asm! {
     CTX_OFFS_FREG_FT9 = const offset_of!(Context, fregs.ft9),
     CTX_OFFS_FREG_FT10 = const offset_of!(Context, fregs.ft10),
     CTX_OFFS_FREG_FT11 = const offset_of!(Context, fregs.ft11),
+    CTX_OFFS_FREG_FCSR = const offset_of!(Context, fregs.fcsr),
 
     CTX_OFFS_SSTATUS = const offset_of!(Context, sstatus),
     CTX_OFFS_SCAUSE = const offset_of!(Context, scause),
@@ -201,6 +220,24 @@ core::arch::global_asm! {
     trap_from_kern_st:
         csrr sp, sscratch
         addi sp, sp, -CTX_SIZE
+        srai sp, sp, GUARD_REGION_SHIFT
+        addi sp, sp, 1
+        bnez sp, trap_from_kern_frame
+
+        csrr sp, sscratch
+        addi sp, sp, -CTX_SIZE
+        slli sp, sp, GUARD_STACK_SHIFT
+        bltz sp, trap_from_kern_frame
+
+        la sp, {EMERGENCY_STACK_TOP}
+        POP_REG sp, 0
+        add sp, sp, gp
+        addi sp, sp, -CTX_SIZE
+        j trap_from_user_st
+
+    trap_from_kern_frame:
+        csrr sp, sscratch
+        addi sp, sp, -CTX_SIZE
 
     trap_from_user_st:
         PUSH_REG ra, CTX_OFFS_REG_RA
@@ -234,6 +271,12 @@ core::arch::global_asm! {
         PUSH_REG t5, CTX_OFFS_REG_T5
         PUSH_REG t6, CTX_OFFS_REG_T6
 
+        csrr t0, sstatus
+        srli t0, t0, 13
+        andi t0, t0, 3
+        addi t0, t0, -3
+        bnez t0, trap_fregs_saved
+
         PUSH_FREG ft0, CTX_OFFS_FREG_FT0
         PUSH_FREG ft1, CTX_OFFS_FREG_FT1
         PUSH_FREG ft2, CTX_OFFS_FREG_FT2
@@ -266,7 +309,10 @@ core::arch::global_asm! {
         PUSH_FREG ft9, CTX_OFFS_FREG_FT9
         PUSH_FREG ft10, CTX_OFFS_FREG_FT10
         PUSH_FREG ft11, CTX_OFFS_FREG_FT11
+        frcsr t0
+        PUSH_REG t0, CTX_OFFS_FREG_FCSR
 
+    trap_fregs_saved:
         csrrw t0, sscratch, zero
         PUSH_REG t0, CTX_OFFS_REG_SP
 
@@ -281,6 +327,9 @@ core::arch::global_asm! {
         csrr t5, sepc
         PUSH_REG t5, CTX_OFFS_SEPC
 
+        li t6, 3 << 13
+        csrc sstatus, t6
+
         andi t1, t1, 1 << 8
         beqz t1, trap_from_user_ed
 
@@ -350,6 +399,12 @@ core::arch::global_asm! {
         POP_REG t1, CTX_OFFS_SSTATUS
         csrw sstatus, t1
 
+        srli t1, t1, 13
+        andi t1, t1, 3
+        beqz t1, trap_fregs_restored
+
+        POP_REG t0, CTX_OFFS_FREG_FCSR
+        fscsr t0
         POP_FREG ft11, CTX_OFFS_FREG_FT11
         POP_FREG ft10, CTX_OFFS_FREG_FT10
         POP_FREG ft9, CTX_OFFS_FREG_FT9
@@ -382,7 +437,10 @@ core::arch::global_asm! {
         POP_FREG ft2, CTX_OFFS_FREG_FT2
         POP_FREG ft1, CTX_OFFS_FREG_FT1
         POP_FREG ft0, CTX_OFFS_FREG_FT0
+        li t1, 1 << 13
+        csrc sstatus, t1
 
+    trap_fregs_restored:
         POP_REG t6, CTX_OFFS_REG_T6
         POP_REG t5, CTX_OFFS_REG_T5
         POP_REG t4, CTX_OFFS_REG_T4
@@ -419,4 +477,5 @@ core::arch::global_asm! {
         sret
     ",
     KERNEL_TRAP_HANDLER = sym handle_kern_trap,
+    EMERGENCY_STACK_TOP = sym EMERGENCY_STACK_TOP,
 }
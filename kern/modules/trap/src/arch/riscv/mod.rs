@@ -1,6 +1,8 @@
 mod entry;
+mod trigger;
 
 use jrinx_addr::VirtAddr;
+use jrinx_hal::{hal, Hal, Interrupt as _};
 use jrinx_paging::{GenericPagePerm, PagePerm};
 use riscv::register::{
     scause::{Exception, Interrupt},
@@ -8,7 +10,29 @@ use riscv::register::{
     stvec::TrapMode,
 };
 
-use crate::{breakpoint, soft_int, timer_int, GenericContext, TrapReason};
+pub(crate) use trigger::{
+    disable_triggers, enable_triggers, install_trigger, trigger_capacity, uninstall_trigger,
+};
+
+use crate::{
+    breakpoint, external_int, fatal, fault, handler,
+    nest::{self, IrqClass},
+    page_fault, soft_int, stack_guard, stats, timer_int, watchpoint, AccessKind, GenericContext,
+    TrapReason,
+};
+
+/// ABI names of the registers, in order of their numbers.
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// Width of a register written in hexadecimal, with its `0x` prefix.
+const REG_WIDTH: usize = 2 + usize::BITS as usize / 4;
+
+pub(crate) const EBREAK: u32 = 0x0010_0073;
+pub(crate) const C_EBREAK: u32 = 0x9002;
 
 #[derive(Debug, Default, Clone, Copy)]
 #[repr(C)]
@@ -49,7 +73,7 @@ pub struct Register {
 
 #[derive(Debug, Default, Clone, Copy)]
 #[repr(C)]
-pub struct FRegister {
+pub struct FpState {
     ft0: usize,
     ft1: usize,
     ft2: usize,
@@ -82,13 +106,14 @@ pub struct FRegister {
     ft9: usize,
     ft10: usize,
     ft11: usize,
+    fcsr: usize,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
 #[repr(C)]
 pub struct Context {
     regs: Register,
-    fregs: FRegister,
+    fregs: FpState,
     sstatus: usize,
     scause: usize,
     sie: usize,
@@ -111,8 +136,15 @@ impl GenericContext for Context {
             let code = Exception::from(self.scause);
             match code {
                 Exception::UserEnvCall => TrapReason::SystemCall,
-                Exception::Breakpoint => TrapReason::Breakpoint {
-                    addr: VirtAddr::new(self.sepc),
+                // Triggers on data accesses trap as breakpoints at the accessing instruction.
+                Exception::Breakpoint => match self.inst() {
+                    Some(inst) if inst != EBREAK && inst != C_EBREAK => TrapReason::Watchpoint {
+                        pc: VirtAddr::new(self.sepc),
+                        addr: VirtAddr::new(self.stval),
+                    },
+                    _ => TrapReason::Breakpoint {
+                        addr: VirtAddr::new(self.sepc),
+                    },
                 },
                 Exception::LoadPageFault => TrapReason::PageFault {
                     addr: VirtAddr::new(self.stval),
@@ -126,6 +158,38 @@ impl GenericContext for Context {
                     addr: VirtAddr::new(self.stval),
                     perm: PagePerm::X,
                 },
+                // Harts may leave stval zero rather than hand over the instruction bits.
+                Exception::IllegalInstruction => TrapReason::IllegalInstruction {
+                    addr: VirtAddr::new(self.sepc),
+                    inst: match self.stval {
+                        0 => self.inst().unwrap_or(0),
+                        stval => stval as u32,
+                    },
+                },
+                Exception::LoadMisaligned => TrapReason::MisalignedAccess {
+                    addr: VirtAddr::new(self.stval),
+                    kind: AccessKind::Read,
+                },
+                Exception::StoreMisaligned => TrapReason::MisalignedAccess {
+                    addr: VirtAddr::new(self.stval),
+                    kind: AccessKind::Write,
+                },
+                Exception::InstructionMisaligned => TrapReason::MisalignedAccess {
+                    addr: VirtAddr::new(self.stval),
+                    kind: AccessKind::Fetch,
+                },
+                Exception::LoadFault => TrapReason::AccessFault {
+                    addr: VirtAddr::new(self.stval),
+                    kind: AccessKind::Read,
+                },
+                Exception::StoreFault => TrapReason::AccessFault {
+                    addr: VirtAddr::new(self.stval),
+                    kind: AccessKind::Write,
+                },
+                Exception::InstructionFault => TrapReason::AccessFault {
+                    addr: VirtAddr::new(self.stval),
+                    kind: AccessKind::Fetch,
+                },
                 _ => TrapReason::Unknown { code: self.scause },
             }
         }
@@ -133,7 +197,7 @@ impl GenericContext for Context {
 
     fn user_setup(&mut self, entry_point: usize, stack_top: usize) {
         self.regs.sp = stack_top;
-        self.sstatus = 1 << 18 | (FS::Initial as usize) << 13 | (SPP::User as usize) << 8 | 1 << 5; // sum | fs | spp | spie
+        self.sstatus = 1 << 18 | (FS::Off as usize) << 13 | (SPP::User as usize) << 8 | 1 << 5; // sum | fs | spp | spie
         self.sepc = entry_point;
         self.enable_int();
     }
@@ -146,6 +210,155 @@ impl GenericContext for Context {
         self.sie = 0;
     }
 
+    fn int_enabled(&self) -> bool {
+        self.sie != 0
+    }
+
+    fn pc(&self) -> usize {
+        self.sepc
+    }
+
+    fn fp(&self) -> usize {
+        self.regs.s0
+    }
+
+    const GPR_COUNT: usize = REG_NAMES.len();
+
+    fn gpr(&self, num: usize) -> usize {
+        self.reg(num)
+    }
+
+    fn set_gpr(&mut self, num: usize, value: usize) {
+        assert!(num < 32, "invalid register x{}", num);
+        if num != 0 {
+            // Registers are laid out in order of their numbers.
+            unsafe {
+                (&mut self.regs as *mut Register as *mut usize)
+                    .add(num)
+                    .write(value)
+            };
+        }
+    }
+
+    fn log_regs(&self) {
+        for (num, names) in REG_NAMES.chunks(4).enumerate() {
+            let num = num * 4;
+            error!(
+                "{:>4} {:#0w$x} {:>4} {:#0w$x} {:>4} {:#0w$x} {:>4} {:#0w$x}",
+                names[0],
+                self.reg(num),
+                names[1],
+                self.reg(num + 1),
+                names[2],
+                self.reg(num + 2),
+                names[3],
+                self.reg(num + 3),
+                w = REG_WIDTH,
+            );
+        }
+        error!(
+            "sepc {:#0w$x} stval {:#0w$x} scause {:#0w$x} sstatus {:#0w$x}",
+            self.sepc,
+            self.stval,
+            self.scause,
+            self.sstatus,
+            w = REG_WIDTH,
+        );
+    }
+
+    fn fp_state(&mut self) -> Option<&mut FpState> {
+        match self.sstatus >> 13 & 0b11 {
+            0 => None,
+            _ => Some(&mut self.fregs),
+        }
+    }
+
+    fn attach_fp_state(&mut self) {
+        self.fregs = FpState::default();
+        self.sstatus = self.sstatus & !(0b11 << 13) | (FS::Initial as usize) << 13;
+    }
+
+    fn inst(&self) -> Option<u32> {
+        let from_user = self.sstatus & 1 << 8 == 0; // spp
+        let fetch_failed = matches!(
+            Exception::from(self.scause),
+            Exception::InstructionMisaligned
+                | Exception::InstructionFault
+                | Exception::InstructionPageFault
+        );
+        if from_user || fetch_failed {
+            return None;
+        }
+
+        // Instructions are only 16-bit aligned with compressed ones around.
+        let low = unsafe { (self.sepc as *const u16).read() };
+        if low & 0b11 != 0b11 {
+            return Some(low as u32);
+        }
+        let high = unsafe { (self.sepc as *const u16).add(1).read() };
+        Some((high as u32) << 16 | low as u32)
+    }
+
+    fn next_pc(&self) -> Option<usize> {
+        let inst = self.inst()?;
+        let pc = self.sepc;
+
+        if inst & 0b11 != 0b11 {
+            let next = match (inst & 0b11, inst >> 13 & 0b111) {
+                // c.j, and c.jal on rv32 only, where rv64 has c.addiw instead.
+                (0b01, 0b101) => pc.wrapping_add(c_j_offset(inst)),
+                (0b01, 0b001) if cfg!(target_arch = "riscv32") => pc.wrapping_add(c_j_offset(inst)),
+                // c.beqz and c.bnez.
+                (0b01, funct3 @ (0b110 | 0b111)) => {
+                    let rs1 = self.reg(bits(inst, 9, 7) + 8);
+                    if (rs1 == 0) == (funct3 == 0b110) {
+                        pc.wrapping_add(c_b_offset(inst))
+                    } else {
+                        pc + 2
+                    }
+                }
+                // c.jr and c.jalr.
+                (0b10, 0b100) if bits(inst, 6, 2) == 0 && bits(inst, 11, 7) != 0 => {
+                    self.reg(bits(inst, 11, 7)) & !1
+                }
+                _ => pc + 2,
+            };
+            return Some(next);
+        }
+
+        let next = match inst & 0x7f {
+            // jal
+            0x6f => pc.wrapping_add(j_offset(inst)),
+            // jalr
+            0x67 => self.reg(bits(inst, 19, 15)).wrapping_add(i_offset(inst)) & !1,
+            // beq, bne, blt, bge, bltu and bgeu
+            0x63 => {
+                let rs1 = self.reg(bits(inst, 19, 15));
+                let rs2 = self.reg(bits(inst, 24, 20));
+                let taken = match bits(inst, 14, 12) {
+                    0b000 => rs1 == rs2,
+                    0b001 => rs1 != rs2,
+                    0b100 => (rs1 as isize) < (rs2 as isize),
+                    0b101 => (rs1 as isize) >= (rs2 as isize),
+                    0b110 => rs1 < rs2,
+                    0b111 => rs1 >= rs2,
+                    _ => false,
+                };
+                if taken {
+                    pc.wrapping_add(b_offset(inst))
+                } else {
+                    pc + 4
+                }
+            }
+            _ => pc + 4,
+        };
+        Some(next)
+    }
+
+    fn set_pc(&mut self, pc: usize) {
+        self.sepc = pc;
+    }
+
     fn pc_advance(&mut self) {
         let is_rvc = (unsafe { (self.sepc as *const u8).read() & 0b11 }) != 0b11;
         if is_rvc {
@@ -175,12 +388,155 @@ impl GenericContext for Context {
         self.regs.a0 = ret;
     }
 
+    fn set_args(&mut self, args: &[usize]) {
+        let regs = [
+            &mut self.regs.a0,
+            &mut self.regs.a1,
+            &mut self.regs.a2,
+            &mut self.regs.a3,
+            &mut self.regs.a4,
+            &mut self.regs.a5,
+            &mut self.regs.a6,
+        ];
+        assert!(
+            args.len() <= regs.len(),
+            "too many arguments: {}",
+            args.len()
+        );
+        for (reg, &arg) in regs.into_iter().zip(args) {
+            *reg = arg;
+        }
+    }
+
     fn run(&mut self) {
         extern "C" {
             fn run_user(ctx: &mut Context);
         }
-        unsafe { run_user(self) };
+        // An interrupt taken once the context is installed for traps from user mode, yet before
+        // leaving kernel mode, would be saved over the context.
+        hal!()
+            .interrupt()
+            .with_saved_off(|| unsafe { run_user(self) });
+        #[cfg(feature = "trap-stats")]
+        crate::stats::record(&self.trap_reason());
+    }
+}
+
+impl Context {
+    fn reg(&self, num: usize) -> usize {
+        assert!(num < 32, "invalid register x{}", num);
+        match num {
+            0 => 0,
+            // Registers are laid out in order of their numbers.
+            num => unsafe {
+                (&self.regs as *const Register as *const usize)
+                    .add(num)
+                    .read()
+            },
+        }
+    }
+}
+
+fn bits(inst: u32, hi: u32, lo: u32) -> usize {
+    (inst >> lo & ((1 << (hi - lo + 1)) - 1)) as usize
+}
+
+fn sign_extend(imm: usize, width: u32) -> usize {
+    let shift = usize::BITS - width;
+    ((imm << shift) as isize >> shift) as usize
+}
+
+fn i_offset(inst: u32) -> usize {
+    sign_extend(bits(inst, 31, 20), 12)
+}
+
+fn j_offset(inst: u32) -> usize {
+    let imm = bits(inst, 31, 31) << 20
+        | bits(inst, 19, 12) << 12
+        | bits(inst, 20, 20) << 11
+        | bits(inst, 30, 21) << 1;
+    sign_extend(imm, 21)
+}
+
+fn b_offset(inst: u32) -> usize {
+    let imm = bits(inst, 31, 31) << 12
+        | bits(inst, 7, 7) << 11
+        | bits(inst, 30, 25) << 5
+        | bits(inst, 11, 8) << 1;
+    sign_extend(imm, 13)
+}
+
+fn c_j_offset(inst: u32) -> usize {
+    let imm = bits(inst, 12, 12) << 11
+        | bits(inst, 8, 8) << 10
+        | bits(inst, 10, 9) << 8
+        | bits(inst, 6, 6) << 7
+        | bits(inst, 7, 7) << 6
+        | bits(inst, 2, 2) << 5
+        | bits(inst, 11, 11) << 4
+        | bits(inst, 5, 3) << 1;
+    sign_extend(imm, 12)
+}
+
+fn c_b_offset(inst: u32) -> usize {
+    let imm = bits(inst, 12, 12) << 8
+        | bits(inst, 6, 5) << 6
+        | bits(inst, 2, 2) << 5
+        | bits(inst, 11, 10) << 3
+        | bits(inst, 4, 3) << 1;
+    sign_extend(imm, 9)
+}
+
+/// Returns whether `inst` is a floating-point instruction, which is illegal while the
+/// floating-point unit is off.
+pub fn is_fp_inst(inst: u32) -> bool {
+    if inst & 0b11 != 0b11 {
+        // Compressed loads and stores of floating-point registers.
+        let quadrant = inst & 0b11;
+        let funct3 = inst >> 13 & 0b111;
+        return matches!(quadrant, 0b00 | 0b10)
+            && match funct3 {
+                0b001 | 0b101 => true,
+                0b011 | 0b111 => cfg!(target_arch = "riscv32"),
+                _ => false,
+            };
+    }
+    match inst & 0x7f {
+        0x07 | 0x27 | 0x43 | 0x47 | 0x4b | 0x4f | 0x53 => true,
+        // Accesses to fflags, frm or fcsr.
+        0x73 => inst >> 12 & 0b111 != 0 && matches!(inst >> 20, 0x001..=0x003),
+        _ => false,
+    }
+}
+
+/// Runs `f` with interrupts on for `classes`, as far as they are enabled at all.
+pub(crate) fn with_int_classes<R>(
+    classes: impl Iterator<Item = IrqClass>,
+    f: impl FnOnce() -> R,
+) -> R {
+    let mask = classes.fold(0, |mask, class| {
+        mask | match class {
+            IrqClass::Timer => 1 << 5,
+            IrqClass::External => 1 << 9,
+            IrqClass::Software => 1 << 1,
+        }
+    });
+    let sie: usize;
+    unsafe { core::arch::asm!("csrr {}, sie", out(reg) sie) };
+    if sie & mask == 0 {
+        return f();
+    }
+
+    unsafe {
+        core::arch::asm!("csrw sie, {}", in(reg) sie & mask);
+        riscv::register::sstatus::set_sie();
+    }
+    let result = f();
+    unsafe {
+        riscv::register::sstatus::clear_sie();
+        core::arch::asm!("csrw sie, {}", in(reg) sie);
     }
+    result
 }
 
 pub(crate) fn init() {
@@ -188,17 +544,62 @@ pub(crate) fn init() {
         fn trap_entry();
     }
     unsafe {
+        // The kernel never touches floating-point registers, whose state user contexts own.
+        riscv::register::sstatus::set_fs(FS::Off);
         riscv::register::sscratch::write(0);
         riscv::register::stvec::write(trap_entry as usize, TrapMode::Direct);
     }
 }
 
 extern "C" fn handle_kern_trap(ctx: &mut Context) {
+    nest::trap(|| kern_trap(ctx));
+}
+
+fn kern_trap(ctx: &mut Context) {
     let reason = ctx.trap_reason();
+    stats::record(&reason);
+
+    // The trap entry found no room for the frame below the interrupted stack.
+    if stack_guard::on_emergency_stack(ctx as *const _ as usize) {
+        let addr = match reason {
+            TrapReason::PageFault { addr, .. } => addr,
+            _ => VirtAddr::new(ctx.regs.sp),
+        };
+        stack_guard::overflow(ctx, addr);
+    }
+
+    if handler::dispatch(ctx, reason) {
+        return;
+    }
+
     match reason {
         TrapReason::Breakpoint { addr: _ } => breakpoint::handle(ctx),
-        TrapReason::SoftwareInterrupt => soft_int::handle(ctx),
-        TrapReason::TimerInterrupt => timer_int::handle(ctx),
-        _ => unimplemented!("{:#x?}", ctx),
+        TrapReason::Watchpoint { .. } => watchpoint::handle(ctx),
+        TrapReason::IllegalInstruction { addr, inst } if is_fp_inst(inst) => {
+            panic!(
+                "floating-point instruction {:#010x} in kernel at {}",
+                inst, addr
+            )
+        }
+        TrapReason::PageFault { .. } if page_fault::handle(ctx) => {}
+        TrapReason::PageFault { addr, .. } if stack_guard::is_guard(addr) => {
+            stack_guard::overflow(ctx, addr)
+        }
+        TrapReason::IllegalInstruction { .. }
+        | TrapReason::MisalignedAccess { .. }
+        | TrapReason::AccessFault { .. } => fault::handle_kern(ctx),
+        TrapReason::ExternalInterrupt => {
+            external_int::handle(ctx);
+            crate::int_return();
+        }
+        TrapReason::SoftwareInterrupt => {
+            soft_int::handle(ctx);
+            crate::int_return();
+        }
+        TrapReason::TimerInterrupt => {
+            timer_int::handle(ctx);
+            crate::int_return();
+        }
+        reason => fatal::fatal_trap(ctx, reason),
     }
 }
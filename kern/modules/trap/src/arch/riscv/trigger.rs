@@ -0,0 +1,151 @@
+use jrinx_addr::VirtAddr;
+use jrinx_error::{InternalError, Result};
+use spin::{Mutex, Once};
+
+use crate::watchpoint::WatchKind;
+
+const BASE_EXTENSION_ID: usize = 0x10;
+const BASE_PROBE_EXTENSION: usize = 3;
+
+const DBTR_EXTENSION_ID: usize = 0x4442_5452;
+const DBTR_NUM_TRIGGERS: usize = 0;
+const DBTR_SET_SHMEM: usize = 1;
+const DBTR_INSTALL_TRIGGERS: usize = 3;
+const DBTR_UNINSTALL_TRIGGERS: usize = 5;
+const DBTR_ENABLE_TRIGGERS: usize = 6;
+const DBTR_DISABLE_TRIGGERS: usize = 7;
+
+/// Types of triggers matching data addresses, mcontrol6 preferred over the older mcontrol,
+/// which share the layout of the fields in use.
+const TRIGGER_TYPES: [usize; 2] = [6, 2];
+
+const TDATA1_MATCH_NAPOT: usize = 1 << 7;
+const TDATA1_S: usize = 1 << 4;
+const TDATA1_STORE: usize = 1 << 1;
+const TDATA1_LOAD: usize = 1 << 0;
+
+/// Message passed through the memory shared with the SBI, carrying a trigger to install and
+/// the index it is installed at on return.
+#[derive(Default)]
+#[repr(C, align(32))]
+struct TriggerMsg {
+    tstate_or_idx: usize,
+    tdata1: usize,
+    tdata2: usize,
+    tdata3: usize,
+}
+
+static TRIGGER_MSG: Mutex<TriggerMsg> = Mutex::new(TriggerMsg {
+    tstate_or_idx: 0,
+    tdata1: 0,
+    tdata2: 0,
+    tdata3: 0,
+});
+
+static TRIGGER_TYPE: Once<Option<(usize, usize)>> = Once::new();
+
+/// Returns the type of triggers to use along with how many of them there are, if the SBI
+/// offers any.
+fn trigger_type() -> Option<(usize, usize)> {
+    *TRIGGER_TYPE.call_once(|| {
+        let (_, available) = sbi_call(
+            BASE_EXTENSION_ID,
+            BASE_PROBE_EXTENSION,
+            DBTR_EXTENSION_ID,
+            0,
+            0,
+        );
+        if available == 0 {
+            return None;
+        }
+        TRIGGER_TYPES.into_iter().find_map(|ty| {
+            let (error, count) =
+                sbi_call(DBTR_EXTENSION_ID, DBTR_NUM_TRIGGERS, tdata1_type(ty), 0, 0);
+            (error == 0 && count != 0).then_some((ty, count))
+        })
+    })
+}
+
+/// Returns how many triggers can watch data accesses on each hart.
+pub(crate) fn trigger_capacity() -> usize {
+    trigger_type().map_or(0, |(_, count)| count)
+}
+
+/// Installs a trigger on the current hart firing on `kind` accesses in `addr .. addr + len`,
+/// which must be naturally aligned, returning its index.
+pub(crate) fn install_trigger(addr: usize, len: usize, kind: WatchKind) -> Result<usize> {
+    let (ty, _) = trigger_type().ok_or(InternalError::NotEnoughTriggers)?;
+    let access = match kind {
+        WatchKind::Read => TDATA1_LOAD,
+        WatchKind::Write => TDATA1_STORE,
+        WatchKind::Both => TDATA1_LOAD | TDATA1_STORE,
+    };
+    let (matching, tdata2) = match len {
+        1 => (0, addr),
+        len => (TDATA1_MATCH_NAPOT, addr | (len / 2 - 1)),
+    };
+
+    let mut msg = TRIGGER_MSG.lock();
+    *msg = TriggerMsg {
+        tdata1: tdata1_type(ty) | matching | TDATA1_S | access,
+        tdata2,
+        ..Default::default()
+    };
+
+    // Harts have memory shared with the SBI of their own, all set to the message here.
+    let shmem = VirtAddr::new(&*msg as *const _ as usize).to_phys();
+    let (error, _) = sbi_call(DBTR_EXTENSION_ID, DBTR_SET_SHMEM, shmem.as_usize(), 0, 0);
+    if error != 0 {
+        warn!(
+            "failed to set memory shared with sbi for triggers: {}",
+            error
+        );
+        return Err(InternalError::NotEnoughTriggers);
+    }
+
+    match sbi_call(DBTR_EXTENSION_ID, DBTR_INSTALL_TRIGGERS, 1, 0, 0) {
+        (0, _) => Ok(msg.tstate_or_idx),
+        _ => Err(InternalError::NotEnoughTriggers),
+    }
+}
+
+pub(crate) fn uninstall_trigger(idx: usize) {
+    let (error, _) = sbi_call(DBTR_EXTENSION_ID, DBTR_UNINSTALL_TRIGGERS, idx, 1, 0);
+    if error != 0 {
+        warn!("failed to uninstall trigger {}: {}", idx, error);
+    }
+}
+
+/// Enables the triggers of the current hart at the indexes set in `mask`.
+pub(crate) fn enable_triggers(mask: usize) {
+    if mask != 0 {
+        sbi_call(DBTR_EXTENSION_ID, DBTR_ENABLE_TRIGGERS, 0, mask, 0);
+    }
+}
+
+/// Disables the triggers of the current hart at the indexes set in `mask`.
+pub(crate) fn disable_triggers(mask: usize) {
+    if mask != 0 {
+        sbi_call(DBTR_EXTENSION_ID, DBTR_DISABLE_TRIGGERS, 0, mask, 0);
+    }
+}
+
+fn tdata1_type(ty: usize) -> usize {
+    ty << (usize::BITS - 4)
+}
+
+fn sbi_call(extension: usize, function: usize, a0: usize, a1: usize, a2: usize) -> (isize, usize) {
+    let (error, value);
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") a0 => error,
+            inlateout("a1") a1 => value,
+            in("a2") a2,
+            in("a6") function,
+            in("a7") extension,
+            options(nostack),
+        );
+    }
+    (error, value)
+}
@@ -1,22 +1,352 @@
-use spin::RwLock;
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt::Display;
 
-use crate::{GenericContext, TrapReason};
+use jrinx_addr::VirtAddr;
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::{hal, Cache, Cpu, Hal};
+use jrinx_serial_id_macro::SerialId;
+use jrinx_stats::StatKind;
+use spin::Mutex;
 
-static BREAKPOINT_COUNTER: RwLock<u64> = RwLock::new(0);
+use crate::{
+    arch::{Context, C_EBREAK, EBREAK},
+    gdb, GenericContext, TrapReason,
+};
 
-pub(crate) fn handle(ctx: &mut impl GenericContext) {
+/// Breakpoints patched into the kernel text.
+pub static MANAGER: Manager = Manager::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SerialId)]
+pub struct BreakpointId(u64);
+
+impl Display for BreakpointId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Runs on every hit of a breakpoint, before the instruction under it is stepped over.
+///
+/// Stepping over is skipped if the callback moves the pc of `ctx` away from the breakpoint.
+pub type BreakpointCallback = fn(id: BreakpointId, ctx: &mut Context);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakpointInfo {
+    pub id: BreakpointId,
+    pub addr: VirtAddr,
+    pub hits: u64,
+}
+
+struct Breakpoint {
+    id: BreakpointId,
+    orig: u32,
+    hits: u64,
+    callback: Option<BreakpointCallback>,
+}
+
+/// An instruction a hart is running with a temporary breakpoint patched at the instruction to
+/// run after, such as one under a breakpoint it has disarmed.
+struct Step {
+    addr: Option<usize>,
+    next: usize,
+    orig: u32,
+    int_enabled: bool,
+    done: Option<fn()>,
+}
+
+enum Hit {
+    Managed(BreakpointId, Option<BreakpointCallback>),
+    Retry,
+    Unmanaged,
+}
+
+struct ManagerInner {
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    steps: BTreeMap<usize, Step>,
+}
+
+/// Sets breakpoints by patching `ebreak`, or `c.ebreak` over compressed instructions, into the
+/// kernel text.
+///
+/// A hit breakpoint is stepped over by restoring the original instruction, with interrupts off
+/// and a temporary breakpoint at the instruction to run after it, and re-armed once the
+/// temporary one is hit. Other harts hitting it in the meantime run the original instruction
+/// without counting a hit.
+pub struct Manager {
+    inner: Mutex<ManagerInner>,
+}
+
+impl Manager {
+    const fn new() -> Self {
+        Self {
+            inner: Mutex::new(ManagerInner {
+                breakpoints: BTreeMap::new(),
+                steps: BTreeMap::new(),
+            }),
+        }
+    }
+
+    pub fn set(&self, addr: VirtAddr) -> Result<BreakpointId> {
+        self.insert(addr, None)
+    }
+
+    pub fn set_with_callback(
+        &self,
+        addr: VirtAddr,
+        callback: BreakpointCallback,
+    ) -> Result<BreakpointId> {
+        self.insert(addr, Some(callback))
+    }
+
+    pub fn clear(&self, id: BreakpointId) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let addr = inner
+            .breakpoints
+            .iter()
+            .find_map(|(&addr, bp)| (bp.id == id).then_some(addr))
+            .ok_or(InternalError::InvalidBreakpointId)?;
+        let bp = inner.breakpoints.remove(&addr).unwrap();
+
+        // Either the original instruction is already back, or a step still needs the patch.
+        if !inner.is_busy(addr) {
+            unsafe { write_inst(addr, bp.orig) };
+            hal!().cache().sync_all();
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<BreakpointInfo> {
+        self.inner
+            .lock()
+            .breakpoints
+            .iter()
+            .map(|(&addr, bp)| BreakpointInfo {
+                id: bp.id,
+                addr: VirtAddr::new(addr),
+                hits: bp.hits,
+            })
+            .collect()
+    }
+
+    fn insert(&self, addr: VirtAddr, callback: Option<BreakpointCallback>) -> Result<BreakpointId> {
+        let addr = addr.as_usize();
+        if addr % 2 != 0 || !(jrinx_layout::_stext()..jrinx_layout::_etext()).contains(&addr) {
+            return Err(InternalError::InvalidVirtAddr(VirtAddr::new(addr)));
+        }
+
+        let mut inner = self.inner.lock();
+        if inner.breakpoints.contains_key(&addr) {
+            return Err(InternalError::DuplicateBreakpoint);
+        }
+
+        let orig = inner.orig_inst(addr);
+        let id = BreakpointId::new();
+        inner.breakpoints.insert(
+            addr,
+            Breakpoint {
+                id,
+                orig,
+                hits: 0,
+                callback,
+            },
+        );
+        unsafe { write_inst(addr, ebreak_for(orig)) };
+        hal!().cache().sync_all();
+        Ok(id)
+    }
+
+    fn hit(&self, ctx: &mut Context, addr: usize) -> Hit {
+        let mut inner = self.inner.lock();
+        let stepped = inner.finish_step(ctx, addr);
+        let busy = inner.is_busy(addr);
+        match inner.breakpoints.get_mut(&addr) {
+            Some(bp) if !busy => {
+                bp.hits += 1;
+                Hit::Managed(bp.id, bp.callback)
+            }
+            Some(_) => Hit::Retry,
+            None if stepped || busy => Hit::Retry,
+            None => Hit::Unmanaged,
+        }
+    }
+
+    fn step_over(&self, ctx: &mut Context, addr: usize) {
+        let mut inner = self.inner.lock();
+        if ctx.pc() != addr || inner.is_busy(addr) {
+            return;
+        }
+        let Some(orig) = inner.breakpoints.get(&addr).map(|bp| bp.orig) else {
+            return;
+        };
+
+        unsafe { write_inst(addr, orig) };
+        if !inner.start_step(ctx, Some(addr), None) {
+            // Spinning on itself, the instruction is about to hit the breakpoint again anyway.
+            unsafe { write_inst(addr, ebreak_for(orig)) };
+            hal!().cache().sync_all();
+        }
+    }
+
+    /// Lets the current hart run the instruction at the pc of `ctx` with interrupts off,
+    /// calling `done` right after, unless the instruction jumps to itself.
+    pub(crate) fn step(&self, ctx: &mut Context, done: fn()) -> bool {
+        self.inner.lock().start_step(ctx, None, Some(done))
+    }
+}
+
+impl ManagerInner {
+    /// Returns whether the instruction at `addr` is disarmed or temporarily patched by a step.
+    fn is_busy(&self, addr: usize) -> bool {
+        self.steps
+            .values()
+            .any(|step| step.addr == Some(addr) || step.next == addr)
+    }
+
+    fn start_step(&mut self, ctx: &mut Context, addr: Option<usize>, done: Option<fn()>) -> bool {
+        let next = ctx.next_pc().unwrap();
+        if next == ctx.pc() {
+            return false;
+        }
+
+        let orig = self.orig_inst(next);
+        unsafe { write_inst(next, ebreak_for(orig)) };
+        hal!().cache().sync_all();
+
+        let int_enabled = ctx.int_enabled();
+        ctx.disable_int();
+        self.steps.insert(
+            hal!().cpu().id(),
+            Step {
+                addr,
+                next,
+                orig,
+                int_enabled,
+                done,
+            },
+        );
+        true
+    }
+
+    fn orig_inst(&self, addr: usize) -> u32 {
+        self.breakpoints
+            .get(&addr)
+            .map(|bp| bp.orig)
+            .or_else(|| {
+                self.steps
+                    .values()
+                    .find_map(|step| (step.next == addr).then_some(step.orig))
+            })
+            .unwrap_or_else(|| unsafe { read_inst(addr) })
+    }
+
+    /// Completes the step of the current hart if `addr` is where it stops, returning whether
+    /// it did.
+    fn finish_step(&mut self, ctx: &mut Context, addr: usize) -> bool {
+        let cpu_id = hal!().cpu().id();
+        if !self
+            .steps
+            .get(&cpu_id)
+            .is_some_and(|step| step.next == addr)
+        {
+            return false;
+        }
+        let step = self.steps.remove(&cpu_id).unwrap();
+
+        // Leave the patch to other steps stopping there, or to a breakpoint armed there.
+        let patched = self.steps.values().any(|other| other.next == step.next)
+            || self.breakpoints.contains_key(&step.next)
+                && self
+                    .steps
+                    .values()
+                    .all(|other| other.addr != Some(step.next));
+        if !patched {
+            unsafe { write_inst(step.next, step.orig) };
+        }
+        if let Some(addr) = step.addr {
+            if let Some(bp) = self.breakpoints.get(&addr) {
+                unsafe { write_inst(addr, ebreak_for(bp.orig)) };
+            }
+        }
+        hal!().cache().sync_all();
+        if let Some(done) = step.done {
+            done();
+        }
+
+        if step.int_enabled {
+            ctx.enable_int();
+        }
+        true
+    }
+}
+
+pub(crate) fn handle(ctx: &mut Context) {
     let TrapReason::Breakpoint { addr } = ctx.trap_reason() else {
         panic!("not a breakpoint trap");
     };
 
-    debug!("breakpoint at {}\n{:#x?}", addr, ctx);
+    match MANAGER.hit(ctx, addr.as_usize()) {
+        Hit::Managed(id, callback) => {
+            debug!("breakpoint {} hit at {}", id, addr);
+            jrinx_stats::record(StatKind::Breakpoint);
+            if let Some(callback) = callback {
+                callback(id, ctx);
+            }
+            MANAGER.step_over(ctx, addr.as_usize());
+        }
+        Hit::Retry => {
+            if gdb::take_stepping() {
+                gdb::stop(ctx);
+            }
+        }
+        Hit::Unmanaged if gdb::is_active() => {
+            jrinx_stats::record(StatKind::Breakpoint);
+            ctx.pc_advance();
+            gdb::stop(ctx);
+        }
+        Hit::Unmanaged => {
+            debug!("breakpoint at {}\n{:#x?}", addr, ctx);
+            jrinx_stats::record(StatKind::Breakpoint);
+            ctx.pc_advance();
+        }
+    }
+}
+
+pub fn count() -> u64 {
+    jrinx_stats::snapshot().total(StatKind::Breakpoint)
+}
+
+fn inst_len(inst: u32) -> usize {
+    if inst & 0b11 == 0b11 {
+        4
+    } else {
+        2
+    }
+}
 
-    let mut counter = BREAKPOINT_COUNTER.write();
-    *counter += 1;
+fn ebreak_for(inst: u32) -> u32 {
+    match inst_len(inst) {
+        2 => C_EBREAK,
+        _ => EBREAK,
+    }
+}
 
-    ctx.pc_advance();
+unsafe fn read_inst(addr: usize) -> u32 {
+    let ptr = addr as *const u16;
+    let low = ptr.read_volatile() as u32;
+    match inst_len(low) {
+        2 => low,
+        _ => (ptr.add(1).read_volatile() as u32) << 16 | low,
+    }
 }
 
-pub fn count() -> u64 {
-    *BREAKPOINT_COUNTER.read()
+unsafe fn write_inst(addr: usize, inst: u32) {
+    match inst_len(inst) {
+        2 => (addr as *mut u16).write_volatile(inst as u16),
+        _ if addr % 4 == 0 => (addr as *mut u32).write_volatile(inst),
+        _ => {
+            let ptr = addr as *mut u16;
+            ptr.add(1).write_volatile((inst >> 16) as u16);
+            ptr.write_volatile(inst as u16);
+        }
+    }
 }
@@ -0,0 +1,74 @@
+//! External interrupts, claimed from the interrupt controller and passed to the handler
+//! registered for their source.
+
+use alloc::collections::BTreeMap;
+
+use jrinx_hal::{hal, Hal, Interrupt};
+use spin::{Once, RwLock};
+
+use crate::{
+    latency::{self, IrqSource},
+    nest::{self, IrqClass},
+    GenericContext, TrapReason,
+};
+
+/// Handles an interrupt of the source it is registered for, once claimed.
+pub type IrqHandler = fn(irq: u32);
+
+/// Controller routing the interrupts of devices to the CPUs, such as the PLIC.
+pub trait IrqChip: Send + Sync {
+    /// Claims the highest priority interrupt pending for the current CPU, if any.
+    fn claim(&self) -> Option<u32>;
+
+    /// Completes the handling of `irq`, which lets the source interrupt again.
+    fn complete(&self, irq: u32);
+
+    /// Lets `irq` interrupt the CPU which registered it.
+    fn enable(&self, irq: u32);
+}
+
+static CHIP: Once<&'static dyn IrqChip> = Once::new();
+
+static HANDLERS: RwLock<BTreeMap<u32, IrqHandler>> = RwLock::new(BTreeMap::new());
+
+/// Sets the interrupt controller, enabling the sources registered so far.
+pub fn set_chip(chip: &'static dyn IrqChip) {
+    let chip = *CHIP.call_once(|| chip);
+    for &irq in HANDLERS.read().keys() {
+        chip.enable(irq);
+    }
+}
+
+/// Registers `handler` for the interrupts of `irq`, enabling it once the interrupt controller
+/// is set.
+pub fn register(irq: u32, handler: IrqHandler) {
+    // Handlers are looked up in the trap path, which must not find the lock held on its CPU.
+    hal!()
+        .interrupt()
+        .with_saved_off(|| HANDLERS.write().insert(irq, handler));
+    if let Some(chip) = CHIP.get() {
+        chip.enable(irq);
+    }
+}
+
+pub(crate) fn handle(ctx: &mut impl GenericContext) {
+    let TrapReason::ExternalInterrupt = ctx.trap_reason() else {
+        panic!("not an external interrupt");
+    };
+    let asserted = latency::asserted(IrqSource::ExternalInterrupt);
+
+    let Some(chip) = CHIP.get() else {
+        warn!("external interrupt without an interrupt controller");
+        return;
+    };
+    while let Some(irq) = chip.claim() {
+        let handler = HANDLERS.read().get(&irq).copied();
+        nest::handle(IrqClass::External, || match handler {
+            Some(handler) => handler(irq),
+            None => warn!("external interrupt {} has no handler", irq),
+        });
+        chip.complete(irq);
+    }
+
+    latency::complete(IrqSource::ExternalInterrupt, asserted);
+}
@@ -0,0 +1,66 @@
+//! Dumps of traps no handler resolves, taken before the kernel panics on them.
+//!
+//! Nothing here allocates nor waits for a lock, since the trap may have hit the allocator or a
+//! holder of the locks read.
+
+use core::fmt::{self, Display, Write};
+
+use jrinx_addr::VirtAddr;
+use jrinx_hal::{hal, Cpu, Hal};
+use spin::Once;
+
+use crate::{GenericContext, TrapReason};
+
+/// Writes what the current CPU runs, set by [`register`].
+static DESCRIBE: Once<fn(w: &mut dyn Write) -> fmt::Result> = Once::new();
+
+/// Registers `describe` to write what the current CPU runs, such as its task, into dumps of
+/// fatal traps. It must neither allocate nor block.
+pub fn register(describe: fn(w: &mut dyn Write) -> fmt::Result) {
+    DESCRIBE.call_once(|| describe);
+}
+
+/// What the current CPU runs, as written by the registered hook.
+struct Running;
+
+impl Display for Running {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match DESCRIBE.get() {
+            Some(describe) => describe(f),
+            None => f.write_str("<unknown>"),
+        }
+    }
+}
+
+/// Mapping of an address in the kernel page table.
+struct Mapping(VirtAddr);
+
+impl Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(page_table) = jrinx_vmm::KERN_PAGE_TABLE.try_read() else {
+            return f.write_str("unknown, page table busy");
+        };
+        match page_table.probe(self.0) {
+            Some((phys_addr, perm)) => write!(f, "mapped to {} {:?}", phys_addr, perm),
+            None => f.write_str("not mapped"),
+        }
+    }
+}
+
+/// Dumps a trap of kernel code no handler resolves, with the registers of `ctx`, what the
+/// CPU runs and the backtrace of the trapped code, then panics.
+pub fn fatal_trap(ctx: &impl GenericContext, reason: TrapReason) -> ! {
+    error!("fatal trap {:?} on cpu#{}", reason, hal!().cpu().id());
+    error!("running {}", Running);
+    ctx.log_regs();
+    if let TrapReason::PageFault { addr, .. }
+    | TrapReason::MisalignedAccess { addr, .. }
+    | TrapReason::AccessFault { addr, .. }
+    | TrapReason::Watchpoint { addr, .. } = reason
+    {
+        error!("address {}: {}", addr, Mapping(addr));
+    }
+    jrinx_backtrace::log_trapped(ctx.pc(), ctx.fp());
+
+    panic!("fatal trap {:?} at {:#x}", reason, ctx.pc())
+}
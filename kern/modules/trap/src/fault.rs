@@ -0,0 +1,66 @@
+use jrinx_stats::StatKind;
+use spin::Once;
+
+use crate::{fatal, GenericContext, TrapReason};
+
+/// Code run in place of kernel code faulting beyond repair, set by [`set_abort_hook`].
+struct AbortHook {
+    can_abort: fn() -> bool,
+    abort: fn() -> !,
+}
+
+static ABORT_HOOK: Once<AbortHook> = Once::new();
+
+/// Registers `abort` to run in place of kernel code hitting an illegal instruction, a
+/// misaligned access or an access fault, if `can_abort` tells that code may be given up on,
+/// such as a task. Faults of other kernel code panic.
+pub fn set_abort_hook(can_abort: fn() -> bool, abort: fn() -> !) {
+    ABORT_HOOK.call_once(|| AbortHook { can_abort, abort });
+}
+
+/// Logs a fault no handler resolves, along with the faulting instruction if it can be read.
+pub fn report(ctx: &impl GenericContext) {
+    let pc = ctx.pc();
+    match ctx.trap_reason() {
+        TrapReason::IllegalInstruction { addr, inst } => {
+            error!("illegal instruction {:#010x} at {}", inst, addr);
+        }
+        TrapReason::MisalignedAccess { addr, kind } => match ctx.inst() {
+            Some(inst) => error!(
+                "misaligned {} of {} by instruction {:#010x} at {:#x}",
+                kind, addr, inst, pc
+            ),
+            None => error!("misaligned {} of {} at {:#x}", kind, addr, pc),
+        },
+        TrapReason::AccessFault { addr, kind } => match ctx.inst() {
+            Some(inst) => error!(
+                "access fault on {} of {} by instruction {:#010x} at {:#x}",
+                kind, addr, inst, pc
+            ),
+            None => error!("access fault on {} of {} at {:#x}", kind, addr, pc),
+        },
+        reason => error!("unresolved {:?} at {:#x}", reason, pc),
+    }
+}
+
+/// Reports a fault trapped from kernel mode, then makes the trap return into the abort hook,
+/// or dumps the trap and panics if the faulting code cannot be aborted.
+pub(crate) fn handle_kern(ctx: &mut impl GenericContext) {
+    jrinx_stats::record(StatKind::Fault);
+    report(ctx);
+
+    match ABORT_HOOK.get() {
+        Some(hook) if (hook.can_abort)() => ctx.set_pc(abort as usize),
+        _ => fatal::fatal_trap(ctx, ctx.trap_reason()),
+    }
+}
+
+/// Runs the abort hook once the trap returns, on the stack of the faulting code and with its
+/// interrupt state.
+extern "C" fn abort() -> ! {
+    (ABORT_HOOK.get().unwrap().abort)()
+}
+
+pub fn count() -> u64 {
+    jrinx_stats::snapshot().total(StatKind::Fault)
+}
@@ -0,0 +1,18 @@
+use crate::{arch, GenericContext, TrapReason};
+
+/// Lets a user context trapped on its first floating-point instruction own a floating-point
+/// state, returning whether the instruction can be retried.
+///
+/// Contexts start with the floating-point unit off, so that only those using it have their
+/// floating-point registers saved and restored across traps.
+pub fn handle_first_use(ctx: &mut impl GenericContext) -> bool {
+    match ctx.trap_reason() {
+        TrapReason::IllegalInstruction { inst, .. }
+            if ctx.fp_state().is_none() && arch::is_fp_inst(inst) =>
+        {
+            ctx.attach_fp_state();
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,355 @@
+//! Stub of the GDB remote serial protocol, debugging the kernel over the console.
+//!
+//! Breakpoints and single steps are software ones, backed by [`MANAGER`]. One hart is served
+//! at a time.
+
+pub mod packet;
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use jrinx_addr::VirtAddr;
+use jrinx_config::PAGE_SIZE;
+use jrinx_hal::{hal, Cache, Earlycon, Hal};
+use jrinx_paging::{GenericPagePerm, PagePerm};
+use jrinx_percpu::percpu;
+use spin::Mutex;
+
+use self::packet::{Decoder, Event, PACKET_SIZE};
+use crate::{
+    arch::Context,
+    breakpoint::{BreakpointId, MANAGER},
+    GenericContext,
+};
+
+/// Signal reported for every stop.
+const SIGTRAP: u8 = 5;
+
+const E_INVALID: &[u8] = b"E16";
+const E_FAULT: &[u8] = b"E0e";
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+static STUB: Mutex<Stub> = Mutex::new(Stub::new());
+
+#[percpu]
+static STEPPING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Reply(Vec<u8>),
+    Continue,
+    Step,
+    Detach(Option<Vec<u8>>),
+}
+
+#[derive(Debug)]
+pub struct Stub {
+    breakpoints: BTreeMap<usize, BreakpointId>,
+    /// Whether the debugger waits for a stop reply.
+    running: bool,
+}
+
+impl Default for Stub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stub {
+    pub const fn new() -> Self {
+        Self {
+            breakpoints: BTreeMap::new(),
+            running: false,
+        }
+    }
+
+    /// Serves a packet, answering those not supported with an empty reply.
+    pub fn respond(&mut self, ctx: &mut Context, packet: &[u8]) -> Response {
+        let (&kind, args) = match packet.split_first() {
+            Some(split) => split,
+            None => return Response::Reply(Vec::new()),
+        };
+        let reply = match kind {
+            b'?' => stop_reply(),
+            b'g' => {
+                let mut reply = Vec::new();
+                for num in 0..=Context::GPR_COUNT {
+                    packet::push_hex(&mut reply, &reg(ctx, num).to_le_bytes());
+                }
+                reply
+            }
+            b'G' => match packet::parse_hex_bytes(args) {
+                Some(bytes) if bytes.len() == (Context::GPR_COUNT + 1) * REG_SIZE => {
+                    for (num, value) in bytes.chunks(REG_SIZE).enumerate() {
+                        set_reg(ctx, num, usize::from_le_bytes(value.try_into().unwrap()));
+                    }
+                    b"OK".to_vec()
+                }
+                _ => E_INVALID.to_vec(),
+            },
+            b'p' => match packet::parse_hex(args) {
+                Some(num) if num <= Context::GPR_COUNT => {
+                    let mut reply = Vec::new();
+                    packet::push_hex(&mut reply, &reg(ctx, num).to_le_bytes());
+                    reply
+                }
+                // Registers beyond the pc, such as floating-point ones, are unavailable.
+                Some(_) => [b'x'; REG_SIZE * 2].to_vec(),
+                None => E_INVALID.to_vec(),
+            },
+            b'P' => {
+                let parsed = split(args, b'=').and_then(|(num, value)| {
+                    let num = packet::parse_hex(num).filter(|&num| num <= Context::GPR_COUNT)?;
+                    let value = packet::parse_hex_bytes(value)?;
+                    Some((num, usize::from_le_bytes(value.try_into().ok()?)))
+                });
+                match parsed {
+                    Some((num, value)) => {
+                        set_reg(ctx, num, value);
+                        b"OK".to_vec()
+                    }
+                    None => E_INVALID.to_vec(),
+                }
+            }
+            b'm' => match parse_addr_len(args) {
+                Some((addr, len)) if len <= PACKET_SIZE / 2 => {
+                    if accessible(addr, len, PagePerm::R) {
+                        let mut reply = Vec::with_capacity(len * 2);
+                        for addr in addr..addr + len {
+                            let byte = unsafe { (addr as *const u8).read_volatile() };
+                            packet::push_hex(&mut reply, &[byte]);
+                        }
+                        reply
+                    } else {
+                        E_FAULT.to_vec()
+                    }
+                }
+                _ => E_INVALID.to_vec(),
+            },
+            b'M' => {
+                let parsed = split(args, b':').and_then(|(addr_len, data)| {
+                    let (addr, len) = parse_addr_len(addr_len)?;
+                    let data = packet::parse_hex_bytes(data).filter(|data| data.len() == len)?;
+                    Some((addr, data))
+                });
+                match parsed {
+                    Some((addr, data)) if accessible(addr, data.len(), PagePerm::W) => {
+                        for (addr, &byte) in (addr..).zip(&data) {
+                            unsafe { (addr as *mut u8).write_volatile(byte) };
+                        }
+                        // The debugger may patch the kernel text.
+                        hal!().cache().sync_all();
+                        b"OK".to_vec()
+                    }
+                    Some(_) => E_FAULT.to_vec(),
+                    None => E_INVALID.to_vec(),
+                }
+            }
+            b'c' | b's' => {
+                if !args.is_empty() {
+                    match packet::parse_hex(args) {
+                        Some(addr) => ctx.set_pc(addr),
+                        None => return Response::Reply(E_INVALID.to_vec()),
+                    }
+                }
+                return match kind {
+                    b'c' => Response::Continue,
+                    _ => Response::Step,
+                };
+            }
+            b'Z' | b'z' => match args.strip_prefix(b"0,").and_then(parse_addr_len) {
+                Some((addr, _)) if kind == b'Z' => self.insert(addr),
+                Some((addr, _)) => self.remove(addr),
+                // Only software breakpoints are supported.
+                None => Vec::new(),
+            },
+            b'D' => {
+                self.detach();
+                return Response::Detach(Some(b"OK".to_vec()));
+            }
+            b'k' => {
+                self.detach();
+                return Response::Detach(None);
+            }
+            b'H' => b"OK".to_vec(),
+            b'q' if args.starts_with(b"Supported") => {
+                let mut reply = b"PacketSize=".to_vec();
+                packet::push_hex(&mut reply, &(PACKET_SIZE as u16).to_be_bytes());
+                reply
+            }
+            b'q' if args == b"Attached" => b"1".to_vec(),
+            _ => Vec::new(),
+        };
+        Response::Reply(reply)
+    }
+
+    fn insert(&mut self, addr: usize) -> Vec<u8> {
+        if self.breakpoints.contains_key(&addr) {
+            return b"OK".to_vec();
+        }
+        match MANAGER.set_with_callback(VirtAddr::new(addr), on_breakpoint) {
+            Ok(id) => {
+                self.breakpoints.insert(addr, id);
+                b"OK".to_vec()
+            }
+            Err(_) => E_INVALID.to_vec(),
+        }
+    }
+
+    fn remove(&mut self, addr: usize) -> Vec<u8> {
+        match self.breakpoints.remove(&addr) {
+            Some(id) if MANAGER.clear(id).is_ok() => b"OK".to_vec(),
+            _ => E_INVALID.to_vec(),
+        }
+    }
+
+    fn detach(&mut self) {
+        for (_, id) in core::mem::take(&mut self.breakpoints) {
+            let _ = MANAGER.clear(id);
+        }
+        self.running = false;
+        ACTIVE.store(false, Ordering::SeqCst);
+    }
+}
+
+const REG_SIZE: usize = core::mem::size_of::<usize>();
+
+/// Enables the stub, so that breakpoint traps stop into it.
+pub fn enable() {
+    ACTIVE.store(true, Ordering::SeqCst);
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Serves the debugger on the current hart until it resumes.
+pub(crate) fn stop(ctx: &mut Context) {
+    STEPPING.as_ref().store(false, Ordering::SeqCst);
+    let mut stub = STUB.lock();
+    if !is_active() {
+        return;
+    }
+    let mut decoder = Decoder::new();
+    let mut sent = Vec::new();
+    if core::mem::take(&mut stub.running) {
+        sent = packet::encode(&stop_reply());
+        hal!().earlycon().write(&sent);
+    }
+
+    loop {
+        let byte = loop {
+            if let Some(byte) = hal!().earlycon().getc() {
+                break byte;
+            }
+            core::hint::spin_loop();
+        };
+        let reply = match decoder.feed(byte) {
+            Some(Event::Packet(data)) => {
+                hal!().earlycon().putc(b'+');
+                match stub.respond(ctx, &data) {
+                    Response::Reply(reply) => reply,
+                    Response::Continue => {
+                        stub.running = true;
+                        return;
+                    }
+                    Response::Step if step(ctx) => {
+                        stub.running = true;
+                        return;
+                    }
+                    // Spinning on itself, the instruction stops where it is.
+                    Response::Step => stop_reply(),
+                    Response::Detach(reply) => {
+                        if let Some(reply) = reply {
+                            hal!().earlycon().write(&packet::encode(&reply));
+                        }
+                        return;
+                    }
+                }
+            }
+            Some(Event::BadPacket) => {
+                hal!().earlycon().putc(b'-');
+                continue;
+            }
+            Some(Event::Nack) => {
+                hal!().earlycon().write(&sent);
+                continue;
+            }
+            Some(Event::Ack) | Some(Event::Interrupt) | None => continue,
+        };
+        sent = packet::encode(&reply);
+        hal!().earlycon().write(&sent);
+    }
+}
+
+pub(crate) fn take_stepping() -> bool {
+    STEPPING.as_ref().swap(false, Ordering::SeqCst)
+}
+
+fn step(ctx: &mut Context) -> bool {
+    STEPPING.as_ref().store(true, Ordering::SeqCst);
+    // A breakpoint armed there is stepped over once its callback returns.
+    let armed = MANAGER
+        .list()
+        .iter()
+        .any(|bp| bp.addr.as_usize() == ctx.pc());
+    if armed || MANAGER.step(ctx, || {}) {
+        return true;
+    }
+    STEPPING.as_ref().store(false, Ordering::SeqCst);
+    false
+}
+
+fn on_breakpoint(_: BreakpointId, ctx: &mut Context) {
+    stop(ctx);
+}
+
+fn stop_reply() -> Vec<u8> {
+    let mut reply = b"S".to_vec();
+    packet::push_hex(&mut reply, &[SIGTRAP]);
+    reply
+}
+
+/// Registers are numbered as by gdb: the general-purpose ones, then the pc.
+fn reg(ctx: &Context, num: usize) -> usize {
+    if num == Context::GPR_COUNT {
+        ctx.pc()
+    } else {
+        ctx.gpr(num)
+    }
+}
+
+fn set_reg(ctx: &mut Context, num: usize, value: usize) {
+    if num == Context::GPR_COUNT {
+        ctx.set_pc(value);
+    } else {
+        ctx.set_gpr(num, value);
+    }
+}
+
+fn split(args: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let at = args.iter().position(|&byte| byte == separator)?;
+    Some((&args[..at], &args[at + 1..]))
+}
+
+fn parse_addr_len(args: &[u8]) -> Option<(usize, usize)> {
+    let (addr, len) = split(args, b',')?;
+    Some((packet::parse_hex(addr)?, packet::parse_hex(len)?))
+}
+
+fn accessible(addr: usize, len: usize, perm: PagePerm) -> bool {
+    let Some(end) = addr.checked_add(len) else {
+        return false;
+    };
+    let Some(page_table) = jrinx_vmm::KERN_PAGE_TABLE.try_read() else {
+        return false;
+    };
+    (addr & !(PAGE_SIZE - 1)..end)
+        .step_by(PAGE_SIZE)
+        .all(|page| {
+            page_table
+                .probe(VirtAddr::new(page))
+                .is_some_and(|(_, page_perm)| page_perm.contains(perm))
+        })
+}
@@ -0,0 +1,160 @@
+//! Framing of the GDB remote serial protocol, as `$data#cs` acknowledged by `+` or `-`.
+
+use alloc::vec::Vec;
+
+/// Longest packet data taken, as told by `qSupported`.
+pub const PACKET_SIZE: usize = 0x1000;
+
+pub const INTERRUPT: u8 = 0x03;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Unescaped data of a packet to acknowledge by `+`.
+    Packet(Vec<u8>),
+    /// Packet to refuse by `-`.
+    BadPacket,
+    Ack,
+    Nack,
+    Interrupt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Data,
+    Escape,
+    Checksum,
+    ChecksumLow(u8),
+}
+
+#[derive(Debug)]
+pub struct Decoder {
+    state: State,
+    data: Vec<u8>,
+    sum: u8,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Idle,
+            data: Vec::new(),
+            sum: 0,
+        }
+    }
+
+    /// Takes the next byte received, returning what it completes, if anything.
+    pub fn feed(&mut self, byte: u8) -> Option<Event> {
+        match (self.state, byte) {
+            (State::Idle, b'$') | (State::Data, b'$') => {
+                self.data.clear();
+                self.sum = 0;
+                self.state = State::Data;
+                None
+            }
+            (State::Idle, b'+') => Some(Event::Ack),
+            (State::Idle, b'-') => Some(Event::Nack),
+            (State::Idle, INTERRUPT) => Some(Event::Interrupt),
+            (State::Idle, _) => None,
+            (State::Data, b'#') => {
+                self.state = State::Checksum;
+                None
+            }
+            (State::Data, b'}') => {
+                self.sum = self.sum.wrapping_add(byte);
+                self.state = State::Escape;
+                None
+            }
+            (State::Data, _) | (State::Escape, _) => {
+                self.sum = self.sum.wrapping_add(byte);
+                let byte = match self.state {
+                    State::Escape => byte ^ 0x20,
+                    _ => byte,
+                };
+                self.state = State::Data;
+                if self.data.len() <= PACKET_SIZE {
+                    self.data.push(byte);
+                }
+                None
+            }
+            (State::Checksum, _) => match hex_digit(byte) {
+                Some(high) => {
+                    self.state = State::ChecksumLow(high);
+                    None
+                }
+                None => {
+                    self.state = State::Idle;
+                    Some(Event::BadPacket)
+                }
+            },
+            (State::ChecksumLow(high), _) => {
+                self.state = State::Idle;
+                match hex_digit(byte) {
+                    Some(low) if high << 4 | low == self.sum && self.data.len() <= PACKET_SIZE => {
+                        Some(Event::Packet(core::mem::take(&mut self.data)))
+                    }
+                    _ => Some(Event::BadPacket),
+                }
+            }
+        }
+    }
+}
+
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(data.len() + 4);
+    packet.push(b'$');
+    for &byte in data {
+        match byte {
+            b'$' | b'#' | b'}' | b'*' => packet.extend_from_slice(&[b'}', byte ^ 0x20]),
+            _ => packet.push(byte),
+        }
+    }
+    let sum = checksum(&packet[1..]);
+    packet.push(b'#');
+    push_hex(&mut packet, &[sum]);
+    packet
+}
+
+pub fn push_hex(out: &mut Vec<u8>, bytes: &[u8]) {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    for &byte in bytes {
+        out.extend_from_slice(&[DIGITS[byte as usize >> 4], DIGITS[byte as usize & 0xf]]);
+    }
+}
+
+pub fn parse_hex_bytes(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| Some(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
+        .collect()
+}
+
+pub fn parse_hex(hex: &[u8]) -> Option<usize> {
+    if hex.is_empty() || hex.len() > usize::BITS as usize / 4 {
+        return None;
+    }
+    hex.iter().try_fold(0, |value, &digit| {
+        Some(value << 4 | hex_digit(digit)? as usize)
+    })
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
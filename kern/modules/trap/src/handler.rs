@@ -0,0 +1,99 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{
+    fmt::Display,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use jrinx_error::{InternalError, Result};
+use jrinx_serial_id_macro::SerialId;
+use jrinx_sync::IrqSafeMutex;
+
+use crate::{arch::Context, TrapKind, TrapReason};
+
+/// Handles a trap of the kind it is registered for, returning whether it did, in which case
+/// the trapped context resumes without any other handling.
+pub type TrapHandler = fn(ctx: &mut Context, reason: TrapReason) -> bool;
+
+/// Handlers of each kind, whose list is replaced as a whole on every change, so that a dispatch
+/// walks the snapshot it took even if handlers come and go meanwhile.
+type Handlers = BTreeMap<TrapKind, Arc<[(TrapHandlerId, TrapHandler)]>>;
+
+static HANDLERS: IrqSafeMutex<Handlers> = IrqSafeMutex::new("trap-handlers", BTreeMap::new());
+
+/// Kinds with handlers, by bit of their discriminant, whose traps are the only ones looking
+/// the registry up.
+static REGISTERED: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SerialId)]
+pub struct TrapHandlerId(u64);
+
+impl Display for TrapHandlerId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Registers `handler` for the traps of `kind`, trapped from either mode.
+///
+/// Registered handlers are tried before the handling built into the trap entry, those
+/// registered last first.
+pub fn register(kind: impl Into<TrapKind>, handler: TrapHandler) -> TrapHandlerId {
+    let id = TrapHandlerId::new();
+    let kind = kind.into();
+    let mut handlers = HANDLERS.lock();
+    let mut list = handlers
+        .get(&kind)
+        .map_or_else(Vec::new, |list| list.to_vec());
+    list.push((id, handler));
+    handlers.insert(kind, list.into());
+    REGISTERED.fetch_or(kind_bit(kind), Ordering::SeqCst);
+    id
+}
+
+pub fn unregister(id: TrapHandlerId) -> Result<()> {
+    let mut handlers = HANDLERS.lock();
+    let (&kind, list) = handlers
+        .iter()
+        .find(|(_, list)| list.iter().any(|&(handler_id, _)| handler_id == id))
+        .ok_or(InternalError::InvalidTrapHandlerId)?;
+    let list = list
+        .iter()
+        .copied()
+        .filter(|&(handler_id, _)| handler_id != id)
+        .collect::<Vec<_>>();
+    if list.is_empty() {
+        handlers.remove(&kind);
+        REGISTERED.fetch_and(!kind_bit(kind), Ordering::SeqCst);
+    } else {
+        handlers.insert(kind, list.into());
+    }
+    Ok(())
+}
+
+/// Lets the handlers registered for the kind of `reason` handle the trap, returning whether
+/// one did, after which interrupts take the return path of handled interrupts.
+///
+/// The handlers are those registered when the trap is taken. The registry is not locked while
+/// they run, so that they can register handlers themselves.
+pub fn dispatch(ctx: &mut Context, reason: TrapReason) -> bool {
+    let kind = TrapKind::from(reason);
+    if REGISTERED.load(Ordering::SeqCst) & kind_bit(kind) == 0 {
+        return false;
+    }
+    let Some(list) = HANDLERS.lock().get(&kind).cloned() else {
+        return false;
+    };
+    for &(_, handler) in list.iter().rev() {
+        if handler(ctx, reason) {
+            if kind.is_interrupt() {
+                crate::int_return();
+            }
+            return true;
+        }
+    }
+    false
+}
+
+fn kind_bit(kind: TrapKind) -> u32 {
+    1 << kind as u32
+}
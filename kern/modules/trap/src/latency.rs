@@ -0,0 +1,153 @@
+use core::{
+    fmt::Display,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use jrinx_hal::{hal, Cpu, Hal, Instant};
+use jrinx_percpu::percpu;
+use jrinx_sync::IrqSafeMutex;
+use jrinx_timed_event::jitter::JitterHistogram;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IrqSource {
+    TimerInterrupt,
+    SoftwareInterrupt,
+    ExternalInterrupt,
+}
+
+impl IrqSource {
+    pub const ALL: [Self; 3] = [
+        Self::TimerInterrupt,
+        Self::SoftwareInterrupt,
+        Self::ExternalInterrupt,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TimerInterrupt => "timer-interrupt",
+            Self::SoftwareInterrupt => "software-interrupt",
+            Self::ExternalInterrupt => "external-interrupt",
+        }
+    }
+}
+
+#[percpu]
+static IRQ_LATENCY: IrqSafeMutex<[IrqLatency; IrqSource::ALL.len()]> =
+    IrqSafeMutex::new("irq-latency", [IrqLatency::new(); IrqSource::ALL.len()]);
+
+/// Time, in nanoseconds, of the earliest software interrupt raised on the CPU and not taken yet.
+#[percpu]
+static SOFT_RAISED: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Latency of an interrupt source, from its assertion to handler completion.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IrqLatency {
+    histogram: JitterHistogram,
+    max_at: Instant,
+}
+
+impl IrqLatency {
+    const fn new() -> Self {
+        Self {
+            histogram: JitterHistogram::new(),
+            max_at: Instant::ZERO,
+        }
+    }
+
+    fn record(&mut self, asserted: Instant, latency: Duration) {
+        if self.histogram.count() == 0 || latency > self.histogram.max() {
+            self.max_at = asserted;
+        }
+        self.histogram.record(latency);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        if other.histogram.count() != 0
+            && (self.histogram.count() == 0 || other.histogram.max() > self.histogram.max())
+        {
+            self.max_at = other.max_at;
+        }
+        self.histogram.merge(&other.histogram);
+    }
+
+    pub fn histogram(&self) -> &JitterHistogram {
+        &self.histogram
+    }
+
+    pub fn count(&self) -> u64 {
+        self.histogram.count()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.histogram.max()
+    }
+
+    /// Returns the assertion time of the interrupt that set the [`max`](Self::max) watermark.
+    ///
+    /// Comparing it with the hold times of interrupt-off sections tells which one delayed it.
+    pub fn max_at(&self) -> Instant {
+        self.max_at
+    }
+}
+
+impl Display for IrqLatency {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} max-at={:?}",
+            self.histogram,
+            self.max_at.as_duration()
+        )
+    }
+}
+
+/// Timestamps the software interrupts about to be raised on `cpu_ids`.
+pub(crate) fn raise_soft(cpu_ids: &[usize]) {
+    let now = hal!().cpu().now().as_nanos() as u64;
+    for &cpu_id in cpu_ids.iter().filter(|&&id| id < hal!().cpu().nproc()) {
+        SOFT_RAISED.with_spec_ref(cpu_id, |raised| raised.fetch_min(now, Ordering::Relaxed));
+    }
+}
+
+/// Returns when the interrupt of `source` being taken was asserted, to be passed to
+/// [`complete`] once it is handled.
+///
+/// External interrupts are not timestamped by their controller, so they count from the trap.
+pub(crate) fn asserted(source: IrqSource) -> Instant {
+    let now = hal!().cpu().now();
+    let asserted = match source {
+        IrqSource::TimerInterrupt => {
+            jrinx_timed_event::with_current(|tq| tq.next_deadline()).map(Instant::from_duration)
+        }
+        IrqSource::SoftwareInterrupt => {
+            match SOFT_RAISED.as_ref().swap(u64::MAX, Ordering::Relaxed) {
+                u64::MAX => None,
+                nanos => Some(Instant::from_nanos(nanos)),
+            }
+        }
+        IrqSource::ExternalInterrupt => None,
+    };
+    asserted.map_or(now, |asserted| asserted.min(now))
+}
+
+pub(crate) fn complete(source: IrqSource, asserted: Instant) {
+    let latency = hal!().cpu().now() - asserted;
+    IRQ_LATENCY.as_ref().lock()[source as usize].record(asserted, latency);
+}
+
+/// Returns the latency of `source`, merged over all CPUs.
+pub fn get(source: IrqSource) -> IrqLatency {
+    IRQ_LATENCY
+        .iter()
+        .fold(IrqLatency::new(), |mut latency, cpu| {
+            latency.merge(&cpu.lock()[source as usize]);
+            latency
+        })
+}
+
+pub fn reset() {
+    for cpu in IRQ_LATENCY.iter() {
+        *cpu.lock() = [IrqLatency::new(); IrqSource::ALL.len()];
+    }
+}
@@ -2,18 +2,36 @@
 #![feature(asm_const)]
 #![feature(offset_of_nested)]
 
+extern crate alloc;
 #[macro_use]
 extern crate log;
 
 pub mod arch;
 pub mod breakpoint;
+pub mod external_int;
+pub mod fatal;
+pub mod fault;
+pub mod fp;
+pub mod gdb;
+pub mod handler;
+pub mod latency;
+pub mod nest;
+pub mod page_fault;
+pub mod smp;
 pub mod soft_int;
+pub mod stack_guard;
+pub mod stats;
 pub mod timer_int;
+pub mod watchpoint;
 
-use core::fmt::Debug;
+use core::fmt::{Debug, Display};
 
+use arch::FpState;
 use jrinx_addr::VirtAddr;
 use jrinx_paging::PagePerm;
+use spin::Once;
+
+static INT_RETURN_HOOK: Once<fn()> = Once::new();
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TrapReason {
@@ -22,10 +40,75 @@ pub enum TrapReason {
     TimerInterrupt,
     SystemCall,
     Breakpoint { addr: VirtAddr },
+    Watchpoint { pc: VirtAddr, addr: VirtAddr },
     PageFault { addr: VirtAddr, perm: PagePerm },
+    IllegalInstruction { addr: VirtAddr, inst: u32 },
+    MisalignedAccess { addr: VirtAddr, kind: AccessKind },
+    AccessFault { addr: VirtAddr, kind: AccessKind },
     Unknown { code: usize },
 }
 
+/// Kind of a trap, as told by its [`TrapReason`] regardless of the details it carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrapKind {
+    ExternalInterrupt,
+    SoftwareInterrupt,
+    TimerInterrupt,
+    SystemCall,
+    Breakpoint,
+    Watchpoint,
+    PageFault,
+    IllegalInstruction,
+    MisalignedAccess,
+    AccessFault,
+    Unknown,
+}
+
+impl TrapKind {
+    pub fn is_interrupt(self) -> bool {
+        matches!(
+            self,
+            Self::ExternalInterrupt | Self::SoftwareInterrupt | Self::TimerInterrupt
+        )
+    }
+}
+
+impl From<TrapReason> for TrapKind {
+    fn from(reason: TrapReason) -> Self {
+        match reason {
+            TrapReason::ExternalInterrupt => Self::ExternalInterrupt,
+            TrapReason::SoftwareInterrupt => Self::SoftwareInterrupt,
+            TrapReason::TimerInterrupt => Self::TimerInterrupt,
+            TrapReason::SystemCall => Self::SystemCall,
+            TrapReason::Breakpoint { .. } => Self::Breakpoint,
+            TrapReason::Watchpoint { .. } => Self::Watchpoint,
+            TrapReason::PageFault { .. } => Self::PageFault,
+            TrapReason::IllegalInstruction { .. } => Self::IllegalInstruction,
+            TrapReason::MisalignedAccess { .. } => Self::MisalignedAccess,
+            TrapReason::AccessFault { .. } => Self::AccessFault,
+            TrapReason::Unknown { .. } => Self::Unknown,
+        }
+    }
+}
+
+/// Kind of the access a fault is trapped on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Fetch,
+}
+
+impl Display for AccessKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Read => write!(f, "read"),
+            Self::Write => write!(f, "write"),
+            Self::Fetch => write!(f, "fetch"),
+        }
+    }
+}
+
 pub trait GenericContext: Debug + Clone + Copy {
     fn trap_reason(&self) -> TrapReason;
 
@@ -35,17 +118,94 @@ pub trait GenericContext: Debug + Clone + Copy {
 
     fn syscall_ret(&mut self, ret: usize);
 
+    /// Sets the leading argument registers to `args`, at most as many as a system call takes.
+    fn set_args(&mut self, args: &[usize]);
+
     fn user_setup(&mut self, entry_point: usize, stack_top: usize);
 
     fn enable_int(&mut self);
 
     fn disable_int(&mut self);
 
+    fn int_enabled(&self) -> bool;
+
+    fn pc(&self) -> usize;
+
+    /// Returns the frame pointer of the interrupted code, which its backtrace starts from.
+    fn fp(&self) -> usize;
+
+    /// Number of the general-purpose registers, numbered from zero as by the architecture.
+    const GPR_COUNT: usize;
+
+    /// Returns the general-purpose register numbered `num`.
+    fn gpr(&self, num: usize) -> usize;
+
+    /// Sets the general-purpose register numbered `num`, ignoring writes to a register wired to
+    /// zero.
+    fn set_gpr(&mut self, num: usize, value: usize);
+
+    /// Logs the registers of the context along with the registers describing its trap, without
+    /// allocating, for dumps of fatal traps.
+    fn log_regs(&self);
+
+    /// Returns the floating-point state of the context, if it owns one since its first
+    /// floating-point instruction.
+    fn fp_state(&mut self) -> Option<&mut FpState>;
+
+    /// Attaches a cleared floating-point state to the context, turning the floating-point unit
+    /// on for it.
+    fn attach_fp_state(&mut self);
+
+    /// Returns the bits of the instruction at [`GenericContext::pc`], if the trap came from
+    /// kernel mode and the instruction can be read.
+    fn inst(&self) -> Option<u32>;
+
+    /// Returns the address of the instruction to run after the one at [`GenericContext::pc`],
+    /// with branches and jumps resolved against the registers, if the instruction can be read.
+    fn next_pc(&self) -> Option<usize>;
+
+    fn set_pc(&mut self, pc: usize);
+
     fn pc_advance(&mut self);
 
     fn run(&mut self);
 }
 
 pub fn init() {
+    stack_guard::init();
     arch::init();
+    jrinx_hal::interrupt::set_ipi_hook(latency::raise_soft);
+}
+
+/// Handles an interrupt trapped from user mode, whose context is owned by the caller.
+///
+/// The interrupt is counted in [`nest::trap_depth`] while handled.
+pub fn handle_user_int(ctx: &mut impl GenericContext) {
+    nest::trap(|| match ctx.trap_reason() {
+        TrapReason::ExternalInterrupt => external_int::handle(ctx),
+        TrapReason::SoftwareInterrupt => soft_int::handle(ctx),
+        TrapReason::TimerInterrupt => timer_int::handle(ctx),
+        reason => panic!("not an interrupt: {:?}", reason),
+    });
+    int_return();
+}
+
+/// Registers `hook` to run on the return path of interrupts, kernel or user, where the
+/// interrupted code may be switched out, such as a task running past its time slice.
+///
+/// The hook only runs on the return path of the outermost interrupt, with no handler left to
+/// be switched out along with the interrupted code, nor any trap handler the interrupt nests
+/// in, as told by [`nest::in_nested_trap`].
+pub fn set_int_return_hook(hook: fn()) {
+    INT_RETURN_HOOK.call_once(|| hook);
+}
+
+pub(crate) fn int_return() {
+    if nest::depth() != 0 || nest::in_nested_trap() {
+        return;
+    }
+    if let Some(hook) = INT_RETURN_HOOK.get() {
+        // Whatever is switched in meanwhile resumes out of this trap.
+        nest::with_trap_depth(0, hook);
+    }
 }
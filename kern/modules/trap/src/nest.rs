@@ -0,0 +1,140 @@
+//! Nesting of interrupt handlers by priority class.
+//!
+//! Once the interrupt it handles is acknowledged, a handler runs with the interrupts of the
+//! classes above its own let in, so that a long handler does not hold back the timer. Frames
+//! of nested traps pile up on the interrupted kernel stack. At most [`INT_NEST_LIMIT`] handlers
+//! run at once on a CPU, the innermost of them with interrupts off.
+//!
+//! Locks taken in handlers of different classes must be irq-safe, as for tasks and handlers.
+//!
+//! Apart from interrupts, any trap taken in kernel mode nests in the code it interrupts, which
+//! may be the handler of another trap, such as an interrupt let in during a page fault handler,
+//! whether that page fault was taken in kernel or in user mode. The [`trap_depth`] tells how
+//! many traps are being handled at once on a CPU.
+
+use core::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use jrinx_percpu::percpu;
+
+use crate::arch;
+
+/// Most interrupt handlers running at once on a CPU.
+pub const INT_NEST_LIMIT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IrqClass {
+    Timer,
+    External,
+    Software,
+}
+
+impl IrqClass {
+    pub const ALL: [Self; 3] = [Self::Timer, Self::External, Self::Software];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Timer => "timer",
+            Self::External => "external",
+            Self::Software => "software",
+        }
+    }
+}
+
+static PRIORITIES: [AtomicU8; IrqClass::ALL.len()] =
+    [AtomicU8::new(2), AtomicU8::new(1), AtomicU8::new(0)];
+
+static NESTED: AtomicU64 = AtomicU64::new(0);
+
+#[percpu]
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+#[percpu]
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+#[percpu]
+static TRAP_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+pub fn priority(class: IrqClass) -> u8 {
+    PRIORITIES[class as usize].load(Ordering::SeqCst)
+}
+
+/// Sets the priority of `class`, whose handlers only let in interrupts of higher priorities.
+pub fn set_priority(class: IrqClass, priority: u8) {
+    PRIORITIES[class as usize].store(priority, Ordering::SeqCst);
+}
+
+/// Returns the number of interrupt handlers running on the current CPU.
+pub fn depth() -> usize {
+    DEPTH.as_ref().load(Ordering::Relaxed)
+}
+
+/// Returns the most interrupt handlers that ran at once on any CPU.
+pub fn max_depth() -> usize {
+    MAX_DEPTH
+        .iter()
+        .map(|depth| depth.load(Ordering::Relaxed))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns the number of traps, from either mode, being handled on the current CPU, zero
+/// outside of trap handlers.
+pub fn trap_depth() -> usize {
+    TRAP_DEPTH.as_ref().load(Ordering::Relaxed)
+}
+
+/// Whether the current CPU handles a trap nested in the handler of another, whose interrupted
+/// code must not be switched out, as it is no task but that handler.
+pub fn in_nested_trap() -> bool {
+    trap_depth() > 1
+}
+
+/// Returns the number of interrupt handlers that ran nested in another one since boot.
+pub fn nested_count() -> u64 {
+    NESTED.load(Ordering::Relaxed)
+}
+
+/// Runs `f`, the handler of an acknowledged interrupt of `class`, letting in the interrupts of
+/// higher priority classes unless as many handlers as [`INT_NEST_LIMIT`] would be running.
+pub(crate) fn handle<R>(class: IrqClass, f: impl FnOnce() -> R) -> R {
+    let depth = DEPTH.as_ref().load(Ordering::Relaxed) + 1;
+    DEPTH.as_ref().store(depth, Ordering::Relaxed);
+    MAX_DEPTH.as_ref().fetch_max(depth, Ordering::Relaxed);
+    if depth > 1 {
+        NESTED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let result = if depth < INT_NEST_LIMIT {
+        let priority = priority(class);
+        arch::with_int_classes(
+            IrqClass::ALL
+                .into_iter()
+                .filter(|&other| self::priority(other) > priority),
+            f,
+        )
+    } else {
+        f()
+    };
+
+    DEPTH.as_ref().store(depth - 1, Ordering::Relaxed);
+    result
+}
+
+/// Runs `f`, the handler of a trap, counting it in [`trap_depth`].
+///
+/// The kernel trap entry counts the traps it takes. Traps from user mode are counted by the
+/// code owning their context, around the handlers which run before it resumes the context.
+pub fn trap<R>(f: impl FnOnce() -> R) -> R {
+    with_trap_depth(trap_depth() + 1, f)
+}
+
+/// Runs `f` at trap depth `depth`, restoring the current depth once it returns.
+///
+/// Code switched out of a trap handler, and switched back in later, must see the depth of its
+/// own trap rather than that of whatever else ran on the CPU meanwhile.
+pub(crate) fn with_trap_depth<R>(depth: usize, f: impl FnOnce() -> R) -> R {
+    let saved = TRAP_DEPTH.as_ref().swap(depth, Ordering::Relaxed);
+    let result = f();
+    TRAP_DEPTH.as_ref().store(saved, Ordering::Relaxed);
+    result
+}
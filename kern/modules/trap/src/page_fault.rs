@@ -0,0 +1,34 @@
+use jrinx_addr::VirtAddr;
+use jrinx_paging::PagePerm;
+use jrinx_stats::StatKind;
+use spin::Once;
+
+use crate::{GenericContext, TrapReason};
+
+/// Resolves a fault on `addr` for an access requiring `perm`, returning whether the access can
+/// be retried.
+pub type PageFaultHandler = fn(addr: VirtAddr, perm: PagePerm) -> bool;
+
+static HANDLER: Once<PageFaultHandler> = Once::new();
+
+pub fn register(handler: PageFaultHandler) {
+    HANDLER.call_once(|| handler);
+}
+
+/// Lets the registered handler resolve a page fault trapped from either mode, returning
+/// whether the faulting access can be retried.
+pub fn handle(ctx: &impl GenericContext) -> bool {
+    let TrapReason::PageFault { addr, perm } = ctx.trap_reason() else {
+        panic!("not a page fault trap");
+    };
+
+    let resolved = HANDLER.get().is_some_and(|handler| handler(addr, perm));
+    if resolved {
+        jrinx_stats::record(StatKind::PageFault);
+    }
+    resolved
+}
+
+pub fn count() -> u64 {
+    jrinx_stats::snapshot().total(StatKind::PageFault)
+}
@@ -0,0 +1,259 @@
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::{hal, Cpu, Hal, Interrupt};
+use jrinx_percpu::percpu;
+use spin::Once;
+
+/// Most calls pending on a CPU, beyond which [`call`] and [`call_async`] fail.
+pub const SMP_CALL_QUEUE_CAPACITY: usize = 64;
+
+type CallFn = dyn Fn() -> usize + Send + Sync;
+
+struct CallSlot {
+    func: Arc<CallFn>,
+    result: Once<usize>,
+    /// Set when posting the call failed for another CPU, so that this one is skipped.
+    withdrawn: AtomicBool,
+}
+
+#[percpu]
+static SMP_CALL_QUEUE: CallQueue = CallQueue::new();
+
+#[percpu]
+static SMP_CALL_RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+pub struct PerCpuResults {
+    results: BTreeMap<usize, Option<usize>>,
+}
+
+impl PerCpuResults {
+    pub fn get(&self, cpu_id: usize) -> Option<usize> {
+        self.results.get(&cpu_id).copied().flatten()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Option<usize>)> + '_ {
+        self.results
+            .iter()
+            .map(|(&cpu_id, &result)| (cpu_id, result))
+    }
+
+    pub fn unresponsive(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter()
+            .filter_map(|(cpu_id, result)| result.is_none().then_some(cpu_id))
+    }
+
+    pub fn into_result(self) -> Result<BTreeMap<usize, usize>> {
+        self.results
+            .into_iter()
+            .map(|(cpu_id, result)| result.map(|r| (cpu_id, r)))
+            .collect::<Option<_>>()
+            .ok_or(InternalError::SmpCallTimeout)
+    }
+}
+
+/// Runs `func` on each of `cpu_ids`, waiting up to `timeout` for their results.
+///
+/// The local CPU runs `func` in place if it is among `cpu_ids`.
+pub fn call<F>(cpu_ids: &[usize], timeout: Duration, func: F) -> Result<PerCpuResults>
+where
+    F: Fn() -> usize + Send + Sync + 'static,
+{
+    let slots = post(cpu_ids, Arc::new(func))?;
+
+    let deadline = hal!().cpu().get_time() + timeout;
+    while slots.values().any(|slot| !slot.result.is_completed())
+        && hal!().cpu().get_time() < deadline
+    {
+        handle_pending();
+        core::hint::spin_loop();
+    }
+
+    Ok(PerCpuResults {
+        results: slots
+            .into_iter()
+            .map(|(cpu_id, slot)| (cpu_id, slot.result.get().copied()))
+            .collect(),
+    })
+}
+
+/// Runs `func` on each of `cpu_ids` without waiting for the remote ones.
+///
+/// The local CPU runs `func` in place if it is among `cpu_ids`.
+pub fn call_async<F>(cpu_ids: &[usize], func: F) -> Result<()>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    post(
+        cpu_ids,
+        Arc::new(move || {
+            func();
+            0
+        }),
+    )
+    .map(|_| ())
+}
+
+fn post(cpu_ids: &[usize], func: Arc<CallFn>) -> Result<BTreeMap<usize, Arc<CallSlot>>> {
+    if SMP_CALL_RUNNING.as_ref().load(Ordering::Acquire) {
+        return Err(InternalError::SmpCallNested);
+    }
+
+    let nproc = hal!().cpu().nproc();
+    if let Some(&cpu_id) = cpu_ids.iter().find(|&&cpu_id| cpu_id >= nproc) {
+        return Err(InternalError::InvalidCpuId(cpu_id));
+    }
+
+    let local_id = hal!().cpu().id();
+
+    let mut slots = BTreeMap::new();
+    let mut remote_ids = Vec::new();
+    for &cpu_id in cpu_ids {
+        if slots.contains_key(&cpu_id) {
+            continue;
+        }
+        let slot = Arc::new(CallSlot {
+            func: func.clone(),
+            result: Once::new(),
+            withdrawn: AtomicBool::new(false),
+        });
+        if cpu_id != local_id {
+            if let Err(err) = SMP_CALL_QUEUE.with_spec_ref(cpu_id, |queue| queue.push(slot.clone()))
+            {
+                // No IPI is sent yet, so the calls queued so far are withdrawn unless some
+                // unrelated interrupt has already run them.
+                for cpu_id in &remote_ids {
+                    slots[cpu_id].withdrawn.store(true, Ordering::Release);
+                }
+                return Err(err);
+            }
+            remote_ids.push(cpu_id);
+        }
+        slots.insert(cpu_id, slot);
+    }
+
+    if !remote_ids.is_empty() {
+        hal!().interrupt().send_ipi(&remote_ids);
+    }
+
+    if let Some(slot) = slots.get(&local_id) {
+        run_slot(slot);
+    }
+
+    Ok(slots)
+}
+
+pub(crate) fn handle_pending() {
+    while let Some(slot) = SMP_CALL_QUEUE.with_ref(|queue| queue.pop()) {
+        run_slot(&slot);
+    }
+}
+
+fn run_slot(slot: &CallSlot) {
+    if slot.withdrawn.load(Ordering::Acquire) {
+        return;
+    }
+    SMP_CALL_RUNNING.as_ref().store(true, Ordering::Release);
+    let result = hal!().interrupt().with_saved_off(|| (slot.func)());
+    SMP_CALL_RUNNING.as_ref().store(false, Ordering::Release);
+    slot.result.call_once(|| result);
+}
+
+/// Bounded lock-free FIFO of the calls pending on a CPU.
+///
+/// Position `pos` lives in cell `pos % SMP_CALL_QUEUE_CAPACITY` during lap
+/// `pos / SMP_CALL_QUEUE_CAPACITY`. The turn of a cell is `2 * lap` while it waits to be
+/// written in that lap, and `2 * lap + 1` while it waits to be read.
+struct CallQueue {
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    cells: [CallCell; SMP_CALL_QUEUE_CAPACITY],
+}
+
+struct CallCell {
+    turn: AtomicUsize,
+    slot: UnsafeCell<MaybeUninit<Arc<CallSlot>>>,
+}
+
+// SAFETY: a cell is only written by the producer that claimed its position in the current
+// lap, and only read by the consumer that claimed it, each ordered by the turn of the cell.
+unsafe impl Sync for CallQueue {}
+
+impl CallQueue {
+    const fn new() -> Self {
+        Self {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            cells: [const {
+                CallCell {
+                    turn: AtomicUsize::new(0),
+                    slot: UnsafeCell::new(MaybeUninit::uninit()),
+                }
+            }; SMP_CALL_QUEUE_CAPACITY],
+        }
+    }
+
+    fn push(&self, slot: Arc<CallSlot>) -> Result<()> {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+        loop {
+            let cell = &self.cells[pos % SMP_CALL_QUEUE_CAPACITY];
+            let turn = 2 * (pos / SMP_CALL_QUEUE_CAPACITY);
+            let current = cell.turn.load(Ordering::Acquire);
+            if current == turn {
+                match self.tail.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*cell.slot.get()).write(slot) };
+                        cell.turn.store(turn + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(tail) => pos = tail,
+                }
+            } else if current < turn {
+                // The cell still holds, or is about to hold, a call of the previous lap.
+                return Err(InternalError::SmpCallQueueFull);
+            } else {
+                pos = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<Arc<CallSlot>> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let cell = &self.cells[pos % SMP_CALL_QUEUE_CAPACITY];
+            let turn = 2 * (pos / SMP_CALL_QUEUE_CAPACITY) + 1;
+            let current = cell.turn.load(Ordering::Acquire);
+            if current == turn {
+                match self.head.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let slot = unsafe { (*cell.slot.get()).assume_init_read() };
+                        cell.turn.store(turn + 1, Ordering::Release);
+                        return Some(slot);
+                    }
+                    Err(head) => pos = head,
+                }
+            } else if current < turn {
+                return None;
+            } else {
+                pos = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
@@ -1,20 +1,27 @@
 use jrinx_hal::{hal, Hal, Interrupt};
-use spin::RwLock;
+use jrinx_stats::StatKind;
 
-use crate::{GenericContext, TrapReason};
-
-static SOFT_INT_COUNTER: RwLock<u64> = RwLock::new(0);
+use crate::{
+    latency::{self, IrqSource},
+    nest::{self, IrqClass},
+    smp, GenericContext, TrapReason,
+};
 
 pub(crate) fn handle(ctx: &mut impl GenericContext) {
     let TrapReason::SoftwareInterrupt = ctx.trap_reason() else {
         panic!("not a software interrupt");
     };
 
-    *SOFT_INT_COUNTER.write() += 1;
+    jrinx_stats::record(StatKind::SoftwareInterrupt);
 
     hal!().interrupt().clr_soft();
+    let asserted = latency::asserted(IrqSource::SoftwareInterrupt);
+
+    nest::handle(IrqClass::Software, smp::handle_pending);
+
+    latency::complete(IrqSource::SoftwareInterrupt, asserted);
 }
 
 pub fn count() -> u64 {
-    *SOFT_INT_COUNTER.read()
+    jrinx_stats::snapshot().total(StatKind::SoftwareInterrupt)
 }
@@ -0,0 +1,93 @@
+use core::{
+    fmt::{self, Display, Write},
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use jrinx_addr::VirtAddr;
+use jrinx_config::PAGE_SIZE;
+use jrinx_hal::{hal, Hal, HaltReason};
+use jrinx_percpu::percpu;
+use spin::Once;
+
+use crate::GenericContext;
+
+/// Size of the stack a CPU takes a trap on when the kernel stack has no room for its frame.
+pub const EMERGENCY_STACK_SIZE: usize = PAGE_SIZE * 4;
+
+#[repr(C, align(16))]
+struct EmergencyStack([u8; EMERGENCY_STACK_SIZE]);
+
+#[percpu]
+static EMERGENCY_STACK: EmergencyStack = EmergencyStack([0; EMERGENCY_STACK_SIZE]);
+
+/// Offset of the top of [`EMERGENCY_STACK`] from the local pointer, read by the trap entry.
+pub(crate) static EMERGENCY_STACK_TOP: AtomicUsize = AtomicUsize::new(0);
+
+/// Kernel stacks guarded against overflows, set by [`register`].
+struct GuardHook {
+    stack_of: fn(addr: VirtAddr) -> Option<Range<VirtAddr>>,
+    owner: fn(w: &mut dyn Write) -> fmt::Result,
+}
+
+static GUARD_HOOK: Once<GuardHook> = Once::new();
+
+/// Registers `stack_of` to tell the bounds of the kernel stack whose guard holds an address,
+/// and `owner` to write the name of the task running on the current CPU when its stack
+/// overflows.
+pub fn register(
+    stack_of: fn(addr: VirtAddr) -> Option<Range<VirtAddr>>,
+    owner: fn(w: &mut dyn Write) -> fmt::Result,
+) {
+    GUARD_HOOK.call_once(|| GuardHook { stack_of, owner });
+}
+
+pub(crate) fn init() {
+    EMERGENCY_STACK_TOP.store(
+        EMERGENCY_STACK.offset() + EMERGENCY_STACK_SIZE,
+        Ordering::Relaxed,
+    );
+}
+
+/// Returns whether `addr` is on the emergency stack of the current CPU.
+pub(crate) fn on_emergency_stack(addr: usize) -> bool {
+    let bottom = EMERGENCY_STACK.as_ptr() as usize;
+    (bottom..bottom + EMERGENCY_STACK_SIZE).contains(&addr)
+}
+
+/// Returns whether `addr` lies on the guard below a kernel stack.
+pub(crate) fn is_guard(addr: VirtAddr) -> bool {
+    GUARD_HOOK
+        .get()
+        .is_some_and(|hook| (hook.stack_of)(addr).is_some())
+}
+
+/// Reports kernel code running past its stack onto `addr`, then halts, since nothing it
+/// left on the stack can be trusted anymore.
+pub(crate) fn overflow(ctx: &impl GenericContext, addr: VirtAddr) -> ! {
+    let hook = GUARD_HOOK.get();
+    match hook.and_then(|hook| (hook.stack_of)(addr)) {
+        Some(stack) => error!(
+            "kernel stack overflow in task {}, stack {} .. {}",
+            Owner, stack.start, stack.end
+        ),
+        None => error!("kernel stack overflow in task {} onto {}", Owner, addr),
+    }
+    error!("{:#x?}", ctx);
+    jrinx_backtrace::log_trapped(ctx.pc(), ctx.fp());
+    log::logger().flush();
+
+    hal!().halt(HaltReason::StackOverflow)
+}
+
+/// Task running on the current CPU, as written by the registered hook.
+struct Owner;
+
+impl Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match GUARD_HOOK.get() {
+            Some(hook) => (hook.owner)(f),
+            None => f.write_str("<none>"),
+        }
+    }
+}
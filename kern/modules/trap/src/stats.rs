@@ -0,0 +1,196 @@
+use core::fmt::Display;
+#[cfg(feature = "trap-stats")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::vec::Vec;
+use jrinx_hal::{hal, Cpu, Hal};
+#[cfg(feature = "trap-stats")]
+use jrinx_percpu::percpu;
+
+use crate::TrapReason;
+
+/// Sources of external interrupts counted apart, beyond which they are only counted in total.
+pub const EXTERNAL_IRQ_SOURCES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrapKind {
+    ExternalInterrupt,
+    SoftwareInterrupt,
+    TimerInterrupt,
+    SystemCall,
+    Breakpoint,
+    Watchpoint,
+    PageFault,
+    IllegalInstruction,
+    MisalignedAccess,
+    AccessFault,
+    Unknown,
+}
+
+impl TrapKind {
+    pub const ALL: [Self; 11] = [
+        Self::ExternalInterrupt,
+        Self::SoftwareInterrupt,
+        Self::TimerInterrupt,
+        Self::SystemCall,
+        Self::Breakpoint,
+        Self::Watchpoint,
+        Self::PageFault,
+        Self::IllegalInstruction,
+        Self::MisalignedAccess,
+        Self::AccessFault,
+        Self::Unknown,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ExternalInterrupt => "external-interrupt",
+            Self::SoftwareInterrupt => "software-interrupt",
+            Self::TimerInterrupt => "timer-interrupt",
+            Self::SystemCall => "system-call",
+            Self::Breakpoint => "breakpoint",
+            Self::Watchpoint => "watchpoint",
+            Self::PageFault => "page-fault",
+            Self::IllegalInstruction => "illegal-instruction",
+            Self::MisalignedAccess => "misaligned-access",
+            Self::AccessFault => "access-fault",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl From<&TrapReason> for TrapKind {
+    fn from(reason: &TrapReason) -> Self {
+        match reason {
+            TrapReason::ExternalInterrupt => Self::ExternalInterrupt,
+            TrapReason::SoftwareInterrupt => Self::SoftwareInterrupt,
+            TrapReason::TimerInterrupt => Self::TimerInterrupt,
+            TrapReason::SystemCall => Self::SystemCall,
+            TrapReason::Breakpoint { .. } => Self::Breakpoint,
+            TrapReason::Watchpoint { .. } => Self::Watchpoint,
+            TrapReason::PageFault { .. } => Self::PageFault,
+            TrapReason::IllegalInstruction { .. } => Self::IllegalInstruction,
+            TrapReason::MisalignedAccess { .. } => Self::MisalignedAccess,
+            TrapReason::AccessFault { .. } => Self::AccessFault,
+            TrapReason::Unknown { .. } => Self::Unknown,
+        }
+    }
+}
+
+#[cfg(feature = "trap-stats")]
+struct TrapCounters {
+    traps: [AtomicU64; TrapKind::ALL.len()],
+    external: [AtomicU64; EXTERNAL_IRQ_SOURCES],
+}
+
+#[cfg(feature = "trap-stats")]
+#[percpu]
+static TRAP_COUNTERS: TrapCounters = TrapCounters::new();
+
+/// Traps taken on a CPU, by reason.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapStats {
+    cpu_id: usize,
+    traps: [u64; TrapKind::ALL.len()],
+    external: [u64; EXTERNAL_IRQ_SOURCES],
+}
+
+/// Counts a trap for `reason` on the current CPU.
+///
+/// Each CPU is the only writer of its own counters, which are bumped with traps off.
+#[inline]
+pub(crate) fn record(reason: &TrapReason) {
+    #[cfg(feature = "trap-stats")]
+    TRAP_COUNTERS.with_ref(|counters| bump(&counters.traps[TrapKind::from(reason) as usize]));
+    #[cfg(not(feature = "trap-stats"))]
+    let _ = reason;
+}
+
+/// Counts an external interrupt from `source` on the current CPU, as claimed from the interrupt
+/// controller.
+#[inline]
+pub fn record_external(source: usize) {
+    #[cfg(feature = "trap-stats")]
+    TRAP_COUNTERS.with_ref(|counters| {
+        if let Some(counter) = counters.external.get(source) {
+            bump(counter);
+        }
+    });
+    #[cfg(not(feature = "trap-stats"))]
+    let _ = source;
+}
+
+/// Returns the traps taken on `cpu_id` so far, all zero if counters are compiled out.
+pub fn snapshot(cpu_id: usize) -> TrapStats {
+    assert!(cpu_id < hal!().cpu().nproc(), "invalid cpu id: {}", cpu_id);
+
+    #[cfg_attr(not(feature = "trap-stats"), allow(unused_mut))]
+    let mut stats = TrapStats {
+        cpu_id,
+        traps: [0; TrapKind::ALL.len()],
+        external: [0; EXTERNAL_IRQ_SOURCES],
+    };
+    #[cfg(feature = "trap-stats")]
+    TRAP_COUNTERS.with_spec_ref(cpu_id, |counters| {
+        for (value, counter) in stats.traps.iter_mut().zip(counters.traps.iter()) {
+            *value = counter.load(Ordering::Relaxed);
+        }
+        for (value, counter) in stats.external.iter_mut().zip(counters.external.iter()) {
+            *value = counter.load(Ordering::Relaxed);
+        }
+    });
+    stats
+}
+
+pub fn snapshot_all() -> Vec<TrapStats> {
+    (0..hal!().cpu().nproc()).map(snapshot).collect()
+}
+
+#[cfg(feature = "trap-stats")]
+fn bump(counter: &AtomicU64) {
+    counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+}
+
+#[cfg(feature = "trap-stats")]
+impl TrapCounters {
+    const fn new() -> Self {
+        Self {
+            traps: [const { AtomicU64::new(0) }; TrapKind::ALL.len()],
+            external: [const { AtomicU64::new(0) }; EXTERNAL_IRQ_SOURCES],
+        }
+    }
+}
+
+impl TrapStats {
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id
+    }
+
+    pub fn get(&self, kind: TrapKind) -> u64 {
+        self.traps[kind as usize]
+    }
+
+    /// Returns the external interrupts counted from `source`, if it is counted apart.
+    pub fn external(&self, source: usize) -> Option<u64> {
+        self.external.get(source).copied()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.traps.iter().sum()
+    }
+}
+
+impl Display for TrapStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "traps on cpu#{}:", self.cpu_id)?;
+        for kind in TrapKind::ALL {
+            write!(f, " {}={}", kind.name(), self.get(kind))?;
+        }
+        for (source, &count) in self.external.iter().enumerate() {
+            if count != 0 {
+                write!(f, " irq#{}={}", source, count)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,31 +1,46 @@
 use core::time::Duration;
 
 use jrinx_hal::{hal, Cpu, Hal, Interrupt};
-use spin::RwLock;
+use jrinx_stats::StatKind;
 
-use crate::{GenericContext, TrapReason};
-
-static TIMER_INT_COUNTER: RwLock<u64> = RwLock::new(0);
+use crate::{
+    latency::{self, IrqSource},
+    nest::{self, IrqClass},
+    GenericContext, TrapReason,
+};
 
 pub(crate) fn handle(ctx: &mut impl GenericContext) {
     let TrapReason::TimerInterrupt = ctx.trap_reason() else {
         panic!("not a timer interrupt");
     };
+    let asserted = latency::asserted(IrqSource::TimerInterrupt);
+
+    jrinx_stats::record(StatKind::TimerInterrupt);
 
-    *TIMER_INT_COUNTER.write() += 1;
+    // The primary deadline is polled before every timed event, so a window switch is delayed
+    // by at most one timed-event handler no matter how many of them are due.
+    nest::handle(IrqClass::Timer, || loop {
+        if let Some(primary) = jrinx_timed_event::with_current(|tq| tq.take_outdated_primary()) {
+            primary.fire();
+            continue;
+        }
 
-    while let Some(tracker) = jrinx_timed_event::with_current(|tq| tq.peek_outdated()) {
+        let Some(tracker) = jrinx_timed_event::with_current(|tq| tq.peek_outdated()) else {
+            break;
+        };
         if let Err(err) = tracker.timeout() {
             warn!("Failed to handle timed event timeout: {:?}", err);
         }
-    }
+    });
 
     if hal!().interrupt().is_timer_pending() {
         warn!("timer interrupt is pending, but no timed event is scheduled");
         hal!().cpu().set_timer(Duration::MAX);
     }
+
+    latency::complete(IrqSource::TimerInterrupt, asserted);
 }
 
 pub fn count() -> u64 {
-    *TIMER_INT_COUNTER.read()
+    jrinx_stats::snapshot().total(StatKind::TimerInterrupt)
 }
@@ -0,0 +1,180 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{fmt::Display, time::Duration};
+
+use jrinx_addr::VirtAddr;
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::{hal, Cpu, Hal};
+use jrinx_serial_id_macro::SerialId;
+use spin::Mutex;
+
+use crate::{arch, breakpoint, smp, GenericContext, TrapReason};
+
+const SMP_CALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Watchpoints installed on every hart, so that their triggers stay put whichever context runs.
+static WATCHPOINTS: Mutex<Vec<Watchpoint>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SerialId)]
+pub struct WatchpointId(u64);
+
+impl Display for WatchpointId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Kind of the accesses a watchpoint fires on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Both,
+}
+
+impl Display for WatchKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Read => write!(f, "read"),
+            Self::Write => write!(f, "write"),
+            Self::Both => write!(f, "read-write"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointInfo {
+    pub id: WatchpointId,
+    pub addr: VirtAddr,
+    pub len: usize,
+    pub kind: WatchKind,
+    pub hits: u64,
+}
+
+struct Watchpoint {
+    id: WatchpointId,
+    addr: usize,
+    len: usize,
+    kind: WatchKind,
+    hits: u64,
+    triggers: BTreeMap<usize, usize>,
+}
+
+/// Watches `kind` accesses in `addr .. addr + len` on every hart, where `len` is a power of two
+/// `addr` is aligned to.
+pub fn set(addr: VirtAddr, len: usize, kind: WatchKind) -> Result<WatchpointId> {
+    let addr = addr.as_usize();
+    if !len.is_power_of_two() || addr % len != 0 {
+        return Err(InternalError::InvalidVirtAddr(VirtAddr::new(addr)));
+    }
+    if WATCHPOINTS.lock().len() >= arch::trigger_capacity() {
+        return Err(InternalError::NotEnoughTriggers);
+    }
+
+    let cpu_ids = (0..hal!().cpu().nproc_valid()).collect::<Vec<_>>();
+    let results = smp::call(&cpu_ids, SMP_CALL_TIMEOUT, move || {
+        arch::install_trigger(addr, len, kind).unwrap_or(usize::MAX)
+    })?;
+    let triggers = results
+        .iter()
+        .filter_map(|(cpu_id, idx)| Some(cpu_id).zip(idx.filter(|&idx| idx != usize::MAX)))
+        .collect::<BTreeMap<_, _>>();
+    if triggers.len() != cpu_ids.len() {
+        uninstall(triggers)?;
+        return Err(InternalError::NotEnoughTriggers);
+    }
+
+    let id = WatchpointId::new();
+    WATCHPOINTS.lock().push(Watchpoint {
+        id,
+        addr,
+        len,
+        kind,
+        hits: 0,
+        triggers,
+    });
+    Ok(id)
+}
+
+pub fn clear(id: WatchpointId) -> Result<()> {
+    let watchpoint = {
+        let mut watchpoints = WATCHPOINTS.lock();
+        let index = watchpoints
+            .iter()
+            .position(|watchpoint| watchpoint.id == id)
+            .ok_or(InternalError::InvalidWatchpointId)?;
+        watchpoints.remove(index)
+    };
+    uninstall(watchpoint.triggers)
+}
+
+pub fn list() -> Vec<WatchpointInfo> {
+    WATCHPOINTS
+        .lock()
+        .iter()
+        .map(|watchpoint| WatchpointInfo {
+            id: watchpoint.id,
+            addr: VirtAddr::new(watchpoint.addr),
+            len: watchpoint.len,
+            kind: watchpoint.kind,
+            hits: watchpoint.hits,
+        })
+        .collect()
+}
+
+fn uninstall(triggers: BTreeMap<usize, usize>) -> Result<()> {
+    let cpu_ids = triggers.keys().copied().collect::<Vec<_>>();
+    smp::call(&cpu_ids, SMP_CALL_TIMEOUT, move || {
+        if let Some(&idx) = triggers.get(&hal!().cpu().id()) {
+            arch::uninstall_trigger(idx);
+        }
+        0
+    })?
+    .into_result()
+    .map(|_| ())
+}
+
+fn local_trigger_mask() -> usize {
+    let cpu_id = hal!().cpu().id();
+    WATCHPOINTS
+        .lock()
+        .iter()
+        .filter_map(|watchpoint| watchpoint.triggers.get(&cpu_id))
+        .fold(0, |mask, &idx| mask | 1 << idx)
+}
+
+fn enable_local_triggers() {
+    arch::enable_triggers(local_trigger_mask());
+}
+
+/// Reports a hit watchpoint and lets the accessing instruction run with the triggers of the
+/// current hart disabled.
+pub(crate) fn handle(ctx: &mut arch::Context) {
+    let TrapReason::Watchpoint { pc, addr } = ctx.trap_reason() else {
+        panic!("not a watchpoint trap");
+    };
+
+    let hit = WATCHPOINTS
+        .lock()
+        .iter_mut()
+        .find(|watchpoint| {
+            (watchpoint.addr..watchpoint.addr + watchpoint.len).contains(&addr.as_usize())
+        })
+        .map(|watchpoint| {
+            watchpoint.hits += 1;
+            (watchpoint.id, watchpoint.kind)
+        });
+    match hit {
+        Some((id, kind)) => info!(
+            "{} watchpoint {} hit by pc {} accessing {}",
+            kind, id, pc, addr
+        ),
+        // Harts may not tell the accessed address, or the breakpoint trapped on at pc has just
+        // been taken away by another hart.
+        None => debug!("unattributed watchpoint hit by pc {}", pc),
+    }
+
+    arch::disable_triggers(local_trigger_mask());
+    if !breakpoint::MANAGER.step(ctx, enable_local_triggers) {
+        enable_local_triggers();
+    }
+}
@@ -0,0 +1,312 @@
+//! Registry of the tunables of the kernel, the settings that may change at runtime.
+//!
+//! Subsystems register each tunable under a dotted key, such as `lowmem.policy`, with its
+//! type, default value, a validator and a change callback. Values are set as text, from the
+//! `--set` boot argument or the `SYS_SET_TUNABLE` system call, or typed from the kernel.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use jrinx_sync::IrqSafeMutex;
+use spin::RwLock;
+
+static REGISTRY: RwLock<BTreeMap<&'static str, Arc<dyn Entry>>> = RwLock::new(BTreeMap::new());
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TunableError {
+    /// No tunable is registered under the key.
+    UnknownKey,
+    /// A tunable is already registered under the key.
+    DuplicateKey,
+    /// The value is not of the type of the tunable, named here.
+    Malformed(&'static str),
+    /// The validator of the tunable rejected the value, for the reason given.
+    Rejected(String),
+}
+
+impl Display for TunableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownKey => write!(f, "unknown key"),
+            Self::DuplicateKey => write!(f, "duplicate key"),
+            Self::Malformed(kind) => write!(f, "not a value of type {}", kind),
+            Self::Rejected(reason) => write!(f, "rejected, {}", reason),
+        }
+    }
+}
+
+/// A type of tunable values, which are read from and shown as text.
+pub trait TunableValue: Clone + Send + Sync + 'static {
+    /// Name of the type, as listed with the tunables.
+    const KIND: &'static str;
+
+    fn parse(text: &str) -> Option<Self>;
+
+    fn show(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+impl TunableValue for bool {
+    const KIND: &'static str = "bool";
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "true" | "on" | "1" => Some(true),
+            "false" | "off" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn show(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl TunableValue for u64 {
+    const KIND: &'static str = "u64";
+
+    fn parse(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+
+    fn show(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Durations are written with a unit, as in `200ms`, which is one of `ns`, `us`, `ms` and `s`.
+impl TunableValue for Duration {
+    const KIND: &'static str = "duration";
+
+    fn parse(text: &str) -> Option<Self> {
+        let unit_at = text.find(|c: char| !c.is_ascii_digit())?;
+        let value = text[..unit_at].parse().ok()?;
+        match &text[unit_at..] {
+            "ns" => Some(Duration::from_nanos(value)),
+            "us" => Some(Duration::from_micros(value)),
+            "ms" => Some(Duration::from_millis(value)),
+            "s" => Some(Duration::from_secs(value)),
+            _ => None,
+        }
+    }
+
+    fn show(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = self.as_nanos();
+        match nanos {
+            _ if nanos % 1_000_000_000 == 0 => write!(f, "{}s", nanos / 1_000_000_000),
+            _ if nanos % 1_000_000 == 0 => write!(f, "{}ms", nanos / 1_000_000),
+            _ if nanos % 1_000 == 0 => write!(f, "{}us", nanos / 1_000),
+            _ => write!(f, "{}ns", nanos),
+        }
+    }
+}
+
+impl TunableValue for log::LevelFilter {
+    const KIND: &'static str = "level";
+
+    fn parse(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+
+    fn show(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str().to_ascii_lowercase())
+    }
+}
+
+/// Optional values are written `none` when absent.
+impl<T: TunableValue> TunableValue for Option<T> {
+    const KIND: &'static str = T::KIND;
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "none" => Some(None),
+            _ => T::parse(text).map(Some),
+        }
+    }
+
+    fn show(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(value) => value.show(f),
+            None => write!(f, "none"),
+        }
+    }
+}
+
+struct Shown<'a, T>(&'a T);
+
+impl<T: TunableValue> Display for Shown<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.show(f)
+    }
+}
+
+type Validator<T> = Box<dyn Fn(&T) -> Result<(), String> + Send + Sync>;
+type Callback<T> = Box<dyn Fn(&T) + Send + Sync>;
+
+struct Tunable<T> {
+    default: T,
+    value: IrqSafeMutex<T>,
+    validate: Validator<T>,
+    on_change: Callback<T>,
+}
+
+impl<T: TunableValue> Tunable<T> {
+    /// Validates, applies and stores `value` under the lock of the tunable, so that changes
+    /// to it are serialized, and take effect in the order they are stored.
+    fn store(&self, value: T) -> Result<(), TunableError> {
+        let mut current = self.value.lock();
+        (self.validate)(&value).map_err(TunableError::Rejected)?;
+        (self.on_change)(&value);
+        *current = value;
+        Ok(())
+    }
+}
+
+trait Entry: Send + Sync {
+    fn kind(&self) -> &'static str;
+
+    fn value(&self) -> String;
+
+    fn default(&self) -> String;
+
+    fn set(&self, text: &str) -> Result<(), TunableError>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: TunableValue> Entry for Tunable<T> {
+    fn kind(&self) -> &'static str {
+        T::KIND
+    }
+
+    fn value(&self) -> String {
+        Shown(&*self.value.lock()).to_string()
+    }
+
+    fn default(&self) -> String {
+        Shown(&self.default).to_string()
+    }
+
+    fn set(&self, text: &str) -> Result<(), TunableError> {
+        self.store(T::parse(text).ok_or(TunableError::Malformed(T::KIND))?)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A registered tunable, as listed by [`list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunableInfo {
+    pub key: &'static str,
+    pub kind: &'static str,
+    pub value: String,
+    pub default: String,
+}
+
+impl Display for TunableInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} = {} (default {})",
+            self.key, self.kind, self.value, self.default
+        )
+    }
+}
+
+/// Registers the tunable `key`, whose subsystem starts with the `default` value.
+///
+/// Each change is checked by `validate`, which returns the reason to reject it, then applied
+/// by `on_change`. Both run with interrupts disabled, and must not change `key` themselves.
+pub fn register<T: TunableValue>(
+    key: &'static str,
+    default: T,
+    validate: impl Fn(&T) -> Result<(), String> + Send + Sync + 'static,
+    on_change: impl Fn(&T) + Send + Sync + 'static,
+) -> Result<(), TunableError> {
+    let tunable = Tunable {
+        default: default.clone(),
+        value: IrqSafeMutex::new("tunable", default),
+        validate: Box::new(validate),
+        on_change: Box::new(on_change),
+    };
+    let mut registry = REGISTRY.write();
+    if registry.contains_key(key) {
+        return Err(TunableError::DuplicateKey);
+    }
+    registry.insert(key, Arc::new(tunable));
+    Ok(())
+}
+
+fn find(key: &str) -> Result<Arc<dyn Entry>, TunableError> {
+    REGISTRY
+        .read()
+        .get(key)
+        .cloned()
+        .ok_or(TunableError::UnknownKey)
+}
+
+/// Sets the tunable `key` to the value written as `text`.
+pub fn set(key: &str, text: &str) -> Result<(), TunableError> {
+    find(key)?.set(text)
+}
+
+/// Sets the tunable `key` to `value`, which must be of its type.
+pub fn set_value<T: TunableValue>(key: &str, value: T) -> Result<(), TunableError> {
+    find(key)?
+        .as_any()
+        .downcast_ref::<Tunable<T>>()
+        .ok_or(TunableError::Malformed(T::KIND))?
+        .store(value)
+}
+
+/// Returns the value of the tunable `key`, written as text.
+pub fn get(key: &str) -> Result<String, TunableError> {
+    Ok(find(key)?.value())
+}
+
+/// Returns the value of the tunable `key`, which must be of type `T`.
+pub fn get_value<T: TunableValue>(key: &str) -> Result<T, TunableError> {
+    let entry = find(key)?;
+    let tunable = entry
+        .as_any()
+        .downcast_ref::<Tunable<T>>()
+        .ok_or(TunableError::Malformed(T::KIND))?;
+    let value = tunable.value.lock().clone();
+    Ok(value)
+}
+
+/// Lists the registered tunables, ordered by key.
+pub fn list() -> Vec<TunableInfo> {
+    REGISTRY
+        .read()
+        .iter()
+        .map(|(&key, entry)| TunableInfo {
+            key,
+            kind: entry.kind(),
+            value: entry.value(),
+            default: entry.default(),
+        })
+        .collect()
+}
+
+/// Parses an assignment `key=value`, as given to `--set`.
+pub fn parse_assignment(assignment: &str) -> Option<(&str, &str)> {
+    assignment
+        .split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+}
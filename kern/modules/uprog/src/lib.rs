@@ -1,7 +1,7 @@
 #![no_std]
 
 use elf::{endian::AnyEndian, ElfBytes};
-use jrinx_error::{InternalError, Result};
+use jrinx_error::{ContextError, InternalError, ResultExt};
 
 static USER_PROGRAMS: &[u8] = include_bytes!(core::env!("UPROG_PATH"));
 
@@ -9,16 +9,21 @@ pub fn all() -> impl Iterator<Item = &'static str> {
     cpio_reader::iter_files(USER_PROGRAMS).map(|entry| entry.name())
 }
 
-pub fn find(slug: &str) -> Result<ElfBytes<'static, AnyEndian>> {
+pub fn find(slug: &str) -> Result<ElfBytes<'static, AnyEndian>, ContextError> {
     cpio_reader::iter_files(USER_PROGRAMS)
         .find_map(|entry| {
             let name = entry.name();
             let content = entry.file();
             if name == slug {
-                Some(ElfBytes::minimal_parse(content).map_err(|_| InternalError::ElfParseError))
+                Some(
+                    ElfBytes::minimal_parse(content)
+                        .map_err(|_| InternalError::ElfParseError)
+                        .context("parse elf header"),
+                )
             } else {
                 None
             }
         })
-        .unwrap_or(Err(InternalError::ElfParseError))
+        .unwrap_or_else(|| Err(InternalError::ElfParseError).context("no such program"))
+        .with_context(|| slug)
 }
@@ -1,12 +1,171 @@
+use core::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// Color of the ANSI palette, written by SGR escape sequences.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[allow(dead_code)]
-#[repr(u8)]
 pub enum ColorCode {
-    Red = 31,
-    Green = 32,
-    Yellow = 33,
-    Blue = 34,
-    Magenta = 35,
-    Cyan = 36,
-    White = 37,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    /// Color of the 256-color palette, which older terminals lack.
+    #[cfg(feature = "colorful")]
+    Ansi256(u8),
+}
+
+const NAMES: [(&str, ColorCode, ColorCode); 8] = [
+    ("black", ColorCode::Black, ColorCode::BrightBlack),
+    ("red", ColorCode::Red, ColorCode::BrightRed),
+    ("green", ColorCode::Green, ColorCode::BrightGreen),
+    ("yellow", ColorCode::Yellow, ColorCode::BrightYellow),
+    ("blue", ColorCode::Blue, ColorCode::BrightBlue),
+    ("magenta", ColorCode::Magenta, ColorCode::BrightMagenta),
+    ("cyan", ColorCode::Cyan, ColorCode::BrightCyan),
+    ("white", ColorCode::White, ColorCode::BrightWhite),
+];
+
+impl ColorCode {
+    /// Writes the SGR parameters selecting `self` as the foreground, or as the background if
+    /// `background` holds.
+    fn write_sgr(self, f: &mut fmt::Formatter<'_>, background: bool) -> fmt::Result {
+        let code = match self {
+            Self::Black => 30,
+            Self::Red => 31,
+            Self::Green => 32,
+            Self::Yellow => 33,
+            Self::Blue => 34,
+            Self::Magenta => 35,
+            Self::Cyan => 36,
+            Self::White => 37,
+            Self::BrightBlack => 90,
+            Self::BrightRed => 91,
+            Self::BrightGreen => 92,
+            Self::BrightYellow => 93,
+            Self::BrightBlue => 94,
+            Self::BrightMagenta => 95,
+            Self::BrightCyan => 96,
+            Self::BrightWhite => 97,
+            #[cfg(feature = "colorful")]
+            Self::Ansi256(index) => {
+                return write!(f, "{};5;{}", if background { 48 } else { 38 }, index);
+            }
+        };
+        write!(f, "{}", if background { code + 10 } else { code })
+    }
+}
+
+/// Error of parsing a [`ColorCode`] or a [`ColorSpec`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseColorError;
+
+impl Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid color")
+    }
+}
+
+impl FromStr for ColorCode {
+    type Err = ParseColorError;
+
+    /// Parses a color such as `red` or `bright-red`, or an index of the 256-color palette.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, bright) = match s.strip_prefix("bright-") {
+            Some(name) => (name, true),
+            None => (s, false),
+        };
+        if let Some(&(_, normal, bright_color)) = NAMES.iter().find(|(n, ..)| *n == name) {
+            return Ok(if bright { bright_color } else { normal });
+        }
+        #[cfg(feature = "colorful")]
+        if let Ok(index) = s.parse() {
+            return Ok(Self::Ansi256(index));
+        }
+        Err(ParseColorError)
+    }
+}
+
+/// Foreground, background and weight of text, which renders as the SGR escape sequence
+/// selecting them.
+///
+/// The sequence resets the attributes first, so that it replaces whatever spec is in effect.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ColorSpec {
+    pub fg: Option<ColorCode>,
+    pub bg: Option<ColorCode>,
+    pub bold: bool,
+}
+
+impl ColorSpec {
+    pub const fn fg(color: ColorCode) -> Self {
+        Self {
+            fg: Some(color),
+            bg: None,
+            bold: false,
+        }
+    }
+
+    pub const fn on(self, color: ColorCode) -> Self {
+        Self {
+            bg: Some(color),
+            ..self
+        }
+    }
+
+    pub const fn bold(self) -> Self {
+        Self { bold: true, ..self }
+    }
+}
+
+impl Display for ColorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\u{1B}[0")?;
+        if self.bold {
+            f.write_str(";1")?;
+        }
+        if let Some(fg) = self.fg {
+            f.write_str(";")?;
+            fg.write_sgr(f, false)?;
+        }
+        if let Some(bg) = self.bg {
+            f.write_str(";")?;
+            bg.write_sgr(f, true)?;
+        }
+        f.write_str("m")
+    }
+}
+
+impl FromStr for ColorSpec {
+    type Err = ParseColorError;
+
+    /// Parses a spec such as `red`, `red+bold` or `bright-white+on-blue`, where `plain` is the
+    /// spec without attributes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split('+').try_fold(Self::default(), |spec, part| {
+            Ok(match part {
+                "plain" => spec,
+                "bold" => spec.bold(),
+                _ => match part.strip_prefix("on-") {
+                    Some(color) => spec.on(color.parse()?),
+                    None => Self {
+                        fg: Some(part.parse()?),
+                        ..spec
+                    },
+                },
+            })
+        })
+    }
 }
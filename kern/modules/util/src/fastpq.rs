@@ -1,4 +1,7 @@
-use alloc::collections::VecDeque;
+use alloc::{
+    collections::{TryReserveError, VecDeque},
+    vec::Vec,
+};
 use spin::Mutex;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -13,6 +16,10 @@ impl FastPriority {
 
         Self(priority)
     }
+
+    pub const fn value(self) -> u8 {
+        self.0
+    }
 }
 
 pub struct FastPriorityQueue<P: Clone + Copy + Into<FastPriority>, I> {
@@ -43,6 +50,12 @@ impl<P: Clone + Copy + Into<FastPriority>, I> FastPriorityQueue<P, I> {
         self.queues[pri as usize].push_back((priority, item));
     }
 
+    /// Makes room for `additional` more items of `priority`, so that enqueuing them does not
+    /// allocate.
+    pub fn try_reserve(&mut self, priority: P, additional: usize) -> Result<(), TryReserveError> {
+        self.queues[priority.into().0 as usize].try_reserve(additional)
+    }
+
     pub fn dequeue(&mut self) -> Option<(P, I)> {
         if self.bits == 0 {
             return None;
@@ -57,6 +70,57 @@ impl<P: Clone + Copy + Into<FastPriority>, I> FastPriorityQueue<P, I> {
                 result
             })
     }
+
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Keeps only the items accepted by `filter`, in their order.
+    pub fn retain(&mut self, mut filter: impl FnMut(&P, &I) -> bool) {
+        for pri in 0..FastPriority::NUM {
+            if self.bits & (1 << pri) == 0 {
+                continue;
+            }
+            self.queues[pri].retain(|(priority, item)| filter(priority, item));
+            if self.queues[pri].is_empty() {
+                self.bits &= !(1 << pri);
+            }
+        }
+    }
+
+    /// Takes up to `count` items accepted by `filter` out of the queue, from the highest
+    /// priority down and oldest first, keeping the order of the remaining ones.
+    pub fn steal(&mut self, count: usize, mut filter: impl FnMut(&P, &I) -> bool) -> Vec<(P, I)>
+    where
+        I: Clone,
+    {
+        let mut stolen = Vec::new();
+        for pri in (0..FastPriority::NUM).rev() {
+            if stolen.len() >= count {
+                break;
+            }
+            if self.bits & (1 << pri) == 0 {
+                continue;
+            }
+            let queue = &mut self.queues[pri];
+            queue.retain(|(priority, item)| {
+                if stolen.len() < count && filter(priority, item) {
+                    stolen.push((*priority, item.clone()));
+                    false
+                } else {
+                    true
+                }
+            });
+            if queue.is_empty() {
+                self.bits &= !(1 << pri);
+            }
+        }
+        stolen
+    }
 }
 
 pub struct FastPriorityQueueWithLock<P: Clone + Copy + Into<FastPriority>, I> {
@@ -83,4 +147,13 @@ impl<P: Clone + Copy + Into<FastPriority>, I> FastPriorityQueueWithLock<P, I> {
     pub fn dequeue(&self) -> Option<(P, I)> {
         self.inner.lock().dequeue()
     }
+
+    pub fn retain(&self, filter: impl FnMut(&P, &I) -> bool) {
+        self.inner.lock().retain(filter);
+    }
+
+    /// Returns the number of items, unless the queue is locked.
+    pub fn try_len(&self) -> Option<usize> {
+        self.inner.try_lock().map(|queue| queue.len())
+    }
 }
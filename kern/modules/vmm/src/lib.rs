@@ -5,7 +5,7 @@ use jrinx_paging::{common::PageTable, GenericPageTable};
 use spin::{Lazy, RwLock};
 
 pub static KERN_PAGE_TABLE: Lazy<RwLock<PageTable>> =
-    Lazy::new(|| RwLock::new(PageTable::new().unwrap()));
+    Lazy::new(|| RwLock::new(PageTable::new_kernel().unwrap()));
 
 pub fn init() {
     hal!().vm().enable(KERN_PAGE_TABLE.read().addr());
@@ -0,0 +1,121 @@
+#![no_std]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate log;
+
+pub mod sntp;
+
+use alloc::collections::VecDeque;
+use core::{fmt::Display, time::Duration};
+
+use jrinx_hal::{hal, Cpu, Hal};
+use jrinx_sync::IrqSafeMutex;
+use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+use spin::Once;
+
+/// Number of most recent anchors kept for interpolation.
+pub const ANCHOR_CAPACITY: usize = 64;
+
+static SOURCE: Once<fn() -> Duration> = Once::new();
+
+static ANCHORS: IrqSafeMutex<VecDeque<Anchor>> =
+    IrqSafeMutex::new("wallclock-anchors", VecDeque::new());
+
+/// A monotonic timestamp paired with the wall-clock time read at that instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Anchor {
+    pub monotonic: Duration,
+    pub wallclock: Duration,
+}
+
+impl Display for Anchor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "monotonic={}ns wallclock={}ns",
+            self.monotonic.as_nanos(),
+            self.wallclock.as_nanos()
+        )
+    }
+}
+
+/// Registers the wall-clock source, returning the time elapsed since the UNIX epoch, and
+/// emits the first anchor.
+pub fn register(source: fn() -> Duration) -> Option<Anchor> {
+    SOURCE.call_once(|| source);
+    anchor()
+}
+
+/// Pairs the current monotonic time with the wall-clock time and logs the anchor, so that
+/// host tooling can map the timestamps of the surrounding log lines to UTC.
+///
+/// Returns `None` if no source is registered, or if the anchors are locked by the interrupted
+/// context, which happens when called from the panic path.
+pub fn anchor() -> Option<Anchor> {
+    let source = SOURCE.get()?;
+
+    let anchor = {
+        let mut anchors = ANCHORS.try_lock()?;
+        let anchor = Anchor {
+            monotonic: hal!().cpu().get_time(),
+            wallclock: source(),
+        };
+        if anchors.len() == ANCHOR_CAPACITY {
+            anchors.pop_front();
+        }
+        anchors.push_back(anchor);
+        anchor
+    };
+
+    info!("clock anchor: {}", anchor);
+    Some(anchor)
+}
+
+/// Emits an anchor every `period` on the current CPU, keeping a slewing wall clock in step.
+pub fn calibrate_every(period: Duration) {
+    fn rearm(time: Duration, period: Duration) {
+        TimedEvent::create(
+            time,
+            TimedEventHandler::new(
+                move || {
+                    anchor();
+                    rearm(time + period, period);
+                },
+                || {},
+            ),
+        );
+    }
+
+    rearm(hal!().cpu().get_time() + period, period);
+}
+
+pub fn latest() -> Option<Anchor> {
+    ANCHORS.lock().back().copied()
+}
+
+/// Maps a monotonic timestamp to wall-clock time.
+///
+/// Between two anchors, the wall clock is interpolated linearly, so a slew of the source
+/// between calibrations is spread over the interval instead of showing up as a step. Outside
+/// of the anchored interval, the nearest anchor is extrapolated at the monotonic rate.
+pub fn to_wallclock(monotonic: Duration) -> Option<Duration> {
+    let anchors = ANCHORS.lock();
+    let next = anchors.partition_point(|anchor| anchor.monotonic <= monotonic);
+
+    match (
+        next.checked_sub(1).map(|prev| anchors[prev]),
+        anchors.get(next),
+    ) {
+        (Some(prev), Some(next)) => {
+            let elapsed = (monotonic - prev.monotonic).as_nanos();
+            let interval = (next.monotonic - prev.monotonic).as_nanos();
+            let span = next.wallclock.saturating_sub(prev.wallclock).as_nanos();
+            Some(prev.wallclock + Duration::from_nanos((span * elapsed / interval) as u64))
+        }
+        (Some(prev), None) => Some(prev.wallclock + (monotonic - prev.monotonic)),
+        (None, Some(next)) => Some(next.wallclock.saturating_sub(next.monotonic - monotonic)),
+        (None, None) => None,
+    }
+}
@@ -0,0 +1,354 @@
+//! Simple Network Time Protocol (RFC 4330) client, for boards without a battery-backed RTC.
+//!
+//! The software wall clock it keeps is the monotonic clock plus an offset, slewed at
+//! [`SLEW_PPM`] unless off by more than [`STEP_THRESHOLD`].
+
+use core::{
+    fmt::Display,
+    future::Future,
+    net::Ipv4Addr,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use jrinx_hal::{hal, Cpu, Hal};
+use jrinx_sync::IrqSafeMutex;
+use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+use spin::Once;
+
+pub const NTP_PORT: u16 = 123;
+
+pub const PACKET_LEN: usize = 48;
+
+pub const QUERY_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Slew rate, in parts per million.
+pub const SLEW_PPM: i64 = 500;
+
+/// Offsets larger than this are stepped instead of slewed.
+pub const STEP_THRESHOLD: Duration = Duration::from_millis(128);
+
+const NTP_VERSION: u8 = 4;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+/// Seconds from the NTP epoch (1900) to the UNIX epoch (1970).
+const UNIX_EPOCH: u64 = 2_208_988_800;
+
+static SERVER: Once<Ipv4Addr> = Once::new();
+
+static DISCIPLINE: IrqSafeMutex<Discipline> = IrqSafeMutex::new(
+    "sntp-discipline",
+    Discipline {
+        since: Duration::ZERO,
+        offset: 0,
+        target: 0,
+        synced_at: None,
+        error: Duration::ZERO,
+    },
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SntpError {
+    /// No reply arrived within the timeout.
+    Timeout,
+    /// The reply is not a well-formed server packet.
+    Malformed,
+    /// The reply does not answer the last request.
+    Mismatch,
+    /// The server asked the client to go away (stratum 0).
+    KissOfDeath,
+    /// The server is not synchronized itself.
+    Unsynchronized,
+}
+
+/// Fields of an NTP packet used by SNTP, with timestamps since the UNIX epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub leap: u8,
+    pub version: u8,
+    pub mode: u8,
+    pub stratum: u8,
+    pub originate: Duration,
+    pub receive: Duration,
+    pub transmit: Duration,
+}
+
+impl Packet {
+    pub fn request(transmit: Duration) -> Self {
+        Self {
+            leap: 0,
+            version: NTP_VERSION,
+            mode: MODE_CLIENT,
+            stratum: 0,
+            originate: Duration::ZERO,
+            receive: Duration::ZERO,
+            transmit,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; PACKET_LEN] {
+        let mut buf = [0; PACKET_LEN];
+        buf[0] = (self.leap & 0x3) << 6 | (self.version & 0x7) << 3 | (self.mode & 0x7);
+        buf[1] = self.stratum;
+        buf[24..32].copy_from_slice(&to_ntp(self.originate).to_be_bytes());
+        buf[32..40].copy_from_slice(&to_ntp(self.receive).to_be_bytes());
+        buf[40..48].copy_from_slice(&to_ntp(self.transmit).to_be_bytes());
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, SntpError> {
+        if buf.len() < PACKET_LEN {
+            return Err(SntpError::Malformed);
+        }
+        let timestamp =
+            |at: usize| from_ntp(u64::from_be_bytes(buf[at..at + 8].try_into().unwrap()));
+
+        Ok(Self {
+            leap: buf[0] >> 6,
+            version: (buf[0] >> 3) & 0x7,
+            mode: buf[0] & 0x7,
+            stratum: buf[1],
+            originate: timestamp(24),
+            receive: timestamp(32),
+            transmit: timestamp(40),
+        })
+    }
+}
+
+fn to_ntp(time: Duration) -> u64 {
+    if time.is_zero() {
+        return 0;
+    }
+    let secs = (time.as_secs() + UNIX_EPOCH) as u32 as u64;
+    let frac = ((time.subsec_nanos() as u64) << 32) / 1_000_000_000;
+    secs << 32 | frac
+}
+
+fn from_ntp(timestamp: u64) -> Duration {
+    if timestamp == 0 {
+        return Duration::ZERO;
+    }
+    let mut secs = timestamp >> 32;
+    // Timestamps with the top bit clear belong to era 1, which starts in 2036.
+    if secs & (1 << 31) == 0 {
+        secs += 1 << 32;
+    }
+    let nanos = ((timestamp & 0xffff_ffff) * 1_000_000_000) >> 32;
+    Duration::new(secs - UNIX_EPOCH, nanos as u32)
+}
+
+/// Offset of the server clock from [`now`] and round-trip delay of a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub offset: i64,
+    pub delay: Duration,
+}
+
+impl Sample {
+    /// Computes the sample from the four timestamps of RFC 4330.
+    pub fn new(t1: Duration, t2: Duration, t3: Duration, t4: Duration) -> Self {
+        let [t1, t2, t3, t4] = [t1, t2, t3, t4].map(|t| t.as_nanos() as i128);
+        Self {
+            offset: (((t2 - t1) + (t3 - t4)) / 2) as i64,
+            delay: Duration::from_nanos(((t4 - t1) - (t3 - t2)).max(0) as u64),
+        }
+    }
+}
+
+pub trait Transport {
+    /// Sends `request` to `server` and waits up to `timeout` for the reply.
+    fn exchange(
+        &mut self,
+        server: Ipv4Addr,
+        request: &[u8; PACKET_LEN],
+        timeout: Duration,
+    ) -> impl Future<Output = Result<[u8; PACKET_LEN], SntpError>>;
+}
+
+struct Discipline {
+    since: Duration,
+    offset: i64,
+    target: i64,
+    synced_at: Option<Duration>,
+    error: Duration,
+}
+
+impl Discipline {
+    fn offset_at(&self, monotonic: Duration) -> i64 {
+        let elapsed = monotonic.saturating_sub(self.since).as_nanos() as i64;
+        let budget = elapsed.saturating_mul(SLEW_PPM) / 1_000_000;
+        self.offset + (self.target - self.offset).clamp(-budget, budget)
+    }
+}
+
+/// Synchronization state of the software wall clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    /// Monotonic time of the last successful query.
+    pub synced_at: Option<Duration>,
+    /// Offset of the software wall clock from the monotonic clock.
+    pub offset: i64,
+    /// Part of the last sample not slewed in yet.
+    pub slewing: i64,
+    /// Half the round-trip delay of the last sample.
+    pub error: Duration,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.synced_at {
+            Some(synced_at) => write!(
+                f,
+                "synced-at={:?} offset={}ns slewing={}ns error={:?}",
+                synced_at, self.offset, self.slewing, self.error
+            ),
+            None => write!(f, "unsynchronized, monotonic only"),
+        }
+    }
+}
+
+pub fn set_server(server: Ipv4Addr) {
+    SERVER.call_once(|| server);
+}
+
+pub fn server() -> Option<Ipv4Addr> {
+    SERVER.get().copied()
+}
+
+/// Returns the software wall clock.
+pub fn now() -> Duration {
+    let monotonic = hal!().cpu().get_time();
+    let offset = DISCIPLINE.lock().offset_at(monotonic);
+    Duration::from_nanos((monotonic.as_nanos() as i64 + offset).max(0) as u64)
+}
+
+pub fn status() -> Status {
+    let monotonic = hal!().cpu().get_time();
+    let discipline = DISCIPLINE.lock();
+    let offset = discipline.offset_at(monotonic);
+    Status {
+        synced_at: discipline.synced_at,
+        offset,
+        slewing: discipline.target - offset,
+        error: discipline.error,
+    }
+}
+
+pub fn adjust(sample: Sample) {
+    let monotonic = hal!().cpu().get_time();
+
+    let (first, stepped) = {
+        let mut discipline = DISCIPLINE.lock();
+        let current = discipline.offset_at(monotonic);
+        let first = discipline.synced_at.is_none();
+        let stepped = first || sample.offset.unsigned_abs() > STEP_THRESHOLD.as_nanos() as u64;
+
+        discipline.offset = if stepped {
+            current + sample.offset
+        } else {
+            current
+        };
+        discipline.target = current + sample.offset;
+        discipline.since = monotonic;
+        discipline.synced_at = Some(monotonic);
+        discipline.error = sample.delay / 2;
+        (first, stepped)
+    };
+
+    info!(
+        "ntp: offset {}ns, delay {:?}, {}",
+        sample.offset,
+        sample.delay,
+        if stepped { "stepped" } else { "slewing" }
+    );
+
+    if first {
+        if crate::SOURCE.get().is_some() {
+            warn!("ntp: wall clock already has a source, keeping it");
+        }
+        crate::register(now);
+    } else {
+        crate::anchor();
+    }
+}
+
+/// Queries `server` once and applies the sample to the software wall clock.
+pub async fn sync(
+    transport: &mut impl Transport,
+    server: Ipv4Addr,
+    timeout: Duration,
+) -> Result<Sample, SntpError> {
+    let t1 = now();
+    let request = Packet::request(t1).encode();
+    let reply = transport.exchange(server, &request, timeout).await?;
+    let t4 = now();
+
+    // The server copies the transmit timestamp of the request verbatim.
+    if reply[24..32] != request[40..48] {
+        return Err(SntpError::Mismatch);
+    }
+    let reply = Packet::decode(&reply)?;
+    if reply.mode != MODE_SERVER || reply.version == 0 || reply.transmit.is_zero() {
+        return Err(SntpError::Malformed);
+    }
+    if reply.stratum == 0 {
+        return Err(SntpError::KissOfDeath);
+    }
+    if reply.leap == LEAP_UNSYNCHRONIZED || reply.stratum > 15 {
+        return Err(SntpError::Unsynchronized);
+    }
+
+    let sample = Sample::new(t1, reply.receive, reply.transmit, t4);
+    adjust(sample);
+    Ok(sample)
+}
+
+/// Queries the configured server at boot and every [`QUERY_PERIOD`] thereafter.
+pub async fn run(mut transport: impl Transport) {
+    let Some(server) = server() else {
+        return;
+    };
+
+    loop {
+        let next = hal!().cpu().get_time() + QUERY_PERIOD;
+        if let Err(err) = sync(&mut transport, server, QUERY_TIMEOUT).await {
+            warn!("ntp: query to {} failed: {:?}, {}", server, err, status());
+        }
+        Sleep::new(next).await;
+    }
+}
+
+struct Sleep {
+    until: Duration,
+    armed: bool,
+}
+
+impl Sleep {
+    fn new(until: Duration) -> Self {
+        Self {
+            until,
+            armed: false,
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if hal!().cpu().get_time() >= self.until {
+            return Poll::Ready(());
+        }
+        if !self.armed {
+            let waker = cx.waker().clone();
+            TimedEvent::create(self.until, TimedEventHandler::new(|| waker.wake(), || {}));
+            self.armed = true;
+        }
+        Poll::Pending
+    }
+}
@@ -1,10 +1,26 @@
-use core::alloc::{Allocator, Layout};
+use core::{
+    alloc::{Allocator, Layout},
+    time::Duration,
+};
 
-use alloc::alloc::Global;
+use alloc::{alloc::Global, collections::BTreeSet};
 use fdt::{node::FdtNode, Fdt};
 use jrinx_addr::VirtAddr;
+use jrinx_error::{InternalError, Result};
 use jrinx_hal::{Cpu, Hal};
+use jrinx_multitask::runtime::Runtime;
 use sbi::base::{probe_extension, ExtensionAvailability};
+use spin::{Mutex, Once};
+
+/// Longest wait of [`online`] for the runtime of the hart to come up.
+const ONLINE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Harts brought up at boot, the boot hart included.
+static BOOT_NPROC: Once<usize> = Once::new();
+
+/// Harts started by the kernel, and the ones of them onlined after boot.
+static STARTED: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+static LATE: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
 
 fn is_cpu(node: &FdtNode) -> bool {
     node.name == "cpu" || node.name.starts_with("cpu@")
@@ -17,6 +33,14 @@ fn is_valid_cpu(node: &FdtNode) -> bool {
             .is_some_and(|prop| prop.as_str().is_some_and(|status| status != "okay"))
 }
 
+fn cpu_id(node: &FdtNode) -> Option<usize> {
+    if node.name == "cpu" {
+        Some(0)
+    } else {
+        node.name.strip_prefix("cpu@")?.parse().ok()
+    }
+}
+
 pub fn init(fdt: &Fdt<'_>) {
     let node = fdt.find_all_nodes("/cpus").next().unwrap();
 
@@ -36,38 +60,99 @@ pub fn init(fdt: &Fdt<'_>) {
         .set_nproc_valid(node.children().filter(is_valid_cpu).count());
 }
 
-pub(in crate::arch) fn start(fdt: &Fdt<'_>) {
+/// Starts the valid harts other than the boot one, up to `max` harts in total if given.
+pub(in crate::arch) fn start(fdt: &Fdt<'_>, max: Option<usize>) {
     let node = fdt.find_all_nodes("/cpus").next().unwrap();
+    let local_id = hal!().cpu().id();
+    STARTED.lock().insert(local_id);
 
     if let ExtensionAvailability::Available(_) = probe_extension(sbi::hsm::EXTENSION_ID) {
-        for cpu in node.children().filter(is_valid_cpu) {
-            let id = if cpu.name == "cpu" {
-                0
-            } else if cpu.name.starts_with("cpu@") {
-                cpu.name[4..].parse::<usize>().unwrap()
-            } else {
-                continue;
-            };
-            if id == hal!().cpu().id() {
+        for id in node
+            .children()
+            .filter(is_valid_cpu)
+            .filter_map(|cpu| cpu_id(&cpu))
+        {
+            if id == local_id {
                 continue;
             }
-            let entry = VirtAddr::new(super::_sencondary_start as usize);
-            let stack_top = VirtAddr::new(
-                Global
-                    .allocate(
-                        Layout::from_size_align(jrinx_config::KSTACK_SIZE, jrinx_config::PAGE_SIZE)
-                            .unwrap(),
-                    )
-                    .unwrap()
-                    .as_ptr()
-                    .cast::<u8>() as usize,
-            ) + jrinx_config::KSTACK_SIZE;
-            sbi::hsm::hart_start(
-                id,
-                entry.to_phys().as_usize(),
-                stack_top.to_phys().as_usize(),
-            )
-            .unwrap();
+            if max.is_some_and(|max| STARTED.lock().len() >= max) {
+                break;
+            }
+            hart_start(id).unwrap();
+            STARTED.lock().insert(id);
+        }
+    }
+
+    BOOT_NPROC.call_once(|| STARTED.lock().len());
+}
+
+/// Number of harts brought up at boot, the boot hart included.
+pub fn nproc_booted() -> usize {
+    *BOOT_NPROC.get().unwrap()
+}
+
+/// Whether hart `cpu_id` was onlined after boot.
+pub fn is_late(cpu_id: usize) -> bool {
+    LATE.lock().contains(&cpu_id)
+}
+
+/// Onlines hart `cpu_id` after boot, returning once its runtime is up.
+///
+/// This fails with [`InternalError::InvalidCpuId`] if the hart is not a valid one or cannot be
+/// started, and with [`InternalError::InvalidRuntimeStatus`] if it is already started or its
+/// runtime does not come up in time.
+pub fn online(cpu_id: usize) -> Result<()> {
+    let fdt = crate::boot_fdt();
+    let node = fdt.find_all_nodes("/cpus").next().unwrap();
+    if !node
+        .children()
+        .filter(is_valid_cpu)
+        .any(|cpu| cpu_id(&cpu) == Some(cpu_id))
+    {
+        return Err(InternalError::InvalidCpuId(cpu_id));
+    }
+
+    if !STARTED.lock().insert(cpu_id) {
+        return Err(InternalError::InvalidRuntimeStatus);
+    }
+    LATE.lock().insert(cpu_id);
+    if let Err(err) = hart_start(cpu_id) {
+        LATE.lock().remove(&cpu_id);
+        STARTED.lock().remove(&cpu_id);
+        return Err(err);
+    }
+    info!("cpu#{} onlined", cpu_id);
+
+    let deadline = hal!().cpu().get_time() + ONLINE_TIMEOUT;
+    while Runtime::with_spec_cpu(cpu_id, |_| ()).is_err() {
+        if hal!().cpu().get_time() >= deadline {
+            return Err(InternalError::InvalidRuntimeStatus);
         }
+        core::hint::spin_loop();
     }
+    Ok(())
+}
+
+fn hart_start(id: usize) -> Result<()> {
+    let ExtensionAvailability::Available(_) = probe_extension(sbi::hsm::EXTENSION_ID) else {
+        return Err(InternalError::InvalidCpuId(id));
+    };
+
+    let layout =
+        Layout::from_size_align(jrinx_config::KSTACK_SIZE, jrinx_config::PAGE_SIZE).unwrap();
+    let stack = Global
+        .allocate(layout)
+        .map_err(|_| InternalError::NotEnoughMem)?
+        .cast::<u8>();
+    let entry = VirtAddr::new(super::_sencondary_start as usize);
+    let stack_top = VirtAddr::new(stack.as_ptr() as usize) + jrinx_config::KSTACK_SIZE;
+    sbi::hsm::hart_start(
+        id,
+        entry.to_phys().as_usize(),
+        stack_top.to_phys().as_usize(),
+    )
+    .map_err(|_| {
+        unsafe { Global.deallocate(stack, layout) };
+        InternalError::InvalidCpuId(id)
+    })
 }
@@ -2,6 +2,7 @@ pub mod cpus;
 
 use fdt::Fdt;
 use jrinx_addr::PhysAddr;
+use jrinx_hal::earlycon::{self, EarlyconBackend};
 use jrinx_paging::boot::BootPageTable;
 use riscv::register::{sie, sstatus};
 
@@ -71,6 +72,14 @@ unsafe extern "C" fn secondary_init() -> ! {
     crate::secondary_init();
 }
 
-pub fn secondary_boot(fdt: &Fdt) {
-    cpus::start(fdt);
+/// Prefers the SBI debug console as the early console, unless the firmware names a UART for
+/// the standard output, which the legacy console calls keep driving.
+pub fn earlycon_init(fdt: &Fdt) {
+    if fdt.chosen().stdout().is_some() || !earlycon::select(EarlyconBackend::DebugConsole) {
+        earlycon::select(EarlyconBackend::Legacy);
+    }
+}
+
+pub fn secondary_boot(fdt: &Fdt, max: Option<usize>) {
+    cpus::start(fdt, max);
 }
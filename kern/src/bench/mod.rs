@@ -0,0 +1,19 @@
+use alloc::vec::Vec;
+
+mod task;
+mod timer;
+
+/// Runs the benches matching the glob `pattern` in sorted order, logging a line for each.
+pub async fn run(pattern: &str) {
+    let mut benches = jrinx_benchdef::find_all(pattern).collect::<Vec<_>>();
+    if benches.is_empty() {
+        panic!("bench pattern matches no bench: {}", pattern);
+    }
+    benches.sort_unstable_by_key(|bench| bench.name());
+    for bench in benches {
+        match bench.run().await {
+            Some(summary) => info!("bench {} ... {}", bench.name(), summary),
+            None => warn!("bench {} ... measured nothing", bench.name()),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+pub(super) mod spawn_join {
+    use jrinx_benchdef::{benchdef, Bencher};
+    use jrinx_multitask::spawn;
+
+    #[benchdef]
+    async fn bench(bencher: &mut Bencher) {
+        bencher.iter_async(|| spawn!(async {})).await;
+    }
+}
+
+pub(super) mod yield_now {
+    use jrinx_benchdef::{benchdef, Bencher};
+    use jrinx_multitask::yield_now;
+
+    /// Measures a round trip through the executor, which polls the task again at once.
+    #[benchdef]
+    async fn bench(bencher: &mut Bencher) {
+        bencher.iter_async(|| async { yield_now!() }).await;
+    }
+}
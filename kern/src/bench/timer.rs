@@ -0,0 +1,53 @@
+pub(super) mod insert_cancel {
+    use core::time::Duration;
+
+    use jrinx_benchdef::{benchdef, Bencher};
+    use jrinx_timed_event::wheel::TimingWheel;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    const ACTIVE_MAX: u64 = 10000;
+
+    /// Arms and cancels a timer on a wheel already holding [`ACTIVE_MAX`] of them, spread over
+    /// the levels and the overflow.
+    #[benchdef]
+    async fn bench(bencher: &mut Bencher) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut wheel = TimingWheel::new();
+        for key in 0..ACTIVE_MAX {
+            wheel.insert(Duration::from_micros(rng.gen_range(0..100000000000)), key);
+        }
+        bencher.iter(|| {
+            let time = Duration::from_micros(rng.gen_range(0..100000000000));
+            let handle = wheel.insert(time, ACTIVE_MAX);
+            wheel.remove(handle)
+        });
+    }
+}
+
+pub(super) mod cancel_earliest {
+    use core::time::Duration;
+
+    use jrinx_benchdef::{benchdef, Bencher};
+    use jrinx_timed_event::wheel::TimingWheel;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    const ACTIVE_MAX: u64 = 10000;
+
+    /// Arms and cancels the earliest timer of a wheel holding [`ACTIVE_MAX`] of them, which
+    /// makes the wheel look its next deadline up again.
+    #[benchdef]
+    async fn bench(bencher: &mut Bencher) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut wheel = TimingWheel::new();
+        for key in 0..ACTIVE_MAX {
+            wheel.insert(
+                Duration::from_micros(rng.gen_range(1000..100000000000)),
+                key,
+            );
+        }
+        bencher.iter(|| {
+            let handle = wheel.insert(Duration::ZERO, ACTIVE_MAX);
+            wheel.remove(handle)
+        });
+    }
+}
@@ -1,53 +1,199 @@
 use alloc::{borrow::ToOwned, collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
-use core::num::ParseIntError;
+use core::{fmt, num::ParseIntError, time::Duration};
 
 use getargs::{Opt, Options};
 use jrinx_a653::{
+    lowmem::LowMemAction,
     partition::{Partition, PartitionConfig, PartitionId, PartitionTypeConfig},
-    process::{Process, ProcessRunner},
+    process::{Process, ProcessExit, ProcessRunner},
+    semaphore::{SemaphoreConfig, SemaphoreProtocol},
 };
+use jrinx_abi::cap::Capabilities;
 use jrinx_apex::*;
-use jrinx_hal::{Cpu, Hal};
+use jrinx_benchdef::BenchDef;
+use jrinx_hal::{Cpu, Hal, HaltReason};
 use jrinx_multitask::{
-    inspector::Inspector,
+    executor,
+    inspector::{Inspector, ResourceLimits},
     runtime::{Runtime, RuntimeSchedTable, RuntimeSchedTableEntry},
-    spawn, yield_now,
+    spawn,
 };
+use jrinx_testdef::{TestDef, TestFn};
+use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+use jrinx_util::color::ColorSpec;
 use spin::Once;
 
 static BOOTARGS: Once<String> = Once::new();
 
+/// Error of splitting boot arguments into words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ParseError {
+    /// The quote at `column`, counted in characters from 1, is never closed.
+    UnterminatedQuote { quote: char, column: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnterminatedQuote { quote, column } => {
+                write!(f, "unterminated {} quote at column {}", quote, column)
+            }
+        }
+    }
+}
+
 pub(super) fn set(bootargs: &str) {
     BOOTARGS
         .try_call_once::<_, ()>(|| Ok(bootargs.to_owned()))
         .unwrap();
 }
 
+/// Finds the test selected by `bootargs` without allocating, for use before the heap exists.
+///
+/// Quotes are not understood here, which no test name needs.
+pub(super) fn early_test(bootargs: &str) -> Option<&str> {
+    let mut args = bootargs.split_whitespace();
+    while let Some(arg) = args.next() {
+        match arg {
+            "-t" | "--test" => return args.next(),
+            _ => {
+                if let Some(test) = arg.strip_prefix("--test=") {
+                    return Some(test);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Number of CPUs to bring up at boot, as limited by `--boot-cpus`.
+pub(super) fn boot_cpus() -> Option<usize> {
+    let args = tokenize(BOOTARGS.get()?).ok()?;
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        let value = match arg {
+            "--boot-cpus" => args.next(),
+            _ => arg.strip_prefix("--boot-cpus="),
+        };
+        if let Some(value) = value {
+            return Some(value.parse().unwrap_or_else(|err| {
+                panic!("invalid argument for option: --boot-cpus, {}", err)
+            }));
+        }
+    }
+    None
+}
+
+/// Whether the system calls of initial processes are traced, as set by `--strace`.
+pub(super) fn strace() -> bool {
+    BOOTARGS.get().is_some_and(|bootargs| {
+        tokenize(bootargs).is_ok_and(|args| args.iter().any(|arg| arg == "--strace"))
+    })
+}
+
+/// Splits `bootargs` into words at whitespace, as a shell does.
+///
+/// Single quotes keep everything up to the closing quote as is, while double quotes let `\"`
+/// and `\\` stand for a quote and a backslash. Backslashes are kept as is anywhere else, so
+/// unquoted words are split exactly as by [`str::split_whitespace`].
+pub(super) fn tokenize(bootargs: &str) -> Result<Vec<String>, ParseError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = bootargs.chars().zip(1..);
+    while let Some((c, column)) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                let unterminated = ParseError::UnterminatedQuote { quote: c, column };
+                loop {
+                    match chars.next().ok_or(unterminated)? {
+                        (quoted, _) if quoted == c => break,
+                        ('\\', _) if c == '"' => match chars.next().ok_or(unterminated)? {
+                            (escaped @ ('"' | '\\'), _) => word.push(escaped),
+                            (escaped, _) => {
+                                word.push('\\');
+                                word.push(escaped);
+                            }
+                        },
+                        (quoted, _) => word.push(quoted),
+                    }
+                }
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
 pub async fn execute() {
     if let Some(bootargs) = BOOTARGS.get() {
-        let args = bootargs
-            .split_whitespace()
-            .map(|s| s.to_owned())
-            .collect::<Vec<_>>();
+        let args = tokenize(bootargs).unwrap_or_else(|err| panic!("invalid bootargs: {}", err));
+        set_log_config(&args);
         let mut opts = Options::new(args.iter().map(String::as_str));
 
         info!("bootargs: {}", bootargs.replace("--", "\n\t--"));
 
         let mut partitions: Vec<Arc<Partition>> = Vec::new();
+        let mut tests: Vec<&str> = Vec::new();
+        let mut list_tests = false;
+        let mut shell = false;
+        let mut test_jobs = None;
+        let mut benches: Vec<&str> = Vec::new();
+        let mut init_program = None;
 
         while let Some(opt) = opts.next_opt().unwrap() {
             match opt {
                 Opt::Short('h') | Opt::Long("help") => help().await,
 
                 Opt::Short('t') | Opt::Long("test") => {
-                    test(match opts.value() {
-                        Ok(opt) => opt,
+                    match opts.value() {
+                        Ok("help") => test("help").await,
+                        Ok(opt) => tests.extend(opt.split(',')),
                         _ => {
                             panic!("missing argument for option: {opt}, try '-t/--test help' for more information");
                         }
-                    }).await;
+                    }
+                }
+
+                Opt::Long("test-all") => tests.push("*"),
+
+                Opt::Long("bench") => match opts.value() {
+                    Ok("help") => bench_help(),
+                    Ok(opt) => benches.push(opt),
+                    _ => {
+                        panic!("missing argument for option: {opt}, try '--bench help' for more information");
+                    }
+                },
+
+                Opt::Long("list-tests") => list_tests = true,
+
+                Opt::Long("shell") => shell = true,
+
+                Opt::Long("gdb") => {
+                    jrinx_trap::gdb::enable();
+                    info!("waiting for gdb on the console");
+                    hal!().breakpoint();
                 }
 
+                Opt::Long("test-jobs") => match opts.value() {
+                    Ok(opt) => test_jobs = Some(opt.parse::<usize>().ok().filter(|&jobs| jobs > 0).unwrap_or_else(|| {
+                        panic!("invalid argument for option: {opt}, expected a positive number of jobs")
+                    })),
+                    _ => {
+                        panic!("missing argument for option: {opt}");
+                    }
+                },
+
+                Opt::Long("init") => match opts.value() {
+                    Ok("help") => init("help", &[]).await,
+                    Ok(opt) => init_program = Some(opt),
+                    _ => {
+                        panic!("missing argument for option: {opt}, try '--init help' for more information");
+                    }
+                },
+
                 Opt::Long("partition") => {
                     if let Some(partition) = partition(match opts.value() {
                         Ok(opt) => opt,
@@ -59,6 +205,55 @@ pub async fn execute() {
                     }
                 }
 
+                Opt::Long("lowmem-policy") => lowmem_policy(match opts.value() {
+                    Ok(opt) => opt,
+                    _ => {
+                        panic!("missing argument for option: {opt}, try '--lowmem-policy help' for more information");
+                    }
+                }).await,
+
+                Opt::Long("set") => set_tunable(match opts.value() {
+                    Ok(opt) => opt,
+                    _ => {
+                        panic!("missing argument for option: {opt}, try '--set help' for more information");
+                    }
+                }).await,
+
+                // Taken into account when creating initial processes.
+                Opt::Long("strace") => {}
+
+                // Taken into account before any other option.
+                Opt::Short('l')
+                | Opt::Long("log-level")
+                | Opt::Long("log-filter")
+                | Opt::Long("log-colors") => {
+                    if opts.value().is_err() {
+                        panic!("missing argument for option: {opt}");
+                    }
+                }
+                Opt::Long("no-color") => {}
+
+                // Taken into account at boot already.
+                Opt::Long("boot-cpus") => {
+                    if opts.value().is_err() {
+                        panic!("missing argument for option: {opt}");
+                    }
+                }
+
+                Opt::Long("timeout") => watchdog(match opts.value() {
+                    Ok(opt) => opt,
+                    _ => {
+                        panic!("missing argument for option: {opt}");
+                    }
+                }),
+
+                Opt::Long("ntp") => ntp(match opts.value() {
+                    Ok(opt) => opt,
+                    _ => {
+                        panic!("missing argument for option: {opt}, try '--ntp help' for more information");
+                    }
+                }).await,
+
                 Opt::Long("scheduler") => {
                     if let Some((cpu_id, sched_table, inspectors)) = scheduler(match opts.value() {
                         Ok(opt) => opt,
@@ -78,6 +273,50 @@ pub async fn execute() {
                 Opt::Short(_) | Opt::Long(_) => panic!("unrecognized option: {}", opt),
             };
         }
+
+        // Benches run ahead of tests, which may shut the system down once over.
+        for pattern in benches {
+            crate::bench::run(pattern).await;
+        }
+
+        // Tests run once the other options are in effect. A single one runs as it always has, a
+        // panic in it failing the boot, while several, or any given `--test-jobs`, are run in
+        // isolation from one another.
+        if list_tests {
+            crate::test::list(if tests.is_empty() { &["*"] } else { &tests[..] });
+        } else {
+            match tests[..] {
+                [] => {}
+                [name] if !jrinx_testdef::is_glob(name) && test_jobs.is_none() => test(name).await,
+                _ => {
+                    let failed = match test_jobs {
+                        Some(jobs) => crate::test::run_parallel(&tests, jobs).await,
+                        None => crate::test::run_all(&tests).await,
+                    };
+                    // The shell is left to halt the system instead.
+                    if !shell {
+                        Runtime::shutdown(match failed {
+                            0 => HaltReason::NormalExit,
+                            failed => HaltReason::TestFailure(failed as u32),
+                        })
+                    }
+                }
+            }
+        }
+
+        // The shell starts last, as the init program would never let it run.
+        if shell {
+            if init_program.is_some() {
+                panic!("--shell and --init cannot be given together");
+            }
+            spawn!(name = "shell", crate::shell::run());
+        }
+
+        // Arguments after `--` are for the init program, which runs last as it never returns.
+        let init_args = opts.positionals().collect::<Vec<_>>();
+        if let Some(program) = init_program {
+            init(program, &init_args).await;
+        }
     }
 }
 
@@ -87,29 +326,241 @@ async fn help() {
     info!("                           * use '--partition help' for more information");
     info!("       --scheduler <opts>  Create a scheduler to schedule partitions");
     info!("                           * use '--scheduler help' for more information");
-    info!("   -t, --test <test>       Run the specified test");
+    info!("       --lowmem-policy <action>");
+    info!("                           Set the action on partitions exhausting the kernel heap");
+    info!("                           * use '--lowmem-policy help' for more information");
+    info!("       --set <key>=<value> Set a tunable");
+    info!("                           * use '--set help' for more information");
+    info!("       --boot-cpus <num>   Bring up at most <num> CPUs at boot");
+    info!("                           * the others may be onlined later");
+    info!("       --ntp <ip>          Synchronize the wall clock with an NTP server");
+    info!("                           * use '--ntp help' for more information");
+    info!(
+        "   -l, --log-level <level> Log records up to <level>, one of {}",
+        LOG_LEVELS
+    );
+    info!("       --log-filter <module>=<level>,...");
+    info!("                           Log records of each <module> up to its <level> instead");
+    info!("       --log-colors <level>=<color>,...");
+    info!("                           Color records of each <level> as <color> instead");
+    info!("                           * <color> is e.g. 'red', 'bright-red+bold' or 'plain'");
+    info!("                           * a background is given as '+on-<color>'");
+    info!("       --no-color          Log without colors");
+    info!("       --init <program>    Run the user program as the init process");
+    info!("                           * use '--init help' for more information");
+    info!("       --strace            Trace the system calls of initial processes");
+    info!("                           * inherited by the processes they create");
+    info!("       --timeout <ms>      Fail the run unless the system shuts down within <ms>");
+    info!("   -t, --test <test>,...   Run the specified tests, in order");
+    info!("                           * may be given several times");
+    info!("                           * several tests are isolated and summarized");
+    info!("                           * <test> may be a pattern with '*' and '?'");
+    info!("       --test-all          Run all tests, as '--test *'");
+    info!("       --list-tests        List the selected (or all) tests instead of running them");
+    info!("       --test-jobs <n>     Run the selected tests on up to <n> CPUs at once");
+    info!("                           * serial tests and test groups run after, on CPU 0");
+    info!("       --shell             Take commands from the console, after the other options");
+    info!("                           * instead of halting after several tests");
+    info!("                           * type 'help' at the prompt for the commands");
+    info!("       --gdb               Stop for gdb on the console, at this option");
+    info!("                           * breakpoint traps stop for gdb from then on");
+    info!("       --bench <pattern>   Run the benches matching <pattern>, before any test");
+    info!("                           * use '--bench help' for more information");
     info!("   -h, --help              Display this information");
+    info!("values with spaces may be quoted with '...' or \"...\"");
+}
+
+/// Installs the log level, filters and colors set by `-l/--log-level`, `--log-filter`,
+/// `--log-colors` and `--no-color`, ahead of the other options so that these log accordingly.
+fn set_log_config(args: &[String]) {
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        let (option, value) = match arg {
+            "--no-color" => {
+                jrinx_logging::set_color_enabled(false);
+                continue;
+            }
+            "-l" | "--log-level" => ("--log-level", args.next()),
+            "--log-filter" | "--log-colors" => (arg, args.next()),
+            _ => match arg.split_once('=') {
+                Some((option @ ("--log-level" | "--log-filter" | "--log-colors"), value)) => {
+                    (option, Some(value))
+                }
+                _ => continue,
+            },
+        };
+        let Some(value) = value else {
+            continue;
+        };
+        match option {
+            "--log-level" => jrinx_logging::set_level(parse_log_level(value)),
+            "--log-filter" => jrinx_logging::set_filters(parse_log_filters(value)),
+            _ => jrinx_logging::set_colors(parse_log_colors(value)),
+        }
+    }
+}
+
+const LOG_LEVELS: &str = "off, error, warn, info, debug, trace";
+
+fn parse_log_level(level: &str) -> log::LevelFilter {
+    level.parse().unwrap_or_else(|_| {
+        panic!(
+            "invalid log level: {:?}, expected one of {}",
+            level, LOG_LEVELS
+        )
+    })
+}
+
+fn parse_log_filters(filters: &str) -> Vec<(String, log::LevelFilter)> {
+    filters
+        .split(',')
+        .map(|filter| {
+            let (module, level) = filter
+                .split_once('=')
+                .unwrap_or_else(|| panic!("invalid log filter: {:?}", filter));
+            (module.to_owned(), parse_log_level(level))
+        })
+        .collect()
+}
+
+fn parse_log_colors(colors: &str) -> Vec<(log::Level, ColorSpec)> {
+    colors
+        .split(',')
+        .map(|color| {
+            let (level, spec) = color
+                .split_once('=')
+                .unwrap_or_else(|| panic!("invalid log color: {:?}", color));
+            let level = level.parse().unwrap_or_else(|_| {
+                panic!(
+                    "invalid log level: {:?}, expected one of error, warn, info, debug, trace",
+                    level
+                )
+            });
+            let spec = spec
+                .parse()
+                .unwrap_or_else(|_| panic!("invalid color of {} records: {:?}", level, spec));
+            (level, spec)
+        })
+        .collect()
 }
 
 async fn test(args: &str) {
     if args == "help" {
         info!("all available tests:");
-        let mut all_tests = jrinx_testdef::all().collect::<Vec<_>>();
+        let mut all_tests = jrinx_testdef::all().map(TestDef::name).collect::<Vec<_>>();
         all_tests.sort();
         all_tests.iter().for_each(|test| info!("- {test}"));
     } else {
-        let test = args;
-        let (name, func) =
-            jrinx_testdef::find(test).unwrap_or_else(|| panic!("unrecognized test case: {}", test));
+        let test =
+            jrinx_testdef::find(args).unwrap_or_else(|| panic!("unrecognized test case: {}", args));
+        // Only a test run in isolation can panic without failing the boot, be timed, or be
+        // given a fixture.
+        if test.needs_isolation() {
+            if !crate::test::run_isolated(test).await {
+                panic!("test case {} failed", test.name());
+            }
+            return;
+        }
+        let TestFn::Plain(func) = test.test() else {
+            unreachable!()
+        };
+        let name = test.name();
         info!("test case {} begin", name);
-        spawn!(async move {
+        spawn!(name = name, async move {
             func();
-        });
-        yield_now!();
+        })
+        .await
+        .unwrap();
         info!("test case {} end", name);
     }
 }
 
+/// Memory of the partition of the init program.
+const INIT_MEMORY: usize = 0x100000;
+
+/// Runs the user program `name` as the initial process of a partition of its own on the
+/// current CPU, and shuts the runtime down once it exits.
+async fn init(name: &str, args: &[&str]) {
+    let mut programs = jrinx_uprog::all().collect::<Vec<_>>();
+    programs.sort_unstable();
+    if name == "help" {
+        info!("The init program runs in a partition of its own on the boot CPU, with all");
+        info!("capabilities, and the system shuts down once it exits");
+        info!("Arguments after '--' are meant for it, but not passed to processes yet");
+        info!("all available programs:");
+        programs.iter().for_each(|program| info!("- {program}"));
+        return;
+    }
+    if !programs.contains(&name) {
+        panic!(
+            "unrecognized init program: {}, available: {}",
+            name,
+            programs.join(", ")
+        );
+    }
+    if !args.is_empty() {
+        warn!(
+            "init program arguments are not supported yet, ignoring {:?}",
+            args
+        );
+    }
+
+    let partition = Partition::new(&PartitionConfig {
+        name: name.rsplit('/').next().unwrap().try_into().unwrap(),
+        memory: INIT_MEMORY,
+        period: APEX_TIME_INFINITY,
+        duration: APEX_TIME_INFINITY,
+        num_cores: 1,
+        limits: ResourceLimits::default(),
+        capabilities: Capabilities::all(),
+        lowmem_action: None,
+        semaphores: Vec::new(),
+        partition_type: PartitionTypeConfig::User(jrinx_uprog::find(name).unwrap()),
+    })
+    .unwrap();
+    partition.assign_core(hal!().cpu().id() as _).unwrap();
+
+    let inspector = partition.gen_inspector().unwrap();
+    let process = Process::new_init(partition.identifier()).unwrap();
+    process.set_strace(strace());
+    inspector
+        .register(
+            process
+                .gen_executor(ProcessRunner {
+                    syscall: jrinx_syscall::handle,
+                })
+                .unwrap(),
+        )
+        .unwrap();
+    Runtime::with_current(|rt| rt.register(inspector).unwrap());
+    info!("init program {} started", name);
+
+    let exit = loop {
+        if let Some(exit) = process.exit_status() {
+            break exit;
+        }
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+    };
+    info!("init program {} {}", name, exit);
+    Runtime::shutdown(match exit {
+        ProcessExit::Exited(0) => HaltReason::NormalExit,
+        _ => HaltReason::SysFailure,
+    });
+}
+
+fn bench_help() {
+    info!("Benches matching '--bench <pattern>' run in sorted order, '*' and '?' matching");
+    info!("as for '--test'. Each logs its cycles per iteration, as the median of its samples,");
+    info!("plus or minus their spread");
+    info!("all available benches:");
+    let mut all_benches = jrinx_benchdef::all()
+        .map(BenchDef::name)
+        .collect::<Vec<_>>();
+    all_benches.sort();
+    all_benches.iter().for_each(|bench| info!("- {bench}"));
+}
+
 async fn partition(args: &str) -> Option<Arc<Partition>> {
     let nproc = hal!().cpu().nproc_valid();
 
@@ -127,6 +578,32 @@ async fn partition(args: &str) -> Option<Arc<Partition>> {
         info!("                             * the negative value indicates inf. duration");
         info!("   num_cores=<unsigned>      Specify the number of cores of the partition");
         info!("                             * up to {nproc} cores");
+        info!("Optional (comma-seperated) arguments to create a partition configuration:");
+        info!("   max_executors=<unsigned>    Specify the maximum number of executors of the partition");
+        info!("                             * default to unlimited");
+        info!(
+            "   max_tasks=<unsigned>        Specify the maximum number of tasks of the partition"
+        );
+        info!("                             * default to unlimited");
+        info!("   max_timed_events=<unsigned> Specify the maximum number of timed events of the partition");
+        info!("                             * default to unlimited");
+        info!("   max_descriptors=<unsigned>  Specify the maximum number of semaphores of the partition");
+        info!("                             * default to unlimited");
+        info!("   max_shared_bytes=<unsigned> Specify the maximum bytes of regions shared with the kernel");
+        info!("                             * the radix of the value is determined by the prefix");
+        info!("                             * default to unlimited");
+        info!("   caps=<name>|<name>|...      Specify the capabilities granted to the initial process");
+        info!("                             * e.g. LOG|LOG_RING|HALT|PARTITION_MODE");
+        info!("                             * default to all capabilities");
+        info!("   lowmem=<action>             Specify the action on the partition exhausting the kernel heap");
+        info!("                             * see '--lowmem-policy help' for the actions");
+        info!("                             * default to the one set by '--lowmem-policy'");
+        info!("   semaphores=<name>:<ceiling>[:<protocol>]|...");
+        info!("                             Specify the priority ceilings of the semaphores of the partition");
+        info!(
+            "                             * processes above the ceiling are refused the semaphore"
+        );
+        info!("                             * the protocol can be inherit (default) or ceiling");
         info!("Required (comma-seperated) arguments to create a *kern* partition configuration:");
         info!("   entry=<str>               Specify the entry of the kernel partition (TODO)");
         info!("Required (comma-seperated) arguments to create a *user* partition configuration:");
@@ -170,6 +647,34 @@ async fn partition(args: &str) -> Option<Arc<Partition>> {
             .parse()
             .unwrap();
         let program: Option<&str> = parse_key_value(config.iter(), "program");
+        let limits = ResourceLimits {
+            max_executors: parse_key_value(config.iter(), "max_executors")
+                .map(|s| parse_usize_from_proper_redix(s).unwrap()),
+            max_tasks: parse_key_value(config.iter(), "max_tasks")
+                .map(|s| parse_usize_from_proper_redix(s).unwrap()),
+            max_timed_events: parse_key_value(config.iter(), "max_timed_events")
+                .map(|s| parse_usize_from_proper_redix(s).unwrap()),
+            max_descriptors: parse_key_value(config.iter(), "max_descriptors")
+                .map(|s| parse_usize_from_proper_redix(s).unwrap()),
+            max_shared_bytes: parse_key_value(config.iter(), "max_shared_bytes")
+                .map(|s| parse_usize_from_proper_redix(s).unwrap()),
+        };
+        let capabilities = parse_key_value(config.iter(), "caps")
+            .map(|s| {
+                s.split('|')
+                    .map(|name| {
+                        Capabilities::from_name(name)
+                            .unwrap_or_else(|| panic!("invalid capability: {:?}", name))
+                    })
+                    .fold(Capabilities::empty(), |caps, cap| caps | cap)
+            })
+            .unwrap_or(Capabilities::all());
+        let lowmem_action = parse_key_value(config.iter(), "lowmem").map(|s| {
+            LowMemAction::from_name(s).unwrap_or_else(|| panic!("invalid lowmem action: {:?}", s))
+        });
+        let semaphores = parse_key_value(config.iter(), "semaphores")
+            .map(|s| s.split('|').map(parse_semaphore_config).collect())
+            .unwrap_or_default();
         if nproc < num_cores as _ {
             panic!("number of cores should be less than or equal to {nproc}, got {num_cores}");
         }
@@ -180,6 +685,10 @@ async fn partition(args: &str) -> Option<Arc<Partition>> {
                 period,
                 duration,
                 num_cores,
+                limits,
+                capabilities,
+                lowmem_action,
+                semaphores,
                 partition_type: if is_user {
                     PartitionTypeConfig::User(jrinx_uprog::find(program.unwrap()).unwrap())
                 } else {
@@ -191,6 +700,111 @@ async fn partition(args: &str) -> Option<Arc<Partition>> {
     }
 }
 
+fn parse_semaphore_config(s: &str) -> SemaphoreConfig {
+    let mut fields = s.split(':');
+    let (Some(name), Some(ceiling)) = (fields.next(), fields.next()) else {
+        panic!("invalid semaphore configuration: {:?}", s);
+    };
+    let protocol = fields.next().map(|protocol| {
+        SemaphoreProtocol::from_name(protocol)
+            .unwrap_or_else(|| panic!("invalid semaphore protocol: {:?}", protocol))
+    });
+    if fields.next().is_some() {
+        panic!("invalid semaphore configuration: {:?}", s);
+    }
+    SemaphoreConfig {
+        name: name.try_into().unwrap(),
+        ceiling: ceiling
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid semaphore ceiling: {:?}", ceiling)),
+        protocol: protocol.unwrap_or_default(),
+    }
+}
+
+async fn lowmem_policy(args: &str) {
+    if args == "help" {
+        info!("When an allocation of a partition fails for the kernel heap running out, the user");
+        info!("partition using the most memory is held responsible, and gets the action below:");
+        info!("   report                    Raise a health-monitor error only (default)");
+        info!("   fence                     Also fail all further allocations of the partition");
+        info!("   pause                     Also pause the partition until it is resumed");
+        info!("   warm-restart              Also restart the partition in warm-start mode");
+        info!("   none                      Do nothing, only failing the allocation");
+        info!("The action can be overridden per partition, see '--partition help'");
+    } else {
+        jrinx_tunable::set("lowmem.policy", args)
+            .unwrap_or_else(|err| panic!("invalid lowmem action: {:?}, {}", args, err));
+    }
+}
+
+async fn set_tunable(args: &str) {
+    if args == "help" {
+        info!("Tunables are set by '--set <key>=<value>', and may be given several times:");
+        for tunable in jrinx_tunable::list() {
+            info!("   {}", tunable);
+        }
+    } else {
+        let (key, value) = jrinx_tunable::parse_assignment(args)
+            .unwrap_or_else(|| panic!("invalid tunable assignment: {:?}", args));
+        jrinx_tunable::set(key, value)
+            .unwrap_or_else(|err| panic!("invalid tunable {}={:?}: {}", key, value, err));
+    }
+}
+
+/// Arms a timed event on the current CPU failing the run unless the system shuts down within
+/// `args` milliseconds.
+fn watchdog(args: &str) {
+    let timeout = Duration::from_millis(
+        args.parse()
+            .unwrap_or_else(|err| panic!("invalid argument for option: --timeout, {}", err)),
+    );
+    TimedEvent::create(
+        hal!().cpu().get_time() + timeout,
+        TimedEventHandler::new(move || watchdog_expired(timeout), || {}),
+    );
+    info!("watchdog armed for {:?}", timeout);
+}
+
+/// Reports what the system was doing when it failed to shut down within `timeout`, then halts.
+///
+/// This runs from the timer interrupt, possibly with locks held by whatever is stuck, so the
+/// report only takes the locks it gets at once.
+fn watchdog_expired(timeout: Duration) -> ! {
+    jrinx_logging::set_synchronous();
+
+    error!("watchdog expired: no shutdown within {:?}", timeout);
+    if let Some(name) = executor::polling_task_name() {
+        error!("watchdog expired in task {}", name);
+    }
+    Runtime::report_all();
+    #[cfg(feature = "trap-stats")]
+    for cpu_id in 0..hal!().cpu().nproc() {
+        error!("{}", jrinx_trap::stats::snapshot(cpu_id));
+    }
+
+    jrinx_wallclock::anchor();
+    log::logger().flush();
+    hal!().halt(HaltReason::WatchdogTimeout);
+}
+
+async fn ntp(args: &str) {
+    if args == "help" {
+        info!("The wall clock is synchronized with the NTP server at the IPv4 address given, at");
+        info!("boot and every hour thereafter. Until the first reply, the kernel runs on the");
+        info!("monotonic clock only");
+    } else {
+        let server = args
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid ntp server address: {:?}", args));
+        jrinx_wallclock::sntp::set_server(server);
+        warn!(
+            "ntp: no udp transport to reach {}, wall clock {}",
+            server,
+            jrinx_wallclock::sntp::status()
+        );
+    }
+}
+
 async fn scheduler(
     args: &str,
     partitions: &[Arc<Partition>],
@@ -285,6 +899,7 @@ async fn scheduler(
             let inspector = partition.gen_inspector().unwrap();
             if init {
                 let process = Process::new_init(partition.identifier()).unwrap();
+                process.set_strace(strace());
                 let executor = process
                     .gen_executor(ProcessRunner {
                         syscall: jrinx_syscall::handle,
@@ -1,5 +1,6 @@
 #![feature(allocator_api)]
 #![feature(asm_const)]
+#![feature(error_in_core)]
 #![feature(naked_functions)]
 #![feature(panic_info_message)]
 #![feature(used_with_arg)]
@@ -8,9 +9,16 @@
 #![no_main]
 
 use arch::BootInfo;
+use fdt::Fdt;
+use jrinx_a653::{
+    partition::Partition,
+    process::{Process, ProcessRunner},
+};
+use jrinx_error::Result;
 use jrinx_hal::{Cpu, Hal};
+use jrinx_init::kernel_init;
 use jrinx_multitask::runtime::{self, Runtime};
-use spin::Mutex;
+use spin::{Mutex, Once};
 
 extern crate alloc;
 #[macro_use]
@@ -21,8 +29,10 @@ extern crate jrinx_driver as _;
 extern crate jrinx_hal;
 
 mod arch;
+mod bench;
 mod bootargs;
 mod panic;
+mod shell;
 mod test;
 
 enum BootState {
@@ -32,6 +42,7 @@ enum BootState {
 }
 
 static BOOT_STATE: Mutex<BootState> = Mutex::new(BootState::Bootstrap);
+static BOOT_INFO: Once<BootInfo> = Once::new();
 
 fn boot_set_ready() {
     let mut boot_state = BOOT_STATE.lock();
@@ -51,25 +62,88 @@ fn boot_set_finished() {
     }
 }
 
-fn primary_init(boot_info: BootInfo) -> ! {
-    jrinx_trap::init();
-    jrinx_heap::init();
-    jrinx_logging::init();
-
-    let fdt = &boot_info.fdt();
+fn boot_fdt() -> Fdt<'static> {
+    BOOT_INFO.get().unwrap().fdt()
+}
 
-    arch::cpus::init(fdt);
+#[kernel_init(name = "cpus", depends = ["heap"])]
+fn cpus_init() -> Result<()> {
+    arch::cpus::init(&boot_fdt());
+    Ok(())
+}
 
+#[kernel_init(name = "percpu", depends = ["cpus"])]
+fn percpu_init() -> Result<()> {
     jrinx_percpu::init(hal!().cpu().nproc());
     jrinx_percpu::set_local_pointer(hal!().cpu().id());
+    Ok(())
+}
+
+#[kernel_init(name = "memory", depends = ["logging", "percpu"])]
+fn memory_init() -> Result<()> {
+    jrinx_driver::probe_memory(&boot_fdt());
+    Ok(())
+}
+
+#[kernel_init(name = "vmm", depends = ["memory"])]
+fn vmm_init() -> Result<()> {
+    jrinx_vmm::init();
+    Ok(())
+}
+
+#[kernel_init(name = "devices", depends = ["vmm"])]
+fn devices_init() -> Result<()> {
+    jrinx_driver::probe_all(&boot_fdt());
+    Ok(())
+}
+
+#[kernel_init(name = "page-fault")]
+fn page_fault_init() -> Result<()> {
+    jrinx_trap::page_fault::register(Partition::resolve_page_fault);
+    Ok(())
+}
 
-    jrinx_driver::probe_all(fdt);
+#[kernel_init(name = "partition-restart")]
+fn partition_restart_init() -> Result<()> {
+    Partition::register_restart(|partition| {
+        Process::new_init(partition.identifier())?.gen_executor(ProcessRunner {
+            syscall: jrinx_syscall::handle,
+        })
+    });
+    Ok(())
+}
 
-    if let Some(bootargs) = fdt.chosen().bootargs() {
+#[kernel_init(name = "bootargs", depends = ["heap"])]
+fn bootargs_init() -> Result<()> {
+    if let Some(bootargs) = boot_fdt().chosen().bootargs() {
         bootargs::set(bootargs);
     }
+    Ok(())
+}
+
+#[kernel_init(name = "secondary-boot", depends = ["devices", "bootargs"])]
+fn secondary_boot_init() -> Result<()> {
+    arch::secondary_boot(&boot_fdt(), bootargs::boot_cpus());
+    Ok(())
+}
 
-    arch::secondary_boot(fdt);
+fn primary_init(boot_info: BootInfo) -> ! {
+    jrinx_trap::init();
+
+    BOOT_INFO.call_once(|| boot_info);
+
+    // Logging needs no heap, so it comes first to cover the boot before it.
+    arch::earlycon_init(&boot_fdt());
+    jrinx_logging::init().unwrap();
+    if let Some(test) = boot_fdt()
+        .chosen()
+        .bootargs()
+        .and_then(bootargs::early_test)
+    {
+        test::early(test);
+    }
+
+    jrinx_init::run_all();
 
     let arch = core::option_env!("ARCH").unwrap_or("unknown");
     let build_time = core::option_env!("BUILD_TIME").unwrap_or("unknown");
@@ -81,8 +155,17 @@ fn primary_init(boot_info: BootInfo) -> ! {
     );
     info!("build-host: {}", build_host);
 
-    jrinx_vmm::init();
-
+    jrinx_trap::set_int_return_hook(jrinx_multitask::preempt::trap_return);
+    jrinx_trap::fault::set_abort_hook(
+        jrinx_multitask::executor::can_abort_polling,
+        jrinx_multitask::executor::abort_polling,
+    );
+    jrinx_trap::stack_guard::register(
+        jrinx_multitask::executor::overflowed_stack,
+        jrinx_multitask::executor::write_polling_task_name,
+    );
+    jrinx_backtrace::register(jrinx_multitask::executor::stack_of);
+    jrinx_trap::fatal::register(jrinx_multitask::executor::describe_current);
     runtime::init(primary_task());
 
     boot_set_ready();
@@ -101,6 +184,10 @@ fn secondary_init() -> ! {
 
     jrinx_vmm::init();
 
+    if arch::cpus::is_late(hal!().cpu().id()) {
+        Runtime::init_secondary();
+    }
+
     runtime::init(secondary_task());
 
     boot_set_ready();
@@ -112,7 +199,7 @@ async fn primary_task() {
     info!("primary task started");
 
     while let BootState::Ready(count) = *BOOT_STATE.lock() {
-        if count == hal!().cpu().nproc_valid() {
+        if count == arch::cpus::nproc_booted() {
             break;
         }
         core::hint::spin_loop();
@@ -123,7 +210,7 @@ async fn primary_task() {
     boot_set_finished();
 
     while let BootState::Finished(count) = *BOOT_STATE.lock() {
-        if count == hal!().cpu().nproc_valid() {
+        if count == arch::cpus::nproc_booted() {
             break;
         }
         core::hint::spin_loop();
@@ -134,7 +221,7 @@ async fn secondary_task() {
     info!("secondary task started");
 
     while let BootState::Ready(count) = *BOOT_STATE.lock() {
-        if count == hal!().cpu().nproc_valid() {
+        if count == arch::cpus::nproc_booted() {
             break;
         }
         core::hint::spin_loop();
@@ -143,7 +230,7 @@ async fn secondary_task() {
     boot_set_finished();
 
     while let BootState::Finished(count) = *BOOT_STATE.lock() {
-        if count == hal!().cpu().nproc_valid() {
+        if count == arch::cpus::nproc_booted() {
             break;
         }
         core::hint::spin_loop();
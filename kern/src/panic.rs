@@ -1,9 +1,17 @@
 use core::panic::PanicInfo;
 
-use jrinx_hal::{Hal, HaltReason};
+use jrinx_hal::{Cpu, Hal, HaltReason};
+
+/// Records of the log ring dumped by a panic, for the context of the panic.
+const PANIC_RING_TAIL: usize = 16;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    let isolated = crate::test::panic_is_isolated();
+    if !isolated {
+        jrinx_logging::set_synchronous();
+    }
+
     if let Some(location) = info.location() {
         error!(
             "panicked at {}:{} {}",
@@ -14,5 +22,27 @@ fn panic(info: &PanicInfo) -> ! {
     } else {
         error!("panicked: {}", info.message().unwrap());
     }
-    hal!().halt(HaltReason::SysFailure);
+    if let Some(name) = jrinx_multitask::executor::polling_task_name() {
+        error!("panicked in task {}", name);
+    }
+    jrinx_backtrace::Backtrace::capture().log();
+    #[cfg(feature = "trap-stats")]
+    if jrinx_percpu::local_pointer_ready(hal!().cpu().id()) {
+        error!("{}", jrinx_trap::stats::snapshot(hal!().cpu().id()));
+    }
+
+    let payload = jrinx_kpanic::take();
+    if let Some(payload) = payload {
+        error!("panic payload: {}", payload);
+    }
+    if isolated {
+        crate::test::abort_isolated();
+    }
+    jrinx_logging::ring::dump_tail(PANIC_RING_TAIL);
+    jrinx_wallclock::anchor();
+    log::logger().flush();
+
+    hal!().halt(payload.map_or(HaltReason::SysFailure, |payload| {
+        HaltReason::Failure(payload.code() as u32)
+    }));
 }
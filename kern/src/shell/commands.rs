@@ -0,0 +1,158 @@
+//! Commands built into the shell.
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use jrinx_config::PAGE_SIZE;
+use jrinx_hal::HaltReason;
+use jrinx_multitask::runtime::Runtime;
+use jrinx_shelldef::shelldef;
+use log::LevelFilter;
+
+use super::Console;
+
+fn usage(name: &str) {
+    if let Some(command) = jrinx_shelldef::find(name) {
+        let _ = writeln!(Console, "usage: {} {}", command.name(), command.usage());
+    }
+}
+
+shelldef!(
+    name = "help",
+    usage = "",
+    help = "List the commands",
+    run = help,
+);
+
+async fn help(_: &str) {
+    let mut commands = jrinx_shelldef::all().collect::<Vec<_>>();
+    commands.sort_unstable_by_key(|command| command.name());
+    let _ = writeln!(Console, "commands:");
+    for command in commands {
+        let _ = writeln!(
+            Console,
+            "  {:<6} {:<26} {}",
+            command.name(),
+            command.usage(),
+            command.help()
+        );
+    }
+}
+
+shelldef!(
+    name = "test",
+    usage = "<test>...",
+    help = "Run the tests matching each <test>, in isolation",
+    run = test,
+);
+
+async fn test(args: &str) {
+    let selectors = args.split_whitespace().collect::<Vec<_>>();
+    if selectors.is_empty() {
+        return usage("test");
+    }
+    crate::test::run_all(&selectors).await;
+}
+
+shelldef!(
+    name = "log",
+    usage = "[<level> | <module>=<level>]",
+    help = "Show or set the log level, or set that of a module",
+    run = log_level,
+);
+
+async fn log_level(args: &str) {
+    if args.is_empty() {
+        let _ = writeln!(Console, "log level: {}", jrinx_logging::level());
+        return;
+    }
+    let (module, level) = match args.split_once('=') {
+        Some((module, level)) => (Some(module), level),
+        None => (None, args),
+    };
+    let Ok(filter) = level.parse::<LevelFilter>() else {
+        let _ = writeln!(
+            Console,
+            "invalid log level: {:?}, expected one of off, error, warn, info, debug, trace",
+            level
+        );
+        return;
+    };
+    match module {
+        Some(module) => {
+            jrinx_logging::set_target_filter(module, filter);
+            let _ = writeln!(Console, "log level of {}: {}", module, filter);
+        }
+        None => {
+            jrinx_logging::set_level(filter);
+            let _ = writeln!(Console, "log level: {}", filter);
+        }
+    }
+}
+
+shelldef!(
+    name = "ps",
+    usage = "",
+    help = "Show runtimes, inspectors, executors and tasks",
+    run = ps,
+);
+
+async fn ps(_: &str) {
+    Runtime::report_tasks();
+}
+
+shelldef!(
+    name = "dmesg",
+    usage = "[<records>]",
+    help = "Show the log ring, or its last <records> records",
+    run = dmesg,
+);
+
+async fn dmesg(args: &str) {
+    let since = match args {
+        "" => 0,
+        records => match records.parse::<u64>() {
+            Ok(records) => jrinx_logging::ring::next_seq().saturating_sub(records),
+            Err(_) => return usage("dmesg"),
+        },
+    };
+    for record in jrinx_logging::ring::read_since(since) {
+        let _ = writeln!(Console, "{}", record);
+    }
+}
+
+shelldef!(
+    name = "mem",
+    usage = "",
+    help = "Show the usage of the kernel heap and of frames",
+    run = mem,
+);
+
+async fn mem(_: &str) {
+    let (used, total) = jrinx_heap::usage();
+    let _ = writeln!(
+        Console,
+        "heap: {} of {} bytes used ({}%)",
+        used,
+        total,
+        used * 100 / total.max(1)
+    );
+    let frames = jrinx_phys_frame::allocated();
+    let _ = writeln!(
+        Console,
+        "frames: {} allocated ({} bytes)",
+        frames,
+        frames * PAGE_SIZE
+    );
+}
+
+shelldef!(
+    name = "halt",
+    usage = "",
+    help = "Shut the system down",
+    run = halt,
+);
+
+async fn halt(_: &str) {
+    Runtime::shutdown(HaltReason::NormalExit);
+}
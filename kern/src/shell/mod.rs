@@ -0,0 +1,99 @@
+//! Shell on the console, started by `--shell` after the other boot arguments.
+//!
+//! Commands are registered by `shelldef!`, as the built-in ones in [`commands`] are.
+
+mod commands;
+
+use alloc::string::String;
+use core::{
+    fmt::{self, Write},
+    time::Duration,
+};
+
+use jrinx_hal::{Earlycon, Hal};
+use jrinx_multitask::time::sleep;
+
+const PROMPT: &str = "jrinx> ";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Longest line taken at the prompt, in bytes.
+const LINE_MAX: usize = 256;
+
+/// Console output of the shell, apart from the log.
+pub(crate) struct Console;
+
+impl Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        hal!().earlycon().write(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct LineEditor {
+    line: String,
+    /// Whether the last byte was a carriage return ending a line.
+    after_cr: bool,
+}
+
+impl LineEditor {
+    /// Takes a byte typed at the prompt, returning the line once it ends.
+    pub(crate) fn feed(&mut self, byte: u8, echo: &mut impl Write) -> Option<String> {
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        match byte {
+            b'\n' if after_cr => None,
+            b'\r' | b'\n' => {
+                let _ = echo.write_str("\n");
+                Some(core::mem::take(&mut self.line))
+            }
+            0x08 | 0x7f => {
+                if self.line.pop().is_some() {
+                    let _ = echo.write_str("\x08 \x08");
+                }
+                None
+            }
+            b' '..=b'~' if self.line.len() < LINE_MAX => {
+                self.line.push(byte as char);
+                let _ = echo.write_char(byte as char);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Runs the shell, taking commands until one of them halts the system.
+pub async fn run() {
+    let mut editor = LineEditor::default();
+    loop {
+        let _ = Console.write_str(PROMPT);
+        let line = loop {
+            match hal!().earlycon().getc() {
+                Some(byte) => {
+                    if let Some(line) = editor.feed(byte, &mut Console) {
+                        break line;
+                    }
+                }
+                None => sleep(POLL_INTERVAL).await,
+            }
+        };
+        execute(&line).await;
+    }
+}
+
+pub async fn execute(line: &str) {
+    let line = line.trim();
+    if line.is_empty() {
+        return;
+    }
+    let (name, args) = line
+        .split_once(char::is_whitespace)
+        .map_or((line, ""), |(name, args)| (name, args.trim_start()));
+    match jrinx_shelldef::find(name) {
+        Some(command) => command.run(args).await,
+        None => {
+            let _ = writeln!(Console, "unknown command: {}, try 'help'", name);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use core::hint::black_box;
+
+use jrinx_backtrace::{ksyms, Backtrace, WalkEnd};
+use jrinx_testdef::testdef;
+
+#[testdef(should_panic)]
+fn test() {
+    level_one();
+    black_box(());
+}
+
+#[inline(never)]
+fn level_one() {
+    level_two();
+    black_box(());
+}
+
+#[inline(never)]
+fn level_two() {
+    level_three();
+    black_box(());
+}
+
+#[inline(never)]
+fn level_three() {
+    let backtrace = Backtrace::capture();
+    assert_ne!(backtrace.end(), WalkEnd::Truncated);
+    let callers = ["level_two", "level_one", "test"]
+        .into_iter()
+        .filter(|caller| {
+            backtrace.addrs().iter().any(|&addr| {
+                ksyms::resolve(addr - 1).is_some_and(|symbol| {
+                    symbol
+                        .name
+                        .strip_prefix("jrinx::test::backtrace::")
+                        .is_some_and(|name| name == *caller)
+                })
+            })
+        })
+        .count();
+    info!("backtrace: {} of 3 callers resolved", callers);
+
+    panic!("deliberate panic three calls deep");
+}
@@ -0,0 +1,20 @@
+use alloc::vec::Vec;
+
+use jrinx_benchdef::BenchDef;
+use jrinx_testdef::testdef;
+
+#[testdef]
+fn test() {
+    let mut benches = jrinx_benchdef::find_all("task::*")
+        .map(BenchDef::name)
+        .collect::<Vec<_>>();
+    benches.sort_unstable();
+    assert_eq!(
+        benches,
+        [
+            "jrinx::bench::task::spawn_join",
+            "jrinx::bench::task::yield_now"
+        ]
+    );
+    assert!(jrinx_benchdef::find_all("no_such_*").next().is_none());
+}
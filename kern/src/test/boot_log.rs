@@ -0,0 +1,29 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use jrinx_logging::LOG_BUFFER_SIZE;
+use jrinx_testdef::testdef;
+
+pub(super) const NAME: &str = module_path!();
+
+const MESSAGE_LEN: usize = 4096;
+
+static EARLY_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Logs a message larger than the log buffer, before the heap exists.
+pub(super) fn early() {
+    log_oversized();
+    EARLY_LOGGED.store(true, Ordering::SeqCst);
+}
+
+fn log_oversized() {
+    let message = [b'x'; MESSAGE_LEN];
+    info!("boot log: {}", core::str::from_utf8(&message).unwrap());
+}
+
+#[testdef]
+fn test() {
+    assert!(EARLY_LOGGED.load(Ordering::SeqCst));
+    assert!(MESSAGE_LEN > LOG_BUFFER_SIZE);
+
+    log_oversized();
+}
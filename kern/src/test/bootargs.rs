@@ -0,0 +1,59 @@
+use alloc::{format, vec::Vec};
+use jrinx_testdef::testdef;
+
+use crate::bootargs::{tokenize, ParseError};
+
+#[testdef]
+fn test() {
+    for bootargs in [
+        "",
+        "   ",
+        "-t test",
+        "  --partition name=p1,memory=0x1000000\t--strace \n -t user::exit ",
+        "--set a=b\\c",
+    ] {
+        assert_eq!(
+            tokenize(bootargs).unwrap(),
+            bootargs.split_whitespace().collect::<Vec<_>>()
+        );
+    }
+
+    assert_eq!(
+        tokenize(r#"--init "/bin/sh -l" -t 'a b'"#).unwrap(),
+        ["--init", "/bin/sh -l", "-t", "a b"]
+    );
+    assert_eq!(tokenize(r#"--key="a b"c"#).unwrap(), ["--key=a bc"]);
+    assert_eq!(
+        tokenize(r#""it's" 'say "hi"' "\"\\\n""#).unwrap(),
+        ["it's", r#"say "hi""#, r#""\\n"#]
+    );
+    assert_eq!(tokenize(r#"'\"' "\'""#).unwrap(), [r#"\""#, r#"\'"#]);
+    assert_eq!(tokenize(r#""" a '' """#).unwrap(), ["", "a", "", ""]);
+    assert_eq!(tokenize(r#"a\ b\"#).unwrap(), [r#"a\"#, r#"b\"#]);
+
+    assert_eq!(
+        tokenize(r#"-t "a b"#),
+        Err(ParseError::UnterminatedQuote {
+            quote: '"',
+            column: 4
+        })
+    );
+    assert_eq!(
+        tokenize(r#"ok 'x"#),
+        Err(ParseError::UnterminatedQuote {
+            quote: '\'',
+            column: 4
+        })
+    );
+    assert_eq!(
+        tokenize(r#"é "trailing\"#),
+        Err(ParseError::UnterminatedQuote {
+            quote: '"',
+            column: 3
+        })
+    );
+    assert_eq!(
+        format!("{}", tokenize("--init 'sh").unwrap_err()),
+        "unterminated ' quote at column 8"
+    );
+}
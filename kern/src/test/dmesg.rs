@@ -0,0 +1,47 @@
+use alloc::{format, vec::Vec};
+use jrinx_logging::{ring::Ring, Timestamp};
+use jrinx_testdef::testdef;
+use log::Level;
+
+#[testdef]
+fn test() {
+    const RING_SIZE: usize = 256;
+    const RECORDS: u64 = 16;
+
+    let mut ring = Ring::<RING_SIZE>::new();
+    for seq in 0..RECORDS {
+        let text = format!("dmesg: record {} in a small ring", seq);
+        assert_eq!(ring.push(Timestamp(None), 1, Level::Warn, &text), seq);
+    }
+    assert_eq!(ring.next_seq(), RECORDS);
+    assert!(ring.first_seq() > 0);
+    let records = ring.read_since(0);
+    assert_eq!(records.first().unwrap().seq, ring.first_seq());
+    for (record, seq) in records.iter().zip(ring.first_seq()..) {
+        assert_eq!(record.seq, seq);
+        assert_eq!(record.cpu_id, 1);
+        assert_eq!(record.level, Level::Warn);
+        assert_eq!(
+            record.text,
+            format!("dmesg: record {} in a small ring", seq)
+        );
+    }
+    assert_eq!(ring.read_since(RECORDS - 1).len(), 1);
+
+    let since = jrinx_logging::ring::next_seq();
+    info!("dmesg: first record\nof two lines");
+    warn!("dmesg: second record");
+    let records = jrinx_logging::ring::read_since(since)
+        .filter(|record| record.text.starts_with("dmesg: ") || record.text == "of two lines")
+        .collect::<Vec<_>>();
+    assert_eq!(records.len(), 3);
+    assert!(records.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+    assert_eq!(records[0].level, Level::Info);
+    assert_eq!(records[1].text, "of two lines");
+    assert_eq!(records[2].level, Level::Warn);
+    assert!(!records[2].text.contains('\u{1B}'));
+
+    for record in jrinx_logging::ring::read_since(0) {
+        info!("dmesg {}", record);
+    }
+}
@@ -0,0 +1,23 @@
+use jrinx_hal::{
+    earlycon::{self, EarlyconBackend, DBCN_CHUNK_SIZE},
+    hal, Earlycon, Hal,
+};
+use jrinx_testdef::testdef;
+
+const LINE_LEN: usize = 3 * DBCN_CHUNK_SIZE + 17;
+
+#[testdef]
+fn test() {
+    let backend = earlycon::backend();
+    info!("earlycon backend: {:?}", backend);
+
+    if !earlycon::dbcn_available() {
+        assert_eq!(backend, EarlyconBackend::Legacy);
+        assert!(!earlycon::select(EarlyconBackend::DebugConsole));
+        assert_eq!(earlycon::backend(), EarlyconBackend::Legacy);
+    }
+
+    let mut line = [b'y'; LINE_LEN + 1];
+    line[LINE_LEN] = b'\n';
+    hal!().earlycon().write(&line);
+}
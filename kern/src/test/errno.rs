@@ -0,0 +1,60 @@
+use jrinx_abi::errno as abi;
+use jrinx_addr::VirtAddr;
+use jrinx_error::{Errno, InternalError, ResourceKind};
+use jrinx_testdef::testdef;
+
+#[testdef]
+fn test() {
+    let table = [
+        (Errno::EPERM, 1, abi::EPERM),
+        (Errno::ENOENT, 2, abi::ENOENT),
+        (Errno::ESRCH, 3, abi::ESRCH),
+        (Errno::EINTR, 4, abi::EINTR),
+        (Errno::EAGAIN, 11, abi::EAGAIN),
+        (Errno::ENOMEM, 12, abi::ENOMEM),
+        (Errno::EFAULT, 14, abi::EFAULT),
+        (Errno::EBUSY, 16, abi::EBUSY),
+        (Errno::EEXIST, 17, abi::EEXIST),
+        (Errno::EINVAL, 22, abi::EINVAL),
+        (Errno::ENOSYS, 38, abi::ENOSYS),
+        (Errno::ETIMEDOUT, 110, abi::ETIMEDOUT),
+    ];
+    assert_eq!(table.len(), Errno::ALL.len());
+    for (errno, value, expected) in table {
+        assert_eq!(errno.value(), value);
+        assert_eq!(errno.into_ret(), expected.as_ret());
+        assert_eq!(errno.into_ret(), (-value) as usize);
+    }
+
+    for errno in Errno::ALL {
+        assert_eq!(Errno::from_ret(errno.into_ret()), Some(errno));
+        assert_eq!(
+            abi::Errno::from_ret(errno.into_ret()),
+            Err(abi::Errno(errno.value()))
+        );
+    }
+    for ret in [0, 1, 0x1000, (-5isize) as usize, isize::MIN as usize] {
+        assert_eq!(Errno::from_ret(ret), None);
+    }
+
+    assert_eq!(
+        Errno::from(InternalError::InvalidVirtAddr(VirtAddr::new(0))),
+        Errno::EFAULT
+    );
+    assert_eq!(Errno::from(InternalError::NotEnoughMem), Errno::ENOMEM);
+    assert_eq!(
+        Errno::from(InternalError::InvalidSyscallNumber),
+        Errno::ENOSYS
+    );
+    assert_eq!(
+        Errno::from(InternalError::ResourceLimitExceeded(
+            ResourceKind::Executors
+        )),
+        Errno::EAGAIN
+    );
+    assert_eq!(
+        Errno::from(InternalError::DuplicateTaskId(1)),
+        Errno::EEXIST
+    );
+    assert_eq!(Errno::from(InternalError::InvalidApexName), Errno::EINVAL);
+}
@@ -0,0 +1,44 @@
+use alloc::format;
+use jrinx_addr::VirtAddr;
+use jrinx_error::{InternalError, ResourceKind};
+use jrinx_testdef::testdef;
+
+#[testdef]
+fn test() {
+    assert_eq!(
+        format!(
+            "{}",
+            InternalError::InvalidVirtAddr(VirtAddr::new(0xdead000))
+        ),
+        "invalid virtual address 0xdead000"
+    );
+    assert_eq!(
+        format!("{}", InternalError::InvalidCpuId(7)),
+        "invalid cpu#7"
+    );
+    assert_eq!(
+        format!("{}", InternalError::DuplicateTaskId(42)),
+        "duplicate task#42"
+    );
+    assert_eq!(
+        format!(
+            "{}",
+            InternalError::DevProbeError {
+                compatible: "ns16550a"
+            }
+        ),
+        "failed to probe device ns16550a"
+    );
+    assert_eq!(
+        format!(
+            "{}",
+            InternalError::ResourceLimitExceeded(ResourceKind::TimedEvents)
+        ),
+        "too many timed events"
+    );
+    assert_eq!(format!("{}", InternalError::NotEnoughMem), "out of memory");
+
+    let err: &dyn core::error::Error = &InternalError::ChannelClosed;
+    assert_eq!(format!("{}", err), "channel closed");
+    assert!(err.source().is_none());
+}
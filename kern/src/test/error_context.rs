@@ -0,0 +1,58 @@
+use alloc::{format, vec::Vec};
+use jrinx_addr::VirtAddr;
+use jrinx_error::{ContextError, InternalError, ResultExt, CONTEXT_FRAMES};
+use jrinx_testdef::testdef;
+
+fn map(addr: usize) -> jrinx_error::Result<()> {
+    Err(InternalError::InvalidVirtAddr(VirtAddr::new(addr)))
+}
+
+fn probe(name: &str) -> Result<(), ContextError> {
+    map(0xdead000)
+        .context("map registers")
+        .with_context(|| name)
+}
+
+#[testdef]
+fn test() {
+    let err = probe("serial@10000000")
+        .context("probe devices")
+        .unwrap_err();
+    assert_eq!(
+        format!("{}", err),
+        "probe devices: serial@10000000: map registers: invalid virtual address 0xdead000"
+    );
+    assert_eq!(
+        err.frames().collect::<Vec<_>>(),
+        ["probe devices", "serial@10000000", "map registers"]
+    );
+    assert!(matches!(err.error(), InternalError::InvalidVirtAddr(_)));
+    assert!(core::error::Error::source(&err).is_some());
+
+    let mut rendered = false;
+    assert!(Ok::<_, InternalError>(())
+        .with_context(|| {
+            rendered = true;
+            "unused"
+        })
+        .is_ok());
+    assert!(!rendered);
+
+    let long = "x".repeat(100);
+    let err = map(0).with_context(|| &long).unwrap_err();
+    let frame = err.frames().next().unwrap();
+    assert!(frame.len() < long.len() && long.starts_with(frame));
+
+    let mut result: Result<(), ContextError> = map(0x1000).map_err(Into::into);
+    for depth in 0..CONTEXT_FRAMES + 2 {
+        result = result.with_context(|| depth);
+    }
+    assert_eq!(
+        format!("{}", result.unwrap_err()),
+        "...: 3: 2: 1: 0: invalid virtual address 0x1000"
+    );
+    assert_eq!(
+        format!("{:?}", map(0x1000).context("map").unwrap_err()),
+        "map: InvalidVirtAddr(VirtAddr(4096))"
+    );
+}
@@ -0,0 +1,21 @@
+use jrinx_error::InternalError;
+use jrinx_testdef::testdef;
+
+#[testdef]
+fn test() {
+    assert!(matches!(
+        jrinx_heap::init(),
+        Err(InternalError::RepeatInitialization)
+    ));
+    assert!(matches!(
+        jrinx_logging::init(),
+        Err(InternalError::RepeatInitialization)
+    ));
+
+    assert_eq!(jrinx_init::phase_of("heap"), Some(0));
+    assert!(jrinx_init::phase_of("percpu") > jrinx_init::phase_of("cpus"));
+    assert!(jrinx_init::phase_of("secondary-boot") > jrinx_init::phase_of("devices"));
+    for name in jrinx_init::all() {
+        assert!(jrinx_init::phase_of(name).is_some(), "{} not done", name);
+    }
+}
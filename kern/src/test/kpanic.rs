@@ -0,0 +1,13 @@
+use jrinx_kpanic::{kpanic, PanicCode};
+use jrinx_testdef::testdef;
+
+#[testdef]
+fn test() {
+    let id = 0x2a_usize;
+    kpanic!(
+        code = PanicCode::SchedInvariant,
+        inspector = id,
+        cpu = 0_usize,
+        "deliberate panic of inspector {id:?}"
+    );
+}
@@ -0,0 +1,76 @@
+use alloc::format;
+use jrinx_testdef::testdef;
+use jrinx_util::color::{ColorCode, ColorSpec};
+use log::Level;
+
+#[testdef]
+fn test() {
+    for (spec, sequence) in [
+        (ColorSpec::default(), "\u{1B}[0m"),
+        (ColorSpec::default().bold(), "\u{1B}[0;1m"),
+        (ColorSpec::fg(ColorCode::Red), "\u{1B}[0;31m"),
+        (ColorSpec::fg(ColorCode::Red).bold(), "\u{1B}[0;1;31m"),
+        (ColorSpec::fg(ColorCode::BrightRed), "\u{1B}[0;91m"),
+        (ColorSpec::default().on(ColorCode::Blue), "\u{1B}[0;44m"),
+        (
+            ColorSpec::default().on(ColorCode::BrightBlue),
+            "\u{1B}[0;104m",
+        ),
+        (
+            ColorSpec::fg(ColorCode::BrightWhite)
+                .on(ColorCode::Black)
+                .bold(),
+            "\u{1B}[0;1;97;40m",
+        ),
+        #[cfg(feature = "colorful")]
+        (ColorSpec::fg(ColorCode::Ansi256(208)), "\u{1B}[0;38;5;208m"),
+        #[cfg(feature = "colorful")]
+        (
+            ColorSpec::fg(ColorCode::Ansi256(15))
+                .on(ColorCode::Ansi256(0))
+                .bold(),
+            "\u{1B}[0;1;38;5;15;48;5;0m",
+        ),
+    ] {
+        assert_eq!(format!("{}", spec), sequence);
+    }
+
+    for (text, spec) in [
+        ("plain", ColorSpec::default()),
+        ("red", ColorSpec::fg(ColorCode::Red)),
+        ("red+bold", ColorSpec::fg(ColorCode::Red).bold()),
+        (
+            "bold+bright-red",
+            ColorSpec::fg(ColorCode::BrightRed).bold(),
+        ),
+        (
+            "bright-white+on-blue",
+            ColorSpec::fg(ColorCode::BrightWhite).on(ColorCode::Blue),
+        ),
+        #[cfg(feature = "colorful")]
+        (
+            "208+on-16",
+            ColorSpec::fg(ColorCode::Ansi256(208)).on(ColorCode::Ansi256(16)),
+        ),
+    ] {
+        assert_eq!(text.parse(), Ok(spec));
+    }
+    for text in ["", "pink", "bright-", "on-red+", "red+italic", "256"] {
+        assert!(text.parse::<ColorSpec>().is_err());
+    }
+
+    assert_eq!(
+        jrinx_logging::color_of(Level::Error),
+        ColorSpec::fg(ColorCode::Red).bold()
+    );
+    assert_eq!(
+        jrinx_logging::color_of(Level::Warn),
+        ColorSpec::fg(ColorCode::BrightYellow)
+    );
+    assert_eq!(
+        jrinx_logging::color_of(Level::Info),
+        ColorSpec::fg(ColorCode::Green)
+    );
+    assert!(!jrinx_logging::is_color_enabled());
+    info!("log colors: logged without colors");
+}
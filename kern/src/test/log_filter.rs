@@ -0,0 +1,16 @@
+use jrinx_testdef::testdef;
+use log::LevelFilter;
+
+#[testdef]
+fn test() {
+    assert_eq!(jrinx_logging::level(), LevelFilter::Info);
+    assert_eq!(log::max_level(), LevelFilter::Trace);
+
+    trace!("log filter: trace of the module");
+    trace!(target: "jrinx::test::log_filter::inner", "log filter: trace of a submodule");
+    trace!(target: "jrinx::test", "log filter: trace of the parent");
+    trace!(target: "jrinx::test::log_filter_sibling", "log filter: trace of a sibling");
+
+    info!(target: "jrinx::test::log_filter::quiet", "log filter: info of a quiet submodule");
+    warn!(target: "jrinx::test::log_filter::quiet", "log filter: warning of a quiet submodule");
+}
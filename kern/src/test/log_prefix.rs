@@ -0,0 +1,74 @@
+use alloc::format;
+use core::time::Duration;
+
+use jrinx_hal::{Cpu, Hal};
+use jrinx_logging::Timestamp;
+use jrinx_testdef::testdef;
+use spin::Mutex;
+
+const CAPTURE_SIZE: usize = 16384;
+
+static CAPTURED: Mutex<([u8; CAPTURE_SIZE], usize)> = Mutex::new(([0; CAPTURE_SIZE], 0));
+
+fn capture(s: &str) {
+    let mut captured = CAPTURED.lock();
+    let (buf, len) = &mut *captured;
+    let n = s.len().min(CAPTURE_SIZE - *len);
+    buf[*len..*len + n].copy_from_slice(&s.as_bytes()[..n]);
+    *len += n;
+}
+
+#[testdef(serial)]
+fn test() {
+    const RECORDS: usize = 8;
+
+    assert_eq!(format!("{}", Timestamp(None)), "     ?.??????");
+    assert_eq!(
+        format!("{}", Timestamp(Some(Duration::from_micros(12_034_567)))),
+        "    12.034567"
+    );
+
+    let color = jrinx_logging::is_color_enabled();
+    jrinx_logging::set_color_enabled(false);
+    jrinx_logging::set_sink(Some(capture));
+    for i in 0..RECORDS {
+        info!("log prefix: record {}", i);
+    }
+    jrinx_logging::set_sink(None);
+    jrinx_logging::set_color_enabled(color);
+
+    let captured = CAPTURED.lock();
+    let captured = core::str::from_utf8(&captured.0[..captured.1]).unwrap();
+    let mut last = Duration::ZERO;
+    let mut records = 0;
+    // Other CPUs may log meanwhile, with timestamps taken before their records serialize.
+    for line in captured
+        .lines()
+        .filter(|line| line.contains("log prefix: record"))
+    {
+        let prefix = line.strip_prefix("[ ").unwrap();
+        let (time, rest) = prefix.split_at(13);
+        let (secs, micros) = time.trim_start().split_once('.').unwrap();
+        assert_eq!(micros.len(), 6);
+        let time = Duration::from_secs(secs.parse().unwrap())
+            + Duration::from_micros(micros.parse().unwrap());
+        assert!(
+            time >= last,
+            "timestamps went back from {:?} to {:?}",
+            last,
+            time
+        );
+        last = time;
+
+        let cpu_id = rest
+            .strip_prefix(" cpu#")
+            .and_then(|rest| rest.split_once(' '))
+            .map(|(id, _)| id.parse::<usize>().unwrap())
+            .unwrap();
+        assert!(cpu_id < hal!().cpu().nproc());
+        assert!(line.contains(&format!("log prefix: record {}", records)));
+        records += 1;
+    }
+    assert_eq!(records, RECORDS);
+    info!("log prefix: {} records checked", records);
+}
@@ -0,0 +1,71 @@
+use jrinx_logging::with_log_level;
+use jrinx_multitask::{
+    executor::{Executor, ExecutorPriority},
+    inspector::Inspector,
+    runtime::Runtime,
+    Task, TaskPriority,
+};
+use jrinx_testdef::testdef;
+use log::{Level, LevelFilter, Log, Metadata};
+
+fn enabled(target: &str, level: Level) -> bool {
+    log::logger().enabled(&Metadata::builder().target(target).level(level).build())
+}
+
+#[testdef(serial)]
+fn test() {
+    const TARGET: &str = "jrinx_multitask::runtime";
+    assert_eq!(jrinx_logging::target_filter("jrinx_multitask"), None);
+    assert!(!enabled(TARGET, Level::Trace));
+
+    with_log_level("jrinx_multitask", LevelFilter::Trace, || {
+        assert!(enabled(TARGET, Level::Trace));
+        assert_eq!(log::max_level(), LevelFilter::Trace);
+        trace!(target: TARGET, "log scope: record of a loud module");
+
+        with_log_level("jrinx_multitask", LevelFilter::Off, || {
+            assert!(!enabled(TARGET, Level::Error));
+            assert!(enabled("jrinx::test::log_scope", Level::Info));
+
+            let inspector = Inspector::new();
+            inspector
+                .register(Executor::new(
+                    ExecutorPriority::default(),
+                    Task::new(async {}, TaskPriority::default()),
+                ))
+                .unwrap();
+            Runtime::register_balanced(inspector).unwrap();
+            error!(target: TARGET, "log scope: record of a silenced module");
+        });
+
+        assert_eq!(
+            jrinx_logging::target_filter("jrinx_multitask"),
+            Some(LevelFilter::Trace)
+        );
+    });
+
+    with_log_level(
+        "jrinx_multitask::runtime::inner",
+        LevelFilter::Debug,
+        || {
+            assert!(enabled(
+                "jrinx_multitask::runtime::inner::deeper",
+                Level::Debug
+            ));
+            assert!(!enabled(TARGET, Level::Debug));
+            assert!(!enabled(
+                "jrinx_multitask::runtime::inner_sibling",
+                Level::Debug
+            ));
+        },
+    );
+
+    assert_eq!(jrinx_logging::target_filter("jrinx_multitask"), None);
+    assert_eq!(
+        jrinx_logging::target_filter("jrinx_multitask::runtime::inner"),
+        None
+    );
+    assert!(!enabled(TARGET, Level::Trace));
+    assert_eq!(log::max_level(), jrinx_logging::level());
+    info!("log scope: filters restored");
+}
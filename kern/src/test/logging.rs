@@ -0,0 +1,11 @@
+use jrinx_testdef::testdef;
+
+#[testdef]
+fn test() {
+    const BURST_LEN: usize = 256;
+
+    for i in 0..BURST_LEN {
+        info!("log burst line {} of {}", i + 1, BURST_LEN);
+    }
+    info!("log burst finished");
+}
@@ -0,0 +1,115 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    alloc::{Allocator, Layout},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use jrinx_a653::{
+    hm::HmErrorKind,
+    lowmem::LowMemAction,
+    partition::{Partition, PartitionConfig, PartitionTypeConfig},
+};
+use jrinx_abi::cap::Capabilities;
+use jrinx_apex::*;
+use jrinx_multitask::{
+    executor::{Executor, ExecutorPriority},
+    inspector::{Inspector, InspectorStatus, ResourceLimits},
+    runtime::Runtime,
+    yield_now, Task, TaskPriority,
+};
+use jrinx_testdef::testdef;
+
+#[testdef(serial)]
+fn test() {
+    let small = new_partition("small", None);
+    let large = new_partition("large", Some(LowMemAction::Fence));
+
+    let layout = Layout::from_size_align(0x10000, 8).unwrap();
+    let ptr = large.allocator().allocate(layout).unwrap();
+    assert!(large.memory_used() > small.memory_used());
+
+    // Within the budget of the partition, but beyond the whole kernel heap.
+    let (_, total) = jrinx_heap::usage();
+    let used = small.memory_used();
+    let huge = Layout::from_size_align(total + 1, 8).unwrap();
+    assert!(small.allocator().allocate(huge).is_err());
+    assert_eq!(small.memory_used(), used);
+
+    // The partition using the most memory is held responsible, not the one allocating next.
+    assert!(large.heap_fenced());
+    assert!(!small.heap_fenced());
+    assert_eq!(
+        large.take_hm_error().map(|error| error.kind),
+        Some(HmErrorKind::HeapExhausted)
+    );
+    assert!(large.take_hm_error().is_none());
+    assert!(small.take_hm_error().is_none());
+    assert!(large.allocator().allocate(layout).is_err());
+
+    let small_ptr = small.allocator().allocate(layout).unwrap();
+    unsafe { small.allocator().deallocate(small_ptr.cast(), layout) };
+
+    large.unfence_heap();
+    let large_ptr = large.allocator().allocate(layout).unwrap();
+    unsafe {
+        large.allocator().deallocate(large_ptr.cast(), layout);
+        large.allocator().deallocate(ptr.cast(), layout);
+    }
+
+    // A paused partition stays registered, but is no longer scheduled until resumed.
+    static STOP: AtomicBool = AtomicBool::new(false);
+    let paused = new_partition("paused", Some(LowMemAction::Pause));
+    let inspector = paused.gen_inspector().unwrap();
+    inspector
+        .register(Executor::new(
+            ExecutorPriority::default(),
+            Task::new(
+                async {
+                    while !STOP.load(Ordering::SeqCst) {
+                        yield_now!();
+                    }
+                },
+                TaskPriority::default(),
+            ),
+        ))
+        .unwrap();
+    let id = inspector.id();
+    Runtime::with_current(|rt| rt.register(inspector).unwrap());
+
+    let ptr = paused.allocator().allocate(layout).unwrap();
+    assert!(small.allocator().allocate(huge).is_err());
+    assert!(matches!(
+        Runtime::with_current(|rt| rt.with_registry(|registry| registry[&id].status())),
+        InspectorStatus::Paused(_)
+    ));
+    assert_eq!(
+        paused.take_hm_error().map(|error| error.kind),
+        Some(HmErrorKind::HeapExhausted)
+    );
+
+    unsafe { paused.allocator().deallocate(ptr.cast(), layout) };
+    STOP.store(true, Ordering::SeqCst);
+    Runtime::with_current(|rt| rt.resume_inspector(id)).unwrap();
+    while Runtime::with_current(|rt| rt.with_registry(|registry| registry.contains_key(&id))) {
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+    }
+}
+
+fn new_partition(name: &str, lowmem_action: Option<LowMemAction>) -> Arc<Partition> {
+    Partition::new(&PartitionConfig {
+        name: name.try_into().unwrap(),
+        memory: usize::MAX / 2,
+        period: APEX_TIME_INFINITY,
+        duration: APEX_TIME_INFINITY,
+        num_cores: 1,
+        limits: ResourceLimits::default(),
+        capabilities: Capabilities::all(),
+        lowmem_action,
+        semaphores: Vec::new(),
+        partition_type: PartitionTypeConfig::User(
+            jrinx_uprog::find("test/user/capability").unwrap(),
+        ),
+    })
+    .unwrap()
+}
@@ -1,3 +1,181 @@
+pub(super) mod addr {
+    use alloc::{format, vec::Vec};
+    use core::ops::Range;
+
+    use jrinx_addr::{PhysAddr, PhysAddrRange, VirtAddr, VirtAddrRange};
+    use jrinx_config::PAGE_SIZE;
+    use jrinx_testdef::testdef;
+
+    const TOP: usize = usize::MAX;
+
+    fn range(start: usize, end: usize) -> VirtAddrRange {
+        VirtAddrRange::new(VirtAddr::new(start), VirtAddr::new(end))
+    }
+
+    #[testdef]
+    fn test() {
+        let zero = VirtAddr::new(0);
+        let top = VirtAddr::new(TOP);
+        assert_eq!(zero.checked_add(TOP), Some(top));
+        assert_eq!(top.checked_add(0), Some(top));
+        assert_eq!(top.checked_add(1), None);
+        assert_eq!(zero.checked_sub(0), Some(zero));
+        assert_eq!(zero.checked_sub(1), None);
+        assert_eq!(top.checked_sub(TOP), Some(zero));
+
+        assert_eq!(zero.align_down(PAGE_SIZE), zero);
+        assert_eq!(zero.align_up(PAGE_SIZE), Some(zero));
+        assert!(zero.is_aligned(PAGE_SIZE));
+        assert_eq!(top.align_down(1), top);
+        assert_eq!(top.align_up(1), Some(top));
+        assert_eq!(
+            top.align_down(PAGE_SIZE),
+            VirtAddr::new(TOP - (PAGE_SIZE - 1))
+        );
+        assert_eq!(top.align_up(2), None);
+        assert_eq!(top.align_up(PAGE_SIZE), None);
+        assert_eq!(
+            VirtAddr::new(TOP - (PAGE_SIZE - 1)).align_up(PAGE_SIZE),
+            Some(VirtAddr::new(TOP - (PAGE_SIZE - 1)))
+        );
+        assert_eq!(
+            VirtAddr::new(TOP - PAGE_SIZE).align_up(PAGE_SIZE),
+            Some(VirtAddr::new(TOP - (PAGE_SIZE - 1)))
+        );
+        // Past the start of the last page, rounding up would wrap around to zero.
+        assert_eq!(
+            VirtAddr::new(TOP - (PAGE_SIZE - 2)).align_up(PAGE_SIZE),
+            None
+        );
+        assert!(!top.is_aligned(2));
+        assert!(top.is_aligned(1));
+
+        let page = PhysAddr::new(5 * PAGE_SIZE);
+        assert!(page.is_aligned(PAGE_SIZE));
+        assert_eq!(page.align_up(PAGE_SIZE), Some(page));
+        assert_eq!(page.align_down(PAGE_SIZE), page);
+        assert_eq!((page + 1).align_up(PAGE_SIZE), Some(page + PAGE_SIZE));
+        assert_eq!((page - 1).align_down(PAGE_SIZE), page - PAGE_SIZE);
+        assert_eq!(page.page_number(), 5);
+        assert_eq!(page.page_offset(), 0);
+        assert_eq!((page + 0x123).page_number(), 5);
+        assert_eq!((page + 0x123).page_offset(), 0x123);
+        assert_eq!((page - 1).page_offset(), PAGE_SIZE - 1);
+        assert_eq!(top.page_number(), TOP / PAGE_SIZE);
+        assert_eq!(top.page_offset(), PAGE_SIZE - 1);
+        info!("addr: arithmetic and alignment checked");
+
+        let whole = range(0, TOP);
+        assert_eq!(whole.len(), TOP);
+        assert!(whole.contains(zero));
+        assert!(!whole.contains(top));
+        assert_eq!(VirtAddrRange::from_len(zero, TOP), Some(whole));
+        assert_eq!(VirtAddrRange::from_len(top, 0), Some(range(TOP, TOP)));
+        assert_eq!(VirtAddrRange::from_len(top, 1), None);
+        assert_eq!(VirtAddrRange::from_len(VirtAddr::new(1), TOP), None);
+
+        let empty = range(PAGE_SIZE, PAGE_SIZE);
+        let reversed = range(2 * PAGE_SIZE, PAGE_SIZE);
+        for empty in [empty, reversed] {
+            assert!(empty.is_empty());
+            assert_eq!(empty.len(), 0);
+            assert!(!empty.contains(VirtAddr::new(PAGE_SIZE)));
+            assert!(!empty.overlaps(whole));
+            assert!(!whole.overlaps(empty));
+            assert_eq!(empty.intersection(whole), None);
+            assert_eq!(empty.iter_pages(PAGE_SIZE).count(), 0);
+        }
+
+        let a = range(PAGE_SIZE, 3 * PAGE_SIZE);
+        let b = range(2 * PAGE_SIZE, 4 * PAGE_SIZE);
+        let c = range(3 * PAGE_SIZE, 4 * PAGE_SIZE);
+        assert!(a.contains(VirtAddr::new(PAGE_SIZE)));
+        assert!(!a.contains(VirtAddr::new(3 * PAGE_SIZE)));
+        assert!(a.overlaps(b) && b.overlaps(a));
+        assert_eq!(a.intersection(b), Some(range(2 * PAGE_SIZE, 3 * PAGE_SIZE)));
+        assert_eq!(a.intersection(whole), Some(a));
+        // Ranges sharing only a bound are disjoint.
+        assert!(!a.overlaps(c));
+        assert_eq!(a.intersection(c), None);
+
+        let pages = range(PAGE_SIZE + 1, 3 * PAGE_SIZE + 1)
+            .iter_pages(PAGE_SIZE)
+            .map(VirtAddr::as_usize)
+            .collect::<Vec<_>>();
+        assert_eq!(pages, [PAGE_SIZE, 2 * PAGE_SIZE, 3 * PAGE_SIZE]);
+        assert_eq!(a.iter_pages(PAGE_SIZE).count(), 2);
+        assert_eq!(a.iter_pages(2 * PAGE_SIZE).count(), 2);
+        let last = range(TOP - PAGE_SIZE, TOP)
+            .iter_pages(PAGE_SIZE)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            last,
+            [
+                VirtAddr::new(TOP - PAGE_SIZE).align_down(PAGE_SIZE),
+                top.align_down(PAGE_SIZE)
+            ]
+        );
+
+        let phys = PhysAddrRange::from_len(page, PAGE_SIZE).unwrap();
+        assert_eq!(phys.iter_pages(PAGE_SIZE).collect::<Vec<_>>(), [page]);
+        assert_eq!(
+            format!("{}", phys),
+            format!("[{}, {})", page, page + PAGE_SIZE)
+        );
+        let core_range: Range<VirtAddr> = a.into();
+        assert_eq!(VirtAddrRange::from(core_range), a);
+        info!("addr: ranges checked");
+    }
+}
+
+pub(super) mod mmio {
+    use alloc::vec;
+    use jrinx_addr::{PhysAddr, VirtAddr};
+    use jrinx_mmio::{register_block, MmioRegion};
+    use jrinx_testdef::testdef;
+
+    register_block! {
+        struct MockRegs {
+            @0x00 rbr: u8 [ro],
+            @0x00 thr: u8 [wo],
+            @0x02 div: u16 [rw],
+            @0x04 ctl: u32 [rw],
+            @0x08 cnt: u64 [rw],
+        }
+    }
+
+    #[testdef]
+    fn test() {
+        let mut mock = vec![0u64; 2];
+        let region = unsafe { MmioRegion::from_raw(VirtAddr::new(mock.as_mut_ptr() as usize), 16) };
+        let regs = MockRegs::new(region);
+        assert_eq!(regs.region(), &region);
+
+        regs.thr().write(0xa5);
+        assert_eq!(regs.rbr().read(), 0xa5);
+        assert_eq!(region.read::<u32>(0), 0xa5);
+
+        regs.div().write(0x1234);
+        assert_eq!(region.read::<u8>(2), 0x34);
+        assert_eq!(region.read::<u8>(3), 0x12);
+
+        regs.ctl().write(0xdead_beef);
+        regs.ctl().modify(|ctl| ctl & !0xff);
+        assert_eq!(regs.ctl().read(), 0xdead_be00);
+        assert_eq!(region.read::<u16>(6), 0xdead);
+        assert_eq!(regs.ctl().offset(), 4);
+
+        regs.cnt().write(0x0123_4567_89ab_cdef);
+        assert_eq!(region.read::<u32>(8), 0x89ab_cdef);
+        assert_eq!(region.read::<u32>(12), 0x0123_4567);
+        assert_eq!(mock[1], 0x0123_4567_89ab_cdef);
+
+        assert_eq!(mock[0], 0xdead_be00_1234_00a5);
+
+        assert!(MmioRegion::map(PhysAddr::new(usize::MAX - 0xff), 0x200).is_err());
+    }
+}
+
 pub(super) mod phys {
     use core::mem::forget;
 
@@ -7,7 +185,7 @@ pub(super) mod phys {
     use jrinx_phys_frame::PhysFrame;
     use jrinx_testdef::testdef;
 
-    #[testdef]
+    #[testdef(serial)]
     fn test() {
         let (frame1, addr1) = alloc().unwrap();
         let (frame2, addr2) = alloc().unwrap();
@@ -107,3 +285,519 @@ pub(super) mod virt {
         unsafe { *dst }
     }
 }
+
+pub(super) mod teardown {
+    use alloc::sync::Arc;
+
+    use jrinx_addr::VirtAddr;
+    use jrinx_hal::{Cpu, Hal, Vm};
+    use jrinx_paging::{common::PageTable, GenericPagePerm, GenericPageTable, PagePerm};
+    use jrinx_phys_frame::PhysFrame;
+    use jrinx_testdef::testdef;
+
+    const BASE: usize = 0x1000_0000;
+    const SIZE: usize = 64 * 1024 * 1024;
+
+    #[testdef(serial)]
+    fn test() {
+        let shared = PhysFrame::alloc().unwrap();
+
+        let mut page_table = populate(&shared);
+        let begin = hal!().cpu().get_time();
+        for i in (0..SIZE).step_by(jrinx_config::PAGE_SIZE) {
+            page_table.unmap(VirtAddr::new(BASE + i)).unwrap();
+            hal!().vm().sync_all();
+        }
+        let per_page = hal!().cpu().get_time() - begin;
+        assert_eq!(Arc::strong_count(&shared), 2);
+        // The root, the shared frame, and the page tables left behind.
+        assert!(page_table.frame_count() > 2);
+        drop(page_table);
+        assert_eq!(Arc::strong_count(&shared), 1);
+
+        let mut page_table = populate(&shared);
+        let begin = hal!().cpu().get_time();
+        assert_eq!(
+            page_table.clear().unwrap(),
+            SIZE / jrinx_config::PAGE_SIZE + 1
+        );
+        hal!().vm().sync_all();
+        let bulk = hal!().cpu().get_time() - begin;
+        assert_eq!(Arc::strong_count(&shared), 1);
+        assert_eq!(page_table.frame_count(), 1);
+        assert!(page_table.translate(VirtAddr::new(BASE)).is_err());
+        assert!(page_table
+            .unmap_range_bulk(VirtAddr::new(0)..VirtAddr::new(usize::MAX))
+            .is_err());
+
+        info!(
+            "{} MiB teardown: per-page {:?}, bulk {:?}",
+            SIZE / 1024 / 1024,
+            per_page,
+            bulk
+        );
+        assert!(bulk < per_page);
+    }
+
+    fn populate(shared: &Arc<PhysFrame>) -> PageTable {
+        let mut page_table = PageTable::new().unwrap();
+        let perm = PagePerm::U | PagePerm::R | PagePerm::W;
+        for i in (0..SIZE).step_by(jrinx_config::PAGE_SIZE) {
+            page_table
+                .map(VirtAddr::new(BASE + i), PhysFrame::alloc().unwrap(), perm)
+                .unwrap();
+        }
+        page_table
+            .map(VirtAddr::new(BASE + SIZE), shared.clone(), perm)
+            .unwrap();
+        page_table
+    }
+}
+
+pub(super) mod huge {
+    use jrinx_addr::{PhysAddr, VirtAddr, VirtAddrRange};
+    use jrinx_config::PAGE_SIZE;
+    use jrinx_paging::{common::PageTable, GenericPagePerm, GenericPageTable, PagePerm, PageSize};
+    use jrinx_testdef::testdef;
+
+    const BASE: usize = 0x4000_0000;
+    const PHYS: usize = 0x4000_0000;
+
+    fn range(start: usize, end: usize) -> VirtAddrRange {
+        VirtAddrRange::new(VirtAddr::new(start), VirtAddr::new(end))
+    }
+
+    fn translate(page_table: &PageTable, addr: usize) -> Option<(usize, PagePerm)> {
+        page_table
+            .translate(VirtAddr::new(addr))
+            .ok()
+            .map(|(phys_addr, perm)| (phys_addr.as_usize(), perm))
+    }
+
+    #[testdef]
+    fn test() {
+        let huge = PageSize::ALL[1];
+        let size = huge.bytes();
+        let perm = PagePerm::R | PagePerm::W;
+
+        let mut page_table = PageTable::new().unwrap();
+        let phys = PhysAddr::new(PHYS);
+        assert!(page_table
+            .map_huge(VirtAddr::new(BASE + PAGE_SIZE), phys, huge, perm)
+            .is_err());
+        assert!(page_table
+            .map_huge(VirtAddr::new(BASE), phys + PAGE_SIZE, huge, perm)
+            .is_err());
+        assert!(page_table
+            .map_range_best_fit(range(BASE, BASE + 1), phys, perm)
+            .is_err());
+        page_table
+            .map_huge(VirtAddr::new(BASE), phys, huge, perm)
+            .unwrap();
+        assert!(page_table
+            .map_huge(VirtAddr::new(BASE), phys, huge, perm)
+            .is_err());
+        assert_eq!(
+            page_table.unmap_huge(range(BASE, BASE + size)).unwrap(),
+            size
+        );
+        assert!(translate(&page_table, BASE).is_none());
+
+        // A small page on each side of a huge one.
+        let mapped = range(BASE - PAGE_SIZE, BASE + size + PAGE_SIZE);
+        page_table
+            .map_range_best_fit(mapped, phys - PAGE_SIZE, perm)
+            .unwrap();
+        assert_eq!(
+            page_table.direct_leaf(VirtAddr::new(BASE - 1)),
+            Some((VirtAddr::new(BASE - PAGE_SIZE), PageSize::ALL[0]))
+        );
+        assert_eq!(
+            page_table.direct_leaf(VirtAddr::new(BASE + size / 2)),
+            Some((VirtAddr::new(BASE), huge))
+        );
+        assert_eq!(
+            page_table.direct_leaf(VirtAddr::new(BASE + size)),
+            Some((VirtAddr::new(BASE + size), PageSize::ALL[0]))
+        );
+        for offset in [0, 8, PAGE_SIZE + 8, size / 2, size + PAGE_SIZE + 8] {
+            let (phys_addr, leaf_perm) = translate(&page_table, BASE - PAGE_SIZE + offset).unwrap();
+            assert_eq!(phys_addr, PHYS - PAGE_SIZE + offset);
+            assert!(leaf_perm.contains(perm));
+        }
+        assert!(translate(&page_table, BASE + size + PAGE_SIZE).is_none());
+        info!("huge: best-fit mapping checked");
+
+        assert_eq!(
+            page_table
+                .change_perm(range(BASE + PAGE_SIZE, BASE + 2 * PAGE_SIZE), PagePerm::R)
+                .unwrap(),
+            PAGE_SIZE
+        );
+        let (_, leaf_perm) = translate(&page_table, BASE + PAGE_SIZE).unwrap();
+        assert!(!leaf_perm.contains(PagePerm::W));
+        for offset in [0, 2 * PAGE_SIZE, size - PAGE_SIZE] {
+            let (phys_addr, leaf_perm) = translate(&page_table, BASE + offset).unwrap();
+            assert_eq!(phys_addr, PHYS + offset);
+            assert!(leaf_perm.contains(perm));
+        }
+        assert!(page_table
+            .direct_leaf(VirtAddr::new(BASE + size / 2))
+            .is_some_and(|(_, leaf)| leaf < huge));
+
+        page_table
+            .unmap(VirtAddr::new(BASE + 2 * PAGE_SIZE))
+            .unwrap();
+        assert!(translate(&page_table, BASE + 2 * PAGE_SIZE).is_none());
+        assert_eq!(
+            translate(&page_table, BASE + 3 * PAGE_SIZE).map(|(phys_addr, _)| phys_addr),
+            Some(PHYS + 3 * PAGE_SIZE)
+        );
+        info!("huge: partial change and unmap checked");
+
+        assert_eq!(
+            page_table.clear().unwrap(),
+            (size + 2 * PAGE_SIZE) / PAGE_SIZE - 1
+        );
+        assert_eq!(page_table.frame_count(), 1);
+        assert!(translate(&page_table, BASE).is_none());
+
+        page_table
+            .map_range_best_fit(mapped, phys - PAGE_SIZE, perm)
+            .unwrap();
+        assert!(page_table
+            .unmap_huge(range(BASE, BASE + PAGE_SIZE + 1))
+            .is_err());
+        assert_eq!(
+            page_table
+                .unmap_huge(range(BASE + PAGE_SIZE, BASE + 2 * PAGE_SIZE))
+                .unwrap(),
+            PAGE_SIZE
+        );
+        assert_eq!(
+            page_table
+                .unmap_huge(range(BASE - PAGE_SIZE, BASE + size + PAGE_SIZE))
+                .unwrap(),
+            size + PAGE_SIZE
+        );
+        assert!(translate(&page_table, BASE).is_none());
+        info!("huge: unmapping checked");
+    }
+}
+
+pub(super) mod soft_dirty {
+    use alloc::collections::BTreeSet;
+    use core::{ops::Range, time::Duration};
+
+    use jrinx_addr::VirtAddr;
+    use jrinx_hal::{Cpu, Hal, Vm};
+    use jrinx_paging::{GenericPagePerm, GenericPageTable, PagePerm};
+    use jrinx_phys_frame::PhysFrame;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::{page_fault, smp};
+    use jrinx_vmm::KERN_PAGE_TABLE;
+
+    const BASE: usize = 0x2000_0000;
+    const PAGES: usize = 64;
+
+    #[testdef]
+    fn test() {
+        let range = VirtAddr::new(BASE)..VirtAddr::new(BASE + PAGES * jrinx_config::PAGE_SIZE);
+        {
+            let mut page_table = KERN_PAGE_TABLE.write();
+            for page in 0..PAGES {
+                page_table
+                    .map(
+                        page_addr(page),
+                        PhysFrame::alloc().unwrap(),
+                        PagePerm::G | PagePerm::R | PagePerm::W,
+                    )
+                    .unwrap();
+            }
+        }
+        hal!().vm().sync_all();
+
+        // Freshly mapped pages count as written.
+        assert_eq!(dirty(&range).len(), PAGES);
+
+        let local_id = hal!().cpu().id();
+        let remote_id = (0..hal!().cpu().nproc_valid())
+            .find(|&cpu_id| cpu_id != local_id)
+            .unwrap();
+        let cpu_ids = [local_id, remote_id];
+
+        for round in 0..4 {
+            assert_eq!(
+                KERN_PAGE_TABLE
+                    .write()
+                    .clear_soft_dirty(range.clone())
+                    .unwrap(),
+                PAGES
+            );
+            smp::call(&cpu_ids, Duration::from_secs(1), || {
+                hal!().vm().sync_all();
+                0
+            })
+            .unwrap()
+            .into_result()
+            .unwrap();
+            assert!(dirty(&range).is_empty());
+
+            let expected = cpu_ids
+                .iter()
+                .flat_map(|&cpu_id| written(cpu_id, round))
+                .collect::<BTreeSet<_>>();
+
+            let faults = page_fault::count();
+            smp::call(&cpu_ids, Duration::from_secs(1), move || {
+                let cpu_id = hal!().cpu().id();
+                for page in written(cpu_id, round) {
+                    let ptr = page_addr(page).as_usize() as *mut usize;
+                    unsafe { ptr.add(cpu_id).write_volatile(round) };
+                }
+                0
+            })
+            .unwrap()
+            .into_result()
+            .unwrap();
+
+            assert_eq!(dirty(&range), expected);
+            assert!(page_fault::count() - faults >= expected.len() as u64);
+        }
+
+        let mut page_table = KERN_PAGE_TABLE.write();
+        for page in 0..PAGES {
+            page_table.unmap(page_addr(page)).unwrap();
+        }
+        drop(page_table);
+        hal!().vm().sync_all();
+    }
+
+    /// Each hart writes its own stride of pages, with every fourth page written by both.
+    fn written(cpu_id: usize, round: usize) -> impl Iterator<Item = usize> {
+        (0..PAGES).filter(move |page| page % 4 == 0 || page % 3 == (cpu_id + round) % 3)
+    }
+
+    fn page_addr(page: usize) -> VirtAddr {
+        VirtAddr::new(BASE + page * jrinx_config::PAGE_SIZE)
+    }
+
+    fn dirty(range: &Range<VirtAddr>) -> BTreeSet<usize> {
+        KERN_PAGE_TABLE
+            .read()
+            .collect_dirty(range.clone())
+            .unwrap()
+            .map(|(addr, _)| (addr.as_usize() - BASE) / jrinx_config::PAGE_SIZE)
+            .collect()
+    }
+}
+
+pub(super) mod cow {
+    use alloc::{alloc::Global, collections::BTreeSet, sync::Arc};
+
+    use jrinx_addr::{PhysAddr, VirtAddr};
+    use jrinx_paging::{common::PageTable, GenericPagePerm, GenericPageTable, PagePerm};
+    use jrinx_phys_frame::PhysFrame;
+    use jrinx_testdef::testdef;
+
+    const BASE: usize = 0x3000_0000;
+    const PRIVATE: usize = 8;
+
+    #[testdef]
+    fn test() {
+        let mut parent = PageTable::new().unwrap();
+        let perm = PagePerm::U | PagePerm::R | PagePerm::W;
+        for i in 0..PRIVATE {
+            parent
+                .map(page(i), PhysFrame::alloc().unwrap(), perm)
+                .unwrap();
+            unsafe { word(&parent, i).write(i) };
+        }
+        let readonly = PhysFrame::alloc().unwrap();
+        parent
+            .map(page(PRIVATE), readonly.clone(), PagePerm::U | PagePerm::R)
+            .unwrap();
+        let shared = PhysFrame::alloc().unwrap();
+        parent
+            .map_shared(page(PRIVATE + 1), shared.clone(), perm)
+            .unwrap();
+        let frames = parent.frame_count();
+
+        // Both tables hold the same frames, the private ones write-protected.
+        let mut child = parent.duplicate_cow().unwrap();
+        assert_eq!(child.frame_count(), frames);
+        for i in 0..PRIVATE {
+            let (parent_frame, parent_perm) = parent.lookup(page(i)).unwrap();
+            let (child_frame, child_perm) = child.lookup(page(i)).unwrap();
+            assert!(Arc::ptr_eq(&parent_frame, &child_frame));
+            assert!(!parent_perm.contains(PagePerm::W) && !child_perm.contains(PagePerm::W));
+            assert!(parent.is_cow(page(i)) && child.is_cow(page(i)));
+            assert_eq!(unsafe { word(&child, i).read() }, i);
+        }
+
+        // Read-only and shared pages are left as they are.
+        assert!(!child.is_cow(page(PRIVATE)));
+        assert!(!child.resolve_cow_write(page(PRIVATE), Global).unwrap());
+        let (frame, shared_perm) = child.lookup(page(PRIVATE + 1)).unwrap();
+        assert!(Arc::ptr_eq(&frame, &shared) && shared_perm.contains(PagePerm::W));
+        assert!(!child.is_cow(page(PRIVATE + 1)));
+        drop(frame);
+        assert_eq!(Arc::strong_count(&readonly), 3);
+        assert_eq!(Arc::strong_count(&shared), 3);
+
+        // The first side to write gets a copy, the other one keeps the frame, then alone.
+        for i in 0..PRIVATE {
+            let original = addr(&parent, i);
+            assert!(parent.resolve_cow_write(page(i), Global).unwrap());
+            assert_ne!(addr(&parent, i), original);
+            unsafe { word(&parent, i).write(i + 100) };
+
+            assert!(child.resolve_cow_write(page(i), Global).unwrap());
+            assert_eq!(addr(&child, i), original);
+            unsafe { word(&child, i).write(i + 200) };
+        }
+        assert!(!parent.resolve_cow_write(page(0), Global).unwrap());
+
+        let parent_frames: BTreeSet<_> = (0..PRIVATE).map(|i| addr(&parent, i)).collect();
+        let child_frames: BTreeSet<_> = (0..PRIVATE).map(|i| addr(&child, i)).collect();
+        assert!(parent_frames.is_disjoint(&child_frames));
+        for i in 0..PRIVATE {
+            assert_eq!(unsafe { word(&parent, i).read() }, i + 100);
+            assert_eq!(unsafe { word(&child, i).read() }, i + 200);
+            assert!(!parent.is_cow(page(i)) && !child.is_cow(page(i)));
+            let (frame, _) = child.lookup(page(i)).unwrap();
+            assert_eq!(Arc::strong_count(&frame), 2);
+        }
+        assert_eq!(parent.frame_count(), frames);
+        assert_eq!(child.frame_count(), frames);
+
+        drop(child);
+        assert_eq!(Arc::strong_count(&readonly), 2);
+        assert_eq!(Arc::strong_count(&shared), 2);
+        info!("{} private pages diverged after duplication", PRIVATE);
+    }
+
+    fn page(i: usize) -> VirtAddr {
+        VirtAddr::new(BASE + i * jrinx_config::PAGE_SIZE)
+    }
+
+    fn addr(page_table: &PageTable, i: usize) -> PhysAddr {
+        page_table.translate(page(i)).unwrap().0
+    }
+
+    fn word(page_table: &PageTable, i: usize) -> *mut usize {
+        addr(page_table, i).to_virt().as_usize() as *mut usize
+    }
+}
+
+pub(super) mod space {
+    use jrinx_addr::{PhysAddr, VirtAddr};
+    use jrinx_paging::{common::PageTable, GenericPagePerm, GenericPageTable, PagePerm};
+    use jrinx_phys_frame::PhysFrame;
+    use jrinx_testdef::testgroup;
+    use spin::Mutex;
+
+    const BASE: usize = 0x4000_0000;
+    const PAGES: usize = 256;
+
+    /// An address space whose pages each hold their index in their first word.
+    pub struct Space {
+        page_table: Mutex<PageTable>,
+        frames: usize,
+    }
+
+    #[testgroup(teardown)]
+    async fn setup() -> Space {
+        let mut page_table = PageTable::new().unwrap();
+        for i in 0..PAGES {
+            page_table
+                .map(
+                    page(i),
+                    PhysFrame::alloc().unwrap(),
+                    PagePerm::U | PagePerm::R | PagePerm::W,
+                )
+                .unwrap();
+            unsafe { word(&page_table, i).write(i) };
+        }
+        let frames = page_table.frame_count();
+        Space {
+            page_table: Mutex::new(page_table),
+            frames,
+        }
+    }
+
+    async fn teardown(space: Space) {
+        let mut page_table = space.page_table.into_inner();
+        // The tests leave the space as they found it.
+        assert_eq!(page_table.frame_count(), space.frames);
+        assert_eq!(page_table.clear().unwrap(), PAGES);
+        assert_eq!(page_table.frame_count(), 1);
+        info!("address space of {} pages torn down", PAGES);
+    }
+
+    pub(super) mod translate {
+        use jrinx_paging::{GenericPagePerm, GenericPageTable, PagePerm};
+        use jrinx_testdef::testdef;
+
+        use super::{page, word, Space, PAGES};
+
+        #[testdef]
+        fn test(space: &Space) {
+            let page_table = space.page_table.lock();
+            for i in 0..PAGES {
+                let (_, perm) = page_table.translate(page(i)).unwrap();
+                assert!(perm.contains(PagePerm::U | PagePerm::R | PagePerm::W));
+                assert_eq!(unsafe { word(&page_table, i).read() }, i);
+            }
+            assert!(page_table.translate(page(PAGES)).is_err());
+        }
+    }
+
+    pub(super) mod remap {
+        use jrinx_paging::{common::PageTable, GenericPageTable};
+        use jrinx_testdef::testdef;
+
+        use super::{page, word, Space, PAGES};
+
+        #[testdef]
+        fn test(space: &Space) {
+            let mut page_table = space.page_table.lock();
+            // Each page of a pair then reads the word of the other.
+            for i in (0..PAGES).step_by(2) {
+                swap(&mut page_table, i);
+            }
+            for i in 0..PAGES {
+                assert_eq!(unsafe { word(&page_table, i).read() }, i ^ 1);
+            }
+
+            for i in (0..PAGES).step_by(2) {
+                swap(&mut page_table, i);
+            }
+            for i in 0..PAGES {
+                assert_eq!(unsafe { word(&page_table, i).read() }, i);
+            }
+            assert_eq!(page_table.frame_count(), space.frames);
+        }
+
+        fn swap(page_table: &mut PageTable, i: usize) {
+            let (frame, perm) = page_table.lookup(page(i)).unwrap();
+            let (other, other_perm) = page_table.lookup(page(i + 1)).unwrap();
+            page_table.unmap(page(i)).unwrap();
+            page_table.unmap(page(i + 1)).unwrap();
+            page_table.map(page(i), other, other_perm).unwrap();
+            page_table.map(page(i + 1), frame, perm).unwrap();
+        }
+    }
+
+    fn page(i: usize) -> VirtAddr {
+        VirtAddr::new(BASE + i * jrinx_config::PAGE_SIZE)
+    }
+
+    fn word(page_table: &PageTable, i: usize) -> *mut usize {
+        addr(page_table, i).to_virt().as_usize() as *mut usize
+    }
+
+    fn addr(page_table: &PageTable, i: usize) -> PhysAddr {
+        page_table.translate(page(i)).unwrap().0
+    }
+}
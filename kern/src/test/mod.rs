@@ -1,6 +1,489 @@
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    future::Future,
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use jrinx_hal::{Cpu, Hal};
+use jrinx_multitask::{
+    executor::{self, Executor, ExecutorPriority},
+    inspector::Inspector,
+    runtime::Runtime,
+    spawn, Task, TaskName, TaskPriority,
+};
+use jrinx_testdef::{Fixture, GroupDef, TestDef, TestFn};
+use spin::{Mutex, MutexGuard};
+
+mod backtrace;
+mod bench;
+mod boot_log;
+mod bootargs;
+mod dmesg;
+mod earlycon;
+mod errno;
+mod error;
+mod error_context;
 mod heap;
+mod init;
+mod kpanic;
+mod log_colors;
+mod log_filter;
+mod log_prefix;
+mod log_scope;
+mod logging;
+mod lowmem;
 mod mm;
+mod semaphore;
+mod shell;
 mod stack;
+mod stats;
+mod sync;
+mod syscall;
 mod task;
+mod test_attrs;
+mod test_glob;
+mod test_jobs;
+mod test_run;
 mod time;
 mod trap;
+mod tunable;
+mod user;
+mod watchdog;
+
+/// Longest a test of a run may leave its task pending before it is failed as hung, unless it
+/// sets a timeout of its own.
+///
+/// A test looping without ever yielding holds up its CPU, and is beyond this.
+const WATCHDOG: Duration = Duration::from_secs(30);
+
+/// Period at which a run checks on the test it waits for.
+const WATCHDOG_PERIOD: Duration = Duration::from_millis(10);
+
+/// Attempts of the panic handler to lock [`ISOLATED`].
+const ISOLATED_LOCK_SPINS: usize = 1 << 16;
+
+/// Tests whose panics only fail the test, as a run is waiting for them, by the name of their
+/// task, along with whether each panicked and its task was given up on.
+static ISOLATED: Mutex<BTreeMap<&'static str, bool>> = Mutex::new(BTreeMap::new());
+
+/// A test selected to run, by the selector it came from, if that selects any.
+type Selection<'a> = (&'a str, Option<&'static TestDef>);
+
+/// Resolves `selectors` into the tests they select, in order.
+///
+/// A glob selects the tests it matches in sorted order, and panics if it matches none, while
+/// a name selects the test it is part of as for a single test. A test selected more than once
+/// is only selected the first time, and the tests of a group are moved up to the first of
+/// them selected, so that the group is set up once.
+pub fn select<'a>(selectors: &[&'a str]) -> Vec<Selection<'a>> {
+    let mut selection = Vec::new();
+    for &selector in selectors {
+        if !jrinx_testdef::is_glob(selector) {
+            selection.push((selector, jrinx_testdef::find(selector)));
+            continue;
+        }
+        let mut tests = jrinx_testdef::find_all(selector).collect::<Vec<_>>();
+        if tests.is_empty() {
+            panic!("test pattern matches no test case: {}", selector);
+        }
+        tests.sort_unstable_by_key(|test| test.name());
+        selection.extend(tests.into_iter().map(|test| (selector, Some(test))));
+    }
+
+    let mut selected = BTreeSet::new();
+    selection.retain(|(_, test)| test.map_or(true, |test| selected.insert(test.name())));
+
+    let mut ordered: Vec<Selection> = Vec::with_capacity(selection.len());
+    for (selector, test) in selection {
+        let last_of_group = test.and_then(TestDef::group).and_then(|group| {
+            ordered.iter().rposition(|(_, other)| {
+                other
+                    .and_then(TestDef::group)
+                    .is_some_and(|other| ptr::eq(other, group))
+            })
+        });
+        match last_of_group {
+            Some(i) => ordered.insert(i + 1, (selector, test)),
+            None => ordered.push((selector, test)),
+        }
+    }
+    ordered
+}
+
+/// Logs the tests selected by `selectors` without running them.
+pub fn list(selectors: &[&str]) {
+    info!("selected tests:");
+    for (selector, test) in select(selectors) {
+        match test {
+            Some(test) => info!("- {}", test.name()),
+            None => error!("unrecognized test case: {}", selector),
+        }
+    }
+}
+
+/// Runs the tests selected by `selectors` as by [`select`], in order and each in its own task,
+/// and logs a summary naming the selector of each failure, returning the number of failures.
+///
+/// A test fails as by [`run_isolated`], or by its selector matching no test. The fixture of a
+/// group is set up before its first test and torn down after its last, even if some of them
+/// failed. Its tests fail if it cannot be set up, while failing to tear it down is reported
+/// apart.
+pub async fn run_all(selectors: &[&str]) -> usize {
+    let mut summary = Summary::default();
+    run_selection(select(selectors), &mut summary).await;
+    summary.log()
+}
+
+/// Runs the tests selected by `selectors` as [`run_all`] does, but up to `jobs` of them at once
+/// on as many CPUs, and logs a summary sorted by test name whatever order they end in.
+///
+/// Each CPU taking part runs a test runner inspector, balanced across CPUs, taking the next
+/// test left until none is. Tests marked serial, and those of a group, run alone on CPU 0 after
+/// the others are over.
+pub async fn run_parallel(selectors: &[&str], jobs: usize) -> usize {
+    let (serial, parallel): (Vec<_>, Vec<_>) = select(selectors)
+        .into_iter()
+        .partition(|(_, test)| test.map_or(true, |test| test.serial() || test.group().is_some()));
+    let tests: Arc<[&'static TestDef]> = parallel.iter().filter_map(|(_, test)| *test).collect();
+    let results = Arc::new(Mutex::new(BTreeMap::new()));
+
+    let runners = jobs.min(hal!().cpu().nproc_valid()).min(tests.len());
+    let begin = hal!().cpu().get_time();
+    let next = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+    let mut cpu_ids = BTreeSet::new();
+    for _ in 0..runners {
+        let (tests, results, next, done) =
+            (tests.clone(), results.clone(), next.clone(), done.clone());
+        let inspector = Inspector::new();
+        inspector
+            .register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(
+                    async move {
+                        while let Some(&test) = tests.get(next.fetch_add(1, Ordering::SeqCst)) {
+                            let passed = run_test(test, None).await;
+                            results.lock().insert(test.name(), passed);
+                        }
+                        done.fetch_add(1, Ordering::SeqCst);
+                    },
+                    TaskPriority::default(),
+                )
+                .with_name("test-runner"),
+            ))
+            .unwrap();
+        cpu_ids.insert(Runtime::register_balanced(inspector).unwrap());
+    }
+    while done.load(Ordering::SeqCst) < runners {
+        jrinx_multitask::time::sleep(WATCHDOG_PERIOD).await;
+    }
+    info!(
+        "{} tests run on {} cpus in {:?}",
+        tests.len(),
+        cpu_ids.len(),
+        hal!().cpu().get_time() - begin
+    );
+
+    let mut summary = Summary::default();
+    {
+        let results = results.lock();
+        for (selector, test) in parallel {
+            let test = test.unwrap();
+            summary.record(selector, test, results[&test.name()]);
+        }
+    }
+
+    if !serial.is_empty() {
+        // The runner moves over to CPU 0, where it goes on once it switches out.
+        let id = Inspector::with_current(|is| is.id()).unwrap();
+        Runtime::with_current(|rt| rt.migrate_inspector(id, 0)).unwrap();
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+        run_selection(serial, &mut summary).await;
+    }
+    summary.failures.sort_unstable();
+    summary.log()
+}
+
+/// Tallies of a run, logged as its summary.
+#[derive(Default)]
+struct Summary {
+    passed: usize,
+    failures: Vec<String>,
+    teardown_failures: Vec<&'static str>,
+}
+
+impl Summary {
+    fn record(&mut self, selector: &str, test: &TestDef, passed: bool) {
+        if passed {
+            self.passed += 1;
+        } else if test.name() == selector {
+            self.failures.push(test.name().into());
+        } else {
+            self.failures
+                .push(format!("{} (from {})", test.name(), selector));
+        }
+    }
+
+    /// Logs the summary, returning the number of failures, teardowns included.
+    fn log(&self) -> usize {
+        let mut summary = format!(
+            "test result: {} passed; {} failed; failures: [{}]",
+            self.passed,
+            self.failures.len(),
+            self.failures.join(", ")
+        );
+        if !self.teardown_failures.is_empty() {
+            summary += &format!(
+                "; teardown failures: [{}]",
+                self.teardown_failures.join(", ")
+            );
+        }
+        info!("{}", summary);
+        self.failures.len() + self.teardown_failures.len()
+    }
+}
+
+/// Runs `selection` in order as [`run_all`] does, tallying the results into `summary`.
+async fn run_selection(selection: Vec<Selection<'_>>, summary: &mut Summary) {
+    let mut group: Option<(&'static GroupDef, Option<Box<Fixture>>)> = None;
+    for (selector, test) in selection {
+        let Some(test) = test else {
+            error!("unrecognized test case: {}", selector);
+            summary.failures.push(selector.into());
+            continue;
+        };
+
+        let test_group = test.group();
+        let current = group.as_ref().map(|(group, _)| *group as *const GroupDef);
+        if current != test_group.map(|group| group as *const GroupDef) {
+            if let Some((group, fixture)) = group.take() {
+                if !teardown(group, fixture).await {
+                    summary.teardown_failures.push(group.name());
+                }
+            }
+            if let Some(test_group) = test_group {
+                group = Some((test_group, setup(test_group).await));
+            }
+        }
+
+        // SAFETY: The fixture outlives the tests of its group, as a test is done with it once
+        // it returns, and its task is never polled again once it panics or is aborted.
+        let fixture = group
+            .as_ref()
+            .and_then(|(_, fixture)| fixture.as_deref())
+            .map(|fixture| unsafe { &*(fixture as *const Fixture) });
+        let passed = match (test.test(), test_group, fixture) {
+            (TestFn::Grouped(_), None, _) => {
+                error!(
+                    "test case {} takes a fixture, but is in no group",
+                    test.name()
+                );
+                false
+            }
+            (TestFn::Grouped(_), Some(group), None) => {
+                error!(
+                    "test case {} failed, as group {} is not set up",
+                    test.name(),
+                    group.name()
+                );
+                false
+            }
+            _ => run_test(test, fixture).await,
+        };
+        summary.record(selector, test, passed);
+    }
+    if let Some((group, fixture)) = group {
+        if !teardown(group, fixture).await {
+            summary.teardown_failures.push(group.name());
+        }
+    }
+}
+
+/// Sets up the fixture of `group` in a task of its own as for a test, returning it unless that
+/// fails.
+async fn setup(group: &'static GroupDef) -> Option<Box<Fixture>> {
+    let name = group.name();
+    info!("test group {} setup begin", name);
+    match isolate(name, WATCHDOG, group.setup()).await {
+        Outcome::Returned(fixture) => {
+            info!("test group {} setup end", name);
+            Some(fixture)
+        }
+        outcome => {
+            report(&format!("test group {} setup", name), &outcome, WATCHDOG);
+            error!("test group {} setup failed", name);
+            None
+        }
+    }
+}
+
+/// Tears down the fixture of `group`, if it was set up, in a task of its own as for a test,
+/// returning whether that succeeded.
+async fn teardown(group: &'static GroupDef, fixture: Option<Box<Fixture>>) -> bool {
+    let Some(fixture) = fixture else {
+        return true;
+    };
+    let name = group.name();
+    info!("test group {} teardown begin", name);
+    match isolate(name, WATCHDOG, group.teardown(fixture)).await {
+        Outcome::Returned(()) => {
+            info!("test group {} teardown end", name);
+            true
+        }
+        outcome => {
+            report(&format!("test group {} teardown", name), &outcome, WATCHDOG);
+            error!("test group {} teardown failed", name);
+            false
+        }
+    }
+}
+
+/// How the task of a test ended.
+enum Outcome<T> {
+    Returned(T),
+    Panicked,
+    Cancelled,
+    TimedOut,
+}
+
+/// Runs `test` in its own task, whose panics only fail the test, and returns whether it passed.
+///
+/// A test fails by panicking unless it should, by its task being cancelled, or by running past
+/// its timeout or, without one, by pending for longer than [`WATCHDOG`]. A test pending past
+/// its time is aborted, while one holding up its CPU is only failed once it returns.
+///
+/// A test of a group is run by [`run_all`], which sets its fixture up.
+pub async fn run_isolated(test: &'static TestDef) -> bool {
+    if matches!(test.test(), TestFn::Grouped(_)) {
+        return run_all(&[test.name()]).await == 0;
+    }
+    run_test(test, None).await
+}
+
+async fn run_test(test: &'static TestDef, fixture: Option<&'static Fixture>) -> bool {
+    let name = test.name();
+    info!("test case {} begin", name);
+    let func = test.test();
+    let budget = test.timeout().unwrap_or(WATCHDOG);
+    let outcome = isolate(name, budget, async move {
+        match func {
+            TestFn::Plain(func) => func(),
+            TestFn::Grouped(func) => func(fixture.unwrap()),
+        }
+    })
+    .await;
+
+    let passed = match outcome {
+        Outcome::Returned(()) if test.should_panic() => {
+            error!("test case {} did not panic", name);
+            false
+        }
+        Outcome::Returned(()) => true,
+        Outcome::Panicked => test.should_panic(),
+        ref outcome => {
+            report(&format!("test case {}", name), outcome, budget);
+            false
+        }
+    };
+    if passed {
+        info!("test case {} end", name);
+    } else {
+        error!("test case {} failed", name);
+    }
+    passed
+}
+
+/// Runs `future` in a task named `name`, whose panics only end the task, for at most `budget`
+/// as for a test.
+async fn isolate<T>(
+    name: &'static str,
+    budget: Duration,
+    future: impl Future<Output = T> + Send + 'static,
+) -> Outcome<T>
+where
+    T: Send + 'static,
+{
+    ISOLATED.lock().insert(name, false);
+
+    let mut handle = spawn!(name = name, future);
+    let deadline = hal!().cpu().get_time() + budget;
+    let outcome = loop {
+        match jrinx_multitask::time::timeout(WATCHDOG_PERIOD, &mut handle).await {
+            Ok(_) if hal!().cpu().get_time() > deadline => break Outcome::TimedOut,
+            Ok(Ok(value)) => break Outcome::Returned(value),
+            Ok(Err(_)) => break Outcome::Cancelled,
+            Err(_) if ISOLATED.lock().get(name) == Some(&true) => break Outcome::Panicked,
+            Err(_) if hal!().cpu().get_time() >= deadline => {
+                handle.abort();
+                break Outcome::TimedOut;
+            }
+            Err(_) => {}
+        }
+    };
+    ISOLATED.lock().remove(name);
+    outcome
+}
+
+/// Logs how `what` failed, unless it only panicked, which has been logged already.
+fn report<T>(what: &str, outcome: &Outcome<T>, budget: Duration) {
+    match outcome {
+        Outcome::Returned(_) | Outcome::Panicked => {}
+        Outcome::Cancelled => error!("{} was cancelled", what),
+        Outcome::TimedOut => error!("{} timed out after {:?}", what, budget),
+    }
+}
+
+/// Returns whether a panic on the current CPU is one of the test a run waits for, which then
+/// only fails that test, see [`abort_isolated`].
+pub fn panic_is_isolated() -> bool {
+    executor::can_abort_polling()
+        && matches!(
+            executor::polling_task_name(),
+            Some(TaskName::Named(name))
+                if lock_isolated().is_some_and(|isolated| isolated.contains_key(name))
+        )
+}
+
+/// Fails the isolated test, which panicked, giving up on its task.
+pub fn abort_isolated() -> ! {
+    if let Some(TaskName::Named(name)) = executor::polling_task_name() {
+        if let Some(aborted) = lock_isolated()
+            .as_mut()
+            .and_then(|isolated| isolated.get_mut(name))
+        {
+            *aborted = true;
+        }
+    }
+    executor::abort_polling()
+}
+
+/// Locks [`ISOLATED`] for the panic handler, which gives up after a while rather than block
+/// forever, should the panicking CPU hold the lock.
+///
+/// Runners on other CPUs only hold it briefly, so a single attempt could fail a run spuriously.
+fn lock_isolated() -> Option<MutexGuard<'static, BTreeMap<&'static str, bool>>> {
+    (0..ISOLATED_LOCK_SPINS).find_map(|_| {
+        let isolated = ISOLATED.try_lock();
+        if isolated.is_none() {
+            core::hint::spin_loop();
+        }
+        isolated
+    })
+}
+
+/// Runs the part of the test `name` which must run at boot, before the heap exists.
+pub fn early(name: &str) {
+    if jrinx_testdef::find(name).is_some_and(|test| test.name() == boot_log::NAME) {
+        boot_log::early();
+    }
+}
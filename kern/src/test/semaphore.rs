@@ -0,0 +1,196 @@
+use alloc::{sync::Arc, task::Wake, vec};
+use core::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use jrinx_a653::{
+    partition::{Partition, PartitionConfig, PartitionTypeConfig},
+    process::{Process, ProcessConfig},
+    semaphore::{Semaphore, SemaphoreConfig, SemaphoreProtocol},
+    A653Entry,
+};
+use jrinx_abi::cap::Capabilities;
+use jrinx_apex::*;
+use jrinx_error::{InternalError, Result};
+use jrinx_hal::{Cpu, Hal};
+use jrinx_multitask::inspector::ResourceLimits;
+use jrinx_testdef::testdef;
+
+const LOW: ApexPriority = 1;
+const MEDIUM: ApexPriority = 5;
+const HIGH: ApexPriority = 10;
+
+#[testdef]
+fn test() {
+    let partition = Partition::new(&PartitionConfig {
+        name: "semaphore".try_into().unwrap(),
+        memory: 0x100000,
+        period: APEX_TIME_INFINITY,
+        duration: APEX_TIME_INFINITY,
+        num_cores: 1,
+        limits: ResourceLimits::default(),
+        capabilities: Capabilities::all(),
+        lowmem_action: None,
+        semaphores: vec![
+            config("inherit", HIGH, SemaphoreProtocol::Inheritance),
+            config("ceiling", HIGH, SemaphoreProtocol::Ceiling),
+            config("low", MEDIUM, SemaphoreProtocol::Inheritance),
+        ],
+        partition_type: PartitionTypeConfig::User(
+            jrinx_uprog::find("test/user/capability").unwrap(),
+        ),
+    })
+    .unwrap();
+    let id = partition.identifier();
+
+    let low = new_process(&partition, "low", LOW);
+    let medium = new_process(&partition, "medium", MEDIUM);
+    let high = new_process(&partition, "high", HIGH);
+    let new_semaphore = |name: &str| {
+        Semaphore::new(
+            id,
+            name.try_into().unwrap(),
+            1,
+            1,
+            ApexQueueDiscipline::Priority,
+        )
+        .unwrap()
+    };
+
+    // Priority inheritance: the holder is raised once the high-priority process waits.
+    let semaphore = new_semaphore("inherit");
+    assert_eq!(semaphore.protocol(), SemaphoreProtocol::Inheritance);
+    assert!(matches!(
+        poll(semaphore.wait(&low, Duration::MAX)),
+        Poll::Ready(Ok(true))
+    ));
+    assert_eq!(low.curr_priority(), LOW);
+    {
+        let mut wait = pin!(semaphore.wait(&high, Duration::MAX));
+        assert!(poll(wait.as_mut()).is_pending());
+        assert_eq!(semaphore.status().waiting_processes, 1);
+        assert_eq!(low.curr_priority(), HIGH);
+        // The medium-priority process can no longer preempt the holder.
+        assert!(Arc::ptr_eq(dispatch(&[&medium, &low]), &low));
+
+        assert!(semaphore.signal(&low));
+        assert_eq!(low.curr_priority(), LOW);
+        assert!(matches!(poll(wait.as_mut()), Poll::Ready(Ok(true))));
+    }
+    assert!(semaphore.signal(&high));
+    assert!(!semaphore.signal(&high));
+
+    // A timed-out waiter withdraws its inherited priority.
+    assert!(matches!(
+        poll(semaphore.wait(&low, Duration::MAX)),
+        Poll::Ready(Ok(true))
+    ));
+    assert!(matches!(
+        poll(semaphore.wait(&medium, Duration::ZERO)),
+        Poll::Ready(Ok(false))
+    ));
+    {
+        let mut wait = pin!(semaphore.wait(&high, Duration::from_millis(1)));
+        assert!(poll(wait.as_mut()).is_pending());
+        assert_eq!(low.curr_priority(), HIGH);
+        let timed_out = loop {
+            if let Poll::Ready(ret) = poll(wait.as_mut()) {
+                break ret;
+            }
+        };
+        assert!(matches!(timed_out, Ok(false)));
+    }
+    assert_eq!(semaphore.status().waiting_processes, 0);
+    assert_eq!(low.curr_priority(), LOW);
+    assert!(semaphore.signal(&low));
+
+    // Immediate priority ceiling: the holder runs at the ceiling as soon as it acquires.
+    let semaphore = new_semaphore("ceiling");
+    assert_eq!(semaphore.ceiling(), Some(HIGH));
+    assert!(matches!(
+        poll(semaphore.wait(&low, Duration::MAX)),
+        Poll::Ready(Ok(true))
+    ));
+    assert_eq!(low.curr_priority(), HIGH);
+    assert!(Arc::ptr_eq(dispatch(&[&medium, &low]), &low));
+    {
+        let mut wait = pin!(semaphore.wait(&high, Duration::MAX));
+        assert!(poll(wait.as_mut()).is_pending());
+        assert!(semaphore.signal(&low));
+        assert_eq!(low.curr_priority(), LOW);
+        assert!(matches!(poll(wait.as_mut()), Poll::Ready(Ok(true))));
+        assert_eq!(high.curr_priority(), HIGH);
+    }
+    assert!(semaphore.signal(&high));
+
+    // Ceiling violation: a process above the ceiling is refused, leaving the semaphore alone.
+    let semaphore = new_semaphore("low");
+    assert!(matches!(
+        poll(semaphore.wait(&high, Duration::MAX)),
+        Poll::Ready(Err(InternalError::InvalidApexPriority))
+    ));
+    assert_eq!(semaphore.status().current_value, 1);
+    assert!(matches!(
+        poll(semaphore.wait(&medium, Duration::MAX)),
+        Poll::Ready(Ok(true))
+    ));
+    assert!(semaphore.signal(&medium));
+
+    // Semaphores left out of the configuration have no ceiling.
+    let semaphore = new_semaphore("free");
+    assert_eq!(semaphore.ceiling(), None);
+    assert!(matches!(
+        poll(semaphore.wait(&high, Duration::MAX)),
+        Poll::Ready(Ok(true))
+    ));
+    assert_eq!(high.curr_priority(), HIGH);
+}
+
+fn config(name: &str, ceiling: ApexPriority, protocol: SemaphoreProtocol) -> SemaphoreConfig {
+    SemaphoreConfig {
+        name: name.try_into().unwrap(),
+        ceiling,
+        protocol,
+    }
+}
+
+fn new_process(partition: &Partition, name: &str, priority: ApexPriority) -> Arc<Process> {
+    Process::new(
+        partition.identifier(),
+        &ProcessConfig {
+            name: name.try_into().unwrap(),
+            priority,
+            deadline: ApexDeadline::Soft,
+            entry: A653Entry::User(0),
+            period: APEX_TIME_INFINITY,
+            stack_size: 0x1000,
+            time_capacity: APEX_TIME_INFINITY,
+            capabilities: Capabilities::all(),
+            strace: false,
+        },
+    )
+    .unwrap()
+}
+
+/// Picks the ready process a priority-driven dispatcher would run.
+fn dispatch<'a>(ready: &[&'a Arc<Process>]) -> &'a Arc<Process> {
+    ready
+        .iter()
+        .max_by_key(|process| process.curr_priority())
+        .unwrap()
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+fn poll<F: Future<Output = Result<bool>>>(future: F) -> Poll<Result<bool>> {
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    pin!(future).poll(&mut cx)
+}
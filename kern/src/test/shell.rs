@@ -0,0 +1,61 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{format, string::String, vec::Vec};
+use jrinx_multitask::{
+    executor::{Executor, ExecutorPriority},
+    inspector::Inspector,
+    runtime::Runtime,
+    Task, TaskPriority,
+};
+use jrinx_testdef::testdef;
+use log::LevelFilter;
+
+use crate::shell::{self, LineEditor};
+
+const MODULE: &str = "jrinx::test::shell";
+
+#[testdef(serial)]
+fn test() {
+    static DONE: AtomicBool = AtomicBool::new(false);
+
+    let mut editor = LineEditor::default();
+    let mut echo = String::new();
+    let typed = b"tesx\x7ft  \x1bdmesg\x08\x08\x08\x08\x08help\r\n";
+    let lines = typed
+        .iter()
+        .filter_map(|&byte| editor.feed(byte, &mut echo))
+        .collect::<Vec<_>>();
+    assert_eq!(lines, ["test  help"]);
+    assert!(echo.starts_with("tesx\x08 \x08t  dmesg"));
+    assert!(echo.ends_with("help\n"));
+
+    for name in ["help", "test", "log", "ps", "dmesg", "mem", "halt"] {
+        assert!(jrinx_shelldef::find(name).is_some(), "no command {}", name);
+    }
+
+    let executor = Executor::new(
+        ExecutorPriority::default(),
+        Task::new(
+            async {
+                for line in ["", "help", "mem", "ps", "dmesg 4", "no-such-command"] {
+                    shell::execute(line).await;
+                }
+                shell::execute(&format!("log {}=warn", MODULE)).await;
+                DONE.store(true, Ordering::SeqCst);
+            },
+            TaskPriority::default(),
+        ),
+    );
+    Inspector::with_current(|is| is.register(executor).unwrap()).unwrap();
+    while !DONE.load(Ordering::SeqCst) {
+        Runtime::switch_yield();
+    }
+
+    assert_eq!(
+        jrinx_logging::target_filter(MODULE),
+        Some(LevelFilter::Warn)
+    );
+    info!("shell: record of a module set to warn");
+    jrinx_logging::clear_target_filter(MODULE);
+    info!("shell: commands run");
+}
@@ -39,6 +39,15 @@ fn test() {
     let page_3 = stack_allocator.allocate(PAGE_SIZE);
     assert!(page_3.is_ok());
 
+    assert_eq!(
+        stack_allocator.guarded_by(VirtAddr::new(0x1000 + PAGE_SIZE + 8)),
+        Some(VirtAddr::new(0x1000 + 2 * PAGE_SIZE)..page_3.unwrap())
+    );
+    assert_eq!(
+        stack_allocator.guarded_by(VirtAddr::new(0x1000 + 2 * PAGE_SIZE)),
+        None
+    );
+
     drop(stack_allocator);
 
     assert_eq!(
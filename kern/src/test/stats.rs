@@ -0,0 +1,52 @@
+use core::time::Duration;
+
+use alloc::vec::Vec;
+use jrinx_hal::{Cpu, Hal};
+use jrinx_stats::StatKind;
+use jrinx_testdef::testdef;
+use jrinx_trap::smp;
+
+#[testdef(serial)]
+fn test() {
+    const BREAKPOINTS: u64 = 16;
+
+    let local_id = hal!().cpu().id();
+    let before = jrinx_stats::snapshot();
+    assert!(!before.torn());
+
+    for _ in 0..BREAKPOINTS {
+        hal!().breakpoint();
+    }
+
+    let remote_ids = (0..hal!().cpu().nproc_valid())
+        .filter(|&cpu_id| cpu_id != local_id)
+        .collect::<Vec<_>>();
+    let results = smp::call(&remote_ids, Duration::from_secs(1), || 0).unwrap();
+    for cpu_id in results.unresponsive() {
+        warn!("cpu#{} did not respond to smp call", cpu_id);
+    }
+    let responded = results.iter().filter(|(_, r)| r.is_some()).count() as u64;
+
+    let after = jrinx_stats::snapshot();
+    assert!(!after.torn());
+    assert!(after.time() >= before.time());
+    assert_eq!(after.cpus().len(), before.cpus().len());
+
+    for (before, after) in before.cpus().iter().zip(after.cpus()) {
+        assert_eq!(before.cpu_id(), after.cpu_id());
+        for kind in StatKind::ALL {
+            assert!(after.get(kind) >= before.get(kind));
+        }
+    }
+    assert_eq!(
+        after.cpus()[local_id].get(StatKind::Breakpoint)
+            - before.cpus()[local_id].get(StatKind::Breakpoint),
+        BREAKPOINTS
+    );
+    assert!(
+        after.total(StatKind::SoftwareInterrupt)
+            >= before.total(StatKind::SoftwareInterrupt) + responded
+    );
+
+    info!("{}", after);
+}
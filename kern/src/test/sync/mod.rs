@@ -0,0 +1,45 @@
+pub(super) mod lockdep {
+    use alloc::vec::Vec;
+    use jrinx_sync::{lockdep::MAX_LOCK_CLASSES, IrqSafeMutex};
+    use jrinx_testdef::testdef;
+
+    #[testdef(should_panic)]
+    fn test() {
+        static LOCK_A: IrqSafeMutex<()> = IrqSafeMutex::new("test-lockdep-a", ());
+        static LOCK_B: IrqSafeMutex<()> = IrqSafeMutex::new("test-lockdep-b", ());
+
+        let instances = (0..2 * MAX_LOCK_CLASSES)
+            .map(|_| IrqSafeMutex::new("test-lockdep-instance", ()))
+            .collect::<Vec<_>>();
+        for instance in &instances {
+            drop(instance.lock());
+        }
+
+        {
+            let _a = LOCK_A.lock();
+            let _b = LOCK_B.lock();
+        }
+
+        let _b = LOCK_B.lock();
+        let _a = LOCK_A.lock();
+    }
+}
+
+pub(super) mod lockdep_try {
+    use jrinx_sync::IrqSafeMutex;
+    use jrinx_testdef::testdef;
+
+    #[testdef(should_panic)]
+    fn test() {
+        static LOCK_A: IrqSafeMutex<()> = IrqSafeMutex::new("test-lockdep-try-a", ());
+        static LOCK_B: IrqSafeMutex<()> = IrqSafeMutex::new("test-lockdep-try-b", ());
+
+        {
+            let _a = LOCK_A.lock();
+            let _b = LOCK_B.try_lock().unwrap();
+        }
+
+        let _b = LOCK_B.lock();
+        let _a = LOCK_A.lock();
+    }
+}
@@ -0,0 +1,88 @@
+use alloc::{sync::Arc, task::Wake};
+use core::{
+    future::{poll_fn, Future},
+    pin::pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use jrinx_abi::sysno::*;
+use jrinx_apex::ApexReturnCode;
+use jrinx_error::Result;
+use jrinx_hal::{Cpu, Hal};
+use jrinx_syscall::budget;
+use jrinx_testdef::testdef;
+
+const SYSNO: usize = SYS_DEBUG_LOG_RING_DOORBELL;
+const BUDGET: Duration = Duration::from_millis(1);
+
+#[testdef]
+fn test() {
+    assert_eq!(
+        jrinx_abi::sysno::name(SYSNO),
+        Some("SYS_DEBUG_LOG_RING_DOORBELL")
+    );
+    assert_eq!(jrinx_abi::sysno::name(0), None);
+    assert_eq!(budget::budget(SYSNO), None);
+    assert_eq!(budget::budget(SYS_GET_PROCESS_STATUS), Some(BUDGET));
+
+    budget::set_budget(SYSNO, Some(BUDGET));
+    assert_eq!(budget::budget(SYSNO), Some(BUDGET));
+
+    // Time spent parked does not count against the budget.
+    assert!(matches!(run(Duration::ZERO, BUDGET * 5), Ok(0)));
+    assert_eq!(budget::overruns(SYSNO), 0);
+
+    assert!(matches!(run(BUDGET * 2, Duration::ZERO), Ok(0)));
+    assert_eq!(budget::overruns(SYSNO), 1);
+
+    jrinx_tunable::set_value("syscall.strict_budgets", true).unwrap();
+    assert!(matches!(
+        run(BUDGET * 2, Duration::ZERO),
+        Ok(ret) if ret == ApexReturnCode::TimedOut as usize
+    ));
+    assert!(matches!(run(Duration::ZERO, Duration::ZERO), Ok(0)));
+    jrinx_tunable::set_value("syscall.strict_budgets", false).unwrap();
+    assert_eq!(budget::overruns(SYSNO), 2);
+
+    budget::set_budget(SYSNO, None);
+    assert!(matches!(run(BUDGET * 2, Duration::ZERO), Ok(0)));
+    assert_eq!(budget::overruns(SYSNO), 2);
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// Meters a handler running for `running` in its first poll, which stays parked for `parked`
+/// until its second poll.
+fn run(running: Duration, parked: Duration) -> Result<usize> {
+    let mut polled = false;
+    let handler = poll_fn(move |_| {
+        if polled {
+            return Poll::Ready(Ok(0));
+        }
+        spin(running);
+        polled = true;
+        Poll::Pending
+    });
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    let mut metered = pin!(budget::metered(SYSNO, [0xa5; 7], handler));
+    loop {
+        if let Poll::Ready(ret) = metered.as_mut().poll(&mut cx) {
+            return ret;
+        }
+        spin(parked);
+    }
+}
+
+fn spin(duration: Duration) {
+    let begin = hal!().cpu().get_time();
+    while hal!().cpu().get_time() - begin < duration {
+        core::hint::spin_loop();
+    }
+}
@@ -40,6 +40,97 @@ pub(super) mod executor {
     }
 }
 
+pub(super) mod group {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use jrinx_multitask::{
+        executor::{self, Executor},
+        group::TaskGroup,
+        spawn, yield_now, TaskId,
+    };
+    use jrinx_testdef::testdef;
+    use spin::Mutex;
+
+    #[testdef]
+    fn test() {
+        static CHILDREN: AtomicUsize = AtomicUsize::new(0);
+        static NESTED: AtomicUsize = AtomicUsize::new(0);
+        static CANCELLED: AtomicUsize = AtomicUsize::new(0);
+        static CHILD: Mutex<Option<TaskId>> = Mutex::new(None);
+
+        spawn!(async {
+            const CHILD_MAX: usize = 4;
+
+            let group = TaskGroup::new();
+            for _ in 0..CHILD_MAX {
+                let spawner = group.spawner();
+                group.spawn(async move {
+                    spawner.spawn(async {
+                        CHILDREN.fetch_add(1, Ordering::SeqCst);
+                    });
+                    yield_now!();
+                    CHILDREN.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            group.join_all().await;
+            assert!(group.is_empty());
+            assert_eq!(CHILDREN.load(Ordering::SeqCst), CHILD_MAX * 2);
+
+            let outer = TaskGroup::new();
+            for _ in 0..CHILD_MAX {
+                outer.spawn(async {
+                    let inner = TaskGroup::new();
+                    inner.spawn(async {
+                        NESTED.fetch_add(1, Ordering::SeqCst);
+                    });
+                    inner.join_all().await;
+                    NESTED.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            outer.join_all().await;
+            assert_eq!(NESTED.load(Ordering::SeqCst), CHILD_MAX * 2);
+
+            let group = TaskGroup::new();
+            group.spawn(async {
+                loop {
+                    yield_now!();
+                }
+            });
+            group.spawn(async {
+                yield_now!();
+                CANCELLED.fetch_add(1, Ordering::SeqCst);
+            });
+            yield_now!();
+            group.cancel_all();
+            group.join_all().await;
+            assert!(group.is_empty());
+            assert!(CANCELLED.load(Ordering::SeqCst) <= 1);
+
+            // A child dropped by its executor rather than by the group still leaves it.
+            let group = TaskGroup::new();
+            group.spawn(async {
+                *CHILD.lock() = executor::polling_task().map(|task| task.task);
+                loop {
+                    yield_now!();
+                }
+            });
+            yield_now!();
+            let child = CHILD.lock().take().unwrap();
+            assert!(Executor::with_current(|ex| ex.cancel_task(child)).unwrap());
+            group.join_all().await;
+            assert!(group.is_empty());
+
+            let group = TaskGroup::new();
+            group.spawn(async {
+                loop {
+                    yield_now!();
+                }
+            });
+            drop(group);
+        });
+    }
+}
+
 pub(super) mod inspector {
     use alloc::vec::Vec;
     use jrinx_multitask::{
@@ -110,3 +201,1448 @@ pub(super) mod inspector {
 }
 
 pub(super) mod runtime;
+
+pub(super) mod limits {
+    use core::time::Duration;
+
+    use jrinx_error::{InternalError, ResourceKind};
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::{Inspector, ResourceLimits},
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        let inspector = Inspector::new().with_limits(ResourceLimits {
+            max_executors: Some(1),
+            max_tasks: Some(2),
+            max_timed_events: Some(1),
+            max_descriptors: Some(1),
+            max_shared_bytes: Some(0x1000),
+        });
+
+        let executor = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(async {}, TaskPriority::default()),
+        );
+        let executor_id = executor.id();
+        inspector.register(executor).unwrap();
+        assert!(matches!(
+            inspector.register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(async {}, TaskPriority::default()),
+            )),
+            Err(InternalError::ResourceLimitExceeded(
+                ResourceKind::Executors
+            ))
+        ));
+        assert_eq!(inspector.resource_usage().executors, 1);
+        assert_eq!(inspector.resource_usage().tasks, 1);
+        assert_eq!(inspector.resource_usage().violations, 1);
+
+        inspector.unregister(executor_id).unwrap();
+        assert_eq!(inspector.resource_usage().executors, 0);
+        assert_eq!(inspector.resource_usage().tasks, 0);
+
+        let mut executor = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(async {}, TaskPriority::default()),
+        );
+        for _ in 0..2 {
+            executor
+                .spawn(Task::new(async {}, TaskPriority::default()))
+                .unwrap();
+        }
+        assert!(matches!(
+            inspector.register(executor),
+            Err(InternalError::ResourceLimitExceeded(ResourceKind::Tasks))
+        ));
+        assert_eq!(inspector.resource_usage().executors, 0);
+        assert_eq!(inspector.resource_usage().tasks, 0);
+
+        let descriptor = inspector.charge(ResourceKind::Descriptors, 1).unwrap();
+        assert!(matches!(
+            inspector.charge(ResourceKind::Descriptors, 1),
+            Err(InternalError::ResourceLimitExceeded(
+                ResourceKind::Descriptors
+            ))
+        ));
+        drop(descriptor);
+        assert_eq!(inspector.resource_usage().descriptors, 0);
+
+        let shared = inspector.charge(ResourceKind::SharedBytes, 0x1000).unwrap();
+        assert_eq!(inspector.resource_usage().shared_bytes, 0x1000);
+        assert!(matches!(
+            inspector.charge(ResourceKind::SharedBytes, 1),
+            Err(InternalError::ResourceLimitExceeded(
+                ResourceKind::SharedBytes
+            ))
+        ));
+        drop(shared);
+        assert_eq!(inspector.resource_usage().shared_bytes, 0);
+
+        let time = hal!().cpu().get_time() + Duration::from_secs(60);
+        let tracker = inspector.create_timed_event(time, || {}, || {}).unwrap();
+        assert!(matches!(
+            inspector.create_timed_event(time, || {}, || {}),
+            Err(InternalError::ResourceLimitExceeded(
+                ResourceKind::TimedEvents
+            ))
+        ));
+        tracker.cancel().unwrap();
+        assert_eq!(inspector.resource_usage().timed_events, 0);
+        inspector
+            .create_timed_event(time, || {}, || {})
+            .unwrap()
+            .cancel()
+            .unwrap();
+
+        info!("inspector resource usage: {}", inspector.resource_usage());
+    }
+}
+
+pub(super) mod workqueue {
+    use core::{
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{inspector::Inspector, runtime::Runtime, workqueue::DelayedWork};
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+
+    #[testdef]
+    fn test() {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        static REARMS: AtomicUsize = AtomicUsize::new(0);
+        static STARTED: AtomicBool = AtomicBool::new(false);
+        static FINISHED: AtomicBool = AtomicBool::new(false);
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+        let work = DelayedWork::new(|_| {
+            RUNS.fetch_add(1, Ordering::SeqCst);
+        });
+        assert!(work.schedule(Duration::from_millis(10)));
+        assert!(!work.schedule(Duration::from_millis(10)));
+        wait_until(|| RUNS.load(Ordering::SeqCst) == 1);
+        assert!(!work.pending());
+
+        assert!(work.schedule(Duration::from_secs(60)));
+        assert!(work.reschedule(Duration::from_secs(120)));
+        assert!(work.cancel());
+        assert!(!work.cancel());
+        assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+
+        let rearm = DelayedWork::new(|work| {
+            if REARMS.fetch_add(1, Ordering::SeqCst) + 1 < 3 {
+                assert!(work.schedule(Duration::from_millis(5)));
+            }
+        });
+        assert!(!rearm.reschedule(Duration::ZERO));
+        wait_until(|| REARMS.load(Ordering::SeqCst) == 3 && !rearm.running());
+        assert!(!rearm.pending());
+
+        let slow = DelayedWork::new(|work| {
+            STARTED.store(true, Ordering::SeqCst);
+            assert!(!work.cancel());
+            for _ in 0..8 {
+                yield_cpu();
+            }
+            FINISHED.store(true, Ordering::SeqCst);
+        });
+        assert!(slow.schedule(Duration::ZERO));
+        wait_until(|| STARTED.load(Ordering::SeqCst));
+        assert!(slow.running());
+        assert!(!FINISHED.load(Ordering::SeqCst));
+        assert!(!slow.cancel());
+        assert!(FINISHED.load(Ordering::SeqCst));
+        assert!(!slow.running());
+
+        assert!(work.schedule(Duration::from_secs(60)));
+        let handler_work = work.clone();
+        TimedEvent::create(
+            hal!().cpu().get_time() + Duration::from_millis(5),
+            TimedEventHandler::new(
+                move || {
+                    CANCELLED.store(handler_work.cancel(), Ordering::SeqCst);
+                    FIRED.store(true, Ordering::SeqCst);
+                },
+                || {},
+            ),
+        );
+        wait_until(|| FIRED.load(Ordering::SeqCst));
+        assert!(CANCELLED.load(Ordering::SeqCst));
+        assert!(!work.pending());
+        assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+    }
+
+    fn wait_until(cond: impl Fn() -> bool) {
+        while !cond() {
+            yield_cpu();
+        }
+    }
+
+    fn yield_cpu() {
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+    }
+}
+
+pub(super) mod retire {
+    use core::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Wake, Waker},
+        time::Duration,
+    };
+
+    use alloc::sync::Arc;
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        inspector::{Inspector, InspectorId},
+        runtime::Runtime,
+    };
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+    use jrinx_trap::smp;
+    use spin::Mutex;
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[testdef]
+    fn test() {
+        static TIMER: Mutex<Option<InspectorId>> = Mutex::new(None);
+        static WAKE: Mutex<Option<InspectorId>> = Mutex::new(None);
+
+        let target = Inspector::new();
+        let bystander = Inspector::new();
+        let target_id = target.id();
+        let bystander_id = bystander.id();
+
+        let timer_ref = target.acquire_ref().unwrap();
+        target
+            .create_timed_event(
+                hal!().cpu().get_time() + Duration::from_millis(10),
+                move || *TIMER.lock() = timer_ref.with(|is| is.id()).ok(),
+                || {},
+            )
+            .unwrap();
+        let wake_ref = Arc::new(Mutex::new(Some(target.acquire_ref().unwrap())));
+        let bystander_ref = bystander.acquire_ref().unwrap();
+
+        Runtime::with_current(|rt| {
+            rt.register(target).unwrap();
+            rt.register(bystander).unwrap();
+        });
+
+        let mut retirement = Runtime::with_current(|rt| rt.retire_inspector(target_id)).unwrap();
+        assert!(matches!(
+            Runtime::with_current(|rt| rt.inspector_ref(target_id)),
+            Err(InternalError::InvalidInspectorStatus)
+        ));
+        assert_eq!(
+            wake_ref
+                .lock()
+                .as_ref()
+                .unwrap()
+                .with(|is| is.id())
+                .unwrap(),
+            target_id
+        );
+
+        let remote_id = (hal!().cpu().id() + 1) % hal!().cpu().nproc_valid();
+        smp::call(&[remote_id], Duration::from_secs(1), move || {
+            if let Some(wake_ref) = wake_ref.lock().take() {
+                TimedEvent::create(
+                    hal!().cpu().get_time() + Duration::from_millis(20),
+                    TimedEventHandler::new(
+                        move || *WAKE.lock() = wake_ref.with(|is| is.id()).ok(),
+                        || {},
+                    ),
+                );
+            }
+            0
+        })
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        while Pin::new(&mut retirement).poll(&mut cx).is_pending() {
+            Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+            Runtime::switch_yield();
+        }
+        assert!(retirement.retired());
+
+        assert_eq!(*TIMER.lock(), Some(target_id));
+        assert_eq!(*WAKE.lock(), Some(target_id));
+        assert!(!Runtime::with_current(
+            |rt| rt.with_registry(|registry| registry.contains_key(&target_id))
+        ));
+        assert_eq!(bystander_ref.with(|is| is.id()).unwrap(), bystander_id);
+    }
+}
+
+pub(super) mod top {
+    use core::time::Duration;
+
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{executor::Executor, spawn, yield_now};
+    use jrinx_testdef::testdef;
+
+    const BUSY: Duration = Duration::from_millis(5);
+    const ROUNDS: usize = 4;
+
+    async fn busy() {
+        for _ in 0..ROUNDS {
+            let until = hal!().cpu().get_time() + BUSY;
+            while hal!().cpu().get_time() < until {
+                core::hint::spin_loop();
+            }
+            yield_now!();
+        }
+    }
+
+    async fn idle() {
+        for _ in 0..ROUNDS {
+            yield_now!();
+        }
+    }
+
+    async fn finished() {}
+
+    #[testdef]
+    fn test() {
+        spawn!(async {
+            spawn!(busy());
+            spawn!(idle());
+            spawn!(finished());
+            for _ in 0..ROUNDS / 2 {
+                yield_now!();
+            }
+
+            let top = Executor::with_current(|ex| ex.top(usize::MAX)).unwrap();
+            for usage in &top {
+                info!("{}", usage);
+            }
+
+            let heaviest = &top[0];
+            assert!(heaviest.name.contains("busy"));
+            assert!(heaviest.polls >= 1);
+            assert!(heaviest.run_time >= BUSY);
+            assert!(heaviest.spawn_site.file().ends_with("mod.rs"));
+
+            let idle = top
+                .iter()
+                .find(|usage| usage.name.contains("idle"))
+                .unwrap();
+            assert!(idle.polls >= 1);
+            assert!(idle.run_time < heaviest.run_time);
+            assert!(idle.recent <= idle.run_time);
+
+            assert!(!top.iter().any(|usage| usage.name.contains("finished")));
+            assert_eq!(Executor::with_current(|ex| ex.top(1).len()).unwrap(), 1);
+        });
+    }
+}
+
+pub(super) mod join {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use alloc::vec::Vec;
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        spawn, yield_now, Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const TASK_MAX: usize = 10;
+
+    #[testdef]
+    fn test() {
+        static DETACHED: AtomicBool = AtomicBool::new(false);
+
+        spawn!(async {
+            let handles: Vec<_> = (0..TASK_MAX)
+                .map(|i| {
+                    spawn!(async move {
+                        for _ in 0..i {
+                            yield_now!();
+                        }
+                        i * i
+                    })
+                })
+                .collect();
+            for (i, handle) in handles.into_iter().enumerate().rev() {
+                assert_eq!(handle.await.unwrap(), i * i);
+            }
+
+            let handle = spawn!(async { "finished" });
+            assert!(!handle.is_finished());
+            yield_now!();
+            assert!(handle.is_finished());
+            assert_eq!(handle.await.unwrap(), "finished");
+
+            drop(spawn!(async {
+                yield_now!();
+                DETACHED.store(true, Ordering::SeqCst);
+            }));
+            while !DETACHED.load(Ordering::SeqCst) {
+                yield_now!();
+            }
+
+            let (task, handle) = Task::new_joinable(
+                async {
+                    yield_now!();
+                    Executor::with_current(|ex| ex.id()).unwrap()
+                },
+                TaskPriority::default(),
+            );
+            let executor = Executor::new(ExecutorPriority::default(), task);
+            let executor_id = executor.id();
+            Inspector::with_current(|is| is.register(executor))
+                .unwrap()
+                .unwrap();
+            assert_eq!(handle.await.unwrap(), executor_id);
+            assert_ne!(executor_id, Executor::with_current(|ex| ex.id()).unwrap());
+        });
+    }
+}
+
+pub(super) mod steal {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        spawn, Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const TASK_MAX: usize = 100;
+
+    #[testdef]
+    fn test() {
+        static RETIRED: AtomicUsize = AtomicUsize::new(0);
+        static STOLEN: AtomicUsize = AtomicUsize::new(0);
+
+        let home = Executor::with_current(|ex| ex.id()).unwrap();
+        let before = Executor::with_current(|ex| ex.steal_stats()).unwrap();
+
+        for _ in 0..TASK_MAX {
+            drop(spawn!(async move {
+                if Executor::with_current(|ex| ex.id()).unwrap() != home {
+                    STOLEN.fetch_add(1, Ordering::SeqCst);
+                }
+                RETIRED.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+
+        // The pinned task stays here, while the others are stolen half by half.
+        Executor::with_current(|ex| {
+            ex.spawn(
+                Task::new(
+                    async move {
+                        Executor::with_current(|ex| assert_eq!(ex.id(), home)).unwrap();
+                        RETIRED.fetch_add(1, Ordering::SeqCst);
+                    },
+                    TaskPriority::default(),
+                )
+                .pinned(),
+            )
+            .unwrap();
+        })
+        .unwrap();
+
+        let thief = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(async {}, TaskPriority::default()),
+        );
+        Inspector::with_current(|is| is.register(thief).unwrap()).unwrap();
+
+        // The thief runs first, stealing from this executor once its root task is done.
+        Runtime::switch_yield();
+
+        let stats = Executor::with_current(|ex| ex.steal_stats()).unwrap();
+        let stolen = STOLEN.load(Ordering::SeqCst);
+        info!("{} of {} tasks stolen: {}", stolen, TASK_MAX, stats);
+        assert!(stolen > 0);
+        assert!(RETIRED.load(Ordering::SeqCst) >= stolen);
+        assert_eq!((stats.given - before.given) as usize, stolen);
+    }
+}
+
+pub(super) mod priority {
+    use core::{iter, pin::Pin};
+
+    use alloc::{boxed::Box, vec::Vec};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+    use spin::Mutex;
+
+    const ROUNDS: usize = 10;
+
+    static ORDER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+    /// Creates an executor staying runnable for [`ROUNDS`] switches, recording `name` on each.
+    fn busy(name: u8, priority: u8) -> Pin<Box<Executor>> {
+        Executor::new(
+            ExecutorPriority::new(priority),
+            Task::new(
+                async move {
+                    for _ in 0..ROUNDS {
+                        ORDER.lock().push(name);
+                        Runtime::switch_yield();
+                    }
+                },
+                TaskPriority::default(),
+            ),
+        )
+    }
+
+    fn take_order() -> Vec<u8> {
+        core::mem::take(&mut *ORDER.lock())
+    }
+
+    #[testdef]
+    fn test() {
+        // This executor has the lowest priority, so it only resumes once the others finish, each
+        // of them starving those below it until then.
+        Inspector::with_current(|is| {
+            is.register(busy(b'l', 1)).unwrap();
+            is.register(busy(b'm', 2)).unwrap();
+            is.register(busy(b'h', 3)).unwrap();
+        })
+        .unwrap();
+        Runtime::switch_yield();
+
+        let expected: Vec<u8> = [b'h', b'm', b'l']
+            .into_iter()
+            .flat_map(|name| iter::repeat(name).take(ROUNDS))
+            .collect();
+        assert_eq!(take_order(), expected);
+
+        // A queued executor raised above the others runs first, and equal priorities take turns.
+        let raised = busy(b'r', 1);
+        let raised_id = raised.id();
+        Inspector::with_current(|is| {
+            is.register(raised).unwrap();
+            is.register(busy(b'a', 2)).unwrap();
+            is.register(busy(b'b', 2)).unwrap();
+            is.set_priority(raised_id, ExecutorPriority::new(3))
+                .unwrap();
+        })
+        .unwrap();
+        Runtime::switch_yield();
+
+        let expected: Vec<u8> = iter::repeat(b'r')
+            .take(ROUNDS)
+            .chain([b'a', b'b'].into_iter().cycle().take(2 * ROUNDS))
+            .collect();
+        assert_eq!(take_order(), expected);
+    }
+}
+
+pub(super) mod preempt {
+    use core::{
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        preempt,
+        runtime::Runtime,
+        time::sleep,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const TICKS: usize = 5;
+    const PERIOD: Duration = Duration::from_millis(5);
+
+    #[testdef]
+    fn test() {
+        static DONE: AtomicBool = AtomicBool::new(false);
+        static TICKED: AtomicUsize = AtomicUsize::new(0);
+
+        let before = preempt::count();
+
+        // The busy task never awaits, so only preemption lets the periodic one run.
+        let busy = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(
+                async {
+                    while !DONE.load(Ordering::SeqCst) {
+                        core::hint::spin_loop();
+                    }
+                },
+                TaskPriority::new(0),
+            ),
+        );
+        let periodic = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(
+                async {
+                    for _ in 0..TICKS {
+                        sleep(PERIOD).await;
+                        TICKED.fetch_add(1, Ordering::SeqCst);
+                    }
+                    DONE.store(true, Ordering::SeqCst);
+                },
+                TaskPriority::new(TaskPriority::MAX),
+            ),
+        );
+        Inspector::with_current(|is| {
+            is.register(busy).unwrap();
+            is.register(periodic).unwrap();
+        })
+        .unwrap();
+
+        while !DONE.load(Ordering::SeqCst) {
+            Runtime::switch_yield();
+        }
+
+        assert_eq!(TICKED.load(Ordering::SeqCst), TICKS);
+        assert!(preempt::count() > before);
+        info!(
+            "{} preemptions, time slice {:?}",
+            preempt::count() - before,
+            preempt::time_slice(TaskPriority::new(0))
+        );
+    }
+}
+
+pub(super) mod cancel {
+    use core::{
+        future::Future,
+        pin::pin,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{Context, Poll, Wake, Waker},
+        time::Duration,
+    };
+
+    use alloc::sync::Arc;
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        time::sleep,
+        yield_now, Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const NAP: Duration = Duration::from_secs(60 * 60);
+    const PROMPTLY: Duration = Duration::from_millis(100);
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[testdef]
+    fn test() {
+        static SPINS: AtomicUsize = AtomicUsize::new(0);
+
+        let before = Inspector::with_current(|is| is.resource_usage()).unwrap();
+
+        let (task, handle) = Task::new_joinable(sleep(NAP), TaskPriority::default());
+        let sleeper = Executor::new(ExecutorPriority::default(), task);
+        Inspector::with_current(|is| is.register(sleeper).unwrap()).unwrap();
+
+        // The sleeper arms its timed event, parking its executor.
+        Runtime::switch_yield();
+        let usage = Inspector::with_current(|is| is.resource_usage()).unwrap();
+        assert_eq!(usage.executors, before.executors + 1);
+        assert_eq!(usage.timed_events, before.timed_events + 1);
+        assert!(!handle.is_finished());
+
+        let begin = hal!().cpu().get_time();
+        handle.abort();
+        while Inspector::with_current(|is| is.resource_usage().executors).unwrap()
+            > before.executors
+        {
+            assert!(hal!().cpu().get_time() - begin < PROMPTLY);
+            Runtime::switch_yield();
+        }
+        info!(
+            "aborted sleeper finished after {:?}",
+            hal!().cpu().get_time() - begin
+        );
+
+        let usage = Inspector::with_current(|is| is.resource_usage()).unwrap();
+        assert_eq!(usage.timed_events, before.timed_events);
+        assert!(handle.is_finished());
+        assert!(handle.cancel_token().is_cancelled());
+        handle.abort();
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        assert!(matches!(
+            pin!(handle).poll(&mut cx),
+            Poll::Ready(Err(InternalError::TaskCancelled))
+        ));
+
+        // A task cancelled by a sibling is dropped the next time it is dequeued, waking those
+        // awaiting its token, and is gone for later cancellations.
+        let canceller = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(
+                async {
+                    let spinner = Task::new(
+                        async {
+                            loop {
+                                SPINS.fetch_add(1, Ordering::SeqCst);
+                                yield_now!();
+                            }
+                        },
+                        TaskPriority::default(),
+                    );
+                    let (id, token) = (spinner.id(), spinner.cancel_token());
+                    Executor::with_current(|ex| ex.spawn(spinner).map(|_| ()))
+                        .unwrap()
+                        .unwrap();
+                    yield_now!();
+
+                    assert!(Executor::with_current(|ex| ex.cancel_task(id)).unwrap());
+                    token.cancelled().await;
+                    let spins = SPINS.load(Ordering::SeqCst);
+                    assert!(spins > 0);
+                    yield_now!();
+                    yield_now!();
+                    assert_eq!(SPINS.load(Ordering::SeqCst), spins);
+                    assert!(!Executor::with_current(|ex| ex.cancel_task(id)).unwrap());
+                },
+                TaskPriority::default(),
+            ),
+        );
+        Inspector::with_current(|is| is.register(canceller).unwrap()).unwrap();
+        while Inspector::with_current(|is| is.resource_usage().executors).unwrap()
+            > before.executors
+        {
+            Runtime::switch_yield();
+        }
+    }
+}
+
+pub(super) mod mutex {
+    use alloc::vec::Vec;
+    use jrinx_multitask::{spawn, sync::Mutex, yield_now};
+    use jrinx_testdef::testdef;
+
+    const TASK_MAX: usize = 2;
+    const ROUNDS: usize = 10_000;
+
+    #[testdef]
+    fn test() {
+        static COUNTER: Mutex<usize> = Mutex::new(0);
+
+        spawn!(async {
+            let handles: Vec<_> = (0..TASK_MAX)
+                .map(|_| {
+                    spawn!(async {
+                        for _ in 0..ROUNDS {
+                            let mut counter = COUNTER.lock().await;
+                            let value = *counter;
+                            // Holding the mutex across a yield lets the other task contend for it.
+                            yield_now!();
+                            *counter = value + 1;
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.await.unwrap();
+            }
+
+            assert_eq!(*COUNTER.lock().await, TASK_MAX * ROUNDS);
+            assert!(!COUNTER.is_locked());
+
+            // A waiter dropped before getting the mutex leaves the waiters, letting others in.
+            let guard = COUNTER.lock().await;
+            let waiter = spawn!(async {
+                *COUNTER.lock().await += 1;
+            });
+            yield_now!();
+            assert!(COUNTER.try_lock().is_none());
+            waiter.abort();
+            yield_now!();
+            assert!(waiter.await.is_err());
+            drop(guard);
+            assert!(COUNTER.try_lock().is_some());
+            assert_eq!(*COUNTER.lock().await, TASK_MAX * ROUNDS);
+        });
+    }
+}
+
+pub(super) mod channel {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use jrinx_error::InternalError;
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        sync::channel,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const CAPACITY: usize = 4;
+    const MESSAGES: usize = 1000;
+
+    #[testdef]
+    fn test() {
+        static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+        let (tx, mut rx) = channel(CAPACITY);
+        for i in 0..CAPACITY {
+            tx.try_send(i).unwrap();
+        }
+        assert!(matches!(
+            tx.try_send(CAPACITY),
+            Err(InternalError::ChannelFull)
+        ));
+        for i in 0..CAPACITY {
+            assert_eq!(rx.try_recv().unwrap(), i);
+        }
+        assert!(matches!(rx.try_recv(), Err(InternalError::ChannelEmpty)));
+        tx.try_send(0).unwrap();
+        drop(tx);
+        assert_eq!(rx.try_recv().unwrap(), 0);
+        assert!(matches!(rx.try_recv(), Err(InternalError::ChannelClosed)));
+
+        let (tx, rx) = channel(CAPACITY);
+        drop(rx);
+        assert!(tx.is_closed());
+        assert!(matches!(tx.try_send(0), Err(InternalError::ChannelClosed)));
+
+        // The producer fills the channel and waits, running in turn with the consumer.
+        let before = Inspector::with_current(|is| is.resource_usage()).unwrap();
+        let (tx, mut rx) = channel(CAPACITY);
+        let producer = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(
+                async move {
+                    for i in 0..MESSAGES {
+                        tx.send(i).await.unwrap();
+                    }
+                },
+                TaskPriority::default(),
+            ),
+        );
+        let consumer = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(
+                async move {
+                    let mut expected = 0;
+                    while let Some(i) = rx.recv().await {
+                        assert_eq!(i, expected);
+                        assert!(rx.len() <= CAPACITY);
+                        expected += 1;
+                    }
+                    RECEIVED.store(expected, Ordering::SeqCst);
+                },
+                TaskPriority::default(),
+            ),
+        );
+        Inspector::with_current(|is| {
+            is.register(producer).unwrap();
+            is.register(consumer).unwrap();
+        })
+        .unwrap();
+
+        while Inspector::with_current(|is| is.resource_usage().executors).unwrap()
+            > before.executors
+        {
+            Runtime::switch_yield();
+        }
+        assert_eq!(RECEIVED.load(Ordering::SeqCst), MESSAGES);
+    }
+}
+
+pub(super) mod wait_queue {
+    use core::{
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use alloc::vec::Vec;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{spawn, sync::WaitQueue, yield_now};
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+
+    const DELAY: Duration = Duration::from_millis(20);
+    const WAITERS: usize = 3;
+
+    #[testdef]
+    fn test() {
+        static QUEUE: WaitQueue = WaitQueue::new();
+        static FLAG: AtomicBool = AtomicBool::new(false);
+        static WOKEN: AtomicUsize = AtomicUsize::new(0);
+
+        spawn!(async {
+            // A condition which already holds completes without waiting.
+            assert_eq!(QUEUE.wait_until(|| Some(42)).await, 42);
+            assert!(QUEUE.is_empty());
+            assert!(!QUEUE.notify_one());
+
+            let begin = hal!().cpu().get_time();
+            let handles: Vec<_> = (0..WAITERS)
+                .map(|_| {
+                    spawn!(async move {
+                        QUEUE
+                            .wait_until(|| FLAG.load(Ordering::SeqCst).then_some(()))
+                            .await;
+                        assert!(hal!().cpu().get_time() - begin >= DELAY);
+                        WOKEN.fetch_add(1, Ordering::SeqCst);
+                    })
+                })
+                .collect();
+            yield_now!();
+            assert_eq!(QUEUE.len(), WAITERS);
+
+            // A notification without the condition holding is a spurious wakeup.
+            assert!(QUEUE.notify_one());
+            yield_now!();
+            assert_eq!(QUEUE.len(), WAITERS);
+            assert_eq!(WOKEN.load(Ordering::SeqCst), 0);
+
+            TimedEvent::create(
+                begin + DELAY,
+                TimedEventHandler::new(
+                    || {
+                        FLAG.store(true, Ordering::SeqCst);
+                        QUEUE.notify_all();
+                    },
+                    || {},
+                ),
+            );
+            for handle in handles {
+                handle.await.unwrap();
+            }
+            assert_eq!(WOKEN.load(Ordering::SeqCst), WAITERS);
+            assert!(QUEUE.is_empty());
+        });
+    }
+}
+
+pub(super) mod budget {
+    use core::{
+        sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorBudget, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const CHUNK: Duration = Duration::from_micros(500);
+    const BUDGET: ExecutorBudget =
+        ExecutorBudget::new(Duration::from_millis(2), Duration::from_millis(20));
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    static STOP: AtomicBool = AtomicBool::new(false);
+    static DONE: AtomicUsize = AtomicUsize::new(0);
+
+    /// Spins in chunks of [`CHUNK`], switching out after each, and adds the time spun to `busy`.
+    async fn spin(busy: &AtomicU64) {
+        while !STOP.load(Ordering::SeqCst) {
+            let begin = hal!().cpu().get_time();
+            while hal!().cpu().get_time() - begin < CHUNK {
+                core::hint::spin_loop();
+            }
+            let spun = hal!().cpu().get_time() - begin;
+            busy.fetch_add(spun.as_nanos() as u64, Ordering::SeqCst);
+            Runtime::switch_yield();
+        }
+        DONE.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[testdef]
+    fn test() {
+        static BUDGETED: AtomicU64 = AtomicU64::new(0);
+        static UNBUDGETED: AtomicU64 = AtomicU64::new(0);
+
+        let budgeted = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(spin(&BUDGETED), TaskPriority::default()),
+        )
+        .with_budget(BUDGET);
+        assert_eq!(budgeted.budget(), Some(BUDGET));
+        let unbudgeted = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(spin(&UNBUDGETED), TaskPriority::default()),
+        );
+        assert_eq!(unbudgeted.budget(), None);
+        Inspector::with_current(|is| {
+            is.register(budgeted).unwrap();
+            is.register(unbudgeted).unwrap();
+        })
+        .unwrap();
+
+        let begin = hal!().cpu().get_time();
+        while hal!().cpu().get_time() - begin < WINDOW {
+            Runtime::switch_yield();
+        }
+        STOP.store(true, Ordering::SeqCst);
+        while DONE.load(Ordering::SeqCst) < 2 {
+            Runtime::switch_yield();
+        }
+
+        let budgeted = Duration::from_nanos(BUDGETED.load(Ordering::SeqCst));
+        let unbudgeted = Duration::from_nanos(UNBUDGETED.load(Ordering::SeqCst));
+        info!(
+            "budgeted executor ran for {:?}, unbudgeted one for {:?}",
+            budgeted, unbudgeted
+        );
+
+        // Each period may be overdrawn by one chunk, and the window spans one more period
+        // than it fits.
+        let periods = (WINDOW.as_nanos() / BUDGET.period.as_nanos()) as u32 + 1;
+        assert!(budgeted <= (BUDGET.runtime + CHUNK) * periods);
+        // The budget is replenished, so the executor keeps running in later periods.
+        assert!(budgeted > BUDGET.runtime + CHUNK);
+        assert!(unbudgeted > budgeted * 2);
+    }
+}
+
+pub(super) mod edf {
+    use core::{
+        sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use alloc::vec::Vec;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        set_deadline,
+        time::sleep_until,
+        yield_now, Task, TaskDeadline, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+    use spin::Mutex;
+
+    /// Periods and execution times of the tasks of a set, with a utilization of 0.3.
+    const FEASIBLE: [(Duration, Duration); 3] = [
+        (Duration::from_millis(10), Duration::from_millis(1)),
+        (Duration::from_millis(20), Duration::from_millis(2)),
+        (Duration::from_millis(40), Duration::from_millis(4)),
+    ];
+    /// Periods and execution times of the tasks of a set, with a utilization of 1.2.
+    const INFEASIBLE: [(Duration, Duration); 3] = [
+        (Duration::from_millis(10), Duration::from_millis(5)),
+        (Duration::from_millis(20), Duration::from_millis(8)),
+        (Duration::from_millis(40), Duration::from_millis(12)),
+    ];
+    const HYPERPERIODS: u32 = 4;
+    const CHUNK: Duration = Duration::from_micros(500);
+
+    static ORDER: Mutex<Vec<Option<u64>>> = Mutex::new(Vec::new());
+    static DONE: AtomicUsize = AtomicUsize::new(0);
+    static MISSES: AtomicU64 = AtomicU64::new(0);
+
+    fn spin(work: Duration) {
+        let begin = hal!().cpu().get_time();
+        while hal!().cpu().get_time() - begin < work {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Runs `jobs` jobs of `work` released every `period` from `start`, each due by the release
+    /// of the next one.
+    async fn periodic(start: Duration, period: Duration, work: Duration, jobs: u32) {
+        let mut release = start;
+        for _ in 0..jobs {
+            sleep_until(release).await;
+            // A late job is ready at once, but still goes through the queue to be dispatched.
+            yield_now!();
+            spin(work);
+            release += period;
+            set_deadline(Some(TaskDeadline::new(release + period))).unwrap();
+        }
+        let misses = Executor::with_current(|ex| ex.deadline_misses()).unwrap();
+        MISSES.fetch_max(misses, Ordering::SeqCst);
+        DONE.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Spins in chunks of [`CHUNK`] without a deadline until the periodic tasks are done.
+    async fn background(tasks: usize) {
+        while DONE.load(Ordering::SeqCst) < tasks {
+            spin(CHUNK);
+            yield_now!();
+        }
+    }
+
+    /// Runs the periodic task set `set` along with a background task in an EDF executor,
+    /// returning its deadline misses.
+    fn run(set: &[(Duration, Duration)]) -> u64 {
+        DONE.store(0, Ordering::SeqCst);
+        MISSES.store(0, Ordering::SeqCst);
+
+        let hyperperiod = set.iter().map(|&(period, _)| period).max().unwrap();
+        let start = hal!().cpu().get_time() + Duration::from_millis(10);
+        let mut executor = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(background(set.len()), TaskPriority::default()),
+        )
+        .with_edf();
+        for &(period, work) in set {
+            let jobs = HYPERPERIODS * (hyperperiod.as_nanos() / period.as_nanos()) as u32;
+            let task = Task::new(periodic(start, period, work, jobs), TaskPriority::default())
+                .with_deadline(TaskDeadline::new(start + period));
+            executor.spawn(task).unwrap();
+        }
+        Inspector::with_current(|is| is.register(executor).unwrap()).unwrap();
+
+        while DONE.load(Ordering::SeqCst) < set.len() {
+            Runtime::switch_yield();
+        }
+        MISSES.load(Ordering::SeqCst)
+    }
+
+    #[testdef]
+    fn test() {
+        // Deadline tasks go first, earliest first, whatever their priorities, then the rest.
+        let far = hal!().cpu().get_time() + Duration::from_secs(60);
+        let mut executor = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(
+                async { ORDER.lock().push(None) },
+                TaskPriority::new(TaskPriority::MAX),
+            ),
+        )
+        .with_edf();
+        for (secs, priority) in [(3, TaskPriority::MAX), (1, 0), (2, TaskPriority::MAX / 2)] {
+            let deadline = TaskDeadline::new(far + Duration::from_secs(secs));
+            let task = Task::new(
+                async move { ORDER.lock().push(Some(secs)) },
+                priority.into(),
+            )
+            .with_deadline(deadline);
+            assert_eq!(task.deadline(), Some(deadline));
+            executor.spawn(task).unwrap();
+        }
+        Inspector::with_current(|is| is.register(executor).unwrap()).unwrap();
+        while ORDER.lock().len() < 4 {
+            Runtime::switch_yield();
+        }
+        assert_eq!(*ORDER.lock(), [Some(1), Some(2), Some(3), None]);
+
+        let misses = run(&FEASIBLE);
+        info!("feasible task set missed {} deadlines", misses);
+        assert_eq!(misses, 0);
+
+        let misses = run(&INFEASIBLE);
+        info!("infeasible task set missed {} deadlines", misses);
+        assert!(misses > 0);
+
+        assert!(set_deadline(None).is_ok());
+    }
+}
+
+#[cfg(feature = "task-stats")]
+pub(super) mod task_stats {
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{executor::Executor, spawn, yield_now, Task, TaskPriority};
+    use jrinx_testdef::testdef;
+
+    const ROUNDS: u64 = 3;
+
+    /// Cycles spun by each poll of the spinning task.
+    const SPIN_CYCLES: u64 = 2_000_000;
+
+    async fn spinner() {
+        for _ in 0..ROUNDS {
+            let begin = hal!().cpu().get_cycles();
+            while hal!().cpu().get_cycles() - begin < SPIN_CYCLES {
+                core::hint::spin_loop();
+            }
+            yield_now!();
+        }
+    }
+
+    #[testdef]
+    fn test() {
+        spawn!(async {
+            let task = Task::new(spinner(), TaskPriority::default()).pinned();
+            let id = task.id();
+            Executor::with_current(|ex| ex.spawn(task).map(|_| ()))
+                .unwrap()
+                .unwrap();
+            for _ in 0..ROUNDS - 1 {
+                yield_now!();
+            }
+
+            let stats = Executor::with_current(|ex| ex.task_stats()[&id]).unwrap();
+            info!("spinning task: {}", stats);
+
+            // Polls end with a yield, so each of them is charged with one spin and a little more.
+            assert_eq!(stats.polls, ROUNDS - 1);
+            assert!(stats.busy_cycles >= SPIN_CYCLES * stats.polls);
+            assert!(stats.busy_cycles < 2 * SPIN_CYCLES * stats.polls);
+            assert!(stats.last_poll_cycles >= SPIN_CYCLES);
+            assert!(stats.last_poll_cycles < 2 * SPIN_CYCLES);
+        });
+    }
+}
+
+pub(super) mod task_local {
+    use core::{
+        cell::Cell,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    use jrinx_error::InternalError;
+    use jrinx_multitask::{spawn, task_local, yield_now};
+    use jrinx_testdef::testdef;
+
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    static FAILED_OUTSIDE: AtomicBool = AtomicBool::new(true);
+
+    task_local! {
+        static COUNTER: Cell<usize> = Cell::new(0);
+        static TRACKER: Tracker = Tracker;
+    }
+
+    /// Records its drop, which happens after the poll of its task is over.
+    struct Tracker;
+
+    impl Drop for Tracker {
+        fn drop(&mut self) {
+            let outside = matches!(COUNTER.try_with(|_| ()), Err(InternalError::NoCurrentTask));
+            FAILED_OUTSIDE.fetch_and(outside, Ordering::SeqCst);
+            DROPPED.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    async fn count(rounds: usize) -> usize {
+        TRACKER.with(|_| ());
+        for _ in 0..rounds {
+            COUNTER.with(|counter| counter.set(counter.get() + 1));
+            yield_now!();
+        }
+        COUNTER.with(|counter| counter.get())
+    }
+
+    #[testdef]
+    fn test() {
+        spawn!(async {
+            let three = spawn!(count(3));
+            let five = spawn!(count(5));
+            assert_eq!(three.await.unwrap(), 3);
+            assert_eq!(five.await.unwrap(), 5);
+
+            // The counter of this task is untouched by the others.
+            assert_eq!(COUNTER.with(|counter| counter.get()), 0);
+            assert!(matches!(
+                COUNTER.with(|_| COUNTER.try_with(|_| ())),
+                Err(InternalError::TaskLocalInUse)
+            ));
+
+            while DROPPED.load(Ordering::SeqCst) < 2 {
+                yield_now!();
+            }
+            assert!(FAILED_OUTSIDE.load(Ordering::SeqCst));
+        });
+    }
+}
+
+pub(super) mod spawn {
+    use alloc::{format, string::ToString};
+    use jrinx_multitask::{
+        executor::{polling_task, polling_task_name},
+        spawn, TaskName, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    fn name() -> TaskName {
+        polling_task_name().unwrap()
+    }
+
+    fn is_anonymous() -> bool {
+        let task = polling_task().unwrap();
+        task.name == TaskName::Anonymous(task.task)
+            && task.name.to_string() == format!("task-{}", task.task.value())
+    }
+
+    #[testdef]
+    fn test() {
+        spawn!(async {
+            let named = spawn!(name = "named", async { name() });
+            assert_eq!(named.await.unwrap(), TaskName::Named("named"));
+
+            let both = spawn!(name = "both", pri = TaskPriority::new(3), async { name() });
+            assert_eq!(both.await.unwrap(), TaskName::Named("both"));
+            let swapped = spawn!(pri = TaskPriority::new(3), name = "swapped", async {
+                name()
+            });
+            assert_eq!(swapped.await.unwrap(), TaskName::Named("swapped"));
+
+            let prioritized = spawn!(pri = TaskPriority::new(3), async { is_anonymous() });
+            assert!(prioritized.await.unwrap());
+            let old = spawn!(pri := TaskPriority::new(3) => async { is_anonymous() });
+            assert!(old.await.unwrap());
+            let plain = spawn!(async { is_anonymous() });
+            assert!(plain.await.unwrap());
+        });
+    }
+}
+
+pub(super) mod try_spawn {
+    use core::{
+        alloc::Layout,
+        future::Future,
+        pin::Pin,
+        ptr,
+        task::{Context, Poll},
+    };
+
+    use alloc::alloc::{alloc, dealloc};
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Hal, Interrupt};
+    use jrinx_multitask::{executor::Executor, TaskPriority};
+    use jrinx_testdef::testdef;
+
+    const CHUNK: usize = 256 * 1024;
+
+    fn chunk(size: usize) -> Layout {
+        Layout::from_size_align(size, 8).unwrap()
+    }
+
+    /// A future larger than any free block of the heap once it is filled.
+    struct Bulky([u8; CHUNK * 3 / 4]);
+
+    impl Future for Bulky {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+            Poll::Ready(())
+        }
+    }
+
+    /// Fills the heap with chunks linked through their first word, but half a chunk left for
+    /// the allocations of the rest of the kernel, returning the last chunk and the half.
+    fn fill() -> (*mut u8, *mut u8) {
+        let mut last = ptr::null_mut();
+        loop {
+            let block = unsafe { alloc(chunk(CHUNK)) };
+            if block.is_null() {
+                break;
+            }
+            unsafe { block.cast::<*mut u8>().write(last) };
+            last = block;
+        }
+        assert!(!last.is_null());
+
+        let rest = unsafe { last.cast::<*mut u8>().read() };
+        unsafe { dealloc(last, chunk(CHUNK)) };
+        let half = unsafe { alloc(chunk(CHUNK / 2)) };
+        assert!(!half.is_null());
+        (rest, half)
+    }
+
+    fn release((mut last, half): (*mut u8, *mut u8)) {
+        unsafe { dealloc(half, chunk(CHUNK / 2)) };
+        while !last.is_null() {
+            let rest = unsafe { last.cast::<*mut u8>().read() };
+            unsafe { dealloc(last, chunk(CHUNK)) };
+            last = rest;
+        }
+    }
+
+    #[testdef]
+    fn test() {
+        // Timer interrupt handlers allocate, so they are kept out while the heap is filled.
+        let spawned = hal!().interrupt().with_saved_off(|| {
+            let filled = fill();
+            let spawned = Executor::with_current(|ex| {
+                ex.try_spawn(Bulky([0; CHUNK * 3 / 4]), TaskPriority::default())
+            });
+            release(filled);
+            spawned
+        });
+        assert!(matches!(spawned, Ok(Err(InternalError::NotEnoughMem))));
+
+        let spawned = Executor::with_current(|ex| {
+            ex.try_spawn(Bulky([0; CHUNK * 3 / 4]), TaskPriority::default())
+        });
+        assert!(matches!(spawned, Ok(Ok(_))));
+    }
+}
+
+pub(super) mod ipi {
+    use core::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::ipi;
+    use jrinx_percpu::percpu;
+    use jrinx_testdef::testdef;
+
+    #[percpu]
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn increment() {
+        COUNTER.with_ref(|c| c.fetch_add(1, Ordering::SeqCst));
+    }
+
+    fn count(cpu_id: usize) -> usize {
+        COUNTER.with_spec_ref(cpu_id, |c| c.load(Ordering::SeqCst))
+    }
+
+    #[testdef]
+    fn test() {
+        let local_id = hal!().cpu().id();
+        let Some(remote_id) = (0..hal!().cpu().nproc_valid()).find(|&id| id != local_id) else {
+            warn!("no remote cpu to call");
+            return;
+        };
+        let cpus = [local_id, remote_id];
+
+        ipi::remote_call_sync(&cpus, Duration::from_secs(1), increment).unwrap();
+        assert_eq!(count(local_id), 1);
+        assert_eq!(count(remote_id), 1);
+
+        ipi::remote_call(&cpus, increment).unwrap();
+        assert_eq!(count(local_id), 2);
+        let deadline = hal!().cpu().get_time() + Duration::from_secs(1);
+        while count(remote_id) != 2 {
+            assert!(hal!().cpu().get_time() < deadline);
+            core::hint::spin_loop();
+        }
+    }
+}
@@ -9,7 +9,7 @@ pub(super) mod round_robin {
     use jrinx_testdef::testdef;
     use spin::Mutex;
 
-    #[testdef]
+    #[testdef(serial)]
     fn test() {
         static ORDER: Mutex<Vec<(u8, u8)>> = Mutex::new(Vec::new());
 
@@ -114,7 +114,7 @@ pub(super) mod sched_table {
         }
     }
 
-    #[testdef]
+    #[testdef(serial)]
     fn test() {
         const INSPECTOR_MAX: usize = 3;
         const FRAME_SIZE: usize = 4;
@@ -197,3 +197,993 @@ pub(super) mod sched_table {
         Runtime::switch_yield();
     }
 }
+
+pub(super) mod window_jitter {
+    use core::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use alloc::vec::Vec;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::{Runtime, RuntimeSchedTable, RuntimeSchedTableEntry},
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::{jitter, TimedEvent, TimedEventHandler};
+
+    const WINDOW: Duration = Duration::from_millis(20);
+    const FRAMES: u32 = 8;
+
+    const ABUSE_PERIOD: Duration = Duration::from_millis(3);
+    const ABUSE_BURST: usize = 16;
+    const ABUSE_SPIN: Duration = Duration::from_micros(200);
+
+    /// A window switch may wait for at most one timed-event handler already running in the
+    /// trap, plus the trap latency, whereas a whole burst of handlers takes
+    /// `ABUSE_BURST * ABUSE_SPIN`.
+    const JITTER_BOUND: Duration = Duration::from_millis(1);
+
+    static ABUSE: AtomicBool = AtomicBool::new(false);
+
+    fn spin(duration: Duration) {
+        let start = hal!().cpu().get_time();
+        while hal!().cpu().get_time() - start < duration {
+            core::hint::spin_loop();
+        }
+    }
+
+    fn abuse(time: Duration) {
+        if !ABUSE.load(Ordering::SeqCst) {
+            return;
+        }
+        for _ in 0..ABUSE_BURST {
+            TimedEvent::create(time, TimedEventHandler::new(|| spin(ABUSE_SPIN), || {}));
+        }
+        TimedEvent::create(
+            time,
+            TimedEventHandler::new(move || abuse(time + ABUSE_PERIOD), || {}),
+        );
+    }
+
+    #[testdef(serial)]
+    fn test() {
+        let mut inspector_list = Vec::new();
+        let deadline = hal!().cpu().get_time() + WINDOW * 2 * FRAMES;
+
+        for _ in 0..2 {
+            let inspector = Inspector::new();
+            inspector
+                .register(Executor::new(
+                    ExecutorPriority::default(),
+                    Task::new(
+                        async move {
+                            while hal!().cpu().get_time() < deadline {
+                                core::hint::spin_loop();
+                            }
+                            let _ = Runtime::with_current(|rt| rt.revoke_sched_table());
+                        },
+                        TaskPriority::default(),
+                    ),
+                ))
+                .unwrap();
+            inspector_list.push(inspector.id());
+            Runtime::with_current(|rt| rt.register(inspector).unwrap());
+        }
+
+        let sched_table = RuntimeSchedTable::new(
+            WINDOW * 2,
+            inspector_list
+                .iter()
+                .zip(0..)
+                .map(|(&inspector_id, i)| RuntimeSchedTableEntry {
+                    inspector_id,
+                    offset: WINDOW * i,
+                    period: WINDOW * 2,
+                    duration: WINDOW,
+                }),
+        )
+        .unwrap();
+
+        jitter::reset_primary();
+        ABUSE.store(true, Ordering::SeqCst);
+        abuse(hal!().cpu().get_time() + ABUSE_PERIOD);
+
+        Runtime::with_current(|rt| rt.enact_sched_table(sched_table).unwrap());
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+
+        ABUSE.store(false, Ordering::SeqCst);
+
+        let histogram = jitter::primary();
+        info!("window switch jitter: {}", histogram);
+        assert!(histogram.count() > 0);
+        assert!(histogram.max() < JITTER_BOUND);
+    }
+}
+
+pub(super) mod sched_table_replace {
+    use core::time::Duration;
+
+    use alloc::vec::Vec;
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal, Interrupt};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::{Inspector, InspectorId},
+        runtime::{Runtime, RuntimeSchedReplacePolicy, RuntimeSchedTable, RuntimeSchedTableEntry},
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+    use spin::{Mutex, Once};
+
+    const FRAME: Duration = Duration::from_millis(40);
+    const FRAMES: u32 = 6;
+    const TOLERANCE: Duration = Duration::from_millis(5);
+
+    static INSPECTORS: Once<[InspectorId; 3]> = Once::new();
+    static REQUESTED: Once<Duration> = Once::new();
+    static RECORD: Mutex<Vec<(Duration, InspectorId)>> = Mutex::new(Vec::new());
+
+    fn record() {
+        let time = hal!().cpu().get_time();
+        let inspector_id = Inspector::with_current(|is| is.id()).unwrap();
+        let mut record = RECORD.lock();
+        if record.last().map(|&(_, last)| last) != Some(inspector_id) {
+            record.push((time, inspector_id));
+        }
+    }
+
+    fn entry(
+        inspector_id: InspectorId,
+        offset_ms: u64,
+        duration_ms: u64,
+    ) -> RuntimeSchedTableEntry {
+        RuntimeSchedTableEntry {
+            inspector_id,
+            offset: Duration::from_millis(offset_ms),
+            period: FRAME,
+            duration: Duration::from_millis(duration_ms),
+        }
+    }
+
+    fn replace() {
+        let [a, _, c] = *INSPECTORS.get().unwrap();
+        let table =
+            || RuntimeSchedTable::new(FRAME, [entry(a, 0, 10), entry(c, 10, 30)].into_iter());
+
+        assert!(matches!(
+            Runtime::with_current(|rt| {
+                rt.replace_sched_table(table().unwrap(), RuntimeSchedReplacePolicy::Strict)
+            }),
+            Err(InternalError::UnscheduledInspector)
+        ));
+        Runtime::with_current(|rt| {
+            rt.replace_sched_table(table().unwrap(), RuntimeSchedReplacePolicy::PauseOmitted)
+        })
+        .unwrap();
+        assert!(matches!(
+            Runtime::with_current(|rt| {
+                rt.replace_sched_table(table().unwrap(), RuntimeSchedReplacePolicy::PauseOmitted)
+            }),
+            Err(InternalError::DuplicateRuntimeSchedTable)
+        ));
+    }
+
+    #[testdef(serial)]
+    fn test() {
+        let start = hal!().cpu().get_time();
+        let deadline = start + FRAME * FRAMES;
+
+        let mut inspector_list = Vec::new();
+        for i in 0..3 {
+            let inspector = Inspector::new();
+            inspector
+                .register(Executor::new(
+                    ExecutorPriority::default(),
+                    Task::new(
+                        async move {
+                            while hal!().cpu().get_time() < deadline {
+                                hal!().interrupt().with_saved_off(record);
+
+                                // The replacement is requested during the first window of the
+                                // second frame, so the rest of that frame must stay untouched.
+                                if i == 0 && hal!().cpu().get_time() > start + FRAME {
+                                    REQUESTED.call_once(|| {
+                                        replace();
+                                        hal!().cpu().get_time()
+                                    });
+                                }
+
+                                core::hint::spin_loop();
+                            }
+                            let _ = Runtime::with_current(|rt| rt.revoke_sched_table());
+                        },
+                        TaskPriority::default(),
+                    ),
+                ))
+                .unwrap();
+            inspector_list.push(inspector.id());
+            Runtime::with_current(|rt| rt.register(inspector).unwrap());
+        }
+        let [a, b, c]: [InspectorId; 3] = inspector_list.try_into().unwrap();
+        INSPECTORS.call_once(|| [a, b, c]);
+
+        let sched_table =
+            RuntimeSchedTable::new(FRAME, [entry(a, 0, 20), entry(b, 20, 20)].into_iter()).unwrap();
+        Runtime::with_current(|rt| rt.enact_sched_table(sched_table).unwrap());
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+
+        let requested = *REQUESTED.get().unwrap();
+        let record = RECORD.lock().clone();
+        trace!("record: {:?}", record);
+
+        let c_first = record
+            .iter()
+            .find_map(|&(time, id)| (id == c).then_some(time))
+            .unwrap();
+        let b_last = record
+            .iter()
+            .filter_map(|&(time, id)| (id == b).then_some(time))
+            .last()
+            .unwrap();
+        assert!(requested < b_last && b_last < c_first);
+        assert!(c_first - b_last > Duration::from_millis(30) - TOLERANCE);
+        assert!(c_first - b_last < Duration::from_millis(30) + TOLERANCE);
+
+        Runtime::with_current(|rt| {
+            rt.with_registry(|registry| {
+                let windows = |id| registry.get(&id).unwrap().take_sched_windows().unwrap();
+                assert_eq!(windows(a), [entry(a, 0, 10)]);
+                assert!(windows(b).is_empty());
+                assert_eq!(windows(c), [entry(c, 10, 30)]);
+            })
+        });
+
+        assert!(matches!(
+            Runtime::with_current(|rt| rt.resume_inspector(a)),
+            Err(InternalError::InvalidInspectorStatus)
+        ));
+        Runtime::with_current(|rt| rt.resume_inspector(b)).unwrap();
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+        assert!(!Runtime::with_current(
+            |rt| rt.with_registry(|registry| registry.contains_key(&b))
+        ));
+    }
+}
+
+pub(super) mod affinity {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal, Interrupt};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::{CpuAffinity, Inspector},
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    fn probe(ran_on: &'static AtomicUsize, affinity: CpuAffinity) -> Inspector {
+        let inspector = Inspector::new().with_affinity(affinity);
+        inspector
+            .register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(
+                    async move {
+                        ran_on.store(hal!().cpu().id(), Ordering::SeqCst);
+                    },
+                    TaskPriority::default(),
+                ),
+            ))
+            .unwrap();
+        inspector
+    }
+
+    fn wait_for(ran_on: &AtomicUsize) -> usize {
+        while ran_on.load(Ordering::SeqCst) == usize::MAX {
+            core::hint::spin_loop();
+        }
+        ran_on.load(Ordering::SeqCst)
+    }
+
+    #[testdef(serial)]
+    fn test() {
+        static PINNED: AtomicUsize = AtomicUsize::new(usize::MAX);
+        static MOVED: AtomicUsize = AtomicUsize::new(usize::MAX);
+        static BEFORE: AtomicUsize = AtomicUsize::new(usize::MAX);
+        static AFTER: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+        let nproc = hal!().cpu().nproc();
+        assert!(nproc >= 2);
+        let home = hal!().cpu().id();
+        let target = (home + 1) % nproc;
+
+        assert!(matches!(
+            Runtime::with_current(|rt| rt.register(probe(&PINNED, CpuAffinity::single(target)))),
+            Err(InternalError::InvalidCpuAffinity)
+        ));
+        Runtime::with_spec_cpu(target, |rt| {
+            rt.register(probe(&PINNED, CpuAffinity::single(target)))
+        })
+        .unwrap()
+        .unwrap();
+        hal!().interrupt().send_ipi(&[target]);
+        assert_eq!(wait_for(&PINNED), target);
+
+        let moved = probe(&MOVED, CpuAffinity::single(home).with(target));
+        let moved_id = moved.id();
+        Runtime::with_current(|rt| {
+            rt.register(moved).unwrap();
+            rt.migrate_inspector(moved_id, home).unwrap();
+            if nproc > 2 {
+                assert!(matches!(
+                    rt.migrate_inspector(moved_id, (target + 1) % nproc),
+                    Err(InternalError::InvalidCpuAffinity)
+                ));
+            }
+            rt.migrate_inspector(moved_id, target).unwrap();
+        });
+        assert_eq!(wait_for(&MOVED), target);
+
+        // The inspector migrates itself while running, which takes effect once it yields.
+        let inspector = Inspector::new();
+        inspector
+            .register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(
+                    async move {
+                        BEFORE.store(hal!().cpu().id(), Ordering::SeqCst);
+                        let id = Inspector::with_current(|is| is.id()).unwrap();
+                        Runtime::with_current(|rt| rt.migrate_inspector(id, target)).unwrap();
+                        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+                        Runtime::switch_yield();
+                        AFTER.store(hal!().cpu().id(), Ordering::SeqCst);
+                    },
+                    TaskPriority::default(),
+                ),
+            ))
+            .unwrap();
+        Runtime::with_current(|rt| rt.register(inspector).unwrap());
+
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+
+        assert_eq!(wait_for(&BEFORE), home);
+        assert_eq!(wait_for(&AFTER), target);
+    }
+}
+
+pub(super) mod sched_budget {
+    use core::{
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+
+    use alloc::vec::Vec;
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::{Inspector, InspectorId},
+        runtime::{Runtime, RuntimeSchedTable, RuntimeSchedTableEntry},
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const FRAME: Duration = Duration::from_millis(40);
+    const FRAMES: u32 = 5;
+    const WINDOWS_MS: [u64; 2] = [10, 30];
+
+    /// Samples further apart than this belong to different windows.
+    const GAP: Duration = Duration::from_millis(1);
+
+    static RUN_TIME: [AtomicU64; 2] = [const { AtomicU64::new(0) }; 2];
+
+    fn entry(
+        inspector_id: InspectorId,
+        offset_ms: u64,
+        duration_ms: u64,
+    ) -> RuntimeSchedTableEntry {
+        RuntimeSchedTableEntry {
+            inspector_id,
+            offset: Duration::from_millis(offset_ms),
+            period: FRAME,
+            duration: Duration::from_millis(duration_ms),
+        }
+    }
+
+    #[testdef(serial)]
+    fn test() {
+        let deadline = hal!().cpu().get_time() + FRAME * FRAMES;
+
+        let mut inspector_list = Vec::new();
+        for i in 0..WINDOWS_MS.len() {
+            let inspector = Inspector::new();
+            inspector
+                .register(Executor::new(
+                    ExecutorPriority::default(),
+                    Task::new(
+                        async move {
+                            // The task never yields, so only the end of its window stops it.
+                            let mut last = hal!().cpu().get_time();
+                            while last < deadline {
+                                let now = hal!().cpu().get_time();
+                                if now - last < GAP {
+                                    RUN_TIME[i].fetch_add(
+                                        (now - last).as_nanos() as u64,
+                                        Ordering::SeqCst,
+                                    );
+                                }
+                                last = now;
+                            }
+                            let _ = Runtime::with_current(|rt| rt.revoke_sched_table());
+                        },
+                        TaskPriority::default(),
+                    ),
+                ))
+                .unwrap();
+            inspector_list.push(inspector.id());
+            Runtime::with_current(|rt| rt.register(inspector).unwrap());
+        }
+        let [a, b]: [InspectorId; 2] = inspector_list.try_into().unwrap();
+
+        let invalid = [
+            Vec::new(),
+            [entry(a, 0, 20), entry(b, 10, 20)].into(),
+            [entry(a, 0, 10), entry(b, 30, 20)].into(),
+        ];
+        for table in invalid {
+            assert!(matches!(
+                RuntimeSchedTable::new(FRAME, table.into_iter()),
+                Err(InternalError::InvalidRuntimeSchedTable)
+            ));
+        }
+        let unregistered = Inspector::new().id();
+        assert!(matches!(
+            Runtime::with_current(|rt| rt.enact_sched_table(
+                RuntimeSchedTable::new(FRAME, [entry(unregistered, 0, 10)].into_iter()).unwrap()
+            )),
+            Err(InternalError::InvalidInspectorId)
+        ));
+
+        let sched_table = RuntimeSchedTable::new(
+            FRAME,
+            [
+                entry(a, 0, WINDOWS_MS[0]),
+                entry(b, WINDOWS_MS[0], WINDOWS_MS[1]),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+        Runtime::with_current(|rt| rt.enact_sched_table(sched_table).unwrap());
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+
+        // The last frame is cut short by the deadline, for either inspector.
+        for (run_time, window_ms) in RUN_TIME.iter().zip(WINDOWS_MS) {
+            let run_time = Duration::from_nanos(run_time.load(Ordering::SeqCst));
+            let window = Duration::from_millis(window_ms);
+            info!(
+                "window of {:?} ran for {:?} over {} frames",
+                window, run_time, FRAMES
+            );
+            assert!(run_time >= window * (FRAMES - 1) - GAP * FRAMES);
+            assert!(run_time <= window * FRAMES + GAP * FRAMES);
+        }
+    }
+}
+
+pub(super) mod overrun {
+    use core::time::Duration;
+
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::{Inspector, InspectorStats},
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+    use spin::Mutex;
+
+    const THRESHOLD: Duration = Duration::from_millis(1);
+    const BUSY: Duration = Duration::from_millis(5);
+
+    /// Creates an inspector spinning for `busy` in its first round, and recording its stats in
+    /// its second round.
+    fn probe(busy: Duration, stats: &'static Mutex<Option<InspectorStats>>) -> Inspector {
+        let inspector = Inspector::new().with_overrun_threshold(THRESHOLD);
+        inspector
+            .register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(
+                    async move {
+                        let begin = hal!().cpu().get_time();
+                        while hal!().cpu().get_time() - begin < busy {
+                            core::hint::spin_loop();
+                        }
+                        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+                        Runtime::switch_yield();
+
+                        *stats.lock() = Some(Inspector::with_current(|is| is.stats()).unwrap());
+                    },
+                    TaskPriority::default(),
+                ),
+            ))
+            .unwrap();
+        inspector
+    }
+
+    #[testdef(serial)]
+    fn test() {
+        static BUSY_STATS: Mutex<Option<InspectorStats>> = Mutex::new(None);
+        static QUIET_STATS: Mutex<Option<InspectorStats>> = Mutex::new(None);
+
+        let before = Inspector::with_current(|is| is.stats()).unwrap();
+
+        Runtime::with_current(|rt| {
+            rt.register(probe(BUSY, &BUSY_STATS)).unwrap();
+            rt.register(probe(Duration::ZERO, &QUIET_STATS)).unwrap();
+        });
+        while BUSY_STATS.lock().is_none() || QUIET_STATS.lock().is_none() {
+            Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+            Runtime::switch_yield();
+        }
+
+        let busy = BUSY_STATS.lock().unwrap();
+        info!("busy inspector: {}", busy);
+        assert_eq!(busy.rounds, 1);
+        assert_eq!(busy.overruns, 1);
+        assert!(busy.last_round >= BUSY);
+        assert_eq!(busy.longest_round, busy.last_round);
+
+        let quiet = QUIET_STATS.lock().unwrap();
+        info!("quiet inspector: {}", quiet);
+        assert_eq!(quiet.rounds, 1);
+        assert_eq!(quiet.overruns, 0);
+
+        // The test inspector itself is charged a round each time it switches out.
+        let after = Inspector::with_current(|is| is.stats()).unwrap();
+        assert!(after.rounds > before.rounds);
+        assert!(after.run_time >= before.run_time);
+    }
+}
+
+pub(super) mod balance {
+    use core::{
+        sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const INSPECTOR_MAX: usize = 8;
+    const BUSY: Duration = Duration::from_millis(2);
+
+    #[testdef(serial)]
+    fn test() {
+        static RAN_ON: AtomicU64 = AtomicU64::new(0);
+        static DONE: AtomicUsize = AtomicUsize::new(0);
+
+        assert!(hal!().cpu().nproc_valid() >= 2);
+        let local = hal!().cpu().id();
+
+        let mut remote = 0;
+        for _ in 0..INSPECTOR_MAX {
+            let inspector = Inspector::new();
+            inspector
+                .register(Executor::new(
+                    ExecutorPriority::default(),
+                    Task::new(
+                        async {
+                            let begin = hal!().cpu().get_time();
+                            while hal!().cpu().get_time() - begin < BUSY {
+                                core::hint::spin_loop();
+                            }
+                            RAN_ON.fetch_or(1 << hal!().cpu().id(), Ordering::SeqCst);
+                            DONE.fetch_add(1, Ordering::SeqCst);
+                        },
+                        TaskPriority::default(),
+                    ),
+                ))
+                .unwrap();
+            if Runtime::register_balanced(inspector).unwrap() != local {
+                remote += 1;
+            }
+        }
+        assert!(remote > 0);
+
+        while DONE.load(Ordering::SeqCst) < INSPECTOR_MAX {
+            Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+            Runtime::switch_yield();
+        }
+
+        let ran_on = RAN_ON.load(Ordering::SeqCst);
+        info!("inspectors ran on cpus {:#b}", ran_on);
+        assert!(ran_on.count_ones() >= 2);
+    }
+}
+
+pub(super) mod pause {
+    use core::{
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::{Inspector, InspectorId, InspectorStatus},
+        runtime::Runtime,
+        time::sleep,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const PERIOD: Duration = Duration::from_millis(2);
+
+    fn status_of(id: InspectorId) -> InspectorStatus {
+        Runtime::with_current(|rt| rt.with_registry(|registry| registry[&id].status()))
+    }
+
+    fn switch_for(duration: Duration) {
+        let begin = hal!().cpu().get_time();
+        while hal!().cpu().get_time() - begin < duration {
+            Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+            Runtime::switch_yield();
+        }
+    }
+
+    fn switch_until(ticks: usize, ticked: &AtomicUsize) {
+        while ticked.load(Ordering::SeqCst) < ticks {
+            Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+            Runtime::switch_yield();
+        }
+    }
+
+    #[testdef(serial)]
+    fn test() {
+        static TICKED: AtomicUsize = AtomicUsize::new(0);
+        static STOP: AtomicBool = AtomicBool::new(false);
+
+        let inspector = Inspector::new();
+        inspector
+            .register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(
+                    async {
+                        while !STOP.load(Ordering::SeqCst) {
+                            sleep(PERIOD).await;
+                            TICKED.fetch_add(1, Ordering::SeqCst);
+                        }
+                    },
+                    TaskPriority::default(),
+                ),
+            ))
+            .unwrap();
+        let id = inspector.id();
+        Runtime::with_current(|rt| rt.register(inspector).unwrap());
+
+        switch_until(3, &TICKED);
+
+        Runtime::with_current(|rt| rt.pause_inspector(id)).unwrap();
+        assert!(matches!(
+            Runtime::with_current(|rt| rt.pause_inspector(id)),
+            Err(InternalError::InvalidInspectorStatus)
+        ));
+        let paused_at = TICKED.load(Ordering::SeqCst);
+        switch_for(PERIOD * 10);
+        assert_eq!(TICKED.load(Ordering::SeqCst), paused_at);
+        assert_eq!(status_of(id), InspectorStatus::Paused(None));
+
+        Runtime::with_current(|rt| rt.resume_inspector(id)).unwrap();
+        assert!(matches!(
+            Runtime::with_current(|rt| rt.resume_inspector(id)),
+            Err(InternalError::InvalidInspectorStatus)
+        ));
+        assert!(!matches!(status_of(id), InspectorStatus::Paused(_)));
+        switch_until(paused_at + 3, &TICKED);
+
+        STOP.store(true, Ordering::SeqCst);
+        while Runtime::with_current(|rt| rt.with_registry(|registry| registry.contains_key(&id))) {
+            switch_for(Duration::ZERO);
+        }
+        assert!(matches!(
+            Runtime::with_current(|rt| rt.pause_inspector(id)),
+            Err(InternalError::InvalidInspectorId)
+        ));
+    }
+}
+
+pub(super) mod busy_lock {
+    use core::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal, Interrupt};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    const TIMEOUT: Duration = Duration::from_millis(5);
+
+    static HELD: AtomicBool = AtomicBool::new(false);
+    static RELEASE: AtomicBool = AtomicBool::new(false);
+
+    /// Spins in its drop, which the runtime unregistering its inspector runs under its lock.
+    struct Holder;
+
+    impl Drop for Holder {
+        fn drop(&mut self) {
+            HELD.store(true, Ordering::SeqCst);
+            while !RELEASE.load(Ordering::SeqCst) {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    #[testdef(serial)]
+    fn test() {
+        let local = hal!().cpu().id();
+        let remote = (0..hal!().cpu().nproc())
+            .find(|&cpu_id| cpu_id != local && Runtime::with_spec_cpu(cpu_id, |_| ()).is_ok())
+            .unwrap();
+
+        let bait = Inspector::new_with_ext(Holder);
+        let bait_id = bait.id();
+        let holder = Inspector::new();
+        holder
+            .register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(
+                    async move {
+                        Runtime::with_spec_cpu(local, |rt| rt.unregister(bait_id))
+                            .unwrap()
+                            .unwrap();
+                    },
+                    TaskPriority::default(),
+                ),
+            ))
+            .unwrap();
+        let holder_id = holder.id();
+
+        // This task never switches out, so the bait is never run, only unregistered from the
+        // remote CPU. Interrupt handlers of this CPU would wait for the lock as well, never to
+        // return.
+        hal!().interrupt().with_saved_off(|| {
+            Runtime::with_current(|rt| {
+                rt.register(bait).unwrap();
+                rt.register(holder).unwrap();
+                rt.migrate_inspector(holder_id, remote).unwrap();
+            });
+
+            while !HELD.load(Ordering::SeqCst) {
+                core::hint::spin_loop();
+            }
+
+            assert!(matches!(
+                Runtime::with_current_try_lock(|_| ()),
+                Err(InternalError::BusyLock)
+            ));
+            let begin = hal!().cpu().get_time();
+            assert!(matches!(
+                Runtime::with_current_spin_timeout(|_| (), TIMEOUT),
+                Err(InternalError::BusyLock)
+            ));
+            assert!(hal!().cpu().get_time() - begin >= TIMEOUT);
+
+            RELEASE.store(true, Ordering::SeqCst);
+            let unregistered = Runtime::with_current_spin_timeout(
+                |registry| !registry.contains_key(&bait_id),
+                TIMEOUT * 100,
+            );
+            assert!(unregistered.unwrap());
+        });
+    }
+}
+
+pub(super) mod online {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    use crate::arch::cpus;
+
+    #[testdef(serial)]
+    fn test() {
+        static RAN_ON: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+        assert_eq!(cpus::nproc_booted(), 1);
+        let local = hal!().cpu().id();
+        let target = (0..hal!().cpu().nproc_valid())
+            .find(|&cpu_id| cpu_id != local)
+            .unwrap();
+        assert!(Runtime::with_spec_cpu(target, |_| ()).is_err());
+
+        cpus::online(target).unwrap();
+        assert!(cpus::is_late(target));
+        assert!(Runtime::with_spec_cpu(target, |_| ()).is_ok());
+        assert!(matches!(
+            cpus::online(target),
+            Err(InternalError::InvalidRuntimeStatus)
+        ));
+        assert!(matches!(
+            cpus::online(usize::MAX),
+            Err(InternalError::InvalidCpuId(usize::MAX))
+        ));
+
+        let inspector = Inspector::new();
+        inspector
+            .register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(
+                    async {
+                        RAN_ON.store(hal!().cpu().id(), Ordering::SeqCst);
+                    },
+                    TaskPriority::default(),
+                ),
+            ))
+            .unwrap();
+        assert_eq!(Runtime::register_balanced(inspector).unwrap(), target);
+
+        while RAN_ON.load(Ordering::SeqCst) == usize::MAX {
+            Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+            Runtime::switch_yield();
+        }
+        assert_eq!(RAN_ON.load(Ordering::SeqCst), target);
+    }
+}
+
+pub(super) mod tickless {
+    use core::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use alloc::vec::Vec;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_stats::{StatKind, StatsSnapshot};
+
+    pub const PERIOD: Duration = Duration::from_millis(100);
+
+    fn ticks(before: &StatsSnapshot, after: &StatsSnapshot, cpu_id: usize) -> u64 {
+        after.cpus()[cpu_id].get(StatKind::TimerInterrupt)
+            - before.cpus()[cpu_id].get(StatKind::TimerInterrupt)
+    }
+
+    fn spin(period: Duration) {
+        let begin = hal!().cpu().get_time();
+        while hal!().cpu().get_time() - begin < period {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Returns the timer interrupts each idle CPU took over [`PERIOD`], then the count of a CPU
+    /// running a busy inspector for as long.
+    fn measure() -> (Vec<u64>, u64) {
+        static DONE: AtomicBool = AtomicBool::new(false);
+
+        assert!(hal!().cpu().nproc_valid() >= 2);
+        let local = hal!().cpu().id();
+
+        let before = jrinx_stats::snapshot();
+        spin(PERIOD);
+        let after = jrinx_stats::snapshot();
+        let idle = (0..hal!().cpu().nproc_valid())
+            .filter(|&cpu_id| cpu_id != local)
+            .map(|cpu_id| ticks(&before, &after, cpu_id))
+            .collect::<Vec<_>>();
+        info!("ticks of idle cpus in {:?}: {:?}", PERIOD, idle);
+
+        let inspector = Inspector::new();
+        inspector
+            .register(Executor::new(
+                ExecutorPriority::default(),
+                Task::new(
+                    async {
+                        spin(PERIOD);
+                        DONE.store(true, Ordering::SeqCst);
+                    },
+                    TaskPriority::default(),
+                ),
+            ))
+            .unwrap();
+        let before = jrinx_stats::snapshot();
+        let busy_cpu = Runtime::register_balanced(inspector).unwrap();
+        assert_ne!(busy_cpu, local);
+        while !DONE.load(Ordering::SeqCst) {
+            core::hint::spin_loop();
+        }
+        let after = jrinx_stats::snapshot();
+        let busy = ticks(&before, &after, busy_cpu);
+        info!("ticks of a busy cpu in {:?}: {}", PERIOD, busy);
+
+        (idle, busy)
+    }
+
+    #[cfg(feature = "tickless")]
+    pub(super) mod on {
+        use jrinx_testdef::testdef;
+
+        #[testdef]
+        fn test() {
+            let (idle, busy) = super::measure();
+            assert!(idle.iter().all(|&ticks| ticks == 0));
+            assert!(busy > 0);
+        }
+    }
+
+    #[cfg(not(feature = "tickless"))]
+    pub(super) mod off {
+        use jrinx_multitask::runtime::IDLE_TICK;
+        use jrinx_testdef::testdef;
+
+        use super::PERIOD;
+
+        #[testdef]
+        fn test() {
+            let (idle, busy) = super::measure();
+            let expected = (PERIOD.as_nanos() / IDLE_TICK.as_nanos()) as u64;
+            assert!(idle.iter().all(|&ticks| ticks >= expected / 2));
+            assert!(busy > 0);
+        }
+    }
+}
+
+pub(super) mod shutdown {
+    use jrinx_hal::HaltReason;
+    use jrinx_multitask::runtime::Runtime;
+    use jrinx_testdef::testdef;
+
+    fn first_hook() {
+        info!("shutdown hook registered first");
+    }
+
+    fn second_hook() {
+        info!("shutdown hook registered second");
+    }
+
+    #[testdef(serial)]
+    fn test() {
+        Runtime::on_shutdown(first_hook);
+        Runtime::on_shutdown(second_hook);
+        assert!(!Runtime::is_shutting_down());
+
+        Runtime::shutdown(HaltReason::NormalExit);
+    }
+}
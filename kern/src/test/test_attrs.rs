@@ -0,0 +1,46 @@
+//! Tests declared with attributes, which the runner runs in isolation.
+
+use core::time::Duration;
+
+use jrinx_hal::{Cpu, Hal, Interrupt};
+
+/// Waits for `duration` without yielding.
+fn wait(duration: Duration) {
+    let deadline = hal!().cpu().get_time() + duration;
+    while hal!().cpu().get_time() < deadline {
+        hal!().interrupt().wait();
+    }
+}
+
+pub(super) mod should_panic {
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_testdef::testdef;
+
+    #[testdef(should_panic)]
+    fn test() {
+        jrinx_trap::stats::snapshot(hal!().cpu().nproc());
+    }
+}
+
+pub(super) mod timeout {
+    use core::time::Duration;
+
+    use jrinx_testdef::testdef;
+
+    #[testdef(timeout_ms = 500)]
+    fn test() {
+        super::wait(Duration::from_millis(50));
+    }
+}
+
+/// Runs past its timeout, failing once it returns as it never yields.
+pub(super) mod overrun {
+    use core::time::Duration;
+
+    use jrinx_testdef::testdef;
+
+    #[testdef(timeout_ms = 100)]
+    fn test() {
+        super::wait(Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,47 @@
+use jrinx_testdef::{glob_match, testdef, TestDef};
+
+#[testdef]
+fn test() {
+    for (pattern, name) in [
+        ("", ""),
+        ("*", ""),
+        ("*", "jrinx::test::mm"),
+        ("mm", "mm"),
+        ("m?", "mm"),
+        ("??", "mm"),
+        ("?", "é"),
+        ("*mm", "mm"),
+        ("mm*", "mm"),
+        ("**", "mm"),
+        ("a*b*c", "axxbyybc"),
+        ("*::task::*", "jrinx::test::task::runtime"),
+        ("[ab]", "[ab]"),
+    ] {
+        assert!(
+            glob_match(pattern, name),
+            "{pattern:?} should match {name:?}"
+        );
+    }
+
+    for (pattern, name) in [
+        ("", "mm"),
+        ("?", ""),
+        ("m", "mm"),
+        ("???", "mm"),
+        ("a*c", "abcbd"),
+        ("*::task", "jrinx::test::task::runtime"),
+        ("[ab]", "a"),
+    ] {
+        assert!(
+            !glob_match(pattern, name),
+            "{pattern:?} should not match {name:?}"
+        );
+    }
+
+    let matched = |pattern| jrinx_testdef::find_all(pattern).map(TestDef::name);
+    assert!(matched("test_g*").eq([module_path!()]));
+    assert!(matched("jrinx::test::test_glo?").eq([module_path!()]));
+    assert!(matched("est_glob").next().is_none());
+    assert!(matched("no_such_*").next().is_none());
+    assert_eq!(matched("*").count(), jrinx_testdef::all().count());
+}
@@ -0,0 +1,13 @@
+use core::time::Duration;
+
+use jrinx_hal::{Cpu, Hal};
+use jrinx_testdef::testdef;
+
+#[testdef]
+fn test() {
+    let deadline = hal!().cpu().get_time() + Duration::from_millis(100);
+    while hal!().cpu().get_time() < deadline {
+        core::hint::spin_loop();
+    }
+    info!("test jobs: ran on cpu#{}", hal!().cpu().id());
+}
@@ -0,0 +1,6 @@
+use jrinx_testdef::testdef;
+
+#[testdef]
+fn test() {
+    info!("test run: first test passed");
+}
@@ -95,3 +95,643 @@ pub(super) mod queue {
         }
     }
 }
+
+pub(super) mod wheel {
+    use core::time::Duration;
+
+    use alloc::{collections::BTreeSet, vec::Vec};
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::wheel::TimingWheel;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    #[testdef]
+    fn test() {
+        const ROUNDS: usize = 4096;
+
+        let mut rng =
+            SmallRng::seed_from_u64(option_env!("RAND_SEED").unwrap_or("0").parse().unwrap());
+        let mut wheel = TimingWheel::new();
+        let mut reference = BTreeSet::new();
+        let mut handles = Vec::new();
+        let mut keys = Vec::new();
+        let mut now = Duration::ZERO;
+
+        for key in 0..ROUNDS as u64 {
+            let span = match rng.gen_range(0..8) {
+                0 => Duration::from_secs(rng.gen_range(0..100000)),
+                1..=3 => Duration::from_millis(rng.gen_range(0..5000)),
+                _ => Duration::from_micros(rng.gen_range(0..100000)),
+            };
+            handles.push(wheel.insert(now + span, key));
+            reference.insert((now + span, key));
+            keys.push((now + span, key));
+
+            if rng.gen_bool(0.25) {
+                let (time, key) = keys.swap_remove(rng.gen_range(0..keys.len()));
+                assert!(wheel.remove(handles[key as usize]));
+                assert!(!wheel.remove(handles[key as usize]));
+                assert!(reference.remove(&(time, key)));
+            }
+
+            now += Duration::from_micros(rng.gen_range(0..50000));
+            wheel.advance(now);
+            while let Some((time, key)) = wheel.next_deadline().filter(|&(time, _)| time <= now) {
+                assert_eq!(reference.pop_first(), Some((time, key)));
+                assert!(wheel.remove(handles[key as usize]));
+                keys.retain(|&(_, k)| k != key);
+            }
+            assert_eq!(wheel.next_deadline(), reference.first().copied());
+        }
+
+        while let Some((time, key)) = wheel.next_deadline() {
+            wheel.advance(time);
+            assert_eq!(reference.pop_first(), Some((time, key)));
+            assert!(wheel.remove(handles[key as usize]));
+        }
+        assert!(wheel.is_empty() && reference.is_empty());
+    }
+}
+
+pub(super) mod wheel_levels {
+    use core::time::Duration;
+
+    use alloc::vec::Vec;
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::wheel::TimingWheel;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    const TIMER_MAX: u64 = 10000;
+
+    #[testdef]
+    fn test() {
+        // With 1ms ticks and 64 slots a level, these land in levels 0 to 3 and in the overflow.
+        let spans = [
+            Duration::from_millis(5),
+            Duration::from_millis(300),
+            Duration::from_secs(20),
+            Duration::from_secs(1000),
+            Duration::from_secs(100000),
+        ];
+
+        let mut wheel = TimingWheel::new();
+        let mut handles = spans
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(key, &span)| wheel.insert(span, key))
+            .collect::<Vec<_>>();
+        handles.reverse();
+        assert_eq!(wheel.len(), spans.len());
+        for (key, &span) in spans.iter().enumerate() {
+            assert_eq!(wheel.next_deadline(), Some((span, key)));
+            wheel.advance(span);
+            assert!(wheel.remove(handles[key]));
+        }
+        assert!(wheel.is_empty());
+
+        // A timer cascaded down to the lowest level is still cancelled.
+        let mut wheel = TimingWheel::new();
+        let first = wheel.insert(Duration::from_secs(20), 0);
+        wheel.insert(Duration::from_secs(30), 1);
+        wheel.advance(Duration::from_millis(19990));
+        assert!(wheel.remove(first));
+        assert!(!wheel.remove(first));
+        assert_eq!(wheel.next_deadline(), Some((Duration::from_secs(30), 1)));
+        wheel.advance(Duration::from_secs(25));
+        assert_eq!(wheel.next_deadline(), Some((Duration::from_secs(30), 1)));
+
+        // A cancelled timer in the overflow is neither reported nor cascaded.
+        let mut wheel = TimingWheel::new();
+        let first = wheel.insert(Duration::from_secs(100000), 0);
+        wheel.insert(Duration::from_secs(200000), 1);
+        assert!(wheel.remove(first));
+        assert_eq!(
+            wheel.next_deadline(),
+            Some((Duration::from_secs(200000), 1))
+        );
+        wheel.advance(Duration::from_secs(150000));
+        assert_eq!(wheel.len(), 1);
+        assert_eq!(
+            wheel.next_deadline(),
+            Some((Duration::from_secs(200000), 1))
+        );
+
+        let mut rng =
+            SmallRng::seed_from_u64(option_env!("RAND_SEED").unwrap_or("0").parse().unwrap());
+        let mut wheel = TimingWheel::new();
+        let handles = (0..TIMER_MAX)
+            .map(|key| wheel.insert(Duration::from_micros(rng.gen_range(0..100000000)), key))
+            .collect::<Vec<_>>();
+        let mut fired = 0;
+        let mut last = Duration::ZERO;
+        while let Some((time, key)) = wheel.next_deadline() {
+            assert!(time >= last);
+            wheel.advance(time);
+            assert!(wheel.remove(handles[key as usize]));
+            last = time;
+            fired += 1;
+        }
+        assert_eq!(fired, TIMER_MAX);
+    }
+}
+
+pub(super) mod rearm {
+    use core::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal, Interrupt};
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+
+    #[testdef]
+    fn test() {
+        static FIRED: AtomicBool = AtomicBool::new(false);
+
+        let start = hal!().cpu().get_time();
+        let tracker = TimedEvent::create(
+            start + Duration::from_secs(10),
+            TimedEventHandler::new(
+                || FIRED.store(true, Ordering::SeqCst),
+                || panic!("this timed-event should not be cancelled"),
+            ),
+        );
+        tracker.rearm(start + Duration::from_millis(10)).unwrap();
+
+        while !FIRED.load(Ordering::SeqCst) {
+            hal!().interrupt().wait();
+        }
+        let elapsed = hal!().cpu().get_time() - start;
+        assert!(elapsed >= Duration::from_millis(10) && elapsed < Duration::from_secs(10));
+        assert!(tracker.retired());
+        assert!(matches!(
+            tracker.rearm(start + Duration::from_secs(20)),
+            Err(InternalError::InvalidTimedEventStatus)
+        ));
+    }
+}
+
+pub(super) mod instant {
+    use core::time::Duration;
+
+    use alloc::vec::Vec;
+    use jrinx_hal::{Cpu, Hal, Instant};
+    use jrinx_testdef::testdef;
+    use jrinx_trap::smp;
+
+    const SAMPLES: usize = 1000000;
+    const BUSY: Duration = Duration::from_millis(100);
+    const TOLERANCE: Duration = Duration::from_millis(5);
+
+    #[testdef]
+    fn test() {
+        let freq = hal!().cpu().timebase_freq();
+        assert_ne!(freq, 0);
+        let busy_ticks = freq * BUSY.as_millis() as u64 / 1000;
+
+        let begin = Instant::now();
+        let begin_ticks = hal!().cpu().get_ticks();
+        while hal!().cpu().get_ticks() - begin_ticks < busy_ticks {
+            core::hint::spin_loop();
+        }
+        let elapsed = begin.elapsed();
+        info!("busy loop of {:?} measured as {:?}", BUSY, elapsed);
+        assert!(elapsed >= BUSY && elapsed - BUSY < TOLERANCE);
+
+        let mut last = Instant::now();
+        for _ in 0..SAMPLES {
+            let now = Instant::now();
+            assert!(now >= last);
+            last = now;
+        }
+
+        let before = Instant::now();
+        let cpu_ids = (0..hal!().cpu().nproc_valid()).collect::<Vec<_>>();
+        let results = smp::call(&cpu_ids, Duration::from_secs(1), || {
+            Instant::now().as_nanos() as usize
+        })
+        .unwrap();
+        let after = Instant::now();
+        for (cpu_id, result) in results.iter() {
+            let Some(nanos) = result else {
+                warn!("cpu#{} did not respond to smp call", cpu_id);
+                continue;
+            };
+            let remote = Instant::from_nanos(nanos as u64);
+            assert!(before <= remote && remote <= after);
+        }
+
+        assert_eq!(Instant::ZERO - Instant::from_millis(1), Duration::ZERO);
+        assert_eq!(Instant::MAX + Duration::from_secs(1), Instant::MAX);
+        assert_eq!(
+            Instant::from_millis(3) - Duration::from_millis(5),
+            Instant::ZERO
+        );
+        assert_eq!(
+            Instant::from_micros(1500).checked_duration_since(Instant::from_millis(1)),
+            Some(Duration::from_micros(500))
+        );
+        assert_eq!(
+            Instant::from_millis(1).checked_sub(Duration::from_secs(1)),
+            None
+        );
+    }
+}
+
+pub(super) mod wallclock {
+    use core::{
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_hal::{Cpu, Hal, Interrupt};
+    use jrinx_testdef::testdef;
+
+    const EPOCH_OFFSET: Duration = Duration::from_secs(1_700_000_000);
+
+    static WALLCLOCK: AtomicU64 = AtomicU64::new(0);
+
+    fn read() -> Duration {
+        EPOCH_OFFSET + Duration::from_nanos(WALLCLOCK.load(Ordering::SeqCst))
+    }
+
+    #[testdef]
+    fn test() {
+        assert_eq!(jrinx_wallclock::anchor(), None);
+        assert_eq!(jrinx_wallclock::to_wallclock(Duration::ZERO), None);
+
+        let first = jrinx_wallclock::register(read).unwrap();
+        assert_eq!(first.wallclock, EPOCH_OFFSET);
+        assert_eq!(jrinx_wallclock::latest(), Some(first));
+
+        spin(Duration::from_millis(1));
+        // The wall clock slews to run twice as fast as the monotonic clock.
+        let elapsed = hal!().cpu().get_time() - first.monotonic;
+        WALLCLOCK.store(elapsed.as_nanos() as u64 * 2, Ordering::SeqCst);
+        let second = jrinx_wallclock::anchor().unwrap();
+
+        let midpoint = first.monotonic + (second.monotonic - first.monotonic) / 2;
+        let expected = first.wallclock + (second.wallclock - first.wallclock) / 2;
+        let mapped = jrinx_wallclock::to_wallclock(midpoint).unwrap();
+        assert!(mapped.max(expected) - mapped.min(expected) <= Duration::from_micros(1));
+
+        assert_eq!(
+            jrinx_wallclock::to_wallclock(first.monotonic).unwrap(),
+            first.wallclock
+        );
+        assert_eq!(
+            jrinx_wallclock::to_wallclock(second.monotonic + Duration::from_millis(1)).unwrap(),
+            second.wallclock + Duration::from_millis(1)
+        );
+        assert_eq!(
+            jrinx_wallclock::to_wallclock(first.monotonic - Duration::from_micros(1)).unwrap(),
+            first.wallclock - Duration::from_micros(1)
+        );
+
+        jrinx_wallclock::calibrate_every(Duration::from_millis(1));
+        while jrinx_wallclock::latest().unwrap().monotonic == second.monotonic {
+            hal!().interrupt().wait();
+        }
+    }
+
+    fn spin(duration: Duration) {
+        let begin = hal!().cpu().get_time();
+        while hal!().cpu().get_time() - begin < duration {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+pub(super) mod sntp {
+    use alloc::{string::ToString, sync::Arc, task::Wake};
+    use core::{
+        future::Future,
+        net::Ipv4Addr,
+        pin::pin,
+        task::{Context, Poll, Waker},
+        time::Duration,
+    };
+
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_testdef::testdef;
+    use jrinx_wallclock::sntp::{self, Packet, SntpError, Transport, PACKET_LEN};
+
+    const SERVER: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+    const EPOCH_OFFSET: Duration = Duration::from_secs(1_700_000_000);
+
+    /// A server whose clock is `skew` ahead of the monotonic clock, or silent if `None`.
+    struct FakeServer {
+        skew: Option<Duration>,
+        stratum: u8,
+    }
+
+    impl Transport for FakeServer {
+        async fn exchange(
+            &mut self,
+            server: Ipv4Addr,
+            request: &[u8; PACKET_LEN],
+            _timeout: Duration,
+        ) -> Result<[u8; PACKET_LEN], SntpError> {
+            assert_eq!(server, SERVER);
+            let request = Packet::decode(request)?;
+            let time = hal!().cpu().get_time() + self.skew.ok_or(SntpError::Timeout)?;
+            Ok(Packet {
+                leap: 0,
+                version: 4,
+                mode: 4,
+                stratum: self.stratum,
+                originate: request.transmit,
+                receive: time,
+                transmit: time,
+            }
+            .encode())
+        }
+    }
+
+    #[testdef]
+    fn test() {
+        let time = EPOCH_OFFSET + Duration::new(5, 123_456_789);
+        let decoded = Packet::decode(&Packet::request(time).encode()).unwrap();
+        assert!(within(decoded.transmit, time, Duration::ZERO));
+
+        assert_eq!(sntp::status().to_string(), "unsynchronized, monotonic only");
+        let mut server = FakeServer {
+            skew: None,
+            stratum: 2,
+        };
+        assert!(matches!(run(&mut server), Err(SntpError::Timeout)));
+        server.skew = Some(EPOCH_OFFSET);
+        server.stratum = 0;
+        assert!(matches!(run(&mut server), Err(SntpError::KissOfDeath)));
+        assert_eq!(sntp::status().synced_at, None);
+        let monotonic = hal!().cpu().get_time();
+        assert!(sntp::now() - monotonic < Duration::from_millis(1));
+
+        // The first sample is stepped.
+        server.stratum = 2;
+        let sample = run(&mut server).unwrap();
+        let status = sntp::status();
+        assert_eq!(status.slewing, 0);
+        assert!(within(
+            sntp::now(),
+            hal!().cpu().get_time() + EPOCH_OFFSET,
+            status.error
+        ));
+        assert!(jrinx_wallclock::latest().unwrap().wallclock >= EPOCH_OFFSET);
+        assert_eq!(sample.delay / 2, status.error);
+
+        // Later small offsets are slewed at no more than the slew rate.
+        server.skew = Some(EPOCH_OFFSET + Duration::from_millis(10));
+        run(&mut server).unwrap();
+        let status = sntp::status();
+        assert!(status.slewing > 9_000_000);
+        let begin = hal!().cpu().get_time();
+        while hal!().cpu().get_time() - begin < Duration::from_millis(2) {
+            core::hint::spin_loop();
+        }
+        let absorbed = status.slewing - sntp::status().slewing;
+        assert!(absorbed > 0);
+        let elapsed = (hal!().cpu().get_time() - begin).as_nanos() as i64;
+        assert!(absorbed <= elapsed * sntp::SLEW_PPM / 1_000_000 + 1);
+
+        // A lost reply leaves the clock alone.
+        let before = sntp::status();
+        server.skew = None;
+        assert!(matches!(run(&mut server), Err(SntpError::Timeout)));
+        assert_eq!(sntp::status().synced_at, before.synced_at);
+    }
+
+    fn within(a: Duration, b: Duration, error: Duration) -> bool {
+        a.max(b) - a.min(b) <= error + Duration::from_micros(1)
+    }
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn run(server: &mut FakeServer) -> Result<sntp::Sample, SntpError> {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut sync = pin!(sntp::sync(server, SERVER, sntp::QUERY_TIMEOUT));
+        loop {
+            if let Poll::Ready(ret) = sync.as_mut().poll(&mut cx) {
+                return ret;
+            }
+        }
+    }
+}
+
+pub(super) mod interval {
+    use core::{
+        sync::atomic::{AtomicU64, Ordering},
+        task::{Context, Poll, Wake, Waker},
+        time::Duration,
+    };
+
+    use alloc::{sync::Arc, vec::Vec};
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        spawn,
+        time::{interval, interval_at, Interval, MissedTickBehavior},
+    };
+    use jrinx_testdef::testdef;
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    static FAKE_NANOS: AtomicU64 = AtomicU64::new(0);
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn fake_now() -> Duration {
+        Duration::from_nanos(FAKE_NANOS.load(Ordering::SeqCst))
+    }
+
+    fn set_fake_ms(ms: u64) {
+        FAKE_NANOS.store(ms * 1_000_000, Ordering::SeqCst);
+    }
+
+    /// Polls `interval` at fake time `ms` until it is pending, returning the ticks it fired.
+    fn ticks_at(interval: &mut Interval, ms: u64) -> Vec<u64> {
+        set_fake_ms(ms);
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut ticks = Vec::new();
+        while let Poll::Ready(tick) = interval.poll_tick(&mut cx) {
+            ticks.push(tick.as_millis() as u64);
+        }
+        ticks
+    }
+
+    #[testdef]
+    fn test() {
+        let fake = |behavior| {
+            set_fake_ms(0);
+            let mut interval = interval_at(Duration::ZERO, PERIOD).with_clock(fake_now);
+            interval.set_missed_tick_behavior(behavior);
+            interval
+        };
+
+        let mut burst = fake(MissedTickBehavior::Burst);
+        assert_eq!(ticks_at(&mut burst, 0), [0]);
+        assert!(ticks_at(&mut burst, 9).is_empty());
+        assert_eq!(ticks_at(&mut burst, 35), [10, 20, 30]);
+        assert!(ticks_at(&mut burst, 39).is_empty());
+        assert_eq!(ticks_at(&mut burst, 40), [40]);
+
+        // A 100Hz loop blocked for 35ms sees a single tick, then keeps its phase.
+        let mut skip = fake(MissedTickBehavior::Skip);
+        assert_eq!(ticks_at(&mut skip, 0), [0]);
+        assert_eq!(ticks_at(&mut skip, 35), [10]);
+        assert!(ticks_at(&mut skip, 39).is_empty());
+        assert_eq!(ticks_at(&mut skip, 40), [40]);
+        assert_eq!(ticks_at(&mut skip, 50), [50]);
+
+        let mut delay = fake(MissedTickBehavior::Delay);
+        assert_eq!(ticks_at(&mut delay, 0), [0]);
+        assert_eq!(ticks_at(&mut delay, 35), [10]);
+        assert!(ticks_at(&mut delay, 44).is_empty());
+        assert_eq!(ticks_at(&mut delay, 45), [45]);
+        assert_eq!(ticks_at(&mut delay, 55), [55]);
+
+        spawn!(async {
+            const TICKS: u32 = 5;
+
+            let mut interval = interval(PERIOD);
+            let start = interval.tick().await;
+            for n in 1..TICKS {
+                let tick = interval.tick().await;
+                assert_eq!(tick, start + PERIOD * n);
+                assert!(hal!().cpu().get_time() >= tick);
+            }
+            info!("interval ticked {} times without drift", TICKS);
+        });
+    }
+}
+
+pub(super) mod timeout {
+    use core::time::Duration;
+
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        inspector::Inspector,
+        spawn,
+        time::{sleep, timeout, Elapsed},
+    };
+    use jrinx_testdef::testdef;
+
+    fn timed_events() -> usize {
+        Inspector::with_current(|is| is.resource_usage().timed_events).unwrap()
+    }
+
+    #[testdef]
+    fn test() {
+        spawn!(async {
+            let baseline = timed_events();
+
+            let begin = hal!().cpu().get_time();
+            let never = timeout(Duration::from_millis(10), core::future::pending::<()>()).await;
+            assert_eq!(never, Err(Elapsed));
+            assert!(hal!().cpu().get_time() - begin >= Duration::from_millis(10));
+            assert_eq!(timed_events(), baseline);
+
+            let begin = hal!().cpu().get_time();
+            let early = timeout(Duration::from_secs(10), async {
+                sleep(Duration::from_millis(5)).await;
+                42
+            })
+            .await;
+            assert_eq!(early, Ok(42));
+            assert!(hal!().cpu().get_time() - begin < Duration::from_secs(10));
+            assert_eq!(timed_events(), baseline);
+
+            let outer = timeout(
+                Duration::from_millis(10),
+                timeout(Duration::from_secs(10), core::future::pending::<()>()),
+            )
+            .await;
+            assert_eq!(outer, Err(Elapsed));
+            let inner = timeout(
+                Duration::from_secs(10),
+                timeout(Duration::from_millis(10), core::future::pending::<()>()),
+            )
+            .await;
+            assert_eq!(inner, Ok(Err(Elapsed)));
+            assert_eq!(timed_events(), baseline);
+
+            info!("timeouts left no timed event behind");
+        });
+    }
+}
+
+pub(super) mod sleep {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Wake, Waker},
+        time::Duration,
+    };
+
+    use alloc::{sync::Arc, vec::Vec};
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        spawn,
+        time::{sleep, sleep_until},
+    };
+    use jrinx_testdef::testdef;
+    use spin::Mutex;
+
+    const SLEEPS_MS: [u64; 3] = [20, 30, 10];
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[testdef]
+    fn test() {
+        static WOKEN: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(pin!(sleep(Duration::ZERO)).poll(&mut cx), Poll::Ready(()));
+        assert_eq!(
+            pin!(sleep_until(
+                hal!().cpu().get_time() - Duration::from_nanos(1)
+            ))
+            .poll(&mut cx),
+            Poll::Ready(())
+        );
+
+        spawn!(async {
+            let begin = hal!().cpu().get_time();
+            let handles: Vec<_> = SLEEPS_MS
+                .iter()
+                .map(|&ms| {
+                    spawn!(async move {
+                        sleep(Duration::from_millis(ms)).await;
+                        let elapsed = hal!().cpu().get_time() - begin;
+                        assert!(elapsed >= Duration::from_millis(ms));
+                        info!("woken after {:?}, slept {}ms", elapsed, ms);
+                        WOKEN.lock().push(ms);
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.await.unwrap();
+            }
+
+            let mut sorted = SLEEPS_MS;
+            sorted.sort();
+            assert_eq!(*WOKEN.lock(), sorted);
+        });
+    }
+}
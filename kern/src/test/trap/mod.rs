@@ -24,6 +24,565 @@ pub(super) mod breakpoint {
     }
 }
 
+pub(super) mod breakpoint_manager {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use jrinx_addr::VirtAddr;
+    use jrinx_error::InternalError;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::{
+        arch::Context,
+        breakpoint::{BreakpointId, MANAGER},
+        GenericContext,
+    };
+
+    static HITS: AtomicUsize = AtomicUsize::new(0);
+    static HIT_ADDR: AtomicUsize = AtomicUsize::new(0);
+
+    #[inline(never)]
+    fn traced(x: usize) -> usize {
+        x * 3 + 1
+    }
+
+    fn on_hit(_: BreakpointId, ctx: &mut Context) {
+        HITS.fetch_add(1, Ordering::SeqCst);
+        HIT_ADDR.store(ctx.pc(), Ordering::SeqCst);
+    }
+
+    #[testdef]
+    fn test() {
+        let traced: fn(usize) -> usize = core::hint::black_box(traced);
+        let addr = VirtAddr::new(traced as usize);
+
+        let id = MANAGER.set_with_callback(addr, on_hit).unwrap();
+        assert!(matches!(
+            MANAGER.set(addr),
+            Err(InternalError::DuplicateBreakpoint)
+        ));
+        assert!(matches!(
+            MANAGER.set(VirtAddr::new(&HITS as *const _ as usize)),
+            Err(InternalError::InvalidVirtAddr(_))
+        ));
+        assert!(matches!(
+            MANAGER.set(VirtAddr::new(0)),
+            Err(InternalError::InvalidVirtAddr(_))
+        ));
+
+        assert_eq!(traced(2), 7);
+        assert_eq!(HITS.load(Ordering::SeqCst), 1);
+        assert_eq!(HIT_ADDR.load(Ordering::SeqCst), addr.as_usize());
+
+        // Re-armed after being stepped over.
+        assert_eq!(traced(3), 10);
+        assert_eq!(HITS.load(Ordering::SeqCst), 2);
+
+        let list = MANAGER.list();
+        assert_eq!(list.len(), 1);
+        assert_eq!((list[0].id, list[0].addr, list[0].hits), (id, addr, 2));
+
+        MANAGER.clear(id).unwrap();
+        assert_eq!(traced(4), 13);
+        assert_eq!(HITS.load(Ordering::SeqCst), 2);
+        assert!(MANAGER.list().is_empty());
+        assert!(matches!(
+            MANAGER.clear(id),
+            Err(InternalError::InvalidBreakpointId)
+        ));
+    }
+}
+
+pub(super) mod trap_handler {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use jrinx_error::InternalError;
+    use jrinx_hal::Hal;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::{arch::Context, breakpoint, handler, GenericContext, TrapKind, TrapReason};
+
+    static HANDLED: AtomicUsize = AtomicUsize::new(0);
+    static PASSED: AtomicUsize = AtomicUsize::new(0);
+
+    fn handle(ctx: &mut Context, reason: TrapReason) -> bool {
+        assert_eq!(TrapKind::from(reason), TrapKind::Breakpoint);
+        HANDLED.fetch_add(1, Ordering::SeqCst);
+        ctx.pc_advance();
+        true
+    }
+
+    fn pass(_: &mut Context, _: TrapReason) -> bool {
+        PASSED.fetch_add(1, Ordering::SeqCst);
+        false
+    }
+
+    #[testdef]
+    fn test() {
+        let count = breakpoint::count();
+        let id = handler::register(TrapKind::Breakpoint, handle);
+        hal!().breakpoint();
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+        assert_eq!(breakpoint::count(), count);
+
+        // Handlers registered later come first, and may leave the trap to those before.
+        let pass_id = handler::register(TrapKind::Breakpoint, pass);
+        hal!().breakpoint();
+        assert_eq!(PASSED.load(Ordering::SeqCst), 1);
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 2);
+        assert_eq!(breakpoint::count(), count);
+
+        handler::unregister(id).unwrap();
+        hal!().breakpoint();
+        assert_eq!(PASSED.load(Ordering::SeqCst), 2);
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 2);
+        assert_eq!(breakpoint::count(), count + 1);
+
+        handler::unregister(pass_id).unwrap();
+        assert!(matches!(
+            handler::unregister(pass_id),
+            Err(InternalError::InvalidTrapHandlerId)
+        ));
+        hal!().breakpoint();
+        assert_eq!(PASSED.load(Ordering::SeqCst), 2);
+        assert_eq!(breakpoint::count(), count + 2);
+        info!("trap handlers dispatched in order");
+    }
+}
+
+pub(super) mod watchpoint {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use jrinx_addr::VirtAddr;
+    use jrinx_error::InternalError;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::watchpoint::{self, WatchKind};
+
+    static WATCHED: AtomicUsize = AtomicUsize::new(0);
+
+    #[testdef]
+    fn test() {
+        let addr = VirtAddr::new(WATCHED.as_ptr() as usize);
+        let len = core::mem::size_of::<usize>();
+
+        assert!(matches!(
+            watchpoint::set(addr + 1, len, WatchKind::Write),
+            Err(InternalError::InvalidVirtAddr(_))
+        ));
+
+        let id = match watchpoint::set(addr, len, WatchKind::Write) {
+            Err(InternalError::NotEnoughTriggers) => {
+                warn!("no triggers to watch data accesses with");
+                return;
+            }
+            result => result.unwrap(),
+        };
+
+        WATCHED.store(1, Ordering::SeqCst);
+        assert_eq!(WATCHED.load(Ordering::SeqCst), 1);
+        WATCHED.store(2, Ordering::SeqCst);
+
+        let list = watchpoint::list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(
+            (
+                list[0].id,
+                list[0].addr,
+                list[0].len,
+                list[0].kind,
+                list[0].hits
+            ),
+            (id, addr, len, WatchKind::Write, 2)
+        );
+
+        watchpoint::clear(id).unwrap();
+        WATCHED.store(3, Ordering::SeqCst);
+        assert!(watchpoint::list().is_empty());
+        assert!(matches!(
+            watchpoint::clear(id),
+            Err(InternalError::InvalidWatchpointId)
+        ));
+    }
+}
+
+pub(super) mod smp_call {
+    use core::time::Duration;
+
+    use alloc::vec::Vec;
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_testdef::testdef;
+    use jrinx_trap::smp;
+
+    #[testdef]
+    fn test() {
+        let local_id = hal!().cpu().id();
+        let cpu_ids = (0..hal!().cpu().nproc_valid()).collect::<Vec<_>>();
+
+        let results = smp::call(&cpu_ids, Duration::from_secs(1), || hal!().cpu().id()).unwrap();
+        assert_eq!(results.get(local_id), Some(local_id));
+        for (cpu_id, result) in results.iter() {
+            if let Some(result) = result {
+                assert_eq!(result, cpu_id);
+            } else {
+                warn!("cpu#{} did not respond to smp call", cpu_id);
+            }
+        }
+
+        let results = smp::call(&[local_id], Duration::from_secs(1), || {
+            let local_id = hal!().cpu().id();
+            match smp::call(&[local_id], Duration::from_secs(1), || 0) {
+                Err(InternalError::SmpCallNested) => 1,
+                _ => 0,
+            }
+        })
+        .unwrap();
+        assert_eq!(results.get(local_id), Some(1));
+
+        assert!(matches!(
+            smp::call(&[usize::MAX], Duration::from_secs(1), || 0),
+            Err(InternalError::InvalidCpuId(usize::MAX))
+        ));
+    }
+}
+
+pub(super) mod smp_call_async {
+    use core::{
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_error::InternalError;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_percpu::percpu;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::smp::{self, SMP_CALL_QUEUE_CAPACITY};
+
+    #[percpu]
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn wait_for(cond: impl Fn() -> bool) {
+        let deadline = hal!().cpu().get_time() + Duration::from_secs(1);
+        while !cond() {
+            assert!(hal!().cpu().get_time() < deadline);
+            core::hint::spin_loop();
+        }
+    }
+
+    #[testdef]
+    fn test() {
+        static BLOCKED: AtomicBool = AtomicBool::new(false);
+        static RELEASE: AtomicBool = AtomicBool::new(false);
+
+        let local_id = hal!().cpu().id();
+        let Some(remote_id) = (0..hal!().cpu().nproc_valid()).find(|&id| id != local_id) else {
+            warn!("no remote cpu to call");
+            return;
+        };
+        let count = |cpu_id| COUNTER.with_spec_ref(cpu_id, |c| c.load(Ordering::SeqCst));
+
+        smp::call_async(&[local_id, remote_id], || {
+            COUNTER.with_ref(|c| c.fetch_add(1, Ordering::SeqCst));
+        })
+        .unwrap();
+        assert_eq!(count(local_id), 1);
+        wait_for(|| count(remote_id) == 1);
+
+        // The remote CPU runs calls with interrupts off, so it drains nothing while blocked.
+        smp::call_async(&[remote_id], || {
+            BLOCKED.store(true, Ordering::SeqCst);
+            while !RELEASE.load(Ordering::SeqCst) {
+                core::hint::spin_loop();
+            }
+        })
+        .unwrap();
+        wait_for(|| BLOCKED.load(Ordering::SeqCst));
+
+        for _ in 0..SMP_CALL_QUEUE_CAPACITY {
+            smp::call_async(&[remote_id], || {
+                COUNTER.with_ref(|c| c.fetch_add(1, Ordering::SeqCst));
+            })
+            .unwrap();
+        }
+        assert!(matches!(
+            smp::call_async(&[remote_id], || {}),
+            Err(InternalError::SmpCallQueueFull)
+        ));
+        assert!(matches!(
+            smp::call(&[remote_id], Duration::from_secs(1), || 0),
+            Err(InternalError::SmpCallQueueFull)
+        ));
+
+        RELEASE.store(true, Ordering::SeqCst);
+        wait_for(|| count(remote_id) == 1 + SMP_CALL_QUEUE_CAPACITY);
+        assert_eq!(count(local_id), 1);
+    }
+}
+
+pub(super) mod irq_latency {
+    use core::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use alloc::vec::Vec;
+    use jrinx_hal::{Cpu, Hal, Interrupt};
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+    use jrinx_trap::{
+        latency::{self, IrqSource},
+        smp,
+    };
+
+    const SPIN: Duration = Duration::from_micros(500);
+
+    #[testdef(serial)]
+    fn test() {
+        static FIRED: AtomicBool = AtomicBool::new(false);
+
+        latency::reset();
+        let start = hal!().cpu().now();
+
+        TimedEvent::create(
+            (start + Duration::from_millis(10)).as_duration(),
+            TimedEventHandler::new(
+                || {
+                    let begin = hal!().cpu().get_time();
+                    while hal!().cpu().get_time() - begin < SPIN {
+                        core::hint::spin_loop();
+                    }
+                    FIRED.store(true, Ordering::SeqCst);
+                },
+                || {},
+            ),
+        );
+        while !FIRED.load(Ordering::SeqCst) {
+            hal!().interrupt().wait();
+        }
+
+        let timer = latency::get(IrqSource::TimerInterrupt);
+        assert!(timer.count() >= 1);
+        assert!(timer.max() >= SPIN);
+        assert!(timer.max_at() >= start);
+
+        // A software interrupt held pending while interrupts are off counts from when it was sent.
+        let local_id = hal!().cpu().id();
+        hal!().interrupt().with_saved_off(|| {
+            hal!().interrupt().send_ipi(&[local_id]);
+            let begin = hal!().cpu().get_time();
+            while hal!().cpu().get_time() - begin < SPIN {
+                core::hint::spin_loop();
+            }
+        });
+        assert!(latency::get(IrqSource::SoftwareInterrupt).max() >= SPIN);
+
+        let remote_ids = (0..hal!().cpu().nproc_valid())
+            .filter(|&cpu_id| cpu_id != local_id)
+            .collect::<Vec<_>>();
+        let results = smp::call(&remote_ids, Duration::from_secs(1), || 0).unwrap();
+        let responded = results.iter().filter(|(_, r)| r.is_some()).count() as u64;
+
+        // Remote CPUs complete the interrupt only after publishing the call result.
+        let deadline = hal!().cpu().get_time() + Duration::from_millis(100);
+        while latency::get(IrqSource::SoftwareInterrupt).count() < responded + 1 {
+            assert!(hal!().cpu().get_time() < deadline);
+            core::hint::spin_loop();
+        }
+
+        for source in IrqSource::ALL {
+            info!("{} latency: {}", source.name(), latency::get(source));
+        }
+    }
+}
+
+pub(super) mod int_nesting {
+    use core::{
+        hint::black_box,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_hal::{Cpu, Hal, Interrupt};
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+    use jrinx_trap::nest::{self, IrqClass};
+
+    const ITERATIONS: usize = 10000;
+    const INTERVAL: Duration = Duration::from_micros(20);
+    const NEST_TIMEOUT: Duration = Duration::from_millis(10);
+
+    static FIRED: AtomicUsize = AtomicUsize::new(0);
+    static MISSED: AtomicUsize = AtomicUsize::new(0);
+    static DONE: AtomicBool = AtomicBool::new(false);
+
+    #[inline(never)]
+    fn checksum(len: u64) -> u64 {
+        (0..len).fold(0, |acc, i| {
+            acc.rotate_left(5) ^ i.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        })
+    }
+
+    fn arm() {
+        TimedEvent::create(
+            (hal!().cpu().now() + INTERVAL).as_duration(),
+            TimedEventHandler::new(fire, || {}),
+        );
+    }
+
+    /// Sends a software interrupt to the current CPU, which must nest in this timer handler.
+    fn fire() {
+        let before = checksum(black_box(64));
+        let nested = nest::nested_count();
+
+        hal!().interrupt().send_ipi(&[hal!().cpu().id()]);
+        let deadline = hal!().cpu().get_time() + NEST_TIMEOUT;
+        while nest::nested_count() == nested {
+            if hal!().cpu().get_time() >= deadline {
+                MISSED.fetch_add(1, Ordering::SeqCst);
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        assert_eq!(checksum(black_box(64)), before);
+
+        if FIRED.fetch_add(1, Ordering::SeqCst) + 1 < ITERATIONS {
+            arm();
+        } else {
+            DONE.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[testdef]
+    fn test() {
+        let priority = nest::priority(IrqClass::Software);
+        nest::set_priority(IrqClass::Software, nest::priority(IrqClass::Timer) + 1);
+
+        // The registers of the interrupted task must come back intact through nested frames.
+        let expected = checksum(black_box(256));
+        arm();
+        while !DONE.load(Ordering::SeqCst) {
+            assert_eq!(checksum(black_box(256)), expected);
+        }
+
+        nest::set_priority(IrqClass::Software, priority);
+
+        assert_eq!(MISSED.load(Ordering::SeqCst), 0);
+        assert!(nest::nested_count() >= ITERATIONS as u64);
+        assert!(nest::max_depth() >= 2);
+        assert_eq!(nest::depth(), 0);
+    }
+}
+
+pub(super) mod trap_depth {
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use jrinx_hal::Hal;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::{arch::Context, handler, nest, GenericContext, TrapKind, TrapReason};
+
+    static OUTER: AtomicUsize = AtomicUsize::new(0);
+    static INNER: AtomicUsize = AtomicUsize::new(0);
+    static NESTED: AtomicBool = AtomicBool::new(false);
+
+    /// Takes another breakpoint from within the handler of the first.
+    fn handle(ctx: &mut Context, _: TrapReason) -> bool {
+        if nest::trap_depth() == 1 {
+            OUTER.store(nest::trap_depth(), Ordering::SeqCst);
+            hal!().breakpoint();
+        } else {
+            INNER.store(nest::trap_depth(), Ordering::SeqCst);
+            NESTED.store(nest::in_nested_trap(), Ordering::SeqCst);
+        }
+        ctx.pc_advance();
+        true
+    }
+
+    #[testdef]
+    fn test() {
+        assert_eq!(nest::trap_depth(), 0);
+        assert!(!nest::in_nested_trap());
+
+        let id = handler::register(TrapKind::Breakpoint, handle);
+        hal!().breakpoint();
+        handler::unregister(id).unwrap();
+
+        assert_eq!(OUTER.load(Ordering::SeqCst), 1);
+        assert_eq!(INNER.load(Ordering::SeqCst), 2);
+        assert!(NESTED.load(Ordering::SeqCst));
+        assert_eq!(nest::trap_depth(), 0);
+        info!("nested trap handled at depth 2");
+    }
+}
+
+pub(super) mod user_trap_depth {
+    use core::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use jrinx_addr::VirtAddr;
+    use jrinx_hal::{Cpu, Hal, Interrupt};
+    use jrinx_paging::{GenericPagePerm, PagePerm};
+    use jrinx_testdef::testdef;
+    use jrinx_trap::{arch::Context, handler, nest, GenericContext, TrapKind, TrapReason};
+
+    const IPI_TIMEOUT: Duration = Duration::from_millis(10);
+
+    static CPU: AtomicUsize = AtomicUsize::new(usize::MAX);
+    static FAULT_DEPTH: AtomicUsize = AtomicUsize::new(0);
+    static INT_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+    /// Takes a software interrupt from within the handler of a page fault from user mode.
+    fn handle_fault(_: &mut Context, _: TrapReason) -> bool {
+        FAULT_DEPTH.store(nest::trap_depth(), Ordering::SeqCst);
+        CPU.store(hal!().cpu().id(), Ordering::SeqCst);
+        hal!().interrupt().with_saved_on(|| {
+            hal!().interrupt().send_ipi(&[hal!().cpu().id()]);
+            let deadline = hal!().cpu().get_time() + IPI_TIMEOUT;
+            while INT_DEPTH.load(Ordering::SeqCst) == 0 && hal!().cpu().get_time() < deadline {
+                core::hint::spin_loop();
+            }
+        });
+        true
+    }
+
+    fn handle_int(_: &mut Context, _: TrapReason) -> bool {
+        if hal!().cpu().id() != CPU.load(Ordering::SeqCst) {
+            return false;
+        }
+        INT_DEPTH.store(nest::trap_depth(), Ordering::SeqCst);
+        false
+    }
+
+    #[testdef]
+    fn test() {
+        let nullptr_reader = jrinx_uprog::find("test/kern/nullptr-reader").unwrap();
+        super::load_elf(nullptr_reader);
+
+        let mut ctx = Context::default();
+        ctx.user_setup(nullptr_reader.ehdr.e_entry as usize, 0);
+        ctx.disable_int();
+        ctx.run();
+        let reason = ctx.trap_reason();
+        assert_eq!(
+            reason,
+            TrapReason::PageFault {
+                addr: VirtAddr::new(0),
+                perm: PagePerm::R,
+            }
+        );
+
+        let fault = handler::register(TrapKind::PageFault, handle_fault);
+        let int = handler::register(TrapKind::SoftwareInterrupt, handle_int);
+        // As the trap loop of processes handles the traps of their context.
+        assert!(nest::trap(|| handler::dispatch(&mut ctx, reason)));
+        handler::unregister(int).unwrap();
+        handler::unregister(fault).unwrap();
+
+        assert_eq!(FAULT_DEPTH.load(Ordering::SeqCst), 1);
+        assert_eq!(INT_DEPTH.load(Ordering::SeqCst), 2);
+        assert_eq!(nest::trap_depth(), 0);
+        info!("interrupt in user page fault handled at depth 2");
+    }
+}
+
 pub(super) mod page_fault {
     use jrinx_addr::VirtAddr;
     use jrinx_paging::{GenericPagePerm, PagePerm};
@@ -77,6 +636,89 @@ pub(super) mod page_fault {
     }
 }
 
+pub(super) mod illegal_instruction {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+    use jrinx_trap::fault;
+
+    static REACHED: AtomicBool = AtomicBool::new(false);
+    static FOLLOWED: AtomicBool = AtomicBool::new(false);
+
+    async fn faulting() {
+        unsafe { core::arch::asm!("unimp") };
+        REACHED.store(true, Ordering::SeqCst);
+    }
+
+    #[testdef]
+    fn test() {
+        let faults = fault::count();
+
+        let mut executor = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(faulting(), TaskPriority::default()),
+        );
+        executor
+            .spawn(Task::new(
+                async { FOLLOWED.store(true, Ordering::SeqCst) },
+                TaskPriority::default(),
+            ))
+            .unwrap();
+        Inspector::with_current(|is| is.register(executor).unwrap()).unwrap();
+
+        // The executor goes on with its next task once the faulting one is aborted.
+        while !FOLLOWED.load(Ordering::SeqCst) {
+            Runtime::switch_yield();
+        }
+        assert!(!REACHED.load(Ordering::SeqCst));
+        assert_eq!(fault::count(), faults + 1);
+        info!("faulting task aborted, kernel still running");
+    }
+}
+
+pub(super) mod stack_overflow {
+    use jrinx_multitask::{
+        executor::{Executor, ExecutorPriority},
+        inspector::Inspector,
+        runtime::Runtime,
+        Task, TaskPriority,
+    };
+    use jrinx_testdef::testdef;
+
+    #[inline(never)]
+    fn recurse(depth: usize) -> usize {
+        let frame = core::hint::black_box([depth; 64]);
+        if depth == usize::MAX {
+            return 0;
+        }
+        recurse(depth + 1) + frame[depth % frame.len()]
+    }
+
+    #[testdef]
+    fn test() {
+        let executor = Executor::new(
+            ExecutorPriority::default(),
+            Task::new(
+                async { info!("recursion returned {}", recurse(0)) },
+                TaskPriority::default(),
+            )
+            .with_name("recursing"),
+        );
+        Inspector::with_current(|is| is.register(executor).unwrap()).unwrap();
+
+        // The kernel halts once the task runs past its stack.
+        loop {
+            Runtime::switch_yield();
+        }
+    }
+}
+
 pub(super) mod syscall {
     use jrinx_testdef::testdef;
     use jrinx_trap::{arch::Context, GenericContext, TrapReason};
@@ -98,6 +740,475 @@ pub(super) mod syscall {
     }
 }
 
+#[cfg(feature = "trap-stats")]
+pub(super) mod trap_stats {
+    use jrinx_hal::Hal;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::{
+        arch::Context,
+        stats::{self, TrapKind},
+        GenericContext, TrapReason,
+    };
+
+    const BREAKPOINTS: u64 = 7;
+    const SYSCALLS: u64 = 5;
+
+    fn total(kind: TrapKind) -> u64 {
+        stats::snapshot_all()
+            .iter()
+            .map(|stats| stats.get(kind))
+            .sum()
+    }
+
+    #[testdef]
+    fn test() {
+        let system_caller = jrinx_uprog::find("test/kern/system-caller").unwrap();
+        let system_caller_entry = system_caller.ehdr.e_entry as usize;
+
+        super::load_elf(system_caller);
+
+        let breakpoints = total(TrapKind::Breakpoint);
+        let syscalls = total(TrapKind::SystemCall);
+
+        for _ in 0..BREAKPOINTS {
+            hal!().breakpoint();
+        }
+        for _ in 0..SYSCALLS {
+            let mut ctx = Context::default();
+            ctx.user_setup(system_caller_entry, 0);
+            ctx.disable_int();
+            ctx.run();
+            assert_eq!(ctx.trap_reason(), TrapReason::SystemCall);
+        }
+
+        assert_eq!(total(TrapKind::Breakpoint) - breakpoints, BREAKPOINTS);
+        assert_eq!(total(TrapKind::SystemCall) - syscalls, SYSCALLS);
+        info!("{}", stats::snapshot(0));
+    }
+}
+
+pub(super) mod fatal_trap {
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        info!("fatal trap: writing through a null pointer");
+        // The kernel halts on a page fault of its own code no handler resolves.
+        unsafe { core::arch::asm!("sw zero, 0({})", in(reg) core::hint::black_box(0usize)) };
+        info!("fatal trap: write returned");
+    }
+}
+
+pub(super) mod gdb {
+    use alloc::{format, vec::Vec};
+
+    use jrinx_addr::VirtAddr;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::{
+        arch::Context,
+        breakpoint::MANAGER,
+        gdb::{
+            packet::{self, Decoder, Event},
+            Response, Stub,
+        },
+        GenericContext,
+    };
+
+    /// Bytes gdb sent on attaching, with a line of the log, a packet sent again on a `-` and a
+    /// corrupted packet mixed in.
+    const RECORDED: &[u8] = b"+$qSupported:multiprocess+;swbreak+;hwbreak+;qRelocInsn+;\
+        fork-events+;vfork-events+;exec-events+;vContSupported+;QThreadEvents+;no-resumed+#df\
+        +$vMustReplyEmpty#3a+$Hg0#df[  0.104200 cpu#0] log line\r\n+$qAttached#8f-$?#3f\
+        $g#00$g#67+$a}]b#9d+\x03";
+
+    #[inline(never)]
+    fn traced(x: usize) -> usize {
+        x * 5 + 2
+    }
+
+    fn reply(stub: &mut Stub, ctx: &mut Context, packet: &[u8]) -> Vec<u8> {
+        match stub.respond(ctx, packet) {
+            Response::Reply(reply) => reply,
+            response => panic!(
+                "unexpected response to {:?}: {:?}",
+                core::str::from_utf8(packet),
+                response
+            ),
+        }
+    }
+
+    #[testdef]
+    fn test() {
+        let mut decoder = Decoder::new();
+        let events = RECORDED
+            .iter()
+            .filter_map(|&byte| decoder.feed(byte))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                Event::Ack,
+                Event::Packet(
+                    b"qSupported:multiprocess+;swbreak+;hwbreak+;qRelocInsn+;fork-events+;\
+                    vfork-events+;exec-events+;vContSupported+;QThreadEvents+;no-resumed+"
+                        .to_vec()
+                ),
+                Event::Ack,
+                Event::Packet(b"vMustReplyEmpty".to_vec()),
+                Event::Ack,
+                Event::Packet(b"Hg0".to_vec()),
+                Event::Ack,
+                Event::Packet(b"qAttached".to_vec()),
+                Event::Nack,
+                Event::Packet(b"?".to_vec()),
+                Event::BadPacket,
+                Event::Packet(b"g".to_vec()),
+                Event::Ack,
+                Event::Packet(b"a}b".to_vec()),
+                Event::Ack,
+                Event::Interrupt,
+            ]
+        );
+        assert_eq!(packet::checksum(b"S05"), 0xb8);
+        assert_eq!(packet::encode(b"OK"), b"$OK#9a");
+        assert_eq!(packet::encode(b"a}b"), b"$a}]b#9d");
+        info!("gdb: recorded traffic decoded");
+
+        let mut stub = Stub::new();
+        let mut ctx = Context::default();
+        assert_eq!(
+            reply(&mut stub, &mut ctx, b"qSupported:swbreak+"),
+            b"PacketSize=1000"
+        );
+        assert!(reply(&mut stub, &mut ctx, b"vMustReplyEmpty").is_empty());
+        assert_eq!(reply(&mut stub, &mut ctx, b"Hg0"), b"OK");
+        assert_eq!(reply(&mut stub, &mut ctx, b"qAttached"), b"1");
+        assert_eq!(reply(&mut stub, &mut ctx, b"?"), b"S05");
+
+        // Registers are written in target byte order, the pc numbered right after the GPRs.
+        let traced: fn(usize) -> usize = core::hint::black_box(traced);
+        let pc = traced as usize;
+        let mut hex = Vec::new();
+        packet::push_hex(&mut hex, &pc.to_le_bytes());
+        let pc_num = Context::GPR_COUNT;
+        let set_pc = [format!("P{:x}=", pc_num).as_bytes(), &hex].concat();
+        assert_eq!(reply(&mut stub, &mut ctx, &set_pc), b"OK");
+        assert_eq!(ctx.pc(), pc);
+        assert_eq!(
+            reply(&mut stub, &mut ctx, format!("p{:x}", pc_num).as_bytes()),
+            hex
+        );
+        let set_zero = [b"P0=".as_slice(), &hex].concat();
+        assert_eq!(reply(&mut stub, &mut ctx, &set_zero), b"OK");
+        assert_eq!(ctx.gpr(0), 0);
+        assert!(reply(&mut stub, &mut ctx, b"p41")
+            .iter()
+            .all(|&byte| byte == b'x'));
+
+        let regs = reply(&mut stub, &mut ctx, b"g");
+        assert_eq!(regs.len(), (pc_num + 1) * core::mem::size_of::<usize>() * 2);
+        assert!(regs.ends_with(&hex));
+        let mut other = Context::default();
+        assert_eq!(
+            reply(&mut stub, &mut other, &[b"G".as_slice(), &regs].concat()),
+            b"OK"
+        );
+        assert_eq!(other.pc(), pc);
+        assert_eq!(reply(&mut stub, &mut other, b"g"), regs);
+        info!("gdb: registers read and written");
+
+        let mut bytes = [0xde, 0xad, 0xbe, 0xef];
+        let addr = core::hint::black_box(bytes.as_mut_ptr()) as usize;
+        assert_eq!(
+            reply(&mut stub, &mut ctx, format!("m{:x},4", addr).as_bytes()),
+            b"deadbeef"
+        );
+        assert_eq!(
+            reply(
+                &mut stub,
+                &mut ctx,
+                format!("M{:x},2:cafe", addr).as_bytes()
+            ),
+            b"OK"
+        );
+        assert_eq!(
+            unsafe { (addr as *const [u8; 4]).read_volatile() },
+            [0xca, 0xfe, 0xbe, 0xef]
+        );
+        assert_eq!(reply(&mut stub, &mut ctx, b"m0,4"), b"E0e");
+        assert_eq!(reply(&mut stub, &mut ctx, b"M0,1:00"), b"E0e");
+        assert_eq!(reply(&mut stub, &mut ctx, b"mzz,4"), b"E16");
+        assert_eq!(
+            reply(&mut stub, &mut ctx, format!("M{:x},2:ca", addr).as_bytes()),
+            b"E16"
+        );
+        info!("gdb: memory read and written");
+
+        let z0 = format!("0,{:x},2", pc);
+        assert_eq!(
+            reply(&mut stub, &mut ctx, format!("Z{}", z0).as_bytes()),
+            b"OK"
+        );
+        let armed = |hits| {
+            MANAGER
+                .list()
+                .iter()
+                .any(|bp| bp.addr == VirtAddr::new(pc) && bp.hits == hits)
+        };
+        assert!(armed(0));
+        // With the stub not enabled, a hit does not stop.
+        assert_eq!(traced(1), 7);
+        assert!(armed(1));
+        assert_eq!(
+            reply(&mut stub, &mut ctx, format!("z{}", z0).as_bytes()),
+            b"OK"
+        );
+        assert!(MANAGER.list().iter().all(|bp| bp.addr != VirtAddr::new(pc)));
+        assert_eq!(
+            reply(&mut stub, &mut ctx, format!("z{}", z0).as_bytes()),
+            b"E16"
+        );
+        assert!(reply(&mut stub, &mut ctx, format!("Z1,{:x},2", pc).as_bytes()).is_empty());
+        info!("gdb: breakpoints set and cleared");
+
+        assert_eq!(stub.respond(&mut ctx, b"c"), Response::Continue);
+        assert_eq!(stub.respond(&mut ctx, b"s"), Response::Step);
+        assert_eq!(
+            stub.respond(&mut ctx, format!("c{:x}", pc + 4).as_bytes()),
+            Response::Continue
+        );
+        assert_eq!(ctx.pc(), pc + 4);
+        assert_eq!(
+            stub.respond(&mut ctx, b"D"),
+            Response::Detach(Some(b"OK".to_vec()))
+        );
+        info!("gdb: packets served");
+    }
+}
+
+pub(super) mod fp_state {
+    use jrinx_testdef::testdef;
+    use jrinx_trap::{arch::Context, fp, GenericContext, TrapReason};
+
+    fn run_to_syscall(ctx: &mut Context) -> usize {
+        ctx.run();
+        if let TrapReason::IllegalInstruction { .. } = ctx.trap_reason() {
+            assert!(fp::handle_first_use(ctx));
+            ctx.run();
+        }
+        assert_eq!(ctx.trap_reason(), TrapReason::SystemCall);
+        ctx.pc_advance();
+        ctx.syscall_num()
+    }
+
+    #[testdef]
+    fn test() {
+        let fp_worker = jrinx_uprog::find("test/kern/fp-worker").unwrap();
+        let fp_worker_entry = fp_worker.ehdr.e_entry as usize;
+
+        super::load_elf(fp_worker);
+
+        let mut contexts = [3, 5].map(|seed| {
+            let mut ctx = Context::default();
+            ctx.user_setup(fp_worker_entry, 0);
+            ctx.disable_int();
+            ctx.syscall_ret(seed);
+            ctx
+        });
+        assert!(contexts.iter_mut().all(|ctx| ctx.fp_state().is_none()));
+
+        // Both contexts run in turn between their floating-point steps, so that each one finds
+        // the registers left by the other.
+        for _ in 0..2 {
+            for ctx in contexts.iter_mut() {
+                assert_eq!(run_to_syscall(ctx), 0xF10A);
+            }
+        }
+        assert!(contexts.iter_mut().all(|ctx| ctx.fp_state().is_some()));
+
+        let results = contexts.map(|mut ctx| {
+            assert_eq!(run_to_syscall(&mut ctx), 0xC0DE);
+            ctx.syscall_args()[0]
+        });
+        assert_eq!(results, [6, 10]);
+    }
+}
+
+pub(super) mod log_ring {
+    use alloc::vec::Vec;
+
+    use jrinx_a653::{
+        partition::{Partition, PartitionConfig, PartitionTypeConfig},
+        process::{Process, ProcessRunner},
+    };
+    use jrinx_abi::cap::Capabilities;
+    use jrinx_apex::APEX_TIME_INFINITY;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        inspector::{Inspector, ResourceLimits},
+        runtime::Runtime,
+    };
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        let partition = Partition::new(&PartitionConfig {
+            name: "log-ring".try_into().unwrap(),
+            memory: 0x100000,
+            period: APEX_TIME_INFINITY,
+            duration: APEX_TIME_INFINITY,
+            num_cores: 1,
+            limits: ResourceLimits::default(),
+            capabilities: Capabilities::all(),
+            lowmem_action: None,
+            semaphores: Vec::new(),
+            partition_type: PartitionTypeConfig::User(
+                jrinx_uprog::find("test/user/log-ring").unwrap(),
+            ),
+        })
+        .unwrap();
+        partition.assign_core(hal!().cpu().id() as _).unwrap();
+
+        let inspector = partition.gen_inspector().unwrap();
+        let process = Process::new_init(partition.identifier()).unwrap();
+        inspector
+            .register(
+                process
+                    .gen_executor(ProcessRunner {
+                        syscall: jrinx_syscall::handle,
+                    })
+                    .unwrap(),
+            )
+            .unwrap();
+
+        Runtime::with_current(|rt| rt.register(inspector).unwrap());
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+    }
+}
+
+pub(super) mod capability {
+    use alloc::vec::Vec;
+
+    use jrinx_a653::{
+        partition::{Partition, PartitionConfig, PartitionTypeConfig},
+        process::{Process, ProcessRunner},
+    };
+    use jrinx_abi::cap::Capabilities;
+    use jrinx_apex::APEX_TIME_INFINITY;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        inspector::{Inspector, ResourceLimits},
+        runtime::Runtime,
+    };
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        let partition = Partition::new(&PartitionConfig {
+            name: "capability".try_into().unwrap(),
+            memory: 0x100000,
+            period: APEX_TIME_INFINITY,
+            duration: APEX_TIME_INFINITY,
+            num_cores: 1,
+            limits: ResourceLimits::default(),
+            capabilities: Capabilities::LOG
+                | Capabilities::LOG_RING
+                | Capabilities::HALT
+                | Capabilities::PARTITION_MODE
+                | Capabilities::PROCESS_MANAGE,
+            lowmem_action: None,
+            semaphores: Vec::new(),
+            partition_type: PartitionTypeConfig::User(
+                jrinx_uprog::find("test/user/capability").unwrap(),
+            ),
+        })
+        .unwrap();
+        partition.assign_core(hal!().cpu().id() as _).unwrap();
+
+        let inspector = partition.gen_inspector().unwrap();
+        let process = Process::new_init(partition.identifier()).unwrap();
+        inspector
+            .register(
+                process
+                    .gen_executor(ProcessRunner {
+                        syscall: jrinx_syscall::handle,
+                    })
+                    .unwrap(),
+            )
+            .unwrap();
+
+        Runtime::with_current(|rt| rt.register(inspector).unwrap());
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+    }
+}
+
+pub(super) mod rseq {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    use jrinx_a653::{
+        partition::{Partition, PartitionConfig, PartitionTypeConfig},
+        process::{Process, ProcessRunner},
+    };
+    use jrinx_abi::cap::Capabilities;
+    use jrinx_apex::APEX_TIME_INFINITY;
+    use jrinx_hal::{Cpu, Hal};
+    use jrinx_multitask::{
+        inspector::{Inspector, ResourceLimits},
+        runtime::Runtime,
+    };
+    use jrinx_testdef::testdef;
+    use jrinx_timed_event::{TimedEvent, TimedEventHandler};
+
+    const PREEMPT_PERIOD: Duration = Duration::from_micros(50);
+
+    /// Keeps interrupting the process, so that some of its critical sections get preempted.
+    fn preempt(time: Duration) {
+        TimedEvent::create(
+            time,
+            TimedEventHandler::new(move || preempt(time + PREEMPT_PERIOD), || {}),
+        );
+    }
+
+    #[testdef]
+    fn test() {
+        let partition = Partition::new(&PartitionConfig {
+            name: "rseq".try_into().unwrap(),
+            memory: 0x100000,
+            period: APEX_TIME_INFINITY,
+            duration: APEX_TIME_INFINITY,
+            num_cores: 1,
+            limits: ResourceLimits::default(),
+            capabilities: Capabilities::LOG | Capabilities::HALT,
+            lowmem_action: None,
+            semaphores: Vec::new(),
+            partition_type: PartitionTypeConfig::User(jrinx_uprog::find("test/user/rseq").unwrap()),
+        })
+        .unwrap();
+        partition.assign_core(hal!().cpu().id() as _).unwrap();
+
+        let inspector = partition.gen_inspector().unwrap();
+        let process = Process::new_init(partition.identifier()).unwrap();
+        inspector
+            .register(
+                process
+                    .gen_executor(ProcessRunner {
+                        syscall: jrinx_syscall::handle,
+                    })
+                    .unwrap(),
+            )
+            .unwrap();
+
+        preempt(hal!().cpu().get_time() + PREEMPT_PERIOD);
+
+        Runtime::with_current(|rt| rt.register(inspector).unwrap());
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+    }
+}
+
 fn load_elf(elf: ElfBytes<'_, AnyEndian>) {
     ElfLoader::new(&elf)
         .load(|elf, phdr, vaddr, offst, len| {
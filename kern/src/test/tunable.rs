@@ -0,0 +1,96 @@
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use alloc::{format, vec::Vec};
+use jrinx_a653::lowmem::{self, LowMemAction};
+use jrinx_hal::{Cpu, Hal};
+use jrinx_testdef::testdef;
+use jrinx_trap::smp;
+use jrinx_tunable::TunableError;
+
+const KEY: &str = "test.threshold";
+const DEFAULT: u64 = 100;
+
+static APPLIED: AtomicU64 = AtomicU64::new(DEFAULT);
+
+#[testdef]
+fn test() {
+    jrinx_tunable::register(
+        KEY,
+        DEFAULT,
+        |&value| {
+            if (1..=1000).contains(&value) {
+                Ok(())
+            } else {
+                Err(format!("{} is not within 1..=1000", value))
+            }
+        },
+        |&value| APPLIED.store(value, Ordering::SeqCst),
+    )
+    .unwrap();
+    assert!(matches!(
+        jrinx_tunable::register(KEY, 0u64, |_| Ok(()), |_| {}),
+        Err(TunableError::DuplicateKey)
+    ));
+
+    jrinx_tunable::set(KEY, "200").unwrap();
+    assert_eq!(APPLIED.load(Ordering::SeqCst), 200);
+    assert_eq!(jrinx_tunable::get(KEY).unwrap(), "200");
+    assert_eq!(jrinx_tunable::get_value::<u64>(KEY).unwrap(), 200);
+
+    let err = jrinx_tunable::set(KEY, "2000").unwrap_err();
+    assert_eq!(
+        err,
+        TunableError::Rejected("2000 is not within 1..=1000".into())
+    );
+    info!("{}={} {}", KEY, 2000, err);
+    assert!(matches!(
+        jrinx_tunable::set(KEY, "ten"),
+        Err(TunableError::Malformed("u64"))
+    ));
+    assert!(matches!(
+        jrinx_tunable::set_value(KEY, true),
+        Err(TunableError::Malformed("bool"))
+    ));
+    assert!(matches!(
+        jrinx_tunable::set("test.missing", "1"),
+        Err(TunableError::UnknownKey)
+    ));
+    assert_eq!(APPLIED.load(Ordering::SeqCst), 200);
+
+    // Concurrent changes are applied in the order they are stored.
+    let cpu_ids = (0..hal!().cpu().nproc_valid()).collect::<Vec<_>>();
+    smp::call(&cpu_ids, Duration::from_secs(1), || {
+        let value = hal!().cpu().id() as u64 + 1;
+        for _ in 0..64 {
+            jrinx_tunable::set_value(KEY, value).unwrap();
+        }
+        0
+    })
+    .unwrap();
+    assert_eq!(
+        jrinx_tunable::get_value::<u64>(KEY).unwrap(),
+        APPLIED.load(Ordering::SeqCst)
+    );
+
+    jrinx_tunable::set("lowmem.policy", "none").unwrap();
+    assert_eq!(lowmem::policy(), None);
+    jrinx_tunable::set("lowmem.policy", "fence").unwrap();
+    assert_eq!(lowmem::policy(), Some(LowMemAction::Fence));
+    jrinx_tunable::set("lowmem.policy", "report").unwrap();
+
+    let level = log::max_level();
+    jrinx_tunable::set("log.level", "error").unwrap();
+    assert_eq!(log::max_level(), log::LevelFilter::Error);
+    jrinx_tunable::set_value("log.level", level).unwrap();
+
+    let tunables = jrinx_tunable::list();
+    for key in ["log.level", "lowmem.policy", "syscall.strict_budgets", KEY] {
+        assert!(tunables.iter().any(|tunable| tunable.key == key));
+    }
+    for tunable in tunables {
+        info!("{}", tunable);
+    }
+}
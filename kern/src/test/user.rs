@@ -0,0 +1,213 @@
+//! User programs run as kernel tests, end to end through the system call layer.
+//!
+//! Each program runs as the initial process of a partition named after it, so its log lines
+//! carry the name of the test. A program passes by exiting with code zero, see
+//! `sys_debug_exit`.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use jrinx_a653::{
+    partition::{Partition, PartitionConfig, PartitionTypeConfig},
+    process::{Process, ProcessExit, ProcessRunner},
+};
+use jrinx_abi::cap::Capabilities;
+use jrinx_apex::APEX_TIME_INFINITY;
+use jrinx_hal::{Cpu, Hal};
+use jrinx_multitask::{
+    inspector::{Inspector, ResourceLimits},
+    runtime::Runtime,
+};
+
+/// Runs the user program `slug` and waits until its process exits.
+pub(super) fn run_user(slug: &str) -> ProcessExit {
+    run_in(&new_partition(slug))
+}
+
+/// Creates a partition for the user program `slug`, named after it.
+fn new_partition(slug: &str) -> Arc<Partition> {
+    let name = slug.rsplit('/').next().unwrap();
+    Partition::new(&PartitionConfig {
+        name: name.try_into().unwrap(),
+        memory: 0x100000,
+        period: APEX_TIME_INFINITY,
+        duration: APEX_TIME_INFINITY,
+        num_cores: 1,
+        limits: ResourceLimits::default(),
+        capabilities: Capabilities::all() - Capabilities::HALT,
+        lowmem_action: None,
+        semaphores: Vec::new(),
+        partition_type: PartitionTypeConfig::User(jrinx_uprog::find(slug).unwrap()),
+    })
+    .unwrap()
+}
+
+/// Runs the initial process of `partition` and waits until it exits.
+fn run_in(partition: &Arc<Partition>) -> ProcessExit {
+    partition.assign_core(hal!().cpu().id() as _).unwrap();
+
+    let inspector = partition.gen_inspector().unwrap();
+    let process = Process::new_init(partition.identifier()).unwrap();
+    process.set_strace(crate::bootargs::strace());
+    inspector
+        .register(
+            process
+                .gen_executor(ProcessRunner {
+                    syscall: jrinx_syscall::handle,
+                })
+                .unwrap(),
+        )
+        .unwrap();
+    Runtime::with_current(|rt| rt.register(inspector).unwrap());
+
+    loop {
+        if let Some(exit) = process.exit_status() {
+            return exit;
+        }
+        Inspector::with_current(|is| is.mark_pending().unwrap()).unwrap();
+        Runtime::switch_yield();
+    }
+}
+
+/// Runs the user program `slug`, failing unless it exits with code zero.
+pub(super) fn run_user_test(slug: &str) {
+    match run_user(slug) {
+        ProcessExit::Exited(0) => info!("user test {} passed", slug),
+        exit => panic!("user test {} {}", slug, exit),
+    }
+}
+
+pub(super) mod exit {
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        super::run_user_test("test/user/exit");
+    }
+}
+
+pub(super) mod trap_handler {
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        super::run_user_test("test/user/trap-handler");
+    }
+}
+
+pub(super) mod syscall_table {
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        super::run_user_test("test/user/syscall-table");
+    }
+}
+
+pub(super) mod nanosleep {
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        super::run_user_test("test/user/nanosleep");
+    }
+}
+
+pub(super) mod strace {
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        super::run_user_test("test/user/strace");
+    }
+}
+
+pub(super) mod bad_syscall {
+    use jrinx_testdef::testdef;
+
+    #[testdef]
+    fn test() {
+        super::run_user_test("test/user/bad-syscall");
+    }
+}
+
+pub(super) mod fault {
+    use jrinx_a653::process::ProcessExit;
+    use jrinx_addr::VirtAddr;
+    use jrinx_testdef::testdef;
+    use jrinx_trap::TrapReason;
+
+    #[testdef]
+    fn test() {
+        let exit = super::run_user("test/kern/nullptr-reader");
+        info!("nullptr-reader {}", exit);
+        assert!(matches!(
+            exit,
+            ProcessExit::Faulted {
+                reason: TrapReason::PageFault { addr, .. },
+                ..
+            } if addr == VirtAddr::new(0)
+        ));
+    }
+}
+
+pub(super) mod sparse_bss {
+    use elf::abi::PT_LOAD;
+    use jrinx_a653::process::ProcessExit;
+    use jrinx_addr::VirtAddr;
+    use jrinx_config::PAGE_SIZE;
+    use jrinx_paging::GenericPageTable;
+    use jrinx_testdef::testdef;
+
+    const SLUG: &str = "test/user/sparse-bss";
+
+    #[testdef]
+    fn test() {
+        // The whole pages of the largest zero-filled tail of a segment.
+        let program = jrinx_uprog::find(SLUG).unwrap();
+        let bss = program
+            .segments()
+            .unwrap()
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_LOAD)
+            .map(|phdr| {
+                let start = VirtAddr::new((phdr.p_vaddr + phdr.p_filesz) as usize);
+                let end = VirtAddr::new((phdr.p_vaddr + phdr.p_memsz) as usize);
+                start.align_page_up()..end.align_page_down().max(start.align_page_up())
+            })
+            .max_by_key(|bss| bss.end - bss.start)
+            .unwrap();
+        let pages = (bss.end - bss.start) / PAGE_SIZE;
+        assert!(pages >= 60);
+
+        let partition = super::new_partition(SLUG);
+        assert!(partition.memory_used() < pages * PAGE_SIZE);
+
+        match super::run_in(&partition) {
+            ProcessExit::Exited(0) => {}
+            exit => panic!("user test {} {}", SLUG, exit),
+        }
+
+        // The program touches four pages of its array, and may have a few statics of its own
+        // in other pages.
+        let populated = (0..pages)
+            .filter(|&page| {
+                partition
+                    .pt_read()
+                    .lookup(bss.start + page * PAGE_SIZE)
+                    .is_ok()
+            })
+            .count();
+        info!("{} of {} zero-filled pages populated", populated, pages);
+        assert!((4..8).contains(&populated));
+    }
+}
+
+pub(super) mod init {
+    use jrinx_testdef::testdef;
+
+    /// The program itself is run by `--init`, once the test is over.
+    #[testdef]
+    fn test() {
+        jrinx_uprog::find("hello").unwrap();
+    }
+}
@@ -0,0 +1,14 @@
+use core::time::Duration;
+
+use jrinx_hal::{Cpu, Hal, Interrupt};
+use jrinx_testdef::testdef;
+
+/// Outlasts the `--timeout` of the run, so that the watchdog halts the system.
+#[testdef]
+fn test() {
+    let deadline = hal!().cpu().get_time() + Duration::from_secs(1);
+    while hal!().cpu().get_time() < deadline {
+        hal!().interrupt().wait();
+    }
+    info!("watchdog: still running past the timeout");
+}
@@ -4,6 +4,7 @@ from __future__ import annotations
 
 from abc import abstractmethod
 import argparse
+import itertools
 import signal
 import subprocess
 import re
@@ -144,13 +145,25 @@ class Test:
                 },
                 inc_dirs=[dir for dir in include_dirs if dir.is_dir()],
             )
-            return Test(conf, f'-t {test_name}')
+            bootargs = f'-t {test_name}'
+            if (extra := conf.pop('bootargs', None)) is not None:
+                bootargs += f' {extra}'
+            return Test(conf, bootargs, conf.pop('features', None))
         else:
             raise NotImplementedError()
 
-    def __init__(self, conf: dict, bootargs: str | None):
+    def __init__(self, conf: dict, bootargs: str | None, features: list[str] | None = None):
         self.conf = conf
         self.bootargs = bootargs
+        self.features = features
+
+    def qemu_cmd(self) -> tuple[str]:
+        # Tests run with lockdep, and with the default features unless they list their own.
+        cmd = ('cargo', 'qemu', '-f', 'lockdep')
+        if self.features is not None:
+            cmd += ('--no-default-feat',
+                    *itertools.chain.from_iterable(('-f', feat) for feat in self.features))
+        return cmd
 
     def __call__(self, /, *, verbose: bool = False):
         output = []
@@ -167,7 +180,7 @@ class Test:
                     env['BOOTARGS'] = args + ' ' + self.bootargs
                 else:
                     env['BOOTARGS'] = self.bootargs
-            proc = subprocess.Popen(('cargo', 'qemu'),
+            proc = subprocess.Popen(self.qemu_cmd(),
                                     env=env,
                                     stdin=subprocess.PIPE,
                                     stdout=subprocess.PIPE,
@@ -204,8 +217,21 @@ class Test:
                     if retire:
                         if verbose:
                             info('Expected pattern defined found')
-                        return
-            raise RuntimeError('Expected pattern not found')
+                        break
+            else:
+                raise RuntimeError('Expected pattern not found')
+
+            if (exit_code := self.conf.get('exit_code')) is not None:
+                for out in proc.stdout:
+                    if verbose:
+                        line = out if isinstance(out, str) else out.decode('utf-8')
+                        sys.stdout.write(line)
+                if (returncode := proc.wait()) != exit_code:
+                    raise RuntimeError(
+                        f'Exit code {returncode} found, {exit_code} expected'
+                    )
+                if verbose:
+                    info(f'Exit code {returncode} found as expected')
         finally:
             signal.alarm(0)
             eliminate_child(proc, timeout=Test.TIMEOUT, verbose=verbose)
@@ -9,6 +9,12 @@ import subprocess
 from typing import Callable, Sequence
 
 import pathos.multiprocessing as mp
+import yaml
+
+try:
+    from yaml import CLoader as Loader
+except ImportError:
+    from yaml import Loader
 
 from util import *
 
@@ -181,6 +187,11 @@ def run_testset_rich(testset: tuple[pathlib.Path],
     return result
 
 
+def has_own_features(file: pathlib.Path) -> bool:
+    with file.open('r', encoding='utf-8') as f:
+        return 'features' in yaml.load(f, Loader=Loader)
+
+
 def main():
     args = argparse.ArgumentParser()
     args.add_argument('-f', '--file', type=file_path)
@@ -219,12 +230,21 @@ def main():
             pathlib.Path(
                 'uprog') / os.environ['ARCH'] / os.environ['BUILD_MODE']
         ))
-        subprocess.check_call(('cargo', 'make'))
+        subprocess.check_call(('cargo', 'make', '-f', 'lockdep'))
 
     runner = run_testset_rich if args.rich else run_testset
-
-    exit_code = any(runner(testset, include_dirs, read_board_list(
-        os.environ['ARCH']), parallel=args.parallel, verbose=args.verbose))
+    boards = read_board_list(os.environ['ARCH'])
+
+    # Tests listing their own features rebuild the kernel, so they run one by one, after the
+    # others are done with the default build.
+    variants = tuple(file for file in testset if has_own_features(file))
+    testset = tuple(file for file in testset if file not in variants)
+
+    exit_code = any(runner(testset, include_dirs, boards,
+                    parallel=args.parallel, verbose=args.verbose))
+    if variants:
+        exit_code |= any(runner(variants, include_dirs, boards,
+                         parallel=False, verbose=args.verbose))
 
     exit(exit_code)
 
@@ -2,5 +2,6 @@
 
 mod partition;
 mod process;
+mod semaphore;
 
 pub mod prelude;
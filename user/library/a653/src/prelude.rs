@@ -1,4 +1,5 @@
 pub use crate::partition::*;
 pub use crate::process::*;
+pub use crate::semaphore::*;
 
 pub use jrinx_apex::*;
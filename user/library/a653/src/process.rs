@@ -77,7 +77,8 @@ impl ApexProcessService for Process {
     }
 
     fn get_my_id(&self) -> Result<ApexProcessId, ApexReturnCode> {
-        todo!()
+        let mut id = ApexProcessId::default();
+        sys_get_my_id(&mut id).as_result(id)
     }
 
     fn initialize_process_core_affinity(
@@ -1,12 +1,23 @@
 #![no_std]
 
 use core::fmt::Write;
-use spin::Mutex;
+use spin::{Mutex, Once};
 
-use jrinx_abi::sysfn;
+use jrinx_abi::{
+    logring::{LogRing, LOG_RING_PAYLOAD_MAX},
+    sysfn,
+};
+use jrinx_apex::ApexReturnCode;
 
 struct Logger;
 
+struct RingWriter {
+    buf: [u8; LOG_RING_PAYLOAD_MAX],
+    len: usize,
+}
+
+static RING: Once<LogRing> = Once::new();
+
 impl Write for Logger {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         let bytes = s.as_bytes();
@@ -16,6 +27,15 @@ impl Write for Logger {
     }
 }
 
+impl Write for RingWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let len = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
 impl log::Log for Logger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
         metadata.level() <= log::max_level()
@@ -29,14 +49,45 @@ impl log::Log for Logger {
         }
 
         let mutex = MUTEX.lock();
-        Logger.write_fmt(format_args!("{}", record.args())).unwrap();
+        if let Some(ring) = RING.get() {
+            let mut writer = RingWriter {
+                buf: [0; LOG_RING_PAYLOAD_MAX],
+                len: 0,
+            };
+            writer.write_fmt(format_args!("{}", record.args())).unwrap();
+            ring_push(ring, &writer.buf[..writer.len]);
+        } else {
+            Logger.write_fmt(format_args!("{}", record.args())).unwrap();
+        }
         core::hint::black_box(mutex);
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        if RING.get().is_some() {
+            sysfn::sys_debug_log_ring_doorbell();
+        }
+    }
 }
 
 pub fn init() {
     log::set_logger(&Logger).unwrap();
     log::set_max_level(log::LevelFilter::Debug);
 }
+
+pub fn init_ring(len: usize) -> Result<LogRing, ApexReturnCode> {
+    let ring = RING.try_call_once(|| {
+        let mut base = 0usize;
+        match sysfn::sys_debug_log_ring_setup(len, &mut base) {
+            ApexReturnCode::NoError => Ok(unsafe { LogRing::from_raw(base as *mut u8) }),
+            code => Err(code),
+        }
+    })?;
+    Ok(*ring)
+}
+
+pub fn ring_push(ring: &LogRing, record: &[u8]) {
+    ring.push(record);
+    if ring.pending() >= ring.capacity() / 2 {
+        sysfn::sys_debug_log_ring_doorbell();
+    }
+}
@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::panic::PanicInfo;
+
+use jrinx_abi::sysfn;
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    info!("hello, world");
+    sysfn::sys_debug_exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_exit(sysfn::EXIT_PANICKED);
+}
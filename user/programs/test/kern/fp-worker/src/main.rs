@@ -0,0 +1,28 @@
+#![feature(naked_functions)]
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+
+#[naked]
+#[no_mangle]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+    core::arch::asm!(
+        "fcvt.d.w fa0, a0",
+        "li a7, 0xF10A",
+        "ecall",
+        "fadd.d fa0, fa0, fa0",
+        "ecall",
+        "fcvt.w.d a0, fa0",
+        "li a7, 0xC0DE",
+        "ecall",
+        "unimp",
+        options(noreturn)
+    );
+}
+
+#[panic_handler]
+fn panic(_: &PanicInfo) -> ! {
+    unreachable!();
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::panic::PanicInfo;
+
+use jrinx_abi::{
+    errno::{Errno, EFAULT, ENOSYS},
+    sysfn,
+    sysno::SYS_GET_CAPABILITIES,
+};
+
+const BAD_SYSNO: usize = 0xdead;
+
+const ROUNDS: usize = 1000;
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    for round in 0..ROUNDS {
+        assert_eq!(
+            Errno::from_ret(sysfn::sys_raw(BAD_SYSNO, [round, 0, 0, 0, 0, 0, 0])),
+            Err(ENOSYS)
+        );
+    }
+    info!("bad-syscall: {} unknown system calls rejected", ROUNDS);
+
+    assert_eq!(
+        Errno::from_ret(sysfn::sys_raw(SYS_GET_CAPABILITIES, [0; 7])),
+        Err(EFAULT)
+    );
+    info!("bad-syscall: null pointer rejected");
+
+    sysfn::sys_debug_exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_exit(sysfn::EXIT_PANICKED);
+}
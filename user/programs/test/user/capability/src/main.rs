@@ -0,0 +1,111 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::panic::PanicInfo;
+
+use jrinx_abi::{cap::Capabilities, sysfn};
+use jrlib_a653::prelude::*;
+
+const GRANTED: Capabilities = Capabilities::LOG
+    .union(Capabilities::LOG_RING)
+    .union(Capabilities::HALT)
+    .union(Capabilities::PARTITION_MODE)
+    .union(Capabilities::PROCESS_MANAGE);
+
+fn capabilities() -> Capabilities {
+    let mut caps = 0;
+    assert_eq!(
+        sysfn::sys_get_capabilities(&mut caps),
+        ApexReturnCode::NoError
+    );
+    Capabilities::from_bits(caps).unwrap()
+}
+
+fn log_ring_setup() -> ApexReturnCode {
+    let mut base = 0;
+    sysfn::sys_debug_log_ring_setup(4096, &mut base)
+}
+
+extern "C" fn child() -> ! {
+    // Inherited from the initial process, which dropped LOG_RING before creating this one.
+    assert_eq!(capabilities(), GRANTED - Capabilities::LOG_RING);
+    assert_eq!(log_ring_setup(), ApexReturnCode::InvalidConfig);
+
+    assert_eq!(
+        sysfn::sys_drop_capabilities(Capabilities::PARTITION_MODE.bits()),
+        ApexReturnCode::NoError
+    );
+    assert_eq!(
+        capabilities(),
+        GRANTED - Capabilities::LOG_RING - Capabilities::PARTITION_MODE
+    );
+    assert_eq!(
+        Partition.set_partition_mode(ApexOperatingMode::Normal),
+        Err(ApexReturnCode::InvalidConfig)
+    );
+
+    info!("capability checks passed");
+    sysfn::sys_debug_halt();
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    assert_eq!(capabilities(), GRANTED);
+    assert_eq!(
+        Process.start(ApexProcessId::MAX),
+        Err(ApexReturnCode::InvalidParam)
+    );
+
+    assert_eq!(
+        sysfn::sys_drop_capabilities(Capabilities::LOG_RING.bits()),
+        ApexReturnCode::NoError
+    );
+    assert_eq!(
+        sysfn::sys_drop_capabilities(Capabilities::LOG_RING.bits()),
+        ApexReturnCode::NoAction
+    );
+    assert_eq!(
+        sysfn::sys_drop_capabilities(usize::MAX),
+        ApexReturnCode::InvalidParam
+    );
+    assert_eq!(capabilities(), GRANTED - Capabilities::LOG_RING);
+    assert_eq!(log_ring_setup(), ApexReturnCode::InvalidConfig);
+
+    let child = Process
+        .create_process(&ApexProcessAttribute {
+            period: APEX_TIME_INFINITY,
+            time_capacity: APEX_TIME_INFINITY,
+            entry_point: ApexSystemAddress::of(child),
+            stack_size: 4 * 4096,
+            base_priority: 1,
+            deadline: ApexDeadline::Soft,
+            name: "child".try_into().unwrap(),
+        })
+        .unwrap();
+    Process.start(child).unwrap();
+
+    let code = Partition.set_partition_mode(ApexOperatingMode::Normal);
+    panic!("SET_PARTITION_MODE: {:?}", code);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_halt();
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::panic::PanicInfo;
+
+use jrinx_abi::{cap::Capabilities, sysfn};
+use jrlib_a653::prelude::*;
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    let status = Partition.get_partition_status().unwrap();
+    assert_ne!(status.operating_mode, ApexOperatingMode::Normal);
+
+    let mut caps = 0;
+    assert_eq!(
+        sysfn::sys_get_capabilities(&mut caps),
+        ApexReturnCode::NoError
+    );
+    assert!(!Capabilities::from_bits(caps)
+        .unwrap()
+        .contains(Capabilities::HALT));
+
+    info!("exit: all checks passed");
+    sysfn::sys_debug_exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_exit(sysfn::EXIT_PANICKED);
+}
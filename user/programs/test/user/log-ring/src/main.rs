@@ -0,0 +1,70 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::panic::PanicInfo;
+
+use jrinx_abi::sysfn;
+
+const RING_LEN: usize = 4 * 4096;
+const RECORDS: u32 = 100_000;
+const CORRUPTED_SKIP: u32 = 1_000_000;
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+    let ring = jrlib_logging::init_ring(RING_LEN).unwrap();
+
+    for i in 0..RECORDS {
+        debug!("log-ring record {} of {}", i + 1, RECORDS);
+    }
+    log::logger().flush();
+
+    assert_eq!(ring.pushed(), RECORDS);
+    assert_eq!(ring.drained(), RECORDS);
+    assert_eq!(ring.dropped(), 0);
+
+    for _ in 0..RECORDS {
+        ring.push(b"log-ring overflow record");
+    }
+    assert_eq!(ring.pending(), RECORDS);
+    sysfn::sys_debug_log_ring_doorbell();
+    assert!(ring.is_empty());
+
+    // The kernel keeps its own capacity, so a corrupted header must not throw it off.
+    let header = ring.as_ptr() as *mut u32;
+    for _ in 0..4 {
+        ring.push(b"log-ring corrupted record");
+    }
+    unsafe { header.write_volatile(0) };
+    sysfn::sys_debug_log_ring_doorbell();
+
+    let pushed = ring.pushed();
+    unsafe {
+        header.write_volatile(u32::MAX);
+        header.add(1).write_volatile(pushed.wrapping_add(CORRUPTED_SKIP));
+    }
+
+    // The kernel drains the ring before halting, reporting the skipped records.
+    sysfn::sys_debug_halt();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    #[allow(clippy::empty_loop)]
+    loop {}
+}
@@ -0,0 +1,90 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::{panic::PanicInfo, time::Duration};
+
+use jrinx_abi::{
+    errno::{Errno, EFAULT, EINVAL},
+    sysfn,
+    time::{Timespec, CLOCK_MONOTONIC},
+};
+
+/// An address in the kernel image, mapped in every address space but not for user programs.
+const KERNEL_ADDR: usize = 0x8020_0000;
+
+const SLEEP: Duration = Duration::from_millis(50);
+
+/// Most time the kernel may take to resume the process past the end of its sleep.
+const SLACK: Duration = Duration::from_millis(20);
+
+fn now() -> Duration {
+    let mut tp = Timespec::default();
+    Errno::from_ret(sysfn::sys_clock_gettime(CLOCK_MONOTONIC, &mut tp)).unwrap();
+    tp.to_duration().unwrap()
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    let before = now();
+    let req = Timespec::from(SLEEP);
+    let mut rem = Timespec::default();
+    assert_eq!(Errno::from_ret(sysfn::sys_nanosleep(&req, &mut rem)), Ok(0));
+    let after = now();
+    info!("nanosleep: slept {:?} for {:?}", after - before, SLEEP);
+    assert!(after - before >= SLEEP);
+    assert!(after - before < SLEEP + SLACK);
+    assert_eq!(rem, Timespec::default());
+
+    let mut tp = Timespec::default();
+    assert_eq!(
+        Errno::from_ret(sysfn::sys_clock_gettime(0xbad, &mut tp)),
+        Err(EINVAL)
+    );
+    assert_eq!(
+        Errno::from_ret(sysfn::sys_clock_gettime(
+            CLOCK_MONOTONIC,
+            KERNEL_ADDR as *mut _
+        )),
+        Err(EFAULT)
+    );
+    assert_eq!(
+        Errno::from_ret(sysfn::sys_nanosleep(
+            core::ptr::null(),
+            core::ptr::null_mut()
+        )),
+        Err(EFAULT)
+    );
+    let req = Timespec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(
+        Errno::from_ret(sysfn::sys_nanosleep(&req, core::ptr::null_mut())),
+        Err(EINVAL)
+    );
+
+    info!("nanosleep: all checks passed");
+    sysfn::sys_debug_exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_exit(sysfn::EXIT_PANICKED);
+}
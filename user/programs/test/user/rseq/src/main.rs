@@ -0,0 +1,138 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::{
+    arch::asm,
+    panic::PanicInfo,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use jrinx_abi::{rseq::Rseq, sysfn};
+use jrlib_a653::prelude::*;
+
+const MAX_CPUS: usize = 64;
+const ROUNDS: usize = 1_000_000;
+
+static RSEQ: Rseq = Rseq::new();
+static COUNTERS: [AtomicUsize; MAX_CPUS] = [const { AtomicUsize::new(0) }; MAX_CPUS];
+
+#[cfg(target_pointer_width = "32")]
+macro_rules! xlen {
+    (load) => {
+        "lw"
+    };
+    (store) => {
+        "sw"
+    };
+    (ptr) => {
+        ".word"
+    };
+    (shift) => {
+        "2"
+    };
+}
+
+#[cfg(target_pointer_width = "64")]
+macro_rules! xlen {
+    (load) => {
+        "ld"
+    };
+    (store) => {
+        "sd"
+    };
+    (ptr) => {
+        ".dword"
+    };
+    (shift) => {
+        "3"
+    };
+}
+
+/// Increments the counter of the current CPU in a restartable sequence, returning its id.
+///
+/// The sequence spans from loading the CPU id up to the committing store, so if the process
+/// is preempted or migrated within it, the kernel resumes it at the abort label, which
+/// starts over with the CPU id published afresh.
+fn percpu_inc() -> usize {
+    let cpu_id: usize;
+    unsafe {
+        asm!(
+            ".pushsection .data.rseq_cs, \"aw\"",
+            ".balign 8",
+            concat!("2: ", xlen!(ptr), " 3f, 4f - 3f, 5f"),
+            ".popsection",
+            "6:",
+            "la {tmp}, 2b",
+            concat!(xlen!(store), " {tmp}, 0({rseq_cs})"),
+            "3:",
+            "lw {cpu_id}, 0({cpu_id_ptr})",
+            concat!("slli {tmp}, {cpu_id}, ", xlen!(shift)),
+            "add {tmp}, {tmp}, {counters}",
+            concat!(xlen!(load), " {val}, 0({tmp})"),
+            "addi {val}, {val}, 1",
+            concat!(xlen!(store), " {val}, 0({tmp})"),
+            "4:",
+            concat!(xlen!(store), " zero, 0({rseq_cs})"),
+            "j 7f",
+            "5:",
+            "j 6b",
+            "7:",
+            cpu_id = out(reg) cpu_id,
+            tmp = out(reg) _,
+            val = out(reg) _,
+            rseq_cs = in(reg) RSEQ.rseq_cs_ptr(),
+            cpu_id_ptr = in(reg) RSEQ.cpu_id_ptr(),
+            counters = in(reg) COUNTERS.as_ptr(),
+            options(nostack),
+        );
+    }
+    cpu_id
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    assert_eq!(RSEQ.cpu_id(), None);
+    assert_eq!(
+        sysfn::sys_rseq_register(core::ptr::null()),
+        ApexReturnCode::InvalidParam
+    );
+    assert_eq!(sysfn::sys_rseq_register(&RSEQ), ApexReturnCode::NoError);
+    assert_eq!(sysfn::sys_rseq_register(&RSEQ), ApexReturnCode::NoAction);
+    assert!(RSEQ.cpu_id().is_some_and(|cpu_id| cpu_id < MAX_CPUS));
+
+    for _ in 0..ROUNDS {
+        assert!(percpu_inc() < MAX_CPUS);
+    }
+
+    let total: usize = COUNTERS
+        .iter()
+        .map(|counter| counter.load(Ordering::Relaxed))
+        .sum();
+    assert_eq!(total, ROUNDS);
+
+    // The kernel reports the restarted critical sections on halt.
+    sysfn::sys_debug_halt();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    #[allow(clippy::empty_loop)]
+    loop {}
+}
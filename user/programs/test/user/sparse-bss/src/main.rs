@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::{panic::PanicInfo, ptr};
+
+use jrinx_abi::sysfn;
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 64;
+/// Pages of [`SPARSE`] touched, the others of which the kernel should never populate.
+const TOUCHED: [usize; 4] = [0, 7, 31, 63];
+
+static mut SPARSE: [u8; PAGES * PAGE_SIZE] = [0; PAGES * PAGE_SIZE];
+static mut BACKED: [u8; 16] = *b"lazily populated";
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    let backed = unsafe { ptr::addr_of!(BACKED).read_volatile() };
+    assert_eq!(&backed, b"lazily populated");
+
+    let sparse = unsafe { ptr::addr_of_mut!(SPARSE).cast::<u8>() };
+    for page in TOUCHED {
+        let byte = unsafe { sparse.add(page * PAGE_SIZE + page) };
+        assert_eq!(unsafe { byte.read_volatile() }, 0);
+        unsafe { byte.write_volatile(page as u8 + 1) };
+        assert_eq!(unsafe { byte.read_volatile() }, page as u8 + 1);
+    }
+
+    info!(
+        "sparse-bss: touched {} of {} pages, all checks passed",
+        TOUCHED.len(),
+        PAGES
+    );
+    sysfn::sys_debug_exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_exit(sysfn::EXIT_PANICKED);
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::panic::PanicInfo;
+
+use jrinx_abi::{errno::ENOSYS, sysfn};
+
+const TUNABLE: &str = "syscall.strict_budgets";
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    let mut id = 0;
+    sysfn::sys_get_my_id(&mut id);
+
+    let mut buf = [0u8; 16];
+    let mut len = 0;
+    sysfn::sys_get_tunable(
+        TUNABLE.as_ptr(),
+        TUNABLE.len(),
+        buf.as_mut_ptr(),
+        buf.len(),
+        &mut len,
+    );
+    assert_eq!(&buf[..len], b"false");
+
+    assert_eq!(
+        sysfn::sys_raw(0xbad, [1, 2, 3, 0, 0, 0, 0]),
+        ENOSYS.as_ret()
+    );
+
+    info!("strace: a line long enough to be cut short in the trace of the system call logging it");
+
+    sysfn::sys_strace(0);
+    let mut caps = 0;
+    sysfn::sys_get_capabilities(&mut caps);
+
+    sysfn::sys_debug_exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_exit(sysfn::EXIT_PANICKED);
+}
@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::panic::PanicInfo;
+
+use jrinx_abi::{
+    errno::ENOSYS,
+    sysfn,
+    sysno::{SYS_GET_MY_ID, SYS_GET_TUNABLE},
+};
+use jrlib_a653::prelude::*;
+
+/// An address in the kernel image, mapped in every address space but not for user programs.
+const KERNEL_ADDR: usize = 0x8020_0000;
+
+const TUNABLE: &str = "syscall.strict_budgets";
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    let mut id: ApexProcessId = -1;
+    assert_eq!(sysfn::sys_get_my_id(&mut id), ApexReturnCode::NoError,);
+    assert_eq!(id, Process.get_my_id().unwrap());
+
+    for bogus in [
+        0,
+        KERNEL_ADDR,
+        &mut id as *mut _ as usize + 1,
+        usize::MAX - 3,
+    ] {
+        assert_eq!(
+            sysfn::sys_raw(SYS_GET_MY_ID, [bogus, 0, 0, 0, 0, 0, 0]),
+            ApexReturnCode::InvalidParam as usize,
+        );
+    }
+    info!("syscall-table: bogus pointers rejected");
+
+    let mut buf = [0u8; 16];
+    let mut len = 0;
+    let raw = |key: usize, buf: usize, len: usize| {
+        sysfn::sys_raw(SYS_GET_TUNABLE, [key, TUNABLE.len(), buf, 16, len, 0, 0])
+    };
+    assert_eq!(
+        raw(
+            TUNABLE.as_ptr() as usize,
+            buf.as_mut_ptr() as usize,
+            &mut len as *mut _ as usize,
+        ),
+        ApexReturnCode::NoError as usize,
+    );
+    assert_eq!(&buf[..len], b"false");
+    assert_eq!(
+        raw(
+            TUNABLE.as_ptr() as usize,
+            KERNEL_ADDR,
+            &mut len as *mut _ as usize
+        ),
+        ApexReturnCode::InvalidParam as usize,
+    );
+    assert_eq!(
+        raw(
+            usize::MAX - 8,
+            buf.as_mut_ptr() as usize,
+            &mut len as *mut _ as usize
+        ),
+        ApexReturnCode::InvalidParam as usize,
+    );
+
+    assert_eq!(sysfn::sys_raw(0xbad, [0; 7]), ENOSYS.as_ret());
+    info!("syscall-table: unknown system call rejected");
+
+    info!("syscall-table: all checks passed");
+    sysfn::sys_debug_exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_exit(sysfn::EXIT_PANICKED);
+}
@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+#![feature(panic_info_message)]
+
+#[macro_use]
+extern crate log;
+
+use core::{
+    arch::asm,
+    panic::PanicInfo,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use jrinx_abi::{sysfn, trap::TrapMask};
+use jrlib_a653::prelude::*;
+
+/// Address no region of the program covers, which only the handler maps.
+const PROBE: usize = 0x4000_0000;
+const SIGNAL: usize = 7;
+
+#[repr(C, align(16))]
+struct HandlerStack([u8; 4 * 4096]);
+
+static mut HANDLER_STACK: HandlerStack = HandlerStack([0; 4 * 4096]);
+
+static PAGE_FAULTS: AtomicUsize = AtomicUsize::new(0);
+static BREAKPOINTS: AtomicUsize = AtomicUsize::new(0);
+static SIGNALS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn handler(trap: usize, addr: usize, pc: usize) -> ! {
+    let trap = TrapMask::from_bits_retain(trap);
+    if trap == TrapMask::PAGE_FAULT {
+        info!("page fault on {:#x} at {:#x}", addr, pc);
+        assert_eq!(addr, PROBE);
+        assert_eq!(sysfn::sys_map_page(addr), ApexReturnCode::NoError);
+        PAGE_FAULTS.fetch_add(1, Ordering::SeqCst);
+    } else if trap == TrapMask::BREAKPOINT {
+        assert_eq!(addr, pc);
+        BREAKPOINTS.fetch_add(1, Ordering::SeqCst);
+    } else if trap == TrapMask::SIGNAL {
+        assert_eq!(addr, SIGNAL);
+        SIGNALS.fetch_add(1, Ordering::SeqCst);
+    } else {
+        panic!("unexpected trap {:?}", trap);
+    }
+
+    sysfn::sys_trap_return();
+    unreachable!("no interrupted context to return to");
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    jrlib_logging::init();
+
+    assert_eq!(sysfn::sys_trap_return(), ApexReturnCode::NoAction);
+    assert_eq!(
+        sysfn::sys_trap_handler_set(usize::MAX, handler as usize, 0),
+        ApexReturnCode::InvalidParam
+    );
+
+    let stack_top = unsafe { core::ptr::addr_of_mut!(HANDLER_STACK) as usize } + 4 * 4096;
+    assert_eq!(
+        sysfn::sys_trap_handler_set(TrapMask::all().bits(), handler as usize, stack_top),
+        ApexReturnCode::NoError
+    );
+
+    let probe = PROBE as *mut usize;
+    unsafe { probe.write_volatile(0xC0DE) };
+    assert_eq!(unsafe { probe.read_volatile() }, 0xC0DE);
+    assert_eq!(PAGE_FAULTS.load(Ordering::SeqCst), 1);
+    assert_eq!(sysfn::sys_map_page(PROBE), ApexReturnCode::NoAction);
+
+    unsafe { asm!("ebreak") };
+    assert_eq!(BREAKPOINTS.load(Ordering::SeqCst), 1);
+
+    let id = Process.get_my_id().unwrap();
+    assert_eq!(sysfn::sys_trap_signal(id, SIGNAL), ApexReturnCode::NoError);
+    assert_eq!(SIGNALS.load(Ordering::SeqCst), 1);
+
+    info!("trap-handler: all traps handled");
+    sysfn::sys_debug_exit(0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        error!(
+            "panicked at {}:{} {}",
+            location.file(),
+            location.line(),
+            info.message().unwrap(),
+        );
+    } else {
+        error!("panicked: {}", info.message().unwrap());
+    }
+
+    sysfn::sys_debug_exit(sysfn::EXIT_PANICKED);
+}
@@ -0,0 +1,113 @@
+//! Symbol table of the kernel, written into its `.ksyms` section once linked.
+//!
+//! See `jrinx_backtrace::ksyms` for the layout of the table.
+
+use std::{fs, path::Path, process::Command};
+
+const SECTION: &str = ".ksyms";
+
+const MAGIC: &[u8; 4] = b"KSYM";
+
+/// Longest name kept, in bytes.
+const NAME_MAX: usize = 255;
+
+#[must_use]
+pub fn embed(elf: &Path) -> Option<()> {
+    let output = Command::new("rust-nm")
+        .args(["--defined-only", "--numeric-sort", "--demangle"])
+        .arg(elf)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let symbols = parse(&String::from_utf8_lossy(&output.stdout));
+
+    let reserved = elf.with_file_name("jrinx.ksyms");
+    if !Command::new("rust-objcopy")
+        .arg("--dump-section")
+        .arg(format!("{}={}", SECTION, reserved.display()))
+        .arg(elf)
+        .status()
+        .ok()?
+        .success()
+    {
+        return None;
+    }
+    let size = fs::metadata(&reserved).ok()?.len() as usize;
+
+    let mut table = encode(&symbols);
+    if table.len() > size {
+        eprintln!(
+            "symbol table of {} bytes exceeds the {} bytes reserved, backtraces will not name functions",
+            table.len(),
+            size
+        );
+        table.clear();
+    }
+    table.resize(size, 0);
+    fs::write(&reserved, table).ok()?;
+
+    let status = Command::new("rust-objcopy")
+        .arg("--update-section")
+        .arg(format!("{}={}", SECTION, reserved.display()))
+        .arg(elf)
+        .status()
+        .ok()?;
+    fs::remove_file(&reserved).ok()?;
+    status.success().then_some(())
+}
+
+/// Parses the functions listed by `nm`, in order of address.
+fn parse(nm: &str) -> Vec<(u64, &str)> {
+    let mut symbols: Vec<(u64, &str)> = Vec::new();
+    for line in nm.lines() {
+        let mut fields = line.splitn(3, ' ');
+        let (Some(addr), Some(kind), Some(name)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if !matches!(kind, "t" | "T" | "w" | "W") {
+            continue;
+        }
+        let Ok(addr) = u64::from_str_radix(addr, 16) else {
+            continue;
+        };
+        if symbols.last().is_some_and(|&(last, _)| last == addr) {
+            continue;
+        }
+        symbols.push((addr, strip_hash(name)));
+    }
+    symbols
+}
+
+/// Strips the hash legacy mangling appends to names, as in `jrinx::main::h0123456789abcdef`.
+fn strip_hash(name: &str) -> &str {
+    match name.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+            path
+        }
+        _ => name,
+    }
+}
+
+fn encode(symbols: &[(u64, &str)]) -> Vec<u8> {
+    let mut entries = Vec::new();
+    let mut names = Vec::new();
+    for &(addr, name) in symbols {
+        let mut len = name.len().min(NAME_MAX);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        entries.extend_from_slice(&addr.to_le_bytes());
+        entries.extend_from_slice(&(names.len() as u32).to_le_bytes());
+        entries.extend_from_slice(&(len as u32).to_le_bytes());
+        names.extend_from_slice(&name.as_bytes()[..len]);
+    }
+
+    let mut table = MAGIC.to_vec();
+    table.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+    table.extend(entries);
+    table.extend(names);
+    table
+}
@@ -1,6 +1,7 @@
 mod ar;
 mod arch;
 mod envs;
+mod ksyms;
 mod lint;
 mod make;
 mod qemu;
@@ -50,6 +51,10 @@ fn main() -> ExitCode {
         if code.success() {
             return ExitCode::SUCCESS;
         }
+        // The exit status of QEMU tells how many tests failed.
+        if let Some(code) = code.code().and_then(|code| u8::try_from(code).ok()) {
+            return ExitCode::from(code);
+        }
     }
     ExitCode::FAILURE
 }
@@ -7,7 +7,7 @@ use clap::Args;
 
 use crate::{
     arch::ArchArg,
-    envs,
+    envs, ksyms,
     util::{cargo::Cargo, CmdOptional},
 };
 
@@ -66,25 +66,19 @@ pub fn run(arg: &MakeArg) -> Option<ExitStatus> {
         return None;
     }
 
+    let out_dir = env::current_dir()
+        .unwrap()
+        .join("target")
+        .join(arch.to_string())
+        .join(env::var_os("BUILD_MODE").unwrap().to_str().unwrap());
+
+    ksyms::embed(&out_dir.join("jrinx"))?;
+
     Command::new("rust-objcopy")
         .args(["-O", "binary"])
         .arg(format!("--binary-architecture={}", arch))
-        .arg(
-            env::current_dir()
-                .unwrap()
-                .join("target")
-                .join(arch.to_string())
-                .join(env::var_os("BUILD_MODE").unwrap().to_str().unwrap())
-                .join("jrinx"),
-        )
-        .arg(
-            env::current_dir()
-                .unwrap()
-                .join("target")
-                .join(arch.to_string())
-                .join(env::var_os("BUILD_MODE").unwrap().to_str().unwrap())
-                .join("jrinx.bin"),
-        )
+        .arg(out_dir.join("jrinx"))
+        .arg(out_dir.join("jrinx.bin"))
         .status()
         .ok()
 }